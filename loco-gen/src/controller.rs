@@ -11,7 +11,12 @@ pub fn generate(
     kind: &gen::ScaffoldKind,
     appinfo: &AppInfo,
 ) -> Result<GenerateResults> {
-    let vars = json!({"name": name, "actions": actions, "pkg_name": appinfo.app_name});
+    let vars = json!({
+        "name": name,
+        "actions": actions,
+        "pkg_name": appinfo.app_name,
+        "view_engine": appinfo.view_engine.view_type(),
+    });
     match kind {
         gen::ScaffoldKind::Api => gen::render_template(rrgen, Path::new("controller/api"), &vars),
         gen::ScaffoldKind::Html => {
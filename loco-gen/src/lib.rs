@@ -221,6 +221,60 @@ pub enum ScaffoldKind {
     Htmx,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ViewEngineKind {
+    #[default]
+    Tera,
+    #[clap(name = "minijinja")]
+    MiniJinja,
+}
+
+impl ViewEngineKind {
+    /// The loco view engine type used in the generated controllers
+    #[must_use]
+    pub fn view_type(self) -> &'static str {
+        match self {
+            Self::Tera => "TeraView",
+            Self::MiniJinja => "MiniJinjaView",
+        }
+    }
+}
+
+/// The primary key of the generated models
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PrimaryKeyKind {
+    /// Auto-increment integers
+    #[default]
+    Auto,
+    /// Time-ordered UUIDv7, in `uuid` columns
+    #[clap(name = "uuidv7")]
+    UuidV7,
+    /// Time-ordered ULID, in `char(26)` columns
+    Ulid,
+}
+
+impl PrimaryKeyKind {
+    /// The migration column type of the primary key
+    #[must_use]
+    pub fn col_type(self) -> &'static str {
+        match self {
+            Self::Auto => "PkAuto",
+            Self::UuidV7 => "PkUuid",
+            Self::Ulid => "PkUlid",
+        }
+    }
+
+    /// The Rust type of the primary key in the generated entities
+    #[must_use]
+    pub fn rust_type(self) -> &'static str {
+        match self {
+            Self::Auto => "i32",
+            Self::UuidV7 => "Uuid",
+            Self::Ulid => "String",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum DeploymentKind {
     Docker {
@@ -245,6 +299,9 @@ pub enum Component {
 
         /// Model fields, eg. title:string hits:int
         fields: Vec<(String, String)>,
+
+        /// The primary key of the model
+        primary_key: PrimaryKeyKind,
     },
     #[cfg(feature = "with-db")]
     Migration {
@@ -258,6 +315,24 @@ pub enum Component {
         fields: Vec<(String, String)>,
     },
     #[cfg(feature = "with-db")]
+    SchemaDiff {
+        /// Name of the migration file
+        name: String,
+
+        /// Whether to include timestamps (`created_at`, `updated_at` columns) in the created tables
+        with_tz: bool,
+
+        /// The tables to create, with their fields, eg. title:string hits:int
+        create_tables: Vec<(String, Vec<(String, String)>)>,
+
+        /// The fields to add to existing tables
+        add_columns: Vec<(String, Vec<(String, String)>)>,
+
+        /// The columns to remove from existing tables, with the column type
+        /// adding them back, eg. legacy:TextNull
+        remove_columns: Vec<(String, Vec<(String, String)>)>,
+    },
+    #[cfg(feature = "with-db")]
     Scaffold {
         /// Name of the thing to generate
         name: String,
@@ -270,6 +345,9 @@ pub enum Component {
 
         // k
         kind: ScaffoldKind,
+
+        /// The primary key of the model
+        primary_key: PrimaryKeyKind,
     },
     Controller {
         /// Name of the thing to generate
@@ -298,6 +376,18 @@ pub enum Component {
         /// Name of the thing to generate
         name: String,
     },
+    Policy {
+        /// Name of the resource the policy authorizes
+        name: String,
+    },
+    Channel {
+        /// Name of the thing to generate
+        name: String,
+    },
+    GraphQL {
+        /// Name of the resolvers to generate
+        name: String,
+    },
     Deployment {
         kind: DeploymentKind,
     },
@@ -305,6 +395,8 @@ pub enum Component {
 
 pub struct AppInfo {
     pub app_name: String,
+    /// The view engine of the generated HTML and HTMX controllers
+    pub view_engine: ViewEngineKind,
 }
 
 #[must_use]
@@ -331,20 +423,38 @@ pub fn generate(rrgen: &RRgen, component: Component, appinfo: &AppInfo) -> Resul
             name,
             with_tz,
             fields,
-        } => model::generate(rrgen, &name, with_tz, &fields, appinfo)?,
+            primary_key,
+        } => model::generate(rrgen, &name, with_tz, &fields, primary_key, appinfo)?,
         #[cfg(feature = "with-db")]
         Component::Scaffold {
             name,
             with_tz,
             fields,
             kind,
-        } => scaffold::generate(rrgen, &name, with_tz, &fields, &kind, appinfo)?,
+            primary_key,
+        } => scaffold::generate(rrgen, &name, with_tz, &fields, &kind, primary_key, appinfo)?,
         #[cfg(feature = "with-db")]
         Component::Migration {
             name,
             with_tz,
             fields,
         } => migration::generate(rrgen, &name, with_tz, &fields, appinfo)?,
+        #[cfg(feature = "with-db")]
+        Component::SchemaDiff {
+            name,
+            with_tz,
+            create_tables,
+            add_columns,
+            remove_columns,
+        } => migration::generate_diff(
+            rrgen,
+            &name,
+            with_tz,
+            &create_tables,
+            &add_columns,
+            &remove_columns,
+            appinfo,
+        )?,
         Component::Controller {
             name,
             actions,
@@ -392,6 +502,18 @@ pub fn generate(rrgen: &RRgen, component: Component, appinfo: &AppInfo) -> Resul
             let vars = json!({ "name": name });
             render_template(rrgen, Path::new("data"), &vars)?
         }
+        Component::Policy { name } => {
+            let vars = json!({ "name": name });
+            render_template(rrgen, Path::new("policy"), &vars)?
+        }
+        Component::Channel { name } => {
+            let vars = json!({ "name": name });
+            render_template(rrgen, Path::new("channel"), &vars)?
+        }
+        Component::GraphQL { name } => {
+            let vars = json!({ "name": name });
+            render_template(rrgen, Path::new("graphql"), &vars)?
+        }
     };
 
     Ok(get_result)
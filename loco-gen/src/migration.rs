@@ -5,7 +5,8 @@ use rrgen::RRgen;
 use serde_json::json;
 
 use crate::{
-    infer, model::get_columns_and_references, render_template, AppInfo, GenerateResults, Result,
+    infer, model::get_columns_and_references, render_template, AppInfo, GenerateResults,
+    PrimaryKeyKind, Result,
 };
 
 /// skipping some fields from the generated models.
@@ -28,7 +29,7 @@ pub fn generate(
         // NOTE: re-uses the 'new model' migration template!
         infer::MigrationType::CreateTable { table } => {
             let (columns, references) = get_columns_and_references(fields)?;
-            let vars = json!({"name": table, "ts": ts, "with_tz": with_tz,"pkg_name": pkg_name, "is_link": false, "columns": columns, "references": references});
+            let vars = json!({"name": table, "ts": ts, "with_tz": with_tz,"pkg_name": pkg_name, "is_link": false, "columns": columns, "references": references, "pk_col_type": PrimaryKeyKind::default().col_type()});
             render_template(rrgen, Path::new("model/model.t"), &vars)
         }
         infer::MigrationType::AddColumns { table } => {
@@ -64,3 +65,40 @@ pub fn generate(
         }
     }
 }
+
+/// Generates a single migration creating tables, adding fields to existing
+/// tables and removing columns from them, such as the differences between a
+/// declarative schema and the database.
+pub fn generate_diff(
+    rrgen: &RRgen,
+    name: &str,
+    with_tz: bool,
+    create_tables: &[(String, Vec<(String, String)>)],
+    add_columns: &[(String, Vec<(String, String)>)],
+    remove_columns: &[(String, Vec<(String, String)>)],
+    appinfo: &AppInfo,
+) -> Result<GenerateResults> {
+    let tables = |tables: &[(String, Vec<(String, String)>)]| -> Result<Vec<serde_json::Value>> {
+        tables
+            .iter()
+            .map(|(table, fields)| {
+                let (columns, references) = get_columns_and_references(fields)?;
+                Ok(json!({"name": table, "columns": columns, "references": references}))
+            })
+            .collect()
+    };
+    let remove_columns = remove_columns
+        .iter()
+        .map(|(table, columns)| json!({"name": table, "columns": columns}))
+        .collect::<Vec<_>>();
+    let vars = json!({
+        "name": name,
+        "ts": Utc::now(),
+        "with_tz": with_tz,
+        "pkg_name": appinfo.app_name,
+        "create_tables": tables(create_tables)?,
+        "add_columns": tables(add_columns)?,
+        "remove_columns": remove_columns,
+    });
+    render_template(rrgen, Path::new("migration/diff.t"), &vars)
+}
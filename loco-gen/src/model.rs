@@ -7,7 +7,8 @@ use rrgen::RRgen;
 use serde_json::json;
 
 use crate::{
-    get_mappings, infer::parse_field_type, render_template, AppInfo, Error, GenerateResults, Result,
+    get_mappings, infer::parse_field_type, render_template, AppInfo, Error, GenerateResults,
+    PrimaryKeyKind, Result,
 };
 
 /// skipping some fields from the generated models.
@@ -15,6 +16,10 @@ use crate::{
 /// generated by the Loco app and should be given
 pub const IGNORE_FIELDS: &[&str] = &["created_at", "updated_at", "create_at", "update_at"];
 
+/// The field of the row version of optimistic locking, an integer starting
+/// at 0 whatever type it is given.
+pub const LOCK_VERSION_FIELD: &str = "lock_version";
+
 /// columns are <name>, <dbtype>: ("content", "string")
 /// references are <to table, id col in from table>: ("user", `user_id`)
 ///  parsed from e.g.: model article content:string user:references
@@ -33,6 +38,10 @@ pub fn get_columns_and_references(
             );
             continue;
         }
+        if fname == LOCK_VERSION_FIELD {
+            columns.push((fname.clone(), "IntegerWithDefault(0)".to_string()));
+            continue;
+        }
         let field_type = parse_field_type(ftype)?;
         match field_type {
             crate::infer::FieldType::Reference => {
@@ -101,6 +110,7 @@ pub fn generate(
     name: &str,
     with_tz: bool,
     fields: &[(String, String)],
+    primary_key: PrimaryKeyKind,
     appinfo: &AppInfo,
 ) -> Result<GenerateResults> {
     let pkg_name: &str = &appinfo.app_name;
@@ -108,7 +118,7 @@ pub fn generate(
 
     let (columns, references) = get_columns_and_references(fields)?;
 
-    let vars = json!({"name": name, "ts": ts, "with_tz": with_tz,"pkg_name": pkg_name, "columns": columns, "references": references});
+    let vars = json!({"name": name, "ts": ts, "with_tz": with_tz,"pkg_name": pkg_name, "columns": columns, "references": references, "pk_col_type": primary_key.col_type()});
     let gen_result = render_template(rrgen, Path::new("model"), &vars)?;
 
     if std::env::var("SKIP_MIGRATION").is_err() {
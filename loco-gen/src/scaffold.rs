@@ -1,28 +1,85 @@
 use std::path::Path;
 
+use chrono::{Duration, Utc};
+use cruet::{case::snake::to_snake_case, Inflector};
 use rrgen::RRgen;
 use serde_json::json;
 
 use crate::{
     get_mappings, infer::parse_field_type, model, render_template, AppInfo, Error, GenerateResults,
-    Result, ScaffoldKind,
+    PrimaryKeyKind, Result, ScaffoldKind,
 };
 
+/// The field marking the soft deleted items.
+const SOFT_DELETE_FIELD: &str = "deleted_at";
+
+/// The field naming the parent the items belong to, eg. `belongs_to:user`,
+/// nesting their routes under the ones of the parent.
+const BELONGS_TO_FIELD: &str = "belongs_to";
+
+/// The field naming the children of the items, eg. `has_many:comments`,
+/// referencing the items from the table of the children.
+const HAS_MANY_FIELD: &str = "has_many";
+
 pub fn generate(
     rrgen: &RRgen,
     name: &str,
     with_tz: bool,
     fields: &[(String, String)],
     kind: &ScaffoldKind,
+    primary_key: PrimaryKeyKind,
     appinfo: &AppInfo,
 ) -> Result<GenerateResults> {
+    let mut parent = None;
+    let mut children = Vec::new();
+    let mut own_fields = Vec::new();
+    for (fname, ftype) in fields {
+        match fname.as_str() {
+            BELONGS_TO_FIELD => {
+                if parent
+                    .replace(to_snake_case(&ftype.to_singular()))
+                    .is_some()
+                {
+                    return Err(Error::Message(
+                        "a scaffold can belong to a single parent".to_string(),
+                    ));
+                }
+            }
+            HAS_MANY_FIELD => children.push(to_snake_case(&ftype.to_plural())),
+            _ => own_fields.push((fname.clone(), ftype.clone())),
+        }
+    }
+    // the items reference their parent, whose id is read from the nested routes
+    let mut model_fields = own_fields.clone();
+    if let Some(parent) = &parent {
+        if !matches!(kind, ScaffoldKind::Api) {
+            return Err(Error::Message(format!(
+                "`{BELONGS_TO_FIELD}` nests the routes of API scaffolds only"
+            )));
+        }
+        model_fields.push((parent.clone(), "references".to_string()));
+    }
+
     // - scaffold is never a link table
     // - never run with migration_only, because the controllers will refer to the
     //   models. the models only arrive after migration and entities sync.
-    let mut gen_result = model::generate(rrgen, name, with_tz, fields, appinfo)?;
+    let mut gen_result =
+        model::generate(rrgen, name, with_tz, &model_fields, primary_key, appinfo)?;
 
     let mut columns = Vec::new();
-    for (fname, ftype) in fields {
+    let mut soft_delete = false;
+    let mut optimistic_lock = false;
+    for (fname, ftype) in &own_fields {
+        // a `deleted_at` field soft deletes the items, rather than being edited
+        if fname == SOFT_DELETE_FIELD {
+            soft_delete = true;
+            continue;
+        }
+        // a `lock_version` field locks the updates, and is bumped by them
+        if fname == model::LOCK_VERSION_FIELD {
+            optimistic_lock = true;
+            continue;
+        }
         if model::IGNORE_FIELDS.contains(&fname.as_str()) {
             tracing::warn!(
                 field = fname,
@@ -74,7 +131,16 @@ pub fn generate(
         }
     }
 
-    let vars = json!({"name": name, "columns": columns, "pkg_name": appinfo.app_name});
+    let vars = json!({
+        "name": name,
+        "columns": columns,
+        "soft_delete": soft_delete,
+        "optimistic_lock": optimistic_lock,
+        "id_type": primary_key.rust_type(),
+        "parent": parent,
+        "pkg_name": appinfo.app_name,
+        "view_engine": appinfo.view_engine.view_type(),
+    });
     match kind {
         ScaffoldKind::Api => {
             let res = render_template(rrgen, Path::new("scaffold/api"), &vars)?;
@@ -92,5 +158,23 @@ pub fn generate(
             gen_result.local_templates.extend(res.local_templates);
         }
     }
+
+    // the children reference the items, after the migration creating them
+    let singular = to_snake_case(&name.to_singular());
+    let mut ts = Utc::now();
+    for child in &children {
+        ts += Duration::seconds(1);
+        let vars = json!({
+            "name": format!("add_{singular}_ref_to_{child}"),
+            "table": child,
+            "ts": ts,
+            "pkg_name": appinfo.app_name,
+            "columns": [],
+            "references": [(&singular, "")],
+        });
+        let res = render_template(rrgen, Path::new("migration/add_references.t"), &vars)?;
+        gen_result.rrgen.extend(res.rrgen);
+        gen_result.local_templates.extend(res.local_templates);
+    }
     Ok(gen_result)
 }
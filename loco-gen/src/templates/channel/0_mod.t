@@ -0,0 +1,8 @@
+to: "src/channels/mod.rs"
+skip_exists: true
+message: "Channels module added"
+injections:
+- into: "src/lib.rs"
+  append: true
+  content: "pub mod channels;"
+---
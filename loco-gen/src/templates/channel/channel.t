@@ -0,0 +1,35 @@
+{% set file_name = name | snake_case -%}
+{% set module_name = file_name | pascal_case -%}
+to: "src/channels/{{file_name}}.rs"
+skip_exists: true
+message: "A channel `{{module_name}}Channel` was added successfully. Route it with `channels.handler({{module_name}}Channel)`, the `channels` feature of loco-rs enabled."
+injections:
+- into: "src/channels/mod.rs"
+  append: true
+  content: "pub mod {{ file_name }};"
+---
+use loco_rs::{
+    channels::{Channel, Message, Socket},
+    prelude::*,
+};
+use serde_json::json;
+
+const ROOM: &str = "{{file_name}}";
+
+pub struct {{module_name}}Channel;
+
+#[async_trait]
+impl Channel for {{module_name}}Channel {
+    async fn join(&self, socket: &Socket, _ctx: &AppContext) -> Result<()> {
+        socket.join(ROOM, json!({})).await
+    }
+
+    async fn handle_message(
+        &self,
+        socket: &Socket,
+        message: Message,
+        _ctx: &AppContext,
+    ) -> Result<()> {
+        socket.broadcast(ROOM, &message.event, message.data).await
+    }
+}
@@ -19,7 +19,7 @@ use loco_rs::prelude::*;
 {% for action in actions -%}
 #[debug_handler]
 pub async fn {{action}}(
-    ViewEngine(v): ViewEngine<TeraView>,
+    ViewEngine(v): ViewEngine<{{ view_engine }}>,
     State(_ctx): State<AppContext>
 ) -> Result<Response> {
     format::render().view(&v, "{{file_name}}/{{action}}.html", data!({}))
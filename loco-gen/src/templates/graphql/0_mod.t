@@ -0,0 +1,28 @@
+to: "src/graphql/mod.rs"
+skip_exists: true
+message: "GraphQL schema added"
+injections:
+- into: "src/lib.rs"
+  append: true
+  content: "pub mod graphql;"
+---
+use async_graphql::{EmptySubscription, MergedObject, Schema};
+
+#[derive(MergedObject, Default)]
+pub struct Query(
+    // queries-inject
+);
+
+#[derive(MergedObject, Default)]
+pub struct Mutation(
+    // mutations-inject
+);
+
+pub type AppSchema = Schema<Query, Mutation, EmptySubscription>;
+
+/// The schema of the app, served with
+/// `loco_rs::graphql::GraphQLInitializer::new(graphql::schema())`.
+#[must_use]
+pub fn schema() -> AppSchema {
+    Schema::build(Query::default(), Mutation::default(), EmptySubscription).finish()
+}
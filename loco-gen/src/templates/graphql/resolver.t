@@ -0,0 +1,39 @@
+{% set file_name = name | snake_case -%}
+{% set module_name = file_name | pascal_case -%}
+to: "src/graphql/{{file_name}}.rs"
+skip_exists: true
+message: "GraphQL resolvers `{{module_name}}Query` and `{{module_name}}Mutation` were added successfully. Serve the schema with `GraphQLInitializer::new(graphql::schema())` in the `initializers` hook, the `graphql` feature of loco-rs and the `async-graphql` crate added."
+injections:
+- into: "src/graphql/mod.rs"
+  append: true
+  content: "pub mod {{ file_name }};"
+- into: "src/graphql/mod.rs"
+  before: "// queries-inject"
+  content: "    {{ file_name }}::{{module_name}}Query,"
+- into: "src/graphql/mod.rs"
+  before: "// mutations-inject"
+  content: "    {{ file_name }}::{{module_name}}Mutation,"
+---
+use async_graphql::{Context, Object, Result};
+use loco_rs::app::AppContext;
+
+#[derive(Default)]
+pub struct {{module_name}}Query;
+
+#[Object]
+impl {{module_name}}Query {
+    async fn {{file_name}}(&self, ctx: &Context<'_>) -> Result<String> {
+        let app = ctx.data::<AppContext>()?;
+        Ok(format!("{{file_name}} in {}", app.environment))
+    }
+}
+
+#[derive(Default)]
+pub struct {{module_name}}Mutation;
+
+#[Object]
+impl {{module_name}}Mutation {
+    async fn echo_{{file_name}}(&self, message: String) -> String {
+        message
+    }
+}
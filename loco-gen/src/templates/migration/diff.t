@@ -0,0 +1,85 @@
+{% set mig_ts = ts | date(format="%Y%m%d_%H%M%S") -%}
+{% set mig_name = name | snake_case -%}
+{% set module_name = "m" ~  mig_ts ~ "_" ~ mig_name -%}
+{% if with_tz %}
+{% set create_table_func = "create_table" %}
+{% else %}
+{% set create_table_func = "create_table_without_timestamps" %}
+{% endif %}
+to: "migration/src/{{module_name}}.rs"
+skip_glob: "migration/src/m????????_??????_{{mig_name}}.rs"
+message: "Migration `{{mig_name}}` added! Review it, then apply it with `$ cargo loco db migrate && cargo loco db entities`."
+injections:
+- into: "migration/src/lib.rs"
+  before: "inject-above"
+  content: "            Box::new({{module_name}}::Migration),"
+- into: "migration/src/lib.rs"
+  before: "pub struct Migrator"
+  content: "mod {{module_name}};"
+---
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        {% for table in create_tables -%}
+        {{create_table_func}}(m, "{{table.name}}",
+            &[
+            ("id", ColType::PkAuto),
+            {% for column in table.columns -%}
+            ("{{column.0}}", ColType::{{column.1}}),
+            {% endfor -%}
+            ],
+            &[
+            {% for ref in table.references -%}
+            ("{{ref.0}}", "{{ref.1}}"),
+            {% endfor -%}
+            ]
+        ).await?;
+        {% endfor -%}
+
+        {% for table in add_columns -%}
+        {% for column in table.columns -%}
+        add_column(m, "{{table.name}}", "{{column.0}}", ColType::{{column.1}}).await?;
+        {% endfor -%}
+        {% for ref in table.references -%}
+        add_reference(m, "{{table.name}}", "{{ref.0}}", "{{ref.1}}").await?;
+        {% endfor -%}
+        {% endfor -%}
+
+        {% for table in remove_columns -%}
+        {% for column in table.columns -%}
+        remove_column(m, "{{table.name}}", "{{column.0}}").await?;
+        {% endfor -%}
+        {% endfor -%}
+
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        {% for table in remove_columns -%}
+        {% for column in table.columns -%}
+        add_column(m, "{{table.name}}", "{{column.0}}", ColType::{{column.1}}).await?;
+        {% endfor -%}
+        {% endfor -%}
+
+        {% for table in add_columns -%}
+        {% for ref in table.references -%}
+        remove_reference(m, "{{table.name}}", "{{ref.0}}", "{{ref.1}}").await?;
+        {% endfor -%}
+        {% for column in table.columns -%}
+        remove_column(m, "{{table.name}}", "{{column.0}}").await?;
+        {% endfor -%}
+        {% endfor -%}
+
+        {% for table in create_tables | reverse -%}
+        drop_table(m, "{{table.name}}").await?;
+        {% endfor -%}
+
+        Ok(())
+    }
+}
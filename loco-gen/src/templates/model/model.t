@@ -30,7 +30,7 @@ impl MigrationTrait for Migration {
         {{create_table_func}}(m, "{{plural_snake}}",
             &[
             {% if columns | length > 0 %}
-            ("id", ColType::PkAuto),
+            ("id", ColType::{{pk_col_type}}),
             {% endif %}
             {% for column in columns -%}
             ("{{column.0}}", ColType::{{column.1}}),
@@ -0,0 +1,8 @@
+to: "src/policies/mod.rs"
+skip_exists: true
+message: "Policies module added"
+injections:
+- into: "src/lib.rs"
+  append: true
+  content: "pub mod policies;"
+---
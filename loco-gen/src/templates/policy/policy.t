@@ -0,0 +1,37 @@
+{% set file_name = name | snake_case -%}
+{% set plural_snake = name | plural | snake_case -%}
+{% set struct_name = file_name | pascal_case -%}
+to: "src/policies/{{file_name}}.rs"
+skip_exists: true
+message: "A policy `{{struct_name}}Policy` was added successfully."
+injections:
+- into: "src/policies/mod.rs"
+  append: true
+  content: "pub mod {{ file_name }};"
+---
+use loco_rs::prelude::*;
+
+use crate::models::_entities::{ {{plural_snake}}, users };
+
+pub struct {{struct_name}}Policy<'a> {
+    pub user: &'a users::Model,
+    pub {{file_name}}: &'a {{plural_snake}}::Model,
+}
+
+impl<'a> Policy<&'a users::Model, &'a {{plural_snake}}::Model> for {{struct_name}}Policy<'a> {
+    fn new(user: &'a users::Model, {{file_name}}: &'a {{plural_snake}}::Model) -> Self {
+        Self { user, {{file_name}} }
+    }
+
+    fn index(&self) -> bool {
+        true
+    }
+
+    fn show(&self) -> bool {
+        true
+    }
+
+    fn create(&self) -> bool {
+        true
+    }
+}
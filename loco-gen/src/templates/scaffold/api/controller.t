@@ -17,7 +17,7 @@ injections:
 use loco_rs::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::models::_entities::{{file_name | plural}}::{ActiveModel, Entity, Model};
+use crate::models::_entities::{{file_name | plural}}::{ActiveModel, {% if parent %}Column, {% endif %}Entity, Model};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Params {
@@ -28,6 +28,11 @@ pub struct Params {
     pub {{column.0}}: {{column.1}},
     {%- endif %}
     {% endfor -%}
+    {% if optimistic_lock -%}
+    /// The version the item was read at, the update is rejected when it changed since
+    #[serde(default)]
+    pub lock_version: Option<i32>,
+    {% endif -%}
 }
 
 impl Params {
@@ -46,14 +51,95 @@ impl Params {
     }
 }
 
-async fn load_item(ctx: &AppContext, id: i32) -> Result<Model> {
-    let item = Entity::find_by_id(id).one(&ctx.db).await?;
+{% if parent -%}
+{% set parent_id = parent ~ "_id" -%}
+async fn load_item(ctx: &AppContext, {{parent_id}}: i32, id: {{id_type}}) -> Result<Model> {
+    let item = Entity::{% if soft_delete %}find_active_by_id{% else %}find_by_id{% endif %}(id)
+        .filter(Column::{{parent_id | pascal_case}}.eq({{parent_id}}))
+        .one(&ctx.db)
+        .await?;
+    item.ok_or_else(|| Error::NotFound)
+}
+
+#[debug_handler]
+pub async fn list(Path({{parent_id}}): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+    format::json(
+        Entity::{% if soft_delete %}find_active{% else %}find{% endif %}()
+            .filter(Column::{{parent_id | pascal_case}}.eq({{parent_id}}))
+            .all(&ctx.db)
+            .await?,
+    )
+}
+
+#[debug_handler]
+pub async fn add(
+    Path({{parent_id}}): Path<i32>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<Params>,
+) -> Result<Response> {
+    let mut item = ActiveModel {
+        {{parent_id}}: Set({{parent_id}}),
+        ..Default::default()
+    };
+    params.update(&mut item);
+    let item = item.insert(&ctx.db).await?;
+    format::json(item)
+}
+
+#[debug_handler]
+pub async fn update(
+    Path(({{parent_id}}, id)): Path<(i32, {{id_type}})>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<Params>,
+) -> Result<Response> {
+    let item = load_item(&ctx, {{parent_id}}, id).await?;
+    let mut item = item.into_active_model();
+    params.update(&mut item);
+    {% if optimistic_lock -%}
+    if let Some(lock_version) = params.lock_version {
+        item.lock_version = Set(lock_version);
+    }
+    {% endif -%}
+    let item = item.{% if optimistic_lock %}update_locked{% else %}update{% endif %}(&ctx.db).await?;
+    format::json(item)
+}
+
+#[debug_handler]
+pub async fn remove(
+    Path(({{parent_id}}, id)): Path<(i32, {{id_type}})>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    load_item(&ctx, {{parent_id}}, id).await?.{% if soft_delete %}soft_delete{% else %}delete{% endif %}(&ctx.db).await?;
+    format::empty()
+}
+
+#[debug_handler]
+pub async fn get_one(
+    Path(({{parent_id}}, id)): Path<(i32, {{id_type}})>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    format::json(load_item(&ctx, {{parent_id}}, id).await?)
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("api/{{parent | plural}}/{{ "{" ~ parent_id ~ "}" }}/{{file_name | plural}}/")
+        .add("/", get(list))
+        .add("/", post(add))
+        .add("{id}", get(get_one))
+        .add("{id}", delete(remove))
+        .add("{id}", put(update))
+        .add("{id}", patch(update))
+}
+{%- else -%}
+async fn load_item(ctx: &AppContext, id: {{id_type}}) -> Result<Model> {
+    let item = Entity::{% if soft_delete %}find_active_by_id{% else %}find_by_id{% endif %}(id).one(&ctx.db).await?;
     item.ok_or_else(|| Error::NotFound)
 }
 
 #[debug_handler]
 pub async fn list(State(ctx): State<AppContext>) -> Result<Response> {
-    format::json(Entity::find().all(&ctx.db).await?)
+    format::json(Entity::{% if soft_delete %}find_active{% else %}find{% endif %}().all(&ctx.db).await?)
 }
 
 #[debug_handler]
@@ -68,25 +154,30 @@ pub async fn add(State(ctx): State<AppContext>, Json(params): Json<Params>) -> R
 
 #[debug_handler]
 pub async fn update(
-    Path(id): Path<i32>,
+    Path(id): Path<{{id_type}}>,
     State(ctx): State<AppContext>,
     Json(params): Json<Params>,
 ) -> Result<Response> {
     let item = load_item(&ctx, id).await?;
     let mut item = item.into_active_model();
     params.update(&mut item);
-    let item = item.update(&ctx.db).await?;
+    {% if optimistic_lock -%}
+    if let Some(lock_version) = params.lock_version {
+        item.lock_version = Set(lock_version);
+    }
+    {% endif -%}
+    let item = item.{% if optimistic_lock %}update_locked{% else %}update{% endif %}(&ctx.db).await?;
     format::json(item)
 }
 
 #[debug_handler]
-pub async fn remove(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
-    load_item(&ctx, id).await?.delete(&ctx.db).await?;
+pub async fn remove(Path(id): Path<{{id_type}}>, State(ctx): State<AppContext>) -> Result<Response> {
+    load_item(&ctx, id).await?.{% if soft_delete %}soft_delete{% else %}delete{% endif %}(&ctx.db).await?;
     format::empty()
 }
 
 #[debug_handler]
-pub async fn get_one(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+pub async fn get_one(Path(id): Path<{{id_type}}>, State(ctx): State<AppContext>) -> Result<Response> {
     format::json(load_item(&ctx, id).await?)
 }
 
@@ -100,3 +191,4 @@ pub fn routes() -> Routes {
         .add("{id}", put(update))
         .add("{id}", patch(update))
 }
+{%- endif %}
@@ -34,6 +34,11 @@ pub struct Params {
     pub {{column.0}}: {{column.1}},
     {%- endif %}
     {% endfor -%}
+    {% if optimistic_lock -%}
+    /// The version the item was read at, the update is rejected when it changed since
+    #[serde(default)]
+    pub lock_version: Option<i32>,
+    {% endif -%}
 }
 
 impl Params {
@@ -52,17 +57,17 @@ impl Params {
     }
 }
 
-async fn load_item(ctx: &AppContext, id: i32) -> Result<Model> {
-    let item = Entity::find_by_id(id).one(&ctx.db).await?;
+async fn load_item(ctx: &AppContext, id: {{id_type}}) -> Result<Model> {
+    let item = Entity::{% if soft_delete %}find_active_by_id{% else %}find_by_id{% endif %}(id).one(&ctx.db).await?;
     item.ok_or_else(|| Error::NotFound)
 }
 
 #[debug_handler]
 pub async fn list(
-    ViewEngine(v): ViewEngine<TeraView>,
+    ViewEngine(v): ViewEngine<{{ view_engine }}>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let item = Entity::find()
+    let item = Entity::{% if soft_delete %}find_active{% else %}find{% endif %}()
         .order_by(Column::Id, Order::Desc)
         .all(&ctx.db)
         .await?;
@@ -71,7 +76,7 @@ pub async fn list(
 
 #[debug_handler]
 pub async fn new(
-    ViewEngine(v): ViewEngine<TeraView>,
+    ViewEngine(v): ViewEngine<{{ view_engine }}>,
     State(_ctx): State<AppContext>,
 ) -> Result<Response> {
     views::{{file_name}}::create(&v)
@@ -79,21 +84,26 @@ pub async fn new(
 
 #[debug_handler]
 pub async fn update(
-    Path(id): Path<i32>,
+    Path(id): Path<{{id_type}}>,
     State(ctx): State<AppContext>,
     Form(params): Form<Params>,
 ) -> Result<Redirect> {
     let item = load_item(&ctx, id).await?;
     let mut item = item.into_active_model();
     params.update(&mut item);
-    item.update(&ctx.db).await?;
+    {% if optimistic_lock -%}
+    if let Some(lock_version) = params.lock_version {
+        item.lock_version = Set(lock_version);
+    }
+    {% endif -%}
+    item.{% if optimistic_lock %}update_locked{% else %}update{% endif %}(&ctx.db).await?;
     Ok(Redirect::to("../{{file_name | plural}}"))
 }
 
 #[debug_handler]
 pub async fn edit(
-    Path(id): Path<i32>,
-    ViewEngine(v): ViewEngine<TeraView>,
+    Path(id): Path<{{id_type}}>,
+    ViewEngine(v): ViewEngine<{{ view_engine }}>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
     let item = load_item(&ctx, id).await?;
@@ -102,8 +112,8 @@ pub async fn edit(
 
 #[debug_handler]
 pub async fn show(
-    Path(id): Path<i32>,
-    ViewEngine(v): ViewEngine<TeraView>,
+    Path(id): Path<{{id_type}}>,
+    ViewEngine(v): ViewEngine<{{ view_engine }}>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
     let item = load_item(&ctx, id).await?;
@@ -124,8 +134,8 @@ pub async fn add(
 }
 
 #[debug_handler]
-pub async fn remove(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
-    load_item(&ctx, id).await?.delete(&ctx.db).await?;
+pub async fn remove(Path(id): Path<{{id_type}}>, State(ctx): State<AppContext>) -> Result<Response> {
+    load_item(&ctx, id).await?.{% if soft_delete %}soft_delete{% else %}delete{% endif %}(&ctx.db).await?;
     format::empty()
 }
 
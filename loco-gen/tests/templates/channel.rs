@@ -0,0 +1,46 @@
+use loco_gen::{collect_messages, generate, AppInfo, Component, ViewEngineKind};
+use rrgen::RRgen;
+use std::fs;
+
+#[test]
+fn can_generate() {
+    let component = Component::Channel {
+        name: "chat".to_string(),
+    };
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "pub mod models;\n")
+        .create()
+        .expect("Failed to create tree_fs structure");
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root);
+
+    let gen_result = generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect("Failed to generate components");
+
+    assert_eq!(
+        collect_messages(&gen_result),
+        r"* Channels module added
+* A channel `ChatChannel` was added successfully. Route it with `channels.handler(ChatChannel)`, the `channels` feature of loco-rs enabled.
+"
+    );
+
+    let channels_path = tree_fs.root.join("src").join("channels");
+    let channel = fs::read_to_string(channels_path.join("chat.rs")).expect("channel missing");
+    assert!(channel.contains("impl Channel for ChatChannel {"));
+    assert!(channel.contains(r#"const ROOM: &str = "chat";"#));
+
+    let channels_mod = fs::read_to_string(channels_path.join("mod.rs")).expect("mod.rs missing");
+    assert!(channels_mod.contains("pub mod chat;"));
+
+    let lib = fs::read_to_string(tree_fs.root.join("src").join("lib.rs")).expect("lib.rs missing");
+    assert!(lib.contains("pub mod channels;"));
+}
@@ -1,6 +1,6 @@
 use super::utils::APP_ROUTS;
 use insta::assert_snapshot;
-use loco_gen::{collect_messages, generate, AppInfo, Component, ScaffoldKind};
+use loco_gen::{collect_messages, generate, AppInfo, Component, ScaffoldKind, ViewEngineKind};
 use rrgen::RRgen;
 use rstest::rstest;
 use std::fs;
@@ -38,6 +38,7 @@ fn can_generate(#[case] kind: ScaffoldKind) {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Generation failed");
@@ -86,3 +87,36 @@ fn can_generate(#[case] kind: ScaffoldKind) {
         }
     }
 }
+
+#[test]
+fn can_generate_with_minijinja_view_engine() {
+    let component = Component::Controller {
+        name: "movie".to_string(),
+        actions: vec!["GET".to_string()],
+        kind: ScaffoldKind::Html,
+    };
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add_empty("src/controllers/mod.rs")
+        .add("src/app.rs", APP_ROUTS)
+        .create()
+        .unwrap();
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root);
+
+    generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::MiniJinja,
+        },
+    )
+    .expect("Generation failed");
+
+    let controller = fs::read_to_string(tree_fs.root.join("src/controllers/movie.rs"))
+        .expect("controller file missing");
+    assert!(controller.contains("ViewEngine(v): ViewEngine<MiniJinjaView>"));
+    assert!(!controller.contains("TeraView"));
+}
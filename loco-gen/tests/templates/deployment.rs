@@ -1,5 +1,5 @@
 use insta::assert_snapshot;
-use loco_gen::{collect_messages, generate, AppInfo, Component, DeploymentKind};
+use loco_gen::{collect_messages, generate, AppInfo, Component, DeploymentKind, ViewEngineKind};
 use rrgen::RRgen;
 use std::{fs, path::PathBuf};
 
@@ -29,6 +29,7 @@ fn can_generate_docker(
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Generation failed");
@@ -87,6 +88,7 @@ fn can_generate_nginx() {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Generation failed");
@@ -0,0 +1,48 @@
+use loco_gen::{collect_messages, generate, AppInfo, Component, ViewEngineKind};
+use rrgen::RRgen;
+use std::fs;
+
+#[test]
+fn can_generate() {
+    let component = Component::GraphQL {
+        name: "posts".to_string(),
+    };
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "pub mod models;\n")
+        .create()
+        .expect("Failed to create tree_fs structure");
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root);
+
+    let gen_result = generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect("Failed to generate components");
+
+    assert_eq!(
+        collect_messages(&gen_result),
+        r"* GraphQL schema added
+* GraphQL resolvers `PostsQuery` and `PostsMutation` were added successfully. Serve the schema with `GraphQLInitializer::new(graphql::schema())` in the `initializers` hook, the `graphql` feature of loco-rs and the `async-graphql` crate added.
+"
+    );
+
+    let graphql_path = tree_fs.root.join("src").join("graphql");
+    let resolvers = fs::read_to_string(graphql_path.join("posts.rs")).expect("resolvers missing");
+    assert!(resolvers.contains("impl PostsQuery {"));
+    assert!(resolvers.contains("async fn echo_posts(&self, message: String) -> String {"));
+
+    let graphql_mod = fs::read_to_string(graphql_path.join("mod.rs")).expect("mod.rs missing");
+    assert!(graphql_mod.contains("pub mod posts;"));
+    assert!(graphql_mod.contains("    posts::PostsQuery,\n    // queries-inject"));
+    assert!(graphql_mod.contains("    posts::PostsMutation,\n    // mutations-inject"));
+
+    let lib = fs::read_to_string(tree_fs.root.join("src").join("lib.rs")).expect("lib.rs missing");
+    assert!(lib.contains("pub mod graphql;"));
+}
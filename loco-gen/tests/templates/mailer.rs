@@ -1,5 +1,5 @@
 use insta::assert_snapshot;
-use loco_gen::{collect_messages, generate, AppInfo, Component};
+use loco_gen::{collect_messages, generate, AppInfo, Component, ViewEngineKind};
 use rrgen::RRgen;
 use std::fs;
 
@@ -27,6 +27,7 @@ fn can_generate() {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Generation failed");
@@ -1,6 +1,6 @@
 use super::utils::{guess_file_by_time, MIGRATION_SRC_LIB};
 use insta::{assert_snapshot, with_settings};
-use loco_gen::{collect_messages, generate, AppInfo, Component};
+use loco_gen::{collect_messages, generate, AppInfo, Component, ViewEngineKind};
 use rrgen::RRgen;
 use rstest::rstest;
 use std::fs;
@@ -90,6 +90,7 @@ fn can_generate(
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Generation failed");
@@ -180,6 +181,7 @@ fn fail_when_migration_lib_not_exists(#[case] component: Component) {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect_err("Expected error when migration lib doesn't exist");
@@ -189,3 +191,67 @@ fn fail_when_migration_lib_not_exists(#[case] component: Component) {
         "cannot inject into migration/src/lib.rs: file does not exist"
     );
 }
+
+#[test]
+fn can_generate_schema_diff() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("migration/src/lib.rs", MIGRATION_SRC_LIB)
+        .create()
+        .unwrap();
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root);
+    generate(
+        &rrgen,
+        Component::SchemaDiff {
+            name: "ReconcileSchema".to_string(),
+            with_tz: true,
+            create_tables: vec![(
+                "movies".to_string(),
+                vec![
+                    ("title".to_string(), "string!".to_string()),
+                    ("user".to_string(), "references".to_string()),
+                ],
+            )],
+            add_columns: vec![(
+                "users".to_string(),
+                vec![
+                    ("age".to_string(), "int".to_string()),
+                    ("team".to_string(), "references?".to_string()),
+                ],
+            )],
+            remove_columns: vec![(
+                "users".to_string(),
+                vec![("nickname".to_string(), "StringNull".to_string())],
+            )],
+        },
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect("Generation failed");
+
+    let migration_path = tree_fs.root.join("migration").join("src");
+    let migration = fs::read_to_string(
+        guess_file_by_time(&migration_path, "m{TIME}_reconcile_schema.rs", 3)
+            .expect("Failed to find the generated migration file"),
+    )
+    .unwrap();
+    for statement in [
+        r#"create_table(m, "movies","#,
+        r#"("title", ColType::String),"#,
+        r#"("user", ""),"#,
+        r#"add_column(m, "users", "age", ColType::IntegerNull).await?;"#,
+        r#"add_reference(m, "users", "team?", "").await?;"#,
+        r#"remove_column(m, "users", "nickname").await?;"#,
+        r#"add_column(m, "users", "nickname", ColType::StringNull).await?;"#,
+        r#"remove_reference(m, "users", "team?", "").await?;"#,
+        r#"drop_table(m, "movies").await?;"#,
+    ] {
+        assert!(migration.contains(statement), "missing `{statement}`");
+    }
+    assert!(fs::read_to_string(migration_path.join("lib.rs"))
+        .unwrap()
+        .contains("_reconcile_schema::Migration),"));
+}
@@ -1,10 +1,13 @@
+mod channel;
 mod controller;
 mod deployment;
+mod graphql;
 mod mailer;
 #[cfg(feature = "with-db")]
 mod migration;
 #[cfg(feature = "with-db")]
 mod model;
+mod policy;
 #[cfg(feature = "with-db")]
 mod scaffold;
 mod scheduler;
@@ -1,6 +1,6 @@
 use super::utils::{guess_file_by_time, MIGRATION_SRC_LIB};
 use insta::{assert_snapshot, with_settings};
-use loco_gen::{collect_messages, generate, AppInfo, Component};
+use loco_gen::{collect_messages, generate, AppInfo, Component, PrimaryKeyKind, ViewEngineKind};
 use rrgen::RRgen;
 use std::fs;
 
@@ -29,6 +29,7 @@ fn can_generate() {
         name: "movies".to_string(),
         with_tz: true,
         fields: vec![("title".to_string(), "string".to_string())],
+        primary_key: PrimaryKeyKind::Auto,
     };
 
     let gen_result = generate(
@@ -36,6 +37,7 @@ fn can_generate() {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Generation failed");
@@ -90,6 +92,7 @@ fn fail_when_migration_lib_not_exists() {
         name: "movies".to_string(),
         with_tz: true,
         fields: vec![("title".to_string(), "string".to_string())],
+        primary_key: PrimaryKeyKind::Auto,
     };
 
     let err = generate(
@@ -97,6 +100,7 @@ fn fail_when_migration_lib_not_exists() {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect_err("Expected error when model lib doesn't exist");
@@ -121,6 +125,7 @@ fn fail_when_test_models_mod_not_exists() {
         name: "movies".to_string(),
         with_tz: true,
         fields: vec![("title".to_string(), "string".to_string())],
+        primary_key: PrimaryKeyKind::Auto,
     };
 
     let err = generate(
@@ -128,6 +133,7 @@ fn fail_when_test_models_mod_not_exists() {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect_err("Expected error when migration src doesn't exist");
@@ -0,0 +1,48 @@
+use loco_gen::{collect_messages, generate, AppInfo, Component, ViewEngineKind};
+use rrgen::RRgen;
+use std::fs;
+
+#[test]
+fn can_generate() {
+    let component = Component::Policy {
+        name: "post".to_string(),
+    };
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "pub mod models;\n")
+        .create()
+        .expect("Failed to create tree_fs structure");
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root);
+
+    let gen_result = generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect("Failed to generate components");
+
+    assert_eq!(
+        collect_messages(&gen_result),
+        r"* Policies module added
+* A policy `PostPolicy` was added successfully.
+"
+    );
+
+    let policies_path = tree_fs.root.join("src").join("policies");
+    let policy = fs::read_to_string(policies_path.join("post.rs")).expect("policy missing");
+    assert!(policy.contains("use crate::models::_entities::{ posts, users };"));
+    assert!(
+        policy.contains("impl<'a> Policy<&'a users::Model, &'a posts::Model> for PostPolicy<'a> {")
+    );
+
+    let policies_mod = fs::read_to_string(policies_path.join("mod.rs")).expect("mod.rs missing");
+    assert!(policies_mod.contains("pub mod post;"));
+
+    let lib = fs::read_to_string(tree_fs.root.join("src").join("lib.rs")).expect("lib.rs missing");
+    assert!(lib.contains("pub mod policies;"));
+}
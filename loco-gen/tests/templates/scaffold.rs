@@ -1,6 +1,9 @@
 use super::utils::{guess_file_by_time, APP_ROUTS, MIGRATION_SRC_LIB};
 use insta::{assert_snapshot, with_settings};
-use loco_gen::{collect_messages, generate, tera_ext, AppInfo, Component, ScaffoldKind};
+use loco_gen::{
+    collect_messages, generate, tera_ext, AppInfo, Component, PrimaryKeyKind, ScaffoldKind,
+    ViewEngineKind,
+};
 use rrgen::RRgen;
 use rstest::rstest;
 use std::fs;
@@ -25,6 +28,7 @@ fn can_generate(#[case] kind: ScaffoldKind) {
             ("user".to_string(), "references".to_string()),
         ],
         kind: kind.clone(),
+        primary_key: PrimaryKeyKind::Auto,
     };
 
     let tree_fs = tree_fs::TreeBuilder::default()
@@ -45,6 +49,7 @@ fn can_generate(#[case] kind: ScaffoldKind) {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Generation failed");
@@ -136,3 +141,254 @@ fn can_generate(#[case] kind: ScaffoldKind) {
 }
 
 // thread 'templates::scaffold::can_generate::case_1' panicked at loco-gen/tests/templates/scaffold.rs:48:6:
+
+#[test]
+fn can_generate_soft_delete() {
+    std::env::set_var("SKIP_MIGRATION", "");
+    let component = Component::Scaffold {
+        name: "movie".to_string(),
+        with_tz: true,
+        fields: vec![
+            ("title".to_string(), "string".to_string()),
+            ("deleted_at".to_string(), "tstz".to_string()),
+        ],
+        kind: ScaffoldKind::Api,
+        primary_key: PrimaryKeyKind::Auto,
+    };
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add_empty("src/controllers/mod.rs")
+        .add_empty("tests/models/mod.rs")
+        .add_empty("tests/requests/mod.rs")
+        .add("migration/src/lib.rs", MIGRATION_SRC_LIB)
+        .add("src/app.rs", APP_ROUTS)
+        .create()
+        .unwrap();
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root).add_template_engine(tera_ext::new());
+    generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect("Generation failed");
+
+    let migration_file =
+        guess_file_by_time(&tree_fs.root.join("migration/src"), "m{TIME}_movies.rs", 3)
+            .expect("Failed to find the generated migration file");
+    assert!(fs::read_to_string(migration_file)
+        .unwrap()
+        .contains(r#"("deleted_at", ColType::TimestampWithTimeZoneNull)"#));
+
+    let controller = fs::read_to_string(tree_fs.root.join("src/controllers/movie.rs"))
+        .expect("controller file missing");
+    assert!(controller.contains("Entity::find_active_by_id(id)"));
+    assert!(controller.contains("Entity::find_active().all(&ctx.db)"));
+    assert!(controller.contains(".soft_delete(&ctx.db)"));
+    assert!(!controller.contains("deleted_at"));
+}
+
+#[test]
+fn can_generate_optimistic_lock() {
+    std::env::set_var("SKIP_MIGRATION", "");
+    let component = Component::Scaffold {
+        name: "movie".to_string(),
+        with_tz: true,
+        fields: vec![
+            ("title".to_string(), "string".to_string()),
+            ("lock_version".to_string(), "int".to_string()),
+        ],
+        kind: ScaffoldKind::Api,
+        primary_key: PrimaryKeyKind::Auto,
+    };
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add_empty("src/controllers/mod.rs")
+        .add_empty("tests/models/mod.rs")
+        .add_empty("tests/requests/mod.rs")
+        .add("migration/src/lib.rs", MIGRATION_SRC_LIB)
+        .add("src/app.rs", APP_ROUTS)
+        .create()
+        .unwrap();
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root).add_template_engine(tera_ext::new());
+    generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect("Generation failed");
+
+    let migration_file =
+        guess_file_by_time(&tree_fs.root.join("migration/src"), "m{TIME}_movies.rs", 3)
+            .expect("Failed to find the generated migration file");
+    assert!(fs::read_to_string(migration_file)
+        .unwrap()
+        .contains(r#"("lock_version", ColType::IntegerWithDefault(0))"#));
+
+    let controller = fs::read_to_string(tree_fs.root.join("src/controllers/movie.rs"))
+        .expect("controller file missing");
+    assert!(controller.contains("pub lock_version: Option<i32>,"));
+    assert!(controller.contains("item.lock_version = Set(lock_version);"));
+    assert!(controller.contains("item.update_locked(&ctx.db)"));
+}
+
+#[rstest]
+#[case(PrimaryKeyKind::UuidV7, "PkUuid", "Uuid")]
+#[case(PrimaryKeyKind::Ulid, "PkUlid", "String")]
+fn can_generate_time_ordered_primary_keys(
+    #[case] primary_key: PrimaryKeyKind,
+    #[case] col_type: &str,
+    #[case] id_type: &str,
+) {
+    std::env::set_var("SKIP_MIGRATION", "");
+    let component = Component::Scaffold {
+        name: "movie".to_string(),
+        with_tz: true,
+        fields: vec![("title".to_string(), "string".to_string())],
+        kind: ScaffoldKind::Api,
+        primary_key,
+    };
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add_empty("src/controllers/mod.rs")
+        .add_empty("tests/models/mod.rs")
+        .add_empty("tests/requests/mod.rs")
+        .add("migration/src/lib.rs", MIGRATION_SRC_LIB)
+        .add("src/app.rs", APP_ROUTS)
+        .create()
+        .unwrap();
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root).add_template_engine(tera_ext::new());
+    generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect("Generation failed");
+
+    let migration_file =
+        guess_file_by_time(&tree_fs.root.join("migration/src"), "m{TIME}_movies.rs", 3)
+            .expect("Failed to find the generated migration file");
+    assert!(fs::read_to_string(migration_file)
+        .unwrap()
+        .contains(&format!(r#"("id", ColType::{col_type})"#)));
+
+    let controller = fs::read_to_string(tree_fs.root.join("src/controllers/movie.rs"))
+        .expect("controller file missing");
+    assert!(controller.contains(&format!(
+        "async fn load_item(ctx: &AppContext, id: {id_type})"
+    )));
+    assert!(controller.contains(&format!("Path(id): Path<{id_type}>")));
+    assert!(!controller.contains("Path<i32>"));
+}
+
+#[test]
+fn can_generate_nested_scaffold() {
+    std::env::set_var("SKIP_MIGRATION", "");
+    let component = Component::Scaffold {
+        name: "post".to_string(),
+        with_tz: true,
+        fields: vec![
+            ("title".to_string(), "string!".to_string()),
+            ("belongs_to".to_string(), "users".to_string()),
+            ("has_many".to_string(), "comment".to_string()),
+        ],
+        kind: ScaffoldKind::Api,
+        primary_key: PrimaryKeyKind::Auto,
+    };
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add_empty("src/controllers/mod.rs")
+        .add_empty("tests/models/mod.rs")
+        .add_empty("tests/requests/mod.rs")
+        .add("migration/src/lib.rs", MIGRATION_SRC_LIB)
+        .add("src/app.rs", APP_ROUTS)
+        .create()
+        .unwrap();
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root).add_template_engine(tera_ext::new());
+    generate(
+        &rrgen,
+        component,
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect("Generation failed");
+
+    let migration_path = tree_fs.root.join("migration/src");
+    let migration = fs::read_to_string(
+        guess_file_by_time(&migration_path, "m{TIME}_posts.rs", 3)
+            .expect("Failed to find the generated migration file"),
+    )
+    .unwrap();
+    assert!(migration.contains(r#"("user", ""),"#));
+    // the migration of the children runs after the one of the posts
+    let children = fs::read_dir(&migration_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            path.to_string_lossy()
+                .ends_with("_add_post_ref_to_comments.rs")
+        })
+        .expect("Failed to find the migration of the children");
+    let children = fs::read_to_string(children).unwrap();
+    assert!(children.contains(r#"add_reference(m, "comments", "post", "").await?;"#));
+
+    let controller = fs::read_to_string(tree_fs.root.join("src/controllers/post.rs"))
+        .expect("controller file missing");
+    assert!(controller.contains(r#".prefix("api/users/{user_id}/posts/")"#));
+    assert!(controller.contains(".filter(Column::UserId.eq(user_id))"));
+    assert!(controller.contains("user_id: Set(user_id),"));
+    assert!(controller.contains("Path((user_id, id)): Path<(i32, i32)>"));
+    assert!(!controller.contains("pub user_id: i32,"));
+
+    let test =
+        fs::read_to_string(tree_fs.root.join("tests/requests/post.rs")).expect("test file missing");
+    assert!(test.contains(r#"request.get("/api/users/1/posts/")"#));
+}
+
+#[test]
+fn fail_to_nest_view_scaffolds() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("migration/src/lib.rs", MIGRATION_SRC_LIB)
+        .create()
+        .unwrap();
+
+    let rrgen = RRgen::with_working_dir(&tree_fs.root);
+    let err = generate(
+        &rrgen,
+        Component::Scaffold {
+            name: "post".to_string(),
+            with_tz: true,
+            fields: vec![("belongs_to".to_string(), "user".to_string())],
+            kind: ScaffoldKind::Html,
+            primary_key: PrimaryKeyKind::Auto,
+        },
+        &AppInfo {
+            app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
+        },
+    )
+    .expect_err("Expected an error when nesting an HTML scaffold");
+    assert_eq!(
+        err.to_string(),
+        "`belongs_to` nests the routes of API scaffolds only"
+    );
+}
@@ -1,6 +1,6 @@
 use super::utils::APP_TASK;
 use insta::assert_snapshot;
-use loco_gen::{collect_messages, generate, AppInfo, Component};
+use loco_gen::{collect_messages, generate, AppInfo, Component, ViewEngineKind};
 use rrgen::RRgen;
 use std::fs;
 
@@ -37,6 +37,7 @@ fn can_generate() {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Failed to generate components");
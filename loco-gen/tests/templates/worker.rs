@@ -1,6 +1,6 @@
 use super::utils::APP_WORKER;
 use insta::assert_snapshot;
-use loco_gen::{collect_messages, generate, AppInfo, Component};
+use loco_gen::{collect_messages, generate, AppInfo, Component, ViewEngineKind};
 use rrgen::RRgen;
 use std::fs;
 
@@ -36,6 +36,7 @@ fn can_generate() {
         component,
         &AppInfo {
             app_name: "tester".to_string(),
+            view_engine: ViewEngineKind::Tera,
         },
     )
     .expect("Failed to generate components");
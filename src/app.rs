@@ -1,10 +1,21 @@
 //! This module contains the core components and traits for building a web
 //! server application.
 #[cfg(feature = "with-db")]
-use {sea_orm::DatabaseConnection, std::path::Path};
+use {
+    crate::{
+        listener::Listeners,
+        model::{encryption::Encryptor, ModelError, ModelResult},
+        outbox,
+        seeder::Seeders,
+        tenancy::TenantAuthorizer,
+    },
+    sea_orm::DatabaseConnection,
+    std::path::Path,
+};
 
 use std::{
     any::{Any, TypeId},
+    future::IntoFuture,
     net::SocketAddr,
     sync::Arc,
 };
@@ -15,16 +26,17 @@ use axum::Router as AxumRouter;
 use dashmap::DashMap;
 
 use crate::{
-    bgworker::{self, Queue},
+    bgworker::{self, middleware::JobMiddleware, schedule::Schedule, Queue},
     boot::{shutdown_signal, BootResult, ServeParams, StartMode},
     cache::{self},
     config::Config,
     controller::{
         middleware::{self, MiddlewareLayer},
+        monitoring::HealthCheck,
         AppRoutes,
     },
     environment::Environment,
-    mailer::EmailSender,
+    mailer::{suppression::SuppressionHandler, EmailSender, MailerPreview},
     storage::Storage,
     task::Tasks,
     Result,
@@ -258,6 +270,18 @@ pub struct AppContext {
     #[cfg(feature = "with-db")]
     /// A database connection used by the application.
     pub db: DatabaseConnection,
+    #[cfg(feature = "with-db")]
+    /// The read replicas of the database, see [`AppContext::db_read`].
+    pub replicas: Arc<crate::db::Replicas>,
+    #[cfg(feature = "with-db")]
+    /// Writes the events of the transactions to the outbox, see
+    /// [`crate::outbox`].
+    pub outbox: outbox::Outbox,
+    #[cfg(feature = "with-db")]
+    /// Encrypts the [`crate::model::encryption::Encrypted`] attributes, with
+    /// the `database.encryption` keys. Set it from [`Hooks::after_context`]
+    /// to use keys held by a KMS.
+    pub encryptor: Option<Arc<Encryptor>>,
     /// Queue provider
     pub queue_provider: Option<Arc<bgworker::Queue>>,
     /// Configuration settings for the application
@@ -270,6 +294,40 @@ pub struct AppContext {
     pub cache: Arc<cache::Cache>,
     /// Shared store for arbitrary application data
     pub shared_store: Arc<SharedStore>,
+    #[cfg(feature = "http_client")]
+    /// The HTTP client to call external APIs
+    pub http: Arc<crate::http_client::Client>,
+}
+
+#[cfg(feature = "with-db")]
+impl AppContext {
+    /// Returns a connection to a healthy read replica, or to the primary
+    /// database when there is none.
+    ///
+    /// Replicas lag behind the primary, so read what was just written with
+    /// [`AppContext::db`].
+    #[must_use]
+    pub fn db_read(&self) -> &DatabaseConnection {
+        self.replicas.pick().unwrap_or(&self.db)
+    }
+
+    /// Returns a connection running the `SELECT` queries on the read
+    /// replicas, and the writes on the primary database.
+    #[must_use]
+    pub fn db_routed(&self) -> crate::db::RoutedConnection<'_> {
+        crate::db::RoutedConnection::new(&self.db, &self.replicas)
+    }
+
+    /// Returns the encryptor of the encrypted attributes.
+    ///
+    /// # Errors
+    ///
+    /// When no `database.encryption` keys are configured
+    pub fn encryptor(&self) -> ModelResult<&Encryptor> {
+        self.encryptor.as_deref().ok_or_else(|| {
+            ModelError::msg("encrypted attributes require the `database.encryption` keys")
+        })
+    }
 }
 
 /// A trait that defines hooks for customizing and extending the behavior of a
@@ -326,6 +384,10 @@ pub trait Hooks: Send {
     /// Start serving the Axum web application on the specified address and
     /// port.
     ///
+    /// On a shutdown signal, the server stops accepting connections and
+    /// drains the in-flight requests for up to `server.shutdown_timeout`
+    /// seconds, dropping the remaining ones afterwards.
+    ///
     /// # Returns
     /// A Result indicating success () or an error if the server fails to start.
     async fn serve(app: AxumRouter, ctx: &AppContext, serve_params: &ServeParams) -> Result<()> {
@@ -335,17 +397,34 @@ pub trait Hooks: Send {
         ))
         .await?;
 
-        let cloned_ctx = ctx.clone();
-        axum::serve(
+        let deadline = ctx.config.server.shutdown_deadline();
+        let (draining_tx, draining_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = axum::serve(
             listener,
             app.into_make_service_with_connect_info::<SocketAddr>(),
         )
         .with_graceful_shutdown(async move {
             shutdown_signal().await;
-            tracing::info!("shutting down...");
-            Self::on_shutdown(&cloned_ctx).await;
+            tracing::info!(
+                timeout = deadline.as_secs(),
+                "shutting down, draining connections..."
+            );
+            let _ = draining_tx.send(());
         })
-        .await?;
+        .into_future();
+
+        tokio::select! {
+            result = server => result?,
+            () = async {
+                if draining_rx.await.is_ok() {
+                    tokio::time::sleep(deadline).await;
+                } else {
+                    std::future::pending::<()>().await;
+                }
+            } => {
+                tracing::warn!("shutdown timeout elapsed, dropping the remaining connections");
+            }
+        }
 
         Ok(())
     }
@@ -412,6 +491,45 @@ pub trait Hooks: Send {
     /// Defines the application's routing configuration.
     fn routes(_ctx: &AppContext) -> AppRoutes;
 
+    /// Adds custom checks to the `/_readiness` endpoint, after the database,
+    /// queue and cache ones. The checks marked as liveness checks run on
+    /// `/_health` as well.
+    fn health_checks(_ctx: &AppContext) -> Vec<Arc<dyn HealthCheck>> {
+        vec![]
+    }
+
+    /// Adds middlewares around the jobs of every worker, run in their order
+    /// before a job and in the reverse order after it.
+    fn job_middlewares(_ctx: &AppContext) -> Vec<Arc<dyn JobMiddleware>> {
+        vec![]
+    }
+
+    /// Adds the schedules of the [`crate::bgworker::schedule::Schedulable`]
+    /// workers, enqueueing their jobs from the worker processes.
+    fn schedules(_ctx: &AppContext) -> Vec<Schedule> {
+        vec![]
+    }
+
+    /// Adds the mailer templates rendered with sample locals by
+    /// [`crate::controller::mailer_preview`] in development.
+    fn mailer_previews(_ctx: &AppContext) -> Vec<MailerPreview> {
+        vec![]
+    }
+
+    /// Adds the handlers told of the email addresses added to the
+    /// suppression list by the mailer webhooks.
+    fn suppression_handlers(_ctx: &AppContext) -> Vec<Arc<dyn SuppressionHandler>> {
+        vec![]
+    }
+
+    /// Checks that the users are members of the tenants of their requests,
+    /// for the tenancy middleware. The `header` and `subdomain` tenancy
+    /// resolvers require one.
+    #[cfg(feature = "with-db")]
+    fn tenant_authorizer(_ctx: &AppContext) -> Option<Arc<dyn TenantAuthorizer>> {
+        None
+    }
+
     // Provides the options to change Loco [`AppContext`] after initialization.
     async fn after_context(ctx: AppContext) -> Result<AppContext> {
         Ok(ctx)
@@ -436,7 +554,38 @@ pub trait Hooks: Send {
     #[cfg(feature = "with-db")]
     async fn seed(_ctx: &AppContext, path: &Path) -> Result<()>;
 
-    /// Called when the application is shutting down.
+    /// Registers the programmatic seeders with the provided [`Seeders`]
+    /// object, run by `cargo loco db seed` after [`Hooks::seed`].
+    #[cfg(feature = "with-db")]
+    fn register_seeders(_seeders: &mut Seeders) {}
+
+    /// Registers the handlers of the Postgres notifications with the provided
+    /// [`Listeners`] object, listened to while the app is started.
+    #[cfg(feature = "with-db")]
+    fn register_listeners(_listeners: &mut Listeners) {}
+
+    /// Routes the topics of the outbox events to their publishers, with the
+    /// provided [`outbox::Relay`] object. Without a route, the events are
+    /// enqueued on the background queue.
+    #[cfg(feature = "with-db")]
+    fn register_outbox(_relay: &mut outbox::Relay) {}
+
+    /// Called with the statistics of the connection pool of the database,
+    /// every `database.pool_stats_interval` seconds while the app is started,
+    /// to export them as metrics.
+    #[cfg(feature = "with-db")]
+    async fn on_pool_stats(_ctx: &AppContext, _stats: &crate::db::PoolStats) {}
+
+    /// Called with each snapshot of the `SQLite` database, taken every
+    /// `database.sqlite.backup.interval` seconds while the app is started,
+    /// to ship it away such as to an object store.
+    #[cfg(feature = "with-db")]
+    async fn on_sqlite_backup(_ctx: &AppContext, _path: &std::path::Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the application is shutting down, once the in-flight
+    /// requests are drained and the background workers are stopped.
     /// This function allows users to perform any necessary cleanup or final
     /// actions before the application stops completely.
     async fn on_shutdown(_ctx: &AppContext) {}
@@ -0,0 +1,90 @@
+//! # Impersonation Audit
+//!
+//! Impersonation tokens (see
+//! [`crate::auth::jwt::JWT::generate_impersonation_token`]) let a user, such
+//! as support staff, act as another one. Every request authenticated with
+//! such a token by the `JWTWithUser` extractors is reported to the
+//! registered [`ImpersonationAudit`], to keep a trail of what was done on
+//! behalf of whom.
+//!
+//! # Example
+//! ```rust
+//! use axum::http::request::Parts;
+//! use loco_rs::prelude::*;
+//! use loco_rs::auth::{
+//!     impersonation::{ImpersonationAudit, ImpersonationAuditLog},
+//!     jwt::UserClaims,
+//! };
+//!
+//! struct TracingAudit;
+//!
+//! #[async_trait]
+//! impl ImpersonationAudit for TracingAudit {
+//!     async fn record(&self, _ctx: &AppContext, claims: &UserClaims, parts: &Parts) -> Result<()> {
+//!         tracing::info!(
+//!             impersonator = ?claims.impersonator,
+//!             pid = %claims.pid,
+//!             method = %parts.method,
+//!             uri = %parts.uri,
+//!             "impersonated request"
+//!         );
+//!         Ok(())
+//!     }
+//! }
+//!
+//! fn register(ctx: &AppContext) {
+//!     ctx.shared_store.insert(ImpersonationAuditLog::new(TracingAudit));
+//! }
+//! ```
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::http::request::Parts;
+
+use super::jwt::UserClaims;
+use crate::{app::AppContext, Result};
+
+/// Records the requests made with impersonation tokens.
+#[async_trait]
+pub trait ImpersonationAudit: Send + Sync {
+    /// Records a request authenticated with an impersonation token, whose
+    /// `claims.impersonator` acts as `claims.pid`.
+    ///
+    /// # Errors
+    ///
+    /// Return an error to reject the request, for example when the audit
+    /// record could not be stored.
+    async fn record(&self, ctx: &AppContext, claims: &UserClaims, parts: &Parts) -> Result<()>;
+}
+
+/// The [`ImpersonationAudit`] called by the `JWTWithUser` extractors.
+///
+/// Register it in the application [`crate::app::SharedStore`], for example
+/// in the `after_context` hook.
+#[derive(Clone)]
+pub struct ImpersonationAuditLog(Arc<dyn ImpersonationAudit>);
+
+impl ImpersonationAuditLog {
+    #[must_use]
+    pub fn new(audit: impl ImpersonationAudit + 'static) -> Self {
+        Self(Arc::new(audit))
+    }
+
+    /// Records the request when its token is an impersonation token.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the audit.
+    pub async fn record(&self, ctx: &AppContext, claims: &UserClaims, parts: &Parts) -> Result<()> {
+        if !claims.is_impersonated() {
+            return Ok(());
+        }
+        self.0.record(ctx, claims, parts).await
+    }
+}
+
+impl std::fmt::Debug for ImpersonationAuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ImpersonationAuditLog").finish()
+    }
+}
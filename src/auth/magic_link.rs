@@ -0,0 +1,191 @@
+//! # Magic Links
+//!
+//! Passwordless login: a single-use token with a short expiration is sent
+//! by email as a link, and exchanged for a JWT when the link is opened.
+//!
+//! Tokens are kept in the application cache, hashed, so a persistent cache
+//! (such as Redis) is required for links to survive restarts.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::auth::magic_link::MagicLinks;
+//! use loco_rs::controller::extractor::auth::MagicLinkLogin;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct MagicLinkParams {
+//!     email: String,
+//! }
+//!
+//! async fn request_link(
+//!     State(ctx): State<AppContext>,
+//!     Json(params): Json<MagicLinkParams>,
+//! ) -> Result<Response> {
+//!     // look up the user pid by email, then:
+//!     let pid = "user-pid";
+//!     MagicLinks::from_context(&ctx)
+//!         .send(&ctx, &params.email, pid, "https://example.com/auth/magic-link")
+//!         .await?;
+//!     format::empty()
+//! }
+//!
+//! // GET /auth/magic-link?token=...
+//! async fn login(login: MagicLinkLogin) -> Result<Response> {
+//!     format::json(serde_json::json!({ "token": login.jwt, "pid": login.pid }))
+//! }
+//! ```
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+
+use crate::{
+    app::AppContext,
+    cache::Cache,
+    hash,
+    mailer::{Email, Mailer},
+    Error, Result,
+};
+
+/// Default lifetime of magic link tokens
+const DEFAULT_EXPIRATION: Duration = Duration::from_secs(15 * 60);
+/// Length of the generated tokens
+const TOKEN_LENGTH: usize = 48;
+
+/// Issues and verifies magic link tokens.
+pub struct MagicLinks {
+    cache: Arc<Cache>,
+    expiration: Duration,
+}
+
+impl MagicLinks {
+    #[must_use]
+    pub fn new(cache: Arc<Cache>, expiration: Duration) -> Self {
+        Self { cache, expiration }
+    }
+
+    /// Creates a new [`MagicLinks`] storing tokens in the application cache,
+    /// valid for 15 minutes.
+    #[must_use]
+    pub fn from_context(ctx: &AppContext) -> Self {
+        Self::new(ctx.cache.clone(), DEFAULT_EXPIRATION)
+    }
+
+    /// Sets how long tokens stay valid.
+    #[must_use]
+    pub fn with_expiration(mut self, expiration: Duration) -> Self {
+        self.expiration = expiration;
+        self
+    }
+
+    fn cache_key(token: &str) -> String {
+        format!("magic_link:{}", hash::hash_token(token))
+    }
+
+    /// Creates a token logging in the user with the given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the token could not be stored.
+    pub async fn create(&self, pid: &str) -> Result<String> {
+        let token = hash::random_string(TOKEN_LENGTH);
+        self.cache
+            .insert_with_expiry(&Self::cache_key(&token), pid, self.expiration)
+            .await?;
+        Ok(token)
+    }
+
+    /// Creates a token and emails the link to `to`. The token is appended to
+    /// `url` as the `token` query parameter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the token could not be stored or the email
+    /// could not be queued.
+    pub async fn send(&self, ctx: &AppContext, to: &str, pid: &str, url: &str) -> Result<()> {
+        let token = self.create(pid).await?;
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let link = format!("{url}{separator}token={token}");
+        let minutes = self.expiration.as_secs() / 60;
+
+        MagicLinkMailer::mail(
+            ctx,
+            &Email {
+                to: to.to_string(),
+                subject: "Your sign-in link".to_string(),
+                text: format!(
+                    "Open this link to sign in: {link}\n\nThe link expires in {minutes} \
+                     minutes and can only be used once."
+                ),
+                html: format!(
+                    "<p><a href=\"{link}\">Sign in</a></p><p>The link expires in {minutes} \
+                     minutes and can only be used once.</p>"
+                ),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Verifies a token, returning the `pid` it was created for. A token can
+    /// only be verified once.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthorized`] when the token is unknown, expired or
+    /// was already used.
+    pub async fn verify(&self, token: &str) -> Result<String> {
+        // taken in a single step, so that concurrent requests with the same
+        // link can not both log in
+        self.cache
+            .take::<String>(&Self::cache_key(token))
+            .await?
+            .ok_or_else(|| Error::Unauthorized("magic link is not valid".to_string()))
+    }
+}
+
+/// The mailer sending magic links, using the default mailer options.
+pub struct MagicLinkMailer;
+
+#[async_trait]
+impl Mailer for MagicLinkMailer {}
+
+#[cfg(all(test, feature = "cache_inmem"))]
+mod tests {
+    use super::*;
+    use crate::{cache::drivers::inmem, config::InMemCacheConfig};
+
+    fn magic_links() -> MagicLinks {
+        let cache = inmem::new(&InMemCacheConfig {
+            max_capacity: 32 * 1024 * 1024,
+        });
+        MagicLinks::new(Arc::new(cache), DEFAULT_EXPIRATION)
+    }
+
+    #[tokio::test]
+    async fn can_verify_once() {
+        let links = magic_links();
+        let token = links.create("pid").await.unwrap();
+
+        assert_eq!(links.verify(&token).await.unwrap(), "pid");
+        assert!(matches!(
+            links.verify(&token).await,
+            Err(Error::Unauthorized(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn concurrent_verifications_succeed_once() {
+        let links = magic_links();
+        let token = links.create("pid").await.unwrap();
+
+        let (first, second) = tokio::join!(links.verify(&token), links.verify(&token));
+        assert!(first.is_ok() != second.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reject_unknown_token() {
+        let links = magic_links();
+
+        assert!(links.verify("unknown").await.is_err());
+    }
+}
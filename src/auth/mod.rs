@@ -1,2 +1,16 @@
 #[cfg(feature = "auth_jwt")]
+pub mod impersonation;
+#[cfg(feature = "auth_jwks")]
+pub mod jwks;
+#[cfg(feature = "auth_jwt")]
 pub mod jwt;
+#[cfg(feature = "auth_jwt")]
+pub mod magic_link;
+#[cfg(feature = "auth_oauth2")]
+pub mod oauth2;
+#[cfg(feature = "auth_jwt")]
+pub mod refresh;
+#[cfg(feature = "auth_jwt")]
+pub mod validator;
+#[cfg(feature = "auth_webauthn")]
+pub mod webauthn;
@@ -0,0 +1,501 @@
+//! # OAuth2 / OpenID Connect Login
+//!
+//! Logs users in with an external provider (Google, GitHub or any OpenID
+//! Connect provider) using the authorization code flow with PKCE. The
+//! `state` and PKCE verifier are kept in the [`Session`], so the `session`
+//! middleware has to be enabled.
+//!
+//! Providers are configured under `auth.oauth2.providers` (see
+//! [`crate::config::OAuth2`]). The quickest way to use them is to add the
+//! [`routes`] to the application, which redirect to the provider on
+//! `GET /auth/oauth2/{provider}` and, on the callback, upsert the user with
+//! [`crate::model::OAuth2Authenticable`] and return a JWT.
+//!
+//! # Example
+//! A custom callback handler, for example to log users in with the session
+//! instead of a JWT:
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::auth::oauth2::{CallbackParams, OAuth2Client};
+//! use loco_rs::session::Session;
+//!
+//! async fn callback(
+//!     State(ctx): State<AppContext>,
+//!     Path(provider): Path<String>,
+//!     session: Session,
+//!     Query(params): Query<CallbackParams>,
+//! ) -> Result<Response> {
+//!     let client = OAuth2Client::from_context(&ctx, &provider).await?;
+//!     let profile = client.callback(&session, &params).await?;
+//!     session.renew();
+//!     session.insert("email", &profile.email)?;
+//!     format::redirect("/")
+//! }
+//! ```
+use std::sync::OnceLock;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app::AppContext,
+    config::{OAuth2Provider, OAuth2ProviderKind},
+    hash,
+    session::Session,
+    Error, Result,
+};
+
+/// Endpoints discovered from OpenID Connect issuers, keyed by issuer.
+static DISCOVERED: OnceLock<DashMap<String, Endpoints>> = OnceLock::new();
+
+/// Length of the generated `state` values
+const STATE_LENGTH: usize = 32;
+/// Length of the generated PKCE verifiers
+const VERIFIER_LENGTH: usize = 64;
+
+/// The user profile returned by a provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2Profile {
+    /// The configured provider name
+    pub provider: String,
+    /// The user ID at the provider, stable across logins
+    pub subject: String,
+    pub email: Option<String>,
+    /// Whether the provider verified the email address
+    pub email_verified: bool,
+    pub name: Option<String>,
+    /// The raw user info returned by the provider
+    pub raw: Value,
+}
+
+/// The query parameters of the provider callback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallbackParams {
+    pub code: String,
+    pub state: String,
+}
+
+/// The `state` and PKCE verifier of a pending authorization.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingAuthorization {
+    state: String,
+    verifier: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Endpoints {
+    #[serde(rename = "authorization_endpoint")]
+    authorize: String,
+    #[serde(rename = "token_endpoint")]
+    token: String,
+    #[serde(rename = "userinfo_endpoint")]
+    userinfo: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// A client for a configured OAuth2 provider.
+#[derive(Debug)]
+pub struct OAuth2Client {
+    name: String,
+    config: OAuth2Provider,
+    endpoints: Endpoints,
+    http: reqwest::Client,
+}
+
+impl OAuth2Client {
+    /// Creates a client for a provider. The endpoints of OpenID Connect
+    /// providers are discovered once and cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the OpenID Connect discovery fails.
+    pub async fn new(name: &str, config: &OAuth2Provider) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .user_agent("loco.rs")
+            .build()
+            .map_err(Error::wrap)?;
+
+        let endpoints = match &config.kind {
+            OAuth2ProviderKind::Google => discover(&http, "https://accounts.google.com").await?,
+            OAuth2ProviderKind::GitHub => Endpoints {
+                authorize: "https://github.com/login/oauth/authorize".to_string(),
+                token: "https://github.com/login/oauth/access_token".to_string(),
+                userinfo: "https://api.github.com/user".to_string(),
+            },
+            OAuth2ProviderKind::Oidc { issuer } => discover(&http, issuer).await?,
+            OAuth2ProviderKind::Custom {
+                auth_url,
+                token_url,
+                userinfo_url,
+            } => Endpoints {
+                authorize: auth_url.clone(),
+                token: token_url.clone(),
+                userinfo: userinfo_url.clone(),
+            },
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            config: config.clone(),
+            endpoints,
+            http,
+        })
+    }
+
+    /// Creates a client for a provider of the application configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NotFound`] when the provider is not configured, or an
+    /// error when the OpenID Connect discovery fails.
+    pub async fn from_context(ctx: &AppContext, name: &str) -> Result<Self> {
+        Self::new(name, ctx.config.get_oauth2_provider(name)?).await
+    }
+
+    fn session_key(&self) -> String {
+        format!("_oauth2:{}", self.name)
+    }
+
+    fn scopes(&self) -> String {
+        if !self.config.scopes.is_empty() {
+            return self.config.scopes.join(" ");
+        }
+        match self.config.kind {
+            OAuth2ProviderKind::GitHub => "read:user user:email".to_string(),
+            OAuth2ProviderKind::Custom { .. } => String::new(),
+            OAuth2ProviderKind::Google | OAuth2ProviderKind::Oidc { .. } => {
+                "openid email profile".to_string()
+            }
+        }
+    }
+
+    /// Starts an authorization, returning the provider URL to redirect the
+    /// user to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the authorization could not be stored in the
+    /// session or the authorization URL is not valid.
+    pub fn authorize(&self, session: &Session) -> Result<String> {
+        let pending = PendingAuthorization {
+            state: hash::random_string(STATE_LENGTH),
+            verifier: hash::random_string(VERIFIER_LENGTH),
+        };
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(pending.verifier.as_bytes()));
+
+        let url = reqwest::Url::parse_with_params(
+            &self.endpoints.authorize,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.scopes().as_str()),
+                ("state", pending.state.as_str()),
+                ("code_challenge", challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(Error::wrap)?;
+
+        session.insert(&self.session_key(), &pending)?;
+        Ok(url.to_string())
+    }
+
+    /// Completes an authorization: checks the `state`, exchanges the code
+    /// for an access token and fetches the user profile.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthorized`] when no authorization is pending or
+    /// the `state` does not match, and an error when the provider requests
+    /// fail.
+    pub async fn callback(
+        &self,
+        session: &Session,
+        params: &CallbackParams,
+    ) -> Result<OAuth2Profile> {
+        let pending = session
+            .remove(&self.session_key())
+            .and_then(|value| serde_json::from_value::<PendingAuthorization>(value).ok())
+            .ok_or_else(|| Error::Unauthorized("no pending oauth2 authorization".to_string()))?;
+
+        if !hash::constant_time_eq(pending.state.as_bytes(), params.state.as_bytes()) {
+            return Err(Error::Unauthorized("oauth2 state mismatch".to_string()));
+        }
+
+        let token: TokenResponse = self
+            .http
+            .post(&self.endpoints.token)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", params.code.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("code_verifier", pending.verifier.as_str()),
+            ])
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(Error::wrap)?
+            .json()
+            .await
+            .map_err(Error::wrap)?;
+
+        let raw = self
+            .get_json(&self.endpoints.userinfo, &token.access_token)
+            .await?;
+
+        match self.config.kind {
+            OAuth2ProviderKind::GitHub => self.github_profile(raw, &token.access_token).await,
+            _ => self.oidc_profile(raw),
+        }
+    }
+
+    async fn get_json(&self, url: &str, access_token: &str) -> Result<Value> {
+        self.http
+            .get(url)
+            .bearer_auth(access_token)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(Error::wrap)?
+            .json()
+            .await
+            .map_err(Error::wrap)
+    }
+
+    fn oidc_profile(&self, raw: Value) -> Result<OAuth2Profile> {
+        Ok(OAuth2Profile {
+            provider: self.name.clone(),
+            subject: string_field(&raw, "sub")
+                .filter(|sub| !sub.is_empty())
+                .ok_or_else(|| missing_subject("sub"))?,
+            email: string_field(&raw, "email"),
+            email_verified: raw["email_verified"].as_bool().unwrap_or(false),
+            name: string_field(&raw, "name"),
+            raw,
+        })
+    }
+
+    /// GitHub is not an OpenID Connect provider: the user ID is numeric and
+    /// the email is only included when public, otherwise it is looked up in
+    /// the user emails.
+    async fn github_profile(&self, raw: Value, access_token: &str) -> Result<OAuth2Profile> {
+        let mut email = string_field(&raw, "email");
+        let mut email_verified = false;
+
+        let emails = self
+            .get_json("https://api.github.com/user/emails", access_token)
+            .await?;
+        if let Some(primary) = emails
+            .as_array()
+            .and_then(|emails| emails.iter().find(|e| e["primary"].as_bool() == Some(true)))
+        {
+            email = string_field(primary, "email");
+            email_verified = primary["verified"].as_bool().unwrap_or(false);
+        }
+
+        Ok(OAuth2Profile {
+            provider: self.name.clone(),
+            subject: raw["id"]
+                .as_u64()
+                .map(|id| id.to_string())
+                .ok_or_else(|| missing_subject("id"))?,
+            email,
+            email_verified,
+            name: string_field(&raw, "name").or_else(|| string_field(&raw, "login")),
+            raw,
+        })
+    }
+}
+
+/// A profile without user ID can not be told apart from the others, it must
+/// not be linked to any user.
+fn missing_subject(field: &str) -> Error {
+    Error::Unauthorized(format!("oauth2 profile has no `{field}`"))
+}
+
+fn string_field(value: &Value, field: &str) -> Option<String> {
+    value[field].as_str().map(ToString::to_string)
+}
+
+async fn discover(http: &reqwest::Client, issuer: &str) -> Result<Endpoints> {
+    let discovered = DISCOVERED.get_or_init(DashMap::new);
+    if let Some(endpoints) = discovered.get(issuer) {
+        return Ok(endpoints.clone());
+    }
+
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+    let endpoints: Endpoints = http
+        .get(&url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(Error::wrap)?
+        .json()
+        .await
+        .map_err(Error::wrap)?;
+
+    discovered.insert(issuer.to_string(), endpoints.clone());
+    Ok(endpoints)
+}
+
+/// Routes logging users in with the configured providers:
+/// * `GET /auth/oauth2/{provider}` redirects to the provider
+/// * `GET /auth/oauth2/{provider}/callback` upserts the user and returns a
+///   JWT
+#[cfg(feature = "with-db")]
+#[must_use]
+pub fn routes<T>() -> crate::controller::Routes
+where
+    T: crate::model::OAuth2Authenticable + Send + Sync + 'static,
+{
+    use axum::routing::get;
+
+    crate::controller::Routes::new()
+        .prefix("/auth/oauth2")
+        .add("/{provider}", get(handlers::authorize))
+        .add("/{provider}/callback", get(handlers::callback::<T>))
+}
+
+#[cfg(feature = "with-db")]
+mod handlers {
+    use axum::{
+        extract::{Path, Query, State},
+        response::Response,
+    };
+    use serde::{Deserialize, Serialize};
+
+    use super::{CallbackParams, OAuth2Client};
+    use crate::{
+        app::AppContext, auth::jwt::JWT, controller::format, model::OAuth2Authenticable,
+        session::Session, Error, Result,
+    };
+
+    /// The response of a successful login.
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct LoginResponse {
+        pub token: String,
+        pub pid: String,
+    }
+
+    pub async fn authorize(
+        State(ctx): State<AppContext>,
+        Path(provider): Path<String>,
+        session: Session,
+    ) -> Result<Response> {
+        let client = OAuth2Client::from_context(&ctx, &provider).await?;
+        format::redirect(&client.authorize(&session)?)
+    }
+
+    pub async fn callback<T: OAuth2Authenticable>(
+        State(ctx): State<AppContext>,
+        Path(provider): Path<String>,
+        session: Session,
+        Query(params): Query<CallbackParams>,
+    ) -> Result<Response> {
+        let client = OAuth2Client::from_context(&ctx, &provider).await?;
+        let profile = client.callback(&session, &params).await?;
+        let user = T::upsert_with_oauth2(&ctx.db, &profile).await?;
+        // a new session ID once logged in, against session fixation
+        session.renew();
+
+        let jwt_config = ctx.config.get_jwt_config()?;
+        let pid = user.claims_key();
+        let token = JWT::from_context(&ctx)?
+            .generate_token(jwt_config.expiration, pid.clone(), serde_json::Map::new())
+            .map_err(Error::wrap)?;
+
+        format::json(LoginResponse { token, pid })
+    }
+}
+
+#[cfg(feature = "with-db")]
+pub use handlers::LoginResponse;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionData;
+
+    fn github() -> OAuth2Provider {
+        OAuth2Provider {
+            kind: OAuth2ProviderKind::GitHub,
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_url: "http://localhost:5150/callback".to_string(),
+            scopes: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn can_build_authorize_url() {
+        let client = OAuth2Client::new("github", &github()).await.unwrap();
+        let session = Session::new(None, SessionData::new());
+
+        let url = reqwest::Url::parse(&client.authorize(&session).unwrap()).unwrap();
+        let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.host_str(), Some("github.com"));
+        assert_eq!(params["client_id"], "client");
+        assert_eq!(params["scope"], "read:user user:email");
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let pending: PendingAuthorization = session.get("_oauth2:github").unwrap().unwrap();
+        assert_eq!(params["state"], pending.state);
+        assert_eq!(
+            params["code_challenge"],
+            URL_SAFE_NO_PAD.encode(Sha256::digest(pending.verifier.as_bytes()))
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_state_mismatch() {
+        let client = OAuth2Client::new("github", &github()).await.unwrap();
+        let session = Session::new(None, SessionData::new());
+        client.authorize(&session).unwrap();
+
+        let params = CallbackParams {
+            code: "code".to_string(),
+            state: "forged".to_string(),
+        };
+        assert!(matches!(
+            client.callback(&session, &params).await,
+            Err(Error::Unauthorized(_))
+        ));
+        // the pending authorization can not be retried
+        assert!(session.get::<Value>("_oauth2:github").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_profiles_without_subject() {
+        let client = OAuth2Client::new("github", &github()).await.unwrap();
+
+        let profile = client
+            .oidc_profile(serde_json::json!({"sub": "123", "email": "user@example.com"}))
+            .unwrap();
+        assert_eq!(profile.subject, "123");
+        for raw in [
+            serde_json::json!({"email": "user@example.com"}),
+            serde_json::json!({"sub": ""}),
+        ] {
+            assert!(matches!(
+                client.oidc_profile(raw),
+                Err(Error::Unauthorized(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! # Refresh Tokens
+//!
+//! Issues short-lived access tokens (JWT) together with long-lived, opaque
+//! refresh tokens that can be exchanged for a new pair.
+//!
+//! Refresh tokens are rotated on every use: the presented token is marked as
+//! used and a new one is issued in the same *family* (all the tokens that
+//! descend from the same login). Presenting an already used token means it
+//! leaked, so the whole family is revoked and the user has to log in again.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::auth::refresh::{RefreshTokenParams, RefreshTokens};
+//!
+//! async fn refresh(
+//!     State(ctx): State<AppContext>,
+//!     Json(params): Json<RefreshTokenParams>,
+//! ) -> Result<Response> {
+//!     let tokens = RefreshTokens::from_context(&ctx)?;
+//!     format::json(tokens.refresh(&params.refresh_token).await?)
+//! }
+//! ```
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use jsonwebtoken::get_current_timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::jwt::JWT;
+use crate::{app::AppContext, cache::Cache, config::JWT as JWTConfig, hash, Error, Result};
+
+/// Length of the generated refresh tokens
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+/// The state of an issued refresh token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshTokenRecord {
+    /// The user pid the token was issued for
+    pub pid: String,
+    /// The family of the token, shared by all tokens rotated from the same
+    /// login
+    pub family: String,
+    /// Roles carried over to the refreshed access tokens
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Permissions carried over to the refreshed access tokens
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// Custom claims carried over to the refreshed access tokens
+    pub claims: Map<String, Value>,
+    /// Expiration as a unix timestamp (seconds)
+    pub expires_at: u64,
+    /// Set once the token was exchanged for a new pair
+    pub used: bool,
+}
+
+/// Storage for refresh token records and revoked token families.
+///
+/// The tokens are identified by their hash, from [`hash::hash_token`], so
+/// that the store does not hold usable tokens.
+#[async_trait]
+pub trait RefreshTokenStore: Send + Sync {
+    /// Saves (or replaces) the record of a refresh token.
+    async fn save(&self, token_hash: &str, record: &RefreshTokenRecord) -> Result<()>;
+
+    /// Finds the record of a refresh token.
+    async fn find(&self, token_hash: &str) -> Result<Option<RefreshTokenRecord>>;
+
+    /// Marks a refresh token as used and returns its record as it was
+    /// before, atomically: of concurrent calls, a single one gets the
+    /// record of an unused token.
+    async fn consume(&self, token_hash: &str) -> Result<Option<RefreshTokenRecord>>;
+
+    /// Revokes every token of a family. `ttl` is how long the revocation has
+    /// to be kept, after which all tokens of the family are expired anyway.
+    async fn revoke_family(&self, family: &str, ttl: Duration) -> Result<()>;
+
+    /// Returns `true` when the family was revoked.
+    async fn is_family_revoked(&self, family: &str) -> Result<bool>;
+}
+
+/// A [`RefreshTokenStore`] backed by the application [`Cache`].
+///
+/// Note that a persistent cache (such as Redis) is required for refresh
+/// tokens to survive restarts, and that the `Null` cache does not store
+/// anything.
+pub struct CacheRefreshTokenStore {
+    cache: Arc<Cache>,
+}
+
+impl CacheRefreshTokenStore {
+    #[must_use]
+    pub fn new(cache: Arc<Cache>) -> Self {
+        Self { cache }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenStore for CacheRefreshTokenStore {
+    async fn save(&self, token_hash: &str, record: &RefreshTokenRecord) -> Result<()> {
+        let ttl = record.expires_at.saturating_sub(get_current_timestamp());
+        self.cache
+            .insert_with_expiry(
+                &format!("refresh_token:{token_hash}"),
+                record,
+                Duration::from_secs(ttl),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<RefreshTokenRecord>> {
+        Ok(self
+            .cache
+            .get(&format!("refresh_token:{token_hash}"))
+            .await?)
+    }
+
+    /// Takes the record out of the cache, then saves it back as used: a
+    /// concurrent call in between finds no record, and is rejected as well.
+    async fn consume(&self, token_hash: &str) -> Result<Option<RefreshTokenRecord>> {
+        let Some(record) = self
+            .cache
+            .take::<RefreshTokenRecord>(&format!("refresh_token:{token_hash}"))
+            .await?
+        else {
+            return Ok(None);
+        };
+        self.save(
+            token_hash,
+            &RefreshTokenRecord {
+                used: true,
+                ..record.clone()
+            },
+        )
+        .await?;
+        Ok(Some(record))
+    }
+
+    async fn revoke_family(&self, family: &str, ttl: Duration) -> Result<()> {
+        self.cache
+            .insert_with_expiry(&format!("refresh_token_family:{family}"), &true, ttl)
+            .await?;
+        Ok(())
+    }
+
+    async fn is_family_revoked(&self, family: &str) -> Result<bool> {
+        Ok(self
+            .cache
+            .contains_key(&format!("refresh_token_family:{family}"))
+            .await?)
+    }
+}
+
+/// A pair of access and refresh tokens, ready to be returned as a JSON
+/// response.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// Lifetime of the access token, in seconds
+    pub expires_in: u64,
+}
+
+/// The request body of a refresh endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshTokenParams {
+    pub refresh_token: String,
+}
+
+/// Issues, rotates and revokes refresh tokens.
+pub struct RefreshTokens {
+    jwt: Arc<JWT>,
+    access_expiration: u64,
+    refresh_expiration: u64,
+    store: Arc<dyn RefreshTokenStore>,
+}
+
+impl RefreshTokens {
+    /// Creates a new [`RefreshTokens`], issuing access tokens with `jwt`.
+    #[must_use]
+    pub fn new(
+        jwt: impl Into<Arc<JWT>>,
+        access_expiration: u64,
+        refresh_expiration: u64,
+        store: Arc<dyn RefreshTokenStore>,
+    ) -> Self {
+        Self {
+            jwt: jwt.into(),
+            access_expiration,
+            refresh_expiration,
+            store,
+        }
+    }
+
+    /// Creates a new [`RefreshTokens`] from the `auth.jwt` configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `auth.jwt.refresh_token` is not configured or
+    /// the JWT keys could not be loaded.
+    pub fn from_config(config: &JWTConfig, store: Arc<dyn RefreshTokenStore>) -> Result<Self> {
+        let refresh = config
+            .refresh_token
+            .as_ref()
+            .ok_or_else(|| Error::string("refresh token not configured"))?;
+
+        Ok(Self::new(
+            JWT::from_config(config)?,
+            config.expiration,
+            refresh.expiration,
+            store,
+        ))
+    }
+
+    /// Creates a new [`RefreshTokens`] from the application configuration,
+    /// storing tokens in the application cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `auth.jwt.refresh_token` is not configured or
+    /// the JWT keys could not be loaded.
+    pub fn from_context(ctx: &AppContext) -> Result<Self> {
+        let config = ctx.config.get_jwt_config()?;
+        let refresh = config
+            .refresh_token
+            .as_ref()
+            .ok_or_else(|| Error::string("refresh token not configured"))?;
+
+        Ok(Self::new(
+            JWT::from_context(ctx)?,
+            config.expiration,
+            refresh.expiration,
+            Arc::new(CacheRefreshTokenStore::new(ctx.cache.clone())),
+        ))
+    }
+
+    /// Issues a new token pair for a freshly authenticated user, starting a
+    /// new token family. The roles and permissions are granted to the
+    /// access tokens of the family, like with
+    /// [`JWT::generate_token_with_roles`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the access token could not be generated or the
+    /// refresh token could not be stored.
+    pub async fn issue(
+        &self,
+        pid: &str,
+        roles: Vec<String>,
+        permissions: Vec<String>,
+        claims: Map<String, Value>,
+    ) -> Result<TokenPair> {
+        self.issue_in_family(RefreshTokenRecord {
+            pid: pid.to_string(),
+            family: hash::random_string(REFRESH_TOKEN_LENGTH),
+            roles,
+            permissions,
+            claims,
+            expires_at: 0,
+            used: false,
+        })
+        .await
+    }
+
+    /// Exchanges a refresh token for a new token pair. The presented token
+    /// can not be used again.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthorized`] when the token is unknown, expired,
+    /// revoked or already used.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<TokenPair> {
+        let record = self
+            .store
+            .consume(&hash::hash_token(refresh_token))
+            .await?
+            .ok_or_else(invalid_token)?;
+        self.check_valid(&record).await?;
+
+        if record.used {
+            tracing::warn!(
+                pid = %record.pid,
+                "refresh token reuse detected, revoking the token family"
+            );
+            self.store
+                .revoke_family(&record.family, Duration::from_secs(self.refresh_expiration))
+                .await?;
+            return Err(invalid_token());
+        }
+
+        self.issue_in_family(record).await
+    }
+
+    /// Revokes a refresh token along with its family, for example on logout.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthorized`] when the token is unknown, expired or
+    /// already revoked.
+    pub async fn revoke(&self, refresh_token: &str) -> Result<()> {
+        let record = self
+            .store
+            .find(&hash::hash_token(refresh_token))
+            .await?
+            .ok_or_else(invalid_token)?;
+        self.check_valid(&record).await?;
+        self.store
+            .revoke_family(&record.family, Duration::from_secs(self.refresh_expiration))
+            .await
+    }
+
+    async fn check_valid(&self, record: &RefreshTokenRecord) -> Result<()> {
+        if record.expires_at <= get_current_timestamp()
+            || self.store.is_family_revoked(&record.family).await?
+        {
+            return Err(invalid_token());
+        }
+        Ok(())
+    }
+
+    /// Issues an access token and a new refresh token, of the user, family,
+    /// roles and claims of `record`. The new refresh token gets a fresh
+    /// expiration and is unused, whatever the ones of `record`.
+    async fn issue_in_family(&self, record: RefreshTokenRecord) -> Result<TokenPair> {
+        let access_token = self
+            .jwt
+            .generate_token_with_roles(
+                self.access_expiration,
+                record.pid.clone(),
+                record.roles.clone(),
+                record.permissions.clone(),
+                record.claims.clone(),
+            )
+            .map_err(Error::wrap)?;
+
+        let refresh_token = hash::random_string(REFRESH_TOKEN_LENGTH);
+        let record = RefreshTokenRecord {
+            expires_at: get_current_timestamp().saturating_add(self.refresh_expiration),
+            used: false,
+            ..record
+        };
+        self.store
+            .save(&hash::hash_token(&refresh_token), &record)
+            .await?;
+
+        Ok(TokenPair {
+            access_token,
+            refresh_token,
+            token_type: "Bearer".to_string(),
+            expires_in: self.access_expiration,
+        })
+    }
+}
+
+fn invalid_token() -> Error {
+    Error::Unauthorized("refresh token is not valid".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        tokens: Mutex<HashMap<String, RefreshTokenRecord>>,
+        revoked: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl RefreshTokenStore for MemoryStore {
+        async fn save(&self, token: &str, record: &RefreshTokenRecord) -> Result<()> {
+            self.tokens
+                .lock()
+                .unwrap()
+                .insert(token.to_string(), record.clone());
+            Ok(())
+        }
+
+        async fn find(&self, token: &str) -> Result<Option<RefreshTokenRecord>> {
+            Ok(self.tokens.lock().unwrap().get(token).cloned())
+        }
+
+        async fn consume(&self, token: &str) -> Result<Option<RefreshTokenRecord>> {
+            Ok(self
+                .tokens
+                .lock()
+                .unwrap()
+                .get_mut(token)
+                .map(|record| RefreshTokenRecord {
+                    used: std::mem::replace(&mut record.used, true),
+                    ..record.clone()
+                }))
+        }
+
+        async fn revoke_family(&self, family: &str, _ttl: Duration) -> Result<()> {
+            self.revoked.lock().unwrap().push(family.to_string());
+            Ok(())
+        }
+
+        async fn is_family_revoked(&self, family: &str) -> Result<bool> {
+            Ok(self.revoked.lock().unwrap().iter().any(|f| f == family))
+        }
+    }
+
+    fn refresh_tokens() -> RefreshTokens {
+        RefreshTokens::new(
+            JWT::new("PqRwLF2rhHe8J22oBeHy"),
+            60,
+            3600,
+            Arc::new(MemoryStore::default()),
+        )
+    }
+
+    #[tokio::test]
+    async fn can_issue_and_refresh() {
+        let tokens = refresh_tokens();
+        let mut claims = Map::new();
+        claims.insert("role".to_string(), "admin".into());
+
+        let pair = tokens
+            .issue(
+                "pid",
+                vec!["admin".to_string()],
+                vec!["posts:write".to_string()],
+                claims,
+            )
+            .await
+            .unwrap();
+        let refreshed = tokens.refresh(&pair.refresh_token).await.unwrap();
+
+        assert_ne!(pair.refresh_token, refreshed.refresh_token);
+        assert_eq!(refreshed.expires_in, 60);
+        let claims = tokens.jwt.validate(&refreshed.access_token).unwrap().claims;
+        assert_eq!(claims.pid, "pid");
+        assert!(claims.has_role("admin"));
+        assert!(claims.has_permission("posts:write"));
+        assert_eq!(claims.claims.get("role"), Some(&Value::from("admin")));
+    }
+
+    #[tokio::test]
+    async fn reuse_revokes_family() {
+        let tokens = refresh_tokens();
+
+        let pair = tokens
+            .issue("pid", vec![], vec![], Map::new())
+            .await
+            .unwrap();
+        let refreshed = tokens.refresh(&pair.refresh_token).await.unwrap();
+
+        assert!(tokens.refresh(&pair.refresh_token).await.is_err());
+        // the legitimate, rotated token is revoked as well
+        assert!(tokens.refresh(&refreshed.refresh_token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrent_refreshes_rotate_once() {
+        let tokens = RefreshTokens::new(
+            JWT::new("PqRwLF2rhHe8J22oBeHy"),
+            60,
+            3600,
+            Arc::new(CacheRefreshTokenStore::new(Arc::new(
+                crate::cache::drivers::inmem::new(&crate::config::InMemCacheConfig {
+                    max_capacity: 100,
+                }),
+            ))),
+        );
+
+        let pair = tokens
+            .issue("pid", vec![], vec![], Map::new())
+            .await
+            .unwrap();
+        let (first, second) = tokio::join!(
+            tokens.refresh(&pair.refresh_token),
+            tokens.refresh(&pair.refresh_token)
+        );
+        assert!(first.is_ok() != second.is_ok());
+    }
+
+    #[tokio::test]
+    async fn stores_token_hashes() {
+        let store = Arc::new(MemoryStore::default());
+        let tokens = RefreshTokens::new(JWT::new("PqRwLF2rhHe8J22oBeHy"), 60, 3600, store.clone());
+
+        let pair = tokens
+            .issue("pid", vec![], vec![], Map::new())
+            .await
+            .unwrap();
+        let stored = store.tokens.lock().unwrap();
+        assert!(!stored.contains_key(&pair.refresh_token));
+        assert!(stored.contains_key(&hash::hash_token(&pair.refresh_token)));
+    }
+
+    #[tokio::test]
+    async fn can_revoke() {
+        let tokens = refresh_tokens();
+
+        let pair = tokens
+            .issue("pid", vec![], vec![], Map::new())
+            .await
+            .unwrap();
+        tokens.revoke(&pair.refresh_token).await.unwrap();
+
+        assert!(tokens.refresh(&pair.refresh_token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reject_unknown_token() {
+        let tokens = refresh_tokens();
+
+        assert!(matches!(
+            tokens.refresh("unknown").await,
+            Err(Error::Unauthorized(_))
+        ));
+    }
+}
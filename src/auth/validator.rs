@@ -0,0 +1,92 @@
+//! # Token Validators
+//!
+//! Hooks run by the JWT extractors once a token signature and expiration
+//! were validated, to apply app specific checks such as rejecting revoked
+//! tokens (by their `jti`) stored in Redis or in the database.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::auth::{jwt::UserClaims, validator::{TokenValidator, TokenValidators}};
+//!
+//! struct Denylist;
+//!
+//! #[async_trait]
+//! impl TokenValidator for Denylist {
+//!     async fn validate(&self, ctx: &AppContext, claims: &UserClaims) -> Result<()> {
+//!         let Some(jti) = claims.jti() else {
+//!             return Ok(());
+//!         };
+//!         if ctx.cache.contains_key(&format!("revoked_jwt:{jti}")).await? {
+//!             return unauthorized("token was revoked");
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! fn register(ctx: &AppContext) {
+//!     ctx.shared_store.insert(TokenValidators::new().register(Denylist));
+//! }
+//! ```
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::jwt::UserClaims;
+use crate::{app::AppContext, Result};
+
+/// A check run on the claims of every token accepted by the JWT extractors.
+#[async_trait]
+pub trait TokenValidator: Send + Sync {
+    /// Validates the claims of a token whose signature and expiration are
+    /// already verified.
+    ///
+    /// # Errors
+    ///
+    /// Return an error (typically [`crate::Error::Unauthorized`]) to reject
+    /// the request.
+    async fn validate(&self, ctx: &AppContext, claims: &UserClaims) -> Result<()>;
+}
+
+/// The list of [`TokenValidator`]s run by the JWT extractors, in order.
+///
+/// Register it in the application [`crate::app::SharedStore`], for example
+/// in the `after_context` hook.
+#[derive(Clone, Default)]
+pub struct TokenValidators {
+    validators: Vec<Arc<dyn TokenValidator>>,
+}
+
+impl TokenValidators {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a validator to the list.
+    #[must_use]
+    pub fn register(mut self, validator: impl TokenValidator + 'static) -> Self {
+        self.validators.push(Arc::new(validator));
+        self
+    }
+
+    /// Runs all validators, stopping at the first rejection.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first validator rejecting the claims.
+    pub async fn validate(&self, ctx: &AppContext, claims: &UserClaims) -> Result<()> {
+        for validator in &self.validators {
+            validator.validate(ctx, claims).await?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for TokenValidators {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenValidators")
+            .field("validators", &self.validators.len())
+            .finish()
+    }
+}
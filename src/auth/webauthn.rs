@@ -0,0 +1,388 @@
+//! # WebAuthn / Passkeys
+//!
+//! Registers passkeys and logs users in with them. Each ceremony is started
+//! by the server, which returns a challenge for the browser
+//! (`navigator.credentials.create()` or `navigator.credentials.get()`), and
+//! finished with the credential the browser returns. The state of the
+//! pending ceremony is kept in the [`Session`], so the `session` middleware
+//! has to be enabled.
+//!
+//! The relying party is configured under `auth.webauthn` (see
+//! [`crate::config::WebAuthn`]). The quickest way to use passkeys is to add
+//! the [`routes`] to the application, storing passkeys with
+//! [`crate::model::WebAuthnAuthenticable`].
+//!
+//! # Example
+//! A custom registration flow, for example for users logged in with the
+//! session instead of a JWT:
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::auth::webauthn::{Passkeys, RegisterPublicKeyCredential, Uuid};
+//! use loco_rs::session::Session;
+//!
+//! async fn start(State(ctx): State<AppContext>, session: Session) -> Result<Response> {
+//!     let user_id: Uuid = session
+//!         .get("user_id")?
+//!         .ok_or_else(|| Error::Unauthorized("not logged in".to_string()))?;
+//!     let challenge = Passkeys::from_context(&ctx)?
+//!         .start_registration(&session, user_id, "user@example.com", &[])?;
+//!     format::json(challenge)
+//! }
+//!
+//! async fn finish(
+//!     State(ctx): State<AppContext>,
+//!     session: Session,
+//!     Json(credential): Json<RegisterPublicKeyCredential>,
+//! ) -> Result<Response> {
+//!     let _passkey = Passkeys::from_context(&ctx)?.finish_registration(&session, &credential)?;
+//!     // store the passkey of the user
+//!     format::empty_json()
+//! }
+//! ```
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+pub use webauthn_rs::prelude::{
+    AuthenticationResult, CreationChallengeResponse, Passkey, PublicKeyCredential,
+    RegisterPublicKeyCredential, RequestChallengeResponse, Uuid,
+};
+use webauthn_rs::{
+    prelude::{PasskeyAuthentication, PasskeyRegistration, Url},
+    Webauthn, WebauthnBuilder,
+};
+
+use crate::{app::AppContext, config, session::Session, Error, Result};
+
+/// The session key of a pending registration
+const REGISTRATION_KEY: &str = "_webauthn:registration";
+/// The session key of a pending authentication
+const AUTHENTICATION_KEY: &str = "_webauthn:authentication";
+
+/// A pending authentication, with the user it was started for.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingAuthentication {
+    user: String,
+    state: PasskeyAuthentication,
+}
+
+/// Runs the WebAuthn registration and authentication ceremonies.
+pub struct Passkeys {
+    webauthn: Webauthn,
+}
+
+impl Passkeys {
+    /// Creates the relying party from its configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `rp_origin` is not a valid URL or is not on the
+    /// `rp_id` domain.
+    pub fn new(config: &config::WebAuthn) -> Result<Self> {
+        let origin = Url::parse(&config.rp_origin).map_err(Error::wrap)?;
+        let mut builder = WebauthnBuilder::new(&config.rp_id, &origin)
+            .map_err(Error::wrap)?
+            .timeout(Duration::from_secs(config.timeout));
+        if let Some(name) = &config.rp_name {
+            builder = builder.rp_name(name);
+        }
+
+        Ok(Self {
+            webauthn: builder.build().map_err(Error::wrap)?,
+        })
+    }
+
+    /// Creates the relying party of the application configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `auth.webauthn` is not configured or is not
+    /// valid.
+    pub fn from_context(ctx: &AppContext) -> Result<Self> {
+        Self::new(ctx.config.get_webauthn_config()?)
+    }
+
+    /// Starts registering a passkey for a user, returning the challenge to
+    /// pass to `navigator.credentials.create()`. The `existing` passkeys of
+    /// the user are excluded, so an authenticator is not registered twice.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the registration could not be stored in the
+    /// session.
+    pub fn start_registration(
+        &self,
+        session: &Session,
+        user_id: Uuid,
+        username: &str,
+        existing: &[Passkey],
+    ) -> Result<CreationChallengeResponse> {
+        let exclude = existing
+            .iter()
+            .map(|passkey| passkey.cred_id().clone())
+            .collect::<Vec<_>>();
+
+        let (challenge, state) = self
+            .webauthn
+            .start_passkey_registration(
+                user_id,
+                username,
+                username,
+                (!exclude.is_empty()).then_some(exclude),
+            )
+            .map_err(Error::wrap)?;
+
+        session.insert(REGISTRATION_KEY, &state)?;
+        Ok(challenge)
+    }
+
+    /// Completes a registration, returning the passkey to store for the
+    /// user.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthorized`] when no registration is pending or the
+    /// credential does not match its challenge.
+    pub fn finish_registration(
+        &self,
+        session: &Session,
+        credential: &RegisterPublicKeyCredential,
+    ) -> Result<Passkey> {
+        let state = session
+            .remove(REGISTRATION_KEY)
+            .and_then(|value| serde_json::from_value::<PasskeyRegistration>(value).ok())
+            .ok_or_else(|| Error::Unauthorized("no pending passkey registration".to_string()))?;
+
+        self.webauthn
+            .finish_passkey_registration(credential, &state)
+            .map_err(|err| {
+                tracing::debug!(err = %err, "passkey registration failed");
+                Error::Unauthorized("passkey registration failed".to_string())
+            })
+    }
+
+    /// Starts logging in `user` (for example its JWT `pid`) with one of its
+    /// passkeys, returning the challenge to pass to
+    /// `navigator.credentials.get()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthorized`] when the user has no passkey, or an
+    /// error when the authentication could not be stored in the session.
+    pub fn start_authentication(
+        &self,
+        session: &Session,
+        user: &str,
+        passkeys: &[Passkey],
+    ) -> Result<RequestChallengeResponse> {
+        if passkeys.is_empty() {
+            return Err(Error::Unauthorized("no passkey registered".to_string()));
+        }
+
+        let (challenge, state) = self
+            .webauthn
+            .start_passkey_authentication(passkeys)
+            .map_err(Error::wrap)?;
+
+        session.insert(
+            AUTHENTICATION_KEY,
+            &PendingAuthentication {
+                user: user.to_string(),
+                state,
+            },
+        )?;
+        Ok(challenge)
+    }
+
+    /// Completes a login, returning the user the authentication was started
+    /// for along with the result to persist the passkey counter with.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthorized`] when no authentication is pending or
+    /// the credential is not valid.
+    pub fn finish_authentication(
+        &self,
+        session: &Session,
+        credential: &PublicKeyCredential,
+    ) -> Result<(String, AuthenticationResult)> {
+        let pending = session
+            .remove(AUTHENTICATION_KEY)
+            .and_then(|value| serde_json::from_value::<PendingAuthentication>(value).ok())
+            .ok_or_else(|| Error::Unauthorized("no pending passkey login".to_string()))?;
+
+        let result = self
+            .webauthn
+            .finish_passkey_authentication(credential, &pending.state)
+            .map_err(|err| {
+                tracing::debug!(err = %err, "passkey login failed");
+                Error::Unauthorized("passkey login failed".to_string())
+            })?;
+
+        Ok((pending.user, result))
+    }
+}
+
+/// Routes registering passkeys and logging in with them:
+/// * `POST /auth/webauthn/register/start` starts registering a passkey for
+///   the user of the JWT
+/// * `POST /auth/webauthn/register/finish` stores the passkey
+/// * `POST /auth/webauthn/login/start` starts a login, with the `username`
+///   of the JSON body
+/// * `POST /auth/webauthn/login/finish` returns a JWT
+#[cfg(feature = "with-db")]
+#[must_use]
+pub fn routes<T>() -> crate::controller::Routes
+where
+    T: crate::model::WebAuthnAuthenticable + Send + Sync + 'static,
+{
+    use axum::routing::post;
+
+    crate::controller::Routes::new()
+        .prefix("/auth/webauthn")
+        .add("/register/start", post(handlers::start_registration::<T>))
+        .add("/register/finish", post(handlers::finish_registration::<T>))
+        .add("/login/start", post(handlers::start_login::<T>))
+        .add("/login/finish", post(handlers::finish_login::<T>))
+}
+
+#[cfg(feature = "with-db")]
+mod handlers {
+    use axum::{extract::State, response::Response, Json};
+    use serde::{Deserialize, Serialize};
+
+    use super::{Passkeys, RegisterPublicKeyCredential};
+    use crate::{
+        app::AppContext,
+        controller::{
+            extractor::auth::{JWTWithUser, PasskeyLogin},
+            format,
+        },
+        model::WebAuthnAuthenticable,
+        session::Session,
+        Error, Result,
+    };
+
+    /// The body of a login start request.
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct LoginParams {
+        pub username: String,
+    }
+
+    /// The response of a successful login.
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct LoginResponse {
+        pub token: String,
+        pub pid: String,
+    }
+
+    pub async fn start_registration<T: WebAuthnAuthenticable>(
+        State(ctx): State<AppContext>,
+        auth: JWTWithUser<T>,
+        session: Session,
+    ) -> Result<Response> {
+        let passkeys = auth.user.passkeys(&ctx.db).await?;
+        let challenge = Passkeys::from_context(&ctx)?.start_registration(
+            &session,
+            auth.user.webauthn_user_id(),
+            &auth.user.webauthn_username(),
+            &passkeys,
+        )?;
+        format::json(challenge)
+    }
+
+    pub async fn finish_registration<T: WebAuthnAuthenticable>(
+        State(ctx): State<AppContext>,
+        auth: JWTWithUser<T>,
+        session: Session,
+        Json(credential): Json<RegisterPublicKeyCredential>,
+    ) -> Result<Response> {
+        let passkey = Passkeys::from_context(&ctx)?.finish_registration(&session, &credential)?;
+        auth.user.add_passkey(&ctx.db, passkey).await?;
+        format::empty_json()
+    }
+
+    pub async fn start_login<T: WebAuthnAuthenticable>(
+        State(ctx): State<AppContext>,
+        session: Session,
+        Json(params): Json<LoginParams>,
+    ) -> Result<Response> {
+        let user = T::find_by_webauthn_username(&ctx.db, &params.username)
+            .await
+            .map_err(|_| Error::Unauthorized("no passkey registered".to_string()))?;
+        let passkeys = user.passkeys(&ctx.db).await?;
+
+        let challenge = Passkeys::from_context(&ctx)?.start_authentication(
+            &session,
+            &user.claims_key(),
+            &passkeys,
+        )?;
+        format::json(challenge)
+    }
+
+    pub async fn finish_login<T: WebAuthnAuthenticable>(
+        login: PasskeyLogin<T>,
+    ) -> Result<Response> {
+        format::json(LoginResponse {
+            token: login.jwt,
+            pid: login.pid,
+        })
+    }
+}
+
+#[cfg(feature = "with-db")]
+pub use handlers::{LoginParams, LoginResponse};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionData;
+
+    fn passkeys() -> Passkeys {
+        Passkeys::new(&config::WebAuthn {
+            rp_id: "localhost".to_string(),
+            rp_origin: "http://localhost:5150".to_string(),
+            rp_name: Some("loco".to_string()),
+            timeout: 300,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn rejects_origin_outside_rp_id() {
+        assert!(Passkeys::new(&config::WebAuthn {
+            rp_id: "example.com".to_string(),
+            rp_origin: "https://example.org".to_string(),
+            rp_name: None,
+            timeout: 300,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn stores_pending_registration() {
+        let session = Session::new(None, SessionData::new());
+        let challenge = passkeys()
+            .start_registration(&session, Uuid::new_v4(), "user@example.com", &[])
+            .unwrap();
+
+        assert_eq!(challenge.public_key.rp.id, "localhost");
+        assert!(session
+            .get::<serde_json::Value>(REGISTRATION_KEY)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn rejects_login_without_passkeys() {
+        let session = Session::new(None, SessionData::new());
+
+        assert!(matches!(
+            passkeys().start_authentication(&session, "pid", &[]),
+            Err(Error::Unauthorized(_))
+        ));
+        assert!(session
+            .get::<serde_json::Value>(AUTHENTICATION_KEY)
+            .unwrap()
+            .is_none());
+    }
+}
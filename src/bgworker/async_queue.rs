@@ -0,0 +1,202 @@
+//! # Async Queue
+//!
+//! The in-process queue of the `BackgroundAsync` mode: a bounded channel of
+//! jobs, performed by a fixed pool of workers. When the queue is full, its
+//! [`Overflow`] policy blocks the caller, drops the job, or spills it to the
+//! queue provider. On shutdown, the queue stops taking jobs and its workers
+//! drain the queued ones.
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
+
+use crate::{
+    config::{AsyncQueueConfig, Overflow},
+    Error, Result,
+};
+
+/// A job of the queue, ready to be performed.
+pub(crate) type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// What became of a pushed job.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Pushed {
+    /// The job waits for a worker.
+    Queued,
+    /// The queue was full, and the job was dropped.
+    Dropped,
+    /// The queue was full, and the job is to be spilled to the queue
+    /// provider.
+    Full,
+}
+
+/// The bounded queue of the `BackgroundAsync` mode, kept in the shared store
+/// of the context.
+#[derive(Clone)]
+pub struct AsyncQueue {
+    sender: Arc<std::sync::Mutex<Option<mpsc::Sender<Task>>>>,
+    workers: Arc<std::sync::Mutex<Vec<JoinHandle<()>>>>,
+    overflow: Overflow,
+}
+
+impl AsyncQueue {
+    /// Starts the workers of the queue.
+    #[must_use]
+    pub fn start(config: &AsyncQueueConfig) -> Self {
+        let (sender, receiver) = mpsc::channel::<Task>(config.capacity.max(1));
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..config.num_workers.max(1))
+            .map(|_| {
+                let receiver = receiver.clone();
+                tokio::spawn(async move {
+                    loop {
+                        // the lock is released before performing the job
+                        let task = receiver.lock().await.recv().await;
+                        match task {
+                            Some(task) => task.await,
+                            None => break,
+                        }
+                    }
+                })
+            })
+            .collect();
+        Self {
+            sender: Arc::new(std::sync::Mutex::new(Some(sender))),
+            workers: Arc::new(std::sync::Mutex::new(workers)),
+            overflow: config.overflow,
+        }
+    }
+
+    /// The overflow policy of the queue.
+    #[must_use]
+    pub const fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Pushes a job to the queue, applying the overflow policy when the
+    /// queue is full.
+    ///
+    /// # Errors
+    ///
+    /// When the queue was shut down.
+    pub(crate) async fn push(&self, task: Task) -> Result<Pushed> {
+        let closed = || Error::string("async queue is shut down");
+        let sender = self
+            .sender
+            .lock()
+            .map_err(|_| Error::string("async queue lock poisoned"))?
+            .clone()
+            .ok_or_else(closed)?;
+        match self.overflow {
+            Overflow::Block => {
+                sender.send(task).await.map_err(|_| closed())?;
+                Ok(Pushed::Queued)
+            }
+            Overflow::Drop | Overflow::Spill => match sender.try_send(task) {
+                Ok(()) => Ok(Pushed::Queued),
+                Err(mpsc::error::TrySendError::Full(_)) if self.overflow == Overflow::Drop => {
+                    tracing::warn!("async queue is full, dropping the job");
+                    Ok(Pushed::Dropped)
+                }
+                Err(mpsc::error::TrySendError::Full(_)) => Ok(Pushed::Full),
+                Err(mpsc::error::TrySendError::Closed(_)) => Err(closed()),
+            },
+        }
+    }
+
+    /// Stops taking jobs, and waits for the workers to perform the queued
+    /// ones, up to `grace_period`. The jobs still running or queued after it
+    /// are dropped.
+    ///
+    /// # Errors
+    ///
+    /// When the queue lock is poisoned.
+    pub async fn shutdown(&self, grace_period: Duration) -> Result<()> {
+        // the workers stop once the queued jobs are drained
+        self.sender
+            .lock()
+            .map_err(|_| Error::string("async queue lock poisoned"))?
+            .take();
+        let workers = std::mem::take(
+            &mut *self
+                .workers
+                .lock()
+                .map_err(|_| Error::string("async queue lock poisoned"))?,
+        );
+        let drain = futures_util::future::join_all(workers);
+        if tokio::time::timeout(grace_period, drain).await.is_err() {
+            tracing::warn!("async queue shutdown timeout elapsed, dropping the remaining jobs");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn config(num_workers: u32, capacity: usize, overflow: Overflow) -> AsyncQueueConfig {
+        AsyncQueueConfig {
+            num_workers,
+            capacity,
+            overflow,
+        }
+    }
+
+    fn counting(count: &Arc<AtomicUsize>, delay: Duration) -> Task {
+        let count = count.clone();
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            count.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    #[tokio::test]
+    async fn can_drain_jobs_on_shutdown() {
+        let queue = AsyncQueue::start(&config(2, 16, Overflow::Block));
+        let count = Arc::new(AtomicUsize::new(0));
+        for _ in 0..10 {
+            assert_eq!(
+                queue
+                    .push(counting(&count, Duration::from_millis(10)))
+                    .await
+                    .unwrap(),
+                Pushed::Queued
+            );
+        }
+
+        queue.shutdown(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 10);
+        assert!(queue.push(counting(&count, Duration::ZERO)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_apply_overflow_policy() {
+        let count = Arc::new(AtomicUsize::new(0));
+        for (overflow, expected) in [
+            (Overflow::Drop, Pushed::Dropped),
+            (Overflow::Spill, Pushed::Full),
+        ] {
+            let queue = AsyncQueue::start(&config(1, 1, overflow));
+            // one job running, one job queued
+            queue
+                .push(counting(&count, Duration::from_millis(200)))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            queue.push(counting(&count, Duration::ZERO)).await.unwrap();
+
+            assert_eq!(
+                queue.push(counting(&count, Duration::ZERO)).await.unwrap(),
+                expected
+            );
+            queue.shutdown(Duration::from_secs(5)).await.unwrap();
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+}
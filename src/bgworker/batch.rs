@@ -0,0 +1,279 @@
+//! # Job Batches
+//!
+//! Enqueues related jobs together, with callback jobs run once they are
+//! done: `on_success` once every job of the batch completed, and
+//! `on_failure` as soon as one of them failed, for fan-out/fan-in workflows
+//! such as resizing every image of an album before publishing it.
+//! ```rust,ignore
+//! let batch_id = Batch::new()
+//!     .with_job::<ResizeImage, _>(ResizeArgs { image_id: 1 })?
+//!     .with_job::<ResizeImage, _>(ResizeArgs { image_id: 2 })?
+//!     .on_success::<PublishAlbum, _>(PublishArgs { album_id: 42 })?
+//!     .on_failure::<NotifyOwner, _>(NotifyArgs { album_id: 42 })?
+//!     .enqueue(&ctx)
+//!     .await?;
+//! ```
+//!
+//! In the `BackgroundQueue` mode the queue counts the pending jobs of the
+//! batch and enqueues the callbacks, so that the jobs may run on any worker.
+//! The in-process modes run the jobs concurrently, `ForegroundBlocking`
+//! waiting for them and their callbacks before returning. A callback runs at
+//! most once, and an empty batch succeeds right away.
+
+use std::{future::Future, pin::Pin};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tokio::task::JoinSet;
+
+use super::{
+    middleware::{JobInfo, JobMiddlewares},
+    payload, throttle_key, BackgroundWorker, Placement, DEFAULT_QUEUE,
+};
+use crate::{app::AppContext, config::WorkerMode, Result};
+
+type Perform = Box<
+    dyn FnOnce(AppContext, JobMiddlewares) -> Pin<Box<dyn Future<Output = Result<()>> + Send>>
+        + Send,
+>;
+
+/// A job of a batch, or one of its callbacks, as stored by the queue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchJob {
+    /// The class name of the worker
+    pub name: String,
+    /// The serialized arguments of the job
+    pub args: JsonValue,
+    pub queue: String,
+    pub priority: i32,
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throttle_key: Option<String>,
+}
+
+impl BatchJob {
+    /// Where the job is enqueued, as a job of `batch` or as a callback.
+    #[must_use]
+    pub fn placement(&self, batch: Option<&str>) -> Placement {
+        Placement {
+            queue: self.queue.clone(),
+            priority: self.priority,
+            batch: batch.map(ToString::to_string),
+            throttle_key: self.throttle_key.clone(),
+            ..Placement::default()
+        }
+    }
+}
+
+struct Entry {
+    job: BatchJob,
+    perform: Perform,
+}
+
+impl Entry {
+    fn new<W, A>(args: A) -> Result<Self>
+    where
+        W: BackgroundWorker<A> + 'static,
+        A: Send + Sync + Serialize + 'static,
+    {
+        let tags = W::tags();
+        let job = BatchJob {
+            name: W::class_name(),
+            args: serde_json::to_value(&args)?,
+            queue: W::queue_for(&args).unwrap_or_else(|| DEFAULT_QUEUE.to_string()),
+            priority: W::priority_for(&args),
+            tags: if tags.is_empty() { None } else { Some(tags) },
+            throttle_key: throttle_key::<W, A>(&args),
+        };
+        let info = JobInfo {
+            id: None,
+            name: job.name.clone(),
+            queue: W::queue_for(&args),
+            args: job.args.clone(),
+        };
+        Ok(Self {
+            job,
+            perform: Box::new(move |ctx, middlewares| {
+                Box::pin(async move {
+                    let worker = W::build(&ctx);
+                    middlewares.perform(&info, worker.perform(args)).await
+                })
+            }),
+        })
+    }
+}
+
+/// Jobs enqueued together, with the callbacks run once they are done.
+pub struct Batch {
+    id: String,
+    jobs: Vec<Entry>,
+    on_success: Option<Entry>,
+    on_failure: Option<Entry>,
+}
+
+impl Default for Batch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Batch {
+    /// An empty batch, with a new id.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            id: uuid::Uuid::now_v7().to_string(),
+            jobs: vec![],
+            on_success: None,
+            on_failure: None,
+        }
+    }
+
+    /// The id of the batch, set on the queued jobs of the batch.
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Adds a job of the worker `W` to the batch.
+    ///
+    /// # Errors
+    ///
+    /// When the arguments could not be serialized
+    pub fn with_job<W, A>(mut self, args: A) -> Result<Self>
+    where
+        W: BackgroundWorker<A> + 'static,
+        A: Send + Sync + Serialize + 'static,
+    {
+        self.jobs.push(Entry::new::<W, A>(args)?);
+        Ok(self)
+    }
+
+    /// Sets the job run once every job of the batch completed.
+    ///
+    /// # Errors
+    ///
+    /// When the arguments could not be serialized
+    pub fn on_success<W, A>(mut self, args: A) -> Result<Self>
+    where
+        W: BackgroundWorker<A> + 'static,
+        A: Send + Sync + Serialize + 'static,
+    {
+        self.on_success = Some(Entry::new::<W, A>(args)?);
+        Ok(self)
+    }
+
+    /// Sets the job run as soon as a job of the batch failed.
+    ///
+    /// # Errors
+    ///
+    /// When the arguments could not be serialized
+    pub fn on_failure<W, A>(mut self, args: A) -> Result<Self>
+    where
+        W: BackgroundWorker<A> + 'static,
+        A: Send + Sync + Serialize + 'static,
+    {
+        self.on_failure = Some(Entry::new::<W, A>(args)?);
+        Ok(self)
+    }
+
+    /// The jobs of the batch.
+    pub fn jobs(&self) -> impl Iterator<Item = &BatchJob> {
+        self.jobs.iter().map(|entry| &entry.job)
+    }
+
+    /// The job run once every job of the batch completed.
+    #[must_use]
+    pub fn success_callback(&self) -> Option<&BatchJob> {
+        self.on_success.as_ref().map(|entry| &entry.job)
+    }
+
+    /// The job run as soon as a job of the batch failed.
+    #[must_use]
+    pub fn failure_callback(&self) -> Option<&BatchJob> {
+        self.on_failure.as_ref().map(|entry| &entry.job)
+    }
+
+    /// Enqueues the jobs of the batch according to the worker mode,
+    /// returning the id of the batch.
+    ///
+    /// # Errors
+    ///
+    /// When the batch could not be enqueued, or a job of the batch failed in
+    /// the `ForegroundBlocking` mode
+    pub async fn enqueue(mut self, ctx: &AppContext) -> Result<String> {
+        let id = self.id.clone();
+        let middlewares = ctx.shared_store.get::<JobMiddlewares>().unwrap_or_default();
+        match &ctx.config.workers.mode {
+            WorkerMode::BackgroundQueue => {
+                if let Some(queue) = &ctx.queue_provider {
+                    for entry in self
+                        .jobs
+                        .iter_mut()
+                        .chain(self.on_success.iter_mut())
+                        .chain(self.on_failure.iter_mut())
+                    {
+                        let mut info = JobInfo {
+                            id: None,
+                            name: entry.job.name.clone(),
+                            queue: Some(entry.job.queue.clone()),
+                            args: entry.job.args.clone(),
+                        };
+                        middlewares.before_enqueue(&mut info).await?;
+                        entry.job.args = payload::seal(info.args)?;
+                    }
+                    queue.enqueue_batch(&self).await?;
+                } else {
+                    tracing::error!(
+                        "batch: background queue is selected, but queue was not populated in \
+                         context"
+                    );
+                }
+            }
+            WorkerMode::ForegroundBlocking => {
+                self.perform(ctx, &middlewares).await?;
+            }
+            WorkerMode::BackgroundAsync => {
+                let ctx = ctx.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = self.perform(&ctx, &middlewares).await {
+                        tracing::error!(err = err.to_string(), "batch failed");
+                    }
+                });
+            }
+        }
+        Ok(id)
+    }
+
+    /// Performs the jobs of the batch concurrently in the process, then its
+    /// callbacks, returning the first error.
+    async fn perform(self, ctx: &AppContext, middlewares: &JobMiddlewares) -> Result<()> {
+        let mut running = JoinSet::new();
+        for entry in self.jobs {
+            running.spawn((entry.perform)(ctx.clone(), middlewares.clone()));
+        }
+        let mut on_failure = self.on_failure;
+        let mut failure = None;
+        while let Some(joined) = running.join_next().await {
+            let result = joined.unwrap_or_else(|err| Err(crate::Error::string(&err.to_string())));
+            if let Err(err) = result {
+                tracing::error!(
+                    err = err.to_string(),
+                    batch_id = %self.id,
+                    "job of batch failed"
+                );
+                if let Some(callback) = on_failure.take() {
+                    (callback.perform)(ctx.clone(), middlewares.clone()).await?;
+                }
+                failure.get_or_insert(err);
+            }
+        }
+        match failure {
+            Some(err) => Err(err),
+            None => match self.on_success {
+                Some(callback) => (callback.perform)(ctx.clone(), middlewares.clone()).await,
+                None => Ok(()),
+            },
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! # Job Middlewares
+//!
+//! Runs code around every job of every worker: a [`JobMiddleware`] is called
+//! before a job is enqueued, before and after it is performed, and when it
+//! fails, to open tracing spans, propagate a tenant, capture errors or time
+//! the jobs without changing the workers.
+//!
+//! The middlewares of [`crate::app::Hooks::job_middlewares`] run in their
+//! order before a job, and in the reverse order after it. `before_enqueue`
+//! only runs for the jobs enqueued with `perform_later`, `perform_in` and
+//! `perform_at` in the `BackgroundQueue` mode, or spilled to the queue by the
+//! `BackgroundAsync` mode, and may rewrite the arguments
+//! of the job to carry a context to the worker process, which
+//! `before_perform` reads back.
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+use tracing::Instrument;
+
+use crate::Result;
+
+/// A job, as seen by the middlewares. Its arguments are decrypted, and
+/// redacted when it is logged with `Debug`.
+#[derive(Clone)]
+pub struct JobInfo {
+    /// The id of a queued job, `None` for the jobs performed in the process
+    /// and the jobs not enqueued yet
+    pub id: Option<String>,
+    /// The class name of the worker
+    pub name: String,
+    /// The queue of the job, when known
+    pub queue: Option<String>,
+    /// The serialized arguments of the job
+    pub args: JsonValue,
+}
+
+impl std::fmt::Debug for JobInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JobInfo")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("queue", &self.queue)
+            .field("args", &super::payload::Redacted(&self.args))
+            .finish()
+    }
+}
+
+/// Code run around the jobs of every worker.
+///
+/// # Example
+/// ```rust
+/// use std::time::Duration;
+///
+/// use async_trait::async_trait;
+/// use loco_rs::{
+///     bgworker::middleware::{JobInfo, JobMiddleware},
+///     prelude::*,
+/// };
+///
+/// struct Timing;
+///
+/// #[async_trait]
+/// impl JobMiddleware for Timing {
+///     fn name(&self) -> &str {
+///         "timing"
+///     }
+///
+///     async fn after_perform(&self, job: &JobInfo, elapsed: Duration) {
+///         tracing::info!(job = job.name, elapsed_ms = elapsed.as_millis(), "job performed");
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait JobMiddleware: Send + Sync {
+    /// The name of the middleware.
+    fn name(&self) -> &str;
+
+    /// Runs before the job is enqueued, and may rewrite its arguments.
+    ///
+    /// # Errors
+    ///
+    /// When the job must not be enqueued, failing `perform_later`.
+    async fn before_enqueue(&self, _job: &mut JobInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs before the job is performed, in the span of the job.
+    ///
+    /// # Errors
+    ///
+    /// When the job must not be performed, failing it.
+    async fn before_perform(&self, _job: &JobInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs after the job is performed successfully.
+    async fn after_perform(&self, _job: &JobInfo, _elapsed: Duration) {}
+
+    /// Runs after the job failed.
+    async fn on_error(&self, _job: &JobInfo, _err: &crate::Error) {}
+}
+
+/// The middlewares of the application, kept in the shared store of the
+/// context.
+#[derive(Clone, Default)]
+pub struct JobMiddlewares(pub Vec<Arc<dyn JobMiddleware>>);
+
+impl JobMiddlewares {
+    /// Runs the `before_enqueue` of the middlewares.
+    ///
+    /// # Errors
+    ///
+    /// When a middleware rejects the job
+    pub async fn before_enqueue(&self, job: &mut JobInfo) -> Result<()> {
+        for middleware in &self.0 {
+            middleware.before_enqueue(job).await?;
+        }
+        Ok(())
+    }
+
+    /// Performs a job in its span, through the middlewares.
+    ///
+    /// # Errors
+    ///
+    /// When a middleware rejects the job, or the job fails
+    pub async fn perform<F>(&self, job: &JobInfo, perform: F) -> Result<()>
+    where
+        F: Future<Output = Result<()>> + Send,
+    {
+        let span = tracing::info_span!(
+            "job",
+            job_id = job.id.as_deref(),
+            job_name = job.name,
+            queue = job.queue.as_deref()
+        );
+        async {
+            let started = std::time::Instant::now();
+            let result = match self.before_perform(job).await {
+                Ok(()) => perform.await,
+                Err(err) => Err(err),
+            };
+            for middleware in self.0.iter().rev() {
+                match &result {
+                    Ok(()) => middleware.after_perform(job, started.elapsed()).await,
+                    Err(err) => middleware.on_error(job, err).await,
+                }
+            }
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn before_perform(&self, job: &JobInfo) -> Result<()> {
+        for middleware in &self.0 {
+            middleware.before_perform(job).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    struct Recorder {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl JobMiddleware for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn before_enqueue(&self, job: &mut JobInfo) -> Result<()> {
+            job.args["tenant"] = JsonValue::from("acme");
+            Ok(())
+        }
+
+        async fn before_perform(&self, job: &JobInfo) -> Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{}:before:{}", self.name, job.args["tenant"]));
+            Ok(())
+        }
+
+        async fn after_perform(&self, _job: &JobInfo, _elapsed: Duration) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{}:after", self.name));
+        }
+
+        async fn on_error(&self, _job: &JobInfo, err: &crate::Error) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{}:error:{err}", self.name));
+        }
+    }
+
+    #[tokio::test]
+    async fn can_run_middlewares_around_jobs() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let middlewares = JobMiddlewares(
+            ["outer", "inner"]
+                .into_iter()
+                .map(|name| {
+                    Arc::new(Recorder {
+                        name,
+                        calls: calls.clone(),
+                    }) as Arc<dyn JobMiddleware>
+                })
+                .collect(),
+        );
+
+        let mut job = JobInfo {
+            id: None,
+            name: "Reindex".to_string(),
+            queue: None,
+            args: serde_json::json!({"user_id": 42}),
+        };
+        middlewares.before_enqueue(&mut job).await.unwrap();
+        assert_eq!(job.args["tenant"], "acme");
+
+        assert!(middlewares.perform(&job, async { Ok(()) }).await.is_ok());
+        assert!(middlewares
+            .perform(&job, async { Err(crate::Error::string("boom")) })
+            .await
+            .is_err());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "outer:before:\"acme\"",
+                "inner:before:\"acme\"",
+                "inner:after",
+                "outer:after",
+                "outer:before:\"acme\"",
+                "inner:before:\"acme\"",
+                "inner:error:boom",
+                "outer:error:boom",
+            ]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::Write,
     path::{Path, PathBuf},
@@ -10,13 +11,27 @@ use async_trait::async_trait;
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use serde_variant::to_variant_name;
+pub mod async_queue;
+pub mod batch;
+pub mod middleware;
+#[cfg(feature = "bg_nats")]
+pub mod nats;
+pub mod payload;
 #[cfg(feature = "bg_pg")]
 pub mod pg;
 #[cfg(feature = "bg_redis")]
 pub mod redis;
+pub mod schedule;
 #[cfg(feature = "bg_sqlt")]
 pub mod sqlt;
+#[cfg(feature = "bg_sqs")]
+pub mod sqs;
+pub mod workflow;
 
+use self::{
+    async_queue::{AsyncQueue, Pushed, Task},
+    middleware::{JobInfo, JobMiddlewares},
+};
 use crate::{
     app::AppContext,
     config::{
@@ -62,6 +77,137 @@ impl std::fmt::Display for JobStatus {
     }
 }
 
+/// The queue of the jobs enqueued without one.
+pub const DEFAULT_QUEUE: &str = "default";
+
+/// Where a job is enqueued: its named queue, and its priority among the jobs
+/// of the queue, the higher first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub queue: String,
+    pub priority: i32,
+    /// The key of a unique job: enqueueing a job of the same key is a no-op
+    /// while the job is queued or processing
+    pub unique_key: Option<String>,
+    /// The longest time the key of a unique job is held
+    pub unique_for: Option<std::time::Duration>,
+    /// The id of the batch of the job, see [`batch::Batch`]
+    pub batch: Option<String>,
+    /// The key the job is throttled by, see [`BackgroundWorker::throttle`]
+    pub throttle_key: Option<String>,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            queue: DEFAULT_QUEUE.to_string(),
+            priority: 0,
+            unique_key: None,
+            unique_for: None,
+            batch: None,
+            throttle_key: None,
+        }
+    }
+}
+
+/// A limit on the jobs of a worker performed by the queue, for workers
+/// calling rate-limited APIs. A job over the limit goes back to its queue
+/// until it may run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Throttle {
+    /// At most `limit` jobs started every `per`
+    Rate {
+        limit: u32,
+        per: std::time::Duration,
+    },
+    /// At most `limit` jobs performing at once
+    Concurrency { limit: u32 },
+}
+
+/// The delay before a job throttled by concurrency is tried again.
+pub(crate) const THROTTLE_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The number of jobs of a queue, by status.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct QueueStats {
+    pub queue: String,
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl QueueStats {
+    /// Sums `(queue, status, count)` rows into the stats of each queue,
+    /// ordered by queue.
+    #[must_use]
+    pub fn collect(rows: impl IntoIterator<Item = (String, String, u64)>) -> Vec<Self> {
+        let mut queues: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+        for (queue, status, count) in rows {
+            *queues.entry(queue).or_default().entry(status).or_default() += count;
+        }
+        queues
+            .into_iter()
+            .map(|(queue, counts)| Self { queue, counts })
+            .collect()
+    }
+
+    /// The number of jobs of the queue with this status.
+    #[must_use]
+    pub fn count(&self, status: &JobStatus) -> u64 {
+        self.counts
+            .get(&status.to_string())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Parses a queue concurrency such as `critical:4`, a queue without a number
+/// of workers having one worker.
+///
+/// # Errors
+///
+/// When the number of workers is not a positive number
+pub fn parse_queue_concurrency(spec: &str) -> std::result::Result<(String, u32), String> {
+    let (queue, workers) = spec.split_once(':').unwrap_or((spec, "1"));
+    let queue = queue.trim();
+    match workers.trim().parse::<u32>() {
+        Ok(workers) if workers > 0 && !queue.is_empty() => Ok((queue.to_string(), workers)),
+        _ => Err(format!(
+            "invalid queue concurrency `{spec}`, expected `<queue>:<workers>`"
+        )),
+    }
+}
+
+/// The queues processed by each worker: `num_workers` workers processing
+/// every queue when no concurrency is given, otherwise the given number of
+/// workers for each queue.
+pub(crate) fn worker_queues(
+    num_workers: u32,
+    concurrency: &BTreeMap<String, u32>,
+) -> Vec<Vec<String>> {
+    if concurrency.is_empty() {
+        return (0..num_workers).map(|_| vec![]).collect();
+    }
+    concurrency
+        .iter()
+        .flat_map(|(queue, workers)| (0..*workers).map(move |_| vec![queue.clone()]))
+        .collect()
+}
+
+/// The settings of the workers started by the `run` of the queue providers.
+#[derive(Clone, Default)]
+pub struct WorkerOpts {
+    /// The tags of the jobs processed by the workers, which only process the
+    /// jobs without tags when empty. The brokers ignore them.
+    pub tags: Vec<String>,
+    /// The number of workers of each named queue, the configured number of
+    /// workers processing every queue when empty.
+    pub concurrency: BTreeMap<String, u32>,
+    /// The middlewares performing the jobs
+    pub middlewares: JobMiddlewares,
+    /// How long the running jobs are given to finish on shutdown before they
+    /// are requeued, waiting for them when unset.
+    pub grace_period: Option<std::time::Duration>,
+}
+
 // Queue struct now holds both a QueueProvider and QueueRegistrar
 pub enum Queue {
     #[cfg(feature = "bg_redis")]
@@ -85,6 +231,20 @@ pub enum Queue {
         sqlt::RunOpts,
         tokio_util::sync::CancellationToken,
     ),
+    #[cfg(feature = "bg_sqs")]
+    Sqs(
+        sqs::SqsClient,
+        Arc<tokio::sync::Mutex<sqs::JobRegistry>>,
+        sqs::RunOpts,
+        tokio_util::sync::CancellationToken,
+    ),
+    #[cfg(feature = "bg_nats")]
+    Nats(
+        nats::NatsClient,
+        Arc<tokio::sync::Mutex<nats::JobRegistry>>,
+        nats::RunOpts,
+        tokio_util::sync::CancellationToken,
+    ),
     None,
 }
 
@@ -94,7 +254,6 @@ impl Queue {
     /// # Errors
     ///
     /// This function will return an error if fails
-    #[allow(unused_variables)]
     pub async fn enqueue<A: Serialize + Send + Sync>(
         &self,
         class: String,
@@ -102,37 +261,149 @@ impl Queue {
         args: A,
         tags: Option<Vec<String>>,
     ) -> Result<()> {
-        tracing::debug!(worker = class, queue = ?queue, tags = ?tags, "Enqueuing background job");
+        self.enqueue_with_priority(class, queue, 0, args, tags)
+            .await
+    }
+
+    /// Add a job to the queue, before the queued jobs of a lower priority
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    pub async fn enqueue_with_priority<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        queue: Option<String>,
+        priority: i32,
+        args: A,
+        tags: Option<Vec<String>>,
+    ) -> Result<()> {
+        self.enqueue_at(class, queue, priority, args, tags, chrono::Utc::now())
+            .await
+    }
+
+    /// Add a job to the queue, to be processed once `run_at` is due
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn enqueue_at<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        queue: Option<String>,
+        priority: i32,
+        args: A,
+        tags: Option<Vec<String>>,
+        run_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let placement = Placement {
+            queue: queue.unwrap_or_else(|| DEFAULT_QUEUE.to_string()),
+            priority,
+            ..Placement::default()
+        };
+        self.enqueue_with(class, &placement, args, tags, run_at)
+            .await
+    }
+
+    /// Add a job to the queue at its placement, to be processed once `run_at`
+    /// is due. A unique job whose key is held by a pending job is not added.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn enqueue_with<A: Serialize + Send + Sync>(
+        &self,
+        class: String,
+        placement: &Placement,
+        args: A,
+        tags: Option<Vec<String>>,
+        run_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        tracing::debug!(worker = class, placement = ?placement, tags = ?tags, run_at = %run_at, "Enqueuing background job");
+        let args = payload::seal(serde_json::to_value(args)?)?;
         match self {
             #[cfg(feature = "bg_redis")]
             Self::Redis(pool, _, _, _) => {
-                redis::enqueue(pool, class, queue, args, tags).await?;
+                redis::enqueue_with(pool, class, placement, args, tags, run_at).await?;
             }
             #[cfg(feature = "bg_pg")]
             Self::Postgres(pool, _, _, _) => {
-                pg::enqueue(
-                    pool,
-                    &class,
-                    serde_json::to_value(args)?,
-                    chrono::Utc::now(),
-                    None,
-                    tags,
-                )
-                .await
-                .map_err(Box::from)?;
+                pg::enqueue_with(pool, &class, args, run_at, None, tags, placement)
+                    .await
+                    .map_err(Box::from)?;
             }
             #[cfg(feature = "bg_sqlt")]
             Self::Sqlite(pool, _, _, _) => {
-                sqlt::enqueue(
-                    pool,
-                    &class,
-                    serde_json::to_value(args)?,
+                sqlt::enqueue_with(pool, &class, args, run_at, None, tags, placement)
+                    .await
+                    .map_err(Box::from)?;
+            }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(client, _, _, _) => {
+                sqs::enqueue_with(client, class, placement, args, tags, run_at).await?;
+            }
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(client, _, _, _) => {
+                nats::enqueue_with(client, class, placement, args, tags, run_at).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Add the jobs of a batch to the queue, the queue enqueueing its
+    /// callbacks once they are done. The success callback of an empty batch
+    /// is added right away.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn enqueue_batch(&self, batch: &batch::Batch) -> Result<()> {
+        tracing::debug!(
+            batch_id = batch.id(),
+            jobs = batch.jobs().count(),
+            "Enqueuing job batch"
+        );
+        if batch.jobs().next().is_none() {
+            if let Some(callback) = batch.success_callback() {
+                self.enqueue_with(
+                    callback.name.clone(),
+                    &callback.placement(None),
+                    &callback.args,
+                    callback.tags.clone(),
                     chrono::Utc::now(),
-                    None,
-                    tags,
                 )
-                .await
-                .map_err(Box::from)?;
+                .await?;
+            }
+            return Ok(());
+        }
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => {
+                redis::enqueue_batch(pool, batch).await?;
+            }
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => {
+                pg::enqueue_batch(pool, batch).await.map_err(Box::from)?;
+            }
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => {
+                sqlt::enqueue_batch(pool, batch).await.map_err(Box::from)?;
+            }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => {
+                return Err(Error::string(
+                    "job batches are not supported by the sqs queue",
+                ));
+            }
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => {
+                return Err(Error::string(
+                    "job batches are not supported by the nats queue",
+                ));
             }
             _ => {}
         }
@@ -169,6 +440,16 @@ impl Queue {
                 let mut r = registry.lock().await;
                 r.register_worker(W::class_name(), worker)?;
             }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, registry, _, _) => {
+                let mut r = registry.lock().await;
+                r.register_worker(W::class_name(), worker)?;
+            }
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, registry, _, _) => {
+                let mut r = registry.lock().await;
+                r.register_worker(W::class_name(), worker)?;
+            }
             _ => {}
         }
         Ok(())
@@ -179,16 +460,46 @@ impl Queue {
     /// # Errors
     ///
     /// This function will return an error if fails
-    #[allow(unused_variables)]
     pub async fn run(&self, tags: Vec<String>) -> Result<()> {
-        tracing::info!("Starting background job processing");
+        self.run_queues(tags, &BTreeMap::new(), &JobMiddlewares::default(), None)
+            .await
+    }
+
+    /// Runs the worker loop for this [`Queue`], with the given number of
+    /// workers for each named queue, such as `critical: 4`, instead of the
+    /// configured number of workers processing every queue, performing the
+    /// jobs through the middlewares.
+    ///
+    /// Once [`Self::shutdown`] is called, the workers stop dequeueing jobs,
+    /// and the running jobs are given `grace_period` to finish, after which
+    /// they are interrupted and requeued. Without a grace period, the running
+    /// jobs are waited for.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fails
+    #[allow(unused_variables)]
+    pub async fn run_queues(
+        &self,
+        tags: Vec<String>,
+        concurrency: &BTreeMap<String, u32>,
+        middlewares: &JobMiddlewares,
+        grace_period: Option<std::time::Duration>,
+    ) -> Result<()> {
+        tracing::info!(queues = ?concurrency, "Starting background job processing");
+        let workers = WorkerOpts {
+            tags,
+            concurrency: concurrency.clone(),
+            middlewares: middlewares.clone(),
+            grace_period,
+        };
         match self {
             #[cfg(feature = "bg_redis")]
             Self::Redis(pool, registry, run_opts, token) => {
                 let handles = registry
                     .lock()
                     .await
-                    .run(pool, run_opts, &token.clone(), &tags);
+                    .run(pool, run_opts, &token.clone(), &workers);
                 Self::process_worker_handles(handles).await?;
             }
             #[cfg(feature = "bg_pg")]
@@ -196,7 +507,7 @@ impl Queue {
                 let handles = registry
                     .lock()
                     .await
-                    .run(pool, run_opts, &token.clone(), &tags);
+                    .run(pool, run_opts, &token.clone(), &workers);
                 Self::process_worker_handles(handles).await?;
             }
             #[cfg(feature = "bg_sqlt")]
@@ -204,7 +515,24 @@ impl Queue {
                 let handles = registry
                     .lock()
                     .await
-                    .run(pool, run_opts, &token.clone(), &tags);
+                    .run(pool, run_opts, &token.clone(), &workers);
+                Self::process_worker_handles(handles).await?;
+            }
+            // the brokers deliver every job of a queue, whatever its tags
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(client, registry, run_opts, token) => {
+                let handles = registry
+                    .lock()
+                    .await
+                    .run(client, run_opts, &token.clone(), &workers);
+                Self::process_worker_handles(handles).await?;
+            }
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(client, registry, run_opts, token) => {
+                let handles = registry
+                    .lock()
+                    .await
+                    .run(client, run_opts, &token.clone(), &workers);
                 Self::process_worker_handles(handles).await?;
             }
             _ => {
@@ -265,6 +593,14 @@ impl Queue {
             Self::Sqlite(pool, _, _, _) => {
                 sqlt::initialize_database(pool).await.map_err(Box::from)?;
             }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(client, _, run_opts, _) => {
+                sqs::initialize_queues(client, &run_opts.queues).await?;
+            }
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(client, _, run_opts, _) => {
+                nats::initialize_stream(client, &run_opts.queues).await?;
+            }
             _ => {}
         }
         Ok(())
@@ -290,6 +626,14 @@ impl Queue {
             Self::Sqlite(pool, _, _, _) => {
                 sqlt::clear(pool).await.map_err(Box::from)?;
             }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(client, _, run_opts, _) => {
+                sqs::clear(client, &run_opts.queues).await?;
+            }
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(client, _, _, _) => {
+                nats::clear(client).await?;
+            }
             _ => {}
         }
         Ok(())
@@ -315,6 +659,14 @@ impl Queue {
             Self::Sqlite(pool, _, _, _) => {
                 sqlt::ping(pool).await.map_err(Box::from)?;
             }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(client, _, _, _) => {
+                sqs::ping(client).await?;
+            }
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(client, _, _, _) => {
+                nats::ping(client).await?;
+            }
             _ => {}
         }
         Ok(())
@@ -329,10 +681,36 @@ impl Queue {
             Self::Postgres(_, _, _, _) => "postgres queue".to_string(),
             #[cfg(feature = "bg_sqlt")]
             Self::Sqlite(_, _, _, _) => "sqlite queue".to_string(),
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => "sqs queue".to_string(),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => "nats queue".to_string(),
             _ => "no queue".to_string(),
         }
     }
 
+    /// Whether the workers are shutting down: a long job may check it to
+    /// checkpoint its progress, such as by enqueueing a job for its remaining
+    /// work, and return before its grace period ends.
+    #[must_use]
+    pub fn is_shutting_down(&self) -> bool {
+        match self {
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(_, _, _, cancellation_token) => cancellation_token.is_cancelled(),
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(_, _, _, cancellation_token) => cancellation_token.is_cancelled(),
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(_, _, _, cancellation_token) => cancellation_token.is_cancelled(),
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, cancellation_token) => cancellation_token.is_cancelled(),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, cancellation_token) => cancellation_token.is_cancelled(),
+            Self::None => false,
+        }
+    }
+
+    /// Stops the workers from dequeueing jobs, see [`Self::run_queues`].
+    ///
     /// # Errors
     ///
     /// Does not currently return an error, but the postgres or other future
@@ -346,13 +724,27 @@ impl Queue {
             Self::Postgres(_, _, _, cancellation_token) => cancellation_token.cancel(),
             #[cfg(feature = "bg_sqlt")]
             Self::Sqlite(_, _, _, cancellation_token) => cancellation_token.cancel(),
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, cancellation_token) => cancellation_token.cancel(),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, cancellation_token) => cancellation_token.cancel(),
             _ => {}
         }
 
         Ok(())
     }
 
-    async fn get_jobs(
+    /// The error of an operation the broker of the queue does not support,
+    /// the brokers not keeping the finished jobs.
+    #[cfg(any(feature = "bg_sqs", feature = "bg_nats"))]
+    fn unsupported(&self, operation: &str) -> Error {
+        Error::string(&format!(
+            "{operation} is not supported by the {}",
+            self.describe()
+        ))
+    }
+
+    pub(crate) async fn get_jobs(
         &self,
         status: Option<&Vec<JobStatus>>,
         age_days: Option<i64>,
@@ -379,6 +771,10 @@ impl Queue {
                 let jobs = redis::get_jobs(pool, status, age_days).await?;
                 Ok(serde_json::to_value(jobs)?)
             }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => Err(self.unsupported("listing jobs")),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => Err(self.unsupported("listing jobs")),
             Self::None => {
                 tracing::error!(
                     "No queue provider is configured: compile with at least one queue provider feature"
@@ -405,6 +801,10 @@ impl Queue {
             Self::Sqlite(pool, _, _, _) => sqlt::cancel_jobs_by_name(pool, job_name).await,
             #[cfg(feature = "bg_redis")]
             Self::Redis(pool, _, _, _) => redis::cancel_jobs_by_name(pool, job_name).await,
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => Err(self.unsupported("cancelling jobs")),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => Err(self.unsupported("cancelling jobs")),
             Self::None => {
                 tracing::error!(
                     "No queue provider is configured: compile with at least one queue provider feature"
@@ -441,6 +841,10 @@ impl Queue {
             Self::Redis(pool, _, _, _) => {
                 redis::clear_jobs_older_than(pool, age_days, Some(status)).await
             }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => Err(self.unsupported("clearing jobs by age")),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => Err(self.unsupported("clearing jobs by age")),
             Self::None => {
                 tracing::error!(
                     "No queue provider is configured: compile with at least one queue provider feature"
@@ -465,6 +869,93 @@ impl Queue {
             Self::Sqlite(pool, _, _, _) => sqlt::clear_by_status(pool, status).await,
             #[cfg(feature = "bg_redis")]
             Self::Redis(pool, _, _, _) => redis::clear_by_status(pool, status).await,
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => Err(self.unsupported("clearing jobs by status")),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => Err(self.unsupported("clearing jobs by status")),
+            Self::None => {
+                tracing::error!(
+                    "No queue provider is configured: compile with at least one queue provider feature"
+                );
+                Err(Error::string("provider not configured"))
+            }
+        }
+    }
+
+    /// Counts the jobs of every queue by status.
+    ///
+    /// # Errors
+    /// - If no queue provider is configured, it will return an error indicating the lack of configuration.
+    /// - Any error in the underlying provider's logic will propagate from the respective function.
+    pub async fn stats(&self) -> Result<Vec<QueueStats>> {
+        tracing::trace!("Counting jobs");
+        match self {
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pg::stats(pool).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => sqlt::stats(pool).await,
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::stats(pool).await,
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(client, _, run_opts, _) => sqs::stats(client, &run_opts.queues).await,
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(client, _, run_opts, _) => nats::stats(client, &run_opts.queues).await,
+            Self::None => {
+                tracing::error!(
+                    "No queue provider is configured: compile with at least one queue provider feature"
+                );
+                Err(Error::string("provider not configured"))
+            }
+        }
+    }
+
+    /// Requeues a failed or cancelled job, dropping its error.
+    ///
+    /// # Errors
+    /// - If no queue provider is configured, it will return an error indicating the lack of configuration.
+    /// - If there is no failed or cancelled job with this id, it will return [`Error::NotFound`].
+    /// - Any error in the underlying provider's logic will propagate from the respective function.
+    pub async fn retry_job(&self, id: &str) -> Result<()> {
+        tracing::info!(job_id = id, "Retrying job");
+        match self {
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pg::retry_job(pool, id).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => sqlt::retry_job(pool, id).await,
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::retry_job(pool, id).await,
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => Err(self.unsupported("retrying jobs")),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => Err(self.unsupported("retrying jobs")),
+            Self::None => {
+                tracing::error!(
+                    "No queue provider is configured: compile with at least one queue provider feature"
+                );
+                Err(Error::string("provider not configured"))
+            }
+        }
+    }
+
+    /// Deletes a job from the queue.
+    ///
+    /// # Errors
+    /// - If no queue provider is configured, it will return an error indicating the lack of configuration.
+    /// - If there is no job with this id, it will return [`Error::NotFound`].
+    /// - Any error in the underlying provider's logic will propagate from the respective function.
+    pub async fn delete_job(&self, id: &str) -> Result<()> {
+        tracing::info!(job_id = id, "Deleting job");
+        match self {
+            #[cfg(feature = "bg_pg")]
+            Self::Postgres(pool, _, _, _) => pg::delete_job(pool, id).await,
+            #[cfg(feature = "bg_sqlt")]
+            Self::Sqlite(pool, _, _, _) => sqlt::delete_job(pool, id).await,
+            #[cfg(feature = "bg_redis")]
+            Self::Redis(pool, _, _, _) => redis::delete_job(pool, id).await,
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => Err(self.unsupported("deleting jobs")),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => Err(self.unsupported("deleting jobs")),
             Self::None => {
                 tracing::error!(
                     "No queue provider is configured: compile with at least one queue provider feature"
@@ -489,6 +980,10 @@ impl Queue {
             Self::Sqlite(pool, _, _, _) => sqlt::requeue(pool, age_minutes).await,
             #[cfg(feature = "bg_redis")]
             Self::Redis(pool, _, _, _) => redis::requeue(pool, age_minutes).await,
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => Err(self.unsupported("requeueing jobs")),
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => Err(self.unsupported("requeueing jobs")),
             Self::None => {
                 tracing::error!(
                     "No queue provider is configured: compile with at least one queue provider feature"
@@ -574,6 +1069,24 @@ impl Queue {
                 }
                 Ok(())
             }
+            #[cfg(feature = "bg_sqs")]
+            Self::Sqs(_, _, _, _) => {
+                let jobs: Vec<sqs::Job> = serde_yaml::from_reader(File::open(path)?)?;
+                for job in jobs {
+                    self.enqueue(job.name.clone(), Some(job.queue), job.data, job.tags)
+                        .await?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "bg_nats")]
+            Self::Nats(_, _, _, _) => {
+                let jobs: Vec<nats::Job> = serde_yaml::from_reader(File::open(path)?)?;
+                for job in jobs {
+                    self.enqueue(job.name.clone(), Some(job.queue), job.data, job.tags)
+                        .await?;
+                }
+                Ok(())
+            }
             Self::None => {
                 tracing::error!(
                     "No queue provider is configured: compile with at least one queue provider feature"
@@ -584,6 +1097,60 @@ impl Queue {
     }
 }
 
+/// Performs a job, interrupting it once `grace_period` elapsed after the
+/// workers started shutting down. Returns `None` when the job was interrupted.
+#[allow(dead_code)]
+pub(crate) async fn perform_until_shutdown<F>(
+    perform: F,
+    token: &tokio_util::sync::CancellationToken,
+    grace_period: Option<std::time::Duration>,
+) -> Option<Result<()>>
+where
+    F: std::future::Future<Output = Result<()>> + Send,
+{
+    let Some(grace_period) = grace_period else {
+        return Some(perform.await);
+    };
+    tokio::select! {
+        result = perform => Some(result),
+        () = async {
+            token.cancelled().await;
+            tokio::time::sleep(grace_period).await;
+        } => None,
+    }
+}
+
+/// The key a job of the worker `W` is throttled by, the class name of the
+/// worker followed by its [`BackgroundWorker::throttle_key`].
+pub(crate) fn throttle_key<W, A>(args: &A) -> Option<String>
+where
+    W: BackgroundWorker<A>,
+    A: Send + Sync + Serialize + 'static,
+{
+    W::throttle()?;
+    Some(W::throttle_key(args).map_or_else(
+        || W::class_name(),
+        |key| format!("{}:{key}", W::class_name()),
+    ))
+}
+
+/// The placement of a job of the worker `W` in the queue.
+fn placement<W, A>(args: &A) -> Placement
+where
+    W: BackgroundWorker<A>,
+    A: Send + Sync + Serialize + 'static,
+{
+    Placement {
+        queue: W::queue_for(args).unwrap_or_else(|| DEFAULT_QUEUE.to_string()),
+        priority: W::priority_for(args),
+        // the keys of the workers do not collide
+        unique_key: W::unique_key(args).map(|key| format!("{}:{key}", W::class_name())),
+        unique_for: W::unique_for(),
+        batch: None,
+        throttle_key: throttle_key::<W, A>(args),
+    }
+}
+
 #[async_trait]
 pub trait BackgroundWorker<A: Send + Sync + serde::Serialize + 'static>: Send + Sync {
     /// If you have a specific queue
@@ -594,6 +1161,58 @@ pub trait BackgroundWorker<A: Send + Sync + serde::Serialize + 'static>: Send +
         None
     }
 
+    /// The priority of the jobs of this worker in their queue: the queued jobs
+    /// of a higher priority are processed first.
+    #[must_use]
+    fn priority() -> i32 {
+        0
+    }
+
+    /// The queue of a job, [`Self::queue`] unless the worker places its jobs
+    /// by their arguments.
+    #[must_use]
+    fn queue_for(_args: &A) -> Option<String> {
+        Self::queue()
+    }
+
+    /// The priority of a job, [`Self::priority`] unless the worker places its
+    /// jobs by their arguments.
+    #[must_use]
+    fn priority_for(_args: &A) -> i32 {
+        Self::priority()
+    }
+
+    /// Makes the jobs of this worker unique by a key of their arguments, such
+    /// as `format!("user:{}", args.user_id)`: performing a job later while a
+    /// job of the same key is queued or processing is a no-op.
+    #[must_use]
+    fn unique_key(_args: &A) -> Option<String> {
+        None
+    }
+
+    /// The longest time a unique job holds its key, released earlier when the
+    /// job completes or fails. Without it, the key is held until then.
+    #[must_use]
+    fn unique_for() -> Option<std::time::Duration> {
+        None
+    }
+
+    /// Throttles the jobs of this worker performed by the queue, such as
+    /// `Some(Throttle::Rate { limit: 10, per: Duration::from_secs(1) })`.
+    /// The `ForegroundBlocking` and `BackgroundAsync` modes do not throttle.
+    #[must_use]
+    fn throttle() -> Option<Throttle> {
+        None
+    }
+
+    /// Throttles the jobs separately by a key of their arguments, such as
+    /// `format!("account:{}", args.account_id)`, instead of throttling every
+    /// job of the worker together.
+    #[must_use]
+    fn throttle_key(_args: &A) -> Option<String> {
+        None
+    }
+
     /// Specifies tags associated with this worker. Workers might only process jobs
     /// matching specific tags during startup.
     #[must_use]
@@ -616,30 +1235,116 @@ pub trait BackgroundWorker<A: Send + Sync + serde::Serialize + 'static>: Send +
     where
         Self: Sized,
     {
+        Self::perform_at(ctx, args, chrono::Utc::now()).await
+    }
+
+    /// Performs the job once `delay` has elapsed, see [`Self::perform_at`].
+    async fn perform_in(ctx: &AppContext, args: A, delay: std::time::Duration) -> crate::Result<()>
+    where
+        Self: Sized,
+    {
+        let delay = chrono::Duration::from_std(delay)
+            .map_err(|err| Error::Message(format!("invalid job delay: {err}")))?;
+        Self::perform_at(ctx, args, chrono::Utc::now() + delay).await
+    }
+
+    /// Performs the job at `run_at`. The queue keeps the job until it is due,
+    /// and the async workers wait for it in the process, while the
+    /// `ForegroundBlocking` mode performs the job right away.
+    async fn perform_at(
+        ctx: &AppContext,
+        args: A,
+        run_at: chrono::DateTime<chrono::Utc>,
+    ) -> crate::Result<()>
+    where
+        Self: Sized,
+    {
+        let middlewares = ctx.shared_store.get::<JobMiddlewares>().unwrap_or_default();
+        let mut job = JobInfo {
+            id: None,
+            name: Self::class_name(),
+            queue: Self::queue_for(&args),
+            args: serde_json::to_value(&args)?,
+        };
         match &ctx.config.workers.mode {
             WorkerMode::BackgroundQueue => {
                 if let Some(p) = &ctx.queue_provider {
+                    middlewares.before_enqueue(&mut job).await?;
                     let tags = Self::tags();
                     let tags_option = if tags.is_empty() { None } else { Some(tags) };
-                    p.enqueue(Self::class_name(), Self::queue(), args, tags_option)
-                        .await?;
+                    let placement = placement::<Self, A>(&args);
+                    p.enqueue_with(
+                        Self::class_name(),
+                        &placement,
+                        job.args,
+                        tags_option,
+                        run_at,
+                    )
+                    .await?;
                 } else {
                     tracing::error!(
-                        "perform_later: background queue is selected, but queue was not populated \
-                         in context"
+                        "perform_at: background queue is selected, but queue was not populated in \
+                         context"
                     );
                 }
             }
             WorkerMode::ForegroundBlocking => {
-                Self::build(ctx).perform(args).await?;
+                middlewares
+                    .perform(&job, Self::build(ctx).perform(args))
+                    .await?;
             }
             WorkerMode::BackgroundAsync => {
+                let Some(queue) = ctx.shared_store.get::<AsyncQueue>() else {
+                    tracing::error!(
+                        "perform_at: async mode is selected, but the async queue was not started"
+                    );
+                    return Ok(());
+                };
+                // what the queue provider needs to take the job when it spills
+                let spill = (queue.overflow() == config::Overflow::Spill).then(|| {
+                    let tags = Self::tags();
+                    (
+                        Self::class_name(),
+                        placement::<Self, A>(&args),
+                        if tags.is_empty() { None } else { Some(tags) },
+                        job.clone(),
+                        middlewares.clone(),
+                    )
+                });
                 let dx = ctx.clone();
-                tokio::spawn(async move {
-                    if let Err(err) = Self::build(&dx).perform(args).await {
+                let task: Task = Box::pin(async move {
+                    if let Err(err) = middlewares
+                        .perform(&job, Self::build(&dx).perform(args))
+                        .await
+                    {
                         tracing::error!(err = err.to_string(), "worker failed to perform job");
                     }
                 });
+                let dx = ctx.clone();
+                let push = async move {
+                    if queue.push(task).await? != Pushed::Full {
+                        return Ok(());
+                    }
+                    let (Some(p), Some((class, placement, tags, mut job, middlewares))) =
+                        (&dx.queue_provider, spill)
+                    else {
+                        return Err(Error::QueueProviderMissing);
+                    };
+                    tracing::debug!(worker = class, "async queue is full, spilling the job");
+                    middlewares.before_enqueue(&mut job).await?;
+                    p.enqueue_with(class, &placement, job.args, tags, chrono::Utc::now())
+                        .await
+                };
+                if let Ok(delay) = (run_at - chrono::Utc::now()).to_std() {
+                    tokio::spawn(async move {
+                        tokio::time::sleep(delay).await;
+                        if let Err(err) = push.await {
+                            tracing::error!(err = err.to_string(), "failed to push delayed job");
+                        }
+                    });
+                } else {
+                    push.await?;
+                }
             }
         }
         Ok(())
@@ -683,6 +1388,12 @@ pub async fn converge(queue: &Queue, config: &QueueConfig) -> Result<()> {
             uri: _,
             queues: _,
             num_workers: _,
+        })
+        | QueueConfig::Sqs(config::SqsQueueConfig {
+            dangerously_flush, ..
+        })
+        | QueueConfig::Nats(config::NatsQueueConfig {
+            dangerously_flush, ..
         }) => {
             if *dangerously_flush {
                 tracing::warn!("Flush mode enabled - clearing all jobs from queue");
@@ -718,6 +1429,16 @@ pub async fn create_queue_provider(config: &Config) -> Result<Option<Arc<Queue>>
                     tracing::debug!("Creating SQLite queue provider");
                     Ok(Some(Arc::new(sqlt::create_provider(qcfg).await?)))
                 }
+                #[cfg(feature = "bg_sqs")]
+                config::QueueConfig::Sqs(qcfg) => {
+                    tracing::debug!("Creating SQS queue provider");
+                    Ok(Some(Arc::new(sqs::create_provider(qcfg).await?)))
+                }
+                #[cfg(feature = "bg_nats")]
+                config::QueueConfig::Nats(qcfg) => {
+                    tracing::debug!("Creating NATS queue provider");
+                    Ok(Some(Arc::new(nats::create_provider(qcfg).await?)))
+                }
 
                 #[allow(unreachable_patterns)]
                 _ => Err(Error::string(
@@ -834,4 +1555,32 @@ mod tests {
 
         assert_eq!(count, 14);
     }
+
+    #[test]
+    fn can_spread_workers_by_queue() {
+        assert_eq!(
+            parse_queue_concurrency("critical:4"),
+            Ok(("critical".to_string(), 4))
+        );
+        assert_eq!(
+            parse_queue_concurrency("default"),
+            Ok(("default".to_string(), 1))
+        );
+        assert!(parse_queue_concurrency("default:0").is_err());
+        assert!(parse_queue_concurrency(":2").is_err());
+
+        assert_eq!(
+            worker_queues(2, &BTreeMap::new()),
+            vec![Vec::<String>::new(), vec![]]
+        );
+        let concurrency = BTreeMap::from([("critical".to_string(), 2), ("default".to_string(), 1)]);
+        assert_eq!(
+            worker_queues(2, &concurrency),
+            vec![
+                vec!["critical".to_string()],
+                vec!["critical".to_string()],
+                vec!["default".to_string()],
+            ]
+        );
+    }
 }
@@ -0,0 +1,530 @@
+/// NATS `JetStream` based background job queue provider
+use std::{
+    collections::HashMap, future::Future, panic::AssertUnwindSafe, pin::Pin, sync::Arc,
+    time::Duration,
+};
+
+use super::{
+    middleware::JobInfo, payload, perform_until_shutdown, worker_queues, BackgroundWorker,
+    JobStatus, Placement, Queue, QueueStats, WorkerOpts,
+};
+use crate::{config::NatsQueueConfig, Error, Result};
+use async_nats::jetstream::{self, consumer::pull, AckKind};
+use chrono::{DateTime, Utc};
+use futures_util::{FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tokio::{task::JoinHandle, time::sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, trace, warn};
+use ulid::Ulid;
+
+type JobId = String;
+type JobData = JsonValue;
+
+pub const DEFAULT_QUEUES: &[&str] = &["default", "mailer"];
+
+/// The longest delay before a failed job is delivered again.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+type JobHandler = Box<
+    dyn Fn(
+            JobId,
+            JobData,
+        ) -> Pin<Box<dyn std::future::Future<Output = Result<(), crate::Error>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// A NATS connection, with the `JetStream` stream of the jobs.
+#[derive(Clone)]
+pub struct NatsClient {
+    client: async_nats::Client,
+    jetstream: jetstream::Context,
+    stream: String,
+    ack_wait: Duration,
+    max_deliver: i64,
+}
+
+impl NatsClient {
+    fn subject(&self, queue: &str) -> String {
+        format!("{}.{queue}", self.stream)
+    }
+
+    fn consumer(&self, queue: &str) -> String {
+        format!("{}_{queue}", self.stream)
+    }
+}
+
+/// A job, as the payload of a `JetStream` message.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Job {
+    pub id: JobId,
+    pub name: String,
+    pub data: JobData,
+    pub run_at: DateTime<Utc>,
+    pub queue: String,
+    pub tags: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct JobRegistry {
+    handlers: Arc<HashMap<String, JobHandler>>,
+}
+
+impl JobRegistry {
+    /// Creates a new [`JobRegistry`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            handlers: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Registers a job handler with the provided name.
+    ///
+    /// # Errors
+    ///
+    /// Fails if cannot register worker
+    pub fn register_worker<Args, W>(&mut self, name: String, worker: W) -> Result<()>
+    where
+        Args: Send + Serialize + Sync + 'static,
+        W: BackgroundWorker<Args> + 'static,
+        for<'de> Args: Deserialize<'de>,
+    {
+        let worker = Arc::new(worker);
+        let wrapped_handler = move |_job_id: String, job_data: JobData| {
+            let w = worker.clone();
+            Box::pin(async move {
+                let args = serde_json::from_value::<Args>(job_data);
+                match args {
+                    Ok(args) => {
+                        // Wrap the perform call in catch_unwind to handle panics
+                        match AssertUnwindSafe(w.perform(args)).catch_unwind().await {
+                            Ok(result) => result,
+                            Err(panic) => {
+                                let panic_msg = panic
+                                    .downcast_ref::<String>()
+                                    .map(String::as_str)
+                                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                                    .unwrap_or("Unknown panic occurred");
+                                error!(err = panic_msg, "worker panicked");
+                                Err(Error::string(panic_msg))
+                            }
+                        }
+                    }
+                    Err(err) => Err(err.into()),
+                }
+            }) as Pin<Box<dyn Future<Output = Result<(), crate::Error>> + Send>>
+        };
+        Arc::get_mut(&mut self.handlers)
+            .ok_or_else(|| Error::string("cannot register worker"))?
+            .insert(name, Box::new(wrapped_handler));
+        Ok(())
+    }
+
+    /// Returns a reference to the job handlers.
+    #[must_use]
+    pub fn handlers(&self) -> &Arc<HashMap<String, JobHandler>> {
+        &self.handlers
+    }
+
+    /// Runs the job handlers with the provided number of workers, or with
+    /// the given number of workers for each queue.
+    ///
+    /// A failed job is delivered again after a backoff, until it was
+    /// delivered `max_deliver` times, after which it is given up.
+    #[must_use]
+    pub fn run(
+        &self,
+        client: &NatsClient,
+        opts: &RunOpts,
+        token: &CancellationToken,
+        workers: &WorkerOpts,
+    ) -> Vec<JoinHandle<()>> {
+        let mut jobs = Vec::new();
+        let all_queues = get_queues(&opts.queues);
+        let interval = opts.poll_interval_sec;
+
+        for (idx, queues) in worker_queues(opts.num_workers, &workers.concurrency)
+            .into_iter()
+            .enumerate()
+        {
+            let handlers = self.handlers.clone();
+            let middlewares = workers.middlewares.clone();
+            let grace_period = workers.grace_period;
+            let worker_token = token.clone();
+            let client = client.clone();
+            // A worker without queues processes every queue
+            let queues = if queues.is_empty() {
+                all_queues.clone()
+            } else {
+                queues
+            };
+
+            let job = tokio::spawn(async move {
+                loop {
+                    if worker_token.is_cancelled() {
+                        trace!(worker_num = idx, "cancellation received, stopping worker");
+                        break;
+                    }
+
+                    let received = match receive(&client, &queues).await {
+                        Ok(received) => received,
+                        Err(err) => {
+                            error!(err = err.to_string(), "cannot fetch from queue");
+                            None
+                        }
+                    };
+
+                    if let Some((job, message)) = received {
+                        if let Ok(delay) = (job.run_at - Utc::now()).to_std() {
+                            trace!(job_id = job.id, delay = ?delay, "job is not due yet");
+                            if let Err(err) = message.ack_with(AckKind::Nak(Some(delay))).await {
+                                error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot delay job");
+                            }
+                            continue;
+                        }
+
+                        debug!(job_id = job.id, name = job.name, "working on job");
+                        let Some(handler) = handlers.get(&job.name) else {
+                            error!(job = job.name, "no handler found for job");
+                            continue;
+                        };
+                        let (args, perform) =
+                            payload::open_job(&job.data, |args| handler(job.id.clone(), args));
+                        let info = JobInfo {
+                            id: Some(job.id.clone()),
+                            name: job.name.clone(),
+                            queue: Some(job.queue.clone()),
+                            args,
+                        };
+                        let Some(result) = perform_until_shutdown(
+                            middlewares.perform(&info, perform),
+                            &worker_token,
+                            grace_period,
+                        )
+                        .await
+                        else {
+                            warn!(
+                                job_id = job.id,
+                                name = job.name,
+                                "job interrupted by the shutdown, requeueing"
+                            );
+                            if let Err(err) = message.ack_with(AckKind::Nak(None)).await {
+                                error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot requeue interrupted job");
+                            }
+                            break;
+                        };
+                        let acked = match result {
+                            Ok(()) => message.ack().await,
+                            Err(err) => {
+                                let delivered = message.info().map_or(1, |info| info.delivered);
+                                if delivered >= client.max_deliver {
+                                    error!(
+                                        err = err.to_string(),
+                                        job = ?payload::Redacted(&job),
+                                        delivered = delivered,
+                                        "job failed, giving it up"
+                                    );
+                                    message.ack_with(AckKind::Term).await
+                                } else {
+                                    error!(
+                                        err = err.to_string(),
+                                        job = ?payload::Redacted(&job),
+                                        delivered = delivered,
+                                        "job failed, retrying it"
+                                    );
+                                    message
+                                        .ack_with(AckKind::Nak(Some(retry_backoff(delivered))))
+                                        .await
+                                }
+                            }
+                        };
+                        if let Err(err) = acked {
+                            error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot acknowledge job");
+                        }
+                    } else {
+                        tokio::select! {
+                            biased;
+                            () = worker_token.cancelled() => {
+                                trace!(worker_num = idx, "cancellation received during sleep, stopping worker");
+                                break;
+                            }
+                            () = sleep(Duration::from_secs(interval.into())) => {}
+                        }
+                    }
+                }
+            });
+            jobs.push(job);
+        }
+        jobs
+    }
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The delay before a job failed on its `delivered`th delivery is delivered
+/// again, doubling from a second.
+fn retry_backoff(delivered: i64) -> Duration {
+    let exponent = u32::try_from(delivered.saturating_sub(1).clamp(0, 16)).unwrap_or_default();
+    Duration::from_secs(2u64.pow(exponent)).min(MAX_RETRY_BACKOFF)
+}
+
+/// Receives a job from the first of the queues holding one, with its
+/// message to acknowledge.
+async fn receive(
+    client: &NatsClient,
+    queues: &[String],
+) -> Result<Option<(Job, jetstream::Message)>> {
+    let stream = client
+        .jetstream
+        .get_stream(&client.stream)
+        .await
+        .map_err(Error::wrap)?;
+    for queue in queues {
+        let consumer: jetstream::consumer::Consumer<pull::Config> = stream
+            .get_consumer(&client.consumer(queue))
+            .await
+            .map_err(Error::wrap)?;
+        let mut messages = consumer
+            .fetch()
+            .max_messages(1)
+            .messages()
+            .await
+            .map_err(Error::wrap)?;
+        let Some(message) = messages.next().await else {
+            continue;
+        };
+        let message = message?;
+        match serde_json::from_slice::<Job>(&message.payload) {
+            Ok(job) => return Ok(Some((job, message))),
+            Err(err) => {
+                // an unreadable message would be delivered forever
+                error!(
+                    err = err.to_string(),
+                    queue = queue,
+                    "cannot parse job, dropping it"
+                );
+                message.ack_with(AckKind::Term).await?;
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Add a job to its queue, to be processed once `run_at` is due. A job due
+/// later is delivered to a worker, which puts it back until it is due. The
+/// priority, unique key and throttle key of the placement are not supported
+/// by `JetStream`, and ignored.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_with(
+    client: &NatsClient,
+    class: String,
+    placement: &Placement,
+    args: impl serde::Serialize + Send,
+    tags: Option<Vec<String>>,
+    run_at: DateTime<Utc>,
+) -> Result<()> {
+    let job = Job {
+        id: Ulid::new().to_string(),
+        name: class,
+        data: serde_json::to_value(args)?,
+        run_at,
+        queue: placement.queue.clone(),
+        tags,
+        created_at: Utc::now(),
+    };
+    client
+        .jetstream
+        .publish(
+            client.subject(&placement.queue),
+            serde_json::to_vec(&job)?.into(),
+        )
+        .await
+        .map_err(Error::wrap)?
+        .await
+        .map_err(Error::wrap)?;
+    Ok(())
+}
+
+/// Creates the work queue stream of the jobs, and the durable consumer of
+/// each queue.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn initialize_stream(client: &NatsClient, queues: &Option<Vec<String>>) -> Result<()> {
+    let stream = client
+        .jetstream
+        .get_or_create_stream(jetstream::stream::Config {
+            name: client.stream.clone(),
+            subjects: vec![format!("{}.>", client.stream)],
+            retention: jetstream::stream::RetentionPolicy::WorkQueue,
+            ..Default::default()
+        })
+        .await
+        .map_err(Error::wrap)?;
+    for queue in get_queues(queues) {
+        let name = client.consumer(&queue);
+        stream
+            .get_or_create_consumer(
+                &name,
+                pull::Config {
+                    durable_name: Some(name.clone()),
+                    filter_subject: client.subject(&queue),
+                    ack_wait: client.ack_wait,
+                    max_deliver: client.max_deliver,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(Error::wrap)?;
+    }
+    Ok(())
+}
+
+/// Purges the stream of the jobs.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn clear(client: &NatsClient) -> Result<()> {
+    client
+        .jetstream
+        .get_stream(&client.stream)
+        .await
+        .map_err(Error::wrap)?
+        .purge()
+        .await
+        .map_err(Error::wrap)?;
+    Ok(())
+}
+
+/// Ping NATS
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn ping(client: &NatsClient) -> Result<()> {
+    client.client.flush().await.map_err(Error::wrap)?;
+    Ok(())
+}
+
+/// Counts the jobs of every queue, from its consumer: the pending ones are
+/// queued, the ones waiting for an acknowledgement are processing.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn stats(client: &NatsClient, queues: &Option<Vec<String>>) -> Result<Vec<QueueStats>> {
+    let stream = client
+        .jetstream
+        .get_stream(&client.stream)
+        .await
+        .map_err(Error::wrap)?;
+    let mut rows = Vec::new();
+    for queue in get_queues(queues) {
+        let mut consumer: jetstream::consumer::Consumer<pull::Config> = stream
+            .get_consumer(&client.consumer(&queue))
+            .await
+            .map_err(Error::wrap)?;
+        let info = consumer.info().await.map_err(Error::wrap)?;
+        rows.push((
+            queue.clone(),
+            JobStatus::Queued.to_string(),
+            info.num_pending,
+        ));
+        rows.push((
+            queue,
+            JobStatus::Processing.to_string(),
+            info.num_ack_pending as u64,
+        ));
+    }
+    Ok(QueueStats::collect(rows))
+}
+
+/// Returns the queues of the provider: the default queues, followed by the
+/// configured ones.
+#[must_use]
+pub fn get_queues(config_queues: &Option<Vec<String>>) -> Vec<String> {
+    let mut queues = DEFAULT_QUEUES
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if let Some(config_queues) = config_queues {
+        for q in config_queues {
+            if !queues.iter().any(|aq| q == aq) {
+                queues.push(q.clone());
+            }
+        }
+    }
+    queues
+}
+
+pub struct RunOpts {
+    pub num_workers: u32,
+    pub poll_interval_sec: u32,
+    pub queues: Option<Vec<String>>,
+}
+
+/// Create this provider
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn create_provider(qcfg: &NatsQueueConfig) -> Result<Queue> {
+    let nats = async_nats::connect(&qcfg.uri).await.map_err(Error::wrap)?;
+    let client = NatsClient {
+        jetstream: jetstream::new(nats.clone()),
+        client: nats,
+        stream: qcfg.stream.clone(),
+        ack_wait: Duration::from_secs(qcfg.ack_wait.into()),
+        max_deliver: qcfg.max_deliver,
+    };
+    let run_opts = RunOpts {
+        num_workers: qcfg.num_workers,
+        poll_interval_sec: qcfg.poll_interval_sec,
+        queues: qcfg.queues.clone(),
+    };
+    debug!(
+        queues = ?qcfg.queues,
+        num_workers = qcfg.num_workers,
+        "creating NATS queue provider"
+    );
+    Ok(Queue::Nats(
+        client,
+        Arc::new(tokio::sync::Mutex::new(JobRegistry::new())),
+        run_opts,
+        CancellationToken::new(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_get_queues() {
+        assert_eq!(get_queues(&None), vec!["default", "mailer"]);
+        assert_eq!(
+            get_queues(&Some(vec!["critical".to_string()])),
+            vec!["default", "mailer", "critical"]
+        );
+    }
+
+    #[test]
+    fn can_back_off_retries() {
+        assert_eq!(retry_backoff(1), Duration::from_secs(1));
+        assert_eq!(retry_backoff(2), Duration::from_secs(2));
+        assert_eq!(retry_backoff(4), Duration::from_secs(8));
+        assert_eq!(retry_backoff(20), MAX_RETRY_BACKOFF);
+    }
+}
@@ -0,0 +1,258 @@
+//! # Job Payloads
+//!
+//! Keeps the arguments of the jobs out of sight: with `workers.payload.encrypt`,
+//! the arguments are encrypted before they are enqueued, with the keys of
+//! [`crate::model::encryption`], and decrypted when the job is dequeued, so
+//! that the emails and tokens they carry do not sit in plain text in the
+//! queue backend. The fields of `workers.payload.redact` are replaced by
+//! `[REDACTED]`, at any depth, when the jobs are logged.
+//!
+//! ```yaml
+//! workers:
+//!   payload:
+//!     encrypt: true
+//!     redact:
+//!       - email
+//!       - token
+//! ```
+//!
+//! The jobs enqueued before the encryption was enabled, or after it was
+//! disabled, are still performed: only the encrypted payloads are decrypted.
+
+#[cfg(feature = "with-db")]
+use std::sync::Arc;
+use std::{fmt, future::Future, pin::Pin, sync::RwLock};
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+#[cfg(feature = "with-db")]
+use crate::model::encryption::Encryptor;
+use crate::{config::PayloadConfig, Error, Result};
+
+/// The field holding the encrypted arguments of a job.
+const ENCRYPTED: &str = "$encrypted";
+
+/// The context the encrypted arguments are bound to, so that they only
+/// decrypt as job payloads.
+const CONTEXT: &str = "job-payload";
+
+/// The value of the redacted fields.
+const REDACTED: &str = "[REDACTED]";
+
+static PAYLOADS: RwLock<Option<Payloads>> = RwLock::new(None);
+
+/// The payload configuration, with the encryptor of the payloads.
+#[derive(Clone, Default)]
+struct Payloads {
+    config: PayloadConfig,
+    #[cfg(feature = "with-db")]
+    encryptor: Option<Arc<Encryptor>>,
+}
+
+/// Installs the payload configuration and the encryptor of the payloads,
+/// replacing the previous ones. Done at boot from the `workers.payload`
+/// configuration and [`crate::app::AppContext::encryptor`].
+pub fn install(
+    config: PayloadConfig,
+    #[cfg(feature = "with-db")] encryptor: Option<Arc<Encryptor>>,
+) {
+    *PAYLOADS
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Payloads {
+        config,
+        #[cfg(feature = "with-db")]
+        encryptor,
+    });
+}
+
+fn payloads() -> Payloads {
+    PAYLOADS
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "with-db")]
+fn encryptor(payloads: &Payloads) -> Result<&Encryptor> {
+    payloads.encryptor.as_deref().ok_or_else(|| {
+        Error::string("job payload encryption requires the `database.encryption` keys")
+    })
+}
+
+/// Encrypts the arguments of a job about to be enqueued, when the payloads
+/// are encrypted. Encrypted arguments are kept as they are.
+///
+/// # Errors
+///
+/// When the arguments can not be encrypted, or no encryption keys are
+/// configured
+pub fn seal(args: JsonValue) -> Result<JsonValue> {
+    let payloads = payloads();
+    if !payloads.config.encrypt || is_sealed(&args) {
+        return Ok(args);
+    }
+    #[cfg(feature = "with-db")]
+    {
+        seal_with(encryptor(&payloads)?, &args)
+    }
+    #[cfg(not(feature = "with-db"))]
+    {
+        Err(Error::string(
+            "job payload encryption requires the `with-db` feature",
+        ))
+    }
+}
+
+/// Decrypts the arguments of a dequeued job, when they are encrypted.
+///
+/// # Errors
+///
+/// When the arguments can not be decrypted
+pub fn open(data: &JsonValue) -> Result<JsonValue> {
+    if !is_sealed(data) {
+        return Ok(data.clone());
+    }
+    #[cfg(feature = "with-db")]
+    {
+        open_with(encryptor(&payloads())?, data)
+    }
+    #[cfg(not(feature = "with-db"))]
+    {
+        Err(Error::string(
+            "job payload decryption requires the `with-db` feature",
+        ))
+    }
+}
+
+/// Decrypts the arguments of a dequeued job, returning them for the
+/// middlewares along with the job performed with them, which fails when they
+/// can not be decrypted.
+pub(crate) fn open_job<F>(
+    data: &JsonValue,
+    perform: impl FnOnce(JsonValue) -> F,
+) -> (JsonValue, Pin<Box<dyn Future<Output = Result<()>> + Send>>)
+where
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    match open(data) {
+        Ok(args) => (args.clone(), Box::pin(perform(args))),
+        Err(err) => (JsonValue::Null, Box::pin(std::future::ready(Err(err)))),
+    }
+}
+
+/// Returns `true` when the arguments of a job are encrypted.
+#[must_use]
+pub fn is_sealed(data: &JsonValue) -> bool {
+    data.as_object().is_some_and(|object| {
+        object.len() == 1 && object.get(ENCRYPTED).is_some_and(JsonValue::is_string)
+    })
+}
+
+#[cfg(feature = "with-db")]
+fn seal_with(encryptor: &Encryptor, args: &JsonValue) -> Result<JsonValue> {
+    Ok(serde_json::json!({ ENCRYPTED: encryptor.encrypt_bound(&args.to_string(), CONTEXT)? }))
+}
+
+#[cfg(feature = "with-db")]
+fn open_with(encryptor: &Encryptor, data: &JsonValue) -> Result<JsonValue> {
+    let ciphertext = data[ENCRYPTED]
+        .as_str()
+        .ok_or_else(|| Error::string("the job payload is not encrypted"))?;
+    Ok(serde_json::from_str(
+        &encryptor.decrypt_bound(ciphertext, CONTEXT)?,
+    )?)
+}
+
+/// The arguments of a job with its redacted fields replaced.
+#[must_use]
+pub fn redact(value: &JsonValue) -> JsonValue {
+    let config = payloads().config;
+    if config.redact.is_empty() {
+        return value.clone();
+    }
+    redact_fields(value, &config.redact)
+}
+
+fn redact_fields(value: &JsonValue, fields: &[String]) -> JsonValue {
+    match value {
+        JsonValue::Object(object) => object
+            .iter()
+            .map(|(key, value)| {
+                let value = if fields.iter().any(|field| field == key) {
+                    JsonValue::from(REDACTED)
+                } else {
+                    redact_fields(value, fields)
+                };
+                (key.clone(), value)
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        JsonValue::Array(values) => values
+            .iter()
+            .map(|value| redact_fields(value, fields))
+            .collect(),
+        value => value.clone(),
+    }
+}
+
+/// Logs a job with its redacted fields replaced, as in
+/// `error!(job = ?Redacted(&job), "cannot complete job")`.
+pub struct Redacted<'a, T>(pub &'a T);
+
+impl<T: Serialize> fmt::Debug for Redacted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_value(self.0) {
+            Ok(value) => write!(f, "{}", redact(&value)),
+            Err(_) => f.write_str("<job>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_redact_fields() {
+        let fields = vec!["email".to_string(), "token".to_string()];
+        let args = serde_json::json!({
+            "user_id": 42,
+            "email": "user@example.com",
+            "invites": [{"email": "friend@example.com", "role": "admin"}],
+            "auth": {"token": "secret"},
+        });
+
+        assert_eq!(
+            redact_fields(&args, &fields),
+            serde_json::json!({
+                "user_id": 42,
+                "email": "[REDACTED]",
+                "invites": [{"email": "[REDACTED]", "role": "admin"}],
+                "auth": {"token": "[REDACTED]"},
+            })
+        );
+    }
+
+    #[cfg(feature = "with-db")]
+    #[test]
+    fn can_seal_and_open_payloads() {
+        let encryptor = Encryptor::new(&[vec![7; 32]]).unwrap();
+        let args = serde_json::json!({"email": "user@example.com", "user_id": 42});
+
+        let sealed = seal_with(&encryptor, &args).unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.to_string().contains("user@example.com"));
+        assert_eq!(open_with(&encryptor, &sealed).unwrap(), args);
+
+        // the values of the encrypted attributes are not payloads
+        let attribute = serde_json::json!({
+            ENCRYPTED: encryptor.encrypt_bound(&args.to_string(), "users.email").unwrap()
+        });
+        assert!(open_with(&encryptor, &attribute).is_err());
+
+        assert!(!is_sealed(&args));
+        assert_eq!(open(&args).unwrap(), args);
+    }
+}
@@ -4,7 +4,12 @@ use std::{
     time::Duration,
 };
 
-use super::{BackgroundWorker, JobStatus, Queue};
+use super::{
+    batch::{Batch, BatchJob},
+    middleware::JobInfo,
+    payload, perform_until_shutdown, worker_queues, BackgroundWorker, JobStatus, Placement, Queue,
+    QueueStats, Throttle, WorkerOpts, THROTTLE_BACKOFF,
+};
 use crate::{config::PostgresQueueConfig, Error, Result};
 use chrono::{DateTime, Utc};
 use futures_util::FutureExt;
@@ -13,12 +18,12 @@ use serde_json::Value as JsonValue;
 pub use sqlx::PgPool;
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions, PgRow},
-    ConnectOptions, Row,
+    ConnectOptions, PgConnection, Row,
 };
 use std::fmt::Write;
 use tokio::{task::JoinHandle, time::sleep};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, trace, warn};
 use ulid::Ulid;
 type JobId = String;
 type JobData = JsonValue;
@@ -44,10 +49,15 @@ pub struct Job {
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
     pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throttle_key: Option<String>,
 }
 
 pub struct JobRegistry {
     handlers: Arc<HashMap<String, JobHandler>>,
+    throttles: Arc<HashMap<String, Throttle>>,
 }
 
 impl JobRegistry {
@@ -56,6 +66,7 @@ impl JobRegistry {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(HashMap::new()),
+            throttles: Arc::new(HashMap::new()),
         }
     }
 
@@ -95,6 +106,11 @@ impl JobRegistry {
             }) as Pin<Box<dyn Future<Output = Result<(), crate::Error>> + Send>>
         };
 
+        if let Some(throttle) = W::throttle() {
+            Arc::get_mut(&mut self.throttles)
+                .ok_or_else(|| Error::string("cannot register worker"))?
+                .insert(name.clone(), throttle);
+        }
         Arc::get_mut(&mut self.handlers)
             .ok_or_else(|| Error::string("cannot register worker"))?
             .insert(name, Box::new(wrapped_handler));
@@ -107,22 +123,29 @@ impl JobRegistry {
         &self.handlers
     }
 
-    /// Runs the job handlers with the provided number of workers.
+    /// Runs the job handlers with the provided number of workers, or with
+    /// the given number of workers for each queue.
     #[must_use]
     pub fn run(
         &self,
         pool: &PgPool,
         opts: &RunOpts,
         token: &CancellationToken,
-        tags: &[String],
+        workers: &WorkerOpts,
     ) -> Vec<JoinHandle<()>> {
         let mut jobs = Vec::new();
 
         let interval = opts.poll_interval_sec;
-        for idx in 0..opts.num_workers {
+        for (idx, queues) in worker_queues(opts.num_workers, &workers.concurrency)
+            .into_iter()
+            .enumerate()
+        {
             let handlers = self.handlers.clone();
+            let throttles = self.throttles.clone();
+            let middlewares = workers.middlewares.clone();
+            let grace_period = workers.grace_period;
             let worker_token = token.clone(); // Clone token for this worker
-            let worker_tags = tags.to_vec();
+            let worker_tags = workers.tags.clone();
 
             let pool = pool.clone();
             let job = tokio::spawn(async move {
@@ -137,7 +160,7 @@ impl JobRegistry {
                         worker_id = idx,
                         "Connection pool stats"
                     );
-                    let job_opt = match dequeue(&pool, &worker_tags).await {
+                    let job_opt = match dequeue(&pool, &worker_tags, &queues).await {
                         Ok(t) => t,
                         Err(err) => {
                             error!(error = %err, "Failed to fetch job from queue");
@@ -148,7 +171,64 @@ impl JobRegistry {
                     if let Some(job) = job_opt {
                         debug!(job_id = %job.id, job_name = %job.name, "Processing job");
                         if let Some(handler) = handlers.get(&job.name) {
-                            match handler(job.id.clone(), job.data.clone()).await {
+                            if let (Some(throttle), Some(throttle_key)) =
+                                (throttles.get(&job.name), &job.throttle_key)
+                            {
+                                match throttle_delay(&pool, &job.id, throttle_key, throttle).await {
+                                    Ok(Some(delay)) => {
+                                        debug!(job_id = %job.id, throttle_key = %throttle_key, delay = ?delay, "Job is throttled, rescheduling");
+                                        if let Err(err) =
+                                            reschedule_job(&pool, &job.id, delay).await
+                                        {
+                                            error!(
+                                                error = %err,
+                                                job_id = %job.id,
+                                                job_name = %job.name,
+                                                "Failed to reschedule throttled job"
+                                            );
+                                        }
+                                        continue;
+                                    }
+                                    Ok(None) => {}
+                                    Err(err) => {
+                                        error!(
+                                            error = %err,
+                                            job_id = %job.id,
+                                            job_name = %job.name,
+                                            "Failed to throttle job"
+                                        );
+                                    }
+                                }
+                            }
+                            let (args, perform) =
+                                payload::open_job(&job.data, |args| handler(job.id.clone(), args));
+                            let info = JobInfo {
+                                id: Some(job.id.clone()),
+                                name: job.name.clone(),
+                                queue: None,
+                                args,
+                            };
+                            let Some(result) = perform_until_shutdown(
+                                middlewares.perform(&info, perform),
+                                &worker_token,
+                                grace_period,
+                            )
+                            .await
+                            else {
+                                warn!(job_id = %job.id, job_name = %job.name, "Job interrupted by the shutdown, requeueing");
+                                if let Err(err) =
+                                    reschedule_job(&pool, &job.id, Duration::ZERO).await
+                                {
+                                    error!(
+                                        error = %err,
+                                        job_id = %job.id,
+                                        job_name = %job.name,
+                                        "Failed to requeue interrupted job"
+                                    );
+                                }
+                                break;
+                            };
+                            let failed = match result {
                                 Ok(()) => {
                                     if let Err(err) =
                                         complete_job(&pool, &job.id, job.interval).await
@@ -162,6 +242,7 @@ impl JobRegistry {
                                     } else {
                                         debug!(job_id = %job.id, "Job completed successfully");
                                     }
+                                    false
                                 }
                                 Err(err) => {
                                     if let Err(fail_err) = fail_job(&pool, &job.id, &err).await {
@@ -174,6 +255,17 @@ impl JobRegistry {
                                     } else {
                                         debug!(job_id = %job.id, error = %err, "Job execution failed");
                                     }
+                                    true
+                                }
+                            };
+                            if let Some(batch_id) = &job.batch_id {
+                                if let Err(err) = settle_batch(&pool, batch_id, failed).await {
+                                    error!(
+                                        error = %err,
+                                        job_id = %job.id,
+                                        batch_id = %batch_id,
+                                        "Failed to settle job batch"
+                                    );
                                 }
                             }
                         } else {
@@ -243,15 +335,43 @@ pub async fn initialize_database(pool: &PgPool) -> Result<()> {
                 updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
                 tags JSONB
             );
+
+            ALTER TABLE pg_loco_queue
+                ADD COLUMN IF NOT EXISTS queue VARCHAR NOT NULL DEFAULT '{}',
+                ADD COLUMN IF NOT EXISTS priority INTEGER NOT NULL DEFAULT 0,
+                ADD COLUMN IF NOT EXISTS batch_id VARCHAR,
+                ADD COLUMN IF NOT EXISTS throttle_key VARCHAR;
+
+            CREATE TABLE IF NOT EXISTS pg_loco_queue_unique (
+                key VARCHAR PRIMARY KEY,
+                job_id VARCHAR NOT NULL,
+                expires_at TIMESTAMPTZ
+            );
+
+            CREATE TABLE IF NOT EXISTS pg_loco_queue_batches (
+                id VARCHAR PRIMARY KEY,
+                pending INTEGER NOT NULL,
+                failed INTEGER NOT NULL DEFAULT 0,
+                on_success JSONB,
+                on_failure JSONB,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            );
+
+            CREATE TABLE IF NOT EXISTS pg_loco_queue_throttles (
+                key VARCHAR PRIMARY KEY,
+                window_start BIGINT NOT NULL,
+                count BIGINT NOT NULL
+            );
             ",
-        JobStatus::Queued
+        JobStatus::Queued,
+        super::DEFAULT_QUEUE
     ))
     .execute(pool)
     .await?;
     Ok(())
 }
 
-/// Add a job
+/// Add a job to the default queue
 ///
 /// # Errors
 ///
@@ -263,20 +383,147 @@ pub async fn enqueue(
     run_at: DateTime<Utc>,
     interval: Option<Duration>,
     tags: Option<Vec<String>>,
+) -> Result<JobId> {
+    enqueue_with(
+        pool,
+        name,
+        data,
+        run_at,
+        interval,
+        tags,
+        &Placement::default(),
+    )
+    .await
+}
+
+/// Add a job to a named queue, with a priority. A unique job whose key is
+/// held by a pending job is not added, returning the id of the pending job.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_with(
+    pool: &PgPool,
+    name: &str,
+    data: JobData,
+    run_at: DateTime<Utc>,
+    interval: Option<Duration>,
+    tags: Option<Vec<String>>,
+    placement: &Placement,
+) -> Result<JobId> {
+    #[allow(clippy::cast_possible_truncation)]
+    let interval_ms: Option<i64> = interval.map(|i| i.as_millis() as i64);
+
+    let mut tx = pool.begin().await?;
+    let id = insert_job(&mut tx, name, data, run_at, interval_ms, tags, placement).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Add the jobs of a batch, counted by the batch until they are done.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_batch(pool: &PgPool, batch: &Batch) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let jobs = batch.jobs().collect::<Vec<_>>();
+    debug!(
+        batch_id = batch.id(),
+        jobs = jobs.len(),
+        "Enqueueing job batch"
+    );
+    sqlx::query(
+        "INSERT INTO pg_loco_queue_batches (id, pending, on_success, on_failure) VALUES ($1, $2, \
+         $3, $4)",
+    )
+    .bind(batch.id())
+    .bind(i32::try_from(jobs.len()).unwrap_or(i32::MAX))
+    .bind(
+        batch
+            .success_callback()
+            .map(serde_json::to_value)
+            .transpose()?,
+    )
+    .bind(
+        batch
+            .failure_callback()
+            .map(serde_json::to_value)
+            .transpose()?,
+    )
+    .execute(&mut *tx)
+    .await?;
+    for job in jobs {
+        insert_job(
+            &mut tx,
+            &job.name,
+            job.args.clone(),
+            Utc::now(),
+            None,
+            job.tags.clone(),
+            &job.placement(Some(batch.id())),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn insert_job(
+    conn: &mut PgConnection,
+    name: &str,
+    data: JobData,
+    run_at: DateTime<Utc>,
+    interval_ms: Option<i64>,
+    tags: Option<Vec<String>>,
+    placement: &Placement,
 ) -> Result<JobId> {
     let data_json = serde_json::to_value(data)?;
     let tags_json = tags
         .as_ref()
         .map(|t| serde_json::to_value(t).unwrap_or(serde_json::Value::Null));
 
-    #[allow(clippy::cast_possible_truncation)]
-    let interval_ms: Option<i64> = interval.map(|i| i.as_millis() as i64);
-
     let id = Ulid::new().to_string();
-    debug!(job_id = %id, job_name = %name, run_at = %run_at, tags = ?tags, "Enqueueing job");
+    if let Some(key) = &placement.unique_key {
+        // the key of a job which is no longer pending, or held for too long, is free
+        sqlx::query(
+            "DELETE FROM pg_loco_queue_unique WHERE key = $1 AND (expires_at <= NOW() OR NOT EXISTS \
+             (SELECT 1 FROM pg_loco_queue WHERE id = job_id AND status IN ($2, $3)))",
+        )
+        .bind(key)
+        .bind(JobStatus::Queued.to_string())
+        .bind(JobStatus::Processing.to_string())
+        .execute(&mut *conn)
+        .await?;
+        let expires_at = placement
+            .unique_for
+            .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+            .map(|ttl| Utc::now() + ttl);
+        let taken = sqlx::query(
+            "INSERT INTO pg_loco_queue_unique (key, job_id, expires_at) VALUES ($1, $2, $3) ON \
+             CONFLICT (key) DO NOTHING",
+        )
+        .bind(key)
+        .bind(&id)
+        .bind(expires_at)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected()
+            > 0;
+        if !taken {
+            let pending: JobId =
+                sqlx::query_scalar("SELECT job_id FROM pg_loco_queue_unique WHERE key = $1")
+                    .bind(key)
+                    .fetch_one(&mut *conn)
+                    .await?;
+            debug!(job_id = %pending, job_name = %name, unique_key = %key, "Unique job is already pending");
+            return Ok(pending);
+        }
+    }
+    debug!(job_id = %id, job_name = %name, run_at = %run_at, tags = ?tags, queue = %placement.queue, priority = placement.priority, "Enqueueing job");
     sqlx::query(
-        "INSERT INTO pg_loco_queue (id, task_data, name, run_at, interval, tags) VALUES ($1, $2, $3, \
-         $4, $5, $6)",
+        "INSERT INTO pg_loco_queue (id, task_data, name, run_at, interval, tags, queue, priority, \
+         batch_id, throttle_key) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
     )
     .bind(id.clone())
     .bind(data_json)
@@ -284,17 +531,74 @@ pub async fn enqueue(
     .bind(run_at)
     .bind(interval_ms)
     .bind(tags_json)
-    .execute(pool)
+    .bind(&placement.queue)
+    .bind(placement.priority)
+    .bind(&placement.batch)
+    .bind(&placement.throttle_key)
+    .execute(&mut *conn)
     .await?;
     Ok(id)
 }
 
-async fn dequeue(client: &PgPool, worker_tags: &[String]) -> Result<Option<Job>> {
+/// Counts a job of a batch as done, enqueueing the failure callback of the
+/// batch on its first failed job, and its success callback once every job
+/// completed.
+async fn settle_batch(pool: &PgPool, batch_id: &str, failed: bool) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let Some(row) = sqlx::query(
+        "UPDATE pg_loco_queue_batches SET pending = pending - 1, failed = failed + $2 WHERE id = \
+         $1 RETURNING pending, failed, on_success, on_failure",
+    )
+    .bind(batch_id)
+    .bind(i32::from(failed))
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(());
+    };
+    let pending: i32 = row.get("pending");
+    let failures: i32 = row.get("failed");
+    let callback: Option<JsonValue> = if failed && failures == 1 {
+        row.get("on_failure")
+    } else if pending == 0 && failures == 0 {
+        row.get("on_success")
+    } else {
+        None
+    };
+    if let Some(callback) = callback {
+        let callback: BatchJob = serde_json::from_value(callback)?;
+        debug!(batch_id, job_name = %callback.name, "Enqueueing batch callback");
+        insert_job(
+            &mut tx,
+            &callback.name,
+            callback.args.clone(),
+            Utc::now(),
+            None,
+            callback.tags.clone(),
+            &callback.placement(None),
+        )
+        .await?;
+    }
+    if pending <= 0 {
+        sqlx::query("DELETE FROM pg_loco_queue_batches WHERE id = $1")
+            .bind(batch_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn dequeue(
+    client: &PgPool,
+    worker_tags: &[String],
+    worker_queues: &[String],
+) -> Result<Option<Job>> {
     let mut tx = client.begin().await?;
 
     // Base query
     let mut query = String::from(
-        "SELECT id, name, task_data, status, run_at, interval, tags FROM pg_loco_queue WHERE status = $1 AND run_at <= NOW() "
+        "SELECT id, name, task_data, status, run_at, interval, tags, batch_id, throttle_key FROM pg_loco_queue WHERE status = $1 AND run_at <= NOW() "
     );
 
     // Apply tag filtering logic
@@ -321,7 +625,12 @@ async fn dequeue(client: &PgPool, worker_tags: &[String]) -> Result<Option<Job>>
         }
     }
 
-    query.push_str(" ORDER BY run_at LIMIT 1 FOR UPDATE SKIP LOCKED");
+    // A worker without queues processes every queue
+    if !worker_queues.is_empty() {
+        let _ = write!(query, " AND queue = ANY(${})", worker_tags.len() + 2);
+    }
+
+    query.push_str(" ORDER BY priority DESC, run_at LIMIT 1 FOR UPDATE SKIP LOCKED");
 
     // Create the query
     let mut db_query = sqlx::query(&query).bind(JobStatus::Queued.to_string());
@@ -330,6 +639,9 @@ async fn dequeue(client: &PgPool, worker_tags: &[String]) -> Result<Option<Job>>
     for tag in worker_tags {
         db_query = db_query.bind(tag);
     }
+    if !worker_queues.is_empty() {
+        db_query = db_query.bind(worker_queues);
+    }
 
     let row = db_query
         .map(|row: PgRow| to_job(&row).ok())
@@ -353,6 +665,72 @@ async fn dequeue(client: &PgPool, worker_tags: &[String]) -> Result<Option<Job>>
     }
 }
 
+/// The delay before a job throttled by `throttle_key` may run, `None` when it
+/// may run now. The rate of the jobs of a key is counted over fixed windows,
+/// and their concurrency by the jobs of the key being processed.
+async fn throttle_delay(
+    pool: &PgPool,
+    id: &JobId,
+    throttle_key: &str,
+    throttle: &Throttle,
+) -> Result<Option<Duration>> {
+    match throttle {
+        Throttle::Concurrency { limit } => {
+            let processing: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM pg_loco_queue WHERE throttle_key = $1 AND status = $2 AND id \
+                 <> $3",
+            )
+            .bind(throttle_key)
+            .bind(JobStatus::Processing.to_string())
+            .bind(id)
+            .fetch_one(pool)
+            .await?;
+            Ok((processing >= i64::from(*limit)).then_some(THROTTLE_BACKOFF))
+        }
+        Throttle::Rate { limit, per } => {
+            let now = Utc::now().timestamp_millis();
+            let per_ms = i64::try_from(per.as_millis()).unwrap_or(i64::MAX);
+            let row = sqlx::query(
+                "INSERT INTO pg_loco_queue_throttles (key, window_start, count) VALUES ($1, $2, 1) \
+                 ON CONFLICT (key) DO UPDATE SET count = CASE WHEN \
+                 pg_loco_queue_throttles.window_start <= $3 THEN 1 ELSE \
+                 pg_loco_queue_throttles.count + 1 END, window_start = CASE WHEN \
+                 pg_loco_queue_throttles.window_start <= $3 THEN $2 ELSE \
+                 pg_loco_queue_throttles.window_start END RETURNING count, window_start",
+            )
+            .bind(throttle_key)
+            .bind(now)
+            .bind(now.saturating_sub(per_ms))
+            .fetch_one(pool)
+            .await?;
+            let count: i64 = row.get("count");
+            let window_start: i64 = row.get("window_start");
+            if count <= i64::from(*limit) {
+                return Ok(None);
+            }
+            let wait_ms = window_start.saturating_add(per_ms).saturating_sub(now);
+            Ok(Some(Duration::from_millis(
+                u64::try_from(wait_ms).unwrap_or_default(),
+            )))
+        }
+    }
+}
+
+/// Puts a throttled or interrupted job back in the queue, due once `delay`
+/// has elapsed.
+async fn reschedule_job(pool: &PgPool, id: &JobId, delay: Duration) -> Result<()> {
+    let run_at = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+    sqlx::query(
+        "UPDATE pg_loco_queue SET status = $1, updated_at = NOW(), run_at = $2 WHERE id = $3",
+    )
+    .bind(JobStatus::Queued.to_string())
+    .bind(run_at)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 async fn complete_job(pool: &PgPool, id: &JobId, interval_ms: Option<i64>) -> Result<()> {
     let (status, run_at) = interval_ms.map_or_else(
         || (JobStatus::Completed.to_string(), Utc::now()),
@@ -430,6 +808,15 @@ pub async fn clear(pool: &PgPool) -> Result<()> {
     sqlx::query("DELETE FROM pg_loco_queue")
         .execute(pool)
         .await?;
+    sqlx::query("DELETE FROM pg_loco_queue_unique")
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM pg_loco_queue_batches")
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM pg_loco_queue_throttles")
+        .execute(pool)
+        .await?;
     Ok(())
 }
 
@@ -520,6 +907,68 @@ pub async fn requeue(pool: &PgPool, age_minutes: &i64) -> Result<()> {
     Ok(())
 }
 
+/// Counts the jobs of every queue by status.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn stats(pool: &PgPool) -> Result<Vec<QueueStats>> {
+    let rows = sqlx::query(
+        "SELECT queue, status, COUNT(*) AS count FROM pg_loco_queue GROUP BY queue, status",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(QueueStats::collect(rows.iter().map(|row| {
+        (
+            row.get("queue"),
+            row.get("status"),
+            u64::try_from(row.get::<i64, _>("count")).unwrap_or_default(),
+        )
+    })))
+}
+
+/// Requeues a failed or cancelled job, dropping its error.
+///
+/// # Errors
+///
+/// Returns [`Error::NotFound`] when there is no failed or cancelled job with
+/// this id, or an error if it fails
+pub async fn retry_job(pool: &PgPool, id: &str) -> Result<()> {
+    debug!(job_id = id, "retrying job");
+    let result = sqlx::query(
+        "UPDATE pg_loco_queue SET status = $1, run_at = NOW(), updated_at = NOW(), task_data = \
+         task_data - 'error' WHERE id = $2 AND status IN ($3, $4)",
+    )
+    .bind(JobStatus::Queued.to_string())
+    .bind(id)
+    .bind(JobStatus::Failed.to_string())
+    .bind(JobStatus::Cancelled.to_string())
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound);
+    }
+    Ok(())
+}
+
+/// Deletes a job.
+///
+/// # Errors
+///
+/// Returns [`Error::NotFound`] when there is no job with this id, or an error
+/// if it fails
+pub async fn delete_job(pool: &PgPool, id: &str) -> Result<()> {
+    debug!(job_id = id, "deleting job");
+    let result = sqlx::query("DELETE FROM pg_loco_queue WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound);
+    }
+    Ok(())
+}
+
 /// Ping system
 ///
 /// # Errors
@@ -612,6 +1061,8 @@ fn to_job(row: &PgRow) -> Result<Job> {
         created_at: row.try_get("created_at").unwrap_or_default(),
         updated_at: row.try_get("updated_at").unwrap_or_default(),
         tags,
+        batch_id: row.try_get("batch_id").unwrap_or_default(),
+        throttle_key: row.try_get("throttle_key").unwrap_or_default(),
     })
 }
 
@@ -797,7 +1248,7 @@ mod tests {
 
         std::thread::sleep(std::time::Duration::from_secs(1));
 
-        assert!(dequeue(&pool, &[]).await.is_ok());
+        assert!(dequeue(&pool, &[], &[]).await.is_ok());
 
         let job_after_dequeue = get_all_jobs(&pool)
             .await
@@ -1159,7 +1610,7 @@ mod tests {
             poll_interval_sec: 1,
         };
         let token = CancellationToken::new();
-        let handles = registry.run(&pool, &opts, &token, &[]);
+        let handles = registry.run(&pool, &opts, &token, &WorkerOpts::default());
 
         // Wait a bit for the worker to process the job
         sleep(Duration::from_secs(1)).await;
@@ -1252,7 +1703,7 @@ mod tests {
         assert_eq!(all_jobs.len(), 4);
 
         // 1. Worker with no tags should only get untagged jobs
-        let job = dequeue(&pool, &[]).await.expect("dequeue failed");
+        let job = dequeue(&pool, &[], &[]).await.expect("dequeue failed");
         assert!(job.is_some());
         let job = job.unwrap();
         assert_eq!(job.id, no_tag_id);
@@ -1264,7 +1715,7 @@ mod tests {
             .expect("Failed to complete job");
 
         // 2. Worker with "email" tag should get one of the email-tagged jobs
-        let job = dequeue(&pool, &["email".to_string()])
+        let job = dequeue(&pool, &["email".to_string()], &[])
             .await
             .expect("dequeue failed");
         assert!(job.is_some());
@@ -1281,7 +1732,7 @@ mod tests {
             .expect("Failed to complete job");
 
         // 3. Worker with "email" tag should get the remaining email job
-        let job = dequeue(&pool, &["email".to_string()])
+        let job = dequeue(&pool, &["email".to_string()], &[])
             .await
             .expect("dequeue failed");
         assert!(job.is_some());
@@ -1298,7 +1749,7 @@ mod tests {
             .expect("Failed to complete job");
 
         // 4. Worker with "sms" tag should get the sms job
-        let job = dequeue(&pool, &["sms".to_string()])
+        let job = dequeue(&pool, &["sms".to_string()], &[])
             .await
             .expect("dequeue failed");
         assert!(job.is_some());
@@ -1312,13 +1763,13 @@ mod tests {
             .expect("Failed to complete job");
 
         // 5. No more jobs should be available
-        let job = dequeue(&pool, &["email".to_string()])
+        let job = dequeue(&pool, &["email".to_string()], &[])
             .await
             .expect("dequeue failed");
         assert!(job.is_none());
 
         // 6. No more jobs should be available for untagged worker
-        let job = dequeue(&pool, &[]).await.expect("dequeue failed");
+        let job = dequeue(&pool, &[], &[]).await.expect("dequeue failed");
         assert!(job.is_none());
     }
 }
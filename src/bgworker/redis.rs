@@ -1,10 +1,19 @@
 /// Redis based background job queue provider
 use std::{
-    collections::HashMap, future::Future, panic::AssertUnwindSafe, pin::Pin, sync::Arc,
+    collections::{HashMap, HashSet},
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::Arc,
     time::Duration,
 };
 
-use super::{BackgroundWorker, JobStatus, Queue};
+use super::{
+    batch::{Batch, BatchJob},
+    middleware::JobInfo,
+    payload, perform_until_shutdown, worker_queues, BackgroundWorker, JobStatus, Placement, Queue,
+    QueueStats, Throttle, WorkerOpts, THROTTLE_BACKOFF,
+};
 use crate::{config::RedisQueueConfig, Error, Result};
 use chrono::{DateTime, Utc};
 use futures_util::FutureExt;
@@ -13,7 +22,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use tokio::{task::JoinHandle, time::sleep};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, trace, warn};
 use ulid::Ulid;
 
 pub type RedisPool = Client;
@@ -23,6 +32,14 @@ type JobData = JsonValue;
 const QUEUE_KEY_PREFIX: &str = "queue:";
 const JOB_KEY_PREFIX: &str = "job:";
 const PROCESSING_KEY_PREFIX: &str = "processing:";
+const SCHEDULED_KEY_PREFIX: &str = "scheduled:";
+const UNIQUE_KEY_PREFIX: &str = "unique:";
+const BATCH_KEY_PREFIX: &str = "batch:";
+const THROTTLE_KEY_PREFIX: &str = "throttle:";
+
+/// The time after which the concurrency slot of a job is freed, when its
+/// worker stopped without releasing it.
+const THROTTLE_SLOT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
 
 type JobHandler = Box<
     dyn Fn(
@@ -45,6 +62,12 @@ pub struct Job {
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
     pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throttle_key: Option<String>,
 }
 
 // Implementation for job creation and serialization
@@ -61,6 +84,9 @@ impl Job {
             created_at: Some(now),
             updated_at: Some(now),
             tags: None,
+            batch_id: None,
+            queue: None,
+            throttle_key: None,
         }
     }
 
@@ -77,6 +103,7 @@ impl Job {
 
 pub struct JobRegistry {
     handlers: Arc<HashMap<String, JobHandler>>,
+    throttles: Arc<HashMap<String, Throttle>>,
 }
 
 impl JobRegistry {
@@ -85,6 +112,7 @@ impl JobRegistry {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(HashMap::new()),
+            throttles: Arc::new(HashMap::new()),
         }
     }
 
@@ -124,6 +152,11 @@ impl JobRegistry {
                 }
             }) as Pin<Box<dyn Future<Output = Result<(), crate::Error>> + Send>>
         };
+        if let Some(throttle) = W::throttle() {
+            Arc::get_mut(&mut self.throttles)
+                .ok_or_else(|| Error::string("cannot register worker"))?
+                .insert(name.clone(), throttle);
+        }
         Arc::get_mut(&mut self.handlers)
             .ok_or_else(|| Error::string("cannot register worker"))?
             .insert(name, Box::new(wrapped_handler));
@@ -136,25 +169,37 @@ impl JobRegistry {
         &self.handlers
     }
 
-    /// Runs the job handlers with the provided number of workers.
+    /// Runs the job handlers with the provided number of workers, or with
+    /// the given number of workers for each queue.
     #[must_use]
     pub fn run(
         &self,
         client: &RedisPool,
         opts: &RunOpts,
         token: &CancellationToken,
-        tags: &[String],
+        workers: &WorkerOpts,
     ) -> Vec<JoinHandle<()>> {
         let mut jobs = Vec::new();
-        let queues = get_queues(&opts.queues);
+        let all_queues = get_queues(&opts.queues);
         let interval = opts.poll_interval_sec;
 
-        for idx in 0..opts.num_workers {
+        for (idx, queues) in worker_queues(opts.num_workers, &workers.concurrency)
+            .into_iter()
+            .enumerate()
+        {
             let handlers = self.handlers.clone();
+            let throttles = self.throttles.clone();
+            let middlewares = workers.middlewares.clone();
+            let grace_period = workers.grace_period;
             let worker_token = token.clone();
             let client = client.clone();
-            let queues = queues.clone();
-            let tags = tags.to_owned();
+            // A worker without queues processes every queue
+            let queues = if queues.is_empty() {
+                all_queues.clone()
+            } else {
+                queues
+            };
+            let tags = workers.tags.clone();
 
             let job = tokio::spawn(async move {
                 let mut conn = match client.get_multiplexed_async_connection().await {
@@ -183,7 +228,78 @@ impl JobRegistry {
                     if let Some((job, queue_name)) = job_opt {
                         debug!(job_id = job.id, name = job.name, "working on job");
                         if let Some(handler) = handlers.get(&job.name) {
-                            match handler(job.id.clone(), job.data.clone()).await {
+                            let throttle = throttles.get(&job.name).zip(job.throttle_key.as_ref());
+                            if let Some((throttle, throttle_key)) = throttle {
+                                match throttle_delay_with_conn(
+                                    &mut conn,
+                                    &job.id,
+                                    throttle_key,
+                                    throttle,
+                                )
+                                .await
+                                {
+                                    Ok(Some(delay)) => {
+                                        debug!(job_id = job.id, throttle_key = throttle_key, delay = ?delay, "job is throttled, rescheduling");
+                                        if let Err(err) = reschedule_job_with_conn(
+                                            &mut conn,
+                                            &job,
+                                            &queue_name,
+                                            delay,
+                                        )
+                                        .await
+                                        {
+                                            error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot reschedule throttled job");
+                                        }
+                                        continue;
+                                    }
+                                    Ok(None) => {}
+                                    Err(err) => {
+                                        error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot throttle job");
+                                    }
+                                }
+                            }
+                            let (args, perform) =
+                                payload::open_job(&job.data, |args| handler(job.id.clone(), args));
+                            let info = JobInfo {
+                                id: Some(job.id.clone()),
+                                name: job.name.clone(),
+                                queue: Some(queue_name.clone()),
+                                args,
+                            };
+                            let Some(result) = perform_until_shutdown(
+                                middlewares.perform(&info, perform),
+                                &worker_token,
+                                grace_period,
+                            )
+                            .await
+                            else {
+                                warn!(
+                                    job_id = job.id,
+                                    name = job.name,
+                                    "job interrupted by the shutdown, requeueing"
+                                );
+                                if let Err(err) = reschedule_job_with_conn(
+                                    &mut conn,
+                                    &job,
+                                    &queue_name,
+                                    Duration::ZERO,
+                                )
+                                .await
+                                {
+                                    error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot requeue interrupted job");
+                                }
+                                if let Some((Throttle::Concurrency { .. }, throttle_key)) = throttle
+                                {
+                                    if let Err(err) =
+                                        release_throttle_with_conn(&mut conn, &job.id, throttle_key)
+                                            .await
+                                    {
+                                        error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot release job throttle");
+                                    }
+                                }
+                                break;
+                            };
+                            let failed = match result {
                                 Ok(()) => {
                                     if let Err(err) = complete_job_with_conn(
                                         &mut conn,
@@ -193,16 +309,37 @@ impl JobRegistry {
                                     )
                                     .await
                                     {
-                                        error!(err = err.to_string(), job = ?job, "cannot complete job");
+                                        error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot complete job");
                                     }
+                                    false
                                 }
                                 Err(err) => {
                                     if let Err(err) =
                                         fail_job_with_conn(&mut conn, &job.id, &queue_name, &err)
                                             .await
                                     {
-                                        error!(err = err.to_string(), job = ?job, "cannot fail job");
+                                        error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot fail job");
                                     }
+                                    true
+                                }
+                            };
+                            if let Some(batch_id) = &job.batch_id {
+                                if let Err(err) =
+                                    settle_batch(&client, &mut conn, batch_id, failed).await
+                                {
+                                    error!(
+                                        err = err.to_string(),
+                                        batch_id = %batch_id,
+                                        "cannot settle job batch"
+                                    );
+                                }
+                            }
+                            if let Some((Throttle::Concurrency { .. }, throttle_key)) = throttle {
+                                if let Err(err) =
+                                    release_throttle_with_conn(&mut conn, &job.id, throttle_key)
+                                        .await
+                                {
+                                    error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot release job throttle");
                                 }
                             }
                         } else {
@@ -264,10 +401,32 @@ pub async fn enqueue(
     queue: Option<String>,
     args: impl serde::Serialize + Send,
     tags: Option<Vec<String>>,
+) -> Result<()> {
+    let placement = Placement {
+        queue: queue.unwrap_or_else(|| super::DEFAULT_QUEUE.to_string()),
+        ..Placement::default()
+    };
+    enqueue_with(client, class, &placement, args, tags, Utc::now()).await
+}
+
+/// Add a task to a named queue, the tasks with a positive priority going to
+/// the head of the queue. A task due later waits in the scheduled set of its
+/// queue, until a worker of the queue moves it to the queue. A unique task
+/// whose key is held by a pending task is not added.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_with(
+    client: &RedisPool,
+    class: String,
+    placement: &Placement,
+    args: impl serde::Serialize + Send,
+    tags: Option<Vec<String>>,
+    run_at: DateTime<Utc>,
 ) -> Result<()> {
     let mut conn = get_connection(client).await?;
-    let queue_name = queue.unwrap_or_else(|| "default".to_string());
-    let queue_key = format!("{QUEUE_KEY_PREFIX}{queue_name}");
+    let queue_key = format!("{QUEUE_KEY_PREFIX}{}", placement.queue);
 
     // Convert args to JSON
     let args_json = serde_json::to_value(args)?;
@@ -278,6 +437,10 @@ pub async fn enqueue(
     // Create job
     let mut job = Job::new(job_id.clone(), class, args_json);
     job.tags = tags;
+    job.run_at = run_at;
+    job.batch_id.clone_from(&placement.batch);
+    job.queue = Some(placement.queue.clone());
+    job.throttle_key.clone_from(&placement.throttle_key);
 
     // Serialize job for Redis storage
     let job_json = job.to_json()?;
@@ -285,11 +448,174 @@ pub async fn enqueue(
     // Store job in Redis queue and in job key
     let job_key = format!("{JOB_KEY_PREFIX}{}", job.id);
     let _: () = conn.set(&job_key, &job_json).await?;
-    let _: () = conn.rpush(&queue_key, &job.id).await?;
+
+    if let Some(key) = &placement.unique_key {
+        #[allow(clippy::cast_possible_truncation)]
+        let ttl_ms = placement.unique_for.map_or(0, |ttl| ttl.as_millis() as u64);
+        let pending: Option<String> = Script::new(UNIQUE_SCRIPT)
+            .key(format!("{UNIQUE_KEY_PREFIX}{key}"))
+            .arg(&job.id)
+            .arg(ttl_ms)
+            .invoke_async(&mut conn)
+            .await?;
+        if let Some(pending) = pending {
+            debug!(
+                job_id = pending,
+                unique_key = key,
+                "unique job is already pending"
+            );
+            let _: () = conn.del(&job_key).await?;
+            return Ok(());
+        }
+    }
+    if run_at > Utc::now() {
+        let scheduled_key = format!("{SCHEDULED_KEY_PREFIX}{}", placement.queue);
+        let _: () = conn
+            .zadd(&scheduled_key, &job.id, run_at.timestamp_millis())
+            .await?;
+    } else if placement.priority > 0 {
+        let _: () = conn.lpush(&queue_key, &job.id).await?;
+    } else {
+        let _: () = conn.rpush(&queue_key, &job.id).await?;
+    }
 
     Ok(())
 }
 
+/// Add the jobs of a batch, counted by the batch until they are done.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_batch(client: &RedisPool, batch: &Batch) -> Result<()> {
+    let mut conn = get_connection(client).await?;
+    let batch_key = format!("{BATCH_KEY_PREFIX}{}", batch.id());
+    let mut fields = vec![
+        ("pending", batch.jobs().count().to_string()),
+        ("failed", "0".to_string()),
+    ];
+    if let Some(callback) = batch.success_callback() {
+        fields.push(("on_success", serde_json::to_string(callback)?));
+    }
+    if let Some(callback) = batch.failure_callback() {
+        fields.push(("on_failure", serde_json::to_string(callback)?));
+    }
+    let _: () = conn.hset_multiple(&batch_key, &fields).await?;
+    for job in batch.jobs() {
+        enqueue_with(
+            client,
+            job.name.clone(),
+            &job.placement(Some(batch.id())),
+            &job.args,
+            job.tags.clone(),
+            Utc::now(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Counts a job of a batch as done, enqueueing the failure callback of the
+/// batch on its first failed job, and its success callback once every job
+/// completed.
+async fn settle_batch(
+    client: &RedisPool,
+    conn: &mut Connection,
+    batch_id: &str,
+    failed: bool,
+) -> Result<()> {
+    let callback: Option<String> = Script::new(SETTLE_BATCH_SCRIPT)
+        .key(format!("{BATCH_KEY_PREFIX}{batch_id}"))
+        .arg(i32::from(failed))
+        .invoke_async(conn)
+        .await?;
+    if let Some(callback) = callback {
+        let callback: BatchJob = serde_json::from_str(&callback)?;
+        debug!(
+            batch_id,
+            job_name = %callback.name,
+            "enqueueing batch callback"
+        );
+        enqueue_with(
+            client,
+            callback.name.clone(),
+            &callback.placement(None),
+            &callback.args,
+            callback.tags.clone(),
+            Utc::now(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+// Counts a job of a batch as done, returning the callback to enqueue, if any
+const SETTLE_BATCH_SCRIPT: &str = r#"
+local batch_key = KEYS[1]
+if redis.call('EXISTS', batch_key) == 0 then
+    return nil
+end
+local pending = redis.call('HINCRBY', batch_key, 'pending', -1)
+local failed = redis.call('HINCRBY', batch_key, 'failed', ARGV[1])
+local callback = false
+if ARGV[1] == '1' and failed == 1 then
+    callback = redis.call('HGET', batch_key, 'on_failure')
+elseif pending == 0 and failed == 0 then
+    callback = redis.call('HGET', batch_key, 'on_success')
+end
+if pending <= 0 then
+    redis.call('DEL', batch_key)
+end
+return callback
+"#;
+
+// Takes the key of a unique job, unless the job holding it is still pending,
+// returning the id of the pending job
+const UNIQUE_SCRIPT: &str = r#"
+local unique_key = KEYS[1]
+local job_id = redis.call('GET', unique_key)
+if job_id then
+    local job = redis.call('GET', 'job:' .. job_id)
+    if job then
+        local status = cjson.decode(job)['status']
+        if status == 'queued' or status == 'processing' then
+            return job_id
+        end
+    end
+end
+if ARGV[2] == '0' then
+    redis.call('SET', unique_key, ARGV[1])
+else
+    redis.call('SET', unique_key, ARGV[1], 'PX', ARGV[2])
+end
+return nil
+"#;
+
+// Counts a job against the rate of its throttle key, returning the
+// milliseconds before the key may run a job again, or -1 when it may run now
+const THROTTLE_RATE_SCRIPT: &str = r#"
+local count = redis.call('INCR', KEYS[1])
+if count == 1 then
+    redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+if count > tonumber(ARGV[1]) then
+    return math.max(redis.call('PTTL', KEYS[1]), 0)
+end
+return -1
+"#;
+
+// Takes a concurrency slot of a throttle key for a job, freeing the slots
+// held for too long first, returning 1 when the slot was taken
+const THROTTLE_SLOT_SCRIPT: &str = r#"
+local slots_key = KEYS[1]
+redis.call('ZREMRANGEBYSCORE', slots_key, '-inf', ARGV[3])
+if redis.call('ZSCORE', slots_key, ARGV[2]) or redis.call('ZCARD', slots_key) < tonumber(ARGV[1]) then
+    redis.call('ZADD', slots_key, ARGV[4], ARGV[2])
+    return 1
+end
+return 0
+"#;
+
 const DEQUEUE_SCRIPT: &str = r#"
 local queue_key = KEYS[1]
 local processing_key = KEYS[2]
@@ -307,6 +633,17 @@ else
 end
 "#;
 
+const PROMOTE_SCRIPT: &str = r#"
+local scheduled_key = KEYS[1]
+local queue_key = KEYS[2]
+local due = redis.call('ZRANGEBYSCORE', scheduled_key, '-inf', ARGV[1])
+for _, job_id in ipairs(due) do
+    redis.call('ZREM', scheduled_key, job_id)
+    redis.call('RPUSH', queue_key, job_id)
+end
+return #due
+"#;
+
 async fn dequeue_with_conn(
     conn: &mut Connection,
     queues: &[String],
@@ -317,12 +654,21 @@ async fn dequeue_with_conn(
     }
 
     let script = Script::new(DEQUEUE_SCRIPT);
+    let promote_script = Script::new(PROMOTE_SCRIPT);
 
     // Try to get a job from each queue in order (round-robin is more complex)
     for queue_name in queues {
         let queue_key = format!("{QUEUE_KEY_PREFIX}{queue_name}");
         let processing_key = format!("{PROCESSING_KEY_PREFIX}{queue_name}");
 
+        // Move the scheduled jobs which are due to the queue
+        let _: usize = promote_script
+            .key(format!("{SCHEDULED_KEY_PREFIX}{queue_name}"))
+            .key(&queue_key)
+            .arg(Utc::now().timestamp_millis())
+            .invoke_async(conn)
+            .await?;
+
         let job_id: Option<String> = script
             .key(&queue_key)
             .key(&processing_key)
@@ -374,6 +720,77 @@ async fn dequeue_with_conn(
     Ok(None)
 }
 
+/// The delay before a job throttled by `throttle_key` may run, `None` when it
+/// may run now, taking a concurrency slot of the key released by
+/// [`release_throttle_with_conn`] once the job is done.
+async fn throttle_delay_with_conn(
+    conn: &mut Connection,
+    id: &JobId,
+    throttle_key: &str,
+    throttle: &Throttle,
+) -> Result<Option<Duration>> {
+    match throttle {
+        Throttle::Rate { limit, per } => {
+            let wait_ms: i64 = Script::new(THROTTLE_RATE_SCRIPT)
+                .key(format!("{THROTTLE_KEY_PREFIX}{throttle_key}:rate"))
+                .arg(limit)
+                .arg(u64::try_from(per.as_millis()).unwrap_or(u64::MAX).max(1))
+                .invoke_async(conn)
+                .await?;
+            Ok(u64::try_from(wait_ms).ok().map(Duration::from_millis))
+        }
+        Throttle::Concurrency { limit } => {
+            let now = Utc::now().timestamp_millis();
+            let timeout_ms = i64::try_from(THROTTLE_SLOT_TIMEOUT.as_millis()).unwrap_or(i64::MAX);
+            let taken: bool = Script::new(THROTTLE_SLOT_SCRIPT)
+                .key(format!("{THROTTLE_KEY_PREFIX}{throttle_key}:slots"))
+                .arg(limit)
+                .arg(id)
+                .arg(now - timeout_ms)
+                .arg(now)
+                .invoke_async(conn)
+                .await?;
+            Ok((!taken).then_some(THROTTLE_BACKOFF))
+        }
+    }
+}
+
+/// Releases the concurrency slot of a throttle key taken by a job.
+async fn release_throttle_with_conn(
+    conn: &mut Connection,
+    id: &JobId,
+    throttle_key: &str,
+) -> Result<()> {
+    let _: () = conn
+        .zrem(format!("{THROTTLE_KEY_PREFIX}{throttle_key}:slots"), id)
+        .await?;
+    Ok(())
+}
+
+/// Puts a throttled or interrupted job back in the scheduled set of its
+/// queue, due once `delay` has elapsed.
+async fn reschedule_job_with_conn(
+    conn: &mut Connection,
+    job: &Job,
+    queue_name: &str,
+    delay: Duration,
+) -> Result<()> {
+    let mut job = job.clone();
+    job.run_at = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+    job.updated_at = Some(Utc::now());
+    let _: () = redis::pipe()
+        .set(format!("{JOB_KEY_PREFIX}{}", job.id), job.to_json()?)
+        .srem(format!("{PROCESSING_KEY_PREFIX}{queue_name}"), &job.id)
+        .zadd(
+            format!("{SCHEDULED_KEY_PREFIX}{queue_name}"),
+            &job.id,
+            job.run_at.timestamp_millis(),
+        )
+        .query_async(conn)
+        .await?;
+    Ok(())
+}
+
 async fn complete_job_with_conn(
     conn: &mut Connection,
     id: &JobId,
@@ -420,8 +837,13 @@ async fn fail_job_with_conn(
     let job_json: Option<String> = conn.get(&job_key).await?;
     if let Some(json) = job_json {
         if let Ok(mut job) = Job::from_json(&json) {
-            let error_json = serde_json::json!({ "error": error.to_string() });
-            job.data = error_json;
+            // Keep the arguments of the job, to be able to retry it
+            match job.data.as_object_mut() {
+                Some(data) => {
+                    data.insert("error".to_string(), error.to_string().into());
+                }
+                None => job.data = serde_json::json!({ "error": error.to_string() }),
+            }
             job.status = JobStatus::Failed;
             job.updated_at = Some(Utc::now());
             let updated_json = job.to_json()?;
@@ -820,6 +1242,118 @@ pub async fn requeue(client: &RedisPool, age_minutes: &i64) -> Result<()> {
     Ok(())
 }
 
+/// Counts the jobs of every queue by status.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn stats(client: &RedisPool) -> Result<Vec<QueueStats>> {
+    let mut conn = get_connection(client).await?;
+
+    // Jobs being processed are still marked as queued
+    let processing_keys: Vec<String> = redis::cmd("KEYS")
+        .arg(format!("{PROCESSING_KEY_PREFIX}*"))
+        .query_async(&mut conn)
+        .await?;
+    let mut processing = HashSet::new();
+    for processing_key in processing_keys {
+        let job_ids: Vec<String> = conn.smembers(&processing_key).await?;
+        processing.extend(job_ids);
+    }
+
+    let job_keys: Vec<String> = redis::cmd("KEYS")
+        .arg(format!("{JOB_KEY_PREFIX}*"))
+        .query_async(&mut conn)
+        .await?;
+    let mut counts = Vec::new();
+    for job_key in job_keys {
+        let job_json: Option<String> = conn.get(&job_key).await?;
+        if let Some(Ok(job)) = job_json.as_deref().map(Job::from_json) {
+            let status = if job.status == JobStatus::Queued && processing.contains(&job.id) {
+                JobStatus::Processing
+            } else {
+                job.status
+            };
+            let queue = job
+                .queue
+                .unwrap_or_else(|| super::DEFAULT_QUEUE.to_string());
+            counts.push((queue, status.to_string(), 1));
+        }
+    }
+    Ok(QueueStats::collect(counts))
+}
+
+/// Requeues a failed or cancelled job, dropping its error.
+///
+/// # Errors
+///
+/// Returns [`Error::NotFound`] when there is no failed or cancelled job with
+/// this id, or an error if it fails
+pub async fn retry_job(client: &RedisPool, id: &str) -> Result<()> {
+    let mut conn = get_connection(client).await?;
+    let job_key = format!("{JOB_KEY_PREFIX}{id}");
+    let job_json: Option<String> = conn.get(&job_key).await?;
+    let mut job = job_json
+        .as_deref()
+        .map(Job::from_json)
+        .transpose()?
+        .filter(|job| [JobStatus::Failed, JobStatus::Cancelled].contains(&job.status))
+        .ok_or(Error::NotFound)?;
+
+    let queue = job
+        .queue
+        .clone()
+        .unwrap_or_else(|| super::DEFAULT_QUEUE.to_string());
+    if let Some(data) = job.data.as_object_mut() {
+        data.remove("error");
+    }
+    job.status = JobStatus::Queued;
+    job.run_at = Utc::now();
+    job.updated_at = Some(Utc::now());
+
+    debug!(job_id = id, queue = queue, "retrying job");
+    let _: () = redis::pipe()
+        .set(&job_key, job.to_json()?)
+        .srem(format!("cancelled:{queue}"), id)
+        .srem(format!("failed:{queue}"), id)
+        .rpush(format!("{QUEUE_KEY_PREFIX}{queue}"), id)
+        .query_async(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Deletes a job, removing it from its queue.
+///
+/// # Errors
+///
+/// Returns [`Error::NotFound`] when there is no job with this id, or an error
+/// if it fails
+pub async fn delete_job(client: &RedisPool, id: &str) -> Result<()> {
+    let mut conn = get_connection(client).await?;
+    let job_key = format!("{JOB_KEY_PREFIX}{id}");
+    let job_json: Option<String> = conn.get(&job_key).await?;
+    let job = job_json
+        .as_deref()
+        .map(Job::from_json)
+        .transpose()?
+        .ok_or(Error::NotFound)?;
+
+    let queue = job
+        .queue
+        .unwrap_or_else(|| super::DEFAULT_QUEUE.to_string());
+    debug!(job_id = id, queue = queue, "deleting job");
+    let _: () = redis::pipe()
+        .del(&job_key)
+        .lrem(format!("{QUEUE_KEY_PREFIX}{queue}"), 0, id)
+        .srem(format!("{PROCESSING_KEY_PREFIX}{queue}"), id)
+        .zrem(format!("{SCHEDULED_KEY_PREFIX}{queue}"), id)
+        .srem(format!("cancelled:{queue}"), id)
+        .srem(format!("failed:{queue}"), id)
+        .query_async(&mut conn)
+        .await?;
+    Ok(())
+}
+
 /// Cancels jobs with the specified name in the Redis queue.
 ///
 /// This function updates the status of jobs that match the provided `job_name`
@@ -864,6 +1398,34 @@ pub async fn cancel_jobs_by_name(client: &RedisPool, job_name: &str) -> Result<(
             }
         }
     }
+
+    // Process the scheduled jobs, which are not queued yet
+    let scheduled_keys: Vec<String> = redis::cmd("KEYS")
+        .arg(format!("{SCHEDULED_KEY_PREFIX}*"))
+        .query_async(&mut conn)
+        .await?;
+    for scheduled_key in scheduled_keys {
+        let job_ids: Vec<String> = conn.zrange(&scheduled_key, 0, -1).await?;
+        for job_id in job_ids {
+            let job_key = format!("{JOB_KEY_PREFIX}{job_id}");
+            let job_json: Option<String> = conn.get(&job_key).await?;
+            if let Some(json) = job_json {
+                if let Ok(mut job) = Job::from_json(&json) {
+                    if job.name == job_name && job.status == JobStatus::Queued {
+                        job.status = JobStatus::Cancelled;
+                        job.updated_at = Some(Utc::now());
+                        let _: () = conn.zrem(&scheduled_key, &job_id).await?;
+                        let _: () = conn.set(&job_key, &job.to_json()?).await?;
+                        let cancelled_key = format!(
+                            "cancelled:{}",
+                            scheduled_key.trim_start_matches(SCHEDULED_KEY_PREFIX)
+                        );
+                        let _: () = conn.sadd(&cancelled_key, &job_id).await?;
+                    }
+                }
+            }
+        }
+    }
     Ok(())
 }
 
@@ -953,6 +1515,9 @@ mod tests {
                 created_at: Some(now - chrono::Duration::days(15)),
                 updated_at: Some(now - chrono::Duration::days(15)),
                 tags: None,
+                batch_id: None,
+                queue: None,
+                throttle_key: None,
             };
 
             let mut conn = get_connection(client).await?;
@@ -1294,7 +1859,7 @@ mod tests {
         };
 
         let token = CancellationToken::new();
-        let worker_handles = registry.run(&client, &opts, &token, &[] as &[String]);
+        let worker_handles = registry.run(&client, &opts, &token, &WorkerOpts::default());
 
         // Allow some time for job processing
         tokio::time::sleep(Duration::from_secs(2)).await;
@@ -1443,6 +2008,9 @@ mod tests {
             created_at: Some(Utc::now() - chrono::Duration::days(15)),
             updated_at: Some(Utc::now() - chrono::Duration::days(15)),
             tags: None,
+            batch_id: None,
+            queue: None,
+            throttle_key: None,
         };
 
         // Create an old completed job (older than 10 days)
@@ -1456,6 +2024,9 @@ mod tests {
             created_at: Some(Utc::now() - chrono::Duration::days(15)),
             updated_at: Some(Utc::now() - chrono::Duration::days(15)),
             tags: None,
+            batch_id: None,
+            queue: None,
+            throttle_key: None,
         };
 
         // Store both jobs directly
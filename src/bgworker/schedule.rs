@@ -0,0 +1,326 @@
+//! # Recurring Jobs
+//!
+//! A worker implementing [`Schedulable`] gets a job enqueued on every tick of
+//! its schedule, a cron expression or an English one such as `every 5
+//! minutes`. The schedules of [`crate::app::Hooks::schedules`] tick in every
+//! worker process, and the processes elect a leader for each tick through
+//! the cache: the first one claiming the tick enqueues its job, so that the
+//! job runs once per tick across the instances sharing a Redis cache. With
+//! an in-memory cache or none, every instance enqueues the job of every
+//! tick.
+//!
+//! The ticks are computed from the schedule rather than from the previous
+//! tick, so that they do not drift with the time spent enqueueing the jobs.
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use super::BackgroundWorker;
+use crate::{app::AppContext, config, scheduler, Error, Result};
+
+/// The time a tick stays claimed, longer than the clock skew of the
+/// instances.
+const CLAIM_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// A worker whose jobs are enqueued on a schedule.
+///
+/// # Example
+/// ```rust
+/// use loco_rs::prelude::*;
+/// use loco_rs::bgworker::schedule::Schedulable;
+///
+/// struct ReportWorker {
+///     ctx: AppContext,
+/// }
+///
+/// #[async_trait]
+/// impl BackgroundWorker<()> for ReportWorker {
+///     fn build(ctx: &AppContext) -> Self {
+///         Self { ctx: ctx.clone() }
+///     }
+///
+///     async fn perform(&self, _args: ()) -> Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// impl Schedulable<()> for ReportWorker {
+///     fn schedule() -> String {
+///         "every 5 minutes".to_string()
+///     }
+/// }
+/// ```
+pub trait Schedulable<A: Send + Sync + Serialize + Default + 'static>: BackgroundWorker<A> {
+    /// The schedule of the worker: a cron expression with seconds, such as
+    /// `0 */5 * * * *`, or an English one, such as `every 5 minutes`.
+    fn schedule() -> String;
+
+    /// The timezone of the schedule, such as `Europe/Paris`. UTC by default.
+    #[must_use]
+    fn timezone() -> Option<String> {
+        None
+    }
+
+    /// The arguments of the job enqueued on every tick.
+    #[must_use]
+    fn scheduled_args() -> A {
+        A::default()
+    }
+}
+
+type Enqueue =
+    Arc<dyn Fn(AppContext) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync>;
+
+/// The schedule of a [`Schedulable`] worker.
+#[derive(Clone)]
+pub struct Schedule {
+    name: String,
+    schedule: String,
+    timezone: Option<String>,
+    enqueue: Enqueue,
+}
+
+impl std::fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Schedule")
+            .field("name", &self.name)
+            .field("schedule", &self.schedule)
+            .field("timezone", &self.timezone)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Schedule {
+    /// The schedule of the worker `W`.
+    #[must_use]
+    pub fn of<W, A>() -> Self
+    where
+        W: Schedulable<A> + 'static,
+        A: Send + Sync + Serialize + Default + 'static,
+    {
+        Self {
+            name: W::class_name(),
+            schedule: W::schedule(),
+            timezone: W::timezone(),
+            enqueue: Arc::new(|ctx| {
+                Box::pin(async move { W::perform_later(&ctx, W::scheduled_args()).await })
+            }),
+        }
+    }
+
+    /// Explains the schedule with its next `count` runs after `from`, for
+    /// `cargo loco scheduler --explain`.
+    #[must_use]
+    pub fn explain(&self, from: DateTime<Utc>, count: usize) -> scheduler::Explanation {
+        scheduler::Explanation::new(
+            &self.name,
+            &self.schedule,
+            self.timezone.as_deref(),
+            from,
+            count,
+        )
+    }
+
+    /// Parses the schedule and its timezone.
+    fn ticks(&self) -> Result<Ticks> {
+        let (schedule, timezone) =
+            scheduler::parse_schedule(&self.name, &self.schedule, self.timezone.as_deref())?;
+        Ok(Ticks { schedule, timezone })
+    }
+
+    /// Enqueues the job of every tick claimed by this instance.
+    async fn tick(self, ctx: AppContext, ticks: Ticks) {
+        let Some(mut tick) = ticks.after(Utc::now()) else {
+            return;
+        };
+        loop {
+            if let Ok(delay) = (tick - Utc::now()).to_std() {
+                tokio::time::sleep(delay).await;
+            }
+            match claim(&ctx, &self.name, tick).await {
+                Ok(true) => {
+                    tracing::debug!(worker = self.name, tick = %tick, "enqueueing scheduled job");
+                    if let Err(err) = (self.enqueue)(ctx.clone()).await {
+                        tracing::error!(
+                            err = err.to_string(),
+                            worker = self.name,
+                            "cannot enqueue scheduled job"
+                        );
+                    }
+                }
+                Ok(false) => {
+                    tracing::trace!(worker = self.name, tick = %tick, "tick claimed by another instance");
+                }
+                Err(err) => {
+                    tracing::error!(
+                        err = err.to_string(),
+                        worker = self.name,
+                        "cannot claim scheduled job tick"
+                    );
+                }
+            }
+            // the ticks missed while enqueueing are skipped
+            let Some(next) = ticks.after(tick.max(Utc::now())) else {
+                return;
+            };
+            tick = next;
+        }
+    }
+}
+
+/// The schedules of the application, kept in the shared store of the
+/// context.
+#[derive(Clone, Default, Debug)]
+pub struct Schedules(pub Vec<Schedule>);
+
+/// A parsed schedule.
+struct Ticks {
+    schedule: cron::Schedule,
+    timezone: chrono_tz::Tz,
+}
+
+impl Ticks {
+    /// The first tick after `time`.
+    fn after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule
+            .after(&time.with_timezone(&self.timezone))
+            .next()
+            .map(|tick| tick.with_timezone(&Utc))
+    }
+}
+
+/// Whether the instances share the cache, so that they can claim the ticks
+/// in it: only a Redis cache is shared, an in-memory one is per process.
+fn is_shared(cache: &config::CacheConfig) -> bool {
+    #[cfg(feature = "cache_redis")]
+    {
+        matches!(cache, config::CacheConfig::Redis(_))
+    }
+    #[cfg(not(feature = "cache_redis"))]
+    {
+        let _ = cache;
+        false
+    }
+}
+
+/// Claims a tick of a worker, returning whether this instance is the first
+/// one to claim it. Without a shared cache, every instance claims every
+/// tick.
+async fn claim(ctx: &AppContext, name: &str, tick: DateTime<Utc>) -> Result<bool> {
+    if !is_shared(&ctx.config.cache) {
+        return Ok(true);
+    }
+    claim_in(&ctx.cache, name, tick).await
+}
+
+/// Claims a tick of a worker in the given cache.
+async fn claim_in(cache: &crate::cache::Cache, name: &str, tick: DateTime<Utc>) -> Result<bool> {
+    let key = format!("loco:schedule:{name}:{}", tick.timestamp());
+    Ok(cache.increment(&key, CLAIM_TTL).await? == 1)
+}
+
+/// Runs the schedules until the returned future is dropped.
+///
+/// # Errors
+///
+/// When a schedule or its timezone is invalid
+pub(crate) async fn run(ctx: AppContext, schedules: Vec<Schedule>) -> Result<()> {
+    let mut ticking = JoinSet::new();
+    let parsed = schedules
+        .into_iter()
+        .map(|schedule| Ok((schedule.ticks()?, schedule)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    if !is_shared(&ctx.config.cache) && !parsed.is_empty() {
+        tracing::warn!(
+            "the cache is not shared by the instances, every instance enqueues the scheduled \
+             jobs: configure a Redis cache to enqueue them once"
+        );
+    }
+    for (ticks, schedule) in parsed {
+        tracing::info!(
+            worker = schedule.name,
+            schedule = schedule.schedule,
+            "starting job schedule"
+        );
+        ticking.spawn(schedule.tick(ctx.clone(), ticks));
+    }
+    while ticking.join_next().await.is_some() {}
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    struct ReportWorker;
+
+    #[async_trait::async_trait]
+    impl BackgroundWorker<()> for ReportWorker {
+        fn build(_ctx: &AppContext) -> Self {
+            Self
+        }
+        async fn perform(&self, _args: ()) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Schedulable<()> for ReportWorker {
+        fn schedule() -> String {
+            "every 5 minutes".to_string()
+        }
+    }
+
+    #[test]
+    fn can_compute_ticks() {
+        let ticks = Schedule::of::<ReportWorker, ()>().ticks().unwrap();
+        let time = Utc.with_ymd_and_hms(2025, 1, 1, 10, 2, 30).unwrap();
+
+        let tick = ticks.after(time).unwrap();
+        assert_eq!(tick, Utc.with_ymd_and_hms(2025, 1, 1, 10, 5, 0).unwrap());
+        assert_eq!(
+            ticks.after(tick).unwrap(),
+            Utc.with_ymd_and_hms(2025, 1, 1, 10, 10, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_not_parse_an_invalid_schedule() {
+        let mut schedule = Schedule::of::<ReportWorker, ()>();
+        schedule.timezone = Some("Nowhere/Else".to_string());
+        assert!(schedule.ticks().is_err());
+
+        schedule.timezone = None;
+        schedule.schedule = "whenever".to_string();
+        assert!(schedule.ticks().is_err());
+    }
+
+    #[cfg(feature = "cache_inmem")]
+    #[tokio::test]
+    async fn can_claim_a_tick_once() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let tick = Utc::now();
+
+        assert!(claim_in(&ctx.cache, "ReportWorker", tick).await.unwrap());
+        assert!(!claim_in(&ctx.cache, "ReportWorker", tick).await.unwrap());
+        assert!(claim_in(&ctx.cache, "OtherWorker", tick).await.unwrap());
+    }
+
+    #[cfg(feature = "cache_inmem")]
+    #[tokio::test]
+    async fn can_claim_every_tick_without_a_shared_cache() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.config.cache =
+            config::CacheConfig::InMem(config::InMemCacheConfig { max_capacity: 100 });
+        let tick = Utc::now();
+
+        assert!(!is_shared(&ctx.config.cache));
+        assert!(claim(&ctx, "ReportWorker", tick).await.unwrap());
+        assert!(claim(&ctx, "ReportWorker", tick).await.unwrap());
+    }
+}
@@ -4,7 +4,12 @@ use std::{
     time::Duration,
 };
 
-use super::{BackgroundWorker, JobStatus, Queue};
+use super::{
+    batch::{Batch, BatchJob},
+    middleware::JobInfo,
+    payload, perform_until_shutdown, worker_queues, BackgroundWorker, JobStatus, Placement, Queue,
+    QueueStats, Throttle, WorkerOpts, THROTTLE_BACKOFF,
+};
 use crate::{config::SqliteQueueConfig, Error, Result};
 use chrono::{DateTime, Utc};
 use futures_util::FutureExt;
@@ -13,12 +18,12 @@ use serde_json::Value as JsonValue;
 pub use sqlx::SqlitePool;
 use sqlx::{
     sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
-    ConnectOptions, QueryBuilder, Row,
+    ConnectOptions, QueryBuilder, Row, SqliteConnection,
 };
 use std::fmt::Write;
 use tokio::{task::JoinHandle, time::sleep};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, trace, warn};
 use ulid::Ulid;
 type JobId = String;
 type JobData = JsonValue;
@@ -44,10 +49,15 @@ pub struct Job {
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
     pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throttle_key: Option<String>,
 }
 
 pub struct JobRegistry {
     handlers: Arc<HashMap<String, JobHandler>>,
+    throttles: Arc<HashMap<String, Throttle>>,
 }
 
 impl JobRegistry {
@@ -56,6 +66,7 @@ impl JobRegistry {
     pub fn new() -> Self {
         Self {
             handlers: Arc::new(HashMap::new()),
+            throttles: Arc::new(HashMap::new()),
         }
     }
 
@@ -95,6 +106,11 @@ impl JobRegistry {
             }) as Pin<Box<dyn Future<Output = Result<(), crate::Error>> + Send>>
         };
 
+        if let Some(throttle) = W::throttle() {
+            Arc::get_mut(&mut self.throttles)
+                .ok_or_else(|| Error::string("cannot register worker"))?
+                .insert(name.clone(), throttle);
+        }
         Arc::get_mut(&mut self.handlers)
             .ok_or_else(|| Error::string("cannot register worker"))?
             .insert(name, Box::new(wrapped_handler));
@@ -107,22 +123,29 @@ impl JobRegistry {
         &self.handlers
     }
 
-    /// Runs the job handlers with the provided number of workers.
+    /// Runs the job handlers with the provided number of workers, or with
+    /// the given number of workers for each queue.
     #[must_use]
     pub fn run(
         &self,
         pool: &SqlitePool,
         opts: &RunOpts,
         token: &CancellationToken,
-        tags: &[String],
+        workers: &WorkerOpts,
     ) -> Vec<JoinHandle<()>> {
         let mut jobs = Vec::new();
 
         let interval = opts.poll_interval_sec;
-        for idx in 0..opts.num_workers {
+        for (idx, queues) in worker_queues(opts.num_workers, &workers.concurrency)
+            .into_iter()
+            .enumerate()
+        {
             let handlers = self.handlers.clone();
+            let throttles = self.throttles.clone();
+            let middlewares = workers.middlewares.clone();
+            let grace_period = workers.grace_period;
             let worker_token = token.clone();
-            let worker_tags = tags.to_vec();
+            let worker_tags = workers.tags.clone();
 
             let pool = pool.clone();
             let job = tokio::spawn(async move {
@@ -136,7 +159,7 @@ impl JobRegistry {
                         worker_id = idx,
                         "Connection pool stats"
                     );
-                    let job_opt = match dequeue(&pool, &worker_tags).await {
+                    let job_opt = match dequeue(&pool, &worker_tags, &queues).await {
                         Ok(t) => t,
                         Err(err) => {
                             error!(error = %err, "Failed to fetch job from queue");
@@ -147,7 +170,64 @@ impl JobRegistry {
                     if let Some(job) = job_opt {
                         debug!(job_id = %job.id, job_name = %job.name, "Processing job");
                         if let Some(handler) = handlers.get(&job.name) {
-                            match handler(job.id.clone(), job.data.clone()).await {
+                            if let (Some(throttle), Some(throttle_key)) =
+                                (throttles.get(&job.name), &job.throttle_key)
+                            {
+                                match throttle_delay(&pool, &job.id, throttle_key, throttle).await {
+                                    Ok(Some(delay)) => {
+                                        debug!(job_id = %job.id, throttle_key = %throttle_key, delay = ?delay, "Job is throttled, rescheduling");
+                                        if let Err(err) =
+                                            reschedule_job(&pool, &job.id, delay).await
+                                        {
+                                            error!(
+                                                error = %err,
+                                                job_id = %job.id,
+                                                job_name = %job.name,
+                                                "Failed to reschedule throttled job"
+                                            );
+                                        }
+                                        continue;
+                                    }
+                                    Ok(None) => {}
+                                    Err(err) => {
+                                        error!(
+                                            error = %err,
+                                            job_id = %job.id,
+                                            job_name = %job.name,
+                                            "Failed to throttle job"
+                                        );
+                                    }
+                                }
+                            }
+                            let (args, perform) =
+                                payload::open_job(&job.data, |args| handler(job.id.clone(), args));
+                            let info = JobInfo {
+                                id: Some(job.id.clone()),
+                                name: job.name.clone(),
+                                queue: None,
+                                args,
+                            };
+                            let Some(result) = perform_until_shutdown(
+                                middlewares.perform(&info, perform),
+                                &worker_token,
+                                grace_period,
+                            )
+                            .await
+                            else {
+                                warn!(job_id = %job.id, job_name = %job.name, "Job interrupted by the shutdown, requeueing");
+                                if let Err(err) =
+                                    reschedule_job(&pool, &job.id, Duration::ZERO).await
+                                {
+                                    error!(
+                                        error = %err,
+                                        job_id = %job.id,
+                                        job_name = %job.name,
+                                        "Failed to requeue interrupted job"
+                                    );
+                                }
+                                break;
+                            };
+                            let failed = match result {
                                 Ok(()) => {
                                     if let Err(err) =
                                         complete_job(&pool, &job.id, job.interval).await
@@ -161,6 +241,7 @@ impl JobRegistry {
                                     } else {
                                         debug!(job_id = %job.id, "Job completed successfully");
                                     }
+                                    false
                                 }
                                 Err(err) => {
                                     if let Err(fail_err) = fail_job(&pool, &job.id, &err).await {
@@ -173,6 +254,17 @@ impl JobRegistry {
                                     } else {
                                         debug!(job_id = %job.id, error = %err, "Job execution failed");
                                     }
+                                    true
+                                }
+                            };
+                            if let Some(batch_id) = &job.batch_id {
+                                if let Err(err) = settle_batch(&pool, batch_id, failed).await {
+                                    error!(
+                                        error = %err,
+                                        job_id = %job.id,
+                                        batch_id = %batch_id,
+                                        "Failed to settle job batch"
+                                    );
                                 }
                             }
                         } else {
@@ -250,15 +342,75 @@ pub async fn initialize_database(pool: &SqlitePool) -> Result<()> {
 
             INSERT OR IGNORE INTO sqlt_loco_queue_lock (id, is_locked) VALUES (1, FALSE);
 
+            CREATE TABLE IF NOT EXISTS sqlt_loco_queue_unique (
+                key TEXT PRIMARY KEY,
+                job_id TEXT NOT NULL,
+                expires_at TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS sqlt_loco_queue_batches (
+                id TEXT PRIMARY KEY,
+                pending INTEGER NOT NULL,
+                failed INTEGER NOT NULL DEFAULT 0,
+                on_success JSON,
+                on_failure JSON,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS sqlt_loco_queue_throttles (
+                key TEXT PRIMARY KEY,
+                window_start INTEGER NOT NULL,
+                count INTEGER NOT NULL
+            );
+
             CREATE INDEX IF NOT EXISTS idx_sqlt_queue_status_run_at ON sqlt_loco_queue(status, run_at);
             ", JobStatus::Queued),
     )
     .execute(pool)
     .await?;
+
+    // add the named queues to the tables created before them
+    let has_queue: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('sqlt_loco_queue') WHERE name = 'queue'",
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_queue {
+        sqlx::raw_sql(&format!(
+            r"
+            ALTER TABLE sqlt_loco_queue ADD COLUMN queue TEXT NOT NULL DEFAULT '{}';
+            ALTER TABLE sqlt_loco_queue ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+            ",
+            super::DEFAULT_QUEUE
+        ))
+        .execute(pool)
+        .await?;
+    }
+    let has_batch: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('sqlt_loco_queue') WHERE name = 'batch_id'",
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_batch {
+        sqlx::query("ALTER TABLE sqlt_loco_queue ADD COLUMN batch_id TEXT")
+            .execute(pool)
+            .await?;
+    }
+    let has_throttle: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('sqlt_loco_queue') WHERE name = \
+         'throttle_key'",
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_throttle {
+        sqlx::query("ALTER TABLE sqlt_loco_queue ADD COLUMN throttle_key TEXT")
+            .execute(pool)
+            .await?;
+    }
     Ok(())
 }
 
-/// Add a job
+/// Add a job to the default queue
 ///
 /// # Errors
 ///
@@ -270,6 +422,100 @@ pub async fn enqueue(
     run_at: DateTime<Utc>,
     interval: Option<Duration>,
     tags: Option<Vec<String>>,
+) -> Result<JobId> {
+    enqueue_with(
+        pool,
+        name,
+        data,
+        run_at,
+        interval,
+        tags,
+        &Placement::default(),
+    )
+    .await
+}
+
+/// Add a job to a named queue, with a priority. A unique job whose key is
+/// held by a pending job is not added, returning the id of the pending job.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_with(
+    pool: &SqlitePool,
+    name: &str,
+    data: JobData,
+    run_at: DateTime<Utc>,
+    interval: Option<Duration>,
+    tags: Option<Vec<String>>,
+    placement: &Placement,
+) -> Result<JobId> {
+    #[allow(clippy::cast_possible_truncation)]
+    let interval_ms: Option<i64> = interval.map(|i| i.as_millis() as i64);
+
+    let mut tx = pool.begin().await?;
+    let id = insert_job(&mut tx, name, data, run_at, interval_ms, tags, placement).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Add the jobs of a batch, counted by the batch until they are done.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_batch(pool: &SqlitePool, batch: &Batch) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let jobs = batch.jobs().collect::<Vec<_>>();
+    debug!(
+        batch_id = batch.id(),
+        jobs = jobs.len(),
+        "Enqueueing job batch"
+    );
+    sqlx::query(
+        "INSERT INTO sqlt_loco_queue_batches (id, pending, on_success, on_failure) VALUES ($1, \
+         $2, $3, $4)",
+    )
+    .bind(batch.id())
+    .bind(i32::try_from(jobs.len()).unwrap_or(i32::MAX))
+    .bind(
+        batch
+            .success_callback()
+            .map(serde_json::to_value)
+            .transpose()?,
+    )
+    .bind(
+        batch
+            .failure_callback()
+            .map(serde_json::to_value)
+            .transpose()?,
+    )
+    .execute(&mut *tx)
+    .await?;
+    for job in jobs {
+        insert_job(
+            &mut tx,
+            &job.name,
+            job.args.clone(),
+            Utc::now(),
+            None,
+            job.tags.clone(),
+            &job.placement(Some(batch.id())),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn insert_job(
+    conn: &mut SqliteConnection,
+    name: &str,
+    data: JobData,
+    run_at: DateTime<Utc>,
+    interval_ms: Option<i64>,
+    tags: Option<Vec<String>>,
+    placement: &Placement,
 ) -> Result<JobId> {
     let data = serde_json::to_value(data)?;
     let tags_json = match &tags {
@@ -277,14 +523,47 @@ pub async fn enqueue(
         None => None,
     };
 
-    #[allow(clippy::cast_possible_truncation)]
-    let interval_ms: Option<i64> = interval.map(|i| i.as_millis() as i64);
-
     let id = Ulid::new().to_string();
-    debug!(job_id = %id, job_name = %name, run_at = %run_at, tags = ?tags, "Enqueueing job");
+    if let Some(key) = &placement.unique_key {
+        // the key of a job which is no longer pending, or held for too long, is free
+        sqlx::query(
+            "DELETE FROM sqlt_loco_queue_unique WHERE key = $1 AND (expires_at <= CURRENT_TIMESTAMP OR NOT EXISTS \
+             (SELECT 1 FROM sqlt_loco_queue WHERE id = job_id AND status IN ($2, $3)))",
+        )
+        .bind(key)
+        .bind(JobStatus::Queued.to_string())
+        .bind(JobStatus::Processing.to_string())
+        .execute(&mut *conn)
+        .await?;
+        let expires_at = placement
+            .unique_for
+            .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+            .map(|ttl| Utc::now() + ttl);
+        let taken = sqlx::query(
+            "INSERT INTO sqlt_loco_queue_unique (key, job_id, expires_at) VALUES ($1, $2, DATETIME($3)) ON \
+             CONFLICT (key) DO NOTHING",
+        )
+        .bind(key)
+        .bind(&id)
+        .bind(expires_at)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected()
+            > 0;
+        if !taken {
+            let pending: JobId =
+                sqlx::query_scalar("SELECT job_id FROM sqlt_loco_queue_unique WHERE key = $1")
+                    .bind(key)
+                    .fetch_one(&mut *conn)
+                    .await?;
+            debug!(job_id = %pending, job_name = %name, unique_key = %key, "Unique job is already pending");
+            return Ok(pending);
+        }
+    }
+    debug!(job_id = %id, job_name = %name, run_at = %run_at, tags = ?tags, queue = %placement.queue, priority = placement.priority, "Enqueueing job");
     sqlx::query(
-        "INSERT INTO sqlt_loco_queue (id, task_data, name, run_at, interval, tags) VALUES ($1, $2, $3, \
-         DATETIME($4), $5, $6)",
+        "INSERT INTO sqlt_loco_queue (id, task_data, name, run_at, interval, tags, queue, priority, \
+         batch_id, throttle_key) VALUES ($1, $2, $3, DATETIME($4), $5, $6, $7, $8, $9, $10)",
     )
     .bind(id.clone())
     .bind(data)
@@ -292,12 +571,69 @@ pub async fn enqueue(
     .bind(run_at)
     .bind(interval_ms)
     .bind(tags_json)
-    .execute(pool)
+    .bind(&placement.queue)
+    .bind(placement.priority)
+    .bind(&placement.batch)
+    .bind(&placement.throttle_key)
+    .execute(&mut *conn)
     .await?;
     Ok(id)
 }
 
-async fn dequeue(client: &SqlitePool, worker_tags: &[String]) -> Result<Option<Job>> {
+/// Counts a job of a batch as done, enqueueing the failure callback of the
+/// batch on its first failed job, and its success callback once every job
+/// completed.
+async fn settle_batch(pool: &SqlitePool, batch_id: &str, failed: bool) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let Some(row) = sqlx::query(
+        "UPDATE sqlt_loco_queue_batches SET pending = pending - 1, failed = failed + $2 WHERE id \
+         = $1 RETURNING pending, failed, on_success, on_failure",
+    )
+    .bind(batch_id)
+    .bind(i32::from(failed))
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(());
+    };
+    let pending: i32 = row.get("pending");
+    let failures: i32 = row.get("failed");
+    let callback: Option<JsonValue> = if failed && failures == 1 {
+        row.get("on_failure")
+    } else if pending == 0 && failures == 0 {
+        row.get("on_success")
+    } else {
+        None
+    };
+    if let Some(callback) = callback {
+        let callback: BatchJob = serde_json::from_value(callback)?;
+        debug!(batch_id, job_name = %callback.name, "Enqueueing batch callback");
+        insert_job(
+            &mut tx,
+            &callback.name,
+            callback.args.clone(),
+            Utc::now(),
+            None,
+            callback.tags.clone(),
+            &callback.placement(None),
+        )
+        .await?;
+    }
+    if pending <= 0 {
+        sqlx::query("DELETE FROM sqlt_loco_queue_batches WHERE id = $1")
+            .bind(batch_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn dequeue(
+    client: &SqlitePool,
+    worker_tags: &[String],
+    worker_queues: &[String],
+) -> Result<Option<Job>> {
     let mut tx = client.begin().await?;
 
     let acquired_write_lock = sqlx::query(
@@ -318,7 +654,7 @@ async fn dequeue(client: &SqlitePool, worker_tags: &[String]) -> Result<Option<J
 
     // Build the query with tag filtering
     let mut query = String::from(
-        "SELECT id, name, task_data, status, run_at, interval, tags
+        "SELECT id, name, task_data, status, run_at, interval, tags, batch_id, throttle_key
         FROM sqlt_loco_queue
         WHERE
             status = ? AND
@@ -346,7 +682,14 @@ async fn dequeue(client: &SqlitePool, worker_tags: &[String]) -> Result<Option<J
         }
     }
 
-    query.push_str(" ORDER BY run_at LIMIT 1");
+    // A worker without queues processes every queue
+    if !worker_queues.is_empty() {
+        query.push_str(" AND queue IN (");
+        query.push_str(&vec!["?"; worker_queues.len()].join(", "));
+        query.push(')');
+    }
+
+    query.push_str(" ORDER BY priority DESC, run_at LIMIT 1");
 
     let mut db_query = sqlx::query(&query).bind(JobStatus::Queued.to_string());
 
@@ -355,6 +698,9 @@ async fn dequeue(client: &SqlitePool, worker_tags: &[String]) -> Result<Option<J
         // Format tag for JSON string search: each tag needs to be in format "%\"tagname\"%"
         db_query = db_query.bind(format!("%\"{tag}\"%"));
     }
+    for queue in worker_queues {
+        db_query = db_query.bind(queue);
+    }
 
     let row = db_query
         .map(|row: SqliteRow| to_job(&row).ok())
@@ -402,6 +748,73 @@ async fn dequeue(client: &SqlitePool, worker_tags: &[String]) -> Result<Option<J
     }
 }
 
+/// The delay before a job throttled by `throttle_key` may run, `None` when it
+/// may run now. The rate of the jobs of a key is counted over fixed windows,
+/// and their concurrency by the jobs of the key being processed.
+async fn throttle_delay(
+    pool: &SqlitePool,
+    id: &JobId,
+    throttle_key: &str,
+    throttle: &Throttle,
+) -> Result<Option<Duration>> {
+    match throttle {
+        Throttle::Concurrency { limit } => {
+            let processing: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM sqlt_loco_queue WHERE throttle_key = $1 AND status = $2 AND id \
+                 <> $3",
+            )
+            .bind(throttle_key)
+            .bind(JobStatus::Processing.to_string())
+            .bind(id)
+            .fetch_one(pool)
+            .await?;
+            Ok((processing >= i64::from(*limit)).then_some(THROTTLE_BACKOFF))
+        }
+        Throttle::Rate { limit, per } => {
+            let now = Utc::now().timestamp_millis();
+            let per_ms = i64::try_from(per.as_millis()).unwrap_or(i64::MAX);
+            let row = sqlx::query(
+                "INSERT INTO sqlt_loco_queue_throttles (key, window_start, count) VALUES ($1, $2, 1) \
+                 ON CONFLICT (key) DO UPDATE SET count = CASE WHEN \
+                 sqlt_loco_queue_throttles.window_start <= $3 THEN 1 ELSE \
+                 sqlt_loco_queue_throttles.count + 1 END, window_start = CASE WHEN \
+                 sqlt_loco_queue_throttles.window_start <= $3 THEN $2 ELSE \
+                 sqlt_loco_queue_throttles.window_start END RETURNING count, window_start",
+            )
+            .bind(throttle_key)
+            .bind(now)
+            .bind(now.saturating_sub(per_ms))
+            .fetch_one(pool)
+            .await?;
+            let count: i64 = row.get("count");
+            let window_start: i64 = row.get("window_start");
+            if count <= i64::from(*limit) {
+                return Ok(None);
+            }
+            let wait_ms = window_start.saturating_add(per_ms).saturating_sub(now);
+            Ok(Some(Duration::from_millis(
+                u64::try_from(wait_ms).unwrap_or_default(),
+            )))
+        }
+    }
+}
+
+/// Puts a throttled or interrupted job back in the queue, due once `delay`
+/// has elapsed.
+async fn reschedule_job(pool: &SqlitePool, id: &JobId, delay: Duration) -> Result<()> {
+    let run_at = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+    sqlx::query(
+        "UPDATE sqlt_loco_queue SET status = $1, updated_at = CURRENT_TIMESTAMP, run_at = \
+         DATETIME($2) WHERE id = $3",
+    )
+    .bind(JobStatus::Queued.to_string())
+    .bind(run_at)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 async fn complete_job(pool: &SqlitePool, id: &JobId, interval_ms: Option<i64>) -> Result<()> {
     if let Some(interval_ms) = interval_ms {
         let next_run_at = Utc::now() + chrono::Duration::milliseconds(interval_ms);
@@ -483,6 +896,9 @@ pub async fn clear(pool: &SqlitePool) -> Result<()> {
         "
         DELETE FROM sqlt_loco_queue;
         DELETE FROM sqlt_loco_queue_lock;
+        DELETE FROM sqlt_loco_queue_unique;
+        DELETE FROM sqlt_loco_queue_batches;
+        DELETE FROM sqlt_loco_queue_throttles;
         ",
     )
     .execute(pool)
@@ -541,6 +957,69 @@ pub async fn requeue(pool: &SqlitePool, age_minutes: &i64) -> Result<()> {
     Ok(())
 }
 
+/// Counts the jobs of every queue by status.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn stats(pool: &SqlitePool) -> Result<Vec<QueueStats>> {
+    let rows = sqlx::query(
+        "SELECT queue, status, COUNT(*) AS count FROM sqlt_loco_queue GROUP BY queue, status",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(QueueStats::collect(rows.iter().map(|row| {
+        (
+            row.get("queue"),
+            row.get("status"),
+            u64::try_from(row.get::<i64, _>("count")).unwrap_or_default(),
+        )
+    })))
+}
+
+/// Requeues a failed or cancelled job, dropping its error.
+///
+/// # Errors
+///
+/// Returns [`Error::NotFound`] when there is no failed or cancelled job with
+/// this id, or an error if it fails
+pub async fn retry_job(pool: &SqlitePool, id: &str) -> Result<()> {
+    debug!(job_id = id, "retrying job");
+    let result = sqlx::query(
+        "UPDATE sqlt_loco_queue SET status = $1, run_at = CURRENT_TIMESTAMP, updated_at = \
+         CURRENT_TIMESTAMP, task_data = json_remove(task_data, '$.error') WHERE id = $2 AND \
+         status IN ($3, $4)",
+    )
+    .bind(JobStatus::Queued.to_string())
+    .bind(id)
+    .bind(JobStatus::Failed.to_string())
+    .bind(JobStatus::Cancelled.to_string())
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound);
+    }
+    Ok(())
+}
+
+/// Deletes a job.
+///
+/// # Errors
+///
+/// Returns [`Error::NotFound`] when there is no job with this id, or an error
+/// if it fails
+pub async fn delete_job(pool: &SqlitePool, id: &str) -> Result<()> {
+    debug!(job_id = id, "deleting job");
+    let result = sqlx::query("DELETE FROM sqlt_loco_queue WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound);
+    }
+    Ok(())
+}
+
 /// Deletes jobs from the `sqlt_loco_queue` table that are older than a specified number of days.
 ///
 /// This function removes jobs that have a `created_at` timestamp older than the provided
@@ -701,6 +1180,8 @@ fn to_job(row: &SqliteRow) -> Result<Job> {
         created_at: row.try_get("created_at").unwrap_or_default(),
         updated_at: row.try_get("updated_at").unwrap_or_default(),
         tags,
+        batch_id: row.try_get("batch_id").unwrap_or_default(),
+        throttle_key: row.try_get("throttle_key").unwrap_or_default(),
     })
 }
 
@@ -905,7 +1386,7 @@ mod tests {
 
         std::thread::sleep(std::time::Duration::from_secs(1));
 
-        assert!(dequeue(&pool, &[]).await.is_ok());
+        assert!(dequeue(&pool, &[], &[]).await.is_ok());
 
         let job_after_dequeue = get_all_jobs(&pool)
             .await
@@ -1337,7 +1818,7 @@ mod tests {
             poll_interval_sec: 1,
         };
         let token = CancellationToken::new();
-        let handles = registry.run(&pool, &opts, &token, &[]);
+        let handles = registry.run(&pool, &opts, &token, &WorkerOpts::default());
 
         // Wait a bit for the worker to process the job
         sleep(Duration::from_secs(1)).await;
@@ -1365,6 +1846,72 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn can_requeue_interrupted_jobs_on_shutdown() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        let job_id = enqueue(
+            &pool,
+            "SlowJob",
+            serde_json::json!(null),
+            Utc::now(),
+            None,
+            None,
+        )
+        .await
+        .expect("Failed to enqueue job");
+
+        struct SlowWorker;
+        #[async_trait::async_trait]
+        impl BackgroundWorker<()> for SlowWorker {
+            fn build(_ctx: &crate::app::AppContext) -> Self {
+                Self
+            }
+            async fn perform(&self, _args: ()) -> crate::Result<()> {
+                sleep(Duration::from_secs(60)).await;
+                Ok(())
+            }
+        }
+
+        let mut registry = JobRegistry::new();
+        assert!(registry
+            .register_worker("SlowJob".to_string(), SlowWorker)
+            .is_ok());
+
+        let opts = RunOpts {
+            num_workers: 1,
+            poll_interval_sec: 1,
+        };
+        let token = CancellationToken::new();
+        let handles = registry.run(
+            &pool,
+            &opts,
+            &token,
+            &WorkerOpts {
+                grace_period: Some(Duration::from_millis(100)),
+                ..WorkerOpts::default()
+            },
+        );
+
+        sleep(Duration::from_millis(500)).await;
+        assert_eq!(get_job(&pool, &job_id).await.status, JobStatus::Processing);
+
+        token.cancel();
+        for handle in handles {
+            assert!(handle.await.is_ok());
+        }
+
+        let job = get_job(&pool, &job_id).await;
+        assert_eq!(job.status, JobStatus::Queued);
+        assert!(job.run_at <= Utc::now());
+    }
+
     #[tokio::test]
     async fn can_dequeue_with_tags() {
         let tree_fs = tree_fs::TreeBuilder::default()
@@ -1435,7 +1982,7 @@ mod tests {
         assert_eq!(all_jobs.len(), 4);
 
         // 1. Worker with no tags should only get untagged jobs
-        let job = dequeue(&pool, &[]).await.expect("dequeue failed");
+        let job = dequeue(&pool, &[], &[]).await.expect("dequeue failed");
         assert!(job.is_some());
         let job = job.unwrap();
         assert_eq!(job.id, no_tag_id);
@@ -1447,7 +1994,7 @@ mod tests {
             .expect("Failed to complete job");
 
         // 2. Worker with "email" tag should get one of the email-tagged jobs
-        let job = dequeue(&pool, &["email".to_string()])
+        let job = dequeue(&pool, &["email".to_string()], &[])
             .await
             .expect("dequeue failed");
         assert!(job.is_some());
@@ -1465,7 +2012,7 @@ mod tests {
             .expect("Failed to complete job");
 
         // 3. Worker with "email" tag should get the remaining email job
-        let job = dequeue(&pool, &["email".to_string()])
+        let job = dequeue(&pool, &["email".to_string()], &[])
             .await
             .expect("dequeue failed");
         assert!(job.is_some());
@@ -1483,7 +2030,7 @@ mod tests {
             .expect("Failed to complete job");
 
         // 4. Worker with "sms" tag should get the sms job
-        let job = dequeue(&pool, &["sms".to_string()])
+        let job = dequeue(&pool, &["sms".to_string()], &[])
             .await
             .expect("dequeue failed");
         assert!(job.is_some());
@@ -1498,13 +2045,387 @@ mod tests {
             .expect("Failed to complete job");
 
         // 5. No more jobs should be available
-        let job = dequeue(&pool, &["email".to_string()])
+        let job = dequeue(&pool, &["email".to_string()], &[])
             .await
             .expect("dequeue failed");
         assert!(job.is_none());
 
         // 6. No more jobs should be available for untagged worker
-        let job = dequeue(&pool, &[]).await.expect("dequeue failed");
+        let job = dequeue(&pool, &[], &[]).await.expect("dequeue failed");
+        assert!(job.is_none());
+    }
+
+    #[tokio::test]
+    async fn can_dequeue_by_queue_and_priority() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        let run_at = Utc::now() - chrono::Duration::minutes(5);
+        let mut ids = vec![];
+        for (queue, priority) in [("default", 0), ("critical", 0), ("default", 10)] {
+            let placement = Placement {
+                queue: queue.to_string(),
+                priority,
+                ..Placement::default()
+            };
+            ids.push(
+                enqueue_with(
+                    &pool,
+                    "Notification",
+                    serde_json::json!({}),
+                    run_at,
+                    None,
+                    None,
+                    &placement,
+                )
+                .await
+                .expect("Failed to enqueue job"),
+            );
+        }
+
+        // a worker of the critical queue only gets its jobs
+        let job = dequeue(&pool, &[], &["critical".to_string()])
+            .await
+            .expect("dequeue failed");
+        assert_eq!(job.map(|job| job.id), Some(ids[1].clone()));
+        let job = dequeue(&pool, &[], &["critical".to_string()])
+            .await
+            .expect("dequeue failed");
         assert!(job.is_none());
+
+        // the jobs of a higher priority come first
+        let job = dequeue(&pool, &[], &["default".to_string()])
+            .await
+            .expect("dequeue failed");
+        assert_eq!(job.map(|job| job.id), Some(ids[2].clone()));
+        let job = dequeue(&pool, &[], &[]).await.expect("dequeue failed");
+        assert_eq!(job.map(|job| job.id), Some(ids[0].clone()));
+    }
+
+    #[tokio::test]
+    async fn can_defer_jobs_until_due() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        let scheduled_id = enqueue(
+            &pool,
+            "Reminder",
+            serde_json::json!({}),
+            Utc::now() + chrono::Duration::hours(1),
+            None,
+            None,
+        )
+        .await
+        .expect("Failed to enqueue scheduled job");
+        let due_id = enqueue(
+            &pool,
+            "Reminder",
+            serde_json::json!({}),
+            Utc::now() - chrono::Duration::minutes(1),
+            None,
+            None,
+        )
+        .await
+        .expect("Failed to enqueue due job");
+
+        let job = dequeue(&pool, &[], &[]).await.expect("dequeue failed");
+        assert_eq!(job.map(|job| job.id), Some(due_id));
+        assert!(dequeue(&pool, &[], &[])
+            .await
+            .expect("dequeue failed")
+            .is_none());
+        assert_eq!(
+            get_job(&pool, &scheduled_id).await.status,
+            JobStatus::Queued
+        );
+    }
+
+    #[tokio::test]
+    async fn can_enqueue_unique_jobs() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        let placement = Placement {
+            unique_key: Some("Reindex:user:42".to_string()),
+            ..Placement::default()
+        };
+        let run_at = Utc::now() - chrono::Duration::minutes(1);
+        let mut ids = vec![];
+        for _ in 0..2 {
+            ids.push(
+                enqueue_with(
+                    &pool,
+                    "Reindex",
+                    serde_json::json!({"user_id": 42}),
+                    run_at,
+                    None,
+                    None,
+                    &placement,
+                )
+                .await
+                .expect("Failed to enqueue job"),
+            );
+        }
+        assert_eq!(ids[0], ids[1]);
+        assert_eq!(get_all_jobs(&pool).await.len(), 1);
+
+        // the key is released once the job completes
+        complete_job(&pool, &ids[0], None)
+            .await
+            .expect("Failed to complete job");
+        let id = enqueue_with(
+            &pool,
+            "Reindex",
+            serde_json::json!({"user_id": 42}),
+            run_at,
+            None,
+            None,
+            &placement,
+        )
+        .await
+        .expect("Failed to enqueue job");
+        assert_ne!(id, ids[0]);
+        assert_eq!(get_all_jobs(&pool).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn can_settle_job_batches() {
+        struct Resize;
+        #[async_trait::async_trait]
+        impl BackgroundWorker<String> for Resize {
+            fn build(_ctx: &crate::app::AppContext) -> Self {
+                Self
+            }
+            async fn perform(&self, _image: String) -> crate::Result<()> {
+                Ok(())
+            }
+        }
+
+        struct Publish;
+        #[async_trait::async_trait]
+        impl BackgroundWorker<String> for Publish {
+            fn build(_ctx: &crate::app::AppContext) -> Self {
+                Self
+            }
+            async fn perform(&self, _album: String) -> crate::Result<()> {
+                Ok(())
+            }
+        }
+
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        let batch = |images: u32| {
+            (1..=images)
+                .try_fold(Batch::new(), |batch, image_id| {
+                    batch.with_job::<Resize, _>(format!("image-{image_id}.png"))
+                })
+                .and_then(|batch| batch.on_success::<Publish, _>("published".to_string()))
+                .and_then(|batch| batch.on_failure::<Publish, _>("failed".to_string()))
+                .expect("build batch")
+        };
+        let callbacks = |jobs: Vec<Job>| {
+            jobs.into_iter()
+                .filter(|job| job.name == "Publish")
+                .map(|job| job.data)
+                .collect::<Vec<_>>()
+        };
+
+        // the success callback is enqueued once every job completed
+        let succeeding = batch(2);
+        enqueue_batch(&pool, &succeeding)
+            .await
+            .expect("Failed to enqueue batch");
+        let jobs = get_all_jobs(&pool).await;
+        assert_eq!(jobs.len(), 2);
+        assert!(jobs
+            .iter()
+            .all(|job| job.batch_id.as_deref() == Some(succeeding.id())));
+
+        settle_batch(&pool, succeeding.id(), false)
+            .await
+            .expect("Failed to settle batch");
+        assert!(callbacks(get_all_jobs(&pool).await).is_empty());
+        settle_batch(&pool, succeeding.id(), false)
+            .await
+            .expect("Failed to settle batch");
+        assert_eq!(
+            callbacks(get_all_jobs(&pool).await),
+            vec![serde_json::json!("published")]
+        );
+
+        // the failure callback is enqueued once, on the first failed job
+        let failing = batch(3);
+        enqueue_batch(&pool, &failing)
+            .await
+            .expect("Failed to enqueue batch");
+        for failed in [true, false, true] {
+            settle_batch(&pool, failing.id(), failed)
+                .await
+                .expect("Failed to settle batch");
+        }
+        assert_eq!(
+            callbacks(get_all_jobs(&pool).await),
+            vec![serde_json::json!("published"), serde_json::json!("failed")]
+        );
+
+        let batches: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlt_loco_queue_batches")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(batches, 0);
+    }
+
+    #[tokio::test]
+    async fn can_throttle_jobs() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+
+        // at most 2 jobs a minute
+        let rate = Throttle::Rate {
+            limit: 2,
+            per: Duration::from_secs(60),
+        };
+        let id = "job".to_string();
+        for _ in 0..2 {
+            assert_eq!(
+                throttle_delay(&pool, &id, "Sync:account:1", &rate)
+                    .await
+                    .expect("Failed to throttle job"),
+                None
+            );
+        }
+        let delay = throttle_delay(&pool, &id, "Sync:account:1", &rate)
+            .await
+            .expect("Failed to throttle job")
+            .expect("the job is throttled");
+        assert!(delay <= Duration::from_secs(60));
+        // the keys are throttled separately
+        assert_eq!(
+            throttle_delay(&pool, &id, "Sync:account:2", &rate)
+                .await
+                .expect("Failed to throttle job"),
+            None
+        );
+
+        // at most 1 job processing at once
+        let concurrency = Throttle::Concurrency { limit: 1 };
+        let placement = Placement {
+            throttle_key: Some("Sync".to_string()),
+            ..Placement::default()
+        };
+        for _ in 0..2 {
+            enqueue_with(
+                &pool,
+                "Sync",
+                serde_json::json!({}),
+                Utc::now(),
+                None,
+                None,
+                &placement,
+            )
+            .await
+            .expect("Failed to enqueue job");
+        }
+        let first = dequeue(&pool, &[], &[])
+            .await
+            .expect("Failed to dequeue job")
+            .expect("a job is queued");
+        assert_eq!(first.throttle_key.as_deref(), Some("Sync"));
+        assert_eq!(
+            throttle_delay(&pool, &first.id, "Sync", &concurrency)
+                .await
+                .expect("Failed to throttle job"),
+            None
+        );
+
+        let second = dequeue(&pool, &[], &[])
+            .await
+            .expect("Failed to dequeue job")
+            .expect("a job is queued");
+        assert_eq!(
+            throttle_delay(&pool, &second.id, "Sync", &concurrency)
+                .await
+                .expect("Failed to throttle job"),
+            Some(THROTTLE_BACKOFF)
+        );
+        assert!(reschedule_job(&pool, &second.id, THROTTLE_BACKOFF)
+            .await
+            .is_ok());
+        assert_eq!(get_job(&pool, &second.id).await.status, JobStatus::Queued);
+    }
+
+    #[tokio::test]
+    async fn can_retry_and_delete_failed_jobs() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .create()
+            .expect("create temp folder");
+        let pool = init(&tree_fs.root).await;
+
+        assert!(initialize_database(&pool).await.is_ok());
+        tests_cfg::queue::sqlite_seed_data(&pool).await;
+
+        let failed = |stats: &[QueueStats]| {
+            stats
+                .iter()
+                .map(|queue| queue.count(&JobStatus::Failed))
+                .sum::<u64>()
+        };
+        let before = failed(&stats(&pool).await.expect("Failed to count jobs"));
+
+        let job_id = "01JDM0X8EVAM823JZBGKYNBA97";
+        assert!(fail_job(
+            &pool,
+            &job_id.to_string(),
+            &crate::Error::string("some error")
+        )
+        .await
+        .is_ok());
+        assert_eq!(
+            failed(&stats(&pool).await.expect("Failed to count jobs")),
+            before + 1
+        );
+
+        assert!(retry_job(&pool, job_id).await.is_ok());
+        let retried = get_job(&pool, job_id).await;
+        assert_eq!(retried.status, JobStatus::Queued);
+        assert!(retried.data.get("error").is_none());
+        assert!(matches!(
+            retry_job(&pool, job_id).await,
+            Err(Error::NotFound)
+        ));
+
+        assert!(delete_job(&pool, job_id).await.is_ok());
+        assert!(get_all_jobs(&pool).await.iter().all(|job| job.id != job_id));
+        assert!(matches!(
+            delete_job(&pool, job_id).await,
+            Err(Error::NotFound)
+        ));
     }
 }
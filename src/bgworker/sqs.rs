@@ -0,0 +1,591 @@
+/// AWS SQS based background job queue provider
+use std::{
+    collections::HashMap, future::Future, panic::AssertUnwindSafe, pin::Pin, sync::Arc,
+    time::Duration,
+};
+
+use super::{
+    middleware::JobInfo, payload, perform_until_shutdown, worker_queues, BackgroundWorker,
+    JobStatus, Placement, Queue, QueueStats, WorkerOpts,
+};
+use crate::{config::SqsQueueConfig, Error, Result};
+use aws_sdk_sqs::types::{Message, QueueAttributeName};
+use chrono::{DateTime, Utc};
+use futures_util::FutureExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tokio::{sync::RwLock, task::JoinHandle, time::sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, trace, warn};
+use ulid::Ulid;
+
+type JobId = String;
+type JobData = JsonValue;
+
+pub const DEFAULT_QUEUES: &[&str] = &["default", "mailer"];
+
+/// The longest delay of an SQS message.
+const MAX_DELAY: Duration = Duration::from_secs(15 * 60);
+
+/// The longest visibility timeout of an SQS message.
+const MAX_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(12 * 60 * 60);
+
+type JobHandler = Box<
+    dyn Fn(
+            JobId,
+            JobData,
+        ) -> Pin<Box<dyn std::future::Future<Output = Result<(), crate::Error>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// An SQS client, with the URLs of the queues it resolved.
+#[derive(Clone)]
+pub struct SqsClient {
+    client: aws_sdk_sqs::Client,
+    queue_prefix: String,
+    visibility_timeout: u32,
+    queue_urls: Arc<RwLock<HashMap<String, String>>>,
+}
+
+/// A job, as the body of an SQS message.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Job {
+    pub id: JobId,
+    pub name: String,
+    pub data: JobData,
+    pub run_at: DateTime<Utc>,
+    pub queue: String,
+    pub tags: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct JobRegistry {
+    handlers: Arc<HashMap<String, JobHandler>>,
+}
+
+impl JobRegistry {
+    /// Creates a new [`JobRegistry`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            handlers: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Registers a job handler with the provided name.
+    ///
+    /// # Errors
+    ///
+    /// Fails if cannot register worker
+    pub fn register_worker<Args, W>(&mut self, name: String, worker: W) -> Result<()>
+    where
+        Args: Send + Serialize + Sync + 'static,
+        W: BackgroundWorker<Args> + 'static,
+        for<'de> Args: Deserialize<'de>,
+    {
+        let worker = Arc::new(worker);
+        let wrapped_handler = move |_job_id: String, job_data: JobData| {
+            let w = worker.clone();
+            Box::pin(async move {
+                let args = serde_json::from_value::<Args>(job_data);
+                match args {
+                    Ok(args) => {
+                        // Wrap the perform call in catch_unwind to handle panics
+                        match AssertUnwindSafe(w.perform(args)).catch_unwind().await {
+                            Ok(result) => result,
+                            Err(panic) => {
+                                let panic_msg = panic
+                                    .downcast_ref::<String>()
+                                    .map(String::as_str)
+                                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                                    .unwrap_or("Unknown panic occurred");
+                                error!(err = panic_msg, "worker panicked");
+                                Err(Error::string(panic_msg))
+                            }
+                        }
+                    }
+                    Err(err) => Err(err.into()),
+                }
+            }) as Pin<Box<dyn Future<Output = Result<(), crate::Error>> + Send>>
+        };
+        Arc::get_mut(&mut self.handlers)
+            .ok_or_else(|| Error::string("cannot register worker"))?
+            .insert(name, Box::new(wrapped_handler));
+        Ok(())
+    }
+
+    /// Returns a reference to the job handlers.
+    #[must_use]
+    pub fn handlers(&self) -> &Arc<HashMap<String, JobHandler>> {
+        &self.handlers
+    }
+
+    /// Runs the job handlers with the provided number of workers, or with
+    /// the given number of workers for each queue.
+    ///
+    /// A failed job is left in its SQS queue, to be received again once its
+    /// visibility timeout elapsed, until the redrive policy of the queue moves
+    /// it to a dead-letter queue.
+    #[must_use]
+    pub fn run(
+        &self,
+        client: &SqsClient,
+        opts: &RunOpts,
+        token: &CancellationToken,
+        workers: &WorkerOpts,
+    ) -> Vec<JoinHandle<()>> {
+        let mut jobs = Vec::new();
+        let all_queues = get_queues(&opts.queues);
+        let interval = opts.poll_interval_sec;
+
+        for (idx, queues) in worker_queues(opts.num_workers, &workers.concurrency)
+            .into_iter()
+            .enumerate()
+        {
+            let handlers = self.handlers.clone();
+            let middlewares = workers.middlewares.clone();
+            let grace_period = workers.grace_period;
+            let worker_token = token.clone();
+            let client = client.clone();
+            // A worker without queues processes every queue
+            let queues = if queues.is_empty() {
+                all_queues.clone()
+            } else {
+                queues
+            };
+
+            let job = tokio::spawn(async move {
+                loop {
+                    if worker_token.is_cancelled() {
+                        trace!(worker_num = idx, "cancellation received, stopping worker");
+                        break;
+                    }
+
+                    let received = match receive(&client, &queues).await {
+                        Ok(received) => received,
+                        Err(err) => {
+                            error!(err = err.to_string(), "cannot fetch from queue");
+                            None
+                        }
+                    };
+
+                    if let Some((job, queue_url, receipt)) = received {
+                        if let Ok(delay) = (job.run_at - Utc::now()).to_std() {
+                            trace!(job_id = job.id, delay = ?delay, "job is not due yet");
+                            if let Err(err) = hide(&client, &queue_url, &receipt, delay).await {
+                                error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot delay job");
+                            }
+                            continue;
+                        }
+
+                        debug!(job_id = job.id, name = job.name, "working on job");
+                        let Some(handler) = handlers.get(&job.name) else {
+                            error!(job = job.name, "no handler found for job");
+                            continue;
+                        };
+                        let (args, perform) =
+                            payload::open_job(&job.data, |args| handler(job.id.clone(), args));
+                        let info = JobInfo {
+                            id: Some(job.id.clone()),
+                            name: job.name.clone(),
+                            queue: Some(job.queue.clone()),
+                            args,
+                        };
+                        let Some(result) = perform_until_shutdown(
+                            middlewares.perform(&info, perform),
+                            &worker_token,
+                            grace_period,
+                        )
+                        .await
+                        else {
+                            warn!(
+                                job_id = job.id,
+                                name = job.name,
+                                "job interrupted by the shutdown, requeueing"
+                            );
+                            if let Err(err) =
+                                hide(&client, &queue_url, &receipt, Duration::ZERO).await
+                            {
+                                error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot requeue interrupted job");
+                            }
+                            break;
+                        };
+                        match result {
+                            Ok(()) => {
+                                if let Err(err) = client
+                                    .client
+                                    .delete_message()
+                                    .queue_url(&queue_url)
+                                    .receipt_handle(&receipt)
+                                    .send()
+                                    .await
+                                {
+                                    error!(err = err.to_string(), job = ?payload::Redacted(&job), "cannot complete job");
+                                }
+                            }
+                            Err(err) => {
+                                error!(
+                                    err = err.to_string(),
+                                    job = ?payload::Redacted(&job),
+                                    "job failed, leaving it to be received again"
+                                );
+                            }
+                        }
+                    } else {
+                        tokio::select! {
+                            biased;
+                            () = worker_token.cancelled() => {
+                                trace!(worker_num = idx, "cancellation received during sleep, stopping worker");
+                                break;
+                            }
+                            () = sleep(Duration::from_secs(interval.into())) => {}
+                        }
+                    }
+                }
+            });
+            jobs.push(job);
+        }
+        jobs
+    }
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the URL of the SQS queue of `queue`, resolved once.
+async fn queue_url(client: &SqsClient, queue: &str) -> Result<String> {
+    if let Some(url) = client.queue_urls.read().await.get(queue) {
+        return Ok(url.clone());
+    }
+    let url = client
+        .client
+        .get_queue_url()
+        .queue_name(format!("{}{queue}", client.queue_prefix))
+        .send()
+        .await
+        .map_err(Error::wrap)?
+        .queue_url()
+        .ok_or_else(|| Error::string(&format!("no SQS queue for queue '{queue}'")))?
+        .to_string();
+    client
+        .queue_urls
+        .write()
+        .await
+        .insert(queue.to_string(), url.clone());
+    Ok(url)
+}
+
+/// Receives a job from the first of the queues holding one, with the URL of
+/// its queue and its receipt handle.
+async fn receive(client: &SqsClient, queues: &[String]) -> Result<Option<(Job, String, String)>> {
+    for queue in queues {
+        let url = queue_url(client, queue).await?;
+        let output = client
+            .client
+            .receive_message()
+            .queue_url(&url)
+            .max_number_of_messages(1)
+            .send()
+            .await
+            .map_err(Error::wrap)?;
+        let Some(message) = output.messages().first() else {
+            continue;
+        };
+        let Some(receipt) = message.receipt_handle() else {
+            continue;
+        };
+        match parse(message) {
+            Ok(job) => return Ok(Some((job, url, receipt.to_string()))),
+            Err(err) => {
+                // an unreadable message would be received forever
+                error!(
+                    err = err.to_string(),
+                    queue = queue,
+                    "cannot parse job, deleting it"
+                );
+                client
+                    .client
+                    .delete_message()
+                    .queue_url(&url)
+                    .receipt_handle(receipt)
+                    .send()
+                    .await
+                    .map_err(Error::wrap)?;
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn parse(message: &Message) -> Result<Job> {
+    Ok(serde_json::from_str(message.body().unwrap_or_default())?)
+}
+
+/// Hides a received job from the workers for `delay`, returning it to its
+/// queue right away without a delay.
+async fn hide(client: &SqsClient, queue_url: &str, receipt: &str, delay: Duration) -> Result<()> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let timeout = delay.min(MAX_VISIBILITY_TIMEOUT).as_secs() as i32;
+    client
+        .client
+        .change_message_visibility()
+        .queue_url(queue_url)
+        .receipt_handle(receipt)
+        .visibility_timeout(timeout)
+        .send()
+        .await
+        .map_err(Error::wrap)?;
+    Ok(())
+}
+
+/// Add a job to its queue, to be processed once `run_at` is due. A job due
+/// within 15 minutes is delayed by SQS, a later one is hidden again by the
+/// workers receiving it until it is due. The priority, unique key and
+/// throttle key of the placement are not supported by SQS, and ignored.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn enqueue_with(
+    client: &SqsClient,
+    class: String,
+    placement: &Placement,
+    args: impl serde::Serialize + Send,
+    tags: Option<Vec<String>>,
+    run_at: DateTime<Utc>,
+) -> Result<()> {
+    let job = Job {
+        id: Ulid::new().to_string(),
+        name: class,
+        data: serde_json::to_value(args)?,
+        run_at,
+        queue: placement.queue.clone(),
+        tags,
+        created_at: Utc::now(),
+    };
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let delay = (run_at - Utc::now())
+        .to_std()
+        .unwrap_or_default()
+        .min(MAX_DELAY)
+        .as_secs() as i32;
+    let url = queue_url(client, &placement.queue).await?;
+    client
+        .client
+        .send_message()
+        .queue_url(url)
+        .message_body(serde_json::to_string(&job)?)
+        .delay_seconds(delay)
+        .send()
+        .await
+        .map_err(Error::wrap)?;
+    Ok(())
+}
+
+/// Creates the SQS queues of the queues.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn initialize_queues(client: &SqsClient, queues: &Option<Vec<String>>) -> Result<()> {
+    for queue in get_queues(queues) {
+        let output = client
+            .client
+            .create_queue()
+            .queue_name(format!("{}{queue}", client.queue_prefix))
+            .attributes(
+                QueueAttributeName::VisibilityTimeout,
+                client.visibility_timeout.to_string(),
+            )
+            .send()
+            .await
+            .map_err(Error::wrap)?;
+        if let Some(url) = output.queue_url() {
+            client
+                .queue_urls
+                .write()
+                .await
+                .insert(queue, url.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Purges the SQS queues of the queues.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn clear(client: &SqsClient, queues: &Option<Vec<String>>) -> Result<()> {
+    for queue in get_queues(queues) {
+        let url = queue_url(client, &queue).await?;
+        client
+            .client
+            .purge_queue()
+            .queue_url(url)
+            .send()
+            .await
+            .map_err(Error::wrap)?;
+    }
+    Ok(())
+}
+
+/// Ping SQS
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn ping(client: &SqsClient) -> Result<()> {
+    client
+        .client
+        .list_queues()
+        .queue_name_prefix(&client.queue_prefix)
+        .max_results(1)
+        .send()
+        .await
+        .map_err(Error::wrap)?;
+    Ok(())
+}
+
+/// Counts the jobs of every queue, from the approximate numbers of messages
+/// of their SQS queues: the visible and delayed ones are queued, the hidden
+/// ones are processing.
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn stats(client: &SqsClient, queues: &Option<Vec<String>>) -> Result<Vec<QueueStats>> {
+    let mut rows = Vec::new();
+    for queue in get_queues(queues) {
+        let url = queue_url(client, &queue).await?;
+        let output = client
+            .client
+            .get_queue_attributes()
+            .queue_url(url)
+            .attribute_names(QueueAttributeName::ApproximateNumberOfMessages)
+            .attribute_names(QueueAttributeName::ApproximateNumberOfMessagesDelayed)
+            .attribute_names(QueueAttributeName::ApproximateNumberOfMessagesNotVisible)
+            .send()
+            .await
+            .map_err(Error::wrap)?;
+        let attributes = output.attributes();
+        let count = |name: QueueAttributeName| {
+            attributes
+                .and_then(|attributes| attributes.get(&name))
+                .and_then(|count| count.parse::<u64>().ok())
+                .unwrap_or_default()
+        };
+        rows.push((
+            queue.clone(),
+            JobStatus::Queued.to_string(),
+            count(QueueAttributeName::ApproximateNumberOfMessages)
+                + count(QueueAttributeName::ApproximateNumberOfMessagesDelayed),
+        ));
+        rows.push((
+            queue,
+            JobStatus::Processing.to_string(),
+            count(QueueAttributeName::ApproximateNumberOfMessagesNotVisible),
+        ));
+    }
+    Ok(QueueStats::collect(rows))
+}
+
+/// Returns the queues of the provider: the default queues, followed by the
+/// configured ones.
+#[must_use]
+pub fn get_queues(config_queues: &Option<Vec<String>>) -> Vec<String> {
+    let mut queues = DEFAULT_QUEUES
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    if let Some(config_queues) = config_queues {
+        for q in config_queues {
+            if !queues.iter().any(|aq| q == aq) {
+                queues.push(q.clone());
+            }
+        }
+    }
+    queues
+}
+
+pub struct RunOpts {
+    pub num_workers: u32,
+    pub poll_interval_sec: u32,
+    pub queues: Option<Vec<String>>,
+}
+
+/// Create this provider
+///
+/// # Errors
+///
+/// This function will return an error if it fails
+pub async fn create_provider(qcfg: &SqsQueueConfig) -> Result<Queue> {
+    let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+    if let Some(region) = &qcfg.region {
+        loader = loader.region(aws_config::Region::new(region.clone()));
+    }
+    if let Some(endpoint) = &qcfg.endpoint {
+        loader = loader.endpoint_url(endpoint);
+    }
+    let client = SqsClient {
+        client: aws_sdk_sqs::Client::new(&loader.load().await),
+        queue_prefix: qcfg.queue_prefix.clone(),
+        visibility_timeout: qcfg.visibility_timeout,
+        queue_urls: Arc::new(RwLock::new(HashMap::new())),
+    };
+    let run_opts = RunOpts {
+        num_workers: qcfg.num_workers,
+        poll_interval_sec: qcfg.poll_interval_sec,
+        queues: qcfg.queues.clone(),
+    };
+    debug!(
+        queues = ?qcfg.queues,
+        num_workers = qcfg.num_workers,
+        "creating SQS queue provider"
+    );
+    Ok(Queue::Sqs(
+        client,
+        Arc::new(tokio::sync::Mutex::new(JobRegistry::new())),
+        run_opts,
+        CancellationToken::new(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_get_queues() {
+        assert_eq!(get_queues(&None), vec!["default", "mailer"]);
+        assert_eq!(
+            get_queues(&Some(vec!["critical".to_string(), "default".to_string()])),
+            vec!["default", "mailer", "critical"]
+        );
+    }
+
+    #[test]
+    fn can_parse_job() {
+        let job = Job {
+            id: "01JDM0X8EVAM823JZBGKYNBA99".to_string(),
+            name: "EmailWorker".to_string(),
+            data: serde_json::json!({"user_id": 1}),
+            run_at: Utc::now(),
+            queue: "mailer".to_string(),
+            tags: None,
+            created_at: Utc::now(),
+        };
+        let message = Message::builder()
+            .body(serde_json::to_string(&job).unwrap())
+            .build();
+
+        let parsed = parse(&message).unwrap();
+        assert_eq!(parsed.id, job.id);
+        assert_eq!(parsed.data, job.data);
+        assert!(parse(&Message::builder().body("{").build()).is_err());
+    }
+}
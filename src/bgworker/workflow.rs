@@ -0,0 +1,375 @@
+//! # Workflows
+//!
+//! Chains steps into a workflow, each step receiving the output of the
+//! previous one, for flows such as ETL or provisioning which outlive a
+//! single job:
+//! ```rust,ignore
+//! impl Workflow for Provision {
+//!     type Input = Signup;
+//!     type Output = Tenant;
+//!
+//!     fn build(ctx: &AppContext) -> Self {
+//!         Self { ctx: ctx.clone() }
+//!     }
+//!
+//!     fn steps(&self) -> Steps<Signup, Tenant> {
+//!         Steps::new()
+//!             .then(CreateAccount { db: self.ctx.db.clone() })
+//!             .then(CreateDatabase)
+//!             .then(SendWelcome { mailer: self.ctx.mailer.clone() })
+//!     }
+//! }
+//!
+//! // in `Hooks::connect_workers`
+//! queue.register(WorkflowWorker::<Provision>::build(ctx)).await?;
+//!
+//! // then
+//! let run_id = Provision::start(&ctx, signup).await?;
+//! ```
+//!
+//! Every step runs as a job of the workflow's [`WorkflowWorker`], which
+//! enqueues the next step with the output of the step once it succeeds. The
+//! progress of a run lives in the queue: after a crash, requeueing the
+//! interrupted jobs with `cargo loco jobs requeue` resumes the runs at the
+//! step they were running. A failed step is retried up to its
+//! [`Step::retries`], with an exponential backoff, before failing its job and
+//! stopping the run.
+
+use std::{marker::PhantomData, time::Duration};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::BackgroundWorker;
+use crate::{app::AppContext, Error, Result};
+
+/// A step of a workflow, turning the output of the previous step into the
+/// input of the next one.
+#[async_trait]
+pub trait Step: Send + Sync + 'static {
+    type Input: DeserializeOwned + Send + 'static;
+    type Output: Serialize + Send + 'static;
+
+    /// The name of the step, in the logs.
+    fn name(&self) -> String {
+        let type_name = std::any::type_name::<Self>();
+        type_name
+            .split("::")
+            .last()
+            .unwrap_or(type_name)
+            .to_string()
+    }
+
+    /// The number of times the step is retried after failing.
+    fn retries(&self) -> u32 {
+        0
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output>;
+}
+
+/// The steps of a workflow taking an `I`, and returning the `O` of its last
+/// step.
+pub struct Steps<I, O> {
+    steps: Vec<Box<dyn AnyStep>>,
+    types: PhantomData<fn(I) -> O>,
+}
+
+impl<I> Steps<I, I> {
+    /// No steps yet.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            steps: vec![],
+            types: PhantomData,
+        }
+    }
+}
+
+impl<I> Default for Steps<I, I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, O> Steps<I, O> {
+    /// Adds a step, taking the output of the last step.
+    #[must_use]
+    pub fn then<S: Step<Input = O>>(mut self, step: S) -> Steps<I, S::Output> {
+        self.steps.push(Box::new(step));
+        Steps {
+            steps: self.steps,
+            types: PhantomData,
+        }
+    }
+
+    /// The number of steps.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Whether there are no steps.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// A [`Step`] taking and returning JSON.
+#[async_trait]
+trait AnyStep: Send + Sync {
+    fn name(&self) -> String;
+
+    fn retries(&self) -> u32;
+
+    async fn perform(&self, input: JsonValue) -> Result<JsonValue>;
+}
+
+#[async_trait]
+impl<S: Step> AnyStep for S {
+    fn name(&self) -> String {
+        Step::name(self)
+    }
+
+    fn retries(&self) -> u32 {
+        Step::retries(self)
+    }
+
+    async fn perform(&self, input: JsonValue) -> Result<JsonValue> {
+        let input = serde_json::from_value(input)?;
+        Ok(serde_json::to_value(Step::perform(self, input).await?)?)
+    }
+}
+
+/// A chain of steps, run one job at a time.
+#[async_trait]
+pub trait Workflow: Send + Sync + Sized + 'static {
+    type Input: Serialize + Send + Sync + 'static;
+    type Output: 'static;
+
+    fn build(ctx: &AppContext) -> Self;
+
+    fn steps(&self) -> Steps<Self::Input, Self::Output>;
+
+    /// The name of the workflow, the class name of its jobs.
+    #[must_use]
+    fn name() -> String {
+        use heck::ToUpperCamelCase;
+        let type_name = std::any::type_name::<Self>();
+        let name = type_name.split("::").last().unwrap_or(type_name);
+        name.to_upper_camel_case()
+    }
+
+    /// Starts a run of the workflow, returning the id of the run.
+    ///
+    /// # Errors
+    ///
+    /// When the first step could not be enqueued, or a step failed in the
+    /// `ForegroundBlocking` mode
+    async fn start(ctx: &AppContext, input: Self::Input) -> Result<String> {
+        let run = WorkflowRun {
+            id: uuid::Uuid::now_v7().to_string(),
+            step: 0,
+            attempt: 0,
+            input: serde_json::to_value(input)?,
+        };
+        let id = run.id.clone();
+        WorkflowWorker::<Self>::perform_later(ctx, run).await?;
+        Ok(id)
+    }
+}
+
+/// The job of a step of a run: the input of the step, and how many times it
+/// failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkflowRun {
+    pub id: String,
+    pub step: usize,
+    pub attempt: u32,
+    pub input: JsonValue,
+}
+
+/// The delay before retrying a step which failed `attempt` times.
+#[must_use]
+pub fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_secs(2u64.saturating_pow(attempt.min(16)))
+}
+
+/// The worker running the steps of a workflow, to register in
+/// `Hooks::connect_workers`.
+pub struct WorkflowWorker<W: Workflow> {
+    ctx: AppContext,
+    workflow: W,
+}
+
+#[async_trait]
+impl<W: Workflow> BackgroundWorker<WorkflowRun> for WorkflowWorker<W> {
+    fn build(ctx: &AppContext) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            workflow: W::build(ctx),
+        }
+    }
+
+    fn class_name() -> String {
+        W::name()
+    }
+
+    async fn perform(&self, run: WorkflowRun) -> Result<()> {
+        let steps = self.workflow.steps();
+        let step = steps.steps.get(run.step).ok_or_else(|| {
+            Error::Message(format!("workflow `{}` has no step {}", W::name(), run.step))
+        })?;
+        tracing::debug!(
+            workflow = W::name(),
+            run_id = %run.id,
+            step = step.name(),
+            attempt = run.attempt,
+            "performing workflow step"
+        );
+        match step.perform(run.input.clone()).await {
+            Ok(output) if run.step + 1 < steps.len() => {
+                let next = WorkflowRun {
+                    step: run.step + 1,
+                    attempt: 0,
+                    input: output,
+                    ..run
+                };
+                Self::perform_later(&self.ctx, next).await
+            }
+            Ok(_) => {
+                tracing::info!(workflow = W::name(), run_id = %run.id, "workflow completed");
+                Ok(())
+            }
+            Err(err) if run.attempt < step.retries() => {
+                let delay = retry_delay(run.attempt);
+                tracing::warn!(
+                    workflow = W::name(),
+                    run_id = %run.id,
+                    step = step.name(),
+                    attempt = run.attempt,
+                    retry_in_secs = delay.as_secs(),
+                    err = err.to_string(),
+                    "workflow step failed, retrying"
+                );
+                let retry = WorkflowRun {
+                    attempt: run.attempt + 1,
+                    ..run
+                };
+                Self::perform_in(&self.ctx, retry, delay).await
+            }
+            Err(err) => {
+                tracing::error!(
+                    workflow = W::name(),
+                    run_id = %run.id,
+                    step = step.name(),
+                    err = err.to_string(),
+                    "workflow step failed, stopping the run"
+                );
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    };
+
+    use super::*;
+    use crate::tests_cfg;
+
+    struct Parse;
+
+    #[async_trait]
+    impl Step for Parse {
+        type Input = String;
+        type Output = u64;
+
+        async fn perform(&self, input: String) -> Result<u64> {
+            input
+                .parse()
+                .map_err(|_| Error::Message(format!("`{input}` is not a number")))
+        }
+    }
+
+    /// Fails the first time it runs.
+    struct Double {
+        attempts: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl Step for Double {
+        type Input = u64;
+        type Output = u64;
+
+        fn retries(&self) -> u32 {
+            1
+        }
+
+        async fn perform(&self, input: u64) -> Result<u64> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(Error::string("flaky"));
+            }
+            Ok(input * 2)
+        }
+    }
+
+    struct Record {
+        results: Arc<Mutex<Vec<u64>>>,
+    }
+
+    #[async_trait]
+    impl Step for Record {
+        type Input = u64;
+        type Output = ();
+
+        async fn perform(&self, input: u64) -> Result<()> {
+            self.results.lock().unwrap().push(input);
+            Ok(())
+        }
+    }
+
+    static ATTEMPTS: std::sync::OnceLock<Arc<AtomicU32>> = std::sync::OnceLock::new();
+    static RESULTS: std::sync::OnceLock<Arc<Mutex<Vec<u64>>>> = std::sync::OnceLock::new();
+
+    struct Compute;
+
+    impl Workflow for Compute {
+        type Input = String;
+        type Output = ();
+
+        fn build(_ctx: &AppContext) -> Self {
+            Self
+        }
+
+        fn steps(&self) -> Steps<String, ()> {
+            Steps::new()
+                .then(Parse)
+                .then(Double {
+                    attempts: ATTEMPTS.get_or_init(Arc::default).clone(),
+                })
+                .then(Record {
+                    results: RESULTS.get_or_init(Arc::default).clone(),
+                })
+        }
+    }
+
+    #[tokio::test]
+    async fn can_run_workflow_steps_with_retries() {
+        let ctx = tests_cfg::app::get_app_context().await;
+
+        assert!(Compute::start(&ctx, "21".to_string()).await.is_ok());
+        assert_eq!(*RESULTS.get().unwrap().lock().unwrap(), vec![42]);
+        assert_eq!(ATTEMPTS.get().unwrap().load(Ordering::SeqCst), 2);
+
+        assert!(Compute::start(&ctx, "twenty".to_string()).await.is_err());
+        assert_eq!(*RESULTS.get().unwrap().lock().unwrap(), vec![42]);
+        assert_eq!(WorkflowWorker::<Compute>::class_name(), "Compute");
+    }
+}
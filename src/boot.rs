@@ -13,24 +13,34 @@ use sea_orm_migration::MigratorTrait;
 use tokio::{select, signal, task::JoinHandle};
 use tracing::{debug, error, info, warn};
 
-#[cfg(feature = "with-db")]
-use crate::db;
 use crate::{
     app::{AppContext, Hooks, Initializer},
     banner::print_banner,
-    bgworker, cache,
+    bgworker::{
+        self,
+        async_queue::AsyncQueue,
+        middleware::JobMiddlewares,
+        schedule::{self, Schedules},
+    },
+    cache,
     config::{self, Config, WorkerMode},
-    controller::ListRoutes,
+    controller::{monitoring::HealthChecks, ListRoutes},
     env_vars,
     environment::Environment,
     errors::Error,
-    mailer::{EmailSender, MailerWorker},
+    mailer::{
+        suppression::SuppressionHandlers, EmailSender, MailerPreviews, MailerWorker, StubDeliveries,
+    },
     prelude::BackgroundWorker,
     scheduler::{self, Scheduler},
-    storage::{self, Storage},
+    storage::Storage,
     task::{self, Tasks},
     Result,
 };
+#[cfg(feature = "with-db")]
+use crate::{
+    db, listener::Listeners, outbox::Relay, seeder::Seeders, tenancy::TenantAuthorization,
+};
 
 /// Represents the application startup mode.
 #[derive(Debug)]
@@ -94,6 +104,47 @@ pub async fn start<H: Hooks>(
         });
     }
 
+    #[cfg(feature = "with-db")]
+    let listener = {
+        let mut listeners = Listeners::default();
+        H::register_listeners(&mut listeners);
+        listeners.start(&boot.app_context).await?
+    };
+
+    #[cfg(feature = "with-db")]
+    let outbox_relay = boot
+        .app_context
+        .config
+        .database
+        .outbox
+        .as_ref()
+        .map(|config| {
+            let mut relay = Relay::default();
+            H::register_outbox(&mut relay);
+            relay.start(&boot.app_context, config)
+        });
+
+    #[cfg(feature = "with-db")]
+    let pool_watcher = boot
+        .app_context
+        .config
+        .database
+        .pool_stats_interval
+        .map(|interval| {
+            db::watch_pool::<H>(&boot.app_context, std::time::Duration::from_secs(interval))
+        });
+
+    #[cfg(feature = "with-db")]
+    let backup_watcher = boot
+        .app_context
+        .config
+        .database
+        .sqlite
+        .backup
+        .as_ref()
+        .filter(|_| boot.app_context.config.database.uri.starts_with("sqlite:"))
+        .map(|config| db::watch_sqlite_backups::<H>(&boot.app_context, config));
+
     if !no_banner {
         print_banner(&boot, &server_config);
     }
@@ -110,7 +161,7 @@ pub async fn start<H: Hooks>(
             H::serve(router, &app_context, &server_config).await?;
         }
         (Some(router), Some(tags)) => {
-            let handle = if app_context.config.workers.mode == WorkerMode::BackgroundQueue {
+            let handle = if app_context.config.workers.uses_queue() {
                 Some(start_queue_worker(&app_context, tags)?)
             } else {
                 None
@@ -123,7 +174,7 @@ pub async fn start<H: Hooks>(
             }
         }
         (None, Some(tags)) => {
-            let handle = if app_context.config.workers.mode == WorkerMode::BackgroundQueue {
+            let handle = if app_context.config.workers.uses_queue() {
                 Some(start_queue_worker(&app_context, tags)?)
             } else {
                 None
@@ -137,6 +188,31 @@ pub async fn start<H: Hooks>(
         }
         _ => {}
     }
+
+    if let Some(queue) = app_context.shared_store.get::<AsyncQueue>() {
+        queue
+            .shutdown(app_context.config.worker_grace_period())
+            .await?;
+    }
+
+    #[cfg(feature = "with-db")]
+    if let Some(listener) = listener {
+        listener.abort();
+    }
+    #[cfg(feature = "with-db")]
+    if let Some(outbox_relay) = outbox_relay {
+        outbox_relay.abort();
+    }
+    #[cfg(feature = "with-db")]
+    if let Some(pool_watcher) = pool_watcher {
+        pool_watcher.abort();
+    }
+    #[cfg(feature = "with-db")]
+    if let Some(backup_watcher) = backup_watcher {
+        backup_watcher.abort();
+    }
+
+    H::on_shutdown(&app_context).await;
     Ok(())
 }
 
@@ -145,10 +221,30 @@ fn start_queue_worker(app_context: &AppContext, tags: Vec<String>) -> Result<Joi
 
     if let Some(queue) = &app_context.queue_provider {
         let cloned_queue = queue.clone();
+        let concurrency = app_context.config.workers.queues.clone();
+        let grace_period = app_context.config.worker_grace_period();
+        let middlewares = app_context
+            .shared_store
+            .get::<JobMiddlewares>()
+            .unwrap_or_default();
+        let schedules = app_context
+            .shared_store
+            .get::<Schedules>()
+            .unwrap_or_default();
+        let ctx = app_context.clone();
         let handle = tokio::spawn(async move {
-            if let Err(err) = cloned_queue.run(tags).await {
+            let ticking = tokio::spawn(async move {
+                if let Err(err) = schedule::run(ctx, schedules.0).await {
+                    error!(err = err.to_string(), "error while running job schedules");
+                }
+            });
+            if let Err(err) = cloned_queue
+                .run_queues(tags, &concurrency, &middlewares, Some(grace_period))
+                .await
+            {
                 error!(err = err.to_string(), "error while running worker");
             }
+            ticking.abort();
         });
         return Ok(handle);
     }
@@ -156,6 +252,12 @@ fn start_queue_worker(app_context: &AppContext, tags: Vec<String>) -> Result<Joi
     Err(Error::QueueProviderMissing)
 }
 
+/// The time given to the workers to requeue their interrupted jobs, after
+/// their grace period.
+const REQUEUE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Stops the workers from dequeueing jobs, and waits for the running jobs to
+/// finish, or to be requeued once the grace period of the workers elapsed.
 async fn shutdown_and_await_queue_worker(
     app_context: &AppContext,
     handle: JoinHandle<()>,
@@ -165,9 +267,13 @@ async fn shutdown_and_await_queue_worker(
     }
 
     println!("press ctrl-c again to force quit");
+    let deadline = app_context.config.worker_grace_period() + REQUEUE_TIMEOUT;
     select! {
         _ = handle => {}
         () = shutdown_signal() => {}
+        () = tokio::time::sleep(deadline) => {
+            tracing::warn!("shutdown timeout elapsed, dropping the running jobs");
+        }
     }
     Ok(())
 }
@@ -227,7 +333,9 @@ fn scheduler<H: Hooks>(
 }
 
 /// Runs the scheduler with the given configuration and context. in case if list
-/// args is true prints scheduler job configuration
+/// args is true prints scheduler job configuration, and with `explain`,
+/// prints the given number of next runs of every job, failing when a
+/// schedule is invalid.
 ///
 /// This function initializes the scheduler, registers tasks through the
 /// provided [`Hooks`], and executes the scheduler based on the specified
@@ -245,14 +353,66 @@ pub async fn run_scheduler<H: Hooks>(
     name: Option<String>,
     tag: Option<String>,
     list: bool,
+    history: bool,
+    explain: Option<usize>,
 ) -> Result<()> {
     let task_span = tracing::span!(tracing::Level::DEBUG, "scheduler_jobs");
     let _guard = task_span.enter();
 
-    let scheduler = scheduler::<H>(app_context, config, name, tag)?;
+    let scheduler = scheduler::<H>(app_context, config, name.clone(), tag.clone());
+    if let Some(count) = explain {
+        let now = chrono::Utc::now();
+        // the workers may be scheduled without a scheduler configuration
+        let mut explanations = match scheduler {
+            Ok(scheduler) => scheduler.explain(now, count),
+            Err(Error::Scheduler(scheduler::Error::Empty)) => vec![],
+            Err(err) => return Err(err),
+        };
+        // the schedules of the workers have no tags
+        if tag.is_none() {
+            let schedules = app_context
+                .shared_store
+                .get::<bgworker::schedule::Schedules>()
+                .unwrap_or_default();
+            explanations.extend(
+                schedules
+                    .0
+                    .iter()
+                    .map(|schedule| schedule.explain(now, count))
+                    .filter(|explanation| {
+                        name.as_ref().map_or(true, |name| *name == explanation.job)
+                    }),
+            );
+        }
+        if explanations.is_empty() {
+            return Err(Error::Scheduler(scheduler::Error::Empty));
+        }
+        for explanation in &explanations {
+            println!("{explanation}");
+        }
+        let invalid = explanations
+            .iter()
+            .filter(|explanation| explanation.next_runs.is_err())
+            .count();
+        if invalid > 0 {
+            return Err(Error::Message(format!("{invalid} invalid job schedule(s)")));
+        }
+        return Ok(());
+    }
+
+    let scheduler = scheduler?;
     if list {
         println!("{scheduler}");
         Ok(())
+    } else if history {
+        println!(
+            "{:<27} {:<15} {:<10} {:<6} Duration",
+            "Started", "Job", "Status", "Exit"
+        );
+        for run in scheduler.history()? {
+            println!("{run}");
+        }
+        Ok(())
     } else {
         Ok(scheduler.run().await?)
     }
@@ -280,9 +440,23 @@ pub enum RunDbCommand {
         from: PathBuf,
         dump: bool,
         dump_tables: Option<Vec<String>>,
+        /// Runs only the registered seeder with this name
+        name: Option<String>,
+        /// Runs the seeders again, even when already applied
+        force: bool,
     },
     /// Dump database schema
     Schema,
+    /// Show the statistics of the connection pool
+    PoolStatus,
+    /// Dump the schema as SQL statements into a file
+    SchemaDump(PathBuf),
+    /// Squash the applied migrations into a schema baseline
+    MigrateSquash,
+    /// Encrypt the columns of a table again with the current key
+    Reencrypt { table: String, columns: Vec<String> },
+    /// Count the children of the rows of a counter cache again
+    CounterCacheBackfill(crate::model::counter_cache::CounterCache),
 }
 
 #[cfg(feature = "with-db")]
@@ -328,6 +502,8 @@ pub async fn run_db<H: Hooks, M: MigratorTrait>(
             from,
             dump,
             dump_tables,
+            name,
+            force,
         } => {
             tracing::warn!(reset = reset, from = %from.display(), "seed:");
 
@@ -337,13 +513,49 @@ pub async fn run_db<H: Hooks, M: MigratorTrait>(
                 if reset {
                     db::reset::<M>(&app_context.db).await?;
                 }
-                db::run_app_seed::<H>(app_context, &from).await?;
+                let mut seeders = Seeders::default();
+                H::register_seeders(&mut seeders);
+                // a named seeder runs alone, without the fixtures
+                if name.is_none() {
+                    db::run_app_seed::<H>(app_context, &from).await?;
+                }
+                let ran = seeders.run(app_context, name.as_deref(), force).await?;
+                tracing::info!(seeders = ?ran, "seeders applied");
             }
         }
         RunDbCommand::Schema => {
             db::dump_schema(app_context, "schema_dump.json").await?;
             println!("Database schema dumped to 'schema_dump.json'");
         }
+        RunDbCommand::PoolStatus => {
+            println!("{}", db::pool_stats(&app_context.db).await?);
+        }
+        RunDbCommand::SchemaDump(output) => {
+            db::schema_dump(app_context, &output).await?;
+            println!("Database schema dumped to '{}'", output.display());
+        }
+        RunDbCommand::MigrateSquash => {
+            tracing::warn!("migrate:squash:");
+            let baseline = db::squash::<M>(app_context, Path::new("migration/src")).await?;
+            println!("Migrations squashed into '{baseline}'");
+        }
+        RunDbCommand::Reencrypt { table, columns } => {
+            let updated = crate::model::encryption::reencrypt(
+                &app_context.db,
+                app_context.encryptor()?,
+                &table,
+                &columns,
+            )
+            .await?;
+            println!("{updated} values of '{table}' re-encrypted");
+        }
+        RunDbCommand::CounterCacheBackfill(counter) => {
+            let updated = counter.backfill(&app_context.db).await?;
+            println!(
+                "{updated} rows of '{}' counted in '{}'",
+                counter.table, counter.column
+            );
+        }
     }
     Ok(())
 }
@@ -366,27 +578,86 @@ pub async fn create_context<H: Hooks>(
     }
     #[cfg(feature = "with-db")]
     let db = db::connect(&config.database).await?;
+    #[cfg(feature = "with-db")]
+    let replicas = {
+        let replicas = Arc::new(db::Replicas::connect(&config.database).await?);
+        replicas.watch(std::time::Duration::from_secs(
+            config.database.replicas_check_interval,
+        ));
+        replicas
+    };
+
+    #[cfg(feature = "with-db")]
+    let encryptor = config
+        .database
+        .encryption
+        .as_ref()
+        .map(crate::model::encryption::Encryptor::from_config)
+        .transpose()?
+        .map(Arc::new);
 
     let mailer = if let Some(cfg) = config.mailer.as_ref() {
-        create_mailer(cfg)?
+        create_mailer(cfg).await?
     } else {
         None
     };
 
     let queue_provider = bgworker::create_queue_provider(&config).await?;
+    #[cfg(feature = "http_client")]
+    let http = crate::http_client::Client::new(&config.http, H::app_name())?;
     let ctx = AppContext {
         environment: environment.clone(),
         #[cfg(feature = "with-db")]
         db,
+        #[cfg(feature = "with-db")]
+        replicas,
+        #[cfg(feature = "with-db")]
+        outbox: crate::outbox::Outbox,
+        #[cfg(feature = "with-db")]
+        encryptor,
         queue_provider,
-        storage: Storage::single(storage::drivers::null::new()).into(),
+        storage: Storage::from_config(&config.storage)?.into(),
         cache: cache::create_cache_provider(&config).await?,
         config,
         mailer,
         shared_store: Arc::new(crate::app::SharedStore::default()),
+        #[cfg(feature = "http_client")]
+        http: Arc::new(http),
     };
 
-    H::after_context(ctx).await
+    let ctx = H::after_context(ctx).await?;
+    bgworker::payload::install(
+        ctx.config.workers.payload.clone(),
+        #[cfg(feature = "with-db")]
+        ctx.encryptor.clone(),
+    );
+    ctx.shared_store
+        .insert(HealthChecks(H::health_checks(&ctx)));
+    ctx.shared_store
+        .insert(JobMiddlewares(H::job_middlewares(&ctx)));
+    ctx.shared_store.insert(Schedules(H::schedules(&ctx)));
+    ctx.shared_store
+        .insert(MailerPreviews(H::mailer_previews(&ctx)));
+    ctx.shared_store
+        .insert(SuppressionHandlers(H::suppression_handlers(&ctx)));
+    #[cfg(feature = "with-db")]
+    if let Some(authorizer) = H::tenant_authorizer(&ctx) {
+        ctx.shared_store.insert(TenantAuthorization(authorizer));
+    }
+    #[cfg(feature = "auth_jwt")]
+    if let Some(jwt) = ctx.config.auth.as_ref().and_then(|auth| auth.jwt.as_ref()) {
+        ctx.shared_store.insert(crate::auth::jwt::AppJWT(Arc::new(
+            crate::auth::jwt::JWT::from_config(jwt)?,
+        )));
+    }
+    if ctx.config.mailer.as_ref().is_some_and(|mailer| mailer.stub) {
+        ctx.shared_store.insert(StubDeliveries::default());
+    }
+    if ctx.config.workers.mode == WorkerMode::BackgroundAsync {
+        ctx.shared_store
+            .insert(AsyncQueue::start(&ctx.config.workers.async_queue));
+    }
+    Ok(ctx)
 }
 
 #[cfg(feature = "with-db")]
@@ -501,7 +772,7 @@ async fn setup_routes<H: Hooks>(
 }
 
 async fn register_workers<H: Hooks>(app_context: &AppContext) -> Result<()> {
-    if app_context.config.workers.mode == WorkerMode::BackgroundQueue {
+    if app_context.config.workers.uses_queue() {
         if let Some(queue) = &app_context.queue_provider {
             queue.register(MailerWorker::build(app_context)).await?;
             H::connect_workers(app_context, queue).await?;
@@ -569,10 +840,14 @@ pub fn list_middlewares<H: Hooks>(ctx: &AppContext) -> Vec<MiddlewareInfo> {
 
 /// Initializes an [`EmailSender`] based on the mailer configuration settings
 /// ([`config::Mailer`]).
-fn create_mailer(config: &config::Mailer) -> Result<Option<EmailSender>> {
+async fn create_mailer(config: &config::Mailer) -> Result<Option<EmailSender>> {
     if config.stub {
         return Ok(Some(EmailSender::stub()));
     }
+    if let Some(kind) = config.kind.filter(|kind| *kind != config::MailerKind::Smtp) {
+        let driver = crate::mailer::drivers::create(kind, config).await?;
+        return Ok(Some(EmailSender::driver(driver)));
+    }
     if let Some(smtp) = config.smtp.as_ref() {
         if smtp.enable {
             return Ok(Some(EmailSender::smtp(smtp)?));
@@ -115,6 +115,27 @@ impl CacheDriver for Inmem {
         Ok(())
     }
 
+    /// Atomically increments the counter stored at `key`, keeping the
+    /// expiry it was created with.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn increment(&self, key: &str, duration: Duration) -> CacheResult<u64> {
+        let entry = self
+            .cache
+            .entry(key.to_string())
+            .and_upsert_with(|entry| match entry {
+                Some(entry) => {
+                    let (expiration, value) = entry.into_value();
+                    let count = value.parse::<u64>().unwrap_or(0) + 1;
+                    (expiration, count.to_string())
+                }
+                None => (Expiration::AfterDuration(duration), "1".to_string()),
+            });
+        Ok(entry.value().1.parse().unwrap_or(1))
+    }
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
@@ -125,6 +146,16 @@ impl CacheDriver for Inmem {
         Ok(())
     }
 
+    /// Atomically removes a key-value pair from the cache, returning its
+    /// value.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn take(&self, key: &str) -> CacheResult<Option<String>> {
+        Ok(self.cache.remove(key).map(|(_, value)| value))
+    }
+
     /// Clears all key-value pairs from the cache.
     ///
     /// # Errors
@@ -214,6 +245,30 @@ mod tests {
         assert!(!mem.contains_key("key").await.unwrap());
     }
 
+    #[tokio::test]
+    async fn can_take_key() {
+        let config = create_test_config();
+        let mem = new(&config);
+        assert!(mem.insert("key", "loco").await.is_ok());
+        assert_eq!(
+            mem.take::<String>("key").await.unwrap(),
+            Some("loco".to_string())
+        );
+        assert_eq!(mem.take::<String>("key").await.unwrap(), None);
+        assert!(!mem.contains_key("key").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_increment() {
+        let config = create_test_config();
+        let mem = new(&config);
+        let duration = Duration::from_secs(60);
+        assert_eq!(mem.increment("counter", duration).await.unwrap(), 1);
+        assert_eq!(mem.increment("counter", duration).await.unwrap(), 2);
+        assert_eq!(mem.get::<u64>("counter").await.unwrap(), Some(2));
+        assert_eq!(mem.increment("other", duration).await.unwrap(), 1);
+    }
+
     #[tokio::test]
     async fn can_clear() {
         let config = create_test_config();
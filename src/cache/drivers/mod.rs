@@ -62,6 +62,27 @@ pub trait CacheDriver: Sync + Send {
         duration: Duration,
     ) -> CacheResult<()>;
 
+    /// Increments the counter stored at `key`, creating it with an expiry of
+    /// `duration` when missing, and returns its new value.
+    ///
+    /// The default implementation reads then writes the counter, so that
+    /// concurrent increments may be lost; drivers should override it with an
+    /// atomic operation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn increment(&self, key: &str, duration: Duration) -> CacheResult<u64> {
+        let count = match self.get(key).await? {
+            Some(value) => value.parse::<u64>().unwrap_or(0) + 1,
+            None => 1,
+        };
+        self.insert_with_expiry(key, &count.to_string(), duration)
+            .await?;
+        Ok(count)
+    }
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
@@ -70,6 +91,25 @@ pub trait CacheDriver: Sync + Send {
     /// operation.
     async fn remove(&self, key: &str) -> CacheResult<()>;
 
+    /// Removes a key-value pair from the cache, returning its value, so that
+    /// a single caller gets the value.
+    ///
+    /// The default implementation reads then removes the value, so that
+    /// concurrent callers may all get it; drivers should override it with an
+    /// atomic operation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn take(&self, key: &str) -> CacheResult<Option<String>> {
+        let value = self.get(key).await?;
+        if value.is_some() {
+            self.remove(key).await?;
+        }
+        Ok(value)
+    }
+
     /// Clears all key-value pairs from the cache.
     ///
     /// # Errors
@@ -86,6 +86,17 @@ impl CacheDriver for Null {
         ))
     }
 
+    /// Increments the counter stored at `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn increment(&self, _key: &str, _duration: Duration) -> CacheResult<u64> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
@@ -7,7 +7,7 @@ use async_trait::async_trait;
 use bb8::Pool;
 use bb8_redis::{
     bb8,
-    redis::{cmd, AsyncCommands},
+    redis::{cmd, AsyncCommands, Script},
     RedisConnectionManager,
 };
 
@@ -15,6 +15,16 @@ use super::CacheDriver;
 use crate::cache::{CacheError, CacheResult};
 use crate::config::RedisCacheConfig;
 
+// Increments a counter, setting its expiry when it is created so that the
+// increments do not push it back
+const INCREMENT_SCRIPT: &str = r"
+local count = redis.call('INCR', KEYS[1])
+if count == 1 then
+    redis.call('EXPIRE', KEYS[1], ARGV[1])
+end
+return count
+";
+
 /// Creates a new instance of the Redis cache driver with a default configuration.
 ///
 /// # Returns
@@ -119,6 +129,22 @@ impl CacheDriver for Redis {
         Ok(())
     }
 
+    /// Atomically increments the counter stored at `key`, keeping the
+    /// expiry it was created with.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn increment(&self, key: &str, duration: Duration) -> CacheResult<u64> {
+        let mut conn = self.pool.get().await?;
+        let count: u64 = Script::new(INCREMENT_SCRIPT)
+            .key(key)
+            .arg(duration.as_secs().max(1))
+            .invoke_async(&mut *conn)
+            .await?;
+        Ok(count)
+    }
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
@@ -130,6 +156,24 @@ impl CacheDriver for Redis {
         Ok(())
     }
 
+    /// Atomically removes a key-value pair from the cache, returning its
+    /// value.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn take(&self, key: &str) -> CacheResult<Option<String>> {
+        let mut conn = self.pool.get().await?;
+        let (value,): (Option<String>,) = bb8_redis::redis::pipe()
+            .atomic()
+            .get(key)
+            .del(key)
+            .ignore()
+            .query_async(&mut *conn)
+            .await?;
+        Ok(value)
+    }
+
     /// Clears all key-value pairs from the cache.
     ///
     /// # Errors
@@ -296,4 +340,22 @@ mod tests {
             .await
             .expect("Failed to check if key exists after expiry"));
     }
+
+    #[tokio::test]
+    async fn test_increment_keeps_expiry() {
+        let (redis, _container) = setup_redis_driver().await;
+
+        let window = Duration::from_secs(2);
+        assert_eq!(redis.increment("counter", window).await.unwrap(), 1);
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert_eq!(redis.increment("counter", window).await.unwrap(), 2);
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+
+        // the counter expired with the window of its first increment
+        assert!(!redis
+            .contains_key("counter")
+            .await
+            .expect("Failed to check if key exists after expiry"));
+        assert_eq!(redis.increment("counter", window).await.unwrap(), 1);
+    }
 }
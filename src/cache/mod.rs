@@ -400,6 +400,61 @@ impl Cache {
         self.driver.remove(key).await
     }
 
+    /// Removes a value from the cache and returns it, deserialized. When
+    /// several callers take the same key concurrently, only one of them gets
+    /// the value, such as to consume a single-use token.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn consume() -> CacheResult<Option<String>> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.take::<String>("token:1").await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] containing an `Option` representing the removed
+    /// and deserialized value.
+    pub async fn take<T: DeserializeOwned>(&self, key: &str) -> CacheResult<Option<T>> {
+        self.driver
+            .take(key)
+            .await?
+            .map(|value| {
+                serde_json::from_str::<T>(&value)
+                    .map_err(|e| CacheError::Deserialization(e.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Increments the counter stored at `key`, creating it with an expiry of
+    /// `duration` when missing, and returns its new value. The counter can
+    /// also be read with [`Cache::get`].
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    /// use std::time::Duration;
+    ///
+    /// pub async fn count_visits() -> CacheResult<u64> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.increment("visits", Duration::from_secs(60)).await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn increment(&self, key: &str, duration: Duration) -> CacheResult<u64> {
+        self.driver.increment(key, duration).await
+    }
+
     /// Clears all key-value pairs from the cache.
     ///
     /// # Example
@@ -420,6 +475,35 @@ impl Cache {
     pub async fn clear(&self) -> CacheResult<()> {
         self.driver.clear().await
     }
+
+    /// Invalidates the responses of a path cached by the
+    /// [`crate::controller::middleware::response_cache`] middleware, whatever
+    /// their query and varying headers.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn update_posts() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.invalidate_response("/api/posts").await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn invalidate_response(&self, path: &str) -> CacheResult<()> {
+        use crate::controller::middleware::response_cache;
+
+        self.insert(
+            &response_cache::generation_key(path),
+            &response_cache::new_generation(),
+        )
+        .await
+    }
 }
 
 #[cfg(test)]
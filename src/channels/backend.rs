@@ -0,0 +1,238 @@
+//! Pub/sub backends relaying the broadcasts and presence of [`super::Channels`]
+//! between the instances of an app.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::Presence;
+use crate::Result;
+
+/// A message broadcast to the sockets of a room.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Envelope {
+    pub room: String,
+    pub event: String,
+    pub data: Value,
+    /// The socket which must not receive the message, usually its sender
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub except: Option<String>,
+}
+
+/// Delivers the envelopes published by any instance to the local sockets.
+pub type Deliver = Arc<dyn Fn(Envelope) + Send + Sync>;
+
+/// Relays envelopes and presence between instances.
+#[async_trait]
+pub trait PubSub: Send + Sync {
+    /// Publishes an envelope to all the instances, including this one.
+    async fn publish(&self, envelope: Envelope) -> Result<()>;
+
+    /// Starts delivering the published envelopes to `deliver`. Called once
+    /// by [`super::Channels::new`].
+    async fn subscribe(&self, deliver: Deliver) -> Result<()>;
+
+    /// Records the presence of a socket in a room.
+    async fn track(&self, room: &str, presence: &Presence) -> Result<()>;
+
+    /// Removes the presence of a socket from a room.
+    async fn untrack(&self, room: &str, socket_id: &str) -> Result<()>;
+
+    /// The presence of the sockets of a room.
+    async fn presence(&self, room: &str) -> Result<Vec<Presence>>;
+}
+
+/// Relays envelopes within the process, for single instance deployments.
+#[derive(Default)]
+pub struct InProcess {
+    subscribers: RwLock<Vec<Deliver>>,
+    presence: Mutex<HashMap<String, HashMap<String, Value>>>,
+}
+
+impl InProcess {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl PubSub for InProcess {
+    async fn publish(&self, envelope: Envelope) -> Result<()> {
+        let subscribers = self
+            .subscribers
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone();
+        for deliver in subscribers {
+            deliver(envelope.clone());
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, deliver: Deliver) -> Result<()> {
+        self.subscribers
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(deliver);
+        Ok(())
+    }
+
+    async fn track(&self, room: &str, presence: &Presence) -> Result<()> {
+        self.presence
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(room.to_string())
+            .or_default()
+            .insert(presence.socket_id.clone(), presence.meta.clone());
+        Ok(())
+    }
+
+    async fn untrack(&self, room: &str, socket_id: &str) -> Result<()> {
+        let mut rooms = self
+            .presence
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Some(sockets) = rooms.get_mut(room) {
+            sockets.remove(socket_id);
+            if sockets.is_empty() {
+                rooms.remove(room);
+            }
+        }
+        Ok(())
+    }
+
+    async fn presence(&self, room: &str) -> Result<Vec<Presence>> {
+        let rooms = self
+            .presence
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut presence: Vec<Presence> = rooms
+            .get(room)
+            .map(|sockets| {
+                sockets
+                    .iter()
+                    .map(|(socket_id, meta)| Presence {
+                        socket_id: socket_id.clone(),
+                        meta: meta.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        presence.sort_by(|a, b| a.socket_id.cmp(&b.socket_id));
+        Ok(presence)
+    }
+}
+
+#[cfg(feature = "channels_redis")]
+pub use self::redis::Redis;
+
+#[cfg(feature = "channels_redis")]
+mod redis {
+    use ::redis::{aio::MultiplexedConnection, AsyncCommands, Client};
+    use futures_util::StreamExt;
+
+    use super::{async_trait, Deliver, Envelope, Presence, PubSub, Result};
+    use crate::Error;
+
+    /// The Redis channel the envelopes are published to.
+    const CHANNEL: &str = "loco:channels";
+
+    /// Relays envelopes with Redis pub/sub, and keeps the presence of each
+    /// room in a Redis hash, so that the instances of an app share their
+    /// rooms.
+    pub struct Redis {
+        client: Client,
+        conn: MultiplexedConnection,
+    }
+
+    impl Redis {
+        /// Connects to Redis.
+        ///
+        /// # Errors
+        ///
+        /// When the URI is invalid or Redis is not reachable.
+        pub async fn connect(uri: &str) -> Result<Self> {
+            let client = Client::open(uri)?;
+            let conn = client.get_multiplexed_async_connection().await?;
+            Ok(Self { client, conn })
+        }
+    }
+
+    fn presence_key(room: &str) -> String {
+        format!("loco:channels:presence:{room}")
+    }
+
+    #[async_trait]
+    impl PubSub for Redis {
+        async fn publish(&self, envelope: Envelope) -> Result<()> {
+            let payload = serde_json::to_string(&envelope)?;
+            self.conn
+                .clone()
+                .publish::<_, _, ()>(CHANNEL, payload)
+                .await?;
+            Ok(())
+        }
+
+        async fn subscribe(&self, deliver: Deliver) -> Result<()> {
+            let mut pubsub = self.client.get_async_pubsub().await?;
+            pubsub.subscribe(CHANNEL).await?;
+
+            tokio::spawn(async move {
+                let mut messages = pubsub.into_on_message();
+                while let Some(message) = messages.next().await {
+                    let envelope = message
+                        .get_payload::<String>()
+                        .map_err(Error::from)
+                        .and_then(|payload| Ok(serde_json::from_str::<Envelope>(&payload)?));
+                    match envelope {
+                        Ok(envelope) => deliver(envelope),
+                        Err(err) => tracing::error!(error = %err, "invalid channels message"),
+                    }
+                }
+                tracing::error!("channels redis subscription closed");
+            });
+            Ok(())
+        }
+
+        async fn track(&self, room: &str, presence: &Presence) -> Result<()> {
+            self.conn
+                .clone()
+                .hset::<_, _, _, ()>(
+                    presence_key(room),
+                    &presence.socket_id,
+                    serde_json::to_string(&presence.meta)?,
+                )
+                .await?;
+            Ok(())
+        }
+
+        async fn untrack(&self, room: &str, socket_id: &str) -> Result<()> {
+            self.conn
+                .clone()
+                .hdel::<_, _, ()>(presence_key(room), socket_id)
+                .await?;
+            Ok(())
+        }
+
+        async fn presence(&self, room: &str) -> Result<Vec<Presence>> {
+            let sockets: Vec<(String, String)> =
+                self.conn.clone().hgetall(presence_key(room)).await?;
+            let mut presence = sockets
+                .into_iter()
+                .map(|(socket_id, meta)| {
+                    Ok(Presence {
+                        socket_id,
+                        meta: serde_json::from_str(&meta)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            presence.sort_by(|a, b| a.socket_id.cmp(&b.socket_id));
+            Ok(presence)
+        }
+    }
+}
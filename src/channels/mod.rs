@@ -0,0 +1,465 @@
+//! # Channels
+//!
+//! Real-time communication with clients over WebSockets. A [`Channel`]
+//! handles the messages of the sockets connected to it, which join rooms to
+//! receive the messages broadcast to them, and whose presence in the rooms
+//! is tracked.
+//!
+//! Messages are JSON text frames with an `event` name and `data`:
+//!
+//! ```json
+//! {"event": "message", "data": {"text": "hello"}}
+//! ```
+//!
+//! Broadcasts and presence go through a [`backend::PubSub`] backend, either
+//! [`backend::InProcess`] for a single instance, or `backend::Redis`
+//! (feature `channels_redis`) to share rooms between the instances of an
+//! app.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::channels::{backend::InProcess, Channel, Channels, Message, Socket};
+//!
+//! struct ChatChannel;
+//!
+//! #[async_trait]
+//! impl Channel for ChatChannel {
+//!     async fn join(&self, socket: &Socket, _ctx: &AppContext) -> Result<()> {
+//!         socket.join("lobby", serde_json::json!({})).await
+//!     }
+//!
+//!     async fn handle_message(&self, socket: &Socket, message: Message, _ctx: &AppContext) -> Result<()> {
+//!         socket.broadcast("lobby", &message.event, message.data).await
+//!     }
+//! }
+//!
+//! async fn routes() -> Routes {
+//!     let channels = Channels::new(InProcess::new()).await.unwrap();
+//!     Routes::new().add("/ws/chat", channels.handler(ChatChannel))
+//! }
+//! ```
+pub mod backend;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{
+        ws::{self, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    routing::{get, MethodRouter},
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use self::backend::{Envelope, PubSub};
+use crate::{app::AppContext, Result};
+
+/// The number of messages buffered for a socket. A socket too slow to read
+/// them misses the next ones.
+const SOCKET_BUFFER: usize = 64;
+
+/// A message exchanged with a client.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Message {
+    pub event: String,
+    #[serde(default)]
+    pub data: Value,
+}
+
+/// A socket in a room, with the metadata it joined with, such as the user
+/// name.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Presence {
+    pub socket_id: String,
+    pub meta: Value,
+}
+
+/// Handles the sockets connected to a channel.
+#[async_trait]
+pub trait Channel: Send + Sync + 'static {
+    /// Called when a socket connects, usually to join rooms. An error closes
+    /// the socket.
+    async fn join(&self, _socket: &Socket, _ctx: &AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a socket disconnected, before it leaves its rooms.
+    async fn leave(&self, _socket: &Socket, _ctx: &AppContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for each message of the socket. An error is sent back to the
+    /// client as an `error` event.
+    async fn handle_message(
+        &self,
+        socket: &Socket,
+        message: Message,
+        ctx: &AppContext,
+    ) -> Result<()>;
+}
+
+pub(crate) type Outbox = mpsc::Sender<Message>;
+
+/// The sockets connected to this instance, by room.
+type LocalRooms = Arc<Mutex<HashMap<String, HashMap<String, Outbox>>>>;
+
+/// The hub of the channels: it connects sockets, and broadcasts messages to
+/// rooms. Cloning it shares the hub.
+#[derive(Clone)]
+pub struct Channels {
+    backend: Arc<dyn PubSub>,
+    rooms: LocalRooms,
+}
+
+impl Channels {
+    /// Creates the hub, subscribing to the broadcasts of the backend.
+    ///
+    /// # Errors
+    ///
+    /// When the backend can not subscribe.
+    pub async fn new(backend: impl PubSub + 'static) -> Result<Self> {
+        let channels = Self {
+            backend: Arc::new(backend),
+            rooms: LocalRooms::default(),
+        };
+
+        let rooms = channels.rooms.clone();
+        channels
+            .backend
+            .subscribe(Arc::new(move |envelope| deliver(&rooms, envelope)))
+            .await?;
+
+        Ok(channels)
+    }
+
+    /// Returns a route connecting the WebSockets upgraded on it to
+    /// `channel`.
+    pub fn handler(&self, channel: impl Channel) -> MethodRouter<AppContext> {
+        let channels = self.clone();
+        let channel = Arc::new(channel);
+        get(
+            move |State(ctx): State<AppContext>, upgrade: WebSocketUpgrade| async move {
+                upgrade.on_upgrade(move |websocket| serve(channels, channel, ctx, websocket))
+            },
+        )
+    }
+
+    /// Broadcasts a message to the sockets of a room, on all the instances.
+    ///
+    /// # Errors
+    ///
+    /// When the backend fails to publish the message.
+    pub async fn broadcast(&self, room: &str, event: &str, data: Value) -> Result<()> {
+        self.backend
+            .publish(Envelope {
+                room: room.to_string(),
+                event: event.to_string(),
+                data,
+                except: None,
+            })
+            .await
+    }
+
+    /// The sockets of a room, on all the instances.
+    ///
+    /// # Errors
+    ///
+    /// When the backend fails to read the presence.
+    pub async fn presence(&self, room: &str) -> Result<Vec<Presence>> {
+        self.backend.presence(room).await
+    }
+}
+
+/// Sends an envelope to the local sockets of its room.
+fn deliver(rooms: &LocalRooms, envelope: Envelope) {
+    let rooms = rooms
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let Some(sockets) = rooms.get(&envelope.room) else {
+        return;
+    };
+    let message = Message {
+        event: envelope.event,
+        data: envelope.data,
+    };
+    for (socket_id, outbox) in sockets {
+        if envelope.except.as_ref() == Some(socket_id) {
+            continue;
+        }
+        if outbox.try_send(message.clone()).is_err() {
+            tracing::warn!(socket_id, "channel socket lagging behind, message dropped");
+        }
+    }
+}
+
+/// A connected client.
+pub struct Socket {
+    id: String,
+    outbox: Outbox,
+    channels: Channels,
+    rooms: Mutex<HashSet<String>>,
+}
+
+impl Socket {
+    /// The unique ID of the socket.
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Sends a message to this socket.
+    ///
+    /// # Errors
+    ///
+    /// When the socket is closed.
+    pub async fn send(&self, event: &str, data: Value) -> Result<()> {
+        self.outbox
+            .send(Message {
+                event: event.to_string(),
+                data,
+            })
+            .await
+            .map_err(|_| crate::Error::string("socket closed"))
+    }
+
+    /// Joins a room with the given presence metadata. The sockets of the
+    /// room get a `presence_join` event.
+    ///
+    /// # Errors
+    ///
+    /// When the backend fails.
+    pub async fn join(&self, room: &str, meta: Value) -> Result<()> {
+        self.channels
+            .rooms
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(room.to_string())
+            .or_default()
+            .insert(self.id.clone(), self.outbox.clone());
+        self.rooms
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(room.to_string());
+
+        let presence = Presence {
+            socket_id: self.id.clone(),
+            meta,
+        };
+        self.channels.backend.track(room, &presence).await?;
+        self.publish(room, "presence_join", serde_json::to_value(presence)?)
+            .await
+    }
+
+    /// Leaves a room. The sockets of the room get a `presence_leave` event.
+    ///
+    /// # Errors
+    ///
+    /// When the backend fails.
+    pub async fn leave(&self, room: &str) -> Result<()> {
+        {
+            let mut rooms = self
+                .channels
+                .rooms
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            if let Some(sockets) = rooms.get_mut(room) {
+                sockets.remove(&self.id);
+                if sockets.is_empty() {
+                    rooms.remove(room);
+                }
+            }
+        }
+        self.rooms
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(room);
+
+        self.channels.backend.untrack(room, &self.id).await?;
+        self.publish(room, "presence_leave", json!({ "socket_id": self.id }))
+            .await
+    }
+
+    /// The rooms the socket joined.
+    #[must_use]
+    pub fn rooms(&self) -> Vec<String> {
+        let mut rooms: Vec<String> = self
+            .rooms
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect();
+        rooms.sort();
+        rooms
+    }
+
+    /// Broadcasts a message to the other sockets of a room.
+    ///
+    /// # Errors
+    ///
+    /// When the backend fails to publish the message.
+    pub async fn broadcast(&self, room: &str, event: &str, data: Value) -> Result<()> {
+        self.publish(room, event, data).await
+    }
+
+    /// The hub the socket is connected to.
+    #[must_use]
+    pub fn channels(&self) -> &Channels {
+        &self.channels
+    }
+
+    /// The queue of the messages sent to the socket, to send them from
+    /// another task.
+    pub(crate) fn outbox(&self) -> Outbox {
+        self.outbox.clone()
+    }
+
+    async fn publish(&self, room: &str, event: &str, data: Value) -> Result<()> {
+        self.channels
+            .backend
+            .publish(Envelope {
+                room: room.to_string(),
+                event: event.to_string(),
+                data,
+                except: Some(self.id.clone()),
+            })
+            .await
+    }
+}
+
+/// Runs a connected socket until it disconnects.
+async fn serve(
+    channels: Channels,
+    channel: Arc<dyn Channel>,
+    ctx: AppContext,
+    websocket: WebSocket,
+) {
+    let (mut sink, mut stream) = websocket.split();
+    let (outbox, mut inbox) = mpsc::channel::<Message>(SOCKET_BUFFER);
+
+    let writer = tokio::spawn(async move {
+        while let Some(message) = inbox.recv().await {
+            let Ok(text) = serde_json::to_string(&message) else {
+                continue;
+            };
+            if sink.send(ws::Message::Text(text.into())).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    let socket = Socket {
+        id: uuid::Uuid::new_v4().to_string(),
+        outbox,
+        channels,
+        rooms: Mutex::default(),
+    };
+
+    match channel.join(&socket, &ctx).await {
+        Ok(()) => {
+            while let Some(Ok(frame)) = stream.next().await {
+                let text = match frame {
+                    ws::Message::Text(text) => text,
+                    ws::Message::Close(_) => break,
+                    _ => continue,
+                };
+                let result = match serde_json::from_str::<Message>(&text) {
+                    Ok(message) => channel.handle_message(&socket, message, &ctx).await,
+                    Err(err) => Err(err.into()),
+                };
+                if let Err(err) = result {
+                    tracing::debug!(socket_id = socket.id, error = %err, "channel message failed");
+                    let _ = socket
+                        .send("error", json!({ "message": err.to_string() }))
+                        .await;
+                }
+            }
+
+            if let Err(err) = channel.leave(&socket, &ctx).await {
+                tracing::error!(socket_id = socket.id, error = %err, "channel leave failed");
+            }
+        }
+        Err(err) => {
+            tracing::debug!(socket_id = socket.id, error = %err, "channel join refused");
+        }
+    }
+
+    for room in socket.rooms() {
+        if let Err(err) = socket.leave(&room).await {
+            tracing::error!(socket_id = socket.id, room, error = %err, "could not leave room");
+        }
+    }
+    drop(socket);
+    let _ = writer.await;
+}
+
+/// A socket which is not connected to a client, with the queue of the
+/// messages sent to it.
+#[cfg(test)]
+pub(crate) fn test_socket(channels: &Channels) -> (Socket, mpsc::Receiver<Message>) {
+    let (outbox, inbox) = mpsc::channel(SOCKET_BUFFER);
+    let socket = Socket {
+        id: uuid::Uuid::new_v4().to_string(),
+        outbox,
+        channels: channels.clone(),
+        rooms: Mutex::default(),
+    };
+    (socket, inbox)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backend::InProcess, test_socket as socket, *};
+
+    #[tokio::test]
+    async fn can_broadcast_to_rooms() {
+        let channels = Channels::new(InProcess::new()).await.unwrap();
+        let (alice, mut alice_inbox) = socket(&channels);
+        let (bob, mut bob_inbox) = socket(&channels);
+
+        alice.join("lobby", json!({"name": "alice"})).await.unwrap();
+        bob.join("lobby", json!({"name": "bob"})).await.unwrap();
+        bob.join("other", json!({})).await.unwrap();
+
+        let joined = alice_inbox.recv().await.unwrap();
+        assert_eq!(joined.event, "presence_join");
+        assert_eq!(joined.data["meta"], json!({"name": "bob"}));
+
+        bob.broadcast("lobby", "message", json!("hi"))
+            .await
+            .unwrap();
+        assert_eq!(
+            alice_inbox.recv().await.unwrap(),
+            Message {
+                event: "message".to_string(),
+                data: json!("hi")
+            }
+        );
+
+        channels
+            .broadcast("other", "notice", json!(1))
+            .await
+            .unwrap();
+        assert_eq!(bob_inbox.recv().await.unwrap().event, "notice");
+        assert!(alice_inbox.try_recv().is_err());
+
+        let presence = channels.presence("lobby").await.unwrap();
+        assert_eq!(presence.len(), 2);
+        assert_eq!(bob.rooms(), vec!["lobby", "other"]);
+
+        bob.leave("lobby").await.unwrap();
+        let left = alice_inbox.recv().await.unwrap();
+        assert_eq!(left.event, "presence_leave");
+        assert_eq!(left.data["socket_id"], json!(bob.id()));
+        assert_eq!(channels.presence("lobby").await.unwrap().len(), 1);
+    }
+}
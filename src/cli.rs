@@ -76,6 +76,9 @@ enum Commands {
         /// Start the server, worker, and scheduler in the same process
         #[arg(short, long, action, conflicts_with_all = &["worker", "server_and_worker"])]
         all: bool,
+        /// Number of workers of each queue (e.g. --queues=critical:4,default:2)
+        #[arg(long, action, value_delimiter = ',', value_parser = crate::bgworker::parse_queue_concurrency)]
+        queues: Vec<(String, u32)>,
         /// server bind address
         #[arg(short, long, action)]
         binding: Option<String>,
@@ -93,7 +96,11 @@ enum Commands {
         command: DbCommands,
     },
     /// Describe all application endpoints
-    Routes {},
+    Routes {
+        /// Print the OpenAPI document of the routes, with the `openapi` feature
+        #[arg(long, action)]
+        openapi: bool,
+    },
     /// Describe all application middlewares
     Middleware {
         // print out the middleware configurations.
@@ -131,6 +138,16 @@ enum Commands {
         /// Show all configured jobs
         #[arg(short, long, action)]
         list: bool,
+        /// Show the recorded runs of the jobs
+        #[arg(long, action)]
+        history: bool,
+        /// Validate the schedules and show the next runs of the jobs, without
+        /// running them
+        #[arg(long, action)]
+        explain: bool,
+        /// The number of next runs shown by `--explain`
+        #[arg(long, default_value_t = 5)]
+        runs: usize,
     },
     /// code generation creates a set of files and code templates based on a
     /// predefined set of rules.
@@ -149,6 +166,22 @@ enum Commands {
         #[arg(short, long, action)]
         production: bool,
     },
+    /// Switch the maintenance mode on or off.
+    Maintenance {
+        #[command(subcommand)]
+        command: MaintenanceCommands,
+    },
+    /// Manage the static assets.
+    Assets {
+        #[command(subcommand)]
+        command: AssetsCommands,
+    },
+    /// Manage the view templates.
+    #[cfg(not(feature = "embedded_assets"))]
+    Views {
+        #[command(subcommand)]
+        command: ViewsCommands,
+    },
     /// Display the app version
     Version {},
 
@@ -161,6 +194,9 @@ enum Commands {
         /// start same-process server and worker
         #[arg(short, long, action)]
         server_and_worker: bool,
+        /// number of workers of each queue (e.g. --queues=critical:4,default:2)
+        #[arg(long, action, value_delimiter = ',', value_parser = crate::bgworker::parse_queue_concurrency)]
+        queues: Vec<(String, u32)>,
     },
 }
 
@@ -185,6 +221,9 @@ enum ComponentArg {
 
   - Generate model without timestamps:
       $ cargo loco g model posts title:string content:text --without-tz
+
+  - Generate model with time-ordered UUIDv7 (or ULID) primary keys:
+      $ cargo loco g model posts title:string --primary-key uuidv7
 ",
     "Examples:".bold().underline()
 ))]
@@ -199,6 +238,10 @@ enum ComponentArg {
         /// Model fields, eg. title:string hits:int
         #[clap(value_parser = parse_key_val::<String,String>)]
         fields: Vec<(String, String)>,
+
+        /// The primary key of the model
+        #[clap(long, value_enum, default_value_t)]
+        primary_key: loco_gen::PrimaryKeyKind,
     },
     #[cfg(feature = "with-db")]
     /// Generates a new migration file
@@ -258,7 +301,9 @@ After running the migration, follow these steps to complete the process:
     #[command(after_help = format!("{}
  $ cargo loco g model posts title:string! user:references --api
 
- $ cargo loco g scaffold posts title:string! user:references --api --without-tz", "Examples:".bold().underline()))]
+ $ cargo loco g scaffold posts title:string! user:references --api --without-tz
+
+ $ cargo loco g scaffold posts title:string! belongs_to:user has_many:comments --api", "Examples:".bold().underline()))]
     Scaffold {
         /// Name of the thing to generate
         name: String,
@@ -267,7 +312,8 @@ After running the migration, follow these steps to complete the process:
         #[arg(long, action)]
         without_tz: bool,
 
-        /// Model fields, eg. title:string hits:int
+        /// Model fields, eg. title:string hits:int, and associations, eg.
+        /// belongs_to:user has_many:comments
         #[clap(value_parser = parse_key_val::<String,String>)]
         fields: Vec<(String, String)>,
 
@@ -275,6 +321,10 @@ After running the migration, follow these steps to complete the process:
         #[clap(short, long, value_enum, group = "scaffold_kind_group")]
         kind: Option<loco_gen::ScaffoldKind>,
 
+        /// The primary key of the model
+        #[clap(long, value_enum, default_value_t)]
+        primary_key: loco_gen::PrimaryKeyKind,
+
         /// Use HTMX scaffold
         #[clap(long, group = "scaffold_kind_group")]
         htmx: bool,
@@ -343,6 +393,22 @@ After running the migration, follow these steps to complete the process:
         /// Name of the thing to generate
         name: String,
     },
+    /// Generate an authorization policy for a model
+    Policy {
+        /// Name of the model the policy authorizes
+        name: String,
+    },
+    /// Generate a WebSocket channel
+    Channel {
+        /// Name of the thing to generate
+        name: String,
+    },
+    /// Generate GraphQL query and mutation resolvers
+    #[command(name = "graphql")]
+    GraphQL {
+        /// Name of the resolvers to generate
+        name: String,
+    },
     /// Generate a deployment infrastructure
     Deployment {
         /// The type of deployment to generate
@@ -384,10 +450,12 @@ impl ComponentArg {
                 name,
                 without_tz,
                 fields,
+                primary_key,
             } => Ok(loco_gen::Component::Model {
                 name,
                 with_tz: !without_tz,
                 fields,
+                primary_key,
             }),
             #[cfg(feature = "with-db")]
             Self::Migration {
@@ -405,6 +473,7 @@ impl ComponentArg {
                 without_tz,
                 fields,
                 kind,
+                primary_key,
                 htmx,
                 html,
                 api,
@@ -428,6 +497,7 @@ impl ComponentArg {
                     with_tz: !without_tz,
                     fields,
                     kind,
+                    primary_key,
                 })
             }
             Self::Controller {
@@ -463,6 +533,9 @@ impl ComponentArg {
             Self::Worker { name } => Ok(loco_gen::Component::Worker { name }),
             Self::Mailer { name } => Ok(loco_gen::Component::Mailer { name }),
             Self::Data { name } => Ok(loco_gen::Component::Data { name }),
+            Self::Policy { name } => Ok(loco_gen::Component::Policy { name }),
+            Self::Channel { name } => Ok(loco_gen::Component::Channel { name }),
+            Self::GraphQL { name } => Ok(loco_gen::Component::GraphQL { name }),
             Self::Deployment { kind } => Ok(kind.to_generator_component(config)),
             Self::Override {
                 template_path: _,
@@ -511,9 +584,71 @@ enum DbCommands {
         /// 'src/fixtures').
         #[arg(long, default_value = "src/fixtures")]
         from: PathBuf,
+        /// Runs only the registered seeder with this name.
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Runs the seeders again, even when they were already applied.
+        #[arg(long)]
+        force: bool,
     },
     /// Dump database schema
     Schema,
+    /// Show the statistics of the connection pool
+    PoolStatus,
+    /// Dump the database schema as SQL statements
+    #[command(name = "schema:dump")]
+    SchemaDump {
+        /// The file of the dump
+        #[arg(short, long, default_value = "migration/schema.sql")]
+        output: PathBuf,
+    },
+    /// Squash the applied migrations into a single schema baseline
+    #[command(name = "migrate:squash")]
+    MigrateSquash,
+    /// Encrypt the encrypted columns of a table again with the current key,
+    /// after rotating it
+    Reencrypt {
+        /// The table of the columns
+        #[arg(short, long)]
+        table: String,
+        /// The encrypted columns, comma separated
+        #[arg(short, long, value_delimiter = ',', required = true)]
+        columns: Vec<String>,
+    },
+    /// Count the children of the rows of a counter cache again
+    #[command(name = "counter-cache:backfill")]
+    CounterCacheBackfill {
+        /// The parent table, such as `posts`
+        #[arg(short, long)]
+        table: String,
+        /// The child table, such as `comments`
+        #[arg(long)]
+        children: String,
+        /// The counter column, `<children>_count` by default
+        #[arg(long)]
+        column: Option<String>,
+        /// The column of the children referencing the parent,
+        /// `<singular table>_id` by default
+        #[arg(long)]
+        foreign_key: Option<String>,
+    },
+    /// Generate the migration reconciling the database with a declarative
+    /// schema file
+    #[cfg(debug_assertions)]
+    Diff {
+        /// The schema file, mapping the tables to their fields
+        #[arg(short, long, default_value = "migration/schema.yaml")]
+        schema: PathBuf,
+        /// The name of the generated migration
+        #[arg(short, long, default_value = "ReconcileSchema")]
+        name: String,
+        /// Create the new tables without timestamps
+        #[arg(long)]
+        without_tz: bool,
+        /// Show the differences without generating the migration
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 impl From<DbCommands> for RunDbCommand {
@@ -531,16 +666,43 @@ impl From<DbCommands> for RunDbCommand {
                 from,
                 dump,
                 dump_tables,
+                name,
+                force,
             } => Self::Seed {
                 reset,
                 from,
                 dump,
                 dump_tables,
+                name,
+                force,
             },
             DbCommands::Create => {
                 unreachable!("Create db should't handled in the global db commands")
             }
+            #[cfg(debug_assertions)]
+            DbCommands::Diff { .. } => {
+                unreachable!("Diff db should't handled in the global db commands")
+            }
             DbCommands::Schema => Self::Schema,
+            DbCommands::PoolStatus => Self::PoolStatus,
+            DbCommands::SchemaDump { output } => Self::SchemaDump(output),
+            DbCommands::MigrateSquash => Self::MigrateSquash,
+            DbCommands::Reencrypt { table, columns } => Self::Reencrypt { table, columns },
+            DbCommands::CounterCacheBackfill {
+                table,
+                children,
+                column,
+                foreign_key,
+            } => {
+                let mut counter = crate::model::counter_cache::CounterCache::new(&table, &children);
+                if let Some(column) = column {
+                    counter = counter.column(&column);
+                }
+                if let Some(foreign_key) = foreign_key {
+                    counter = counter.foreign_key(&foreign_key);
+                }
+                Self::CounterCacheBackfill(counter)
+            }
         }
     }
 }
@@ -589,6 +751,36 @@ impl DeploymentKind {
     }
 }
 
+#[derive(Subcommand)]
+enum MaintenanceCommands {
+    /// Answer `503 Service Unavailable` to the requests.
+    On,
+    /// Serve the requests again.
+    Off,
+}
+
+#[derive(Subcommand)]
+enum AssetsCommands {
+    /// Fingerprints the static assets and writes their manifest, used by
+    /// `asset_url()` in views.
+    Precompile {},
+}
+
+#[cfg(not(feature = "embedded_assets"))]
+#[derive(Subcommand)]
+enum ViewsCommands {
+    /// Loads and lints the Tera templates, exiting with an error on syntax
+    /// errors, unknown filters or missing includes.
+    Check {
+        /// The views directory.
+        #[arg(short, long, default_value = "assets/views")]
+        path: PathBuf,
+        /// Filters registered by the app, comma separated.
+        #[arg(short, long, value_delimiter = ',', num_args = 0..)]
+        filters: Vec<String>,
+    },
+}
+
 #[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
 #[derive(Subcommand)]
 enum JobsCommands {
@@ -722,6 +914,7 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
             worker,
             server_and_worker,
             all,
+            queues,
             binding,
             port,
             no_banner,
@@ -736,9 +929,12 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
                 },
                 |tags| StartMode::WorkerOnly { tags },
             );
+            let mut config = app_context.config;
+            if !queues.is_empty() {
+                config.workers.queues = queues.into_iter().collect();
+            }
 
-            let boot_result =
-                create_app::<H, M>(start_mode, &environment, app_context.config).await?;
+            let boot_result = create_app::<H, M>(start_mode, &environment, config).await?;
             let serve_params = ServeParams {
                 port: port.map_or(boot_result.app_context.config.server.port, |p| p),
                 binding: binding
@@ -747,20 +943,26 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
             start::<H>(boot_result, serve_params, no_banner).await?;
         }
         #[cfg(feature = "with-db")]
-        Commands::Db { command } => {
-            if matches!(command, DbCommands::Create) {
-                db::create(&app_context.config.database.uri).await?;
-            } else {
-                run_db::<H, M>(&app_context, command.into()).await?;
+        Commands::Db { command } => match command {
+            DbCommands::Create => db::create(&app_context.config.database.uri).await?,
+            #[cfg(debug_assertions)]
+            DbCommands::Diff {
+                schema,
+                name,
+                without_tz,
+                dry_run,
+            } => {
+                handle_db_diff::<H>(&app_context, &schema, name, !without_tz, dry_run).await?;
             }
-        }
+            command => run_db::<H, M>(&app_context, command.into()).await?,
+        },
         #[cfg(any(feature = "bg_redis", feature = "bg_pg", feature = "bg_sqlt"))]
         Commands::Jobs { command } => {
             handle_job_command::<H>(command, &environment, app_context.config).await?;
         }
-        Commands::Routes {} => {
+        Commands::Routes { openapi } => {
             let app_context = create_context::<H>(&environment, app_context.config).await?;
-            show_list_endpoints::<H>(&app_context);
+            show_routes::<H>(&app_context, openapi)?;
         }
         Commands::Middleware { show_config } => {
             let app_context = create_context::<H>(&environment, app_context.config).await?;
@@ -791,9 +993,21 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
             config_path,
             tag,
             list,
+            history,
+            explain,
+            runs,
         } => {
             let app_context = create_context::<H>(&environment, app_context.config).await?;
-            run_scheduler::<H>(&app_context, config_path.as_ref(), name, tag, list).await?;
+            run_scheduler::<H>(
+                &app_context,
+                config_path.as_ref(),
+                name,
+                tag,
+                list,
+                history,
+                explain.then_some(runs),
+            )
+            .await?;
         }
         #[cfg(debug_assertions)]
         Commands::Generate { component } => {
@@ -819,6 +1033,15 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
                 }
             }
         }
+        Commands::Maintenance { command } => {
+            let app_context = create_context::<H>(&environment, app_context.config).await?;
+            handle_maintenance_command(command, &app_context).await?;
+        }
+        Commands::Assets { command } => {
+            handle_assets_command(command, &app_context.config)?;
+        }
+        #[cfg(not(feature = "embedded_assets"))]
+        Commands::Views { command } => handle_views_command(command),
         Commands::Version {} => {
             println!("{}", H::app_version(),);
         }
@@ -826,6 +1049,7 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
         Commands::Watch {
             worker,
             server_and_worker,
+            queues,
         } => {
             // cargo-watch  -s 'cargo loco start'
             let mut cmd_str = String::from("cargo loco start");
@@ -840,6 +1064,14 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
             } else if server_and_worker {
                 cmd_str.push_str(" --server-and-worker");
             }
+            if !queues.is_empty() {
+                let queues = queues
+                    .iter()
+                    .map(|(queue, workers)| format!("{queue}:{workers}"))
+                    .collect::<Vec<_>>();
+                write!(cmd_str, " --queues={}", queues.join(","))
+                    .expect("Failed to write to string");
+            }
 
             cmd("cargo-watch", &["-s", &cmd_str]).run().map_err(|err| {
                 Error::Message(format!(
@@ -872,6 +1104,7 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
             worker,
             server_and_worker,
             all,
+            queues,
             binding,
             port,
             no_banner,
@@ -886,8 +1119,12 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
                 },
                 |tags| StartMode::WorkerOnly { tags },
             );
+            let mut config = app_context.config;
+            if !queues.is_empty() {
+                config.workers.queues = queues.into_iter().collect();
+            }
 
-            let boot_result = create_app::<H>(start_mode, &environment, app_context.config).await?;
+            let boot_result = create_app::<H>(start_mode, &environment, config).await?;
             let serve_params = ServeParams {
                 port: port.map_or(boot_result.app_context.config.server.port, |p| p),
                 binding: binding.map_or(
@@ -897,7 +1134,7 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
             };
             start::<H>(boot_result, serve_params, no_banner).await?;
         }
-        Commands::Routes {} => show_list_endpoints::<H>(&app_context),
+        Commands::Routes { openapi } => show_routes::<H>(&app_context, openapi)?,
         Commands::Middleware { show_config } => {
             let middlewares = list_middlewares::<H>(&app_context);
             for middleware in middlewares.iter().filter(|m| m.enabled) {
@@ -929,8 +1166,20 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
             config_path,
             tag,
             list,
+            history,
+            explain,
+            runs,
         } => {
-            run_scheduler::<H>(&app_context, config_path.as_ref(), name, tag, list).await?;
+            run_scheduler::<H>(
+                &app_context,
+                config_path.as_ref(),
+                name,
+                tag,
+                list,
+                history,
+                explain.then_some(runs),
+            )
+            .await?;
         }
         #[cfg(debug_assertions)]
         Commands::Generate { component } => {
@@ -956,12 +1205,21 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
                 }
             }
         }
+        Commands::Maintenance { command } => {
+            handle_maintenance_command(command, &app_context).await?;
+        }
+        Commands::Assets { command } => {
+            handle_assets_command(command, &app_context.config)?;
+        }
+        #[cfg(not(feature = "embedded_assets"))]
+        Commands::Views { command } => handle_views_command(command),
         Commands::Version {} => {
             println!("{}", H::app_version(),);
         }
         Commands::Watch {
             worker,
             server_and_worker,
+            queues,
         } => {
             // cargo-watch  -s 'cargo loco start'
             let mut cmd_str = String::from("cargo loco start");
@@ -976,6 +1234,14 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
             } else if server_and_worker {
                 cmd_str.push_str(" --server-and-worker");
             }
+            if !queues.is_empty() {
+                let queues = queues
+                    .iter()
+                    .map(|(queue, workers)| format!("{queue}:{workers}"))
+                    .collect::<Vec<_>>();
+                write!(cmd_str, " --queues={}", queues.join(","))
+                    .expect("Failed to write to string");
+            }
 
             cmd("cargo-watch", &["-s", &cmd_str]).run().map_err(|err| {
                 Error::Message(format!(
@@ -1137,6 +1403,23 @@ impl RouteNode {
     }
 }
 
+fn show_routes<H: Hooks>(ctx: &AppContext, openapi: bool) -> crate::Result<()> {
+    if !openapi {
+        show_list_endpoints::<H>(ctx);
+        return Ok(());
+    }
+    #[cfg(feature = "openapi")]
+    {
+        let document = crate::controller::openapi::document::<H>(&list_endpoints::<H>(ctx));
+        println!("{}", serde_json::to_string_pretty(&document)?);
+        Ok(())
+    }
+    #[cfg(not(feature = "openapi"))]
+    Err(Error::string(
+        "the OpenAPI document requires the `openapi` feature of loco-rs",
+    ))
+}
+
 fn show_list_endpoints<H: Hooks>(ctx: &AppContext) {
     // Get and sort routes
     let mut routes = list_endpoints::<H>(ctx);
@@ -1266,6 +1549,86 @@ async fn handle_job_command<H: Hooks>(
     }
 }
 
+async fn handle_maintenance_command(
+    command: MaintenanceCommands,
+    ctx: &AppContext,
+) -> crate::Result<()> {
+    let config = ctx
+        .config
+        .server
+        .middlewares
+        .maintenance
+        .clone()
+        .unwrap_or_default();
+    if !config.enable {
+        eprintln!(
+            "{}",
+            "the maintenance middleware is disabled, enable it in `server.middlewares.maintenance`"
+                .yellow()
+        );
+    }
+
+    let on = matches!(command, MaintenanceCommands::On);
+    config.switch(&ctx.cache, on).await?;
+    println!("maintenance mode is {}", if on { "on" } else { "off" });
+    Ok(())
+}
+
+fn handle_assets_command(command: AssetsCommands, config: &Config) -> crate::Result<()> {
+    match command {
+        AssetsCommands::Precompile {} => {
+            let folder = config
+                .server
+                .middlewares
+                .static_assets
+                .clone()
+                .unwrap_or_default()
+                .folder
+                .path;
+            let manifest = crate::controller::assets::AssetManifest::precompile(&folder)?;
+            println!(
+                "{} assets fingerprinted, manifest written to `{}`",
+                manifest.assets.len(),
+                folder
+                    .join(crate::controller::assets::MANIFEST_FILE)
+                    .display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "embedded_assets"))]
+fn handle_views_command(command: ViewsCommands) {
+    match command {
+        ViewsCommands::Check { path, filters } => {
+            let issues = match controller::views::engine::TeraView::check(&path, &filters) {
+                Ok(issues) => issues,
+                Err(err) => {
+                    let mut message = err.to_string();
+                    let mut source = std::error::Error::source(&err);
+                    while let Some(err) = source {
+                        let _ = write!(message, "\n  caused by: {err}");
+                        source = err.source();
+                    }
+                    eprintln!("{}", message.red());
+                    exit(1);
+                }
+            };
+            for issue in &issues {
+                eprintln!("{}", issue.to_string().red());
+            }
+            if !issues.is_empty() {
+                exit(1);
+            }
+            println!(
+                "{}",
+                format!("templates in `{}` are valid", path.display()).green()
+            );
+        }
+    }
+}
+
 #[cfg(debug_assertions)]
 fn handle_generate_command<H: Hooks>(
     component: ComponentArg,
@@ -1314,9 +1677,7 @@ fn handle_generate_command<H: Hooks>(
         let get_result = loco_gen::generate(
             &loco_gen::new_generator(),
             component.into_gen_component(config)?,
-            &loco_gen::AppInfo {
-                app_name: H::app_name().to_string(),
-            },
+            &app_info::<H>(config),
         )?;
         let messages = loco_gen::collect_messages(&get_result);
         println!("{messages}");
@@ -1324,6 +1685,59 @@ fn handle_generate_command<H: Hooks>(
     Ok(())
 }
 
+#[cfg(debug_assertions)]
+fn app_info<H: Hooks>(config: &Config) -> loco_gen::AppInfo {
+    loco_gen::AppInfo {
+        app_name: H::app_name().to_string(),
+        view_engine: match config.views.engine {
+            crate::config::ViewEngineKind::Tera | crate::config::ViewEngineKind::Handlebars => {
+                loco_gen::ViewEngineKind::Tera
+            }
+            crate::config::ViewEngineKind::MiniJinja => loco_gen::ViewEngineKind::MiniJinja,
+        },
+    }
+}
+
+#[cfg(all(feature = "with-db", debug_assertions))]
+async fn handle_db_diff<H: Hooks>(
+    app_context: &AppContext,
+    schema: &std::path::Path,
+    name: String,
+    with_tz: bool,
+    dry_run: bool,
+) -> crate::Result<()> {
+    let declared = db::load_schema_file(schema)?;
+    let diff = db::schema_diff(&app_context.db, &declared).await?;
+    print!("{diff}");
+    if diff.is_empty() {
+        println!("{}", "The database matches the schema.".green());
+        return Ok(());
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    let db::SchemaDiff {
+        create_tables,
+        add_columns,
+        remove_columns,
+        ..
+    } = diff;
+    let get_result = loco_gen::generate(
+        &loco_gen::new_generator(),
+        loco_gen::Component::SchemaDiff {
+            name,
+            with_tz,
+            create_tables,
+            add_columns,
+            remove_columns,
+        },
+        &app_info::<H>(&app_context.config),
+    )?;
+    println!("{}", loco_gen::collect_messages(&get_result));
+    Ok(())
+}
+
 #[must_use]
 pub fn format_templates_as_tree(paths: Vec<PathBuf>) -> String {
     let mut categories: BTreeMap<String, BTreeMap<String, Vec<PathBuf>>> = BTreeMap::new();
@@ -9,7 +9,10 @@ use regex::Regex;
 
 use crate::{
     app::{AppContext, Hooks},
-    controller::{middleware::MiddlewareLayer, routes::Routes},
+    controller::{
+        middleware::MiddlewareLayer,
+        routes::{Routes, ScopedMiddleware},
+    },
     Result,
 };
 
@@ -31,6 +34,9 @@ pub struct ListRoutes {
     pub uri: String,
     pub actions: Vec<axum::http::Method>,
     pub method: axum::routing::MethodRouter<AppContext>,
+    pub middlewares: Vec<ScopedMiddleware>,
+    #[cfg(feature = "openapi")]
+    pub doc: Option<super::openapi::Operation>,
 }
 
 impl fmt::Display for ListRoutes {
@@ -94,6 +100,9 @@ impl AppRoutes {
                         uri,
                         actions: handler.actions.clone(),
                         method: handler.method.clone(),
+                        middlewares: handler.middlewares.clone(),
+                        #[cfg(feature = "openapi")]
+                        doc: handler.doc.clone(),
                     }
                 })
             })
@@ -297,9 +306,31 @@ impl AppRoutes {
         // using the router directly, and ServiceBuilder has been reported to give
         // issues in compile times itself (https://github.com/rust-lang/crates.io/pull/7443).
         //
-        for router in self.collect() {
+        let routes = self.collect();
+        #[cfg(feature = "openapi")]
+        let document = super::openapi::document::<H>(&routes);
+        for router in routes {
             tracing::info!("{}", router.to_string());
-            app = app.route(&router.uri, router.method);
+            if router.middlewares.is_empty() {
+                app = app.route(&router.uri, router.method);
+                continue;
+            }
+
+            // scoped middlewares wrap their route only, inside the global ones
+            let mut scoped = AXRouter::new().route(&router.uri, router.method);
+            for mid in &router.middlewares {
+                let mid = mid.layer();
+                if mid.is_enabled() {
+                    scoped = mid.apply(scoped)?;
+                    tracing::info!(name = mid.name(), uri = %router.uri, "+scoped middleware");
+                }
+            }
+            app = app.merge(scoped);
+        }
+        #[cfg(feature = "openapi")]
+        {
+            app = super::openapi::mount(app, &document);
+            tracing::info!(path = super::openapi::DOCS_PATH, "+openapi");
         }
 
         let middlewares = self.middlewares::<H>(&ctx);
@@ -455,4 +486,41 @@ mod tests {
         let response = router.oneshot(req).await.unwrap();
         assert!(response.status().is_success());
     }
+
+    #[tokio::test]
+    async fn can_apply_scoped_middlewares() {
+        use crate::controller::middleware::limit_payload::{DefaultBodyLimitKind, LimitPayload};
+
+        async fn echo(body: axum::body::Bytes) -> Result<Response> {
+            format::text(&String::from_utf8_lossy(&body))
+        }
+
+        let app_router = AppRoutes::empty()
+            .add_route(Routes::new().add("/large", post(echo)))
+            .add_route(
+                Routes::new()
+                    .add("/small", post(echo))
+                    .with_middleware(LimitPayload {
+                        body_limit: DefaultBodyLimitKind::Limit(4),
+                    }),
+            );
+
+        let ctx = tests_cfg::app::get_app_context().await;
+        let router = app_router
+            .to_router::<tests_cfg::db::AppHook>(ctx, axum::Router::new())
+            .unwrap();
+
+        for (uri, status) in [
+            ("/large", axum::http::StatusCode::OK),
+            ("/small", axum::http::StatusCode::PAYLOAD_TOO_LARGE),
+        ] {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .method(Method::POST)
+                .body(axum::body::Body::from("loco-rs"))
+                .unwrap();
+            let response = router.clone().oneshot(req).await.unwrap();
+            assert_eq!(response.status(), status, "{uri}");
+        }
+    }
 }
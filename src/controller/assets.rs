@@ -0,0 +1,202 @@
+//! # Asset Fingerprinting
+//!
+//! `cargo loco assets precompile` copies every file of the static assets
+//! folder (`assets/static` by default) to a name carrying a hash of its
+//! content, such as `css/app-3f2a1b9c0d4e5f60.css`, and records the mapping
+//! in a `manifest.json` file in the same folder.
+//!
+//! The `asset_url(path="css/app.css")` Tera function resolves paths through
+//! the manifest, and the static assets middleware serves the fingerprinted
+//! files with far-future cache headers, as their content never changes.
+//! Without a manifest, `asset_url` returns the plain path, so that
+//! development needs no build step.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::Result;
+
+/// The name of the manifest, in the static assets folder.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// The `Cache-Control` header of fingerprinted assets.
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AssetManifest {
+    /// The fingerprinted path of each asset, by its original path. Both are
+    /// relative to the static assets folder.
+    pub assets: BTreeMap<String, String>,
+}
+
+impl AssetManifest {
+    /// Fingerprints the files of `folder` and writes its manifest, replacing
+    /// the fingerprinted files of a previous run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the folder or the previous manifest can not be
+    /// read, or a file can not be written.
+    pub fn precompile(folder: &Path) -> Result<Self> {
+        if let Some(previous) = Self::load(folder)? {
+            for fingerprinted in previous.assets.values() {
+                let path = folder.join(fingerprinted);
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+
+        let mut files = Vec::new();
+        collect_files(folder, &mut files)?;
+
+        let mut manifest = Self::default();
+        for path in files {
+            let name = asset_name(folder, &path);
+            if name == MANIFEST_FILE {
+                continue;
+            }
+            let content = std::fs::read(&path)?;
+            let fingerprinted = fingerprint(&name, &content);
+            std::fs::write(folder.join(&fingerprinted), content)?;
+            manifest.assets.insert(name, fingerprinted);
+        }
+
+        std::fs::write(
+            folder.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&manifest)?,
+        )?;
+        Ok(manifest)
+    }
+
+    /// Loads the manifest of `folder`, if assets were precompiled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the manifest can not be read or parsed.
+    pub fn load(folder: &Path) -> Result<Option<Self>> {
+        let path = folder.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?))
+    }
+
+    /// The fingerprinted path of an asset, or the path itself when it is not
+    /// in the manifest.
+    #[must_use]
+    pub fn resolve<'a>(&'a self, path: &'a str) -> &'a str {
+        self.assets.get(path).map_or(path, String::as_str)
+    }
+
+    /// The URL of an asset served under `uri`.
+    #[must_use]
+    pub fn url(&self, uri: &str, path: &str) -> String {
+        format!(
+            "{}/{}",
+            uri.trim_end_matches('/'),
+            self.resolve(path.trim_start_matches('/'))
+        )
+    }
+}
+
+/// Collects the files under `dir`, skipping hidden ones.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The path of an asset relative to the folder, with `/` separators.
+fn asset_name(folder: &Path, path: &Path) -> String {
+    path.strip_prefix(folder)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Inserts a hash of `content` in the file name: `css/app.min.css` becomes
+/// `css/app-<hash>.min.css`.
+fn fingerprint(name: &str, content: &[u8]) -> String {
+    let hash = crate::hash::to_hex(&Sha256::digest(content)[..8]);
+
+    let (dir, file) = name
+        .rsplit_once('/')
+        .map_or(("", name), |(dir, file)| (&name[..=dir.len()], file));
+    match file.split_once('.') {
+        Some((stem, ext)) => format!("{dir}{stem}-{hash}.{ext}"),
+        None => format!("{dir}{file}-{hash}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_fingerprint_names() {
+        let hash = fingerprint("app.css", b"body {}");
+        assert!(hash.starts_with("app-") && hash.ends_with(".css"));
+        assert_eq!(hash.len(), "app-.css".len() + 16);
+
+        let hash = &fingerprint("js/app.min.js", b"")["js/app-".len()..];
+        assert_eq!(hash, "e3b0c44298fc1c14.min.js");
+        assert_eq!(fingerprint("LICENSE", b""), "LICENSE-e3b0c44298fc1c14");
+    }
+
+    #[test]
+    fn can_precompile() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add_file("css/app.css", "body {}")
+            .add_file("404.html", "not found")
+            .add_file(".gitkeep", "")
+            .create()
+            .unwrap();
+        let folder = tree_fs.root.as_path();
+
+        assert_eq!(AssetManifest::load(folder).unwrap(), None);
+
+        let manifest = AssetManifest::precompile(folder).unwrap();
+        assert_eq!(manifest.assets.len(), 2);
+        let css = manifest.resolve("css/app.css").to_string();
+        assert_ne!(css, "css/app.css");
+        assert_eq!(
+            std::fs::read_to_string(folder.join(&css)).unwrap(),
+            "body {}"
+        );
+        assert_eq!(
+            AssetManifest::load(folder).unwrap().as_ref(),
+            Some(&manifest)
+        );
+        assert_eq!(
+            manifest.url("/static/", "/css/app.css"),
+            format!("/static/{css}")
+        );
+        assert_eq!(manifest.url("/static", "unknown.js"), "/static/unknown.js");
+
+        // a new run replaces the fingerprinted files
+        std::fs::write(folder.join("css/app.css"), "body { margin: 0 }").unwrap();
+        let manifest = AssetManifest::precompile(folder).unwrap();
+        assert_eq!(manifest.assets.len(), 2);
+        assert!(!folder.join(&css).exists());
+        assert!(folder.join(manifest.resolve("css/app.css")).exists());
+    }
+}
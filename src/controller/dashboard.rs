@@ -0,0 +1,433 @@
+//! # Jobs Dashboard
+//!
+//! An admin UI for the background queue and the scheduler, behind the basic
+//! auth credentials of the `auth.basic` config. It shows the number of
+//! queued, processing and failed jobs of each queue, lists the jobs of a
+//! status with their arguments and errors, retries or deletes a job, and
+//! shows the latest runs of the scheduler history.
+//!
+//! The browsers send the basic auth credentials with the requests of any
+//! site, so the forms retrying and deleting jobs are only accepted from the
+//! dashboard itself: their `Origin` (or `Referer`) has to match the `Host`.
+//!
+//! # Example
+//! ```rust,ignore
+//! fn routes(_ctx: &AppContext) -> AppRoutes {
+//!     AppRoutes::with_default_routes().add_route(loco_rs::controller::dashboard::routes())
+//! }
+//! ```
+use std::fmt::Write;
+
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::{header, HeaderMap, Uri},
+    response::Response,
+    routing::{get, post},
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    app::AppContext,
+    bgworker::{payload, JobStatus, Queue, QueueStats},
+    controller::{extractor::auth::BasicAuth, format, views::escape, Routes},
+    scheduler::Run,
+    Error, Result,
+};
+
+/// The path the dashboard is mounted at.
+pub const PATH: &str = "/_dashboard";
+
+/// The most jobs listed on a page.
+const JOBS_LIMIT: usize = 200;
+
+/// The most scheduler runs shown on the overview.
+const RUNS_LIMIT: usize = 50;
+
+/// The statuses shown for each queue, in order.
+const STATUSES: [JobStatus; 5] = [
+    JobStatus::Queued,
+    JobStatus::Processing,
+    JobStatus::Failed,
+    JobStatus::Completed,
+    JobStatus::Cancelled,
+];
+
+const STYLE: &str = r"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+h1 a { color: inherit; text-decoration: none; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2rem; }
+th, td { border-bottom: 1px solid #ddd; padding: .4rem .6rem; text-align: left; vertical-align: top; }
+td.num { text-align: right; }
+pre { margin: 0; white-space: pre-wrap; word-break: break-all; max-width: 40rem; }
+.failed, .killed { color: #b00020; }
+.error { color: #b00020; }
+form { display: inline; }
+nav a { margin-right: 1rem; }
+";
+
+/// Routes of the dashboard, under [`PATH`]:
+/// * `GET /_dashboard` shows the queues and the scheduler runs
+/// * `GET /_dashboard/jobs?status=failed` lists the jobs of a status
+/// * `POST /_dashboard/jobs/{id}/retry` requeues a failed or cancelled job
+/// * `POST /_dashboard/jobs/{id}/delete` deletes a job
+#[must_use]
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix(PATH)
+        .add("/", get(overview))
+        .add("/jobs", get(jobs))
+        .add("/jobs/{id}/retry", post(retry))
+        .add("/jobs/{id}/delete", post(delete))
+}
+
+#[derive(Debug, Deserialize)]
+struct JobsParams {
+    status: Option<JobStatus>,
+}
+
+async fn overview(
+    _auth: BasicAuth,
+    State(ctx): State<AppContext>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Response> {
+    let base = base_path(&uri, 0);
+    let stats = match &ctx.queue_provider {
+        Some(queue) => Some(queue.stats().await?),
+        None => None,
+    };
+    let runs = match ctx
+        .config
+        .scheduler
+        .as_ref()
+        .and_then(|scheduler| scheduler.history.as_ref())
+    {
+        Some(history) => Some(history.runs()?),
+        None => None,
+    };
+
+    let mut body = String::new();
+    body.push_str("<h2>Queues</h2>");
+    match stats {
+        Some(stats) => body.push_str(&stats_table(&base, &stats)),
+        None => body.push_str("<p>No background queue is configured.</p>"),
+    }
+    body.push_str("<h2>Scheduler runs</h2>");
+    match runs {
+        Some(runs) => body.push_str(&runs_table(&runs)),
+        None => body.push_str(
+            "<p>The scheduler history is not recorded, see <code>scheduler.history</code>.</p>",
+        ),
+    }
+    format::html(&page(&base, "Overview", &body))
+}
+
+async fn jobs(
+    _auth: BasicAuth,
+    State(ctx): State<AppContext>,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<JobsParams>,
+) -> Result<Response> {
+    let base = base_path(&uri, 1);
+    let status = params.status.unwrap_or(JobStatus::Failed);
+    let jobs = queue(&ctx)?
+        .get_jobs(Some(&vec![status.clone()]), None)
+        .await?;
+    let jobs = jobs.as_array().cloned().unwrap_or_default();
+
+    let mut body = String::from("<nav>");
+    for status in &STATUSES {
+        let _ = write!(body, "<a href=\"{base}/jobs?status={status}\">{status}</a>");
+    }
+    body.push_str("</nav>");
+    body.push_str(&jobs_table(&base, &status, &jobs));
+    format::html(&page(&base, &format!("{status} jobs"), &body))
+}
+
+async fn retry(
+    _auth: BasicAuth,
+    State(ctx): State<AppContext>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    check_origin(&headers)?;
+    queue(&ctx)?.retry_job(&id).await?;
+    format::redirect(&format!("{}/jobs?status=failed", base_path(&uri, 3)))
+}
+
+async fn delete(
+    _auth: BasicAuth,
+    State(ctx): State<AppContext>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    check_origin(&headers)?;
+    queue(&ctx)?.delete_job(&id).await?;
+    format::redirect(&format!("{}/jobs?status=failed", base_path(&uri, 3)))
+}
+
+/// Rejects the forms posted from other sites, whose `Origin`, or `Referer`
+/// when there is none, is not the `Host` of the dashboard.
+fn check_origin(headers: &HeaderMap) -> Result<()> {
+    let value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let origin = value(header::ORIGIN)
+        .or_else(|| value(header::REFERER))
+        .and_then(|origin| origin.parse::<Uri>().ok());
+    let same_origin = match (origin, value(header::HOST)) {
+        (Some(origin), Some(host)) => origin
+            .authority()
+            .is_some_and(|authority| authority.as_str().eq_ignore_ascii_case(host)),
+        _ => false,
+    };
+    if same_origin {
+        Ok(())
+    } else {
+        Err(Error::Forbidden(
+            "the request was not sent from the dashboard".to_string(),
+        ))
+    }
+}
+
+fn queue(ctx: &AppContext) -> Result<&Queue> {
+    ctx.queue_provider
+        .as_deref()
+        .ok_or_else(|| Error::string("no background queue is configured"))
+}
+
+/// The path of the dashboard, dropping the last `segments` of the request
+/// path, so that the links follow the prefix the routes are mounted at.
+fn base_path(uri: &Uri, segments: usize) -> String {
+    let mut path = uri.path().trim_end_matches('/');
+    for _ in 0..segments {
+        path = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+    }
+    path.to_string()
+}
+
+fn page(base: &str, title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title} - Loco \
+         dashboard</title><style>{STYLE}</style></head><body><h1><a \
+         href=\"{base}\">Loco dashboard</a></h1>{body}</body></html>",
+        title = escape(title),
+    )
+}
+
+fn stats_table(base: &str, stats: &[QueueStats]) -> String {
+    let mut table = String::from("<table><tr><th>Queue</th>");
+    for status in &STATUSES {
+        let _ = write!(
+            table,
+            "<th><a href=\"{base}/jobs?status={status}\">{status}</a></th>"
+        );
+    }
+    table.push_str("</tr>");
+    for queue in stats {
+        let _ = write!(table, "<tr><td>{}</td>", escape(&queue.queue));
+        for status in &STATUSES {
+            let _ = write!(table, "<td class=\"num\">{}</td>", queue.count(status));
+        }
+        table.push_str("</tr>");
+    }
+    if stats.is_empty() {
+        table.push_str("<tr><td colspan=\"6\">No jobs.</td></tr>");
+    }
+    table.push_str("</table>");
+    table
+}
+
+fn jobs_table(base: &str, status: &JobStatus, jobs: &[Value]) -> String {
+    let retryable = matches!(status, JobStatus::Failed | JobStatus::Cancelled);
+    let mut table = String::from(
+        "<table><tr><th>Id</th><th>Job</th><th>Run \
+         at</th><th>Arguments</th><th>Error</th><th></th></tr>",
+    );
+    for job in jobs.iter().take(JOBS_LIMIT) {
+        let field = |name: &str| job.get(name).and_then(Value::as_str).unwrap_or_default();
+        let id = escape(field("id"));
+        let mut args = job.get("task_data").cloned().unwrap_or_default();
+        let error = args
+            .as_object_mut()
+            .and_then(|data| data.remove("error"))
+            .map(|error| {
+                error
+                    .as_str()
+                    .map_or_else(|| error.to_string(), ToString::to_string)
+            })
+            .unwrap_or_default();
+        let args = serde_json::to_string_pretty(&payload::redact(&args)).unwrap_or_default();
+
+        let _ = write!(
+            table,
+            "<tr><td>{id}</td><td>{}</td><td>{}</td><td><pre>{}</pre></td><td \
+             class=\"error\"><pre>{}</pre></td><td>",
+            escape(field("name")),
+            escape(field("run_at")),
+            escape(&args),
+            escape(&error),
+        );
+        if retryable {
+            let _ = write!(
+                table,
+                "<form method=\"post\" \
+                 action=\"{base}/jobs/{id}/retry\"><button>Retry</button></form> "
+            );
+        }
+        let _ = write!(
+            table,
+            "<form method=\"post\" \
+             action=\"{base}/jobs/{id}/delete\"><button>Delete</button></form></td></tr>"
+        );
+    }
+    if jobs.is_empty() {
+        table.push_str("<tr><td colspan=\"6\">No jobs.</td></tr>");
+    } else if jobs.len() > JOBS_LIMIT {
+        let _ = write!(
+            table,
+            "<tr><td colspan=\"6\">And {} more.</td></tr>",
+            jobs.len() - JOBS_LIMIT
+        );
+    }
+    table.push_str("</table>");
+    table
+}
+
+fn runs_table(runs: &[Run]) -> String {
+    let mut table = String::from(
+        "<table><tr><th>Started</th><th>Job</th><th>Status</th><th>Exit</th><th>Duration</\
+         th><th>Output</th></tr>",
+    );
+    for run in runs.iter().rev().take(RUNS_LIMIT) {
+        let status = format!("{:?}", run.status).to_lowercase();
+        let _ = write!(
+            table,
+            "<tr><td>{}</td><td>{}</td><td class=\"{status}\">{status}</td><td>{}</td><td \
+             class=\"num\">{}ms</td><td><pre>{}</pre></td></tr>",
+            run.started_at
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            escape(&run.job),
+            run.exit_code
+                .map_or_else(|| "-".to_string(), |code| code.to_string()),
+            run.duration_ms,
+            escape(&run.output),
+        );
+    }
+    if runs.is_empty() {
+        table.push_str("<tr><td colspan=\"6\">No runs yet.</td></tr>");
+    }
+    table.push_str("</table>");
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn can_escape_html() {
+        assert_eq!(
+            escape(r#"<script>alert("x & 'y'")</script>"#),
+            "&lt;script&gt;alert(&quot;x &amp; &#39;y&#39;&quot;)&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn can_get_base_path() {
+        let uri = |path: &str| path.parse::<Uri>().unwrap();
+        assert_eq!(base_path(&uri("/_dashboard"), 0), "/_dashboard");
+        assert_eq!(base_path(&uri("/api/_dashboard/"), 0), "/api/_dashboard");
+        assert_eq!(
+            base_path(&uri("/_dashboard/jobs?status=failed"), 1),
+            "/_dashboard"
+        );
+        assert_eq!(
+            base_path(&uri("/_dashboard/jobs/01J/retry"), 3),
+            "/_dashboard"
+        );
+    }
+
+    #[test]
+    fn can_check_the_origin_of_forms() {
+        let headers = |pairs: &[(header::HeaderName, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.clone(), value.parse().unwrap()))
+                .collect::<HeaderMap>()
+        };
+        let host = (header::HOST, "example.com:5150");
+
+        assert!(check_origin(&headers(&[
+            host.clone(),
+            (header::ORIGIN, "https://example.com:5150")
+        ]))
+        .is_ok());
+        assert!(check_origin(&headers(&[
+            host.clone(),
+            (header::REFERER, "https://example.com:5150/_dashboard/jobs")
+        ]))
+        .is_ok());
+        assert!(check_origin(&headers(&[
+            host.clone(),
+            (header::ORIGIN, "https://evil.com"),
+            (header::REFERER, "https://example.com:5150/_dashboard/jobs")
+        ]))
+        .is_err());
+        assert!(check_origin(&headers(&[host, (header::ORIGIN, "null")])).is_err());
+        assert!(check_origin(&headers(&[])).is_err());
+    }
+
+    #[test]
+    fn can_render_jobs_with_their_errors() {
+        let jobs = vec![serde_json::json!({
+            "id": "01J",
+            "name": "<Mailer>",
+            "run_at": "2025-01-01T00:00:00Z",
+            "task_data": { "user_id": 1, "error": "smtp <down>" },
+        })];
+        let table = jobs_table("/_dashboard", &JobStatus::Failed, &jobs);
+
+        assert!(table.contains("&lt;Mailer&gt;"));
+        assert!(table.contains("smtp &lt;down&gt;"));
+        assert!(table.contains("&quot;user_id&quot;: 1"));
+        assert!(!table.contains("&quot;error&quot;"));
+        assert!(table.contains("action=\"/_dashboard/jobs/01J/retry\""));
+        assert!(table.contains("action=\"/_dashboard/jobs/01J/delete\""));
+
+        let table = jobs_table("/_dashboard", &JobStatus::Completed, &jobs);
+        assert!(!table.contains("/retry"));
+    }
+
+    #[test]
+    fn can_render_queue_stats() {
+        let stats = QueueStats::collect(vec![
+            ("mailers".to_string(), "failed".to_string(), 2),
+            ("default".to_string(), "queued".to_string(), 3),
+            ("mailers".to_string(), "failed".to_string(), 1),
+        ]);
+        assert_eq!(
+            stats,
+            vec![
+                QueueStats {
+                    queue: "default".to_string(),
+                    counts: BTreeMap::from([("queued".to_string(), 3)]),
+                },
+                QueueStats {
+                    queue: "mailers".to_string(),
+                    counts: BTreeMap::from([("failed".to_string(), 3)]),
+                },
+            ]
+        );
+        assert_eq!(stats[1].count(&JobStatus::Failed), 3);
+        assert_eq!(stats[1].count(&JobStatus::Queued), 0);
+
+        let table = stats_table("/_dashboard", &stats);
+        assert!(table.contains(
+            "<td>mailers</td><td class=\"num\">0</td><td class=\"num\">0</td><td \
+             class=\"num\">3</td>"
+        ));
+    }
+}
@@ -20,20 +20,30 @@
 //!     format::json(TestResponse{ pid: auth.claims.pid})
 //! }
 //! ```
-use std::collections::HashMap;
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
 
 use axum::{
     extract::{FromRef, FromRequestParts, Query},
-    http::{request::Parts, HeaderMap},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
 };
-use axum_extra::extract::cookie;
-use serde::{Deserialize, Serialize};
+use axum_extra::{
+    extract::cookie,
+    headers::{authorization::Basic, Authorization, HeaderMapExt},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing;
 
-use crate::{app::AppContext, auth, config::JWT as JWTConfig, errors::Error, Result as LocoResult};
+use crate::{
+    app::AppContext, auth, config::JWT as JWTConfig, errors::Error, hash, Result as LocoResult,
+};
 
 #[cfg(feature = "with-db")]
-use crate::model::{Authenticable, ModelError};
+use crate::{
+    controller::ErrorDetail,
+    model::{Authenticable, BasicAuthenticable, ModelError, ScopedApiKey},
+};
 
 // ---------------------------------------
 //
@@ -51,7 +61,11 @@ const AUTH_HEADER: &str = "authorization";
 #[derive(Debug, Deserialize, Serialize)]
 pub struct JWTWithUser<T: Authenticable> {
     pub claims: auth::jwt::UserClaims,
+    /// The user the token was issued for, the impersonated user for
+    /// impersonation tokens
     pub user: T,
+    /// The user acting as `user`, for impersonation tokens
+    pub impersonator: Option<T>,
 }
 
 // Implement the FromRequestParts trait for the Auth struct
@@ -60,89 +74,423 @@ impl<S, T> FromRequestParts<S> for JWTWithUser<T>
 where
     AppContext: FromRef<S>,
     S: Send + Sync,
-    T: Authenticable,
+    T: Authenticable + Send,
 {
     type Rejection = Error;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
         let ctx: AppContext = AppContext::from_ref(state);
 
-        let token = extract_token(get_jwt_from_config(&ctx)?, parts)?;
-
-        let jwt_secret = ctx.config.get_jwt_config()?;
-
-        match auth::jwt::JWT::new(&jwt_secret.secret).validate(&token) {
-            Ok(claims) => {
-                let user = T::find_by_claims_key(&ctx.db, &claims.claims.pid)
-                    .await
-                    .map_err(|e| match e {
-                        ModelError::EntityNotFound => Error::Unauthorized("not found".to_string()),
-                        ModelError::DbErr(db_err) => {
-                            tracing::error!("Database error during authentication: {}", db_err);
-                            Error::InternalServerError
-                        }
-                        _ => {
-                            tracing::error!("Authentication error: {}", e);
-                            Error::Unauthorized("could not authorize".to_string())
-                        }
-                    })?;
-                Ok(Self {
-                    claims: claims.claims,
-                    user,
-                })
-            }
-            Err(err) => {
-                tracing::error!("JWT validation error: {}", err);
-                Err(Error::Unauthorized("token is not valid".to_string()))
-            }
+        let jwt_config = get_jwt_from_request(&ctx, parts)?;
+        let token = extract_token(jwt_config, parts)?;
+
+        Self::from_token(&ctx, jwt_config, &token, parts).await
+    }
+}
+
+#[cfg(feature = "with-db")]
+impl<T: Authenticable + Send> JWTWithUser<T> {
+    async fn from_token(
+        ctx: &AppContext,
+        jwt_config: &JWTConfig,
+        token: &str,
+        parts: &Parts,
+    ) -> LocoResult<Self> {
+        let jwt = get_jwt_keys_from_request(ctx, parts)?;
+        let claims = authenticate(ctx, jwt_config, &jwt, token).await?;
+
+        let user = Self::find_user(ctx, &claims.pid).await?;
+        let impersonator = match &claims.impersonator {
+            Some(pid) => Some(Self::find_user(ctx, pid).await?),
+            None => None,
+        };
+
+        if let Some(audit) = ctx
+            .shared_store
+            .get::<auth::impersonation::ImpersonationAuditLog>()
+        {
+            audit.record(ctx, &claims, parts).await?;
         }
+
+        Ok(Self {
+            claims,
+            user,
+            impersonator,
+        })
+    }
+
+    async fn find_user(ctx: &AppContext, pid: &str) -> LocoResult<T> {
+        T::find_by_claims_key(&ctx.db, pid)
+            .await
+            .map_err(|e| match e {
+                ModelError::EntityNotFound => Error::Unauthorized("not found".to_string()),
+                ModelError::DbErr(db_err) => {
+                    tracing::error!("Database error during authentication: {}", db_err);
+                    Error::InternalServerError
+                }
+                _ => {
+                    tracing::error!("Authentication error: {}", e);
+                    Error::Unauthorized("could not authorize".to_string())
+                }
+            })
+    }
+
+    /// Returns `true` when the request was made with an impersonation token.
+    #[must_use]
+    pub fn is_impersonated(&self) -> bool {
+        self.impersonator.is_some()
     }
 }
 
-// Define a struct to represent user authentication information serialized
-// to/from JSON
+/// Like [`JWTWithUser`], but yields `None` instead of rejecting the request
+/// when no token is present. A present but invalid token is still rejected.
+///
+/// # Example
+/// ```ignore
+/// async fn home(OptionalJWTWithUser(auth): OptionalJWTWithUser<users::Model>) -> Result<Response> {
+///     if let Some(auth) = auth {
+///         // logged-in user: auth.user
+///     }
+/// }
+/// ```
+#[cfg(feature = "with-db")]
 #[derive(Debug, Deserialize, Serialize)]
-pub struct JWT {
-    pub claims: auth::jwt::UserClaims,
+pub struct OptionalJWTWithUser<T: Authenticable>(pub Option<JWTWithUser<T>>);
+
+#[cfg(feature = "with-db")]
+impl<S, T> FromRequestParts<S> for OptionalJWTWithUser<T>
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+    T: Authenticable + Send,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
+        let ctx: AppContext = AppContext::from_ref(state);
+
+        let jwt_config = get_jwt_from_request(&ctx, parts)?;
+        let Ok(token) = extract_token(jwt_config, parts) else {
+            return Ok(Self(None));
+        };
+
+        Ok(Self(Some(
+            JWTWithUser::from_token(&ctx, jwt_config, &token, parts).await?,
+        )))
+    }
+}
+
+/// Validates the request token and exposes its claims.
+///
+/// Claims are deserialized into [`auth::jwt::UserClaims`] by default. Apps
+/// carrying extra claims can use their own type instead, deserialized from
+/// the full claim set once the token is validated:
+///
+/// ```rust
+/// use loco_rs::prelude::*;
+/// use loco_rs::controller::extractor::auth;
+///
+/// #[derive(serde::Deserialize, serde::Serialize)]
+/// struct TenantClaims {
+///     pid: String,
+///     tenant_id: String,
+///     #[serde(default)]
+///     roles: Vec<String>,
+/// }
+///
+/// async fn current(auth: auth::JWT<TenantClaims>) -> Result<Response> {
+///     format::text(&auth.claims.tenant_id)
+/// }
+/// ```
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JWT<C = auth::jwt::UserClaims> {
+    pub claims: C,
 }
 
 // Implement the FromRequestParts trait for the Auth struct
-impl<S> FromRequestParts<S> for JWT
+impl<S, C> FromRequestParts<S> for JWT<C>
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+    C: DeserializeOwned,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
+        let ctx: AppContext = AppContext::from_ref(state);
+
+        let jwt_config = get_jwt_from_request(&ctx, parts)?;
+        let token = extract_token(jwt_config, parts)?;
+        let jwt = get_jwt_keys_from_request(&ctx, parts)?;
+        let claims = authenticate(&ctx, jwt_config, &jwt, &token).await?;
+
+        Ok(Self {
+            claims: into_claims(claims)?,
+        })
+    }
+}
+
+/// Converts validated claims into the claims type of the extractor.
+fn into_claims<C: DeserializeOwned>(claims: auth::jwt::UserClaims) -> LocoResult<C> {
+    serde_json::to_value(claims)
+        .and_then(serde_json::from_value)
+        .map_err(token_is_not_valid)
+}
+
+/// Like [`JWT`], but yields `None` instead of rejecting the request when no
+/// token is present. A present but invalid token is still rejected.
+///
+/// # Example
+/// ```rust
+/// use loco_rs::prelude::*;
+/// use loco_rs::controller::extractor::auth;
+///
+/// async fn home(auth::OptionalJWT(auth): auth::OptionalJWT) -> Result<Response> {
+///     match auth {
+///         Some(auth) => format::text(&format!("hello {}", auth.claims.pid)),
+///         None => format::text("hello stranger"),
+///     }
+/// }
+/// ```
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OptionalJWT<C = auth::jwt::UserClaims>(pub Option<JWT<C>>);
+
+impl<S, C> FromRequestParts<S> for OptionalJWT<C>
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+    C: DeserializeOwned,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
+        let ctx: AppContext = AppContext::from_ref(state);
+
+        let jwt_config = get_jwt_from_request(&ctx, parts)?;
+        let Ok(token) = extract_token(jwt_config, parts) else {
+            return Ok(Self(None));
+        };
+
+        let jwt = get_jwt_keys_from_request(&ctx, parts)?;
+        let claims = authenticate(&ctx, jwt_config, &jwt, &token).await?;
+
+        Ok(Self(Some(JWT {
+            claims: into_claims(claims)?,
+        })))
+    }
+}
+
+// ---------------------------------------
+//
+// Role / Permission extractors
+//
+// ---------------------------------------
+
+/// A role required by the [`RequireRole`] extractor.
+///
+/// # Example
+/// ```rust
+/// use loco_rs::prelude::*;
+/// use loco_rs::controller::extractor::auth::{RequireRole, Role};
+///
+/// pub struct Admin;
+///
+/// impl Role for Admin {
+///     const NAME: &'static str = "admin";
+/// }
+///
+/// async fn dashboard(auth: RequireRole<Admin>) -> Result<Response> {
+///     format::text(&auth.claims.pid)
+/// }
+/// ```
+pub trait Role {
+    /// The role name, as found in the `roles` claim
+    const NAME: &'static str;
+}
+
+/// A permission required by the [`RequirePermission`] extractor.
+pub trait Permission {
+    /// The permission name, as found in the `permissions` claim
+    const NAME: &'static str;
+}
+
+/// Like [`JWT`], but additionally rejects the request with `403 Forbidden`
+/// when the user was not granted the role `R`.
+pub struct RequireRole<R: Role> {
+    pub claims: auth::jwt::UserClaims,
+    role: PhantomData<fn() -> R>,
+}
+
+impl<R: Role> std::fmt::Debug for RequireRole<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequireRole")
+            .field("role", &R::NAME)
+            .field("claims", &self.claims)
+            .finish()
+    }
+}
+
+impl<S, R> FromRequestParts<S> for RequireRole<R>
 where
     AppContext: FromRef<S>,
     S: Send + Sync,
+    R: Role,
 {
     type Rejection = Error;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
-        extract_jwt_from_request_parts(parts, state)
+        let JWT { claims }: JWT = JWT::from_request_parts(parts, state).await?;
+
+        if !claims.has_role(R::NAME) {
+            return Err(Error::Forbidden(format!("missing role `{}`", R::NAME)));
+        }
+
+        Ok(Self {
+            claims,
+            role: PhantomData,
+        })
+    }
+}
+
+/// Like [`JWT`], but additionally rejects the request with `403 Forbidden`
+/// when the user was not granted the permission `P`.
+pub struct RequirePermission<P: Permission> {
+    pub claims: auth::jwt::UserClaims,
+    permission: PhantomData<fn() -> P>,
+}
+
+impl<P: Permission> std::fmt::Debug for RequirePermission<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequirePermission")
+            .field("permission", &P::NAME)
+            .field("claims", &self.claims)
+            .finish()
+    }
+}
+
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+    P: Permission,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
+        let JWT { claims }: JWT = JWT::from_request_parts(parts, state).await?;
+
+        if !claims.has_permission(P::NAME) {
+            return Err(Error::Forbidden(format!(
+                "missing permission `{}`",
+                P::NAME
+            )));
+        }
+
+        Ok(Self {
+            claims,
+            permission: PhantomData,
+        })
     }
 }
 
 /// extract a [JWT] token from request parts, using a non-mutable reference to the [Parts]
 ///
+/// Like the [`JWT`] extractor, the keys of `auth.jwt.jwks` are fetched when
+/// needed and the registered [`auth::validator::TokenValidators`] are run.
+///
 /// # Errors
 /// Return an error when JWT token not configured or when the token is not valid
-pub fn extract_jwt_from_request_parts<S>(parts: &Parts, state: &S) -> Result<JWT, Error>
+pub async fn extract_jwt_from_request_parts<S>(parts: &Parts, state: &S) -> Result<JWT, Error>
 where
     AppContext: FromRef<S>,
     S: Send + Sync,
 {
     let ctx: AppContext = AppContext::from_ref(state); // change to ctx
 
-    let token = extract_token(get_jwt_from_config(&ctx)?, parts)?;
+    let jwt_config = get_jwt_from_request(&ctx, parts)?;
+    let token = extract_token(jwt_config, parts)?;
+    let jwt = get_jwt_keys_from_request(&ctx, parts)?;
 
-    let jwt_secret = ctx.config.get_jwt_config()?;
+    Ok(JWT {
+        claims: authenticate(&ctx, jwt_config, &jwt, &token).await?,
+    })
+}
 
-    match auth::jwt::JWT::new(&jwt_secret.secret).validate(&token) {
-        Ok(claims) => Ok(JWT {
-            claims: claims.claims,
-        }),
-        Err(err) => {
-            tracing::error!("JWT validation error: {}", err);
-            Err(Error::Unauthorized("token is not valid".to_string()))
+/// Validates a token with [`validate_token`], then runs the
+/// [`auth::validator::TokenValidators`] registered in the shared store.
+async fn authenticate(
+    ctx: &AppContext,
+    jwt_config: &JWTConfig,
+    jwt: &auth::jwt::JWT,
+    token: &str,
+) -> LocoResult<auth::jwt::UserClaims> {
+    let claims = validate_token(jwt_config, jwt, token).await?;
+
+    if let Some(validators) = ctx.shared_store.get::<auth::validator::TokenValidators>() {
+        validators.validate(ctx, &claims).await?;
+    }
+
+    Ok(claims)
+}
+
+/// Validates a token with the keys of `jwt`, or with the keys of the
+/// configured JWKS endpoint, fetching them when needed.
+///
+/// # Errors
+/// Return an error when the keys are misconfigured or when the token is not
+/// valid
+pub async fn validate_token(
+    jwt_config: &JWTConfig,
+    jwt: &auth::jwt::JWT,
+    token: &str,
+) -> LocoResult<auth::jwt::UserClaims> {
+    #[cfg(feature = "auth_jwks")]
+    if let Some(jwks) = &jwt_config.jwks {
+        return auth::jwks::Jwks::shared(jwks)
+            .validate_with(
+                token,
+                &auth::jwt::ValidationOptions::from_config(jwt_config),
+            )
+            .await
+            .map(|data| data.claims)
+            .map_err(token_is_not_valid);
+    }
+
+    validate_token_without_fetch(jwt_config, jwt, token)
+}
+
+fn validate_token_without_fetch(
+    jwt_config: &JWTConfig,
+    jwt: &auth::jwt::JWT,
+    token: &str,
+) -> LocoResult<auth::jwt::UserClaims> {
+    if let Some(jwks) = &jwt_config.jwks {
+        #[cfg(feature = "auth_jwks")]
+        return auth::jwks::Jwks::shared(jwks)
+            .validate_cached_with(
+                token,
+                &auth::jwt::ValidationOptions::from_config(jwt_config),
+            )
+            .map(|data| data.claims)
+            .map_err(token_is_not_valid);
+
+        #[cfg(not(feature = "auth_jwks"))]
+        {
+            let _ = jwks;
+            return Err(Error::string(
+                "`auth.jwt.jwks` requires the `auth_jwks` feature",
+            ));
         }
     }
+
+    jwt.validate(token)
+        .map(|data| data.claims)
+        .map_err(token_is_not_valid)
+}
+
+fn token_is_not_valid(err: impl std::fmt::Display) -> Error {
+    tracing::error!("JWT validation error: {}", err);
+    Error::Unauthorized("token is not valid".to_string())
 }
 
 /// extract JWT token from context configuration
@@ -158,6 +506,90 @@ pub fn get_jwt_from_config(ctx: &AppContext) -> LocoResult<&JWTConfig> {
         .as_ref()
         .ok_or_else(|| Error::string("JWT token not configured"))
 }
+
+/// A JWT configuration replacing `auth.jwt` for a group of routes, for
+/// example an admin API using a different secret than the public API.
+///
+/// # Example
+/// ```rust
+/// use loco_rs::prelude::*;
+/// use loco_rs::controller::extractor::auth::{self, RouteJWTConfig};
+///
+/// async fn dashboard(auth: auth::JWT) -> Result<Response> {
+///     format::text(&auth.claims.pid)
+/// }
+///
+/// fn admin_routes(ctx: &AppContext) -> Result<Routes> {
+///     let mut jwt = ctx.config.get_jwt_config()?.clone();
+///     jwt.secret = "admin-secret".to_string();
+///
+///     Ok(Routes::new()
+///         .prefix("admin")
+///         .add("/dashboard", get(dashboard))
+///         .layer(RouteJWTConfig::new(jwt)?.layer()))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RouteJWTConfig {
+    pub config: Arc<JWTConfig>,
+    /// The keys of the configuration, loaded when the routes are built
+    pub jwt: Arc<auth::jwt::JWT>,
+}
+
+impl RouteJWTConfig {
+    /// Loads the keys of the configuration.
+    ///
+    /// # Errors
+    ///
+    /// When a key file could not be read, or an asymmetric algorithm has no
+    /// key
+    pub fn new(config: JWTConfig) -> LocoResult<Self> {
+        Ok(Self {
+            jwt: Arc::new(auth::jwt::JWT::from_config(&config)?),
+            config: Arc::new(config),
+        })
+    }
+
+    /// Returns a layer attaching this configuration to every request of the
+    /// routes it wraps.
+    pub fn layer(self) -> Extension<Self> {
+        Extension(self)
+    }
+}
+
+/// extract the JWT configuration of a request: the [`RouteJWTConfig`]
+/// attached to its route when present, otherwise the `auth.jwt`
+/// configuration.
+///
+/// # Errors
+/// Return an error when JWT token not configured
+pub fn get_jwt_from_request<'a>(
+    ctx: &'a AppContext,
+    parts: &'a Parts,
+) -> LocoResult<&'a JWTConfig> {
+    if let Some(route) = parts.extensions.get::<RouteJWTConfig>() {
+        return Ok(&route.config);
+    }
+    get_jwt_from_config(ctx)
+}
+
+/// The [`auth::jwt::JWT`] of a request, with its keys loaded once: the one
+/// of the [`RouteJWTConfig`] attached to its route when present, otherwise
+/// the one of `auth.jwt`.
+///
+/// # Errors
+/// Return an error when JWT token not configured, or its keys could not be
+/// loaded
+pub fn get_jwt_keys_from_request(
+    ctx: &AppContext,
+    parts: &Parts,
+) -> LocoResult<Arc<auth::jwt::JWT>> {
+    if let Some(route) = parts.extensions.get::<RouteJWTConfig>() {
+        return Ok(route.jwt.clone());
+    }
+    auth::jwt::JWT::from_context(ctx)
+}
+
 /// extract token from the configured jwt location settings
 ///
 /// # Errors
@@ -254,10 +686,56 @@ pub fn extract_token_from_query(name: &str, parts: &Parts) -> LocoResult<String>
 #[cfg(feature = "with-db")]
 #[derive(Debug, Deserialize, Serialize)]
 // Represents the data structure for the API token.
-pub struct ApiToken<T: Authenticable> {
+//
+// `S` optionally restricts the token to keys granted a [`Scope`], which
+// requires the user model to implement [`ScopedApiKey`].
+pub struct ApiToken<T: Authenticable, S = AnyScope> {
     pub user: T,
+    #[serde(skip)]
+    scope: PhantomData<fn() -> S>,
 }
 
+/// A scope required by [`ApiToken`].
+///
+/// # Example
+/// ```rust
+/// use loco_rs::prelude::*;
+/// use loco_rs::controller::extractor::auth::{ApiToken, Scope};
+///
+/// pub struct ReadPosts;
+///
+/// impl Scope for ReadPosts {
+///     const NAME: &'static str = "posts:read";
+/// }
+///
+/// # #[derive(Clone)]
+/// # struct User;
+/// # #[async_trait]
+/// # impl Authenticable for User {
+/// #     async fn find_by_api_key(_: &DatabaseConnection, _: &str) -> ModelResult<Self> { Ok(Self) }
+/// #     async fn find_by_claims_key(_: &DatabaseConnection, _: &str) -> ModelResult<Self> { Ok(Self) }
+/// # }
+/// # #[async_trait]
+/// # impl loco_rs::model::ScopedApiKey for User {
+/// #     async fn find_by_scoped_api_key(_: &DatabaseConnection, _: &str) -> ModelResult<(Self, loco_rs::model::ApiKeyGrant)> {
+/// #         Ok((Self, loco_rs::model::ApiKeyGrant::default()))
+/// #     }
+/// # }
+/// async fn list(auth: ApiToken<User, ReadPosts>) -> Result<Response> {
+///     format::json(())
+/// }
+/// ```
+#[cfg(feature = "with-db")]
+pub trait Scope {
+    /// The scope name, as granted to the API key
+    const NAME: &'static str;
+}
+
+/// The default scope of [`ApiToken`]: any valid API key is accepted.
+#[cfg(feature = "with-db")]
+#[derive(Debug)]
+pub struct AnyScope;
+
 // Implementing the `FromRequestParts` trait for `ApiToken` to enable extracting
 // it from the request.
 #[cfg(feature = "with-db")]
@@ -280,16 +758,363 @@ where
         // Retrieve user information based on the API key from the database.
         let user = T::find_by_api_key(&state.db, &api_key)
             .await
-            .map_err(|e| match e {
-                ModelError::EntityNotFound => Error::Unauthorized("not found".to_string()),
-                ModelError::DbErr(db_err) => {
-                    tracing::error!("Database error during API key authentication: {}", db_err);
-                    Error::InternalServerError
-                }
-                _ => {
-                    tracing::error!("API key authentication error: {}", e);
-                    Error::Unauthorized("could not authorize".to_string())
-                }
+            .map_err(api_key_error)?;
+
+        Ok(Self {
+            user,
+            scope: PhantomData,
+        })
+    }
+}
+
+// Scoped variant: the key must be granted the scope, not be expired, and stay
+// within its rate limit.
+#[cfg(feature = "with-db")]
+impl<S, T, Sc> FromRequestParts<S> for ApiToken<T, Sc>
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+    T: ScopedApiKey + Send,
+    Sc: Scope,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
+        let api_key = extract_token_from_header(&parts.headers)?;
+
+        let state: AppContext = AppContext::from_ref(state);
+
+        let (user, grant) = T::find_by_scoped_api_key(&state.db, &api_key)
+            .await
+            .map_err(api_key_error)?;
+
+        if grant.is_expired() {
+            return Err(Error::Unauthorized("API key is expired".to_string()));
+        }
+
+        if !grant.has_scope(Sc::NAME) {
+            return Err(Error::Forbidden(format!(
+                "API key is missing scope `{}`",
+                Sc::NAME
+            )));
+        }
+
+        if let Some(limit) = grant.rate_limit {
+            check_api_key_rate_limit(&state, &api_key, limit).await?;
+        }
+
+        if let Err(err) = T::api_key_used(&state.db, &api_key).await {
+            tracing::error!("could not track API key usage: {}", err);
+        }
+
+        Ok(Self {
+            user,
+            scope: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "with-db")]
+fn api_key_error(e: ModelError) -> Error {
+    match e {
+        ModelError::EntityNotFound => Error::Unauthorized("not found".to_string()),
+        ModelError::DbErr(db_err) => {
+            tracing::error!("Database error during API key authentication: {}", db_err);
+            Error::InternalServerError
+        }
+        _ => {
+            tracing::error!("API key authentication error: {}", e);
+            Error::Unauthorized("could not authorize".to_string())
+        }
+    }
+}
+
+/// Counts the requests of an API key in the application cache, over fixed
+/// windows of one minute. The requests are let through when the cache can
+/// not count them.
+#[cfg(feature = "with-db")]
+async fn check_api_key_rate_limit(ctx: &AppContext, api_key: &str, limit: u32) -> LocoResult<()> {
+    let window = chrono::Utc::now().timestamp() / 60;
+    let key = format!(
+        "api_key_rate_limit:{}:{window}",
+        hash::hash_api_key(api_key)
+    );
+
+    match ctx
+        .cache
+        .increment(&key, std::time::Duration::from_secs(60))
+        .await
+    {
+        Ok(count) if count > u64::from(limit) => Err(Error::CustomError(
+            StatusCode::TOO_MANY_REQUESTS,
+            ErrorDetail::new("too_many_requests", "API key rate limit exceeded"),
+        )),
+        Ok(_) => Ok(()),
+        Err(err) => {
+            tracing::error!(error = %err, "could not count request for API key rate limiting");
+            Ok(())
+        }
+    }
+}
+
+// ---------------------------------------
+//
+// Magic link extractor
+//
+// ---------------------------------------
+
+#[derive(Debug, Deserialize)]
+struct MagicLinkQuery {
+    token: String,
+}
+
+/// Exchanges the `token` query parameter of a magic link (see
+/// [`auth::magic_link`]) for a JWT. The link can only be used once.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MagicLinkLogin {
+    /// The pid of the logged in user
+    pub pid: String,
+    /// A JWT issued for the user, with the `auth.jwt` configuration
+    pub jwt: String,
+}
+
+impl<S> FromRequestParts<S> for MagicLinkLogin
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Error> {
+        let ctx: AppContext = AppContext::from_ref(state);
+
+        let Query(query) = Query::<MagicLinkQuery>::try_from_uri(&parts.uri)
+            .map_err(|_| Error::Unauthorized("magic link token not found".to_string()))?;
+
+        let pid = auth::magic_link::MagicLinks::from_context(&ctx)
+            .verify(&query.token)
+            .await?;
+
+        let jwt_config = get_jwt_from_request(&ctx, parts)?;
+        let jwt = get_jwt_keys_from_request(&ctx, parts)?
+            .generate_token(jwt_config.expiration, pid.clone(), serde_json::Map::new())
+            .map_err(Error::wrap)?;
+
+        Ok(Self { pid, jwt })
+    }
+}
+
+// ---------------------------------------
+//
+// Passkey extractor
+//
+// ---------------------------------------
+
+/// Completes a passkey login started with
+/// [`auth::webauthn::Passkeys::start_authentication`], reading the
+/// credential from the JSON body, and issues a JWT for the user.
+#[cfg(all(feature = "auth_webauthn", feature = "with-db"))]
+#[derive(Debug)]
+pub struct PasskeyLogin<T: crate::model::WebAuthnAuthenticable> {
+    pub user: T,
+    /// The pid of the logged in user
+    pub pid: String,
+    /// A JWT issued for the user, with the `auth.jwt` configuration
+    pub jwt: String,
+}
+
+#[cfg(all(feature = "auth_webauthn", feature = "with-db"))]
+impl<S, T> axum::extract::FromRequest<S> for PasskeyLogin<T>
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+    T: crate::model::WebAuthnAuthenticable + Send,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: axum::extract::Request, state: &S) -> Result<Self, Error> {
+        let ctx: AppContext = AppContext::from_ref(state);
+
+        let (mut parts, body) = req.into_parts();
+        let session = crate::session::Session::from_request_parts(&mut parts, state).await?;
+        let jwt_config = get_jwt_from_request(&ctx, &parts)?.clone();
+        let keys = get_jwt_keys_from_request(&ctx, &parts)?;
+
+        let axum::Json(credential) =
+            axum::Json::<auth::webauthn::PublicKeyCredential>::from_request(
+                axum::extract::Request::from_parts(parts, body),
+                state,
+            )
+            .await?;
+
+        let (pid, result) = auth::webauthn::Passkeys::from_context(&ctx)?
+            .finish_authentication(&session, &credential)?;
+
+        let user = T::find_by_claims_key(&ctx.db, &pid)
+            .await
+            .map_err(|_| Error::Unauthorized("not found".to_string()))?;
+        user.passkey_used(&ctx.db, &result).await?;
+
+        let jwt = keys
+            .generate_token(jwt_config.expiration, pid.clone(), serde_json::Map::new())
+            .map_err(Error::wrap)?;
+
+        Ok(Self { user, pid, jwt })
+    }
+}
+
+// ---------------------------------------
+//
+// Basic Auth extractor
+//
+// ---------------------------------------
+
+/// The realm sent when no basic auth config is found
+const DEFAULT_BASIC_AUTH_REALM: &str = "loco";
+
+/// Rejection of the basic auth extractors. Unauthorized requests are
+/// answered with a `WWW-Authenticate` challenge so that browsers prompt for
+/// credentials.
+#[derive(Debug)]
+pub struct BasicAuthRejection {
+    realm: String,
+    error: Error,
+}
+
+impl BasicAuthRejection {
+    fn new(realm: &str, error: Error) -> Self {
+        Self {
+            realm: realm.to_string(),
+            error,
+        }
+    }
+}
+
+impl IntoResponse for BasicAuthRejection {
+    fn into_response(self) -> Response {
+        let challenge = HeaderValue::from_str(&format!("Basic realm=\"{}\"", self.realm));
+        let mut response = self.error.into_response();
+        if response.status() == StatusCode::UNAUTHORIZED {
+            if let Ok(challenge) = challenge {
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, challenge);
+            }
+        }
+        response
+    }
+}
+
+/// Extracts the username and password of an `Authorization: Basic` header.
+fn extract_basic_credentials(headers: &HeaderMap) -> LocoResult<(String, String)> {
+    let credentials = headers
+        .typed_get::<Authorization<Basic>>()
+        .ok_or_else(|| Error::Unauthorized("basic auth credentials not found".to_string()))?;
+    Ok((
+        credentials.username().to_string(),
+        credentials.password().to_string(),
+    ))
+}
+
+/// A request authenticated with the static credentials of the `auth.basic`
+/// configuration.
+///
+/// # Example
+/// ```rust
+/// use loco_rs::prelude::*;
+/// use loco_rs::controller::extractor::auth::BasicAuth;
+///
+/// async fn admin(auth: BasicAuth) -> Result<Response> {
+///     format::text(&format!("hello {}", auth.username))
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BasicAuth {
+    pub username: String,
+}
+
+impl<S> FromRequestParts<S> for BasicAuth
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = BasicAuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ctx: AppContext = AppContext::from_ref(state);
+        let config = ctx
+            .config
+            .get_basic_auth_config()
+            .map_err(|err| BasicAuthRejection::new(DEFAULT_BASIC_AUTH_REALM, err))?;
+
+        let (username, password) = extract_basic_credentials(&parts.headers)
+            .map_err(|err| BasicAuthRejection::new(&config.realm, err))?;
+
+        // compare with every user, so that the response time does not tell
+        // which usernames exist
+        let valid = config.users.iter().fold(false, |valid, user| {
+            let username_matches =
+                hash::constant_time_eq(user.username.as_bytes(), username.as_bytes());
+            let password_matches =
+                hash::constant_time_eq(user.password.as_bytes(), password.as_bytes());
+            valid | (username_matches & password_matches)
+        });
+
+        if !valid {
+            return Err(BasicAuthRejection::new(
+                &config.realm,
+                Error::Unauthorized("invalid basic auth credentials".to_string()),
+            ));
+        }
+
+        Ok(Self { username })
+    }
+}
+
+/// A request authenticated with the credentials of a user, looked up with
+/// [`BasicAuthenticable::find_by_basic_auth`].
+#[cfg(feature = "with-db")]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BasicAuthWithUser<T: BasicAuthenticable> {
+    pub user: T,
+}
+
+#[cfg(feature = "with-db")]
+impl<S, T> FromRequestParts<S> for BasicAuthWithUser<T>
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+    T: BasicAuthenticable,
+{
+    type Rejection = BasicAuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ctx: AppContext = AppContext::from_ref(state);
+        let realm = ctx
+            .config
+            .get_basic_auth_config()
+            .map_or(DEFAULT_BASIC_AUTH_REALM, |config| config.realm.as_str());
+
+        let (username, password) = extract_basic_credentials(&parts.headers)
+            .map_err(|err| BasicAuthRejection::new(realm, err))?;
+
+        let user = T::find_by_basic_auth(&ctx.db, &username, &password)
+            .await
+            .map_err(|err| {
+                let err = match err {
+                    ModelError::EntityNotFound => {
+                        Error::Unauthorized("invalid basic auth credentials".to_string())
+                    }
+                    ModelError::DbErr(db_err) => {
+                        tracing::error!("Database error during basic authentication: {}", db_err);
+                        Error::InternalServerError
+                    }
+                    err => {
+                        tracing::error!("basic authentication error: {}", err);
+                        Error::Unauthorized("could not authorize".to_string())
+                    }
+                };
+                BasicAuthRejection::new(realm, err)
             })?;
 
         Ok(Self { user })
@@ -498,6 +1323,7 @@ mod tests {
             location: None,
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let locations = get_jwt_locations(jwt_config.location.as_ref());
@@ -513,6 +1339,7 @@ mod tests {
             )),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let locations = get_jwt_locations(jwt_config.location.as_ref());
@@ -530,6 +1357,7 @@ mod tests {
             )),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let locations = get_jwt_locations(jwt_config.location.as_ref());
@@ -547,6 +1375,7 @@ mod tests {
             )),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let locations = get_jwt_locations(jwt_config.location.as_ref());
@@ -568,6 +1397,7 @@ mod tests {
             ])),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let locations = get_jwt_locations(jwt_config.location.as_ref());
@@ -640,6 +1470,7 @@ mod tests {
             )),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -667,6 +1498,7 @@ mod tests {
             ])),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -693,6 +1525,7 @@ mod tests {
             ])),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -715,6 +1548,7 @@ mod tests {
             location: None,
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -738,6 +1572,7 @@ mod tests {
             )),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -763,6 +1598,7 @@ mod tests {
             )),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -788,6 +1624,7 @@ mod tests {
             )),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -816,6 +1653,7 @@ mod tests {
             ])),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -844,6 +1682,7 @@ mod tests {
             ])),
             secret: String::new(),
             expiration: 1,
+            ..Default::default()
         };
 
         let request = axum::http::Request::builder()
@@ -857,4 +1696,48 @@ mod tests {
         let error_msg = result.unwrap_err().to_string();
         assert!(error_msg.contains("auth.jwt.location configuration"));
     }
+
+    #[tokio::test]
+    async fn test_get_jwt_from_request_prefers_route_config() {
+        let ctx = crate::tests_cfg::app::get_app_context().await;
+        let route_config = JWTConfig {
+            secret: "cm91dGUtc2VjcmV0".to_string(),
+            expiration: 1,
+            ..Default::default()
+        };
+
+        let request = axum::http::Request::builder()
+            .extension(RouteJWTConfig::new(route_config).unwrap())
+            .body(())
+            .unwrap();
+        let (parts, ()) = request.into_parts();
+
+        let jwt_config = get_jwt_from_request(&ctx, &parts).unwrap();
+        assert_eq!(jwt_config.secret, "cm91dGUtc2VjcmV0");
+        let token = auth::jwt::JWT::new("cm91dGUtc2VjcmV0")
+            .algorithm(jsonwebtoken::Algorithm::HS512)
+            .generate_token(60, "pid".to_string(), serde_json::Map::new())
+            .unwrap();
+        assert!(get_jwt_keys_from_request(&ctx, &parts)
+            .unwrap()
+            .validate(&token)
+            .is_ok());
+    }
+
+    #[cfg(feature = "with-db")]
+    #[tokio::test]
+    async fn api_key_rate_limit_counts_requests_and_fails_open() {
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        ctx.cache =
+            crate::cache::drivers::inmem::new(&config::InMemCacheConfig { max_capacity: 100 })
+                .into();
+        assert!(check_api_key_rate_limit(&ctx, "lo-key", 2).await.is_ok());
+        assert!(check_api_key_rate_limit(&ctx, "lo-key", 2).await.is_ok());
+        assert!(check_api_key_rate_limit(&ctx, "lo-key", 2).await.is_err());
+        assert!(check_api_key_rate_limit(&ctx, "lo-other", 2).await.is_ok());
+
+        ctx.cache = crate::cache::Cache::new(crate::cache::drivers::null::new()).into();
+        assert!(check_api_key_rate_limit(&ctx, "lo-key", 1).await.is_ok());
+        assert!(check_api_key_rate_limit(&ctx, "lo-key", 1).await.is_ok());
+    }
 }
@@ -1,4 +1,6 @@
 #[cfg(feature = "auth_jwt")]
 pub mod auth;
+pub mod pagination;
 pub mod shared_store;
+pub mod upload;
 pub mod validate;
@@ -0,0 +1,311 @@
+//! # Pagination
+//!
+//! The [`Pagination`] extractor reads the page a request asks for from its
+//! query, by number (`?page=2&per_page=50`, `page_size` being accepted as
+//! well) or by cursor (`?cursor=abc&per_page=50`). The page size defaults to
+//! `pagination.default_page_size` and is capped to
+//! `pagination.max_page_size` of the configuration.
+//!
+//! Respond with [`crate::controller::format::paginated`], which wraps the
+//! items in a [`Paginated`] envelope with the total counts and the links to
+//! the other pages.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::controller::extractor::pagination::Pagination;
+//!
+//! async fn list(pagination: Pagination) -> Result<Response> {
+//!     let items: Vec<u64> = (1..=1000).collect();
+//!     let page: Vec<u64> = items
+//!         .iter()
+//!         .skip(usize::try_from(pagination.offset()).unwrap_or(usize::MAX))
+//!         .take(usize::try_from(pagination.per_page).unwrap_or(usize::MAX))
+//!         .copied()
+//!         .collect();
+//!     format::paginated(page, pagination.meta(items.len() as u64))
+//! }
+//! ```
+//!
+//! With a database, the page is fetched with the model pagination helpers:
+//! ```rust,ignore
+//! let query = query::PaginationQuery::from(&pagination);
+//! let page = query::fetch_page(&ctx.db, posts::Entity::find(), &query).await?;
+//! let meta = page.meta(&pagination);
+//! format::paginated(page.page, meta)
+//! ```
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppContext, config, Error, Result};
+
+/// The query parameters of the pagination, left out of the other query
+/// parameters kept in the links.
+const PARAMS: [&str; 4] = ["page", "per_page", "page_size", "cursor"];
+
+#[derive(Deserialize)]
+struct Params {
+    page: Option<String>,
+    per_page: Option<String>,
+    page_size: Option<String>,
+    cursor: Option<String>,
+}
+
+/// The page requested by a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pagination {
+    /// The page number, starting at 1
+    pub page: u64,
+    pub per_page: u64,
+    /// The opaque cursor of the page, when paginating by cursor
+    pub cursor: Option<String>,
+    path: String,
+    /// The raw query parameters other than the pagination ones
+    params: Vec<String>,
+}
+
+impl Pagination {
+    /// Reads the pagination of a request.
+    ///
+    /// # Errors
+    ///
+    /// When the page or the page size is not a positive integer.
+    pub fn from_parts(config: &config::Pagination, parts: &Parts) -> Result<Self> {
+        let Query(query) = Query::<Params>::try_from_uri(&parts.uri)
+            .map_err(|err| Error::BadRequest(err.to_string()))?;
+
+        let page = parse_positive("page", query.page.as_deref())?.unwrap_or(1);
+        let per_page = parse_positive(
+            "per_page",
+            query.per_page.as_deref().or(query.page_size.as_deref()),
+        )?
+        .unwrap_or(config.default_page_size)
+        .clamp(1, config.max_page_size.max(1));
+
+        let params = parts
+            .uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|param| {
+                let key = param.split('=').next().unwrap_or_default();
+                !param.is_empty() && !PARAMS.contains(&key)
+            })
+            .map(ToString::to_string)
+            .collect();
+
+        Ok(Self {
+            page,
+            per_page,
+            cursor: query.cursor.filter(|cursor| !cursor.is_empty()),
+            path: parts.uri.path().to_string(),
+            params,
+        })
+    }
+
+    /// The number of items before the page.
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.page.saturating_sub(1).saturating_mul(self.per_page)
+    }
+
+    /// The metadata of the page, out of the total number of items.
+    #[must_use]
+    pub fn meta(&self, total_items: u64) -> PageMeta {
+        let per_page = self.per_page.max(1);
+        let total_pages = (total_items + per_page - 1) / per_page;
+        PageMeta {
+            page: Some(self.page),
+            per_page: self.per_page,
+            total_items: Some(total_items),
+            total_pages: Some(total_pages),
+            next_cursor: None,
+            links: PageLinks {
+                current: self.page_link(self.page),
+                first: Some(self.page_link(1)),
+                prev: (self.page > 1).then(|| self.page_link(self.page - 1)),
+                next: (self.page < total_pages).then(|| self.page_link(self.page + 1)),
+                last: Some(self.page_link(total_pages.max(1))),
+            },
+        }
+    }
+
+    /// The metadata of the page when paginating by cursor, `next_cursor`
+    /// being the cursor of the next page, if any.
+    #[must_use]
+    pub fn cursor_meta(&self, next_cursor: Option<String>) -> PageMeta {
+        PageMeta {
+            page: None,
+            per_page: self.per_page,
+            total_items: None,
+            total_pages: None,
+            links: PageLinks {
+                current: self.link(self.cursor.as_deref().map(cursor_param)),
+                first: Some(self.link(None)),
+                prev: None,
+                next: next_cursor
+                    .as_deref()
+                    .map(|cursor| self.link(Some(cursor_param(cursor)))),
+                last: None,
+            },
+            next_cursor,
+        }
+    }
+
+    fn page_link(&self, page: u64) -> String {
+        self.link(Some(format!("page={page}")))
+    }
+
+    fn link(&self, position: Option<String>) -> String {
+        let mut params = self.params.clone();
+        params.extend(position);
+        params.push(format!("per_page={}", self.per_page));
+        format!("{}?{}", self.path, params.join("&"))
+    }
+}
+
+impl FromRequestParts<AppContext> for Pagination {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppContext) -> Result<Self> {
+        Self::from_parts(&state.config.pagination, parts)
+    }
+}
+
+fn parse_positive(name: &str, value: Option<&str>) -> Result<Option<u64>> {
+    value
+        .map(|value| match value.parse::<u64>() {
+            Ok(number) if number > 0 => Ok(number),
+            _ => Err(Error::BadRequest(format!(
+                "`{name}` must be a positive integer"
+            ))),
+        })
+        .transpose()
+}
+
+fn cursor_param(cursor: &str) -> String {
+    let encoded: String = cursor
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                char::from(b).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect();
+    format!("cursor={encoded}")
+}
+
+/// A page of items, as responded by
+/// [`crate::controller::format::paginated`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Paginated<T> {
+    pub data: Vec<T>,
+    pub meta: PageMeta,
+}
+
+/// The position of a page among the others.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PageMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+    pub per_page: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_items: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    pub links: PageLinks,
+}
+
+/// The links to a page and the pages around it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub current: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn pagination(uri: &str) -> Result<Pagination> {
+        let (parts, ()) = Request::builder().uri(uri).body(()).unwrap().into_parts();
+        Pagination::from_parts(&config::Pagination::default(), &parts)
+    }
+
+    #[rstest]
+    #[case("/posts", 1, 25)]
+    #[case("/posts?page=3&per_page=10", 3, 10)]
+    #[case("/posts?page=2&page_size=10", 2, 10)]
+    #[case("/posts?per_page=1000", 1, 100)]
+    fn can_read_pages(#[case] uri: &str, #[case] page: u64, #[case] per_page: u64) {
+        let pagination = pagination(uri).unwrap();
+        assert_eq!((pagination.page, pagination.per_page), (page, per_page));
+    }
+
+    #[rstest]
+    #[case("/posts?page=0")]
+    #[case("/posts?page=first")]
+    #[case("/posts?per_page=-1")]
+    fn rejects_invalid_pages(#[case] uri: &str) {
+        assert!(matches!(pagination(uri), Err(Error::BadRequest(_))));
+    }
+
+    #[test]
+    fn can_link_pages() {
+        let pagination = pagination("/posts?q=rust&page=2&per_page=10").unwrap();
+        assert_eq!(pagination.offset(), 10);
+
+        let meta = pagination.meta(25);
+        assert_eq!(meta.total_pages, Some(3));
+        assert_eq!(meta.links.current, "/posts?q=rust&page=2&per_page=10");
+        assert_eq!(
+            meta.links.prev.as_deref(),
+            Some("/posts?q=rust&page=1&per_page=10")
+        );
+        assert_eq!(
+            meta.links.next.as_deref(),
+            Some("/posts?q=rust&page=3&per_page=10")
+        );
+        assert_eq!(
+            meta.links.last.as_deref(),
+            Some("/posts?q=rust&page=3&per_page=10")
+        );
+        assert_eq!(pagination.meta(20).links.next, None);
+    }
+
+    #[test]
+    fn can_link_cursors() {
+        let pagination = pagination("/events?cursor=abc").unwrap();
+        assert_eq!(pagination.cursor.as_deref(), Some("abc"));
+
+        let meta = pagination.cursor_meta(Some("d+f".to_string()));
+        assert_eq!(meta.links.current, "/events?cursor=abc&per_page=25");
+        assert_eq!(
+            meta.links.next.as_deref(),
+            Some("/events?cursor=d%2Bf&per_page=25")
+        );
+        assert_eq!(
+            serde_json::to_value(&meta).unwrap()["next_cursor"],
+            serde_json::json!("d+f")
+        );
+        assert_eq!(pagination.cursor_meta(None).links.next, None);
+    }
+}
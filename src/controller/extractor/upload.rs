@@ -0,0 +1,395 @@
+//! # Uploads
+//!
+//! The [`Uploads`] extractor reads a `multipart/form-data` request, enforcing
+//! the `uploads` configuration: the size of each file and of the whole
+//! request, and the accepted content types. Files larger than
+//! `uploads.spool_threshold` are written to temporary files, deleted once
+//! the [`UploadedFile`] is dropped, rather than kept in memory.
+//!
+//! The request body must also fit in the `limit_payload` middleware limit.
+//!
+//! # Example
+//! ```rust
+//! use std::path::Path;
+//!
+//! use loco_rs::prelude::*;
+//! use loco_rs::controller::extractor::upload::Uploads;
+//!
+//! async fn upload(State(ctx): State<AppContext>, uploads: Uploads) -> Result<Response> {
+//!     let Some(avatar) = uploads.file("avatar") else {
+//!         return bad_request("missing avatar");
+//!     };
+//!     avatar
+//!         .save_to_storage(&ctx.storage, Path::new("avatars/1.png"))
+//!         .await?;
+//!     format::json(data!({ "size": avatar.size, "title": uploads.field("title") }))
+//! }
+//! ```
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    extract::{FromRequest, Multipart, Request},
+    http::StatusCode,
+};
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{app::AppContext, config, controller::ErrorDetail, storage::Storage, Error, Result};
+
+/// The size of the chunks read from temporary files.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The files and fields of a multipart request.
+#[derive(Debug, Default)]
+pub struct Uploads {
+    pub files: Vec<UploadedFile>,
+    pub fields: HashMap<String, String>,
+}
+
+impl Uploads {
+    /// Reads a multipart request with the limits of `config`.
+    ///
+    /// # Errors
+    ///
+    /// A `413 Payload Too Large` error when a file or the request is too
+    /// large, a `415 Unsupported Media Type` error when the content type of
+    /// a file is not accepted, or a `400 Bad Request` error when the request
+    /// is invalid.
+    pub async fn from_multipart(
+        config: &config::Uploads,
+        mut multipart: Multipart,
+    ) -> Result<Self> {
+        let mut uploads = Self::default();
+        let mut total: u64 = 0;
+
+        while let Some(mut field) = multipart.next_field().await.map_err(bad_request)? {
+            let name = field.name().unwrap_or_default().to_string();
+
+            let Some(file_name) = field.file_name().map(ToString::to_string) else {
+                let text = field.text().await.map_err(bad_request)?;
+                total += text.len() as u64;
+                if total > config.max_total_size {
+                    return Err(too_large("the request is too large"));
+                }
+                uploads.fields.insert(name, text);
+                continue;
+            };
+
+            let content_type = field.content_type().map(ToString::to_string);
+            if !is_accepted(&config.content_types, content_type.as_deref()) {
+                return Err(Error::CustomError(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    ErrorDetail::new(
+                        "unsupported_media_type",
+                        format!("the content type of `{file_name}` is not accepted"),
+                    ),
+                ));
+            }
+
+            let mut size: u64 = 0;
+            let mut buffer = BytesMut::new();
+            let mut spool: Option<(TempFile, tokio::fs::File)> = None;
+            while let Some(chunk) = field.chunk().await.map_err(bad_request)? {
+                size += chunk.len() as u64;
+                total += chunk.len() as u64;
+                if size > config.max_file_size {
+                    return Err(too_large(&format!("`{file_name}` is too large")));
+                }
+                if total > config.max_total_size {
+                    return Err(too_large("the request is too large"));
+                }
+
+                if let Some((_, file)) = spool.as_mut() {
+                    file.write_all(&chunk).await?;
+                } else {
+                    buffer.extend_from_slice(&chunk);
+                    if buffer.len() as u64 > config.spool_threshold {
+                        let temp = TempFile::new(config.temp_dir.as_deref());
+                        let mut file = tokio::fs::File::create(&temp.0).await?;
+                        file.write_all(&buffer).await?;
+                        buffer.clear();
+                        spool = Some((temp, file));
+                    }
+                }
+            }
+
+            let contents = match spool {
+                Some((temp, mut file)) => {
+                    file.flush().await?;
+                    Contents::Disk(temp)
+                }
+                None => Contents::Memory(buffer.freeze()),
+            };
+            uploads.files.push(UploadedFile {
+                field_name: name,
+                file_name,
+                content_type,
+                size,
+                contents,
+            });
+        }
+
+        Ok(uploads)
+    }
+
+    /// The first file of a field.
+    #[must_use]
+    pub fn file(&self, field_name: &str) -> Option<&UploadedFile> {
+        self.files.iter().find(|file| file.field_name == field_name)
+    }
+
+    /// The value of a text field.
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+}
+
+impl FromRequest<AppContext> for Uploads {
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &AppContext) -> Result<Self> {
+        let multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(|rejection| Error::BadRequest(rejection.body_text()))?;
+        Self::from_multipart(&state.config.uploads, multipart).await
+    }
+}
+
+/// An uploaded file, in memory or in a temporary file.
+#[derive(Debug)]
+pub struct UploadedFile {
+    /// The name of the form field
+    pub field_name: String,
+    /// The file name sent by the client, which must not be trusted as a path
+    pub file_name: String,
+    pub content_type: Option<String>,
+    /// The size in bytes
+    pub size: u64,
+    contents: Contents,
+}
+
+#[derive(Debug)]
+enum Contents {
+    Memory(Bytes),
+    Disk(TempFile),
+}
+
+impl UploadedFile {
+    /// Whether the file was written to a temporary file.
+    #[must_use]
+    pub const fn is_spooled(&self) -> bool {
+        matches!(self.contents, Contents::Disk(_))
+    }
+
+    /// The contents of the file, read in memory.
+    ///
+    /// # Errors
+    ///
+    /// When the temporary file can not be read.
+    pub async fn bytes(&self) -> Result<Bytes> {
+        match &self.contents {
+            Contents::Memory(bytes) => Ok(bytes.clone()),
+            Contents::Disk(temp) => Ok(tokio::fs::read(&temp.0).await?.into()),
+        }
+    }
+
+    /// Uploads the file to `path` in the storage, streaming temporary files.
+    ///
+    /// # Errors
+    ///
+    /// When the temporary file can not be read, or the upload fails.
+    pub async fn save_to_storage(&self, storage: &Storage, path: &Path) -> Result<()> {
+        match &self.contents {
+            Contents::Memory(bytes) => storage.upload(path, bytes).await?,
+            Contents::Disk(temp) => {
+                let file = tokio::fs::File::open(&temp.0).await?;
+                let stream = futures_util::stream::try_unfold(file, |mut file| async move {
+                    let mut buffer = vec![0; CHUNK_SIZE];
+                    let read = file.read(&mut buffer).await?;
+                    if read == 0 {
+                        return Ok(None);
+                    }
+                    buffer.truncate(read);
+                    Ok::<_, std::io::Error>(Some((Bytes::from(buffer), file)))
+                });
+                storage.upload_stream(path, stream).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A temporary file, deleted when dropped.
+#[derive(Debug)]
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(dir: Option<&Path>) -> Self {
+        let dir = dir.map_or_else(std::env::temp_dir, Path::to_path_buf);
+        Self(dir.join(format!("loco-upload-{}", uuid::Uuid::new_v4())))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.0) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!(path = %self.0.display(), error = %err, "could not delete upload");
+            }
+        }
+    }
+}
+
+fn is_accepted(content_types: &[String], content_type: Option<&str>) -> bool {
+    if content_types.is_empty() {
+        return true;
+    }
+    let Some(essence) = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|essence| essence.trim().to_ascii_lowercase())
+    else {
+        return false;
+    };
+    content_types.iter().any(|accepted| {
+        accepted.strip_suffix("/*").map_or_else(
+            || accepted.eq_ignore_ascii_case(&essence),
+            |kind| essence.split('/').next() == Some(kind),
+        )
+    })
+}
+
+fn bad_request(err: axum::extract::multipart::MultipartError) -> Error {
+    Error::BadRequest(err.body_text())
+}
+
+fn too_large(description: &str) -> Error {
+    Error::CustomError(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        ErrorDetail::new("payload_too_large", description),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::header};
+
+    use super::*;
+    use crate::storage::drivers::mem;
+
+    const BOUNDARY: &str = "loco-boundary";
+
+    /// A form field: its name, the file name and content type of files, and
+    /// its value
+    type Part<'a> = (&'a str, Option<(&'a str, &'a str)>, &'a str);
+
+    fn multipart(parts: &[Part<'_>]) -> Request {
+        let mut body = String::new();
+        for (name, file, value) in parts {
+            body.push_str(&format!("--{BOUNDARY}\r\n"));
+            match file {
+                Some((file_name, content_type)) => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{name}\"; filename=\"{file_name}\"\r\nContent-Type: {content_type}\r\n\r\n"
+                )),
+                None => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{name}\"\r\n\r\n"
+                )),
+            }
+            body.push_str(value);
+            body.push_str("\r\n");
+        }
+        body.push_str(&format!("--{BOUNDARY}--\r\n"));
+
+        Request::builder()
+            .method("POST")
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn uploads(config: &config::Uploads, request: Request) -> Result<Uploads> {
+        let multipart = Multipart::from_request(request, &()).await.unwrap();
+        Uploads::from_multipart(config, multipart).await
+    }
+
+    #[tokio::test]
+    async fn can_read_files_and_fields() {
+        let config = config::Uploads {
+            spool_threshold: 8,
+            ..Default::default()
+        };
+        let request = multipart(&[
+            ("title", None, "holidays"),
+            ("small", Some(("a.txt", "text/plain")), "tiny"),
+            (
+                "large",
+                Some(("b.txt", "text/plain")),
+                "larger than eight bytes",
+            ),
+        ]);
+        let uploads = uploads(&config, request).await.unwrap();
+
+        assert_eq!(uploads.field("title"), Some("holidays"));
+        let small = uploads.file("small").unwrap();
+        assert_eq!(small.file_name, "a.txt");
+        assert!(!small.is_spooled());
+        assert_eq!(small.bytes().await.unwrap(), "tiny");
+
+        let large = uploads.file("large").unwrap();
+        assert!(large.is_spooled());
+        assert_eq!(large.size, 23);
+
+        let storage = Storage::single(mem::new());
+        large
+            .save_to_storage(&storage, Path::new("b.txt"))
+            .await
+            .unwrap();
+        let stored: String = storage.download(Path::new("b.txt")).await.unwrap();
+        assert_eq!(stored, "larger than eight bytes");
+
+        let Contents::Disk(temp) = &large.contents else {
+            unreachable!()
+        };
+        let temp_path = temp.0.clone();
+        assert!(temp_path.exists());
+        drop(uploads);
+        assert!(!temp_path.exists());
+    }
+
+    #[tokio::test]
+    async fn enforces_limits() {
+        let config = config::Uploads {
+            max_file_size: 4,
+            max_total_size: 6,
+            content_types: vec!["image/*".to_string()],
+            ..Default::default()
+        };
+
+        let request = multipart(&[("doc", Some(("a.pdf", "application/pdf")), "pdf")]);
+        assert!(matches!(
+            uploads(&config, request).await,
+            Err(Error::CustomError(StatusCode::UNSUPPORTED_MEDIA_TYPE, _))
+        ));
+
+        let request = multipart(&[("avatar", Some(("a.png", "image/png")), "large")]);
+        assert!(matches!(
+            uploads(&config, request).await,
+            Err(Error::CustomError(StatusCode::PAYLOAD_TOO_LARGE, _))
+        ));
+
+        let request = multipart(&[
+            ("first", Some(("a.png", "image/png")), "four"),
+            ("second", Some(("b.png", "image/png")), "four"),
+        ]);
+        assert!(matches!(
+            uploads(&config, request).await,
+            Err(Error::CustomError(StatusCode::PAYLOAD_TOO_LARGE, _))
+        ));
+    }
+}
@@ -1,8 +1,46 @@
-use crate::validation::ValidatorTrait;
-use axum::extract::{Form, FromRequest, Json, Query, Request};
+use crate::validation::{ModelValidationErrors, ValidatorTrait};
+use axum::{
+    extract::{Form, FromRequest, Json, Query, Request},
+    http::StatusCode,
+};
 use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
 
-use crate::Error;
+use crate::{controller::ErrorDetail, Error};
+
+/// The `422 Unprocessable Entity` error of [`JsonValidate`] and
+/// [`FormValidate`], listing the messages of each invalid field (or the
+/// validation code of the errors without message):
+///
+/// ```json
+/// {
+///   "error": "validation_error",
+///   "description": "The request is invalid",
+///   "errors": { "username": ["username must be at least 3 characters"] }
+/// }
+/// ```
+fn unprocessable(errors: &ModelValidationErrors) -> Error {
+    tracing::debug!(err = ?errors, "request validation error occurred");
+    let fields: BTreeMap<&str, Vec<&str>> = errors
+        .errors
+        .iter()
+        .map(|(field, errors)| {
+            let messages = errors
+                .iter()
+                .map(|err| err.message.as_deref().unwrap_or(&err.code))
+                .collect();
+            (field.as_str(), messages)
+        })
+        .collect();
+    Error::CustomError(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorDetail {
+            error: Some("validation_error".to_string()),
+            description: Some("The request is invalid".to_string()),
+            errors: Some(serde_json::to_value(fields).unwrap_or_default()),
+        },
+    )
+}
 
 /// Axum middleware for validating JSON request bodies
 ///
@@ -99,8 +137,8 @@ where
     }
 }
 
-/// Axum middleware for validating JSON request bodies with simplified error
-/// handling
+/// Axum middleware for validating JSON request bodies, answering invalid
+/// ones with a `422 Unprocessable Entity` listing the errors of each field
 ///
 /// # Example:
 ///
@@ -140,15 +178,13 @@ where
 
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
         let Json(value) = Json::<T>::from_request(req, state).await?;
-        value.validate().map_err(|err| {
-            tracing::debug!(err = ?err, "request validation error occurred");
-            Error::BadRequest(String::new())
-        })?;
+        value.validate().map_err(|err| unprocessable(&err))?;
         Ok(Self(value))
     }
 }
 
-/// Axum middleware for validating form data with simplified error handling
+/// Axum middleware for validating form data, answering invalid forms with a
+/// `422 Unprocessable Entity` listing the errors of each field
 ///
 /// # Example:
 ///
@@ -188,10 +224,7 @@ where
 
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
         let Form(value) = Form::<T>::from_request(req, state).await?;
-        value.validate().map_err(|err| {
-            tracing::debug!(err = ?err, "request validation error occurred");
-            Error::BadRequest(String::new())
-        })?;
+        value.validate().map_err(|err| unprocessable(&err))?;
         Ok(Self(value))
     }
 }
@@ -492,19 +525,21 @@ mod tests {
         let result = JsonValidate::<TestUser>::from_request(request, &()).await;
         assert!(result.is_err());
 
-        let err = result.unwrap_err();
-        if let Error::BadRequest(msg) = &err {
-            assert_eq!(msg, &String::new());
-        } else {
-            panic!("Expected BadRequest error");
-        }
-
         let expected = json!({
-            "error": "Bad Request",
-            // "description": ""
+            "error": "validation_error",
+            "description": "The request is invalid",
+            "errors": {
+                "email": ["email must be valid"],
+                "username": ["username must be at least 3 characters"]
+            }
         });
 
-        assert_response_status_and_body(err, StatusCode::BAD_REQUEST, expected).await;
+        assert_response_status_and_body(
+            result.unwrap_err(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            expected,
+        )
+        .await;
     }
 
     #[tokio::test]
@@ -528,19 +563,21 @@ mod tests {
         let result = FormValidate::<TestUser>::from_request(request, &()).await;
         assert!(result.is_err());
 
-        let err = result.unwrap_err();
-        if let Error::BadRequest(msg) = &err {
-            assert_eq!(msg, &String::new());
-        } else {
-            panic!("Expected BadRequest error");
-        }
-
         let expected = json!({
-            "error": "Bad Request",
-            // "description": ""
+            "error": "validation_error",
+            "description": "The request is invalid",
+            "errors": {
+                "email": ["email must be valid"],
+                "username": ["username must be at least 3 characters"]
+            }
         });
 
-        assert_response_status_and_body(err, StatusCode::BAD_REQUEST, expected).await;
+        assert_response_status_and_body(
+            result.unwrap_err(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            expected,
+        )
+        .await;
     }
 
     #[tokio::test]
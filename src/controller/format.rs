@@ -25,19 +25,26 @@ use std::convert::TryInto;
 use axum::{
     body::Body,
     http::{header, response::Builder, HeaderName, HeaderValue, StatusCode},
-    response::{Html, IntoResponse, Redirect, Response},
+    response::{
+        sse::{Event, KeepAlive},
+        Html, IntoResponse, Redirect, Response,
+    },
 };
 use axum_extra::extract::cookie::Cookie;
-use bytes::{BufMut, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::Stream;
 use serde::Serialize;
 use serde_json::json;
 
 use crate::{
     controller::{
+        extractor::pagination::{PageMeta, Paginated},
+        middleware::{self, format::Negotiate},
+        sse,
         views::{self, ViewRenderer},
-        Json,
+        ErrorDetail, Json,
     },
-    Result,
+    Error, Result,
 };
 
 /// Returns an empty response.
@@ -111,6 +118,57 @@ pub fn json<T: Serialize>(t: T) -> Result<Response> {
     Ok(Json(t).into_response())
 }
 
+/// Respond with json and a strong `ETag` computed from it, so that the
+/// `ETag` middleware answers the clients having it already with a
+/// `304 Not Modified`.
+///
+/// # Example:
+///
+/// ```rust
+/// use loco_rs::prelude::*;
+///
+/// async fn endpoint() -> Result<Response> {
+///    format::json_with_etag(data!({ "ok": true }))
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This function will return an error if serde fails
+pub fn json_with_etag<T: Serialize>(t: T) -> Result<Response> {
+    let body = serde_json::to_vec(&t)?;
+    let etag = middleware::etag::compute_etag(&body, false);
+    Ok(Builder::new()
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::ETAG, etag)
+        .body(Body::from(body))?
+        .into_response())
+}
+
+/// Respond with a page of items in json, in a [`Paginated`] envelope with
+/// the pagination metadata and links, usually computed by the
+/// [`Pagination`] extractor.
+///
+/// # Example:
+///
+/// ```rust
+/// use loco_rs::prelude::*;
+///
+/// async fn endpoint(pagination: Pagination) -> Result<Response> {
+///    format::paginated(vec!["first", "second"], pagination.meta(2))
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Currently this function doesn't return any error. this is for feature
+/// functionality
+///
+/// [`Pagination`]: crate::controller::extractor::pagination::Pagination
+pub fn paginated<T: Serialize>(items: Vec<T>, meta: PageMeta) -> Result<Response> {
+    json(Paginated { data: items, meta })
+}
+
 /// Respond with empty json (`{}`)
 ///
 /// # Errors
@@ -140,6 +198,64 @@ pub fn html(content: &str) -> Result<Response> {
     Ok(Html(content.to_string()).into_response())
 }
 
+/// Returns a Server-Sent Events response, sending each event of `stream`
+/// to the client as it comes, and heartbeats while it is idle. See
+/// [`crate::controller::sse::SseBroadcaster`] to send the same events to
+/// many clients.
+///
+/// # Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use axum::response::sse::Event;
+/// use futures_util::StreamExt;
+/// use loco_rs::prelude::*;
+///
+/// async fn ticks() -> Result<Response> {
+///     let stream = futures_util::stream::iter(0..10)
+///         .then(|n| async move {
+///             tokio::time::sleep(Duration::from_secs(1)).await;
+///             Event::default().event("tick").data(n.to_string())
+///         });
+///     format::sse(stream)
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Currently this function doesn't return any error. this is for feature
+/// functionality
+pub fn sse<S>(stream: S) -> Result<Response>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    Ok(sse::sse_response(stream, KeepAlive::default()))
+}
+
+/// Returns an HTML response whose body is streamed, such as a view rendered
+/// by [`ViewRenderer::render_stream`].
+///
+/// # Example:
+///
+/// ```rust
+/// use loco_rs::prelude::*;
+///
+/// async fn report(ViewEngine(v): ViewEngine<TeraView>) -> Result<Response> {
+///     format::html_stream(v.render_stream("report/show.html", data!({})))
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Currently this function doesn't return any error. this is for feature
+/// functionality
+pub fn html_stream<S>(stream: S) -> Result<Response>
+where
+    S: Stream<Item = Result<Bytes>> + Send + 'static,
+{
+    render().html_stream(stream)
+}
+
 /// Returns a YAML response
 ///
 /// # Example:
@@ -163,6 +279,79 @@ pub fn yaml(content: &str) -> Result<Response> {
         .into_response())
 }
 
+/// Returns a CSV response of `rows`, a sequence of structs or maps whose
+/// fields become the columns, in the alphabetical order of the fields of the
+/// first row, with a header line.
+///
+/// # Example:
+///
+/// ```rust
+/// use loco_rs::prelude::*;
+///
+/// pub async fn export() -> Result<Response> {
+///     format::csv(vec![data!({ "id": 1, "title": "hello, world" })])
+/// }
+/// ```
+///
+/// # Errors
+///
+/// When `rows` is not a sequence of structs or maps, or serde fails
+pub fn csv<T: Serialize>(rows: T) -> Result<Response> {
+    Ok(Builder::new()
+        .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+        .body(Body::from(to_csv(rows)?))?
+        .into_response())
+}
+
+fn to_csv<T: Serialize>(rows: T) -> Result<String> {
+    let serde_json::Value::Array(rows) = serde_json::to_value(rows)? else {
+        return Err(Error::string("csv rows must be a sequence"));
+    };
+    let rows = rows
+        .into_iter()
+        .map(|row| match row {
+            serde_json::Value::Object(fields) => Ok(fields),
+            _ => Err(Error::string("csv rows must be structs or maps")),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let Some(columns) = rows
+        .first()
+        .map(|row| row.keys().cloned().collect::<Vec<_>>())
+    else {
+        return Ok(String::new());
+    };
+
+    let mut csv = String::new();
+    let mut write_line = |cells: Vec<String>| {
+        csv.push_str(&cells.join(","));
+        csv.push_str("\r\n");
+    };
+    write_line(columns.iter().map(|column| csv_cell(column)).collect());
+    for row in &rows {
+        write_line(
+            columns
+                .iter()
+                .map(|column| match row.get(column) {
+                    None | Some(serde_json::Value::Null) => String::new(),
+                    Some(serde_json::Value::String(value)) => csv_cell(value),
+                    Some(value) => csv_cell(&value.to_string()),
+                })
+                .collect(),
+        );
+    }
+    Ok(csv)
+}
+
+/// Quotes a CSV cell when it holds separators, quotes or line breaks.
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
 /// Returns an redirect response
 ///
 /// # Example:
@@ -197,6 +386,20 @@ where
     html(&res)
 }
 
+/// Render template located by `key` inside the `layout` template
+///
+/// # Errors
+///
+/// This function will return an error if rendering fails
+pub fn view_with_layout<V, S>(v: &V, key: &str, layout: &str, data: S) -> Result<Response>
+where
+    V: ViewRenderer,
+    S: Serialize,
+{
+    let res = v.render_with_layout(key, layout, data)?;
+    html(&res)
+}
+
 /// Render template from string
 ///
 /// # Errors
@@ -319,6 +522,36 @@ impl RenderBuilder {
         self.html(&content)
     }
 
+    /// Render template located by `key` inside the `layout` template, see
+    /// [`ViewRenderer::render_with_layout`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if rendering fails
+    pub fn view_with_layout<V, S>(self, v: &V, key: &str, layout: &str, data: S) -> Result<Response>
+    where
+        V: ViewRenderer,
+        S: Serialize,
+    {
+        let content = v.render_with_layout(key, layout, data)?;
+        self.html(&content)
+    }
+
+    /// Render template located by `key`, streaming the response while it
+    /// renders
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if IO fails. Rendering errors
+    /// abort the response stream.
+    pub fn view_stream<V, S>(self, v: &V, key: &str, data: S) -> Result<Response>
+    where
+        V: ViewRenderer,
+        S: Serialize,
+    {
+        self.html_stream(v.render_stream(key, data))
+    }
+
     /// Render template located by `key`
     ///
     /// # Errors
@@ -346,6 +579,24 @@ impl RenderBuilder {
             .body(Body::from(content.to_string()))?)
     }
 
+    /// Finalize and return a HTML response streaming its body
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if IO fails
+    pub fn html_stream<S>(self, stream: S) -> Result<Response>
+    where
+        S: Stream<Item = Result<Bytes>> + Send + 'static,
+    {
+        Ok(self
+            .response
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/html; charset=utf-8"),
+            )
+            .body(Body::from_stream(stream))?)
+    }
+
     /// Finalize and return a JSON response
     ///
     /// # Errors
@@ -406,6 +657,98 @@ pub fn render() -> RenderBuilder {
     RenderBuilder::new()
 }
 
+type Render<'a, T> = Box<dyn FnOnce(T) -> Result<Response> + 'a>;
+
+/// Responds with the representation of the same data preferred by the
+/// `Accept` header of the request, built by [`negotiate`].
+#[must_use]
+pub struct Negotiation<'a, T> {
+    negotiate: &'a Negotiate,
+    data: T,
+    html: Option<Render<'a, T>>,
+    csv: bool,
+}
+
+impl<'a, T: Serialize> Negotiation<'a, T> {
+    /// Offers an HTML representation, usually rendering a view with the
+    /// data.
+    pub fn html(mut self, render: impl FnOnce(T) -> Result<Response> + 'a) -> Self {
+        self.html = Some(Box::new(render));
+        self
+    }
+
+    /// Offers a CSV representation of the data, which must be a sequence of
+    /// rows as accepted by [`csv`].
+    pub fn csv(mut self) -> Self {
+        self.csv = true;
+        self
+    }
+
+    /// Responds with the preferred representation, JSON when the request
+    /// accepts any, with a `Vary: Accept` header.
+    ///
+    /// # Errors
+    ///
+    /// A `406 Not Acceptable` error when the request accepts none of the
+    /// offered representations, or the error of the rendering.
+    pub fn respond(self) -> Result<Response> {
+        let mut offered = vec!["application/json"];
+        if self.html.is_some() {
+            offered.push("text/html");
+        }
+        if self.csv {
+            offered.push("text/csv");
+        }
+
+        let mut response = match (self.negotiate.preferred(&offered), self.html) {
+            (Some("text/html"), Some(html)) => html(self.data)?,
+            (Some("text/csv"), _) => csv(self.data)?,
+            (Some(_), _) => json(self.data)?,
+            (None, _) => {
+                return Err(Error::CustomError(
+                    StatusCode::NOT_ACCEPTABLE,
+                    ErrorDetail::new(
+                        "not_acceptable",
+                        format!("Acceptable representations: {}", offered.join(", ")),
+                    ),
+                ))
+            }
+        };
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("accept"));
+        Ok(response)
+    }
+}
+
+/// Responds with `data` in the representation preferred by the request
+/// among JSON and the ones offered, in the "respond to" style.
+///
+/// # Example:
+///
+/// ```rust
+/// use loco_rs::prelude::*;
+///
+/// async fn list(
+///     negotiate: Negotiate,
+///     ViewEngine(v): ViewEngine<TeraView>,
+/// ) -> Result<Response> {
+///     let posts = vec![data!({ "id": 1, "title": "hello" })];
+///     format::negotiate(&negotiate, posts)
+///         .html(|posts| format::view(&v, "posts/list.html", data!({ "posts": posts })))
+///         .csv()
+///         .respond()
+/// }
+/// ```
+pub fn negotiate<T: Serialize>(negotiate: &Negotiate, data: T) -> Negotiation<'_, T> {
+    Negotiation {
+        negotiate,
+        data,
+        html: None,
+        csv: false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -509,6 +852,85 @@ mod tests {
         assert_eq!(&response_body_to_string(response).await, "- loco");
     }
 
+    #[cfg(not(feature = "embedded_assets"))]
+    #[tokio::test]
+    async fn view_with_layout_response() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add_file("template/test.html", "- {{foo}}")
+            .add_file(
+                "layouts/app.html",
+                "<main>{% block content %}{{ content | safe }}{% endblock %}</main>{{ foo }}",
+            )
+            .add_file(
+                "template/child.html",
+                "{% extends 'layouts/app.html' %}{% block content %}child{% endblock %}",
+            )
+            .create()
+            .unwrap();
+
+        let v = TeraView::from_custom_dir(&tree_fs.root, |_| Ok(())).unwrap();
+
+        let response = render()
+            .view_with_layout(
+                &v,
+                "template/test.html",
+                "layouts/app.html",
+                serde_json::json!({"foo": "<loco>"}),
+            )
+            .unwrap();
+        assert_eq!(
+            &response_body_to_string(response).await,
+            "<main>- &lt;loco&gt;</main>&lt;loco&gt;"
+        );
+
+        // the layout can still be extended
+        let response = view(
+            &v,
+            "template/child.html",
+            serde_json::json!({"foo": "loco"}),
+        )
+        .unwrap();
+        assert_eq!(
+            &response_body_to_string(response).await,
+            "<main>child</main>loco"
+        );
+
+        assert!(view_with_layout(
+            &v,
+            "template/test.html",
+            "layouts/app.html",
+            serde_json::json!(["foo"])
+        )
+        .is_err());
+    }
+
+    #[cfg(not(feature = "embedded_assets"))]
+    #[tokio::test]
+    async fn view_stream_response() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add_file("template/test.html", "- {{foo}}")
+            .create()
+            .unwrap();
+
+        let v = TeraView::from_custom_dir(&tree_fs.root, |_| Ok(())).unwrap();
+
+        let response = render()
+            .view_stream(&v, "template/test.html", serde_json::json!({"foo": "loco"}))
+            .unwrap();
+
+        assert_eq!(
+            get_header_from_response(&response, "content-type"),
+            Some("text/html; charset=utf-8".to_string())
+        );
+        assert_eq!(&response_body_to_string(response).await, "- loco");
+
+        let response =
+            html_stream(v.render_stream("template/none.html", serde_json::json!({}))).unwrap();
+        assert!(axum::body::to_bytes(response.into_body(), 200)
+            .await
+            .is_err());
+    }
+
     #[tokio::test]
     async fn template_response() {
         let response = template("- {{foo}}", serde_json::json!({"foo": "loco"})).unwrap();
@@ -555,6 +977,39 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn sse_response() {
+        use futures_util::StreamExt;
+
+        let stream = futures_util::stream::iter(["a", "b"])
+            .map(|data| Event::default().event("letter").data(data));
+        let response = sse(stream).unwrap();
+
+        assert_eq!(
+            get_header_from_response(&response, "content-type"),
+            Some("text/event-stream".to_string())
+        );
+        assert_eq!(
+            response_body_to_string(response).await,
+            "event: letter\ndata: a\n\nevent: letter\ndata: b\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn json_with_etag_response() {
+        let response = json_with_etag(json!({"a": 1})).unwrap();
+        let etag = get_header_from_response(&response, "etag").unwrap();
+        assert!(etag.starts_with('"'));
+        assert_eq!(
+            get_header_from_response(&response, "content-type"),
+            Some("application/json".to_string())
+        );
+        assert_eq!(response_body_to_string(response).await, r#"{"a":1}"#);
+
+        let response = json_with_etag(json!({"a": 2})).unwrap();
+        assert_ne!(get_header_from_response(&response, "etag").unwrap(), etag);
+    }
+
     #[tokio::test]
     async fn builder_cookies_response() {
         let response = render()
@@ -652,4 +1107,54 @@ mod tests {
         assert_debug_snapshot!(response);
         assert_eq!(response_body_to_string(response).await, String::new());
     }
+
+    #[tokio::test]
+    async fn csv_response_format() {
+        let rows = serde_json::json!([
+            {"id": 1, "title": "hello, \"world\"", "draft": null},
+            {"id": 2, "title": "bye", "draft": true},
+        ]);
+        let response = csv(&rows).unwrap();
+
+        assert_eq!(
+            get_header_from_response(&response, "content-type"),
+            Some("text/csv; charset=utf-8".to_string())
+        );
+        assert_eq!(
+            response_body_to_string(response).await,
+            "draft,id,title\r\n,1,\"hello, \"\"world\"\"\"\r\ntrue,2,bye\r\n"
+        );
+        assert!(csv(serde_json::json!({"id": 1})).is_err());
+    }
+
+    fn negotiation(accept: &str) -> Result<Response<Body>> {
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+        negotiate(
+            &Negotiate::from_headers(&headers),
+            vec![serde_json::json!({"id": 1})],
+        )
+        .html(|rows| html(&format!("<p>{}</p>", rows.len())))
+        .csv()
+        .respond()
+    }
+
+    #[tokio::test]
+    async fn can_negotiate_representations() {
+        let response = negotiation("text/html,*/*;q=0.8").unwrap();
+        assert_eq!(
+            get_header_from_response(&response, "vary"),
+            Some("accept".to_string())
+        );
+        assert_eq!(response_body_to_string(response).await, "<p>1</p>");
+
+        let response = negotiation("text/csv").unwrap();
+        assert_eq!(response_body_to_string(response).await, "id\r\n1\r\n");
+
+        let response = negotiation("*/*").unwrap();
+        assert_eq!(response_body_to_string(response).await, r#"[{"id":1}]"#);
+
+        let err = negotiation("image/png").unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::NOT_ACCEPTABLE);
+    }
 }
@@ -0,0 +1,190 @@
+//! # Mailer Previews
+//!
+//! Renders the [`MailerPreview`]s of the application in the browser, so that
+//! the templates of the mailers can be checked without sending an email. The
+//! previews are added by [`crate::app::Hooks::mailer_previews`], and the
+//! routes only answer in the `development` environment.
+//!
+//! # Example
+//! ```rust,ignore
+//! fn routes(_ctx: &AppContext) -> AppRoutes {
+//!     AppRoutes::with_default_routes().add_route(loco_rs::controller::mailer_preview::routes())
+//! }
+//! ```
+use std::fmt::Write;
+
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::Uri,
+    response::Response,
+    routing::get,
+};
+use serde::Deserialize;
+
+use super::views::escape;
+use crate::{
+    app::AppContext,
+    controller::{format, Routes},
+    environment::Environment,
+    mailer::{Content, MailerPreview, MailerPreviews},
+    Error, Result,
+};
+
+/// The path the previews are mounted at.
+pub const PATH: &str = "/_mailers";
+
+const STYLE: &str = r"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+h1 a { color: inherit; text-decoration: none; }
+dl { display: grid; grid-template-columns: max-content auto; gap: .3rem 1rem; }
+dt { font-weight: bold; }
+iframe { width: 100%; height: 60vh; border: 1px solid #ddd; }
+pre { white-space: pre-wrap; border: 1px solid #ddd; padding: 1rem; }
+.error { color: #b00020; }
+";
+
+/// Routes of the previews, under [`PATH`]:
+/// * `GET /_mailers` lists the previews
+/// * `GET /_mailers/preview?name=auth/welcome` shows the subject, HTML and
+///   text of a preview
+/// * `GET /_mailers/html?name=auth/welcome` renders the HTML of a preview
+#[must_use]
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix(PATH)
+        .add("/", get(list))
+        .add("/preview", get(preview))
+        .add("/html", get(html))
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviewParams {
+    name: String,
+}
+
+async fn list(State(ctx): State<AppContext>, OriginalUri(uri): OriginalUri) -> Result<Response> {
+    let previews = previews(&ctx)?;
+    let base = base_path(&uri, 0);
+    format::html(&page(&base, "Mailers", &list_body(&base, &previews.0)))
+}
+
+async fn preview(
+    State(ctx): State<AppContext>,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<PreviewParams>,
+) -> Result<Response> {
+    let previews = previews(&ctx)?;
+    let preview = previews.get(&params.name).ok_or(Error::NotFound)?;
+    let base = base_path(&uri, 1);
+    let body = match preview.render(&ctx) {
+        Ok(content) => preview_body(&base, &preview.name, &content),
+        Err(err) => format!("<p class=\"error\">{}</p>", escape(&err.to_string())),
+    };
+    format::html(&page(&base, &preview.name, &body))
+}
+
+async fn html(
+    State(ctx): State<AppContext>,
+    Query(params): Query<PreviewParams>,
+) -> Result<Response> {
+    let previews = previews(&ctx)?;
+    let preview = previews.get(&params.name).ok_or(Error::NotFound)?;
+    format::html(&preview.render(&ctx)?.html)
+}
+
+/// The previews of the application, only shown in development.
+fn previews(ctx: &AppContext) -> Result<MailerPreviews> {
+    if ctx.environment != Environment::Development {
+        return Err(Error::NotFound);
+    }
+    Ok(ctx.shared_store.get::<MailerPreviews>().unwrap_or_default())
+}
+
+/// The path of the previews, dropping the last `segments` of the request
+/// path, so that the links follow the prefix the routes are mounted at.
+fn base_path(uri: &Uri, segments: usize) -> String {
+    let mut path = uri.path().trim_end_matches('/');
+    for _ in 0..segments {
+        path = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+    }
+    path.to_string()
+}
+
+fn page(base: &str, title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title} - Loco \
+         mailers</title><style>{STYLE}</style></head><body><h1><a href=\"{base}\">Loco \
+         mailers</a></h1>{body}</body></html>",
+        title = escape(title),
+    )
+}
+
+fn list_body(base: &str, previews: &[MailerPreview]) -> String {
+    if previews.is_empty() {
+        return "<p>No mailer previews, see <code>Hooks::mailer_previews</code>.</p>".to_string();
+    }
+    let mut body = String::from("<ul>");
+    for preview in previews {
+        let name = escape(&preview.name);
+        let _ = write!(
+            body,
+            "<li><a href=\"{base}/preview?name={name}\">{name}</a></li>"
+        );
+    }
+    body.push_str("</ul>");
+    body
+}
+
+fn preview_body(base: &str, name: &str, content: &Content) -> String {
+    let name = escape(name);
+    format!(
+        "<dl><dt>Preview</dt><dd>{name}</dd><dt>Subject</dt><dd>{subject}</dd></dl><h2>HTML</\
+         h2><iframe src=\"{base}/html?name={name}\"></iframe><h2>Text</h2><pre>{text}</pre>",
+        subject = escape(&content.subject),
+        text = escape(&content.text),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use include_dir::{include_dir, Dir};
+
+    use super::*;
+
+    static TEST: Dir<'_> = include_dir!("tests/fixtures/email_template/test");
+
+    #[test]
+    fn can_list_previews() {
+        assert!(list_body("/_mailers", &[]).contains("No mailer previews"));
+
+        let previews = vec![MailerPreview::new(
+            "auth/welcome",
+            &TEST,
+            serde_json::json!({}),
+        )];
+        assert_eq!(
+            list_body("/_mailers", &previews),
+            "<ul><li><a href=\"/_mailers/preview?name=auth/welcome\">auth/welcome</a></li></ul>"
+        );
+    }
+
+    #[test]
+    fn can_show_preview() {
+        let content = Content {
+            subject: "Welcome <Jane>".to_string(),
+            text: "Hello & welcome".to_string(),
+            html: "<p>Hello</p>".to_string(),
+        };
+
+        let body = preview_body("/_mailers", "auth/welcome", &content);
+        assert!(body.contains("<dd>Welcome &lt;Jane&gt;</dd>"));
+        assert!(body.contains("<iframe src=\"/_mailers/html?name=auth/welcome\">"));
+        assert!(body.contains("<pre>Hello &amp; welcome</pre>"));
+    }
+
+    #[test]
+    fn can_strip_base_path() {
+        let uri: Uri = "/admin/_mailers/preview?name=auth/welcome".parse().unwrap();
+        assert_eq!(base_path(&uri, 1), "/admin/_mailers");
+    }
+}
@@ -0,0 +1,156 @@
+//! # Mailer Webhooks
+//!
+//! Receives the bounce and complaint webhooks of the email providers, and
+//! adds the reported addresses to the [`crate::mailer::suppression`] list.
+//! The providers call the webhooks with the `mailer.suppression.webhook_token`
+//! as their `token` query parameter:
+//!
+//! * Amazon SES, through an SNS topic subscribed over HTTPS to
+//!   `/_mailer/webhooks/ses?token=...`. The subscription confirmation URL is
+//!   logged, to be visited once.
+//! * `SendGrid`, with the event webhook set to
+//!   `/_mailer/webhooks/sendgrid?token=...`.
+//!
+//! # Example
+//! ```rust,ignore
+//! fn routes(_ctx: &AppContext) -> AppRoutes {
+//!     AppRoutes::with_default_routes().add_route(loco_rs::controller::mailer_webhooks::routes())
+//! }
+//! ```
+use axum::{
+    extract::{Query, State},
+    response::Response,
+    routing::post,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    app::AppContext,
+    config::SuppressionConfig,
+    controller::{format, Routes},
+    hash,
+    mailer::suppression::{self, Suppression},
+    Error, Result,
+};
+
+/// The path the webhooks are mounted at.
+pub const PATH: &str = "/_mailer/webhooks";
+
+/// Routes of the webhooks, under [`PATH`]:
+/// * `POST /_mailer/webhooks/ses` receives the SNS notifications of SES
+/// * `POST /_mailer/webhooks/sendgrid` receives the `SendGrid` events
+#[must_use]
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix(PATH)
+        .add("/ses", post(ses))
+        .add("/sendgrid", post(sendgrid))
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookParams {
+    token: Option<String>,
+}
+
+/// The suppression config, once the token of the request is checked.
+fn authorize(ctx: &AppContext, params: &WebhookParams) -> Result<SuppressionConfig> {
+    let config = ctx
+        .config
+        .mailer
+        .as_ref()
+        .and_then(|mailer| mailer.suppression.clone())
+        .ok_or(Error::NotFound)?;
+    let authorized = params.token.as_ref().is_some_and(|token| {
+        hash::constant_time_eq(token.as_bytes(), config.webhook_token.as_bytes())
+    });
+    if !authorized {
+        return Err(Error::Unauthorized("invalid webhook token".to_string()));
+    }
+    Ok(config)
+}
+
+async fn suppress_all(ctx: &AppContext, suppressions: &[Suppression]) -> Result<()> {
+    for suppression in suppressions {
+        suppression::suppress(ctx, suppression).await?;
+    }
+    Ok(())
+}
+
+/// SNS sends its messages as `text/plain`, so the body is parsed as JSON
+/// whatever its content type.
+async fn ses(
+    State(ctx): State<AppContext>,
+    Query(params): Query<WebhookParams>,
+    body: String,
+) -> Result<Response> {
+    let config = authorize(&ctx, &params)?;
+    let notification: Value = serde_json::from_str(&body)?;
+    match notification["Type"].as_str() {
+        Some("SubscriptionConfirmation") => {
+            tracing::warn!(
+                topic = notification["TopicArn"].as_str(),
+                url = notification["SubscribeURL"].as_str(),
+                "confirm the SNS subscription of the mailer webhook by visiting its url"
+            );
+        }
+        Some("Notification") => {
+            let message: Value = notification["Message"]
+                .as_str()
+                .map_or(Ok(Value::Null), serde_json::from_str)?;
+            suppress_all(&ctx, &suppression::from_ses(&message, config.soft_bounces)).await?;
+        }
+        _ => {}
+    }
+    format::empty()
+}
+
+async fn sendgrid(
+    State(ctx): State<AppContext>,
+    Query(params): Query<WebhookParams>,
+    body: String,
+) -> Result<Response> {
+    let config = authorize(&ctx, &params)?;
+    let events: Value = serde_json::from_str(&body)?;
+    suppress_all(
+        &ctx,
+        &suppression::from_sendgrid(&events, config.soft_bounces),
+    )
+    .await?;
+    format::empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, tests_cfg};
+
+    #[tokio::test]
+    async fn can_authorize_webhooks() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        let params = |token: &str| WebhookParams {
+            token: Some(token.to_string()),
+        };
+        assert!(matches!(
+            authorize(&ctx, &params("secret")),
+            Err(Error::NotFound)
+        ));
+
+        ctx.config.mailer = Some(config::Mailer {
+            suppression: Some(SuppressionConfig {
+                webhook_token: "secret".to_string(),
+                soft_bounces: false,
+            }),
+            ..serde_json::from_value(serde_json::json!({})).unwrap()
+        });
+        assert!(matches!(
+            authorize(&ctx, &params("other")),
+            Err(Error::Unauthorized(_))
+        ));
+        assert!(matches!(
+            authorize(&ctx, &WebhookParams { token: None }),
+            Err(Error::Unauthorized(_))
+        ));
+        assert!(authorize(&ctx, &params("secret")).is_ok());
+    }
+}
@@ -0,0 +1,141 @@
+//! Audit Middleware
+//!
+//! Records the changes made while serving a request with a valid JWT as made
+//! by its subject, the `pid` claim, in the audit trail of
+//! [`crate::model::audit`]. The changes made by an impersonator are
+//! recorded as `<impersonator> as <pid>`.
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     audit:
+//!       enable: true
+//! ```
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::AppContext,
+    controller::{extractor::auth::extract_jwt_from_request_parts, middleware::MiddlewareLayer},
+    model::audit,
+    Result,
+};
+
+/// Audit middleware configuration
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Audit {
+    #[serde(default)]
+    pub enable: bool,
+}
+
+/// [`MiddlewareLayer`] setting the actor of the audit trail.
+#[derive(Clone)]
+pub struct Middleware {
+    config: Audit,
+    ctx: AppContext,
+}
+
+/// Creates the audit middleware, validating the JWTs with the configuration
+/// of the application.
+#[must_use]
+pub fn new(config: &Audit, ctx: &AppContext) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        ctx: ctx.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "audit"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the audit middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            self.ctx.clone(),
+            audit_middleware,
+        )))
+    }
+}
+
+async fn audit_middleware(State(ctx): State<AppContext>, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let actor = extract_jwt_from_request_parts(&parts, &ctx)
+        .await
+        .ok()
+        .map(|jwt| match jwt.claims.impersonator {
+            Some(impersonator) => format!("{impersonator} as {}", jwt.claims.pid),
+            None => jwt.claims.pid,
+        });
+    audit::with_actor(actor, next.run(Request::from_parts(parts, body))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{auth::jwt::JWT, tests_cfg};
+
+    #[tokio::test]
+    async fn can_set_the_actor() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.config.auth = Some(
+            serde_json::from_value(serde_json::json!({
+                "jwt": { "secret": "PqRwLF2rhHe8J22oBeHy", "expiration": 3600 }
+            }))
+            .unwrap(),
+        );
+
+        let app = Router::new().route(
+            "/",
+            get(|| async { audit::current_actor().unwrap_or_default() }),
+        );
+        let app = new(&Audit { enable: true }, &ctx)
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(ctx);
+
+        let token = JWT::new("PqRwLF2rhHe8J22oBeHy")
+            .generate_token(3600, "user-pid".to_string(), serde_json::Map::new())
+            .unwrap();
+        for (authorization, actor) in [
+            (Some(format!("Bearer {token}")), "user-pid"),
+            (Some("Bearer invalid".to_string()), ""),
+            (None, ""),
+        ] {
+            let mut request = axum::http::Request::builder().uri("/");
+            if let Some(authorization) = authorization {
+                request = request.header("authorization", authorization);
+            }
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(body, actor);
+        }
+    }
+}
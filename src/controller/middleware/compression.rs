@@ -3,11 +3,41 @@
 //! This middleware applies compression to HTTP responses to reduce the size of
 //! the data being transmitted. This can improve performance by decreasing load
 //! times and reducing bandwidth usage. The middleware configuration allows for
-//! enabling or disabling compression based on the application settings.
+//! enabling or disabling compression based on the application settings, and
+//! picking the encodings, the compression level, the minimum size and the
+//! content types of the compressed responses.
+//!
+//! Images, gRPC and server-sent events responses are never compressed.
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     compression:
+//!       enable: true
+//!       algorithms: [br, gzip]
+//!       level: best
+//!       min_size: 1024
+//!       content_types:
+//!         - text/*
+//!         - application/json
+//! ```
+
+use std::sync::Arc;
 
-use axum::Router as AXRouter;
+use axum::{
+    http::{header, Extensions, HeaderMap, StatusCode, Version},
+    Router as AXRouter,
+};
 use serde::{Deserialize, Serialize};
-use tower_http::compression::CompressionLayer;
+use serde_json::json;
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    CompressionLevel,
+};
 
 use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 
@@ -15,6 +45,68 @@ use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 pub struct Compression {
     #[serde(default)]
     pub enable: bool,
+    /// The encodings offered to clients, all of them by default
+    #[serde(default = "default_algorithms")]
+    pub algorithms: Vec<Algorithm>,
+    #[serde(default)]
+    pub level: Level,
+    /// The smallest body size in bytes worth compressing. Responses of
+    /// unknown size are compressed.
+    #[serde(default = "default_min_size")]
+    pub min_size: u16,
+    /// The compressed content types, such as `application/json` or
+    /// `text/*`, all of them when empty
+    #[serde(default)]
+    pub content_types: Vec<String>,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_algorithms() -> Vec<Algorithm> {
+    vec![
+        Algorithm::Br,
+        Algorithm::Zstd,
+        Algorithm::Gzip,
+        Algorithm::Deflate,
+    ]
+}
+
+const fn default_min_size() -> u16 {
+    32
+}
+
+/// A response encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Gzip,
+    Br,
+    Zstd,
+    Deflate,
+}
+
+/// The compression level, trading speed for size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Fastest,
+    #[default]
+    Default,
+    Best,
+}
+
+impl From<Level> for CompressionLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Fastest => Self::Fastest,
+            Level::Default => Self::Default,
+            Level::Best => Self::Best,
+        }
+    }
 }
 
 impl MiddlewareLayer for Compression {
@@ -34,6 +126,134 @@ impl MiddlewareLayer for Compression {
 
     /// Applies the Compression middleware layer to the Axum router.
     fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
-        Ok(app.layer(CompressionLayer::new()))
+        let content_types: Arc<[String]> = self
+            .content_types
+            .iter()
+            .map(|content_type| content_type.trim().to_ascii_lowercase())
+            .collect();
+        let predicate = SizeAbove::new(self.min_size)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE)
+            .and(
+                move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+                    is_allowed(&content_types, headers)
+                },
+            );
+
+        let enabled = |algorithm| self.algorithms.contains(&algorithm);
+        Ok(app.layer(
+            CompressionLayer::new()
+                .gzip(enabled(Algorithm::Gzip))
+                .br(enabled(Algorithm::Br))
+                .zstd(enabled(Algorithm::Zstd))
+                .deflate(enabled(Algorithm::Deflate))
+                .quality(self.level.into())
+                .compress_when(predicate),
+        ))
+    }
+}
+
+/// Whether the content type of a response is in the allowlist.
+fn is_allowed(content_types: &[String], headers: &HeaderMap) -> bool {
+    if content_types.is_empty() {
+        return true;
+    }
+    let Some(essence) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|essence| essence.trim().to_ascii_lowercase())
+    else {
+        return false;
+    };
+    content_types.iter().any(|allowed| {
+        allowed.strip_suffix("/*").map_or_else(
+            || *allowed == essence,
+            |kind| essence.split('/').next() == Some(kind),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{HeaderValue, Request},
+        response::IntoResponse,
+        routing::get,
+        Router,
+    };
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[rstest]
+    #[case("application/json", true)]
+    #[case("application/json; charset=utf-8", true)]
+    #[case("text/html", true)]
+    #[case("application/pdf", false)]
+    fn can_allow_content_types(#[case] content_type: &str, #[case] expected: bool) {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(content_type).unwrap(),
+        );
+        let allowed = ["text/*".to_string(), "application/json".to_string()];
+        assert_eq!(is_allowed(&allowed, &headers), expected);
+        assert!(is_allowed(&[], &headers));
+    }
+
+    async fn content_encoding(middleware: &Compression, body: &'static str) -> Option<String> {
+        let app =
+            Router::new().route(
+                "/",
+                get(move || async move {
+                    ([(header::CONTENT_TYPE, "text/plain")], body).into_response()
+                }),
+            );
+        let app = middleware
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        let req = Request::builder()
+            .uri("/")
+            .header(header::ACCEPT_ENCODING, "gzip, br")
+            .body(Body::empty())
+            .expect("request");
+        let response = app.oneshot(req).await.expect("valid response");
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn can_compress_responses() {
+        let body = "loco ".repeat(100).leak();
+        let mut middleware = Compression {
+            enable: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            content_encoding(&middleware, body).await.as_deref(),
+            Some("br")
+        );
+
+        middleware.algorithms = vec![Algorithm::Gzip];
+        assert_eq!(
+            content_encoding(&middleware, body).await.as_deref(),
+            Some("gzip")
+        );
+
+        middleware.min_size = 1024;
+        assert_eq!(content_encoding(&middleware, body).await, None);
+
+        middleware.min_size = 0;
+        middleware.content_types = vec!["application/json".to_string()];
+        assert_eq!(content_encoding(&middleware, body).await, None);
     }
 }
@@ -0,0 +1,229 @@
+//! CSRF Protection Middleware
+//!
+//! Protects form submissions against cross-site request forgery. A random
+//! token is kept in the [`Session`] (so the `session` middleware has to be
+//! enabled too) and every unsafe request (`POST`, `PUT`, `PATCH`,
+//! `DELETE`) has to send it back, either in the `x-csrf-token` header or in
+//! the `csrf_token` field of an URL encoded form. Other requests are
+//! rejected with `403 Forbidden`.
+//!
+//! Templates render the token with the `csrf_token()` Tera function:
+//!
+//! ```html
+//! <form method="post" action="/notes">
+//!   <input type="hidden" name="csrf_token" value="{{ csrf_token() }}">
+//! </form>
+//! ```
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{FromRequest, Request, State},
+    http::{header::CONTENT_TYPE, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Form, Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext, controller::middleware::MiddlewareLayer, hash, session::Session, Error, Result,
+};
+
+/// The session key holding the token
+const SESSION_KEY: &str = "_csrf_token";
+/// Length of the generated tokens
+const TOKEN_LENGTH: usize = 32;
+/// Maximum size of a form body read to find the token
+const MAX_FORM_SIZE: usize = 2 * 1024 * 1024;
+
+tokio::task_local! {
+    static CSRF_TOKEN: String;
+}
+
+/// Returns the CSRF token of the request being handled, `None` outside of
+/// the CSRF middleware.
+#[must_use]
+pub fn current_token() -> Option<String> {
+    CSRF_TOKEN.try_with(Clone::clone).ok()
+}
+
+/// CSRF middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Csrf {
+    #[serde(default)]
+    pub enable: bool,
+    /// The header holding the token, for `fetch` and XHR requests
+    #[serde(default = "default_header_name")]
+    pub header_name: String,
+    /// The form field holding the token
+    #[serde(default = "default_field_name")]
+    pub field_name: String,
+    /// Path prefixes not checked, for example API routes authenticated with
+    /// a bearer token
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Default for Csrf {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+impl Csrf {
+    /// Returns `true` when the path is under an excluded prefix, matching
+    /// whole path segments: excluding `/api` does not exclude `/api-admin`.
+    fn excludes(&self, path: &str) -> bool {
+        self.exclude.iter().any(|prefix| {
+            let prefix = prefix.trim_end_matches('/');
+            path.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+fn default_header_name() -> String {
+    "x-csrf-token".to_string()
+}
+
+fn default_field_name() -> String {
+    "csrf_token".to_string()
+}
+
+impl MiddlewareLayer for Csrf {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "csrf"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the CSRF middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(self.clone()),
+            csrf_middleware,
+        )))
+    }
+}
+
+/// Returns the token of the session, generating it on the first visit.
+fn session_token(session: &Session) -> Result<String> {
+    if let Some(token) = session.get::<String>(SESSION_KEY)? {
+        return Ok(token);
+    }
+    let token = hash::random_string(TOKEN_LENGTH);
+    session.insert(SESSION_KEY, &token)?;
+    Ok(token)
+}
+
+/// Reads the token sent with the request, from the header or from an URL
+/// encoded form body. The body is handed back to be read by the handler.
+async fn submitted_token(config: &Csrf, request: Request) -> Result<(Request, Option<String>)> {
+    if let Some(token) = request
+        .headers()
+        .get(config.header_name.as_str())
+        .and_then(|value| value.to_str().ok())
+    {
+        let token = token.to_string();
+        return Ok((request, Some(token)));
+    }
+
+    let is_form = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    if !is_form {
+        return Ok((request, None));
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, MAX_FORM_SIZE)
+        .await
+        .map_err(|_| Error::BadRequest("form body is too large".to_string()))?;
+
+    let form_request = Request::builder()
+        .method(Method::POST)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(bytes.clone()))
+        .map_err(Error::wrap)?;
+    let token = Form::<HashMap<String, String>>::from_request(form_request, &())
+        .await
+        .ok()
+        .and_then(|Form(mut fields)| fields.remove(&config.field_name));
+
+    Ok((Request::from_parts(parts, Body::from(bytes)), token))
+}
+
+/// Validates the CSRF token of unsafe requests and makes the token available
+/// to `csrf_token()`.
+async fn csrf_middleware(
+    State(config): State<Arc<Csrf>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(session) = request.extensions().get::<Session>().cloned() else {
+        tracing::error!("the csrf middleware requires the session middleware");
+        return Error::InternalServerError.into_response();
+    };
+
+    let token = match session_token(&session) {
+        Ok(token) => token,
+        Err(err) => return err.into_response(),
+    };
+
+    let path = request.uri().path();
+    let checked = !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    ) && !config.excludes(path);
+
+    let request = if checked {
+        let (request, submitted) = match submitted_token(&config, request).await {
+            Ok(res) => res,
+            Err(err) => return err.into_response(),
+        };
+        if !submitted
+            .is_some_and(|submitted| hash::constant_time_eq(submitted.as_bytes(), token.as_bytes()))
+        {
+            return Error::Forbidden("invalid CSRF token".to_string()).into_response();
+        }
+        request
+    } else {
+        request
+    };
+
+    CSRF_TOKEN.scope(token, next.run(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excludes_whole_path_segments() {
+        let config = Csrf {
+            exclude: vec!["/api".to_string(), "/hooks/".to_string()],
+            ..Default::default()
+        };
+
+        assert!(config.excludes("/api"));
+        assert!(config.excludes("/api/users"));
+        assert!(config.excludes("/hooks/stripe"));
+        assert!(!config.excludes("/api-admin/users"));
+        assert!(!config.excludes("/apix"));
+        assert!(!config.excludes("/hooksx"));
+        assert!(!config.excludes("/users"));
+    }
+}
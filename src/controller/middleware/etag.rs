@@ -5,21 +5,39 @@
 //! cache entries by comparing a client's stored `ETag` with the one generated
 //! by the server. If the `ETags` match, a `304 Not Modified` response is sent,
 //! avoiding the need to resend the full content.
+//!
+//! Handlers can set the `ETag` of their responses, for example with
+//! [`crate::controller::format::json_with_etag`]. With `compute` configured,
+//! the middleware also computes the `ETag` of the successful responses
+//! without one, for the configured content types and up to a size, as it
+//! buffers them.
+//!
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     etag:
+//!       enable: true
+//!       compute:
+//!         weak: true
+//!         max_size: 1048576
+//!         content_types: ["application/json", "text/"]
+//! ```
 
 use std::task::{Context, Poll};
 
 use axum::{
-    body::Body,
+    body::{Body, HttpBody},
     extract::Request,
     http::{
-        header::{ETAG, IF_NONE_MATCH},
-        StatusCode,
+        header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderValue, Method, StatusCode,
     },
     response::Response,
     Router as AXRouter,
 };
 use futures_util::future::BoxFuture;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tower::{Layer, Service};
 
 use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
@@ -28,6 +46,89 @@ use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
 pub struct Etag {
     #[serde(default)]
     pub enable: bool,
+    /// Computes the `ETag` of responses which have none
+    #[serde(default)]
+    pub compute: Option<Compute>,
+}
+
+/// Which responses get a computed `ETag`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Compute {
+    /// Computes weak `ETags` (`W/"..."`), for responses which are
+    /// semantically but not byte-for-byte identical, such as compressed ones
+    #[serde(default)]
+    pub weak: bool,
+    /// The size in bytes of the largest response to buffer. Responses of
+    /// unknown size, such as streams, are never buffered.
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+    /// The content types of the responses, matched as prefixes (`text/`
+    /// matches `text/html; charset=utf-8`)
+    #[serde(default = "default_content_types")]
+    pub content_types: Vec<String>,
+}
+
+impl Default for Compute {
+    fn default() -> Self {
+        serde_json::from_value(serde_json::json!({})).unwrap()
+    }
+}
+
+fn default_max_size() -> usize {
+    // 1 MiB
+    1024 * 1024
+}
+
+fn default_content_types() -> Vec<String> {
+    vec!["application/json".to_string(), "text/".to_string()]
+}
+
+impl Compute {
+    fn applies_to(&self, response: &Response) -> bool {
+        if response.status() != StatusCode::OK || response.headers().contains_key(ETAG) {
+            return false;
+        }
+        let Some(content_type) = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return false;
+        };
+        self.content_types
+            .iter()
+            .any(|prefix| content_type.starts_with(prefix.as_str()))
+            && response
+                .body()
+                .size_hint()
+                .exact()
+                .is_some_and(|size| usize::try_from(size).is_ok_and(|size| size <= self.max_size))
+    }
+}
+
+/// Computes the `ETag` of a content: a hash of it, quoted, and prefixed with
+/// `W/` when `weak`.
+#[must_use]
+pub fn compute_etag(content: &[u8], weak: bool) -> String {
+    let mut etag = String::with_capacity(36);
+    if weak {
+        etag.push_str("W/");
+    }
+    etag.push('"');
+    etag.push_str(&crate::hash::to_hex(&Sha256::digest(content)[..16]));
+    etag.push('"');
+    etag
+}
+
+/// Whether an `If-None-Match` header matches an `ETag`, using the weak
+/// comparison: `W/"x"` matches `"x"`.
+fn if_none_match(header: &HeaderValue, etag: &HeaderValue) -> bool {
+    let (Ok(header), Ok(etag)) = (header.to_str(), etag.to_str()) else {
+        return header == etag;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    header.trim() == "*" || header.split(',').any(|tag| opaque(tag) == etag)
 }
 
 impl MiddlewareLayer for Etag {
@@ -47,26 +148,34 @@ impl MiddlewareLayer for Etag {
 
     /// Applies the `ETag` middleware to the application router.
     fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
-        Ok(app.layer(EtagLayer))
+        Ok(app.layer(EtagLayer {
+            compute: self.compute.clone(),
+        }))
     }
 }
 
 /// [`EtagLayer`] struct for adding `ETag` functionality as a Tower service
 /// layer.
 #[derive(Default, Clone)]
-struct EtagLayer;
+struct EtagLayer {
+    compute: Option<Compute>,
+}
 
 impl<S> Layer<S> for EtagLayer {
     type Service = EtagMiddleware<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        EtagMiddleware { inner }
+        EtagMiddleware {
+            inner,
+            compute: self.compute.clone(),
+        }
     }
 }
 
 #[derive(Clone)]
 struct EtagMiddleware<S> {
     inner: S,
+    compute: Option<Compute>,
 }
 
 impl<S> Service<Request<Body>> for EtagMiddleware<S>
@@ -84,25 +193,101 @@ where
     }
 
     fn call(&mut self, request: Request) -> Self::Future {
-        let ifnm = request.headers().get(IF_NONE_MATCH).cloned();
+        let ifnm = matches!(*request.method(), Method::GET | Method::HEAD)
+            .then(|| request.headers().get(IF_NONE_MATCH).cloned())
+            .flatten();
+        let compute = self.compute.clone();
 
         let future = self.inner.call(request);
 
         let res_fut = async move {
-            let response = future.await?;
-            let etag_from_response = response.headers().get(ETAG).cloned();
-            if let Some(etag_in_request) = ifnm {
-                if let Some(etag_from_response) = etag_from_response {
-                    if etag_in_request == etag_from_response {
-                        return Ok(Response::builder()
-                            .status(StatusCode::NOT_MODIFIED)
-                            .body(Body::empty())
-                            .unwrap());
+            let mut response = future.await?;
+
+            if let Some(compute) = compute.filter(|compute| compute.applies_to(&response)) {
+                let (mut parts, body) = response.into_parts();
+                match axum::body::to_bytes(body, compute.max_size).await {
+                    Ok(bytes) => {
+                        if let Ok(etag) = HeaderValue::from_str(&compute_etag(&bytes, compute.weak))
+                        {
+                            parts.headers.insert(ETAG, etag);
+                        }
+                        response = Response::from_parts(parts, Body::from(bytes));
+                    }
+                    Err(err) => {
+                        tracing::error!(error = %err, "could not read response body to compute etag");
+                        response = Response::from_parts(parts, Body::empty());
+                        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
                     }
                 }
             }
+
+            if let (Some(etag_in_request), Some(etag_from_response)) =
+                (ifnm, response.headers().get(ETAG))
+            {
+                if if_none_match(&etag_in_request, etag_from_response) {
+                    return Ok(Response::builder()
+                        .status(StatusCode::NOT_MODIFIED)
+                        .header(ETAG, etag_from_response)
+                        .body(Body::empty())
+                        .unwrap());
+                }
+            }
             Ok(response)
         };
         Box::pin(res_fut)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn can_compute_etag() {
+        let etag = compute_etag(b"loco", false);
+        assert_eq!(etag.len(), 34);
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(compute_etag(b"loco", true), format!("W/{etag}"));
+        assert_ne!(compute_etag(b"loco!", false), etag);
+    }
+
+    #[rstest]
+    #[case("\"a\"", "\"a\"", true)]
+    #[case("W/\"a\"", "\"a\"", true)]
+    #[case("\"a\"", "W/\"a\"", true)]
+    #[case("\"b\", \"a\"", "\"a\"", true)]
+    #[case("*", "\"a\"", true)]
+    #[case("\"b\"", "\"a\"", false)]
+    fn can_match_if_none_match(#[case] header: &str, #[case] etag: &str, #[case] expected: bool) {
+        assert_eq!(
+            if_none_match(
+                &HeaderValue::from_str(header).unwrap(),
+                &HeaderValue::from_str(etag).unwrap()
+            ),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("application/json", "{}", true)]
+    #[case("text/html; charset=utf-8", "<p></p>", true)]
+    #[case("image/png", "png", false)]
+    #[case("application/json", "too large", false)]
+    fn can_select_responses(
+        #[case] content_type: &str,
+        #[case] body: &str,
+        #[case] expected: bool,
+    ) {
+        let compute = Compute {
+            max_size: 8,
+            ..Default::default()
+        };
+        let response = Response::builder()
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        assert_eq!(compute.applies_to(&response), expected);
+    }
+}
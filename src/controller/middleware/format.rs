@@ -69,3 +69,150 @@ where
         Ok(get_respond_to(&parts.headers))
     }
 }
+
+/// The media types accepted by a request, from its `Accept` header, to pick
+/// the representation of a response with [`crate::controller::format::negotiate`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Negotiate {
+    /// The media ranges with their quality in thousandths, none meaning any
+    /// media type is accepted
+    ranges: Vec<(String, u16)>,
+}
+
+impl Negotiate {
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let ranges = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(parse_media_range)
+            .collect();
+        Self { ranges }
+    }
+
+    /// The quality, in thousandths, of a media type such as `text/html`, from
+    /// the most specific range matching it.
+    #[must_use]
+    pub fn quality(&self, media_type: &str) -> u16 {
+        if self.ranges.is_empty() {
+            return 1000;
+        }
+        let kind = media_type.split('/').next().unwrap_or_default();
+        self.ranges
+            .iter()
+            .filter_map(|(range, quality)| {
+                let specificity = if range == media_type {
+                    2
+                } else if range.strip_suffix("/*") == Some(kind) {
+                    1
+                } else if range == "*/*" {
+                    0
+                } else {
+                    return None;
+                };
+                Some((specificity, *quality))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0, |(_, quality)| quality)
+    }
+
+    /// The preferred of the `offered` media types, the first one winning
+    /// ties, or `None` when the request accepts none of them.
+    #[must_use]
+    pub fn preferred<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+        offered
+            .iter()
+            .map(|media_type| (*media_type, self.quality(media_type)))
+            .filter(|(_, quality)| *quality > 0)
+            // `max_by_key` keeps the last maximum, reverse to keep the first
+            .rev()
+            .max_by_key(|(_, quality)| *quality)
+            .map(|(media_type, _)| media_type)
+    }
+}
+
+/// Parses a media range such as `text/html;q=0.8`.
+fn parse_media_range(range: &str) -> Option<(String, u16)> {
+    let mut params = range.split(';');
+    let media_type = params.next()?.trim().to_ascii_lowercase();
+    if media_type.is_empty() {
+        return None;
+    }
+    let quality = params
+        .filter_map(|param| param.trim().strip_prefix("q="))
+        .find_map(parse_quality)
+        .unwrap_or(1000);
+    Some((media_type, quality))
+}
+
+/// Parses a quality value (`0` to `1` with up to 3 decimals) in thousandths.
+fn parse_quality(value: &str) -> Option<u16> {
+    let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let int: u16 = int.parse().ok().filter(|int| *int <= 1)?;
+    let frac: u16 = format!("{frac:0<3}").parse().ok()?;
+    let quality = int * 1000 + frac;
+    (quality <= 1000).then_some(quality)
+}
+
+impl<S> FromRequestParts<S> for Negotiate
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Error> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn negotiate(accept: &str) -> Negotiate {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_str(accept).unwrap());
+        Negotiate::from_headers(&headers)
+    }
+
+    #[rstest]
+    #[case("application/json", Some("application/json"))]
+    #[case("text/html,application/xhtml+xml,*/*;q=0.8", Some("text/html"))]
+    #[case("*/*", Some("application/json"))]
+    #[case("text/*", Some("text/html"))]
+    #[case("text/csv;q=0.9, application/json;q=0.5", Some("text/csv"))]
+    #[case("*/*;q=0.1, application/json;q=0", Some("text/html"))]
+    #[case("image/png", None)]
+    fn can_pick_preferred(#[case] accept: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            negotiate(accept).preferred(&["application/json", "text/html", "text/csv"]),
+            expected
+        );
+    }
+
+    #[test]
+    fn accepts_anything_without_header() {
+        assert_eq!(
+            Negotiate::from_headers(&HeaderMap::new()).preferred(&["text/html", "text/csv"]),
+            Some("text/html")
+        );
+    }
+
+    #[rstest]
+    #[case("1", Some(1000))]
+    #[case("0.8", Some(800))]
+    #[case("0.125", Some(125))]
+    #[case("1.5", None)]
+    #[case("0.1234", None)]
+    fn can_parse_quality(#[case] value: &str, #[case] expected: Option<u16>) {
+        assert_eq!(parse_quality(value), expected);
+    }
+}
@@ -0,0 +1,547 @@
+//! Idempotency Middleware
+//!
+//! Makes the retries of unsafe requests safe: the response of a request with
+//! an `Idempotency-Key` header is stored in the application cache, and
+//! replayed to the retries of the request with the same key, rather than
+//! running the handler again, such as charging a payment twice.
+//!
+//! The keys are scoped by client, the hash of its credentials (the
+//! `Authorization` header and the session cookie), and by route. A retry of
+//! a request which is still running is answered with a `409 Conflict`, and a
+//! key reused with another body with a `422 Unprocessable Entity`.
+//!
+//! Server errors are not stored, so that the requests failing with them can
+//! be retried. Replayed responses carry an `Idempotent-Replayed: true`
+//! header.
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     idempotency:
+//!       enable: true
+//!       ttl: 86400
+//!       prefixes:
+//!         - /api/payments
+//! ```
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app::AppContext,
+    cache::Cache,
+    controller::{middleware::MiddlewareLayer, ErrorDetail},
+    Error, Result,
+};
+
+/// The header of the idempotency keys.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// The header of the replayed responses.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+const MAX_KEY_LEN: usize = 255;
+
+/// Idempotency middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Idempotency {
+    #[serde(default)]
+    pub enable: bool,
+    /// The seconds a response is replayed
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+    /// The seconds a running request holds its key, in case it never
+    /// completes
+    #[serde(default = "default_lock_ttl")]
+    pub lock_ttl: u64,
+    /// The methods of the idempotent requests
+    #[serde(default = "default_methods")]
+    pub methods: Vec<String>,
+    /// The path prefixes of the idempotent routes, all of them when empty
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    /// Whether the requests of the idempotent routes must have a key
+    #[serde(default)]
+    pub required: bool,
+    /// The largest request or response body, in bytes
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+    /// The cookie identifying the clients of cookie sessions, the other
+    /// cookies being left out of the keys
+    #[serde(default = "default_session_cookie")]
+    pub session_cookie: String,
+}
+
+impl Default for Idempotency {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+const fn default_ttl() -> u64 {
+    24 * 60 * 60
+}
+
+const fn default_lock_ttl() -> u64 {
+    60
+}
+
+fn default_methods() -> Vec<String> {
+    vec!["POST".to_string(), "PATCH".to_string()]
+}
+
+const fn default_max_size() -> usize {
+    1024 * 1024
+}
+
+fn default_session_cookie() -> String {
+    "loco_session".to_string()
+}
+
+/// [`MiddlewareLayer`] replaying the responses of idempotent requests.
+#[derive(Clone)]
+pub struct Middleware {
+    config: Idempotency,
+    ctx: AppContext,
+}
+
+/// Creates the idempotency middleware, storing responses in the application
+/// cache.
+#[must_use]
+pub fn new(config: &Idempotency, ctx: &AppContext) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        ctx: ctx.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "idempotency"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the idempotency middleware to the application router.
+    ///
+    /// # Errors
+    /// when a method is invalid
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let methods = self
+            .config
+            .methods
+            .iter()
+            .map(|method| Method::from_bytes(method.to_ascii_uppercase().as_bytes()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(Keeper {
+                config: self.config.clone(),
+                methods,
+                cache: self.ctx.cache.clone(),
+            }),
+            idempotency_middleware,
+        )))
+    }
+}
+
+struct Keeper {
+    config: Idempotency,
+    methods: Vec<Method>,
+    cache: Arc<Cache>,
+}
+
+impl Keeper {
+    fn matches(&self, request: &Request) -> bool {
+        let path = request.uri().path();
+        self.methods.contains(request.method())
+            && (self.config.prefixes.is_empty()
+                || self.config.prefixes.iter().any(|prefix| {
+                    let prefix = prefix.trim_end_matches('/');
+                    path.strip_prefix(prefix)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                }))
+    }
+
+    /// The cache key of a request, by client, route and idempotency key.
+    fn key(&self, request: &Request, idempotency_key: &str) -> String {
+        let session = CookieJar::from_headers(request.headers())
+            .get(&self.config.session_cookie)
+            .map(|cookie| cookie.value().to_string());
+        let credentials = [
+            request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .map(HeaderValue::as_bytes),
+            session.as_deref().map(str::as_bytes),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        let client = if credentials.is_empty() {
+            "anonymous".to_string()
+        } else {
+            hash(&credentials.join(&b'\n'))
+        };
+        format!(
+            "idempotency:{client}:{} {}:{idempotency_key}",
+            request.method(),
+            request.uri().path()
+        )
+    }
+}
+
+fn hash(bytes: &[u8]) -> String {
+    crate::hash::to_hex(&Sha256::digest(bytes))
+}
+
+#[derive(Deserialize, Serialize)]
+struct StoredResponse {
+    /// The hash of the request body
+    fingerprint: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl StoredResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+            .headers_mut()
+            .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+        response
+    }
+}
+
+fn error(status: StatusCode, code: &str, description: &str) -> Response {
+    Error::CustomError(status, ErrorDetail::new(code, description)).into_response()
+}
+
+async fn idempotency_middleware(
+    State(keeper): State<Arc<Keeper>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !keeper.matches(&request) {
+        return next.run(request).await;
+    }
+    let idempotency_key = match request.headers().get(IDEMPOTENCY_KEY) {
+        Some(value) => match value.to_str() {
+            Ok(value) if !value.is_empty() && value.len() <= MAX_KEY_LEN => value.to_string(),
+            _ => {
+                return error(
+                    StatusCode::BAD_REQUEST,
+                    "invalid_idempotency_key",
+                    "The idempotency key is invalid",
+                )
+            }
+        },
+        None if keeper.config.required => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "missing_idempotency_key",
+                "The request requires an idempotency key",
+            )
+        }
+        None => return next.run(request).await,
+    };
+
+    let key = keeper.key(&request, &idempotency_key);
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, keeper.config.max_size).await else {
+        return error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            "The request body is too large",
+        );
+    };
+    let fingerprint = hash(&body);
+
+    if let Some(response) = replay(&keeper, &key, &fingerprint).await {
+        return response;
+    }
+
+    let lock = format!("{key}:lock");
+    match keeper
+        .cache
+        .increment(&lock, Duration::from_secs(keeper.config.lock_ttl))
+        .await
+    {
+        Ok(1) => {}
+        Ok(_) => {
+            return error(
+                StatusCode::CONFLICT,
+                "idempotency_key_in_use",
+                "A request with the same idempotency key is in progress",
+            )
+        }
+        Err(err) => {
+            tracing::error!(error = %err, "could not lock the idempotency key");
+            return error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "idempotency_unavailable",
+                "The request can not be made idempotent, retry later",
+            );
+        }
+    }
+
+    // the request holding the lock before may have stored its response
+    // since the lookup
+    let response = match replay(&keeper, &key, &fingerprint).await {
+        Some(response) => response,
+        None => {
+            let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+            store(&keeper, &key, fingerprint, response).await
+        }
+    };
+    if let Err(err) = keeper.cache.remove(&lock).await {
+        tracing::error!(error = %err, "could not unlock the idempotency key");
+    }
+    response
+}
+
+/// The response to a request whose key was used already: the stored
+/// response, or an error when it was used by another request.
+async fn replay(keeper: &Keeper, key: &str, fingerprint: &str) -> Option<Response> {
+    match keeper.cache.get::<StoredResponse>(key).await {
+        Ok(Some(stored)) if stored.fingerprint == fingerprint => Some(stored.into_response()),
+        Ok(Some(_)) => Some(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "idempotency_key_reused",
+            "The idempotency key was used by another request",
+        )),
+        Ok(None) => None,
+        Err(err) => {
+            tracing::error!(error = %err, "could not read the idempotent responses");
+            Some(error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "idempotency_unavailable",
+                "The request can not be made idempotent, retry later",
+            ))
+        }
+    }
+}
+
+/// Stores a response to be replayed, unless it is a server error or too
+/// large.
+async fn store(keeper: &Keeper, key: &str, fingerprint: String, response: Response) -> Response {
+    let storable = !response.status().is_server_error()
+        && response
+            .body()
+            .size_hint()
+            .upper()
+            .is_some_and(|size| size <= keeper.config.max_size as u64);
+    if !storable {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::error!(error = %err, "could not read the idempotent response");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if let Ok(body) = std::str::from_utf8(&bytes) {
+        let stored = StoredResponse {
+            fingerprint,
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter(|(name, _)| *name != header::SET_COOKIE)
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: body.to_string(),
+        };
+        if let Err(err) = keeper
+            .cache
+            .insert_with_expiry(key, &stored, Duration::from_secs(keeper.config.ttl))
+            .await
+        {
+            tracing::error!(error = %err, "could not store the idempotent response");
+        }
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{routing::post, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[cfg(feature = "cache_inmem")]
+    #[tokio::test]
+    async fn can_replay_responses() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.cache = crate::cache::drivers::inmem::new(&crate::config::InMemCacheConfig {
+            max_capacity: 100,
+        })
+        .into();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/payments",
+            post(move |body: String| {
+                let counter = counter.clone();
+                async move {
+                    let count = counter.fetch_add(1, Ordering::SeqCst);
+                    (StatusCode::CREATED, format!("payment {count} of {body}"))
+                }
+            }),
+        );
+        let config = Idempotency {
+            enable: true,
+            ..Default::default()
+        };
+        let app = new(&config, &ctx)
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(ctx.clone());
+
+        let request = |key: Option<&str>, authorization: &str, body: &'static str| {
+            let mut request = axum::http::Request::builder()
+                .method("POST")
+                .uri("/payments");
+            request = if authorization.contains('=') {
+                request.header(header::COOKIE, authorization)
+            } else {
+                request.header(header::AUTHORIZATION, authorization)
+            };
+            if let Some(key) = key {
+                request = request.header(IDEMPOTENCY_KEY, key);
+            }
+            request.body(Body::from(body)).unwrap()
+        };
+        let text = |response: Response| async move {
+            let replayed = response.headers().contains_key(IDEMPOTENT_REPLAYED);
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(bytes.to_vec()).unwrap(), replayed)
+        };
+
+        let first = app
+            .clone()
+            .oneshot(request(Some("k1"), "alice", "10"))
+            .await
+            .unwrap();
+        assert_eq!(
+            text(first).await,
+            (StatusCode::CREATED, "payment 0 of 10".to_string(), false)
+        );
+        let retry = app
+            .clone()
+            .oneshot(request(Some("k1"), "alice", "10"))
+            .await
+            .unwrap();
+        assert_eq!(
+            text(retry).await,
+            (StatusCode::CREATED, "payment 0 of 10".to_string(), true)
+        );
+
+        // another body with the same key
+        let reused = app
+            .clone()
+            .oneshot(request(Some("k1"), "alice", "20"))
+            .await
+            .unwrap();
+        assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // the keys are scoped by client, and requests without a key pass
+        let other = app
+            .clone()
+            .oneshot(request(Some("k1"), "bob", "10"))
+            .await
+            .unwrap();
+        assert_eq!(text(other).await.1, "payment 1 of 10");
+        let unkeyed = app
+            .clone()
+            .oneshot(request(None, "alice", "10"))
+            .await
+            .unwrap();
+        assert_eq!(text(unkeyed).await.1, "payment 2 of 10");
+        // as are the clients of cookie sessions, by their session cookie only
+        for (cookies, payment, replayed) in [
+            ("loco_session=alice; theme=dark", 3, false),
+            ("loco_session=alice; theme=light", 3, true),
+            ("loco_session=bob; theme=dark", 4, false),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(request(Some("k3"), cookies, "10"))
+                .await
+                .unwrap();
+            assert_eq!(
+                text(response).await,
+                (
+                    StatusCode::CREATED,
+                    format!("payment {payment} of 10"),
+                    replayed
+                )
+            );
+        }
+
+        // a retry of a running request
+        let keeper = Keeper {
+            config,
+            methods: vec![],
+            cache: ctx.cache.clone(),
+        };
+        let key = keeper.key(&request(Some("k2"), "alice", "10"), "k2");
+        ctx.cache
+            .increment(&format!("{key}:lock"), Duration::from_secs(60))
+            .await
+            .unwrap();
+        let running = app
+            .oneshot(request(Some("k2"), "alice", "10"))
+            .await
+            .unwrap();
+        assert_eq!(running.status(), StatusCode::CONFLICT);
+
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+}
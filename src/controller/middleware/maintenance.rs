@@ -0,0 +1,370 @@
+//! Maintenance Middleware
+//!
+//! While the maintenance mode is on, every request is answered with a
+//! `503 Service Unavailable`, as JSON or with an HTML page for browsers,
+//! except for the allowed paths (the health checks by default) and the
+//! allowed client IPs, such as the ones of the admins.
+//!
+//! The mode is switched at runtime with `cargo loco maintenance on|off`,
+//! which creates or removes a flag file (`switch: file`, the default) or a
+//! key of the application cache (`switch: cache`, shared by the instances of
+//! an app using the Redis cache). With `switch: env`, the mode is on when the
+//! `LOCO_MAINTENANCE` environment variable is `1` or `true`.
+//!
+//! The client IP is computed by the remote IP middleware when enabled.
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     maintenance:
+//!       enable: true
+//!       switch: cache
+//!       allow_paths:
+//!         - /_health
+//!         - /_readiness
+//!         - /admin
+//!       allow_ips:
+//!         - 10.0.0.0/8
+//!       retry_after: 600
+//!       page: assets/static/maintenance.html
+//! ```
+
+use std::{net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    Router as AXRouter,
+};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext,
+    cache::Cache,
+    controller::{
+        middleware::{remote_ip::RemoteIP, MiddlewareLayer},
+        ErrorDetail,
+    },
+    Error, Result,
+};
+
+/// The cache key of the `cache` switch.
+pub const CACHE_KEY: &str = "loco:maintenance";
+
+/// The environment variable of the `env` switch.
+pub const ENV_VAR: &str = "LOCO_MAINTENANCE";
+
+/// Maintenance middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Maintenance {
+    #[serde(default)]
+    pub enable: bool,
+    /// Where the maintenance mode is switched on
+    #[serde(default)]
+    pub switch: Switch,
+    /// The flag file of the `file` switch
+    #[serde(default = "default_file")]
+    pub file: PathBuf,
+    /// The path prefixes served during maintenance
+    #[serde(default = "default_allow_paths")]
+    pub allow_paths: Vec<String>,
+    /// The client IPs or networks served during maintenance, such as
+    /// `10.0.0.0/8`
+    #[serde(default)]
+    pub allow_ips: Vec<String>,
+    /// The seconds clients are told to wait with a `Retry-After` header
+    #[serde(default)]
+    pub retry_after: Option<u64>,
+    /// An HTML page answered to browsers
+    #[serde(default)]
+    pub page: Option<PathBuf>,
+    /// The description of the JSON error
+    #[serde(default = "default_message")]
+    pub message: String,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_file() -> PathBuf {
+    PathBuf::from("tmp").join("maintenance")
+}
+
+fn default_allow_paths() -> Vec<String> {
+    vec![
+        "/_ping".to_string(),
+        "/_health".to_string(),
+        "/_readiness".to_string(),
+    ]
+}
+
+fn default_message() -> String {
+    "The service is under maintenance, retry later".to_string()
+}
+
+/// Where the maintenance mode is switched on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Switch {
+    /// A flag file, for apps running on a single host
+    #[default]
+    File,
+    /// A key of the application cache
+    Cache,
+    /// The `LOCO_MAINTENANCE` environment variable, read on every request
+    Env,
+}
+
+impl Maintenance {
+    /// Whether the maintenance mode is on.
+    pub async fn is_on(&self, cache: &Cache) -> bool {
+        match self.switch {
+            Switch::File => tokio::fs::try_exists(&self.file).await.unwrap_or(false),
+            Switch::Cache => match cache.contains_key(CACHE_KEY).await {
+                Ok(on) => on,
+                Err(err) => {
+                    tracing::error!(error = %err, "could not read the maintenance switch");
+                    false
+                }
+            },
+            Switch::Env => {
+                std::env::var(ENV_VAR).is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+            }
+        }
+    }
+
+    /// Switches the maintenance mode on or off.
+    ///
+    /// # Errors
+    ///
+    /// When the flag file or the cache key could not be written, or with the
+    /// `env` switch, which can not be switched at runtime.
+    pub async fn switch(&self, cache: &Cache, on: bool) -> Result<()> {
+        match self.switch {
+            Switch::File => {
+                if on {
+                    if let Some(parent) = self.file.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    tokio::fs::write(&self.file, "").await?;
+                } else if tokio::fs::try_exists(&self.file).await? {
+                    tokio::fs::remove_file(&self.file).await?;
+                }
+            }
+            Switch::Cache => {
+                if on {
+                    cache.insert(CACHE_KEY, &true).await?;
+                } else {
+                    cache.remove(CACHE_KEY).await?;
+                }
+            }
+            Switch::Env => {
+                return Err(Error::Message(format!(
+                    "the maintenance mode is switched with the `{ENV_VAR}` environment variable"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// [`MiddlewareLayer`] answering requests during maintenance.
+#[derive(Clone)]
+pub struct Middleware {
+    config: Maintenance,
+    ctx: AppContext,
+}
+
+/// Creates the maintenance middleware.
+#[must_use]
+pub fn new(config: &Maintenance, ctx: &AppContext) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        ctx: ctx.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "maintenance"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the maintenance middleware to the application router.
+    ///
+    /// # Errors
+    /// when an allowed IP is invalid or the page could not be read
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let allow_ips = self
+            .config
+            .allow_ips
+            .iter()
+            .map(|ip| {
+                IpNetwork::from_str(ip).map_err(|err| {
+                    Error::Message(format!("invalid maintenance allowed IP `{ip}`: {err}"))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let page = self
+            .config
+            .page
+            .as_ref()
+            .map(std::fs::read_to_string)
+            .transpose()?;
+
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(Gate {
+                config: self.config.clone(),
+                cache: self.ctx.cache.clone(),
+                allow_ips,
+                page,
+            }),
+            maintenance_middleware,
+        )))
+    }
+}
+
+struct Gate {
+    config: Maintenance,
+    cache: Arc<Cache>,
+    allow_ips: Vec<IpNetwork>,
+    page: Option<String>,
+}
+
+impl Gate {
+    fn allows_path(&self, path: &str) -> bool {
+        self.config.allow_paths.iter().any(|prefix| {
+            let prefix = prefix.trim_end_matches('/');
+            path.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    fn allows_client(&self, request: &Request) -> bool {
+        let ip = match request.extensions().get::<RemoteIP>() {
+            Some(RemoteIP::Forwarded(ip) | RemoteIP::Socket(ip)) => Some(*ip),
+            _ => request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+        };
+        ip.is_some_and(|ip| self.allow_ips.iter().any(|network| network.contains(ip)))
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        let wants_html = request
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/html"));
+
+        let mut response = match &self.page {
+            Some(page) if wants_html => {
+                (StatusCode::SERVICE_UNAVAILABLE, Html(page.clone())).into_response()
+            }
+            _ => Error::CustomError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorDetail::new("maintenance", &self.config.message),
+            )
+            .into_response(),
+        };
+        if let Some(retry_after) = self.config.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
+
+async fn maintenance_middleware(
+    State(gate): State<Arc<Gate>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if gate.allows_path(request.uri().path())
+        || !gate.config.is_on(&gate.cache).await
+        || gate.allows_client(&request)
+    {
+        return next.run(request).await;
+    }
+    gate.respond(&request)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    async fn status(app: &Router, uri: &str, ip: Option<&str>) -> StatusCode {
+        let mut request = axum::http::Request::builder()
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        if let Some(ip) = ip {
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::new(ip.parse().unwrap(), 8000)));
+        }
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn can_switch_maintenance() {
+        let tree_fs = tree_fs::TreeBuilder::default().create().unwrap();
+        let ctx = tests_cfg::app::get_app_context().await;
+        let config = Maintenance {
+            enable: true,
+            file: tree_fs.root.join("tmp").join("maintenance"),
+            allow_ips: vec!["10.0.0.0/8".to_string()],
+            retry_after: Some(600),
+            ..Default::default()
+        };
+
+        let app = Router::new()
+            .route("/", get(|| async { "loco" }))
+            .route("/_health", get(|| async { "ok" }));
+        let app = new(&config, &ctx)
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(ctx.clone());
+
+        assert_eq!(status(&app, "/", None).await, StatusCode::OK);
+
+        config.switch(&ctx.cache, true).await.unwrap();
+        assert!(config.is_on(&ctx.cache).await);
+        assert_eq!(
+            status(&app, "/", None).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(status(&app, "/_health", None).await, StatusCode::OK);
+        assert_eq!(status(&app, "/", Some("10.1.2.3")).await, StatusCode::OK);
+        assert_eq!(
+            status(&app, "/", Some("192.168.1.1")).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        config.switch(&ctx.cache, false).await.unwrap();
+        assert_eq!(status(&app, "/", None).await, StatusCode::OK);
+    }
+}
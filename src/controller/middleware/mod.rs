@@ -6,18 +6,26 @@
 //! handling. The middleware can be easily configured and applied to the
 //! application's router.
 
+#[cfg(all(feature = "with-db", feature = "auth_jwt"))]
+pub mod audit;
 pub mod catch_panic;
 pub mod compression;
 pub mod cors;
+pub mod csrf;
 pub mod etag;
 pub mod fallback;
 pub mod format;
+pub mod idempotency;
 pub mod limit_payload;
 pub mod logger;
+pub mod maintenance;
 pub mod powered_by;
+pub mod rate_limit;
 pub mod remote_ip;
 pub mod request_id;
+pub mod response_cache;
 pub mod secure_headers;
+pub mod session;
 #[cfg(feature = "embedded_assets")]
 pub mod static_assets_embedded;
 #[cfg(feature = "embedded_assets")]
@@ -25,7 +33,11 @@ pub use static_assets_embedded as static_assets;
 
 #[cfg(not(feature = "embedded_assets"))]
 pub mod static_assets;
+#[cfg(feature = "with-db")]
+pub mod tenancy;
 pub mod timeout;
+#[cfg(feature = "with-db")]
+pub mod transaction;
 
 use axum::Router as AXRouter;
 use serde::{Deserialize, Serialize};
@@ -80,6 +92,51 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
     vec![
         // Limit Payload middleware with a default if none
         Box::new(middlewares.limit_payload.clone().unwrap_or_default()),
+        // Audit middleware with a default if none, wrapping the handlers
+        // whose changes it attributes
+        #[cfg(all(feature = "with-db", feature = "auth_jwt"))]
+        Box::new(audit::new(
+            &middlewares.audit.clone().unwrap_or_default(),
+            ctx,
+        )),
+        // Transaction middleware with a default if none, wrapped by the tenancy
+        // middleware whose schema it runs in
+        #[cfg(feature = "with-db")]
+        Box::new(transaction::new(
+            &middlewares.transaction.clone().unwrap_or_default(),
+            ctx,
+        )),
+        // Tenancy middleware with a default if none, inside the response cache
+        // and the idempotency middlewares so that they see the rejections
+        #[cfg(feature = "with-db")]
+        Box::new(tenancy::new(
+            &middlewares.tenancy.clone().unwrap_or_default(),
+            ctx,
+        )),
+        // Response cache middleware with a default if none, wrapped by the
+        // compression middleware so that bodies are cached uncompressed
+        Box::new(response_cache::new(
+            &middlewares.response_cache.clone().unwrap_or_default(),
+            ctx,
+        )),
+        // Idempotency middleware with a default if none, inside the maintenance
+        // middleware so that no response is stored during maintenance
+        Box::new(idempotency::new(
+            &middlewares.idempotency.clone().unwrap_or_default(),
+            ctx,
+        )),
+        // Maintenance middleware with a default if none, wrapping the response
+        // cache so that cached responses are not served during maintenance
+        Box::new(maintenance::new(
+            &middlewares.maintenance.clone().unwrap_or_default(),
+            ctx,
+        )),
+        // Rate limit middleware with a default if none, wrapped by the remote
+        // IP middleware it reads the client IP from
+        Box::new(rate_limit::new(
+            &middlewares.rate_limit.clone().unwrap_or_default(),
+            ctx,
+        )),
         // CORS middleware with a default if none
         Box::new(middlewares.cors.clone().unwrap_or_else(|| cors::Cors {
             enable: false,
@@ -93,12 +150,10 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
                 .unwrap_or_else(|| catch_panic::CatchPanic { enable: true }),
         ),
         // Etag middleware with a default if none
-        Box::new(
-            middlewares
-                .etag
-                .clone()
-                .unwrap_or_else(|| etag::Etag { enable: true }),
-        ),
+        Box::new(middlewares.etag.clone().unwrap_or_else(|| etag::Etag {
+            enable: true,
+            compute: None,
+        })),
         // Remote IP middleware with a default if none
         Box::new(
             middlewares
@@ -110,12 +165,7 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
                 }),
         ),
         // Compression middleware with a default if none
-        Box::new(
-            middlewares
-                .compression
-                .clone()
-                .unwrap_or_else(|| compression::Compression { enable: false }),
-        ),
+        Box::new(middlewares.compression.clone().unwrap_or_default()),
         // Timeout Request middleware with a default if none
         Box::new(
             middlewares
@@ -165,6 +215,14 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
                     ..Default::default()
                 }),
         ),
+        // CSRF middleware with a default if none, wrapped by the session
+        // middleware it reads the token from
+        Box::new(middlewares.csrf.clone().unwrap_or_default()),
+        // Session middleware with a default if none
+        Box::new(session::new(
+            &middlewares.session.clone().unwrap_or_default(),
+            ctx,
+        )),
         // Powered by middleware with a default identifier
         Box::new(powered_by::new(ctx.config.server.ident.as_deref())),
     ]
@@ -209,4 +267,34 @@ pub struct Config {
 
     /// Request ID
     pub request_id: Option<request_id::RequestId>,
+
+    /// Server-side sessions
+    pub session: Option<session::Config>,
+
+    /// CSRF protection for forms, requires sessions
+    pub csrf: Option<csrf::Csrf>,
+
+    /// Limits the request rate of clients
+    pub rate_limit: Option<rate_limit::RateLimit>,
+
+    /// Caches the responses of `GET` requests in the application cache
+    pub response_cache: Option<response_cache::ResponseCache>,
+
+    /// Answers `503 Service Unavailable` while in maintenance
+    pub maintenance: Option<maintenance::Maintenance>,
+
+    /// Replays the responses of requests with an `Idempotency-Key` header
+    pub idempotency: Option<idempotency::Idempotency>,
+
+    /// Attributes the changes of the audit trail to the subject of the JWT
+    #[cfg(all(feature = "with-db", feature = "auth_jwt"))]
+    pub audit: Option<audit::Audit>,
+
+    /// Resolves the tenant of the requests
+    #[cfg(feature = "with-db")]
+    pub tenancy: Option<tenancy::Tenancy>,
+
+    /// Runs the requests in a database transaction
+    #[cfg(feature = "with-db")]
+    pub transaction: Option<transaction::Transaction>,
 }
@@ -0,0 +1,395 @@
+//! Rate Limit Middleware
+//!
+//! Limits the number of requests a client can make to the routes under a
+//! prefix within a time window. Clients are identified by their IP, by a
+//! header (such as an API key), or by the subject of their JWT.
+//!
+//! Header values are chosen by the clients and are not checked, so a client
+//! could send a new value with every request to escape the limit. Set the
+//! `ip_limit` of the header rules to also limit the requests with the header
+//! by their IP, with a limit high enough for the clients sharing an address
+//! behind a NAT or a proxy, or identify the clients by their JWT instead.
+//!
+//! Requests are counted in fixed windows in the application cache, so that
+//! limits are shared by all the instances of an app using the Redis cache.
+//! Requests over the limit get a `429 Too Many Requests` response with a
+//! `Retry-After` header. When the cache fails (for example with the null
+//! cache), requests are let through.
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     rate_limit:
+//!       enable: true
+//!       rules:
+//!         - prefix: /api/auth
+//!           requests: 10
+//!           window: 60
+//!         - prefix: /api
+//!           requests: 1000
+//!           window: 3600
+//!           key:
+//!             header: x-api-key
+//!           ip_limit: 5000
+//! ```
+
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext,
+    cache::Cache,
+    controller::{
+        middleware::{remote_ip::RemoteIP, MiddlewareLayer},
+        ErrorDetail,
+    },
+    Error, Result,
+};
+
+/// Rate limit middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RateLimit {
+    #[serde(default)]
+    pub enable: bool,
+    /// The limits, the rule with the longest matching prefix applying to a
+    /// request
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+/// The limit of the routes under a prefix
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Rule {
+    /// The path prefix of the limited routes, `/` for all of them
+    pub prefix: String,
+    /// The number of requests allowed in a window
+    pub requests: u64,
+    /// The window length in seconds
+    pub window: u64,
+    /// How clients are identified
+    #[serde(default)]
+    pub key: KeyKind,
+    /// The number of requests allowed in a window from an IP, for the
+    /// requests identified by a header. They are only limited by the value
+    /// of their header when unset.
+    #[serde(default)]
+    pub ip_limit: Option<u64>,
+}
+
+impl Rule {
+    fn matches(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_end_matches('/');
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+/// How the requests of a client are identified
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyKind {
+    /// The client IP, as computed by the remote IP middleware when enabled
+    #[default]
+    Ip,
+    /// The value of a header, such as an API key, falling back to the IP when
+    /// missing. The value is not checked, so a client sending a new value with
+    /// every request is not limited, unless the rule sets an `ip_limit`.
+    Header(String),
+    /// The `pid` claim (the subject) of a valid JWT, falling back to the IP
+    /// for unauthenticated requests
+    #[cfg(feature = "auth_jwt")]
+    JwtSub,
+}
+
+/// [`MiddlewareLayer`] limiting the request rate.
+#[derive(Clone)]
+pub struct Middleware {
+    config: RateLimit,
+    ctx: AppContext,
+}
+
+/// Creates the rate limit middleware, counting requests in the application
+/// cache.
+#[must_use]
+pub fn new(config: &RateLimit, ctx: &AppContext) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        ctx: ctx.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "rate_limit"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the rate limit middleware to the application router.
+    ///
+    /// # Errors
+    /// when a rule allows no request or has an empty window
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        if let Some(rule) = self
+            .config
+            .rules
+            .iter()
+            .find(|rule| rule.requests == 0 || rule.window == 0)
+        {
+            return Err(Error::Message(format!(
+                "rate limit rule for `{}` must allow requests in a non-empty window",
+                rule.prefix
+            )));
+        }
+
+        let mut rules = self.config.rules.clone();
+        // the longest prefix is matched first
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.prefix.trim_end_matches('/').len()));
+
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(Limiter {
+                rules,
+                ctx: self.ctx.clone(),
+            }),
+            rate_limit_middleware,
+        )))
+    }
+}
+
+struct Limiter {
+    rules: Vec<Rule>,
+    ctx: AppContext,
+}
+
+impl Limiter {
+    /// The client keys of a request for a rule, with the number of requests
+    /// allowed to each of them: the value of a header comes along with its IP
+    /// when the rule sets an `ip_limit`.
+    #[cfg_attr(
+        not(feature = "auth_jwt"),
+        allow(clippy::unused_self, clippy::unused_async)
+    )]
+    async fn client_keys(&self, rule: &Rule, parts: &Parts) -> Vec<(String, u64)> {
+        match &rule.key {
+            KeyKind::Ip => {}
+            KeyKind::Header(name) => {
+                if let Some(value) = parts.headers.get(name).and_then(|v| v.to_str().ok()) {
+                    let mut keys = vec![(format!("header:{value}"), rule.requests)];
+                    if let Some(ip_limit) = rule.ip_limit {
+                        keys.push((format!("header_{}", ip_key(parts)), ip_limit));
+                    }
+                    return keys;
+                }
+            }
+            #[cfg(feature = "auth_jwt")]
+            KeyKind::JwtSub => {
+                if let Ok(jwt) = crate::controller::extractor::auth::extract_jwt_from_request_parts(
+                    parts, &self.ctx,
+                )
+                .await
+                {
+                    return vec![(format!("jwt:{}", jwt.claims.pid), rule.requests)];
+                }
+            }
+        }
+        vec![(ip_key(parts), rule.requests)]
+    }
+}
+
+/// The key of the client IP of a request.
+fn ip_key(parts: &Parts) -> String {
+    let ip = match parts.extensions.get::<RemoteIP>() {
+        Some(RemoteIP::Forwarded(ip) | RemoteIP::Socket(ip)) => Some(*ip),
+        _ => parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip()),
+    };
+    ip.map_or_else(|| "ip:unknown".to_string(), |ip| format!("ip:{ip}"))
+}
+
+/// Counts a request in the window it falls in, returning the seconds left in
+/// the window when the client made more than `requests` requests.
+async fn check(cache: &Cache, rule: &Rule, client: &str, requests: u64, now: u64) -> Option<u64> {
+    let window = now / rule.window;
+    let key = format!("rate_limit:{}:{client}:{window}", rule.prefix);
+    match cache
+        .increment(&key, Duration::from_secs(rule.window))
+        .await
+    {
+        Ok(count) if count > requests => Some((window + 1) * rule.window - now),
+        Ok(_) => None,
+        Err(err) => {
+            tracing::error!(error = %err, "could not count request for rate limiting");
+            None
+        }
+    }
+}
+
+async fn rate_limit_middleware(
+    State(limiter): State<Arc<Limiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    let Some(rule) = limiter.rules.iter().find(|rule| rule.matches(path)) else {
+        return next.run(request).await;
+    };
+
+    let (parts, body) = request.into_parts();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    let mut retry_after = None;
+    for (client, requests) in limiter.client_keys(rule, &parts).await {
+        if let Some(seconds) = check(&limiter.ctx.cache, rule, &client, requests, now).await {
+            tracing::debug!(client = %client, prefix = %rule.prefix, "rate limit exceeded");
+            retry_after = retry_after.max(Some(seconds));
+        }
+    }
+
+    if let Some(retry_after) = retry_after {
+        let mut response = Error::CustomError(
+            StatusCode::TOO_MANY_REQUESTS,
+            ErrorDetail::new("too_many_requests", "Too many requests, retry later"),
+        )
+        .into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn rule(prefix: &str) -> Rule {
+        Rule {
+            prefix: prefix.to_string(),
+            requests: 2,
+            window: 60,
+            key: KeyKind::Ip,
+            ip_limit: None,
+        }
+    }
+
+    #[rstest]
+    #[case("/api", "/api", true)]
+    #[case("/api", "/api/users", true)]
+    #[case("/api/", "/api/users", true)]
+    #[case("/api", "/apis", false)]
+    #[case("/", "/anything", true)]
+    fn can_match_prefix(#[case] prefix: &str, #[case] path: &str, #[case] expected: bool) {
+        assert_eq!(rule(prefix).matches(path), expected);
+    }
+
+    #[test]
+    fn can_deserialize_keys() {
+        let config: RateLimit = serde_json::from_value(json!({
+            "enable": true,
+            "rules": [
+                {"prefix": "/api", "requests": 10, "window": 60},
+                {"prefix": "/", "requests": 10, "window": 60, "key": {"header": "x-api-key"}},
+            ]
+        }))
+        .unwrap();
+        assert_eq!(config.rules[0].key, KeyKind::Ip);
+        assert_eq!(
+            config.rules[1].key,
+            KeyKind::Header("x-api-key".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn can_limit_header_keys_by_ip() {
+        let limiter = Limiter {
+            rules: vec![],
+            ctx: crate::tests_cfg::app::get_app_context().await,
+        };
+        let rule = Rule {
+            key: KeyKind::Header("x-api-key".to_string()),
+            ..rule("/api")
+        };
+
+        let (parts, ()) = axum::http::Request::builder()
+            .header("x-api-key", "key-1")
+            .body(())
+            .unwrap()
+            .into_parts();
+        assert_eq!(
+            limiter.client_keys(&rule, &parts).await,
+            vec![("header:key-1".to_string(), 2)]
+        );
+        let capped = Rule {
+            ip_limit: Some(10),
+            ..rule.clone()
+        };
+        assert_eq!(
+            limiter.client_keys(&capped, &parts).await,
+            vec![
+                ("header:key-1".to_string(), 2),
+                ("header_ip:unknown".to_string(), 10)
+            ]
+        );
+
+        let (parts, ()) = axum::http::Request::builder()
+            .body(())
+            .unwrap()
+            .into_parts();
+        assert_eq!(
+            limiter.client_keys(&capped, &parts).await,
+            vec![("ip:unknown".to_string(), 2)]
+        );
+    }
+
+    #[cfg(feature = "cache_inmem")]
+    #[tokio::test]
+    async fn can_limit_in_windows() {
+        let cache = crate::cache::drivers::inmem::new(&crate::config::InMemCacheConfig {
+            max_capacity: 100,
+        });
+        let rule = rule("/api");
+
+        assert_eq!(check(&cache, &rule, "ip:1", 2, 125).await, None);
+        assert_eq!(check(&cache, &rule, "ip:1", 2, 130).await, None);
+        assert_eq!(check(&cache, &rule, "ip:1", 2, 150).await, Some(30));
+        // other clients and windows are counted apart
+        assert_eq!(check(&cache, &rule, "ip:2", 2, 150).await, None);
+        assert_eq!(check(&cache, &rule, "ip:1", 2, 180).await, None);
+    }
+}
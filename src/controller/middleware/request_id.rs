@@ -4,18 +4,31 @@
 //! generated or sanitized if already present in the request.
 //!
 //! This can be useful for tracking requests across services, logging, and
-//! debugging.
+//! debugging. The request ID is recorded in a tracing span wrapping the
+//! whole request, so that every log line of the request carries it, and
+//! handlers can read it with the [`LocoRequestId`] extractor, for example to
+//! show it in error reports.
+//!
+//! The request ID and the W3C trace of the request, continued from its
+//! `traceparent` header or started, are also the [`TraceContext`] of the
+//! task serving it, propagated to the outbound requests of `ctx.http`.
 
 use axum::{
-    extract::Request, http::HeaderValue, middleware::Next, response::Response, Router as AXRouter,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, HeaderValue},
+    middleware::Next,
+    response::Response,
+    Router as AXRouter,
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 use uuid::Uuid;
 
-use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Error, Result};
 
 const X_REQUEST_ID: &str = "x-request-id";
+const TRACEPARENT: &str = "traceparent";
 const MAX_LEN: usize = 255;
 
 use std::sync::OnceLock;
@@ -60,6 +73,19 @@ impl MiddlewareLayer for RequestId {
 }
 
 /// Wrapper struct for storing the request ID in the request's extensions.
+///
+/// It is also an extractor of the request ID, requiring the request ID
+/// middleware.
+///
+/// # Example
+/// ```rust
+/// use loco_rs::prelude::*;
+/// use loco_rs::controller::middleware::request_id::LocoRequestId;
+///
+/// async fn report(request_id: LocoRequestId) -> Result<Response> {
+///     format::json(data!({ "request_id": request_id.get() }))
+/// }
+/// ```
 #[derive(Debug, Clone)]
 pub struct LocoRequestId(String);
 
@@ -71,19 +97,98 @@ impl LocoRequestId {
     }
 }
 
+impl std::fmt::Display for LocoRequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<S> FromRequestParts<S> for LocoRequestId
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        parts.extensions.get::<Self>().cloned().ok_or_else(|| {
+            tracing::error!("the request ID extractor requires the `request_id` middleware");
+            Error::InternalServerError
+        })
+    }
+}
+
+tokio::task_local! {
+    static TRACE_CONTEXT: TraceContext;
+}
+
+/// The request ID and the trace of the request being served.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub request_id: String,
+    /// The W3C trace ID, 32 hex digits
+    pub trace_id: String,
+    /// The W3C trace flags, 2 hex digits
+    pub flags: String,
+}
+
+impl TraceContext {
+    /// The trace context of the request served by the current task, when
+    /// the request ID middleware is enabled.
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        TRACE_CONTEXT.try_with(Clone::clone).ok()
+    }
+
+    /// Continues the trace of a `traceparent` header, or starts one.
+    fn new(request_id: String, traceparent: Option<&HeaderValue>) -> Self {
+        let parent = traceparent
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| {
+                let mut parts = value.split('-');
+                let (_, trace_id, _, flags) =
+                    (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+                let is_hex = |part: &str, len: usize| {
+                    part.len() == len && part.chars().all(|c| c.is_ascii_hexdigit())
+                };
+                (is_hex(trace_id, 32) && is_hex(flags, 2))
+                    .then(|| (trace_id.to_ascii_lowercase(), flags.to_string()))
+            });
+        let (trace_id, flags) =
+            parent.unwrap_or_else(|| (Uuid::new_v4().simple().to_string(), "01".to_string()));
+        Self {
+            request_id,
+            trace_id,
+            flags,
+        }
+    }
+
+    /// A `traceparent` header for a new span of the trace, such as an
+    /// outbound request.
+    #[must_use]
+    pub fn traceparent(&self) -> String {
+        let span_id = Uuid::new_v4().simple().to_string();
+        format!("00-{}-{}-{}", self.trace_id, &span_id[..16], self.flags)
+    }
+}
+
 /// Middleware function to ensure or generate a unique request ID.
 ///
 /// This function intercepts requests, checks for the presence of the
 /// `x-request-id` header, and either sanitizes its value or generates a new
 /// UUID if absent. The resulting request ID is added to both the request
-/// extensions and the response headers.
+/// extensions and the response headers, and the rest of the request runs in
+/// a span recording it, with its [`TraceContext`].
 pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
     let header_request_id = request.headers().get(X_REQUEST_ID).cloned();
     let request_id = make_request_id(header_request_id);
     request
         .extensions_mut()
         .insert(LocoRequestId(request_id.clone()));
-    let mut res = next.run(request).await;
+    let trace = TraceContext::new(request_id.clone(), request.headers().get(TRACEPARENT));
+    let span = tracing::error_span!("request", request_id = %request_id);
+    let mut res = TRACE_CONTEXT
+        .scope(trace, next.run(request).instrument(span))
+        .await;
 
     if let Ok(v) = HeaderValue::from_str(request_id.as_str()) {
         res.headers_mut().insert(X_REQUEST_ID, v);
@@ -112,10 +217,82 @@ fn make_request_id(maybe_request_id: Option<HeaderValue>) -> String {
 
 #[cfg(test)]
 mod tests {
-    use axum::http::HeaderValue;
+    use axum::{
+        body::Body,
+        http::{HeaderValue, Request},
+        routing::get,
+        Router,
+    };
     use insta::assert_debug_snapshot;
+    use tower::ServiceExt;
 
-    use super::make_request_id;
+    use super::*;
+    use crate::tests_cfg;
+
+    #[tokio::test]
+    async fn can_propagate_request_id() {
+        let app = Router::new().route("/", get(|id: LocoRequestId| async move { id.to_string() }));
+        let app = RequestId { enable: true }
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        let req = Request::builder()
+            .uri("/")
+            .header(X_REQUEST_ID, "abc-123")
+            .body(Body::empty())
+            .expect("request");
+        let response = app.clone().oneshot(req).await.expect("valid response");
+        assert_eq!(response.headers().get(X_REQUEST_ID).unwrap(), "abc-123");
+        let body = axum::body::to_bytes(response.into_body(), 1024)
+            .await
+            .unwrap();
+        assert_eq!(body, "abc-123");
+
+        let req = Request::builder()
+            .uri("/")
+            .body(Body::empty())
+            .expect("request");
+        let response = app.oneshot(req).await.expect("valid response");
+        let generated = response.headers().get(X_REQUEST_ID).unwrap();
+        assert_eq!(generated.len(), 36);
+    }
+
+    #[tokio::test]
+    async fn can_continue_traces() {
+        let app = Router::new().route(
+            "/",
+            get(|| async { TraceContext::current().unwrap().traceparent() }),
+        );
+        let app = RequestId { enable: true }
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        let req = Request::builder()
+            .uri("/")
+            .header(
+                TRACEPARENT,
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .body(Body::empty())
+            .expect("request");
+        let response = app.oneshot(req).await.expect("valid response");
+        let body = axum::body::to_bytes(response.into_body(), 1024)
+            .await
+            .unwrap();
+        let traceparent = std::str::from_utf8(&body).unwrap();
+        assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+        assert!(traceparent.ends_with("-01"));
+        assert_ne!(
+            traceparent,
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        assert_eq!(TraceContext::current(), None);
+        let started = TraceContext::new("id".to_string(), None);
+        assert_eq!(started.trace_id.len(), 32);
+    }
 
     #[test]
     fn create_or_fetch_request_id() {
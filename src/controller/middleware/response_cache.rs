@@ -0,0 +1,510 @@
+//! Response Cache Middleware
+//!
+//! Caches the full responses of `GET` requests in the application cache, so
+//! that read-heavy endpoints are not computed again for every request.
+//! Responses are cached by host, path, query and the values of the `vary`
+//! request headers, for `ttl` seconds. The response cache runs before the
+//! tenancy middleware, so apps resolving their tenants from a header must
+//! list that header in `vary`, for the tenants not to share their responses.
+//!
+//! Only successful responses with a text body smaller than `max_size` are
+//! cached, unless they set cookies or their `Cache-Control` header forbids
+//! it. Requests with an `Authorization` or a `Cookie` header, such as the
+//! requests of logged in users, are never served from the cache, so only
+//! cache routes that respond the same to every client.
+//!
+//! Cached responses carry an `X-Cache: HIT` header. After a change, drop the
+//! cached responses of a path with [`crate::cache::Cache::invalidate_response`]:
+//! ```rust,ignore
+//! ctx.cache.invalidate_response("/api/posts").await?;
+//! ```
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     response_cache:
+//!       enable: true
+//!       ttl: 60
+//!       prefixes:
+//!         - /api/posts
+//!       vary:
+//!         - accept
+//! ```
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext,
+    cache::{Cache, CacheResult},
+    controller::middleware::MiddlewareLayer,
+    Result,
+};
+
+/// The header telling whether a response was served from the cache.
+pub const X_CACHE: &str = "x-cache";
+
+/// Response cache middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ResponseCache {
+    #[serde(default)]
+    pub enable: bool,
+    /// The seconds a response is cached
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+    /// The path prefixes of the cached routes, all of them when empty
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    /// The request headers the responses vary with, such as `accept`, and
+    /// the header of the tenants when they are resolved from a header
+    #[serde(default)]
+    pub vary: Vec<String>,
+    /// The largest body cached, in bytes
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+const fn default_ttl() -> u64 {
+    60
+}
+
+const fn default_max_size() -> u64 {
+    1024 * 1024
+}
+
+/// [`MiddlewareLayer`] caching responses.
+#[derive(Clone)]
+pub struct Middleware {
+    config: ResponseCache,
+    ctx: AppContext,
+}
+
+/// Creates the response cache middleware, storing responses in the
+/// application cache.
+#[must_use]
+pub fn new(config: &ResponseCache, ctx: &AppContext) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        ctx: ctx.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "response_cache"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the response cache middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(Cacher {
+                config: self.config.clone(),
+                cache: self.ctx.cache.clone(),
+            }),
+            response_cache_middleware,
+        )))
+    }
+}
+
+struct Cacher {
+    config: ResponseCache,
+    cache: Arc<Cache>,
+}
+
+impl Cacher {
+    fn matches(&self, path: &str) -> bool {
+        self.config.prefixes.is_empty()
+            || self.config.prefixes.iter().any(|prefix| {
+                let prefix = prefix.trim_end_matches('/');
+                path.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+    }
+
+    /// The part of the key of the cached response of a request which does
+    /// not change: its host, path, query and `vary` headers.
+    fn request_key(&self, request: &Request) -> String {
+        let uri = request.uri();
+        let host = request
+            .headers()
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| uri.authority().map(|authority| authority.as_str()))
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let path_and_query = uri
+            .path_and_query()
+            .map_or(uri.path(), |path_and_query| path_and_query.as_str());
+        let mut key = format!("{host}{path_and_query}");
+        for name in &self.config.vary {
+            let value = request
+                .headers()
+                .get(name.as_str())
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            key.push_str(&format!("\n{}={value}", name.to_ascii_lowercase()));
+        }
+        key
+    }
+
+    /// The key of the cached response of a request, changing whenever the
+    /// responses of its path are invalidated.
+    async fn key(&self, path: &str, request_key: &str) -> CacheResult<String> {
+        let generation = self
+            .cache
+            .get::<u64>(&generation_key(path))
+            .await?
+            .unwrap_or_default();
+        Ok(format!("response:{generation}:{request_key}"))
+    }
+}
+
+/// The key of the generation of the cached responses of a path.
+pub(crate) fn generation_key(path: &str) -> String {
+    format!("response:generation:{path}")
+}
+
+/// A generation of cached responses, unique enough for the generations of a
+/// path to not repeat.
+pub(crate) fn new_generation() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX))
+}
+
+#[derive(Deserialize, Serialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl CachedResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+    }
+}
+
+/// Whether a response may be cached, its body being small enough.
+fn is_cacheable(response: &Response, max_size: u64) -> bool {
+    let forbidden = response
+        .headers()
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| {
+            let value = value.to_ascii_lowercase();
+            value.contains("no-store") || value.contains("no-cache") || value.contains("private")
+        });
+    response.status() == StatusCode::OK
+        && !forbidden
+        && !response.headers().contains_key(header::SET_COOKIE)
+        && response
+            .body()
+            .size_hint()
+            .upper()
+            .is_some_and(|size| size <= max_size)
+}
+
+async fn response_cache_middleware(
+    State(cacher): State<Arc<Cacher>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::GET
+        || request.headers().contains_key(header::AUTHORIZATION)
+        || request.headers().contains_key(header::COOKIE)
+        || !cacher.matches(request.uri().path())
+    {
+        return next.run(request).await;
+    }
+
+    // computed before awaiting, the request not being `Sync`
+    let path = request.uri().path().to_string();
+    let request_key = cacher.request_key(&request);
+    let key = match cacher.key(&path, &request_key).await {
+        Ok(key) => key,
+        Err(err) => {
+            tracing::error!(error = %err, "could not read the response cache");
+            return next.run(request).await;
+        }
+    };
+    match cacher.cache.get::<CachedResponse>(&key).await {
+        Ok(Some(cached)) => {
+            let mut response = cached.into_response();
+            response
+                .headers_mut()
+                .insert(X_CACHE, HeaderValue::from_static("HIT"));
+            return response;
+        }
+        Ok(None) => {}
+        Err(err) => tracing::error!(error = %err, "could not read the response cache"),
+    }
+
+    let response = next.run(request).await;
+    if !is_cacheable(&response, cacher.config.max_size) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::error!(error = %err, "could not read the response to cache");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if let Ok(body) = std::str::from_utf8(&bytes) {
+        let cached = CachedResponse {
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: body.to_string(),
+        };
+        if let Err(err) = cacher
+            .cache
+            .insert_with_expiry(&key, &cached, Duration::from_secs(cacher.config.ttl))
+            .await
+        {
+            tracing::error!(error = %err, "could not cache the response");
+        }
+        parts
+            .headers
+            .insert(X_CACHE, HeaderValue::from_static("MISS"));
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{routing::get, Router};
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[rstest]
+    #[case(&[], "/anything", true)]
+    #[case(&["/api/posts"], "/api/posts", true)]
+    #[case(&["/api/posts/"], "/api/posts/1", true)]
+    #[case(&["/api/posts"], "/api/postscript", false)]
+    fn can_match_prefixes(#[case] prefixes: &[&str], #[case] path: &str, #[case] expected: bool) {
+        let cacher = Cacher {
+            config: ResponseCache {
+                prefixes: prefixes.iter().map(ToString::to_string).collect(),
+                ..Default::default()
+            },
+            cache: Arc::new(Cache::new(crate::cache::drivers::null::new())),
+        };
+        assert_eq!(cacher.matches(path), expected);
+    }
+
+    #[cfg(feature = "cache_inmem")]
+    #[tokio::test]
+    async fn can_cache_and_invalidate_responses() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.cache = crate::cache::drivers::inmem::new(&crate::config::InMemCacheConfig {
+            max_capacity: 100,
+        })
+        .into();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/posts",
+            get(move || {
+                let counter = counter.clone();
+                async move { format!("posts {}", counter.fetch_add(1, Ordering::SeqCst)) }
+            }),
+        );
+        let middleware = new(
+            &ResponseCache {
+                enable: true,
+                vary: vec!["accept".to_string()],
+                ..Default::default()
+            },
+            &ctx,
+        );
+        let app = middleware
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(ctx.clone());
+
+        let request = |uri: &str, accept: &str| {
+            axum::http::Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let body = |response: Response| async move {
+            let x_cache = response.headers()[X_CACHE].to_str().unwrap().to_string();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (String::from_utf8(bytes.to_vec()).unwrap(), x_cache)
+        };
+
+        let first = app.clone().oneshot(request("/posts", "*/*")).await.unwrap();
+        assert_eq!(
+            body(first).await,
+            ("posts 0".to_string(), "MISS".to_string())
+        );
+        let second = app.clone().oneshot(request("/posts", "*/*")).await.unwrap();
+        assert_eq!(
+            body(second).await,
+            ("posts 0".to_string(), "HIT".to_string())
+        );
+
+        // other queries and varying headers are cached apart
+        let other = app
+            .clone()
+            .oneshot(request("/posts?page=2", "*/*"))
+            .await
+            .unwrap();
+        assert_eq!(body(other).await.1, "MISS");
+        let other = app
+            .clone()
+            .oneshot(request("/posts", "text/html"))
+            .await
+            .unwrap();
+        assert_eq!(body(other).await.1, "MISS");
+
+        ctx.cache.invalidate_response("/posts").await.unwrap();
+        let fresh = app.clone().oneshot(request("/posts", "*/*")).await.unwrap();
+        assert_eq!(
+            body(fresh).await,
+            ("posts 3".to_string(), "MISS".to_string())
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // the requests of logged in users are never cached
+        let mut with_cookie = request("/posts", "*/*");
+        with_cookie
+            .headers_mut()
+            .insert(header::COOKIE, HeaderValue::from_static("session=user-1"));
+        let response = app.clone().oneshot(with_cookie).await.unwrap();
+        assert!(!response.headers().contains_key(X_CACHE));
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[cfg(feature = "cache_inmem")]
+    #[tokio::test]
+    async fn caches_the_hosts_apart() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.cache = crate::cache::drivers::inmem::new(&crate::config::InMemCacheConfig {
+            max_capacity: 100,
+        })
+        .into();
+
+        let app = Router::new().route(
+            "/",
+            get(|request: Request| async move {
+                format!(
+                    "home of {}",
+                    request.headers()[header::HOST].to_str().unwrap()
+                )
+            }),
+        );
+        let app = new(
+            &ResponseCache {
+                enable: true,
+                ..Default::default()
+            },
+            &ctx,
+        )
+        .apply(app)
+        .expect("apply middleware")
+        .with_state(ctx.clone());
+
+        let request = |host: &str| {
+            axum::http::Request::builder()
+                .uri("/")
+                .header(header::HOST, host)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let body = |response: Response| async move {
+            let x_cache = response.headers()[X_CACHE].to_str().unwrap().to_string();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (String::from_utf8(bytes.to_vec()).unwrap(), x_cache)
+        };
+
+        for _ in 0..2 {
+            app.clone()
+                .oneshot(request("acme.example.com"))
+                .await
+                .unwrap();
+        }
+        let other = app
+            .clone()
+            .oneshot(request("globex.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(
+            body(other).await,
+            ("home of globex.example.com".to_string(), "MISS".to_string())
+        );
+        let cached = app
+            .clone()
+            .oneshot(request("acme.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(
+            body(cached).await,
+            ("home of acme.example.com".to_string(), "HIT".to_string())
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Session Middleware
+//!
+//! Loads the [`Session`] of every request from the configured
+//! [`SessionStore`], using the session ID held in a signed (or encrypted)
+//! cookie, and saves it back once the response was produced.
+//!
+//! See [`crate::session`] for how to use the session in handlers.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use axum_extra::extract::cookie::{Cookie, Key, PrivateCookieJar, SameSite, SignedCookieJar};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha512};
+
+use crate::{
+    app::AppContext,
+    controller::middleware::MiddlewareLayer,
+    hash,
+    session::{
+        store::{CacheStore, MemoryStore, StoreKind},
+        Session, SessionData, SessionStore, State as SessionState, Status,
+    },
+    Error, Result,
+};
+
+/// Length of the generated session IDs
+const SESSION_ID_LENGTH: usize = 64;
+/// Minimum length of the secret used to sign the cookie
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// Session middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    #[serde(default)]
+    pub enable: bool,
+    /// The secret used to sign and encrypt the session cookie, at least 32
+    /// characters long
+    #[serde(default, skip_serializing)]
+    pub secret: String,
+    /// Where the sessions are stored
+    #[serde(default)]
+    pub store: StoreKind,
+    /// The name of the session cookie
+    #[serde(default = "default_cookie_name")]
+    pub cookie_name: String,
+    /// Session lifetime in seconds, renewed on every change
+    #[serde(default = "default_expiration")]
+    pub expiration: u64,
+    /// Encrypts the cookie, otherwise it is only signed
+    #[serde(default = "default_true")]
+    pub encrypt: bool,
+    /// Only sends the cookie over HTTPS
+    #[serde(default = "default_true")]
+    pub secure: bool,
+    #[serde(default)]
+    pub same_site: CookieSameSite,
+}
+
+/// The `SameSite` attribute of the session cookie
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieSameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+impl From<CookieSameSite> for SameSite {
+    fn from(value: CookieSameSite) -> Self {
+        match value {
+            CookieSameSite::Strict => Self::Strict,
+            CookieSameSite::Lax => Self::Lax,
+            CookieSameSite::None => Self::None,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_cookie_name() -> String {
+    "loco_session".to_string()
+}
+
+fn default_expiration() -> u64 {
+    // 2 weeks
+    14 * 24 * 60 * 60
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// [`MiddlewareLayer`] loading and saving sessions.
+#[derive(Clone)]
+pub struct Middleware {
+    config: Config,
+    store: Arc<dyn SessionStore>,
+}
+
+/// Creates the session middleware, with the store selected in the
+/// configuration.
+#[must_use]
+pub fn new(config: &Config, ctx: &AppContext) -> Middleware {
+    let store: Arc<dyn SessionStore> = match config.store {
+        StoreKind::Memory => Arc::new(MemoryStore::new()),
+        StoreKind::Cache => Arc::new(CacheStore::new(ctx.cache.clone())),
+        #[cfg(feature = "with-db")]
+        StoreKind::Db => Arc::new(crate::session::store::DbStore::new(ctx.db.clone())),
+    };
+    Middleware::with_store(config, store)
+}
+
+impl Middleware {
+    /// Creates the session middleware with a custom store.
+    #[must_use]
+    pub fn with_store(config: &Config, store: Arc<dyn SessionStore>) -> Self {
+        Self {
+            config: config.clone(),
+            store,
+        }
+    }
+}
+
+impl std::fmt::Debug for Middleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Middleware")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "session"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the session middleware to the application router.
+    ///
+    /// # Errors
+    /// when the secret is too short
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        if self.config.secret.len() < MIN_SECRET_LENGTH {
+            return Err(Error::Message(format!(
+                "session secret must be at least {MIN_SECRET_LENGTH} characters long"
+            )));
+        }
+
+        let sessions = Sessions {
+            key: Key::from(&Sha512::digest(self.config.secret.as_bytes())),
+            config: self.config.clone(),
+            store: self.store.clone(),
+        };
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(sessions),
+            session_middleware,
+        )))
+    }
+}
+
+struct Sessions {
+    config: Config,
+    key: Key,
+    store: Arc<dyn SessionStore>,
+}
+
+/// The change to apply to the session cookie
+enum CookieChange {
+    Set(String),
+    Remove,
+}
+
+impl Sessions {
+    fn read_id(&self, headers: &HeaderMap) -> Option<String> {
+        let name = self.config.cookie_name.as_str();
+        let cookie = if self.config.encrypt {
+            PrivateCookieJar::from_headers(headers, self.key.clone()).get(name)
+        } else {
+            SignedCookieJar::from_headers(headers, self.key.clone()).get(name)
+        };
+        cookie.map(|cookie| cookie.value().to_string())
+    }
+
+    async fn load(&self, headers: &HeaderMap) -> Session {
+        let Some(id) = self.read_id(headers) else {
+            return Session::new(None, SessionData::new());
+        };
+
+        match self.store.load(&id).await {
+            Ok(Some(data)) => Session::new(Some(id), data),
+            Ok(None) => Session::new(None, SessionData::new()),
+            Err(err) => {
+                tracing::error!(error = %err, "could not load session");
+                Session::new(None, SessionData::new())
+            }
+        }
+    }
+
+    async fn persist(&self, state: SessionState) -> Result<Option<CookieChange>> {
+        let ttl = Duration::from_secs(self.config.expiration);
+        match state.status {
+            Status::Unchanged => Ok(None),
+            Status::Changed => {
+                let id = state
+                    .id
+                    .unwrap_or_else(|| hash::random_string(SESSION_ID_LENGTH));
+                self.store.save(&id, &state.data, ttl).await?;
+                Ok(Some(CookieChange::Set(id)))
+            }
+            Status::Renewed => {
+                if let Some(id) = state.id {
+                    self.store.destroy(&id).await?;
+                }
+                let id = hash::random_string(SESSION_ID_LENGTH);
+                self.store.save(&id, &state.data, ttl).await?;
+                Ok(Some(CookieChange::Set(id)))
+            }
+            Status::Destroyed => match state.id {
+                Some(id) => {
+                    self.store.destroy(&id).await?;
+                    Ok(Some(CookieChange::Remove))
+                }
+                None => Ok(None),
+            },
+        }
+    }
+
+    fn write_cookie(&self, change: CookieChange, response: Response) -> Response {
+        let cookie: Cookie<'static> = match &change {
+            CookieChange::Set(id) => Cookie::build((self.config.cookie_name.clone(), id.clone())),
+            CookieChange::Remove => Cookie::build((self.config.cookie_name.clone(), "")),
+        }
+        .path("/")
+        .http_only(true)
+        .secure(self.config.secure)
+        .same_site(self.config.same_site.into())
+        .max_age(time::Duration::seconds(
+            i64::try_from(self.config.expiration).unwrap_or(i64::MAX),
+        ))
+        .build();
+
+        match (self.config.encrypt, change) {
+            (true, CookieChange::Set(_)) => (
+                PrivateCookieJar::new(self.key.clone()).add(cookie),
+                response,
+            )
+                .into_response(),
+            (true, CookieChange::Remove) => (
+                PrivateCookieJar::new(self.key.clone()).remove(cookie),
+                response,
+            )
+                .into_response(),
+            (false, CookieChange::Set(_)) => {
+                (SignedCookieJar::new(self.key.clone()).add(cookie), response).into_response()
+            }
+            (false, CookieChange::Remove) => (
+                SignedCookieJar::new(self.key.clone()).remove(cookie),
+                response,
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Makes the [`Session`] available to the handlers and saves its changes.
+async fn session_middleware(
+    State(sessions): State<Arc<Sessions>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let session = sessions.load(request.headers()).await;
+    request.extensions_mut().insert(session.clone());
+
+    let response = next.run(request).await;
+
+    match sessions.persist(session.take_state()).await {
+        Ok(Some(change)) => sessions.write_cookie(change, response),
+        Ok(None) => response,
+        Err(err) => {
+            tracing::error!(error = %err, "could not save session");
+            err.into_response()
+        }
+    }
+}
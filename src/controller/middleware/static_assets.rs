@@ -3,22 +3,50 @@
 //! This middleware serves static files (e.g., images, CSS, JS) from a specified
 //! folder to the client. It also allows configuration of a fallback file to
 //! serve in case a requested file is not found. Additionally, it can serve
-//! precompressed files if enabled via the configuration.
+//! precompressed files (`.br` and `.gz` variants) to the clients accepting
+//! them if enabled via the configuration.
+//!
+//! `Range` requests are answered with the requested part of the files, and
+//! requests whose path escapes the folder (such as `/static/../config`,
+//! encoded or not) are refused with a `404 Not Found`.
+//!
+//! With `spa` enabled, the paths without file extension which match no file
+//! are answered with the `index.html` of the folder, so that a single page
+//! app can handle its routes, while missing files still get the fallback.
 //!
 //! The middleware checks if the specified folder and fallback file exist, and
 //! if either is missing, it returns an error. If the files exist, the
 //! middleware is added to the router to serve static files.
+//!
+//! When the assets were precompiled (see [`crate::controller::assets`]), the
+//! fingerprinted files are served with far-future cache headers.
 
-use std::path::PathBuf;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use axum::extract::Request;
 use axum::http::header::{HeaderValue, CACHE_CONTROL};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
 use axum::Router as AXRouter;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tower::ServiceExt;
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::set_header::SetResponseHeaderLayer;
 
-use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Error, Result};
+use crate::{
+    app::AppContext,
+    controller::{
+        assets::{AssetManifest, IMMUTABLE_CACHE_CONTROL},
+        middleware::MiddlewareLayer,
+    },
+    Error, Result,
+};
 
 /// Static asset middleware configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -35,9 +63,13 @@ pub struct StaticAssets {
     /// (single page app) where routes are virtual.
     #[serde(default = "default_fallback")]
     pub fallback: PathBuf,
-    /// Enable `precompressed_gzip`
+    /// Serve the `.br` and `.gz` precompressed variants of the files
     #[serde(default = "default_precompressed")]
     pub precompressed: bool,
+    /// Serve the `index.html` of the folder for the paths without file
+    /// extension matching no file, for single page apps
+    #[serde(default)]
+    pub spa: bool,
     /// Cache control header value for static assets (e.g., "max-age=31536000")
     pub cache_control: Option<String>,
 }
@@ -101,7 +133,12 @@ impl MiddlewareLayer for StaticAssets {
     /// Before applying, it checks if the folder and fallback file exist. If
     /// either is missing, it returns an error.
     fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
-        if self.must_exist && (!&self.folder.path.exists() || !&self.fallback.exists()) {
+        let index = self.folder.path.join("index.html");
+        if self.must_exist
+            && (!&self.folder.path.exists()
+                || !&self.fallback.exists()
+                || (self.spa && !index.exists()))
+        {
             return Err(Error::Message(format!(
                 "one of the static path are not found, Folder `{}` fallback: `{}`",
                 self.folder.path.display(),
@@ -109,7 +146,33 @@ impl MiddlewareLayer for StaticAssets {
             )));
         }
 
-        let serve_dir = ServeDir::new(&self.folder.path).fallback(ServeFile::new(&self.fallback));
+        let fallback = ServeFile::new(&self.fallback);
+        let fallback = if self.spa {
+            let index = ServeFile::new(index);
+            AXRouter::new().fallback(move |request: Request| {
+                let (index, fallback) = (index.clone(), fallback.clone());
+                async move {
+                    let is_page = Path::new(request.uri().path()).extension().is_none();
+                    if is_page {
+                        index.oneshot(request).await.into_response()
+                    } else {
+                        fallback.oneshot(request).await.into_response()
+                    }
+                }
+            })
+        } else {
+            AXRouter::new().fallback_service(fallback)
+        };
+
+        let serve_dir = ServeDir::new(&self.folder.path).fallback(fallback);
+        let serve_dir = if self.precompressed {
+            serve_dir.precompressed_br().precompressed_gzip()
+        } else {
+            serve_dir
+        };
+        let static_service = AXRouter::new()
+            .fallback_service(serve_dir)
+            .layer(axum::middleware::from_fn(reject_traversal));
 
         // Create static service with cache control if configured
         let static_service = if let Some(cache_control) = &self.cache_control {
@@ -118,24 +181,37 @@ impl MiddlewareLayer for StaticAssets {
                 HeaderValue::from_str(cache_control)
                     .unwrap_or_else(|_| HeaderValue::from_static("max-age=31536000")),
             );
+            static_service.layer(cache_header_layer)
+        } else {
+            static_service
+        };
 
-            let base_service = if self.precompressed {
-                serve_dir.precompressed_gzip()
-            } else {
-                serve_dir
-            };
-
-            // Create a router with the cache control layer applied to the static service
-            AXRouter::new()
-                .fallback_service(base_service)
-                .layer(cache_header_layer)
+        // Serve fingerprinted assets with far-future cache headers
+        let static_service = if let Some(manifest) = AssetManifest::load(&self.folder.path)? {
+            let fingerprinted: Arc<HashSet<String>> = Arc::new(
+                manifest
+                    .assets
+                    .into_values()
+                    .map(|path| format!("/{path}"))
+                    .collect(),
+            );
+            static_service.layer(axum::middleware::from_fn(
+                move |request: Request, next: Next| {
+                    let immutable = fingerprinted.contains(request.uri().path());
+                    async move {
+                        let mut response = next.run(request).await;
+                        if immutable && response.status().is_success() {
+                            response.headers_mut().insert(
+                                CACHE_CONTROL,
+                                HeaderValue::from_static(IMMUTABLE_CACHE_CONTROL),
+                            );
+                        }
+                        response
+                    }
+                },
+            ))
         } else {
-            let base_service = if self.precompressed {
-                serve_dir.precompressed_gzip()
-            } else {
-                serve_dir
-            };
-            AXRouter::new().fallback_service(base_service)
+            static_service
         };
 
         if &self.folder.uri == "/" {
@@ -145,3 +221,145 @@ impl MiddlewareLayer for StaticAssets {
         }
     }
 }
+
+/// Refuses the requests whose path could escape the assets folder.
+async fn reject_traversal(request: Request, next: Next) -> Response {
+    if is_safe_path(request.uri().path()) {
+        next.run(request).await
+    } else {
+        tracing::debug!(
+            path = request.uri().path(),
+            "refused unsafe static asset path"
+        );
+        StatusCode::NOT_FOUND.into_response()
+    }
+}
+
+/// Whether a request path, once percent-decoded, stays within the folder.
+fn is_safe_path(path: &str) -> bool {
+    let Some(path) = percent_decode(path) else {
+        return false;
+    };
+    !path.contains(['\\', '\0']) && !path.split('/').any(|segment| segment == "..")
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request},
+    };
+    use rstest::rstest;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[rstest]
+    #[case("/static/app.js", true)]
+    #[case("/static/a..b.js", true)]
+    #[case("/static/../config/production.yaml", false)]
+    #[case("/static/%2e%2e/config/production.yaml", false)]
+    #[case("/static/%2E%2E%2fconfig", false)]
+    #[case("/static/..%5cconfig", false)]
+    #[case("/static/%00", false)]
+    #[case("/static/%zz", false)]
+    fn can_detect_traversal(#[case] path: &str, #[case] expected: bool) {
+        assert_eq!(is_safe_path(path), expected);
+    }
+
+    async fn request(
+        middleware: &StaticAssets,
+        uri: &str,
+        headers: &[(header::HeaderName, &str)],
+    ) -> Response {
+        let app = middleware
+            .apply(AXRouter::new())
+            .expect("apply middleware")
+            .with_state(tests_cfg::app::get_app_context().await);
+        let mut req = Request::builder().uri(uri);
+        for (name, value) in headers {
+            req = req.header(name, *value);
+        }
+        app.oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .expect("valid response")
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), 1024)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_serve_assets() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .add_file("static/app.js", "console.log('loco');")
+            .add_file("static/app.js.br", "brotli")
+            .add_file("static/index.html", "spa")
+            .add_file("static/404.html", "not found")
+            .create()
+            .unwrap();
+        let middleware = StaticAssets {
+            enable: true,
+            folder: FolderConfig {
+                uri: "/static".to_string(),
+                path: tree_fs.root.join("static"),
+            },
+            fallback: tree_fs.root.join("static").join("404.html"),
+            precompressed: true,
+            spa: true,
+            ..Default::default()
+        };
+
+        let response = request(
+            &middleware,
+            "/static/app.js",
+            &[(header::RANGE, "bytes=0-6")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body(response).await, "console");
+
+        let response = request(
+            &middleware,
+            "/static/app.js",
+            &[(header::ACCEPT_ENCODING, "gzip, br")],
+        )
+        .await;
+        assert_eq!(
+            response.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "br"
+        );
+        assert_eq!(body(response).await, "brotli");
+
+        let response = request(&middleware, "/static/dashboard/settings", &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, "spa");
+
+        let response = request(&middleware, "/static/missing.js", &[]).await;
+        assert_eq!(body(response).await, "not found");
+
+        let response = request(&middleware, "/static/%2e%2e/Cargo.toml", &[]).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,286 @@
+//! Tenancy Middleware
+//!
+//! Resolves the tenant of each request with the configured resolver, and
+//! hands it to the handlers as a [`TenantContext`]. Requests without a tenant
+//! are answered with a `400 Bad Request` when the tenant is required, except
+//! for the allowed paths (the health checks by default). See
+//! [`crate::tenancy`] for the isolation modes.
+//!
+//! The tenants are checked by the [`crate::tenancy::TenantAuthorizer`] of the app, when
+//! there is one, and the requests of the other tenants are answered with a
+//! `403 Forbidden`. The `header` and `subdomain` resolvers refuse to start
+//! without one, since the client chooses the tenant.
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     tenancy:
+//!       enable: true
+//!       mode: schema
+//!       schema_prefix: tenant_
+//!       resolver:
+//!         kind: subdomain
+//!         domain: example.com
+//! ```
+//! ```rust,ignore
+//! struct Members;
+//!
+//! #[async_trait]
+//! impl TenantAuthorizer for Members {
+//!     async fn authorize(&self, ctx: &AppContext, parts: &Parts, tenant: &TenantContext) -> Result<bool> {
+//!         let Ok(auth) = JWT::from_request_parts(&mut parts.clone(), ctx).await else {
+//!             return Ok(false);
+//!         };
+//!         Ok(memberships::Model::exists(&ctx.db, &auth.claims.pid, &tenant.id).await?)
+//!     }
+//! }
+//!
+//! // in the app hooks
+//! fn tenant_authorizer(_ctx: &AppContext) -> Option<Arc<dyn TenantAuthorizer>> {
+//!     Some(Arc::new(Members))
+//! }
+//! ```
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext,
+    controller::middleware::MiddlewareLayer,
+    tenancy::{is_valid_id, Mode, Resolver, TenantAuthorization, TenantContext},
+    Error, Result,
+};
+
+/// Tenancy middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Tenancy {
+    #[serde(default)]
+    pub enable: bool,
+    /// How the data of the tenants is isolated
+    #[serde(default)]
+    pub mode: Mode,
+    /// Where the tenant is read from, the `tenant` claim of the JWT by
+    /// default
+    #[serde(default)]
+    pub resolver: Resolver,
+    /// Whether requests without a tenant are rejected
+    #[serde(default = "default_required")]
+    pub required: bool,
+    /// The prefix of the schema names, in the `schema` mode
+    #[serde(default = "default_schema_prefix")]
+    pub schema_prefix: String,
+    /// The path prefixes served without a tenant
+    #[serde(default = "default_allow_paths")]
+    pub allow_paths: Vec<String>,
+}
+
+impl Default for Tenancy {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+const fn default_required() -> bool {
+    true
+}
+
+fn default_schema_prefix() -> String {
+    "tenant_".to_string()
+}
+
+fn default_allow_paths() -> Vec<String> {
+    vec![
+        "/_ping".to_string(),
+        "/_health".to_string(),
+        "/_readiness".to_string(),
+    ]
+}
+
+impl Tenancy {
+    /// The tenant context of a tenant id.
+    #[must_use]
+    pub fn tenant(&self, id: String) -> TenantContext {
+        let schema = match self.mode {
+            Mode::Row => None,
+            Mode::Schema => Some(format!("{}{id}", self.schema_prefix)),
+        };
+        TenantContext { id, schema }
+    }
+}
+
+/// [`MiddlewareLayer`] resolving the tenant of the requests.
+#[derive(Clone)]
+pub struct Middleware {
+    config: Tenancy,
+    ctx: AppContext,
+}
+
+/// Creates the tenancy middleware, resolving JWT claims with the
+/// configuration of the application.
+#[must_use]
+pub fn new(config: &Tenancy, ctx: &AppContext) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        ctx: ctx.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "tenancy"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the tenancy middleware to the application router.
+    ///
+    /// # Errors
+    ///
+    /// When the tenant is chosen by the client and the app has no
+    /// [`crate::tenancy::TenantAuthorizer`]
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        if !self.config.resolver.is_trusted()
+            && !self.ctx.shared_store.contains::<TenantAuthorization>()
+        {
+            return Err(Error::Message(
+                "the tenancy resolver reads a tenant chosen by the client, it requires a \
+                 `TenantAuthorizer` from `Hooks::tenant_authorizer`"
+                    .to_string(),
+            ));
+        }
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            (self.config.clone(), self.ctx.clone()),
+            tenancy_middleware,
+        )))
+    }
+}
+
+async fn tenancy_middleware(
+    State((config, ctx)): State<(Tenancy, AppContext)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = request.into_parts();
+    match config.resolver.resolve(&parts, &ctx).await {
+        Some(id) if is_valid_id(&id) => {
+            let tenant = config.tenant(id);
+            if let Some(TenantAuthorization(authorizer)) = ctx.shared_store.get() {
+                let allowed = match authorizer.authorize(&ctx, &parts, &tenant).await {
+                    Ok(allowed) => allowed,
+                    Err(err) => {
+                        tracing::error!(tenant = tenant.id, error = %err, "could not authorize the tenant");
+                        false
+                    }
+                };
+                if !allowed {
+                    return Error::Forbidden("tenant not allowed".to_string()).into_response();
+                }
+            }
+            parts.extensions.insert(tenant);
+        }
+        Some(_) => return Error::BadRequest("invalid tenant".to_string()).into_response(),
+        None => {
+            let allowed = config
+                .allow_paths
+                .iter()
+                .any(|path| parts.uri.path().starts_with(path.as_str()));
+            if config.required && !allowed {
+                return Error::BadRequest("missing tenant".to_string()).into_response();
+            }
+        }
+    }
+    next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{body::Body, http::request::Parts, http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{tenancy::TenantAuthorizer, tests_cfg};
+
+    struct Members;
+
+    #[async_trait::async_trait]
+    impl TenantAuthorizer for Members {
+        async fn authorize(
+            &self,
+            _ctx: &AppContext,
+            _parts: &Parts,
+            tenant: &TenantContext,
+        ) -> Result<bool> {
+            Ok(tenant.id == "acme")
+        }
+    }
+
+    #[tokio::test]
+    async fn can_resolve_the_tenant_of_requests() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let config = Tenancy {
+            enable: true,
+            mode: Mode::Schema,
+            resolver: Resolver::Header {
+                name: "x-tenant-id".to_string(),
+            },
+            ..Default::default()
+        };
+        // the client chooses the tenant of a header
+        assert!(new(&config, &ctx).apply(Router::new()).is_err());
+        ctx.shared_store
+            .insert(TenantAuthorization(Arc::new(Members)));
+
+        let app = Router::new()
+            .route(
+                "/",
+                get(|tenant: TenantContext| async move { tenant.schema.unwrap_or_default() }),
+            )
+            .route("/_health", get(|| async { "ok" }));
+        let app = new(&config, &ctx)
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(ctx);
+
+        for (uri, tenant, status, body) in [
+            ("/", Some("acme"), StatusCode::OK, "tenant_acme"),
+            ("/", Some("globex"), StatusCode::FORBIDDEN, ""),
+            ("/", Some("ac me"), StatusCode::BAD_REQUEST, ""),
+            ("/", None, StatusCode::BAD_REQUEST, ""),
+            ("/_health", None, StatusCode::OK, "ok"),
+        ] {
+            let mut request = axum::http::Request::builder().uri(uri);
+            if let Some(tenant) = tenant {
+                request = request.header("x-tenant-id", tenant);
+            }
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status);
+            if status == StatusCode::OK {
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                assert_eq!(bytes, body);
+            }
+        }
+    }
+}
@@ -1,23 +1,45 @@
 //! Timeout Request Middleware.
 //!
 //! This middleware applies a timeout to requests processed by the application.
-//! The timeout duration is configurable and defined via the
-//! [`TimeoutRequestMiddleware`] configuration. The middleware ensures that
-//! requests do not run beyond the specified timeout period, improving the
-//! overall performance and responsiveness of the application.
+//! The timeout duration is configurable and defined via the [`TimeOut`]
+//! configuration, with longer or shorter timeouts for the routes under some
+//! prefixes. The middleware ensures that requests do not run beyond the
+//! specified timeout period, improving the overall performance and
+//! responsiveness of the application.
 //!
 //! If a request exceeds the specified timeout duration, the middleware will
 //! return a `408 Request Timeout` status code to the client, indicating that
 //! the request took too long to process.
-use std::time::Duration;
+//!
+//! Handlers read the time left with the [`Deadline`] extractor, to bound
+//! their calls to the database or other services, which answer
+//! `504 Gateway Timeout` when they run out of time.
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     timeout_request:
+//!       enable: true
+//!       timeout: 5000
+//!       overrides:
+//!         - prefix: /api/reports
+//!           timeout: 60000
+//! ```
+use std::{convert::Infallible, future::Future, sync::Arc, time::Duration};
 
-use axum::http::StatusCode;
-use axum::Router as AXRouter;
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tower_http::timeout::TimeoutLayer;
+use tokio::time::Instant;
 
-use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Result};
+use crate::{app::AppContext, controller::middleware::MiddlewareLayer, Error, Result};
 
 /// Timeout middleware configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -27,6 +49,10 @@ pub struct TimeOut {
     // Timeout request in milliseconds
     #[serde(default = "default_timeout")]
     pub timeout: u64,
+    /// The timeouts of the routes under some prefixes, the longest matching
+    /// prefix applying to a request
+    #[serde(default)]
+    pub overrides: Vec<Override>,
 }
 
 impl Default for TimeOut {
@@ -39,6 +65,37 @@ fn default_timeout() -> u64 {
     5_000
 }
 
+/// The timeout of the routes under a prefix
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Override {
+    /// The path prefix of the routes
+    pub prefix: String,
+    /// Timeout request in milliseconds
+    pub timeout: u64,
+}
+
+impl Override {
+    fn matches(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_end_matches('/');
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+impl TimeOut {
+    /// The timeout of a request path.
+    #[must_use]
+    pub fn timeout_for(&self, path: &str) -> Duration {
+        let timeout = self
+            .overrides
+            .iter()
+            .filter(|rule| rule.matches(path))
+            .max_by_key(|rule| rule.prefix.trim_end_matches('/').len())
+            .map_or(self.timeout, |rule| rule.timeout);
+        Duration::from_millis(timeout)
+    }
+}
+
 impl MiddlewareLayer for TimeOut {
     /// Returns the name of the middleware.
     fn name(&self) -> &'static str {
@@ -56,13 +113,191 @@ impl MiddlewareLayer for TimeOut {
 
     /// Applies the timeout middleware to the application router.
     ///
-    /// This method wraps the provided [`AXRouter`] in a [`TimeoutLayer`],
-    /// ensuring that requests exceeding the specified timeout duration will
-    /// be interrupted.
+    /// Requests exceeding their timeout are interrupted, and their deadline
+    /// is given to the handlers as a [`Deadline`]. When the middleware wraps
+    /// another one, such as a route-scoped one, the earliest deadline applies.
     fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
-        Ok(app.layer(TimeoutLayer::with_status_code(
-            StatusCode::REQUEST_TIMEOUT,
-            Duration::from_millis(self.timeout),
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(self.clone()),
+            timeout_middleware,
         )))
     }
 }
+
+async fn timeout_middleware(
+    State(config): State<Arc<TimeOut>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let mut deadline = Instant::now() + config.timeout_for(request.uri().path());
+    if let Some(Deadline(Some(outer))) = request.extensions().get::<Deadline>() {
+        deadline = deadline.min(*outer);
+    }
+    request.extensions_mut().insert(Deadline(Some(deadline)));
+
+    match tokio::time::timeout_at(deadline, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => Error::RequestTimeout.into_response(),
+    }
+}
+
+/// The deadline of a request, set by the timeout middleware, and unlimited
+/// when it is disabled.
+///
+/// # Example
+/// ```rust
+/// use loco_rs::{controller::middleware::timeout::Deadline, prelude::*};
+///
+/// async fn report(deadline: Deadline) -> Result<Response> {
+///     let rows = deadline.run(async { Ok(vec!["row"]) }).await?;
+///     format::json(rows)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// The time left, if the request has a deadline.
+    #[must_use]
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether the deadline has passed.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.remaining()
+            .is_some_and(|remaining| remaining.is_zero())
+    }
+
+    /// Runs a future until the deadline.
+    ///
+    /// # Errors
+    ///
+    /// The error of the future, or [`Error::GatewayTimeout`] when it runs
+    /// past the deadline.
+    pub async fn run<T, F>(&self, future: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>> + Send,
+    {
+        match self.0 {
+            Some(deadline) => tokio::time::timeout_at(deadline, future)
+                .await
+                .map_err(|_| Error::GatewayTimeout)?,
+            None => future.await,
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for Deadline
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().copied().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode, routing::get, Router};
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    fn config() -> TimeOut {
+        TimeOut {
+            enable: true,
+            timeout: 50,
+            overrides: vec![
+                Override {
+                    prefix: "/api".to_string(),
+                    timeout: 500,
+                },
+                Override {
+                    prefix: "/api/fast".to_string(),
+                    timeout: 10,
+                },
+            ],
+        }
+    }
+
+    #[rstest]
+    #[case("/", 50)]
+    #[case("/api", 500)]
+    #[case("/api/reports", 500)]
+    #[case("/api/fast/1", 10)]
+    #[case("/apis", 50)]
+    fn can_pick_timeouts(#[case] path: &str, #[case] expected: u64) {
+        assert_eq!(config().timeout_for(path), Duration::from_millis(expected));
+    }
+
+    async fn request(app: &Router, uri: &str) -> StatusCode {
+        let request = axum::http::Request::builder()
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn can_time_out_requests() {
+        async fn slow(deadline: Deadline) -> Result<Response> {
+            assert!(deadline.remaining().is_some());
+            deadline
+                .run(async {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    Ok(())
+                })
+                .await?;
+            crate::controller::format::text("done")
+        }
+
+        let app = Router::new()
+            .route("/slow", get(slow))
+            .route("/api/slow", get(slow))
+            .route("/api/fast/slow", get(slow));
+        let app = config()
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        assert_eq!(request(&app, "/api/slow").await, StatusCode::OK);
+        // the handler bounds its call with the deadline
+        assert_eq!(request(&app, "/slow").await, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(
+            request(&app, "/api/fast/slow").await,
+            StatusCode::GATEWAY_TIMEOUT
+        );
+    }
+
+    #[tokio::test]
+    async fn can_interrupt_requests() {
+        let app = Router::new().route(
+            "/",
+            get(|| async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                "done"
+            }),
+        );
+        let app = config()
+            .apply(app)
+            .expect("apply middleware")
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        assert_eq!(request(&app, "/").await, StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn handlers_without_deadline_are_unlimited() {
+        let deadline = Deadline::default();
+        assert_eq!(deadline.remaining(), None);
+        assert!(!deadline.is_expired());
+        assert_eq!(deadline.run(async { Ok(1) }).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,314 @@
+//! Transaction Middleware
+//!
+//! Runs each request in a database transaction, handed to the handlers with
+//! the [`Tx`] extractor: the transaction is committed when the response is a
+//! success or a redirection, and rolled back when it is an error, such as
+//! when the handler returns an `Err`. The writes of a handler are then all
+//! applied, or none of them, without beginning and committing the
+//! transaction in every handler.
+//!
+//! With the tenancy middleware in the `schema` mode, the transaction runs in
+//! the schema of the tenant.
+//!
+//! # Example
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     transaction:
+//!       enable: true
+//!       methods: [POST, PUT, PATCH, DELETE]
+//!       prefixes:
+//!         - /api
+//! ```
+//!
+//! ```rust,ignore
+//! async fn add(tx: Tx, Json(params): Json<Params>) -> Result<Response> {
+//!     let order = orders::ActiveModel::from(params).insert(&*tx).await?;
+//!     order_lines::Entity::insert_many(lines(&order)).exec(&*tx).await?;
+//!     format::json(order)
+//! }
+//! ```
+
+use std::{ops::Deref, sync::Arc};
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use sea_orm::{DatabaseConnection, DatabaseTransaction, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    app::AppContext, controller::middleware::MiddlewareLayer, tenancy::TenantContext, Error, Result,
+};
+
+/// Transaction middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Transaction {
+    #[serde(default)]
+    pub enable: bool,
+    /// The methods of the requests run in a transaction
+    #[serde(default = "default_methods")]
+    pub methods: Vec<String>,
+    /// The path prefixes of the routes run in a transaction, all of them when
+    /// empty
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_methods() -> Vec<String> {
+    vec![
+        "POST".to_string(),
+        "PUT".to_string(),
+        "PATCH".to_string(),
+        "DELETE".to_string(),
+    ]
+}
+
+/// The transaction of the request, requiring the transaction middleware.
+///
+/// It dereferences to the [`DatabaseTransaction`], to pass as `&*tx` where a
+/// connection is expected. Don't keep it beyond the handler: a transaction
+/// still in use when the response is ready is rolled back.
+#[derive(Clone)]
+pub struct Tx(Arc<DatabaseTransaction>);
+
+impl Deref for Tx {
+    type Target = DatabaseTransaction;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for Tx
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        parts.extensions.get::<Self>().cloned().ok_or_else(|| {
+            tracing::error!(
+                "the transaction extractor requires the `transaction` middleware to match the \
+                 request"
+            );
+            Error::InternalServerError
+        })
+    }
+}
+
+/// [`MiddlewareLayer`] running the requests in a database transaction.
+#[derive(Clone)]
+pub struct Middleware {
+    config: Transaction,
+    ctx: AppContext,
+}
+
+/// Creates the transaction middleware, beginning the transactions on the
+/// database of the application.
+#[must_use]
+pub fn new(config: &Transaction, ctx: &AppContext) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        ctx: ctx.clone(),
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "transaction"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(&self.config)
+    }
+
+    /// Applies the transaction middleware to the application router.
+    ///
+    /// # Errors
+    /// when a method is invalid
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let methods = self
+            .config
+            .methods
+            .iter()
+            .map(|method| Method::from_bytes(method.to_ascii_uppercase().as_bytes()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(Scope {
+                prefixes: self.config.prefixes.clone(),
+                methods,
+                db: self.ctx.db.clone(),
+            }),
+            transaction_middleware,
+        )))
+    }
+}
+
+struct Scope {
+    prefixes: Vec<String>,
+    methods: Vec<Method>,
+    db: DatabaseConnection,
+}
+
+impl Scope {
+    fn matches(&self, request: &Request) -> bool {
+        let path = request.uri().path();
+        self.methods.contains(request.method())
+            && (self.prefixes.is_empty()
+                || self.prefixes.iter().any(|prefix| {
+                    let prefix = prefix.trim_end_matches('/');
+                    path.strip_prefix(prefix)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                }))
+    }
+
+    async fn begin(&self, tenant: Option<TenantContext>) -> Result<DatabaseTransaction> {
+        match tenant {
+            Some(tenant) => tenant.begin(&self.db).await,
+            None => Ok(self.db.begin().await?),
+        }
+    }
+}
+
+async fn transaction_middleware(
+    State(scope): State<Arc<Scope>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !scope.matches(&request) {
+        return next.run(request).await;
+    }
+    // cloned out of the request, which is not `Sync`, before awaiting
+    let tenant = request.extensions().get::<TenantContext>().cloned();
+    let txn = match scope.begin(tenant).await {
+        Ok(txn) => Arc::new(txn),
+        Err(err) => {
+            tracing::error!(error = %err, "could not begin the request transaction");
+            return Error::InternalServerError.into_response();
+        }
+    };
+    request.extensions_mut().insert(Tx(txn.clone()));
+
+    let response = next.run(request).await;
+    let Ok(txn) = Arc::try_unwrap(txn) else {
+        // rolled back when the last reference is dropped
+        tracing::error!("the request transaction is still in use after the response");
+        return Error::InternalServerError.into_response();
+    };
+    let status = response.status();
+    if status.is_client_error() || status.is_server_error() {
+        if let Err(err) = txn.rollback().await {
+            tracing::error!(error = %err, "could not roll back the request transaction");
+        }
+        return response;
+    }
+    match txn.commit().await {
+        Ok(()) => response,
+        Err(err) => {
+            tracing::error!(error = %err, "could not commit the request transaction");
+            Error::InternalServerError.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::StatusCode,
+        routing::{get, post},
+        Router,
+    };
+    use sea_orm::{ConnectionTrait, DbBackend, Statement};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[tokio::test]
+    async fn can_commit_or_roll_back_requests() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        ctx.db
+            .execute_unprepared("CREATE TABLE orders (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+
+        let app = Router::new()
+            .route(
+                "/orders",
+                post(|tx: Tx| async move {
+                    tx.execute_unprepared("INSERT INTO orders DEFAULT VALUES")
+                        .await?;
+                    Ok::<_, Error>("created")
+                }),
+            )
+            .route(
+                "/failing",
+                post(|tx: Tx| async move {
+                    tx.execute_unprepared("INSERT INTO orders DEFAULT VALUES")
+                        .await?;
+                    Err::<(), _>(Error::BadRequest("invalid order".to_string()))
+                }),
+            )
+            .route("/orders", get(|_tx: Tx| async { "read" }));
+        let app = new(
+            &Transaction {
+                enable: true,
+                ..Default::default()
+            },
+            &ctx,
+        )
+        .apply(app)
+        .expect("apply middleware")
+        .with_state(ctx.clone());
+
+        for (method, uri, status) in [
+            ("POST", "/orders", StatusCode::OK),
+            ("POST", "/failing", StatusCode::BAD_REQUEST),
+            ("GET", "/orders", StatusCode::INTERNAL_SERVER_ERROR),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    axum::http::Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{method} {uri}");
+        }
+
+        let orders = ctx
+            .db
+            .query_one(Statement::from_string(
+                DbBackend::Sqlite,
+                "SELECT COUNT(*) AS count FROM orders",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(orders.try_get::<i64>("", "count").unwrap(), 1);
+    }
+}
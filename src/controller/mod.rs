@@ -72,19 +72,28 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use colored::Colorize;
-pub use routes::Routes;
+pub use routes::{Routes, ScopedMiddleware};
 use serde::Serialize;
 
 use crate::{errors::Error, Result};
 
 mod app_routes;
+pub mod assets;
 mod backtrace;
+#[cfg(feature = "auth_jwt")]
+pub mod dashboard;
 mod describe;
 pub mod extractor;
 pub mod format;
+pub mod mailer_preview;
+pub mod mailer_webhooks;
 pub mod middleware;
 pub mod monitoring;
+#[cfg(feature = "openapi")]
+pub mod openapi;
 mod routes;
+pub mod sse;
+pub mod storage;
 pub mod views;
 
 /// Create an unauthorized error with a specified message.
@@ -113,6 +122,16 @@ pub fn unauthorized<T: Into<String>, U>(msg: T) -> Result<U> {
     Err(Error::Unauthorized(msg.into()))
 }
 
+/// Create a forbidden error with a specified message, for authenticated
+/// users that lack the permission to access a resource.
+///
+/// # Errors
+///
+/// returns forbidden enum
+pub fn forbidden<T: Into<String>, U>(msg: T) -> Result<U> {
+    Err(Error::Forbidden(msg.into()))
+}
+
 /// Return a bad request with a message
 ///
 /// # Errors
@@ -216,7 +235,36 @@ impl IntoResponse for Error {
                     ),
                 )
             }
+            Self::Forbidden(err) => {
+                tracing::warn!(err);
+                (
+                    StatusCode::FORBIDDEN,
+                    ErrorDetail::new(
+                        "forbidden",
+                        "You do not have permission to access this resource",
+                    ),
+                )
+            }
+            Self::RequestTimeout => (
+                StatusCode::REQUEST_TIMEOUT,
+                ErrorDetail::new("request_timeout", "The request took too long to process"),
+            ),
+            Self::GatewayTimeout => (
+                StatusCode::GATEWAY_TIMEOUT,
+                ErrorDetail::new(
+                    "gateway_timeout",
+                    "A downstream call took too long to complete",
+                ),
+            ),
             Self::CustomError(status_code, data) => (status_code, data),
+            #[cfg(feature = "with-db")]
+            Self::Model(crate::model::ModelError::StaleObject) => (
+                StatusCode::CONFLICT,
+                ErrorDetail::new(
+                    "stale_object",
+                    "The resource was changed by another request, reload it and retry",
+                ),
+            ),
             Self::WithBacktrace { inner, backtrace } => {
                 println!("\n{}", inner.to_string().red().underline());
                 backtrace::print_backtrace(&backtrace).unwrap();
@@ -1,25 +1,222 @@
 //! This module contains a base routes related to readiness checks and status
 //! reporting. These routes are commonly used to monitor the readiness of the
 //! application and its dependencies.
-
-use super::{format, routes::Routes};
-#[cfg(any(feature = "cache_inmem", feature = "cache_redis"))]
-use crate::config;
-use crate::{app::AppContext, Result};
+//!
+//! `/_readiness` runs the [`HealthCheck`]s of the database, the queue and the
+//! cache, along with the checks of [`crate::app::Hooks::health_checks`], and
+//! answers `503 Service Unavailable` when any of them fails. `/_health` only
+//! runs the liveness checks, so that a load balancer does not restart an
+//! instance whose dependencies are down. Both report each check:
+//!
+//! ```json
+//! {
+//!   "ok": false,
+//!   "checks": {
+//!     "cache": { "ok": true, "duration_ms": 0 },
+//!     "db": { "ok": false, "duration_ms": 2000, "error": "timed out after 2000ms" }
+//!   }
+//! }
+//! ```
+
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
 use axum::{
     extract::State,
     http::StatusCode,
     response::{IntoResponse, Response},
     routing::get,
 };
+use futures_util::future::join_all;
 use serde::Serialize;
 
+use super::{format, routes::Routes};
+#[cfg(any(feature = "cache_inmem", feature = "cache_redis"))]
+use crate::config;
+use crate::{app::AppContext, Error, Result};
+
+/// The time a check is given by default.
+pub const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A check of the health of the application or one of its dependencies.
+///
+/// # Example
+/// ```rust
+/// use async_trait::async_trait;
+/// use loco_rs::{controller::monitoring::HealthCheck, prelude::*};
+///
+/// struct Payments;
+///
+/// #[async_trait]
+/// impl HealthCheck for Payments {
+///     fn name(&self) -> &str {
+///         "payments"
+///     }
+///
+///     async fn check(&self, _ctx: &AppContext) -> Result<()> {
+///         // ping the payments provider
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// The name of the check in the reports.
+    fn name(&self) -> &str;
+
+    /// Runs the check.
+    ///
+    /// # Errors
+    ///
+    /// When the checked dependency is unhealthy.
+    async fn check(&self, ctx: &AppContext) -> Result<()>;
+
+    /// The time after which the check fails.
+    fn timeout(&self) -> Duration {
+        DEFAULT_CHECK_TIMEOUT
+    }
+
+    /// Whether `/_health` runs the check too, and not only `/_readiness`.
+    fn liveness(&self) -> bool {
+        false
+    }
+}
+
+/// The custom checks of the application, kept in the shared store of the
+/// context.
+#[derive(Clone, Default)]
+pub struct HealthChecks(pub Vec<Arc<dyn HealthCheck>>);
+
 /// Represents the health status of the application.
 #[derive(Serialize)]
 pub struct Health {
     pub ok: bool,
 }
 
+/// The result of the checks run by an endpoint.
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub ok: bool,
+    pub checks: BTreeMap<String, CheckReport>,
+}
+
+/// The result of a check.
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub ok: bool,
+    pub duration_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Pings the database.
+#[cfg(feature = "with-db")]
+pub struct Database;
+
+#[cfg(feature = "with-db")]
+#[async_trait]
+impl HealthCheck for Database {
+    fn name(&self) -> &str {
+        "db"
+    }
+
+    async fn check(&self, ctx: &AppContext) -> Result<()> {
+        Ok(ctx.db.ping().await?)
+    }
+}
+
+/// Pings the queue of the background workers.
+pub struct Queue;
+
+#[async_trait]
+impl HealthCheck for Queue {
+    fn name(&self) -> &str {
+        "queue"
+    }
+
+    async fn check(&self, ctx: &AppContext) -> Result<()> {
+        match &ctx.queue_provider {
+            Some(queue) => queue.ping().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Pings the cache.
+#[cfg(any(feature = "cache_inmem", feature = "cache_redis"))]
+pub struct Cache;
+
+#[cfg(any(feature = "cache_inmem", feature = "cache_redis"))]
+#[async_trait]
+impl HealthCheck for Cache {
+    fn name(&self) -> &str {
+        "cache"
+    }
+
+    async fn check(&self, ctx: &AppContext) -> Result<()> {
+        Ok(ctx.cache.driver.ping().await?)
+    }
+}
+
+/// The checks of the dependencies configured in the context, followed by
+/// the custom checks of the application.
+#[must_use]
+pub fn checks(ctx: &AppContext) -> Vec<Arc<dyn HealthCheck>> {
+    let mut checks: Vec<Arc<dyn HealthCheck>> = vec![];
+    #[cfg(feature = "with-db")]
+    checks.push(Arc::new(Database));
+    if ctx.queue_provider.is_some() {
+        checks.push(Arc::new(Queue));
+    }
+    #[cfg(any(feature = "cache_inmem", feature = "cache_redis"))]
+    if !matches!(ctx.config.cache, config::CacheConfig::Null) {
+        checks.push(Arc::new(Cache));
+    }
+    if let Some(HealthChecks(custom)) = ctx.shared_store.get::<HealthChecks>() {
+        checks.extend(custom);
+    }
+    checks
+}
+
+/// Runs checks concurrently, each within its timeout.
+pub async fn run_checks(ctx: &AppContext, checks: &[Arc<dyn HealthCheck>]) -> HealthReport {
+    let reports = join_all(checks.iter().map(|check| async move {
+        let started = std::time::Instant::now();
+        let timeout = check.timeout();
+        let result = match tokio::time::timeout(timeout, check.check(ctx)).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::Message(format!(
+                "timed out after {}ms",
+                timeout.as_millis()
+            ))),
+        };
+        if let Err(error) = &result {
+            tracing::error!(check = check.name(), err.msg = %error, err.detail = ?error, "health_check_error");
+        }
+        let report = CheckReport {
+            ok: result.is_ok(),
+            duration_ms: started.elapsed().as_millis(),
+            error: result.err().map(|error| error.to_string()),
+        };
+        (check.name().to_string(), report)
+    }))
+    .await;
+
+    HealthReport {
+        ok: reports.iter().all(|(_, report)| report.ok),
+        checks: reports.into_iter().collect(),
+    }
+}
+
+fn respond(report: &HealthReport) -> (StatusCode, Response) {
+    let status = if report.ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, format::json(report).into_response())
+}
+
 /// Check application ping endpoint
 ///
 /// # Errors
@@ -28,73 +225,24 @@ pub async fn ping() -> Result<Response> {
     format::json(Health { ok: true })
 }
 
-/// Check application ping endpoint
-///
-/// # Errors
-/// This function always returns `Ok` with a JSON response indicating the
-pub async fn health() -> Result<Response> {
-    format::json(Health { ok: true })
+/// Check the liveness of the application, running the checks marked with
+/// [`HealthCheck::liveness`].
+pub async fn health(State(ctx): State<AppContext>) -> (StatusCode, Response) {
+    let checks: Vec<_> = checks(&ctx)
+        .into_iter()
+        .filter(|check| check.liveness())
+        .collect();
+    respond(&run_checks(&ctx, &checks).await)
 }
 
-/// Check the readiness of the application by sending a ping request to
-/// Redis or the DB (depending on feature flags) to ensure connection liveness.
+/// Check the readiness of the application by pinging the DB, the queue and
+/// the cache (depending on feature flags and configuration), and running the
+/// custom checks of the application.
 ///
 /// # Errors
 /// All errors are logged, and the readiness status is returned as a JSON response.
 pub async fn readiness(State(ctx): State<AppContext>) -> (StatusCode, Response) {
-    // Check database connection
-    #[cfg(feature = "with-db")]
-    if let Err(error) = &ctx.db.ping().await {
-        tracing::error!(err.msg = %error, err.detail = ?error, "readiness_db_ping_error");
-        return (
-            StatusCode::SERVICE_UNAVAILABLE,
-            format::json(Health { ok: false }).into_response(),
-        );
-    }
-
-    // Check queue connection
-    if let Some(queue) = &ctx.queue_provider {
-        if let Err(error) = queue.ping().await {
-            tracing::error!(err.msg = %error, err.detail = ?error, "readiness_queue_ping_error");
-            return (
-                StatusCode::SERVICE_UNAVAILABLE,
-                format::json(Health { ok: false }).into_response(),
-            );
-        }
-    }
-
-    // Check cache connection
-    #[cfg(any(feature = "cache_inmem", feature = "cache_redis"))]
-    {
-        match ctx.config.cache {
-            #[cfg(feature = "cache_inmem")]
-            config::CacheConfig::InMem(_) => {
-                if let Err(error) = &ctx.cache.driver.ping().await {
-                    tracing::error!(err.msg = %error, err.detail = ?error, "readiness_cache_ping_error");
-                    return (
-                        StatusCode::SERVICE_UNAVAILABLE,
-                        format::json(Health { ok: false }).into_response(),
-                    );
-                }
-            }
-            #[cfg(feature = "cache_redis")]
-            config::CacheConfig::Redis(_) => {
-                if let Err(error) = &ctx.cache.driver.ping().await {
-                    tracing::error!(err.msg = %error, err.detail = ?error, "readiness_cache_ping_error");
-                    return (
-                        StatusCode::SERVICE_UNAVAILABLE,
-                        format::json(Health { ok: false }).into_response(),
-                    );
-                }
-            }
-            config::CacheConfig::Null => (),
-        }
-    }
-
-    (
-        StatusCode::OK,
-        format::json(Health { ok: true }).into_response(),
-    )
+    respond(&run_checks(&ctx, &checks(&ctx)).await)
 }
 
 /// Defines and returns the readiness-related routes.
@@ -457,4 +605,57 @@ mod tests {
         let res_json: Value = serde_json::from_slice(&body).expect("Valid JSON response");
         assert_eq!(res_json["ok"], false);
     }
+
+    struct Slow;
+
+    #[async_trait::async_trait]
+    impl monitoring::HealthCheck for Slow {
+        fn name(&self) -> &str {
+            "slow"
+        }
+
+        async fn check(&self, _ctx: &loco_rs::app::AppContext) -> loco_rs::Result<()> {
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            Ok(())
+        }
+
+        fn timeout(&self) -> std::time::Duration {
+            std::time::Duration::from_millis(10)
+        }
+
+        fn liveness(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn readiness_with_custom_check_timeout() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        ctx.shared_store
+            .insert(monitoring::HealthChecks(vec![std::sync::Arc::new(Slow)]));
+
+        let router = axum::Router::new()
+            .route("/_readiness", get(monitoring::readiness))
+            .route("/_health", get(monitoring::health))
+            .with_state(ctx);
+
+        for uri in ["/_readiness", "/_health"] {
+            let req = axum::http::Request::builder()
+                .uri(uri)
+                .method("GET")
+                .body(axum::body::Body::empty())
+                .unwrap();
+
+            let response = router.clone().oneshot(req).await.unwrap();
+            assert_eq!(response.status(), 503);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let res_json: Value = serde_json::from_slice(&body).expect("Valid JSON response");
+            assert_eq!(res_json["ok"], false);
+            assert_eq!(res_json["checks"]["slow"]["ok"], false);
+            assert_eq!(res_json["checks"]["slow"]["error"], "timed out after 10ms");
+        }
+    }
 }
@@ -0,0 +1,366 @@
+//! # OpenAPI
+//!
+//! With the `openapi` feature, an OpenAPI 3.1 document is derived from the
+//! routes of the application. It is served at `/api-docs/openapi.json`, along
+//! with a Swagger UI at `/api-docs`, and printed by
+//! `cargo loco routes --openapi`.
+//!
+//! Every route is listed with its path parameters. [`Routes::doc`] describes
+//! the operation of the route added last, its body and responses being the
+//! schemas of types deriving [`utoipa::ToSchema`].
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::{controller::openapi::Operation, prelude::*};
+//! use serde::{Deserialize, Serialize};
+//! use utoipa::ToSchema;
+//!
+//! #[derive(Serialize, Deserialize, ToSchema)]
+//! struct Post {
+//!     title: String,
+//! }
+//!
+//! async fn add(Json(post): Json<Post>) -> Result<Response> {
+//!     format::json(post)
+//! }
+//!
+//! Routes::new()
+//!     .prefix("/api/posts")
+//!     .add("/", post(add))
+//!     .doc(
+//!         Operation::new()
+//!             .summary("Create a post")
+//!             .tag("posts")
+//!             .body::<Post>()
+//!             .response::<Post>(200, "The created post"),
+//!     );
+//! ```
+//!
+//! [`Routes::doc`]: crate::controller::Routes::doc
+use std::collections::BTreeMap;
+
+use axum::{
+    http::{header, Method},
+    response::Html,
+    routing::get,
+    Router as AXRouter,
+};
+use serde_json::{json, Map, Value};
+pub use utoipa;
+use utoipa::{openapi::path::ParameterIn, IntoParams, ToSchema};
+
+use crate::{
+    app::{AppContext, Hooks},
+    controller::ListRoutes,
+};
+
+/// The path of the Swagger UI, the document being served under it.
+pub const DOCS_PATH: &str = "/api-docs";
+
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8" />
+    <title>API docs</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+  </head>
+  <body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+    <script>
+      window.ui = SwaggerUIBundle({ url: "/api-docs/openapi.json", dom_id: "#swagger-ui" });
+    </script>
+  </body>
+</html>
+"##;
+
+/// The description of the operation of a route.
+#[derive(Debug, Clone, Default)]
+pub struct Operation {
+    summary: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+    parameters: Vec<Value>,
+    request_body: Option<Value>,
+    responses: BTreeMap<String, Value>,
+    /// The schemas referenced by the operation, by name
+    schemas: BTreeMap<String, Value>,
+}
+
+impl Operation {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.summary = Some(summary.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Groups the operation under a tag.
+    #[must_use]
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// Adds the query parameters of `T`, as read with
+    /// [`axum::extract::Query`].
+    #[must_use]
+    pub fn query<T: IntoParams>(mut self) -> Self {
+        self.parameters.extend(
+            T::into_params(|| Some(ParameterIn::Query))
+                .iter()
+                .map(|parameter| serde_json::to_value(parameter).unwrap_or_default()),
+        );
+        self
+    }
+
+    /// Sets the JSON body of the requests.
+    #[must_use]
+    pub fn body<T: ToSchema>(mut self) -> Self {
+        let schema = self.schema::<T>();
+        self.request_body = Some(json!({
+            "required": true,
+            "content": { "application/json": { "schema": schema } },
+        }));
+        self
+    }
+
+    /// Adds a response with a JSON body.
+    #[must_use]
+    pub fn response<T: ToSchema>(mut self, status: u16, description: &str) -> Self {
+        let schema = self.schema::<T>();
+        self.add_response(status, description, Some(schema));
+        self
+    }
+
+    /// Adds a response with a JSON array body.
+    #[must_use]
+    pub fn response_list<T: ToSchema>(mut self, status: u16, description: &str) -> Self {
+        let schema = self.schema::<T>();
+        self.add_response(
+            status,
+            description,
+            Some(json!({ "type": "array", "items": schema })),
+        );
+        self
+    }
+
+    /// Adds a response without a body.
+    #[must_use]
+    pub fn status(mut self, status: u16, description: &str) -> Self {
+        self.add_response(status, description, None);
+        self
+    }
+
+    fn add_response(&mut self, status: u16, description: &str, schema: Option<Value>) {
+        let mut response = json!({ "description": description });
+        if let Some(schema) = schema {
+            response["content"] = json!({ "application/json": { "schema": schema } });
+        }
+        self.responses.insert(status.to_string(), response);
+    }
+
+    /// Registers the schema of `T`, and those it refers to, returning a
+    /// reference to it.
+    fn schema<T: ToSchema>(&mut self) -> Value {
+        let mut schemas = vec![];
+        T::schemas(&mut schemas);
+        for (name, schema) in schemas {
+            self.schemas
+                .insert(name, serde_json::to_value(schema).unwrap_or_default());
+        }
+        let name = T::name().to_string();
+        self.schemas.insert(
+            name.clone(),
+            serde_json::to_value(T::schema()).unwrap_or_default(),
+        );
+        json!({ "$ref": format!("#/components/schemas/{name}") })
+    }
+
+    fn to_value(&self, path_params: &[String]) -> Value {
+        let mut operation = Map::new();
+        if let Some(summary) = &self.summary {
+            operation.insert("summary".to_string(), json!(summary));
+        }
+        if let Some(description) = &self.description {
+            operation.insert("description".to_string(), json!(description));
+        }
+        if !self.tags.is_empty() {
+            operation.insert("tags".to_string(), json!(self.tags));
+        }
+
+        let mut parameters: Vec<Value> = path_params
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        parameters.extend(self.parameters.iter().cloned());
+        if !parameters.is_empty() {
+            operation.insert("parameters".to_string(), Value::Array(parameters));
+        }
+
+        if let Some(request_body) = &self.request_body {
+            operation.insert("requestBody".to_string(), request_body.clone());
+        }
+        let responses = if self.responses.is_empty() {
+            json!({ "200": { "description": "OK" } })
+        } else {
+            json!(self.responses)
+        };
+        operation.insert("responses".to_string(), responses);
+
+        Value::Object(operation)
+    }
+}
+
+/// Builds the OpenAPI document of the routes.
+#[must_use]
+pub fn document<H: Hooks>(routes: &[ListRoutes]) -> Value {
+    let mut paths: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+    let mut schemas = BTreeMap::new();
+
+    for route in routes {
+        let (path, path_params) = openapi_path(&route.uri);
+        let undocumented = Operation::default();
+        let operation = route.doc.as_ref().unwrap_or(&undocumented);
+        schemas.extend(operation.schemas.clone());
+
+        for action in &route.actions {
+            if *action == Method::CONNECT {
+                continue;
+            }
+            paths.entry(path.clone()).or_default().insert(
+                action.as_str().to_lowercase(),
+                operation.to_value(&path_params),
+            );
+        }
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": H::app_name(),
+            "version": H::app_version(),
+        },
+        "paths": paths,
+        "components": { "schemas": schemas },
+    })
+}
+
+/// Serves the document and the Swagger UI.
+#[must_use]
+pub fn mount(app: AXRouter<AppContext>, document: &Value) -> AXRouter<AppContext> {
+    let document = document.to_string();
+    app.route(DOCS_PATH, get(|| async { Html(SWAGGER_UI) }))
+        .route(
+            &format!("{DOCS_PATH}/openapi.json"),
+            get(move || async move { ([(header::CONTENT_TYPE, "application/json")], document) }),
+        )
+}
+
+/// Converts an axum path to an OpenAPI one, with its parameters: the
+/// wildcard `{*rest}` becomes `{rest}`.
+fn openapi_path(uri: &str) -> (String, Vec<String>) {
+    let mut params = vec![];
+    let path = uri
+        .split('/')
+        .map(|segment| {
+            segment
+                .strip_prefix('{')
+                .and_then(|segment| segment.strip_suffix('}'))
+                .map_or_else(
+                    || segment.to_string(),
+                    |name| {
+                        let name = name.trim_start_matches('*');
+                        params.push(name.to_string());
+                        format!("{{{name}}}")
+                    },
+                )
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    (path, params)
+}
+
+#[cfg(all(test, feature = "with-db"))]
+mod tests {
+    use axum::routing::post;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{
+        controller::{AppRoutes, Routes},
+        tests_cfg::db::AppHook,
+    };
+
+    #[derive(Serialize, Deserialize, ToSchema)]
+    struct Post {
+        title: String,
+    }
+
+    async fn action() -> &'static str {
+        "loco"
+    }
+
+    #[test]
+    fn can_convert_paths() {
+        assert_eq!(
+            openapi_path("/posts/{id}/files/{*path}"),
+            (
+                "/posts/{id}/files/{path}".to_string(),
+                vec!["id".to_string(), "path".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn can_build_document() {
+        let routes = AppRoutes::empty().add_route(
+            Routes::new()
+                .prefix("/api/posts")
+                .add("/", post(action))
+                .doc(
+                    Operation::new()
+                        .summary("Create a post")
+                        .body::<Post>()
+                        .response::<Post>(201, "The created post"),
+                )
+                .add("/{id}", get(action)),
+        );
+        let document = document::<AppHook>(&routes.collect());
+
+        assert_eq!(document["openapi"], "3.1.0");
+        let create = &document["paths"]["/api/posts"]["post"];
+        assert_eq!(create["summary"], "Create a post");
+        assert_eq!(
+            create["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/Post"
+        );
+        assert_eq!(
+            create["responses"]["201"]["description"],
+            "The created post"
+        );
+        assert_eq!(document["components"]["schemas"]["Post"]["type"], "object");
+
+        let show = &document["paths"]["/api/posts/{id}"]["get"];
+        assert_eq!(show["parameters"][0]["name"], "id");
+        assert_eq!(show["parameters"][0]["in"], "path");
+        assert_eq!(show["responses"]["200"]["description"], "OK");
+    }
+}
@@ -1,9 +1,9 @@
-use std::convert::Infallible;
+use std::{convert::Infallible, fmt, sync::Arc};
 
 use axum::{extract::Request, response::IntoResponse, routing::Route};
 use tower::{Layer, Service};
 
-use super::describe;
+use super::{describe, middleware::MiddlewareLayer};
 use crate::app::AppContext;
 #[derive(Clone, Default, Debug)]
 pub struct Routes {
@@ -17,6 +17,29 @@ pub struct Handler {
     pub uri: String,
     pub method: axum::routing::MethodRouter<AppContext>,
     pub actions: Vec<axum::http::Method>,
+    /// The middlewares of the handler only, on top of the global ones
+    pub middlewares: Vec<ScopedMiddleware>,
+    #[cfg(feature = "openapi")]
+    pub doc: Option<super::openapi::Operation>,
+}
+
+/// A middleware applied to some routes only, see [`Routes::with_middleware`].
+#[derive(Clone)]
+pub struct ScopedMiddleware(Arc<dyn MiddlewareLayer + Send + Sync>);
+
+impl ScopedMiddleware {
+    #[must_use]
+    pub fn layer(&self) -> &(dyn MiddlewareLayer + Send + Sync) {
+        self.0.as_ref()
+    }
+}
+
+impl fmt::Debug for ScopedMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ScopedMiddleware")
+            .field(&self.0.name())
+            .finish()
+    }
 }
 
 impl Routes {
@@ -84,10 +107,37 @@ impl Routes {
             uri: uri.to_owned(),
             actions: describe::method_action(&method),
             method,
+            middlewares: vec![],
+            #[cfg(feature = "openapi")]
+            doc: None,
         });
         self
     }
 
+    /// Describes the operation of the route added last, for the OpenAPI
+    /// document of the application.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use loco_rs::{controller::openapi::Operation, prelude::*};
+    ///
+    /// async fn ping() -> Result<Response> {
+    ///     format::json("pong")
+    /// }
+    /// Routes::new()
+    ///     .add("/_ping", get(ping))
+    ///     .doc(Operation::new().summary("Check the server is up"));
+    /// ```
+    #[cfg(feature = "openapi")]
+    #[must_use]
+    pub fn doc(mut self, operation: super::openapi::Operation) -> Self {
+        if let Some(handler) = self.handlers.last_mut() {
+            handler.doc = Some(operation);
+        }
+        self
+    }
+
     /// Merge another Routes instance into this one.
     ///
     /// This method allows you to combine multiple Routes instances into a single
@@ -265,11 +315,53 @@ impl Routes {
                     uri: handler.uri.clone(),
                     actions: handler.actions.clone(),
                     method: handler.method.clone().layer(layer.clone()),
+                    middlewares: handler.middlewares.clone(),
+                    #[cfg(feature = "openapi")]
+                    doc: handler.doc.clone(),
                 })
                 .collect(),
         }
     }
 
+    /// Applies a middleware to the routes added so far, and only to them,
+    /// such as a stricter payload limit or a rate limit on a scope of the
+    /// app. The middleware wraps the global ones configured in
+    /// [`crate::config::Middlewares`], and is skipped when disabled.
+    ///
+    /// Combined with [`Routes::nest`], the middleware applies to a prefix:
+    ///
+    /// ```rust
+    /// use loco_rs::{
+    ///     controller::middleware::limit_payload::{DefaultBodyLimitKind, LimitPayload},
+    ///     prelude::*,
+    /// };
+    ///
+    /// async fn upload() -> Result<Response> {
+    ///     format::json("uploaded")
+    /// }
+    ///
+    /// let uploads = Routes::new()
+    ///     .add("/", post(upload))
+    ///     .with_middleware(LimitPayload {
+    ///         body_limit: DefaultBodyLimitKind::Limit(50_000_000),
+    ///     });
+    ///
+    /// Routes::new()
+    ///     .add("/health", get(|| async { "ok" }))
+    ///     .nest("/uploads", uploads);
+    /// ```
+    #[must_use]
+    pub fn with_middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: MiddlewareLayer + Send + Sync + 'static,
+    {
+        let middleware = ScopedMiddleware(Arc::new(middleware));
+        for handler in &mut self.handlers {
+            handler.middlewares.push(middleware.clone());
+        }
+        self
+    }
+
     /// Nest another Routes instance under a prefix path.
     ///
     /// This method allows you to nest a group of routes under a specific path prefix,
@@ -349,6 +441,9 @@ impl Routes {
                 uri: combined_uri,
                 method: handler.method,
                 actions: handler.actions,
+                middlewares: handler.middlewares,
+                #[cfg(feature = "openapi")]
+                doc: handler.doc,
             };
 
             self.handlers.push(new_handler);
@@ -454,6 +549,31 @@ mod tests {
         assert_eq!(users_handler.uri, "/api/users");
     }
 
+    #[test]
+    fn test_with_middleware_method() {
+        let api_routes = Routes::new()
+            .add("/users", get(users))
+            .with_middleware(crate::controller::middleware::timeout::TimeOut::default());
+
+        let app_routes = Routes::new()
+            .add("/ping", get(ping))
+            .nest("/api", api_routes)
+            .add("/health", get(ping));
+
+        let names = app_routes
+            .handlers
+            .iter()
+            .map(|handler| {
+                handler
+                    .middlewares
+                    .iter()
+                    .map(|middleware| middleware.layer().name())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![vec![], vec!["timeout_request"], vec![]]);
+    }
+
     #[test]
     fn test_merge_method() {
         // Create separate route groups
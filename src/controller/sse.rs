@@ -0,0 +1,228 @@
+//! # Server-Sent Events
+//!
+//! [`SseBroadcaster`] fans messages out to every connected client, such as
+//! the browsers showing a live dashboard. Use [`crate::controller::format::sse`]
+//! to respond with a stream of events of your own.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::controller::sse::SseBroadcaster;
+//!
+//! async fn events(State(ctx): State<AppContext>) -> Result<Response> {
+//!     let broadcaster = ctx
+//!         .shared_store
+//!         .get::<SseBroadcaster>()
+//!         .ok_or(Error::InternalServerError)?;
+//!     broadcaster.response()
+//! }
+//!
+//! async fn publish(ctx: &AppContext) -> Result<()> {
+//!     if let Some(broadcaster) = ctx.shared_store.get::<SseBroadcaster>() {
+//!         broadcaster.publish_json("orders", &serde_json::json!({ "count": 42 }))?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+use std::{convert::Infallible, time::Duration};
+
+use axum::response::{
+    sse::{Event, KeepAlive, Sse},
+    IntoResponse, Response,
+};
+use futures_util::Stream;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::Result;
+
+/// The number of messages buffered for each subscriber by default.
+pub const DEFAULT_CAPACITY: usize = 128;
+
+/// The interval of the heartbeat comments keeping idle connections open by
+/// default.
+pub const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone)]
+struct Message {
+    event: Option<String>,
+    data: String,
+}
+
+impl From<Message> for Event {
+    fn from(message: Message) -> Self {
+        let event = Self::default().data(message.data);
+        match message.event {
+            Some(name) => event.event(name),
+            None => event,
+        }
+    }
+}
+
+/// Publishes messages to all the clients subscribed to it.
+///
+/// Each subscriber buffers up to `capacity` messages. A subscriber too slow
+/// to keep up misses the oldest messages, and gets a `lagged` event telling
+/// how many, instead of slowing down the publisher and the other
+/// subscribers.
+///
+/// Cloning the broadcaster shares its subscribers.
+#[derive(Debug, Clone)]
+pub struct SseBroadcaster {
+    tx: broadcast::Sender<Message>,
+    keep_alive: Duration,
+}
+
+impl Default for SseBroadcaster {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl SseBroadcaster {
+    /// # Panics
+    ///
+    /// When `capacity` is 0.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self {
+            tx,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+        }
+    }
+
+    /// Sets the interval of the heartbeat sent to subscribers.
+    #[must_use]
+    pub fn keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = interval;
+        self
+    }
+
+    /// Publishes a message, named `event` unless it is empty, returning the
+    /// number of subscribers it was sent to.
+    pub fn publish(&self, event: &str, data: impl Into<String>) -> usize {
+        let message = Message {
+            event: (!event.is_empty()).then(|| event.to_string()),
+            data: data.into(),
+        };
+        // there may be no subscriber
+        self.tx.send(message).unwrap_or(0)
+    }
+
+    /// Publishes `data` serialized to JSON.
+    ///
+    /// # Errors
+    ///
+    /// When `data` can not be serialized.
+    pub fn publish_json<T: Serialize>(&self, event: &str, data: &T) -> Result<usize> {
+        Ok(self.publish(event, serde_json::to_string(data)?))
+    }
+
+    /// The number of connected subscribers.
+    #[must_use]
+    pub fn subscribers(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Subscribes to the messages published from now on.
+    pub fn subscribe(&self) -> impl Stream<Item = Event> + Send + 'static {
+        futures_util::stream::unfold(self.tx.subscribe(), |mut rx| async move {
+            match rx.recv().await {
+                Ok(message) => Some((Event::from(message), rx)),
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "sse subscriber lagging behind");
+                    let event = Event::default().event("lagged").data(missed.to_string());
+                    Some((event, rx))
+                }
+                Err(RecvError::Closed) => None,
+            }
+        })
+    }
+
+    /// Responds with a new subscription, kept open with heartbeats.
+    ///
+    /// # Errors
+    ///
+    /// Currently this function doesn't return any error. this is for feature
+    /// functionality
+    pub fn response(&self) -> Result<Response> {
+        Ok(sse_response(
+            self.subscribe(),
+            KeepAlive::new().interval(self.keep_alive),
+        ))
+    }
+}
+
+/// An event stream response.
+pub(crate) fn sse_response<S>(stream: S, keep_alive: KeepAlive) -> Response
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    use futures_util::StreamExt;
+
+    Sse::new(stream.map(Ok::<_, Infallible>))
+        .keep_alive(keep_alive)
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn can_fan_out_messages() {
+        let broadcaster = SseBroadcaster::new(4);
+        assert_eq!(broadcaster.publish("", "nobody"), 0);
+
+        let first = broadcaster.subscribe();
+        let second = broadcaster.clone().subscribe();
+        assert_eq!(broadcaster.subscribers(), 2);
+
+        assert_eq!(broadcaster.publish("greeting", "hello"), 2);
+        assert_eq!(
+            broadcaster
+                .publish_json("", &serde_json::json!({"n": 1}))
+                .unwrap(),
+            2
+        );
+
+        for stream in [first.boxed(), second.boxed()] {
+            let events: Vec<String> = stream
+                .take(2)
+                .map(|event| format!("{event:?}"))
+                .collect()
+                .await;
+            assert!(events[0].contains("greeting") && events[0].contains("hello"));
+            assert!(events[1].contains(r#"{\"n\":1}"#));
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_lagging_subscribers() {
+        let broadcaster = SseBroadcaster::new(2);
+        let stream = broadcaster.subscribe();
+        for n in 0..5 {
+            broadcaster.publish("", n.to_string());
+        }
+
+        let events: Vec<String> = stream
+            .take(3)
+            .map(|event| format!("{event:?}"))
+            .collect()
+            .await;
+        assert!(events[0].contains("lagged"));
+        assert!(events[1].contains('3') && events[2].contains('4'));
+    }
+
+    #[tokio::test]
+    async fn can_respond() {
+        let response = SseBroadcaster::default().response().unwrap();
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+    }
+}
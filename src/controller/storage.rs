@@ -0,0 +1,272 @@
+//! # Storage Routes
+//!
+//! Serves the presigned requests of the stores which can not presign them,
+//! such as the local filesystem: their URLs are signed by the
+//! [`crate::storage::presign::UrlSigner`] of the storage, and the routes
+//! check the signature and the expiry before reading or writing the file
+//! through the storage.
+//!
+//! # Example
+//! ```rust,ignore
+//! fn routes(_ctx: &AppContext) -> AppRoutes {
+//!     AppRoutes::with_default_routes().add_route(loco_rs::controller::storage::routes())
+//! }
+//! ```
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use axum::{
+    body::Body,
+    extract::{Path as UrlPath, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    routing::get,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+
+use crate::{
+    app::AppContext,
+    controller::{format, ErrorDetail, Routes},
+    storage::presign::{PresignOperation, PATH},
+    Error, Result,
+};
+
+/// Routes of the presigned requests, under [`PATH`]:
+/// * `GET /_storage/files/{*path}` downloads a file
+/// * `PUT /_storage/files/{*path}` uploads a file
+#[must_use]
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix(PATH)
+        .add("/{*path}", get(download).put(upload))
+}
+
+#[derive(Debug, Deserialize)]
+struct SignatureParams {
+    expires: i64,
+    signature: String,
+}
+
+/// Checks the signature of a request, without telling why it is rejected.
+fn authorize(
+    ctx: &AppContext,
+    operation: PresignOperation,
+    path: &str,
+    params: &SignatureParams,
+) -> Result<()> {
+    let signer = ctx.storage.url_signer.as_ref().ok_or(Error::NotFound)?;
+    if signer.verify(operation, path, params.expires, &params.signature) {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized(
+            "invalid or expired signature".to_string(),
+        ))
+    }
+}
+
+async fn download(
+    State(ctx): State<AppContext>,
+    UrlPath(path): UrlPath<String>,
+    Query(params): Query<SignatureParams>,
+) -> Result<Response> {
+    authorize(&ctx, PresignOperation::Get, &path, &params)?;
+    let stream = ctx.storage.download_stream(Path::new(&path)).await?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .body(stream.into_body())?)
+}
+
+fn too_large(max_size: u64) -> Error {
+    Error::CustomError(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        ErrorDetail::new(
+            "payload_too_large",
+            format!("the file is larger than {max_size} bytes"),
+        ),
+    )
+}
+
+/// The files are limited to the `uploads.max_file_size`, checked against the
+/// `Content-Length` and while streaming the body.
+async fn upload(
+    State(ctx): State<AppContext>,
+    UrlPath(path): UrlPath<String>,
+    Query(params): Query<SignatureParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response> {
+    authorize(&ctx, PresignOperation::Put, &path, &params)?;
+
+    let max_size = ctx.config.uploads.max_file_size;
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+    if content_length.is_some_and(|length| length > max_size) {
+        return Err(too_large(max_size));
+    }
+
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let mut size = 0u64;
+    let stream = body.into_data_stream().map({
+        let exceeded = exceeded.clone();
+        move |chunk| {
+            let chunk = chunk.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+            size += chunk.len() as u64;
+            if size > max_size {
+                exceeded.store(true, Ordering::Relaxed);
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "the file is too large",
+                ));
+            }
+            Ok(chunk)
+        }
+    });
+    if let Err(err) = ctx.storage.upload_stream(Path::new(&path), stream).await {
+        if exceeded.load(Ordering::Relaxed) {
+            return Err(too_large(max_size));
+        }
+        return Err(err.into());
+    }
+    format::empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        storage::{
+            drivers,
+            presign::{PresignedRequest, UrlSigner},
+            Storage,
+        },
+        tests_cfg,
+    };
+
+    #[tokio::test]
+    async fn can_authorize_presigned_requests() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        let params = |request: &PresignedRequest| SignatureParams {
+            expires: request.expires_at.timestamp(),
+            signature: request.url.rsplit_once("signature=").unwrap().1.to_string(),
+        };
+        let signer = UrlSigner::new("secret", "");
+        let request = signer.sign(
+            PresignOperation::Get,
+            Path::new("users/1.txt"),
+            Duration::from_secs(60),
+        );
+        assert!(matches!(
+            authorize(
+                &ctx,
+                PresignOperation::Get,
+                "users/1.txt",
+                &params(&request)
+            ),
+            Err(Error::NotFound)
+        ));
+
+        ctx.storage = Storage::single(drivers::mem::new())
+            .with_url_signer(signer)
+            .into();
+        assert!(authorize(
+            &ctx,
+            PresignOperation::Get,
+            "users/1.txt",
+            &params(&request)
+        )
+        .is_ok());
+        assert!(matches!(
+            authorize(
+                &ctx,
+                PresignOperation::Put,
+                "users/1.txt",
+                &params(&request)
+            ),
+            Err(Error::Unauthorized(_))
+        ));
+        assert!(matches!(
+            authorize(
+                &ctx,
+                PresignOperation::Get,
+                "users/2.txt",
+                &params(&request)
+            ),
+            Err(Error::Unauthorized(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn can_limit_the_size_of_uploads() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.config.uploads.max_file_size = 8;
+        let signer = UrlSigner::new("secret", "");
+        ctx.storage = Storage::single(drivers::mem::new())
+            .with_url_signer(signer.clone())
+            .into();
+        let put = |path: &str| {
+            let request = signer.sign(
+                PresignOperation::Put,
+                Path::new(path),
+                Duration::from_secs(60),
+            );
+            SignatureParams {
+                expires: request.expires_at.timestamp(),
+                signature: request.url.rsplit_once("signature=").unwrap().1.to_string(),
+            }
+        };
+
+        let response = upload(
+            State(ctx.clone()),
+            UrlPath("small.txt".to_string()),
+            Query(put("small.txt")),
+            HeaderMap::new(),
+            Body::from("12345678"),
+        )
+        .await;
+        assert!(response.is_ok());
+
+        let response = upload(
+            State(ctx.clone()),
+            UrlPath("large.txt".to_string()),
+            Query(put("large.txt")),
+            HeaderMap::new(),
+            Body::from("123456789"),
+        )
+        .await;
+        assert!(matches!(
+            response,
+            Err(Error::CustomError(StatusCode::PAYLOAD_TOO_LARGE, _))
+        ));
+        assert!(!ctx
+            .storage
+            .as_store("store")
+            .unwrap()
+            .exists(Path::new("large.txt"))
+            .await
+            .unwrap());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_LENGTH, "100".parse().unwrap());
+        let response = upload(
+            State(ctx.clone()),
+            UrlPath("large.txt".to_string()),
+            Query(put("large.txt")),
+            headers,
+            Body::from("1"),
+        )
+        .await;
+        assert!(matches!(
+            response,
+            Err(Error::CustomError(StatusCode::PAYLOAD_TOO_LARGE, _))
+        ));
+    }
+}
@@ -0,0 +1,285 @@
+//! # Request View Context
+//!
+//! Values shared by every view rendered for a request, such as the current
+//! user, flash messages or the locale, without passing them to each
+//! `render` call.
+//!
+//! [`ViewContextProvider`]s fill the [`ViewContext`] of each request, and
+//! handlers can add more values with the [`ViewContext`] extractor. View
+//! engines merge these values into the data of every render, the data given
+//! to `render` taking precedence.
+//!
+//! # Example
+//! ```rust
+//! use axum::http::request::Parts;
+//! use loco_rs::prelude::*;
+//! use loco_rs::controller::views::context::{ViewContext, ViewContextProvider, ViewContextProviders};
+//!
+//! struct RequestPath;
+//!
+//! #[async_trait]
+//! impl ViewContextProvider for RequestPath {
+//!     async fn provide(&self, _ctx: &AppContext, parts: &Parts, view_context: &ViewContext) -> Result<()> {
+//!         view_context.insert("path", parts.uri.path())
+//!     }
+//! }
+//!
+//! async fn index(ViewEngine(v): ViewEngine<TeraView>, view_context: ViewContext) -> Result<Response> {
+//!     view_context.insert("title", "Home")?;
+//!     // `path` and `title` are available to the view
+//!     format::render().view(&v, "home/index.html", data!({}))
+//! }
+//!
+//! // in `Hooks::after_routes`
+//! fn after_routes(router: axum::Router<AppContext>, ctx: &AppContext) -> axum::Router<AppContext> {
+//!     ViewContextProviders::new(ctx).register(RequestPath).apply(router)
+//! }
+//! ```
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{app::AppContext, Error, Result};
+
+tokio::task_local! {
+    static VIEW_CONTEXT: ViewContext;
+}
+
+/// The values shared by the views rendered for a request.
+#[derive(Debug, Clone, Default)]
+pub struct ViewContext(Arc<Mutex<Map<String, Value>>>);
+
+impl ViewContext {
+    /// Adds a value to the views of the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the value can not be serialized.
+    pub fn insert<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_value(value)?;
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(key.to_string(), value);
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+
+    /// A copy of all the values.
+    #[must_use]
+    pub fn values(&self) -> Map<String, Value> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Adds the values missing from `context`.
+    pub fn merge_into_tera(&self, context: &mut tera::Context) {
+        for (key, value) in self.values() {
+            if !context.contains_key(&key) {
+                context.insert(key, &value);
+            }
+        }
+    }
+
+    /// Adds the values missing from `data`, which must be an object (or
+    /// `null`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `data` is not an object.
+    pub fn merge_into_json(&self, data: &mut Value) -> Result<()> {
+        if data.is_null() {
+            *data = Value::Object(Map::new());
+        }
+        let Value::Object(map) = data else {
+            return Err(Error::string("view data must be an object"));
+        };
+        for (key, value) in self.values() {
+            map.entry(key).or_insert(value);
+        }
+        Ok(())
+    }
+}
+
+/// The [`ViewContext`] of the request being handled, `None` outside of the
+/// [`ViewContextProviders`] middleware.
+#[must_use]
+pub fn current() -> Option<ViewContext> {
+    VIEW_CONTEXT.try_with(Clone::clone).ok()
+}
+
+/// The Tera context of `data`, with the values of the current
+/// [`ViewContext`].
+pub(crate) fn tera_context<S: Serialize>(data: S) -> Result<tera::Context> {
+    let mut context = tera::Context::from_serialize(data)?;
+    if let Some(view_context) = current() {
+        view_context.merge_into_tera(&mut context);
+    }
+    Ok(context)
+}
+
+/// `data` with the values of the current [`ViewContext`].
+#[cfg(all(
+    any(feature = "view_minijinja", feature = "view_handlebars"),
+    not(feature = "embedded_assets")
+))]
+pub(crate) fn json_data<S: Serialize>(data: S) -> Result<Value> {
+    let mut data = serde_json::to_value(data)?;
+    if let Some(view_context) = current() {
+        view_context.merge_into_json(&mut data)?;
+    }
+    Ok(data)
+}
+
+impl<S> FromRequestParts<S> for ViewContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self> {
+        parts.extensions.get::<Self>().cloned().ok_or_else(|| {
+            tracing::error!("the `ViewContext` extractor requires `ViewContextProviders`");
+            Error::InternalServerError
+        })
+    }
+}
+
+/// Adds request-scoped values to the [`ViewContext`] of every request.
+#[async_trait]
+pub trait ViewContextProvider: Send + Sync {
+    /// # Errors
+    ///
+    /// Return an error to reject the request.
+    async fn provide(
+        &self,
+        ctx: &AppContext,
+        parts: &Parts,
+        view_context: &ViewContext,
+    ) -> Result<()>;
+}
+
+/// The middleware creating the [`ViewContext`] of each request and running
+/// the [`ViewContextProvider`]s.
+#[derive(Clone)]
+pub struct ViewContextProviders {
+    ctx: AppContext,
+    providers: Vec<Arc<dyn ViewContextProvider>>,
+}
+
+impl ViewContextProviders {
+    #[must_use]
+    pub fn new(ctx: &AppContext) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            providers: Vec::new(),
+        }
+    }
+
+    /// Adds a provider, run after the ones already added.
+    #[must_use]
+    pub fn register(mut self, provider: impl ViewContextProvider + 'static) -> Self {
+        self.providers.push(Arc::new(provider));
+        self
+    }
+
+    /// Applies the middleware to the application router.
+    pub fn apply(self, app: AXRouter<AppContext>) -> AXRouter<AppContext> {
+        app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(self),
+            view_context_middleware,
+        ))
+    }
+}
+
+async fn view_context_middleware(
+    State(providers): State<Arc<ViewContextProviders>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let view_context = ViewContext::default();
+
+    let (mut parts, body) = request.into_parts();
+    for provider in &providers.providers {
+        if let Err(err) = provider
+            .provide(&providers.ctx, &parts, &view_context)
+            .await
+        {
+            return err.into_response();
+        }
+    }
+    parts.extensions.insert(view_context.clone());
+
+    VIEW_CONTEXT
+        .scope(view_context, next.run(Request::from_parts(parts, body)))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn can_merge_values() {
+        let view_context = ViewContext::default();
+        view_context
+            .insert("user", &json!({"name": "loco"}))
+            .unwrap();
+        view_context.insert("title", "default").unwrap();
+
+        let mut data = json!({"title": "explicit"});
+        view_context.merge_into_json(&mut data).unwrap();
+        assert_eq!(data, json!({"title": "explicit", "user": {"name": "loco"}}));
+
+        let mut data = Value::Null;
+        view_context.merge_into_json(&mut data).unwrap();
+        assert_eq!(data["title"], "default");
+        assert!(view_context.merge_into_json(&mut json!([1])).is_err());
+
+        let mut context = tera::Context::new();
+        context.insert("title", "explicit");
+        view_context.merge_into_tera(&mut context);
+        assert_eq!(context.get("title"), Some(&json!("explicit")));
+        assert_eq!(context.get("user"), Some(&json!({"name": "loco"})));
+    }
+
+    #[tokio::test]
+    async fn is_available_in_scope() {
+        assert!(current().is_none());
+
+        let view_context = ViewContext::default();
+        view_context.insert("locale", "en-US").unwrap();
+        let (locale, context) = VIEW_CONTEXT
+            .scope(view_context, async {
+                (
+                    current().and_then(|c| c.get("locale")),
+                    tera_context(json!({"name": "loco"})).unwrap(),
+                )
+            })
+            .await;
+        assert_eq!(locale, Some(json!("en-US")));
+        assert_eq!(context.get("locale"), Some(&json!("en-US")));
+        assert_eq!(context.get("name"), Some(&json!("loco")));
+    }
+}
@@ -1,24 +1,43 @@
 use std::path::{Path, PathBuf};
 
-use super::tera_builtins;
-use crate::{controller::views::ViewRenderer, Error, Result};
+use bytes::Bytes;
+use futures_util::Stream;
 use serde::Serialize;
 
-#[cfg(debug_assertions)]
-use notify::{
-    event::{EventKind, ModifyKind},
-    Event, RecursiveMode, Watcher,
-};
+use super::{lint::TemplateIssue, tera_builtins};
+use crate::{controller::views::ViewRenderer, Error, Result};
 
 pub static DEFAULT_ASSET_FOLDER: &str = "assets";
 
+/// Runs on every Tera instance built from the view files
+#[cfg(debug_assertions)]
+type PostProcess = Box<dyn Fn(&mut tera::Tera) -> Result<()> + Send + Sync>;
+
 #[cfg(debug_assertions)]
 pub struct HotReloadingTeraEngine {
     pub engine: tera::Tera,
-    pub view_path: PathBuf,
+    pub view_dir: PathBuf,
     pub file_watcher: Box<dyn notify::Watcher + Send + Sync>,
     pub dirty: bool,
-    pub post_process: Box<dyn Fn(&mut tera::Tera) -> Result<()> + Send + Sync>,
+    pub post_process: PostProcess,
+}
+
+#[cfg(debug_assertions)]
+impl HotReloadingTeraEngine {
+    /// Rebuild the Tera instance from the view files
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if building fails or if the post-processing function fails
+    pub fn reload(&mut self) -> Result<()> {
+        let mut new_engine = TeraView::create_tera_instance(&self.view_dir)?;
+
+        self.post_process.as_ref()(&mut new_engine)?;
+
+        self.engine = new_engine;
+        self.dirty = false;
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -55,24 +74,36 @@ impl TeraView {
         )
     }
 
-    /// Create a new Tera instance from a directory path
+    /// Create a new Tera instance from the `.html` files of a directory
     ///
     /// # Errors
     ///
     /// This function will return an error if building fails
-    fn create_tera_instance<P: AsRef<Path>>(path: P) -> Result<tera::Tera> {
-        let path = path
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| Error::string("invalid glob"))?;
+    fn create_tera_instance(view_dir: &Path) -> Result<tera::Tera> {
+        let mut templates = Vec::new();
+        load_templates(view_dir, view_dir, &mut templates)?;
 
-        let mut tera = tera::Tera::new(path)?;
+        let mut tera = tera::Tera::default();
+        tera.add_raw_templates(templates)?;
 
         tera_builtins::filters::register_filters(&mut tera);
+        tera_builtins::functions::register_functions(&mut tera);
 
         Ok(tera)
     }
 
+    /// Load the templates of a directory like the view engine does, and lint
+    /// them for unknown filters and missing includes, which Tera only
+    /// reports when rendering
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a template can not be loaded
+    pub fn check(view_dir: &Path, extra_filters: &[String]) -> Result<Vec<TemplateIssue>> {
+        let tera = Self::create_tera_instance(view_dir)?;
+        Ok(super::lint::lint(&tera, extra_filters))
+    }
+
     /// Create a Tera view engine from a custom directory
     ///
     /// The post-processing function is also run during the call to this method.
@@ -91,10 +122,9 @@ impl TeraView {
             )));
         }
         let view_dir = path.as_ref();
-        let view_path: PathBuf = view_dir.join("**").join("*.html");
 
         // Create instance
-        let mut tera = Self::create_tera_instance(&view_path)?;
+        let mut tera = Self::create_tera_instance(view_dir)?;
 
         // Do post processing
         post_process(&mut tera)?;
@@ -104,65 +134,29 @@ impl TeraView {
         let tera = {
             let tera = std::sync::Arc::new(std::sync::Mutex::new(HotReloadingTeraEngine {
                 engine: tera,
-                view_path,
+                view_dir: view_dir.to_path_buf(),
                 file_watcher: Box::new(notify::NullWatcher),
                 dirty: false,
                 post_process: Box::new(post_process),
             }));
 
-            let tera2 = tera.clone();
-
-            // Create file watcher
-            let mut watcher = notify::recommended_watcher(move |event| {
-                use tracing::info;
-
-                let Ok(Event { kind, paths, .. }) = event else {
+            // Rebuild the shared instance as soon as the view files change,
+            // leaving it dirty if that fails so that rendering reports the error
+            let tera2 = std::sync::Arc::downgrade(&tera);
+            let watcher = super::watch::watch_views(view_dir, move || {
+                let Some(tera) = tera2.upgrade() else {
                     return;
                 };
+                let mut tera = tera.lock().unwrap();
 
-                // Only handle sub-directories and .html files
-                if !paths
-                    .iter()
-                    .all(|p| p.is_dir() || p.extension().is_some_and(|ext| ext == "html"))
-                {
-                    return;
+                tracing::warn!("Hot-reloading Tera view engine");
+                if let Err(err) = tera.reload() {
+                    tracing::error!(error = %err, "failed to reload Tera view engine");
+                    tera.dirty = true;
                 }
+            })?;
 
-                // Set dirty flag if file/directory modified
-                match kind {
-                    // Simple access, no changes
-                    EventKind::Access(_) => return,
-                    // Metadata changes, no content change
-                    EventKind::Modify(ModifyKind::Metadata(_)) => return,
-                    // Content modified
-                    EventKind::Modify(ModifyKind::Data(change)) => {
-                        info!(?paths, ?change, "View file modified")
-                    }
-                    // File renamed
-                    EventKind::Modify(ModifyKind::Name(change)) => {
-                        info!(?paths, ?change, "View file renamed")
-                    }
-                    // Other modifications
-                    EventKind::Modify(change) => {
-                        info!(?paths, ?change, "View file modified")
-                    }
-                    // File created.
-                    EventKind::Create(_) => info!(?paths, "View file created"),
-                    // File removed.
-                    EventKind::Remove(_) => info!(?paths, "View file removed"),
-                    // All other changes.
-                    change => info!(?paths, ?change, "View file changed"),
-                }
-
-                tera2.lock().unwrap().dirty = true;
-            })
-            .map_err(|_| Error::string("error creating file watcher"))?;
-
-            watcher
-                .watch(view_dir, RecursiveMode::Recursive)
-                .map_err(|_| Error::string("error watching for file changes in view directory"))?;
-
-            tera.lock().unwrap().file_watcher = Box::new(watcher);
+            tera.lock().unwrap().file_watcher = watcher;
             tera
         };
 
@@ -173,25 +167,51 @@ impl TeraView {
     }
 }
 
+/// Read the `.html` templates under `dir`, named by their path relative to
+/// `view_dir`, expanding their `cache` blocks
+///
+/// # Errors
+///
+/// This function will return an error if a template can not be read or expanded
+fn load_templates(
+    view_dir: &Path,
+    dir: &Path,
+    templates: &mut Vec<(String, String)>,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            load_templates(view_dir, &path, templates)?;
+        } else if path.extension().is_some_and(|ext| ext == "html") {
+            let name = path
+                .strip_prefix(view_dir)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let source = std::fs::read_to_string(&path)?;
+            let source = tera_builtins::cache::expand_cache_blocks(&source)
+                .map_err(|err| tera::Error::chain(format!("Failed to parse '{name}'"), err))?;
+            templates.push((name, source));
+        }
+    }
+    Ok(())
+}
+
 impl ViewRenderer for TeraView {
     fn render<S: Serialize>(&self, key: &str, data: S) -> Result<String> {
-        let context = tera::Context::from_serialize(data)?;
+        let context = super::context::tera_context(data)?;
 
         #[cfg(debug_assertions)]
         {
             let mut tera = self.0.lock().unwrap();
 
-            // Only create a new Tera instance if the view path files have changed
+            // Retry a reload which failed in the file watcher
             if tera.dirty {
                 tracing::warn!(key, "Hot-reloading Tera view engine");
 
-                tera.dirty = false;
-
-                let mut new_engine = Self::create_tera_instance(&tera.view_path)?;
-
-                tera.post_process.as_ref()(&mut new_engine)?;
-
-                tera.engine = new_engine;
+                tera.reload()?;
             }
 
             Ok(tera.engine.render(key, &context)?)
@@ -200,6 +220,39 @@ impl ViewRenderer for TeraView {
         #[cfg(not(debug_assertions))]
         Ok(self.0.render(key, &context)?)
     }
+
+    fn render_stream<S: Serialize>(
+        &self,
+        key: &str,
+        data: S,
+    ) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+        let context = super::context::tera_context(data);
+        let view = self.clone();
+        let key = key.to_string();
+
+        super::stream::spawn_render(move |writer| {
+            let context = context?;
+
+            #[cfg(debug_assertions)]
+            {
+                let mut tera = view.0.lock().unwrap();
+
+                // Retry a reload which failed in the file watcher
+                if tera.dirty {
+                    tracing::warn!(key = %key, "Hot-reloading Tera view engine");
+
+                    tera.reload()?;
+                }
+
+                tera.engine.render_to(&key, &context, writer)?;
+            }
+
+            #[cfg(not(debug_assertions))]
+            view.0.render_to(&key, &context, writer)?;
+
+            Ok(())
+        })
+    }
 }
 
 #[cfg(test)]
@@ -310,4 +363,73 @@ mod tests {
         assert!(updated_render.contains("Child Page")); // Should be the same
         assert!(updated_render.contains("Child content")); // Should be the same
     }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn hot_reload_reports_and_recovers_from_broken_templates() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add_file("template/test.html", "v1: {{foo}}")
+            .create()
+            .unwrap();
+
+        let template = tree_fs.root.join("template").join("test.html");
+        let v = TeraView::from_custom_dir(&tree_fs.root, |_| Ok(())).unwrap();
+        assert_eq!(
+            v.render("template/test.html", json!({"foo": "foo"}))
+                .unwrap(),
+            "v1: foo"
+        );
+
+        // A template which fails to compile is reported when rendering
+        std::fs::write(&template, "v2: {{foo").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert!(v.0.lock().unwrap().dirty);
+        assert!(v
+            .render("template/test.html", json!({"foo": "foo"}))
+            .is_err());
+
+        // Fixing it reloads the engine without waiting for a render
+        std::fs::write(&template, "v3: {{foo}}").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert!(!v.0.lock().unwrap().dirty);
+        assert_eq!(
+            v.render("template/test.html", json!({"foo": "foo"}))
+                .unwrap(),
+            "v3: foo"
+        );
+    }
+
+    #[tokio::test]
+    async fn can_stream_view() {
+        use futures_util::StreamExt;
+
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add_file(
+                "template/report.html",
+                "{% for i in range(end=count) %}line {{ i }}\n{% endfor %}",
+            )
+            .create()
+            .unwrap();
+
+        let v = TeraView::from_custom_dir(&tree_fs.root, |_| Ok(())).unwrap();
+
+        let chunks = v
+            .render_stream("template/report.html", json!({"count": 5000}))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert!(chunks.len() > 1);
+        assert_eq!(
+            chunks.concat(),
+            v.render("template/report.html", json!({"count": 5000}))
+                .unwrap()
+                .as_bytes()
+        );
+
+        let mut failing = std::pin::pin!(v.render_stream("template/none.html", json!({})));
+        assert!(failing.next().await.unwrap().is_err());
+        assert!(failing.next().await.is_none());
+    }
 }
@@ -56,6 +56,7 @@ impl TeraView {
         Self::load_templates_into_tera(&mut tera)?;
 
         tera_builtins::filters::register_filters(&mut tera);
+        tera_builtins::functions::register_functions(&mut tera);
         let ctx = tera::Context::default();
 
         Ok(Self {
@@ -94,7 +95,8 @@ impl TeraView {
         // Add all templates to Tera
         for (name, content) in templates {
             tracing::debug!("Adding template '{}' to Tera", name);
-            if let Err(e) = tera.add_raw_template(&name, content) {
+            let content = tera_builtins::cache::expand_cache_blocks(content)?;
+            if let Err(e) = tera.add_raw_template(&name, &content) {
                 tracing::error!("Failed to add template '{}': {}", name, e);
                 return Err(e.into());
             }
@@ -112,7 +114,7 @@ impl TeraView {
 
 impl ViewRenderer for TeraView {
     fn render<S: Serialize>(&self, key: &str, data: S) -> Result<String> {
-        let context = tera::Context::from_serialize(data)?;
+        let context = super::context::tera_context(data)?;
 
         // Try to render the requested template
         match self.tera.render(key, &context) {
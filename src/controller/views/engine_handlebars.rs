@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use futures_util::Stream;
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use serde::Serialize;
+
+use super::{engine::DEFAULT_ASSET_FOLDER, tera_builtins};
+use crate::{controller::views::ViewRenderer, Error, Result};
+
+/// The extension of the Handlebars templates of the views directory.
+pub const TEMPLATE_EXTENSION: &str = "hbs";
+
+#[cfg(debug_assertions)]
+type PostProcess = Box<dyn Fn(&mut Handlebars<'static>) -> Result<()> + Send + Sync>;
+
+#[cfg(debug_assertions)]
+pub struct HotReloadingHandlebarsEngine {
+    pub engine: Handlebars<'static>,
+    pub view_dir: PathBuf,
+    pub file_watcher: Box<dyn notify::Watcher + Send + Sync>,
+    pub dirty: bool,
+    pub post_process: PostProcess,
+}
+
+#[cfg(debug_assertions)]
+impl HotReloadingHandlebarsEngine {
+    /// Rebuild the registry from the view files
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if building fails or if the post-processing function fails
+    pub fn reload(&mut self) -> Result<()> {
+        let mut new_engine = HandlebarsView::create_registry(&self.view_dir)?;
+
+        self.post_process.as_ref()(&mut new_engine)?;
+
+        self.engine = new_engine;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// A view engine rendering Handlebars templates, for apps bringing existing
+/// Handlebars views along.
+///
+/// Templates are the `.hbs` files of the views directory, named by their
+/// path relative to it like `home/index.hbs`, which is also the name used
+/// by partials: `{{> partials/header.hbs}}`. Helpers are registered in the
+/// post-processing function.
+///
+/// The loco builtin filters and functions are registered as helpers taking
+/// their options as hash arguments, such as
+/// `{{asset_url path="css/app.css"}}`. The output of `markdown`, `sanitize`
+/// and `json_encode_safe` must not be escaped again, with triple-stash:
+/// `{{{markdown post.body}}}`.
+#[derive(Clone)]
+pub struct HandlebarsView(
+    #[cfg(debug_assertions)] std::sync::Arc<std::sync::Mutex<HotReloadingHandlebarsEngine>>,
+    #[cfg(not(debug_assertions))] std::sync::Arc<Handlebars<'static>>,
+);
+
+impl HandlebarsView {
+    /// Create a Handlebars view engine
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if building fails
+    pub fn build() -> Result<Self> {
+        Self::from_custom_dir(&PathBuf::from(DEFAULT_ASSET_FOLDER).join("views"), |_| {
+            Ok(())
+        })
+    }
+
+    /// Create a Handlebars view engine with a post-processing function for subsequent instantiation.
+    ///
+    /// The post-processing function is also run during the call to this method.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if building fails or if the post-processing function fails
+    pub fn build_with_post_process(
+        post_process: impl Fn(&mut Handlebars<'static>) -> Result<()> + Send + Sync + 'static,
+    ) -> Result<Self> {
+        Self::from_custom_dir(
+            &PathBuf::from(DEFAULT_ASSET_FOLDER).join("views"),
+            post_process,
+        )
+    }
+
+    /// Create a new Handlebars registry from the `.hbs` files of a directory
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a template can not be read or parsed
+    fn create_registry(view_dir: &Path) -> Result<Handlebars<'static>> {
+        let mut registry = Handlebars::new();
+        register_templates(&mut registry, view_dir, view_dir)?;
+
+        register_helpers(&mut registry);
+
+        Ok(registry)
+    }
+
+    /// Create a Handlebars view engine from a custom directory
+    ///
+    /// The post-processing function is also run during the call to this method.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if building fails or if the post-processing function fails
+    pub fn from_custom_dir<P: AsRef<Path>>(
+        path: &P,
+        post_process: impl Fn(&mut Handlebars<'static>) -> Result<()> + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let view_dir = path.as_ref();
+        if !view_dir.exists() {
+            return Err(Error::string(&format!(
+                "missing views directory: `{}`",
+                view_dir.display()
+            )));
+        }
+
+        // Create instance
+        let mut registry = Self::create_registry(view_dir)?;
+
+        // Do post processing
+        post_process(&mut registry)?;
+
+        // Enable hot-reloading in debug build
+        #[cfg(debug_assertions)]
+        let registry = {
+            let registry =
+                std::sync::Arc::new(std::sync::Mutex::new(HotReloadingHandlebarsEngine {
+                    engine: registry,
+                    view_dir: view_dir.to_path_buf(),
+                    file_watcher: Box::new(notify::NullWatcher),
+                    dirty: false,
+                    post_process: Box::new(post_process),
+                }));
+
+            let registry2 = std::sync::Arc::downgrade(&registry);
+            let watcher = super::watch::watch_views(view_dir, move || {
+                let Some(registry) = registry2.upgrade() else {
+                    return;
+                };
+                let mut registry = registry.lock().unwrap();
+
+                tracing::warn!("Hot-reloading Handlebars view engine");
+                if let Err(err) = registry.reload() {
+                    tracing::error!(error = %err, "failed to reload Handlebars view engine");
+                    registry.dirty = true;
+                }
+            })?;
+
+            registry.lock().unwrap().file_watcher = watcher;
+            registry
+        };
+
+        #[cfg(not(debug_assertions))]
+        let registry = std::sync::Arc::new(registry);
+
+        Ok(Self(registry))
+    }
+}
+
+/// Register the `.hbs` templates under `dir`, named by their path relative
+/// to `view_dir`
+///
+/// # Errors
+///
+/// This function will return an error if a template can not be read or parsed
+fn register_templates(
+    registry: &mut Handlebars<'static>,
+    view_dir: &Path,
+    dir: &Path,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            register_templates(registry, view_dir, &path)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == TEMPLATE_EXTENSION)
+        {
+            let name = path
+                .strip_prefix(view_dir)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            registry.register_template_string(&name, std::fs::read_to_string(&path)?)?;
+        }
+    }
+    Ok(())
+}
+
+impl ViewRenderer for HandlebarsView {
+    fn render<S: Serialize>(&self, key: &str, data: S) -> Result<String> {
+        let data = super::context::json_data(data)?;
+
+        #[cfg(debug_assertions)]
+        {
+            let mut registry = self.0.lock().unwrap();
+
+            // Retry a reload which failed in the file watcher
+            if registry.dirty {
+                tracing::warn!(key, "Hot-reloading Handlebars view engine");
+
+                registry.reload()?;
+            }
+
+            Ok(registry.engine.render(key, &data)?)
+        }
+
+        #[cfg(not(debug_assertions))]
+        Ok(self.0.render(key, &data)?)
+    }
+
+    fn render_stream<S: Serialize>(
+        &self,
+        key: &str,
+        data: S,
+    ) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+        let data = super::context::json_data(data);
+        let view = self.clone();
+        let key = key.to_string();
+
+        super::stream::spawn_render(move |writer| {
+            let data = data?;
+
+            #[cfg(debug_assertions)]
+            {
+                let mut registry = view.0.lock().unwrap();
+
+                // Retry a reload which failed in the file watcher
+                if registry.dirty {
+                    tracing::warn!(key = %key, "Hot-reloading Handlebars view engine");
+
+                    registry.reload()?;
+                }
+
+                registry.engine.render_to_write(&key, &data, writer)?;
+            }
+
+            #[cfg(not(debug_assertions))]
+            view.0.render_to_write(&key, &data, writer)?;
+
+            Ok(())
+        })
+    }
+}
+
+/// Registers the loco builtin filters and functions as helpers, sharing
+/// their implementation with the Tera ones.
+fn register_helpers(registry: &mut Handlebars<'static>) {
+    use tera_builtins::{filters, functions};
+
+    for (name, filter) in [
+        (
+            "number_with_delimiter",
+            filters::number::number_with_delimiter as TeraFilter,
+        ),
+        (
+            "number_to_human_size",
+            filters::number::number_to_human_size,
+        ),
+        (
+            "number_to_percentage",
+            filters::number::number_to_percentage,
+        ),
+        ("markdown", filters::html::markdown),
+        ("sanitize", filters::html::sanitize),
+        ("json_encode_safe", filters::html::json_encode_safe),
+    ] {
+        registry.register_helper(name, Box::new(TeraFilterHelper(filter)));
+    }
+
+    registry.register_helper(
+        "asset_url",
+        Box::new(TeraFunctionHelper(functions::asset_url::AssetUrl::default())),
+    );
+    registry.register_helper(
+        "csrf_token",
+        Box::new(TeraFunctionHelper(functions::csrf::csrf_token)),
+    );
+}
+
+type TeraFilter =
+    fn(&serde_json::Value, &HashMap<String, serde_json::Value>) -> tera::Result<serde_json::Value>;
+
+/// The hash arguments of a helper call.
+fn hash_args(h: &Helper<'_>) -> HashMap<String, serde_json::Value> {
+    h.hash()
+        .iter()
+        .map(|(key, value)| ((*key).to_string(), value.value().clone()))
+        .collect()
+}
+
+fn render_error(err: &tera::Error) -> RenderError {
+    RenderErrorReason::Other(err.to_string()).into()
+}
+
+/// A Tera filter used as a helper, filtering its first parameter:
+/// `{{number_with_delimiter count}}`.
+struct TeraFilterHelper(TeraFilter);
+
+impl HelperDef for TeraFilterHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> std::result::Result<ScopedJson<'rc>, RenderError> {
+        let value = h
+            .param(0)
+            .map_or(serde_json::Value::Null, |param| param.value().clone());
+        (self.0)(&value, &hash_args(h))
+            .map(ScopedJson::Derived)
+            .map_err(|err| render_error(&err))
+    }
+}
+
+/// A Tera function used as a helper: `{{asset_url path="css/app.css"}}`.
+struct TeraFunctionHelper<F>(F);
+
+impl<F: tera::Function> HelperDef for TeraFunctionHelper<F> {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> std::result::Result<ScopedJson<'rc>, RenderError> {
+        self.0
+            .call(&hash_args(h))
+            .map(ScopedJson::Derived)
+            .map_err(|err| render_error(&err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn can_render_view() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add_file("template/test.hbs", "generate test.hbs file: {{foo}}")
+            .add_file(
+                "template/page.hbs",
+                "{{> template/header.hbs}}<main>{{bar}}</main>",
+            )
+            .add_file("template/header.hbs", "<h1>{{title}}</h1>")
+            .add_file("template/ignored.html", "{{{{broken")
+            .create()
+            .unwrap();
+
+        let v = HandlebarsView::from_custom_dir(&tree_fs.root, |_| Ok(())).unwrap();
+
+        assert_eq!(
+            v.render("template/test.hbs", json!({"foo": "foo-txt"}))
+                .unwrap(),
+            "generate test.hbs file: foo-txt"
+        );
+        assert_eq!(
+            v.render(
+                "template/page.hbs",
+                json!({"title": "<loco>", "bar": "bar-txt"})
+            )
+            .unwrap(),
+            "<h1>&lt;loco&gt;</h1><main>bar-txt</main>"
+        );
+        assert!(v.render("template/none.hbs", json!({})).is_err());
+    }
+
+    #[test]
+    fn can_use_builtins_and_post_process() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add_file(
+                "template/test.hbs",
+                "{{number_with_delimiter count}} {{shout name}} {{{markdown body}}}",
+            )
+            .create()
+            .unwrap();
+
+        let v = HandlebarsView::from_custom_dir(&tree_fs.root, |registry| {
+            registry.register_helper(
+                "shout",
+                Box::new(
+                    |h: &Helper<'_>,
+                     _: &Handlebars<'_>,
+                     _: &Context,
+                     _: &mut RenderContext<'_, '_>,
+                     out: &mut dyn handlebars::Output|
+                     -> handlebars::HelperResult {
+                        let value = h.param(0).and_then(|p| p.value().as_str()).unwrap_or("");
+                        out.write(&value.to_uppercase())?;
+                        Ok(())
+                    },
+                ),
+            );
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            v.render(
+                "template/test.hbs",
+                json!({"count": 1_234_567, "name": "loco", "body": "*hi*"})
+            )
+            .unwrap(),
+            "1,234,567 LOCO <p><em>hi</em></p>\n"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::{borrow::Cow, ops::Deref};
+
+use axum::{extract::FromRequestParts, http::request::Parts, Extension};
+
+use crate::{app::AppContext, Error};
+
+/// The layout wrapping the views of a controller, passed to
+/// [`crate::controller::format::RenderBuilder::view_with_layout`].
+///
+/// The layout of a group of routes is set with [`Layout::layer`], and falls
+/// back to the `views.layout` configuration.
+///
+/// # Example
+/// ```rust
+/// use loco_rs::prelude::*;
+///
+/// async fn index(ViewEngine(v): ViewEngine<TeraView>, layout: Layout) -> Result<Response> {
+///     format::render().view_with_layout(&v, "admin/index.html", &layout, data!({}))
+/// }
+///
+/// fn routes() -> Routes {
+///     Routes::new()
+///         .prefix("admin")
+///         .add("/", get(index))
+///         .layer(Layout::new("layouts/admin.html").layer())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout(pub Cow<'static, str>);
+
+impl Layout {
+    #[must_use]
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self(name.into())
+    }
+
+    /// Returns a layer setting this layout for every request of the routes
+    /// it wraps.
+    pub fn layer(self) -> Extension<Self> {
+        Extension(self)
+    }
+}
+
+impl Deref for Layout {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromRequestParts<AppContext> for Layout {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppContext,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(layout) = parts.extensions.get::<Self>() {
+            return Ok(layout.clone());
+        }
+        state
+            .config
+            .views
+            .layout
+            .clone()
+            .map(Self::new)
+            .ok_or_else(|| {
+                tracing::error!("no layout set for the route and no `views.layout` configured");
+                Error::InternalServerError
+            })
+    }
+}
@@ -0,0 +1,219 @@
+//! # Template Lint
+//!
+//! Tera reports syntax errors, missing parent templates and missing macro
+//! files when templates are loaded, but unknown filters and missing
+//! includes only when a template renders them. This pass walks the
+//! templates of a Tera instance to report them before deployment, and backs
+//! `cargo loco views check`.
+use std::{collections::HashSet, fmt};
+
+use tera::ast::{Expr, ExprVal, FunctionCall, Node};
+
+/// The filters registered outside of the Tera instance built by loco, by
+/// the `TeraView` post-processing of apps.
+const POST_PROCESS_FILTERS: &[&str] = &["cache_fragment"];
+
+/// A problem found in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateIssue {
+    pub template: String,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.template, self.message)
+    }
+}
+
+/// Lints the templates of `tera`, accepting `extra_filters` as registered.
+#[must_use]
+pub fn lint(tera: &tera::Tera, extra_filters: &[String]) -> Vec<TemplateIssue> {
+    let mut filters: HashSet<&str> = tera.filters.keys().map(String::as_str).collect();
+    filters.extend(POST_PROCESS_FILTERS);
+    filters.extend(extra_filters.iter().map(String::as_str));
+
+    let mut names: Vec<&String> = tera.templates.keys().collect();
+    names.sort();
+
+    let mut issues = Vec::new();
+    for name in names {
+        let mut linter = Linter {
+            tera,
+            filters: &filters,
+            template: name,
+            issues: &mut issues,
+        };
+        linter.nodes(&tera.templates[name].ast);
+    }
+    issues
+}
+
+struct Linter<'a> {
+    tera: &'a tera::Tera,
+    filters: &'a HashSet<&'a str>,
+    template: &'a str,
+    issues: &'a mut Vec<TemplateIssue>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, message: String) {
+        self.issues.push(TemplateIssue {
+            template: self.template.to_string(),
+            message,
+        });
+    }
+
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::VariableBlock(_, expr) => self.expr(expr),
+            Node::MacroDefinition(_, definition, _) => {
+                for default in definition.args.values().flatten() {
+                    self.expr(default);
+                }
+                self.nodes(&definition.body);
+            }
+            Node::Include(_, includes, ignore_missing)
+                if !ignore_missing
+                    && !includes
+                        .iter()
+                        .any(|include| self.tera.templates.contains_key(include)) =>
+            {
+                self.report(format!(
+                    "included template not found: `{}`",
+                    includes.join("`, `")
+                ));
+            }
+            Node::Set(_, set) => self.expr(&set.value),
+            Node::FilterSection(_, section, _) => {
+                self.call(&section.filter, true);
+                self.nodes(&section.body);
+            }
+            Node::Block(_, block, _) => self.nodes(&block.body),
+            Node::Forloop(_, forloop, _) => {
+                self.expr(&forloop.container);
+                self.nodes(&forloop.body);
+                if let Some(body) = &forloop.empty_body {
+                    self.nodes(body);
+                }
+            }
+            Node::If(conditions, _) => {
+                for (_, condition, body) in &conditions.conditions {
+                    self.expr(condition);
+                    self.nodes(body);
+                }
+                if let Some((_, body)) = &conditions.otherwise {
+                    self.nodes(body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        self.expr_val(&expr.val);
+        for filter in &expr.filters {
+            self.call(filter, true);
+        }
+    }
+
+    fn expr_val(&mut self, val: &ExprVal) {
+        match val {
+            ExprVal::Math(math) => {
+                self.expr(&math.lhs);
+                self.expr(&math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                self.expr(&logic.lhs);
+                self.expr(&logic.rhs);
+            }
+            ExprVal::Test(test) => {
+                for arg in &test.args {
+                    self.expr(arg);
+                }
+            }
+            ExprVal::MacroCall(call) => {
+                for arg in call.args.values() {
+                    self.expr(arg);
+                }
+            }
+            ExprVal::FunctionCall(call) => self.call(call, false),
+            ExprVal::Array(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            ExprVal::StringConcat(concat) => {
+                for value in &concat.values {
+                    self.expr_val(value);
+                }
+            }
+            ExprVal::In(in_expr) => {
+                self.expr(&in_expr.lhs);
+                self.expr(&in_expr.rhs);
+            }
+            _ => {}
+        }
+    }
+
+    fn call(&mut self, call: &FunctionCall, is_filter: bool) {
+        if is_filter && !self.filters.contains(call.name.as_str()) {
+            self.report(format!("unknown filter: `{}`", call.name));
+        }
+        for arg in call.args.values() {
+            self.expr(arg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tera(templates: &[(&str, &str)]) -> tera::Tera {
+        let mut tera = tera::Tera::default();
+        tera.add_raw_templates(templates.to_vec()).unwrap();
+        tera
+    }
+
+    #[test]
+    fn accepts_valid_templates() {
+        let tera = tera(&[
+            ("header.html", "<h1>{{ title | upper }}</h1>"),
+            (
+                "index.html",
+                r#"{% include "header.html" %}{% include "missing.html" ignore missing %}
+{% for item in items | reverse %}{{ item | cache_fragment(key="x") }}{% endfor %}"#,
+            ),
+        ]);
+        assert_eq!(lint(&tera, &[]), vec![]);
+    }
+
+    #[test]
+    fn reports_unknown_filters_and_missing_includes() {
+        let tera = tera(&[(
+            "index.html",
+            r#"{% include "missing.html" %}
+{% if show %}{{ name | shout }}{% else %}{{ range(end=3) | join(sep=name | whisper) }}{% endif %}
+{% filter custom %}text{% endfilter %}"#,
+        )]);
+        let issues: Vec<String> = lint(&tera, &["custom".to_string()])
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                "index.html: included template not found: `missing.html`",
+                "index.html: unknown filter: `shout`",
+                "index.html: unknown filter: `whisper`",
+            ]
+        );
+    }
+}
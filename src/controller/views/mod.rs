@@ -9,10 +9,32 @@ pub mod engine;
 #[cfg(not(feature = "embedded_assets"))]
 pub use engine as engines;
 
+#[cfg(all(feature = "view_minijinja", not(feature = "embedded_assets")))]
+pub mod engine_minijinja;
+#[cfg(all(feature = "view_minijinja", not(feature = "embedded_assets")))]
+pub use engine_minijinja::MiniJinjaView;
+#[cfg(all(feature = "view_handlebars", not(feature = "embedded_assets")))]
+pub mod engine_handlebars;
+#[cfg(all(feature = "view_handlebars", not(feature = "embedded_assets")))]
+pub use engine_handlebars::HandlebarsView;
+#[cfg(all(debug_assertions, not(feature = "embedded_assets")))]
+mod watch;
+
+pub mod context;
+pub mod layout;
+#[cfg(not(feature = "embedded_assets"))]
+pub mod lint;
+mod stream;
+
+pub use context::ViewContext;
+pub use layout::Layout;
+
 use axum::{extract::FromRequestParts, http::request::Parts, Extension};
+use bytes::Bytes;
+use futures_util::Stream;
 use serde::Serialize;
 pub mod tera_builtins;
-use crate::Result;
+use crate::{Error, Result};
 
 #[cfg(feature = "with-db")]
 pub mod pagination;
@@ -24,6 +46,48 @@ pub trait ViewRenderer {
     ///
     /// This function will return an error if render fails
     fn render<S: Serialize>(&self, key: &str, data: S) -> Result<String>;
+
+    /// Render the view template located by `key` inside the `layout` view
+    /// template, which receives the rendered view as its `content`
+    /// variable, along with `data`.
+    ///
+    /// A layout rendering the `content` variable in its `content` block can
+    /// also be extended by views:
+    ///
+    /// ```jinja
+    /// <main>{% block content %}{{ content | safe }}{% endblock %}</main>
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if render fails, or if `data` is
+    /// not an object
+    fn render_with_layout<S: Serialize>(&self, key: &str, layout: &str, data: S) -> Result<String> {
+        let mut data = serde_json::to_value(data)?;
+        let content = self.render(key, &data)?;
+        match &mut data {
+            serde_json::Value::Object(map) => {
+                map.insert("content".to_string(), serde_json::Value::String(content));
+            }
+            serde_json::Value::Null => data = serde_json::json!({ "content": content }),
+            _ => return Err(Error::string("layout data must be an object")),
+        }
+        self.render(layout, data)
+    }
+
+    /// Render a view template located by `key` as a stream of chunks, sent
+    /// to the client while the rest of the view renders. Use it with
+    /// [`crate::controller::format::html_stream`] for very large pages.
+    ///
+    /// The default implementation renders the whole view as a single chunk.
+    fn render_stream<S: Serialize>(
+        &self,
+        key: &str,
+        data: S,
+    ) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+        let rendered = self.render(key, data).map(Bytes::from);
+        futures_util::stream::once(std::future::ready(rendered))
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -94,3 +158,19 @@ where
         Ok(tl)
     }
 }
+
+/// Escapes text for HTML content and attributes.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
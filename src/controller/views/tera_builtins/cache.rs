@@ -0,0 +1,345 @@
+//! # Fragment Caching
+//!
+//! Wrap an expensive part of a template, such as a navigation menu or a
+//! product card, in a `cache` block to render it once and serve it from the
+//! application cache until it expires or one of its tags is invalidated:
+//!
+//! ```jinja
+//! {% cache "product_" ~ product.id, ttl=60, tags=["products"] %}
+//!   ...
+//! {% endcache %}
+//! ```
+//!
+//! The first argument is the cache key, `ttl` (in seconds) and `tags` are
+//! optional.
+//!
+//! Tera does not support custom tags, so `cache` blocks are expanded by
+//! [`expand_cache_blocks`] when the views are loaded, into a
+//! `cached_fragment()` lookup and a `cache_fragment` filter section. Both are
+//! registered by [`FragmentCache::register`], for example in the view engine
+//! initializer:
+//!
+//! ```rust,ignore
+//! let fragments = FragmentCache::new(ctx.cache.clone());
+//! let tera_engine = engines::TeraView::build_with_post_process(move |tera| {
+//!     fragments.register(tera);
+//!     Ok(())
+//! })?;
+//! ```
+//!
+//! Template functions are synchronous while the cache is not: fragments are
+//! only cached on a multi-threaded Tokio runtime, and rendered every time
+//! elsewhere.
+#![allow(clippy::implicit_hasher)]
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use regex::Regex;
+use serde_json::value::Value;
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::cache::{Cache, CacheResult};
+
+const KEY_PREFIX: &str = "fragment:";
+const TAG_PREFIX: &str = "fragment_tag:";
+
+static CACHE_TAG: OnceLock<Regex> = OnceLock::new();
+
+/// Stores rendered template fragments in the application cache.
+#[derive(Clone)]
+pub struct FragmentCache {
+    cache: Arc<Cache>,
+}
+
+impl FragmentCache {
+    #[must_use]
+    pub fn new(cache: Arc<Cache>) -> Self {
+        Self { cache }
+    }
+
+    /// Registers the `cached_fragment` function and the `cache_fragment`
+    /// filter used by `cache` blocks.
+    pub fn register(&self, tera: &mut tera::Tera) {
+        let fragments = self.clone();
+        tera.register_function("cached_fragment", move |args: &HashMap<String, Value>| {
+            fragments.cached_fragment(args)
+        });
+
+        let fragments = self.clone();
+        tera.register_filter(
+            "cache_fragment",
+            move |value: &Value, args: &HashMap<String, Value>| {
+                fragments.cache_fragment(value, args)
+            },
+        );
+    }
+
+    /// Removes the fragment cached under `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the cache fails.
+    pub async fn invalidate(&self, key: &str) -> CacheResult<()> {
+        self.cache.remove(&format!("{KEY_PREFIX}{key}")).await
+    }
+
+    /// Removes all the fragments cached with `tag`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the cache fails.
+    pub async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        let tag_key = format!("{TAG_PREFIX}{tag}");
+        let keys: Vec<String> = self.cache.get(&tag_key).await?.unwrap_or_default();
+        for key in keys {
+            self.invalidate(&key).await?;
+        }
+        self.cache.remove(&tag_key).await
+    }
+
+    /// Stores a rendered fragment under `key`, and indexes it by `tags`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the cache fails.
+    pub async fn insert(
+        &self,
+        key: &str,
+        fragment: &str,
+        ttl: Option<Duration>,
+        tags: &[String],
+    ) -> CacheResult<()> {
+        let cache_key = format!("{KEY_PREFIX}{key}");
+        match ttl {
+            Some(ttl) => {
+                self.cache
+                    .insert_with_expiry(&cache_key, fragment, ttl)
+                    .await?;
+            }
+            None => self.cache.insert(&cache_key, fragment).await?,
+        }
+
+        for tag in tags {
+            let tag_key = format!("{TAG_PREFIX}{tag}");
+            let mut keys: Vec<String> = self.cache.get(&tag_key).await?.unwrap_or_default();
+            if !keys.iter().any(|k| k == key) {
+                keys.push(key.to_string());
+                self.cache.insert(&tag_key, &keys).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the fragment cached under `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the cache fails.
+    pub async fn get(&self, key: &str) -> CacheResult<Option<String>> {
+        self.cache.get(&format!("{KEY_PREFIX}{key}")).await
+    }
+
+    fn cached_fragment(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let key = fragment_key(args)?;
+        let fragment = block_on(self.get(&key))
+            .transpose()
+            .map_err(|err| tera::Error::msg(format!("fragment cache: {err}")))?
+            .flatten();
+        Ok(Value::String(fragment.unwrap_or_default()))
+    }
+
+    fn cache_fragment(&self, value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let key = fragment_key(args)?;
+        let fragment = value
+            .as_str()
+            .ok_or_else(|| tera::Error::msg("`cache_fragment` expects a rendered fragment"))?;
+        let ttl = args
+            .get("ttl")
+            .map(|ttl| {
+                ttl.as_u64()
+                    .map(Duration::from_secs)
+                    .ok_or_else(|| tera::Error::msg("`ttl` must be a number of seconds"))
+            })
+            .transpose()?;
+        let tags = args
+            .get("tags")
+            .map(|tags| {
+                serde_json::from_value::<Vec<String>>(tags.clone())
+                    .map_err(|_| tera::Error::msg("`tags` must be a list of strings"))
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        block_on(self.insert(&key, fragment, ttl, &tags))
+            .transpose()
+            .map_err(|err| tera::Error::msg(format!("fragment cache: {err}")))?;
+        Ok(value.clone())
+    }
+}
+
+fn fragment_key(args: &HashMap<String, Value>) -> tera::Result<String> {
+    match args.get("key") {
+        Some(Value::String(key)) => Ok(key.clone()),
+        Some(Value::Number(key)) => Ok(key.to_string()),
+        _ => Err(tera::Error::msg("a fragment cache key is required")),
+    }
+}
+
+/// Runs a cache operation from a synchronous template function, when the
+/// current runtime allows blocking on it.
+fn block_on<F: Future>(future: F) -> Option<F::Output> {
+    let handle = Handle::try_current().ok()?;
+    if handle.runtime_flavor() != RuntimeFlavor::MultiThread {
+        return None;
+    }
+    Some(tokio::task::block_in_place(|| handle.block_on(future)))
+}
+
+/// Expands the `{% cache key, ttl=.., tags=[..] %}...{% endcache %}` blocks of
+/// a template into Tera syntax.
+///
+/// # Errors
+///
+/// Returns an error when a `cache` block has no key or is not closed.
+pub fn expand_cache_blocks(source: &str) -> tera::Result<String> {
+    let tag = CACHE_TAG.get_or_init(|| {
+        Regex::new(r"(?s)\{%(-?)\s*(cache|endcache)\b(.*?)(-?)%\}").expect("valid cache tag regex")
+    });
+
+    let mut expanded = String::with_capacity(source.len());
+    let mut last = 0;
+    let mut opened = 0;
+    let mut depth = 0usize;
+    for captures in tag.captures_iter(source) {
+        let (Some(all), Some(name)) = (captures.get(0), captures.get(2)) else {
+            continue;
+        };
+        let trim_left = captures.get(1).map_or("", |m| m.as_str());
+        let args = captures.get(3).map_or("", |m| m.as_str()).trim();
+        let trim_right = captures.get(4).map_or("", |m| m.as_str());
+
+        expanded.push_str(&source[last..all.start()]);
+        last = all.end();
+
+        if name.as_str() == "cache" {
+            let (key, options) = split_key(args);
+            if key.is_empty() {
+                return Err(tera::Error::msg("`cache` block requires a key"));
+            }
+            let var = format!("__fragment_{opened}");
+            opened += 1;
+            depth += 1;
+            expanded.push_str(&format!(
+                "{{%{trim_left} set {var} = cached_fragment(key={key}) %}}\
+                 {{% if {var} %}}{{{{ {var} | safe }}}}{{% else %}}\
+                 {{% filter cache_fragment(key={key}{options}) {trim_right}%}}"
+            ));
+        } else {
+            if depth == 0 {
+                return Err(tera::Error::msg("`endcache` without a `cache` block"));
+            }
+            depth -= 1;
+            expanded.push_str(&format!(
+                "{{%{trim_left} endfilter %}}{{% endif {trim_right}%}}"
+            ));
+        }
+    }
+
+    if depth > 0 {
+        return Err(tera::Error::msg("unclosed `cache` block"));
+    }
+
+    expanded.push_str(&source[last..]);
+    Ok(expanded)
+}
+
+/// Splits the arguments of a `cache` tag into the key expression and the
+/// remaining options, at the first top-level comma.
+fn split_key(args: &str) -> (&str, &str) {
+    let mut nesting = 0i32;
+    let mut quote = None;
+    for (idx, c) in args.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '(' | '[' | '{') => nesting += 1,
+            (None, ')' | ']' | '}') => nesting -= 1,
+            (None, ',') if nesting == 0 => return (args[..idx].trim(), &args[idx..]),
+            _ => {}
+        }
+    }
+    (args, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn can_expand_cache_blocks() {
+        assert_eq!(
+            expand_cache_blocks(r#"<nav>{% cache "nav", ttl=60 %}menu{% endcache %}</nav>"#)
+                .unwrap(),
+            "<nav>{% set __fragment_0 = cached_fragment(key=\"nav\") %}\
+             {% if __fragment_0 %}{{ __fragment_0 | safe }}{% else %}\
+             {% filter cache_fragment(key=\"nav\", ttl=60) %}menu\
+             {% endfilter %}{% endif %}</nav>"
+        );
+        assert_eq!(
+            expand_cache_blocks("{%- cache \"a\" ~ b -%}x{%- endcache -%}").unwrap(),
+            "{%- set __fragment_0 = cached_fragment(key=\"a\" ~ b) %}\
+             {% if __fragment_0 %}{{ __fragment_0 | safe }}{% else %}\
+             {% filter cache_fragment(key=\"a\" ~ b) -%}x{%- endfilter %}{% endif -%}"
+        );
+        assert_eq!(
+            split_key(r#""a,b", tags=["x", "y"]"#),
+            (r#""a,b""#, r#", tags=["x", "y"]"#)
+        );
+        assert!(expand_cache_blocks("{% cache %}x{% endcache %}").is_err());
+        assert!(expand_cache_blocks("{% cache \"a\" %}x").is_err());
+        assert!(expand_cache_blocks("x{% endcache %}").is_err());
+    }
+
+    #[cfg(feature = "cache_inmem")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn can_cache_fragments() {
+        use crate::{cache::drivers::inmem, config::InMemCacheConfig};
+
+        let cache = Arc::new(Cache::new(
+            inmem::new(&InMemCacheConfig { max_capacity: 100 }).driver,
+        ));
+        let fragments = FragmentCache::new(cache);
+
+        let mut tera = tera::Tera::default();
+        fragments.register(&mut tera);
+        tera.add_raw_template(
+            "nav.html",
+            &expand_cache_blocks(r#"{% cache "nav", tags=["menu"] %}{{ version }}{% endcache %}"#)
+                .unwrap(),
+        )
+        .unwrap();
+
+        let render = |version: i32| {
+            tera.render(
+                "nav.html",
+                &tera::Context::from_serialize(json!({ "version": version })).unwrap(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(render(1), "1");
+        assert_eq!(render(2), "1");
+        assert_eq!(fragments.get("nav").await.unwrap(), Some("1".to_string()));
+
+        fragments.invalidate_tag("menu").await.unwrap();
+        assert_eq!(fragments.get("nav").await.unwrap(), None);
+        assert_eq!(render(3), "3");
+    }
+}
@@ -0,0 +1,216 @@
+#![allow(clippy::implicit_hasher)]
+use std::collections::{HashMap, HashSet};
+
+use pulldown_cmark::{html, Options, Parser};
+use serde_json::value::Value;
+use tera::Result;
+
+type FilterFn = fn(&Value, &HashMap<String, Value>) -> Result<Value>;
+
+/// A filter producing sanitized HTML, which Tera must not escape again.
+pub struct SafeHtml(pub FilterFn);
+
+impl tera::Filter for SafeHtml {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+        (self.0)(value, args)
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Renders `CommonMark` (with tables, strikethrough and footnotes) to HTML.
+/// The HTML is sanitized like with [`sanitize`], so that user content can be
+/// rendered safely.
+///
+/// # Examples:
+///
+/// ```ignore
+/// {{ post.body | markdown }}
+/// ```
+///
+/// # Errors
+///
+/// If the `value` is not a string.
+pub fn markdown(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let text = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("`markdown` filter expects a string"))?;
+
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_FOOTNOTES;
+    let mut rendered = String::with_capacity(text.len() * 3 / 2);
+    html::push_html(&mut rendered, Parser::new_ext(text, options));
+
+    sanitize(&Value::String(rendered), args)
+}
+
+/// Removes the HTML tags and attributes which are not in an allowlist of
+/// safe ones, such as `<script>` tags, `onclick` attributes or `javascript:`
+/// links.
+///
+/// The allowed tags can be restricted with the `tags` argument.
+///
+/// # Examples:
+///
+/// ```ignore
+/// {{ comment.body | sanitize }}
+/// {{ comment.body | sanitize(tags=["b", "i", "a"]) }}
+/// ```
+///
+/// # Errors
+///
+/// If the `value` is not a string, or `tags` is not a list of strings.
+pub fn sanitize(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let html = value
+        .as_str()
+        .ok_or_else(|| tera::Error::msg("`sanitize` filter expects a string"))?;
+
+    let tags = args
+        .get("tags")
+        .map(|tags| {
+            tags.as_array()
+                .and_then(|tags| {
+                    tags.iter()
+                        .map(Value::as_str)
+                        .collect::<Option<HashSet<_>>>()
+                })
+                .ok_or_else(|| tera::Error::msg("`tags` must be a list of strings"))
+        })
+        .transpose()?;
+
+    let mut builder = ammonia::Builder::default();
+    if let Some(mut tags) = tags {
+        // the content of these tags is always removed
+        tags.retain(|tag| !matches!(*tag, "script" | "style"));
+        builder.tags(tags);
+    }
+    Ok(Value::String(builder.clean(html).to_string()))
+}
+
+/// Serializes a value to JSON which can be embedded in a `<script>` tag,
+/// for example to hydrate frontend state. `<`, `>` and `&` are escaped as
+/// unicode escapes, so that the JSON can neither close the tag
+/// (`</script>`) nor open an HTML comment (`<!--`), and so are the line
+/// and paragraph separators, which older JavaScript engines reject in
+/// strings.
+///
+/// The JSON is indented with the `pretty` argument.
+///
+/// # Examples:
+///
+/// ```ignore
+/// <script>window.state = {{ state | json_encode_safe }};</script>
+/// ```
+///
+/// # Errors
+///
+/// If the `value` can not be serialized.
+pub fn json_encode_safe(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let pretty = args.get("pretty").and_then(Value::as_bool).unwrap_or(false);
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+    .map_err(tera::Error::json)?;
+
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '<' => escaped.push_str("\\u003c"),
+            '>' => escaped.push_str("\\u003e"),
+            '&' => escaped.push_str("\\u0026"),
+            '\u{2028}' => escaped.push_str("\\u2028"),
+            '\u{2029}' => escaped.push_str("\\u2029"),
+            c => escaped.push(c),
+        }
+    }
+    Ok(Value::String(escaped))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+
+    #[rstest]
+    #[case("# Title", "<h1>Title</h1>\n")]
+    #[case("**bold** ~~old~~", "<p><strong>bold</strong> <del>old</del></p>\n")]
+    #[case(
+        "[link](https://loco.rs)",
+        "<p><a href=\"https://loco.rs\" rel=\"noopener noreferrer\">link</a></p>\n"
+    )]
+    #[case(
+        "[xss](javascript:alert(1))",
+        "<p><a rel=\"noopener noreferrer\">xss</a></p>\n"
+    )]
+    #[case("a <span onclick=\"x()\">b</span>", "<p>a <span>b</span></p>\n")]
+    fn can_render_markdown(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(
+            markdown(&json!(input), &HashMap::new()).unwrap(),
+            json!(expected)
+        );
+    }
+
+    #[rstest]
+    #[case("<b onclick=\"x()\">bold</b>", None, "<b>bold</b>")]
+    #[case("<img src=x onerror=alert(1)>", None, "<img src=\"x\">")]
+    #[case("<script>alert(1)</script>hi", None, "hi")]
+    #[case("<b>bold</b> <i>italic</i>", Some(json!(["i"])), "bold <i>italic</i>")]
+    fn can_sanitize(#[case] input: &str, #[case] tags: Option<Value>, #[case] expected: &str) {
+        let args = tags
+            .map(|tags| HashMap::from([("tags".to_string(), tags)]))
+            .unwrap_or_default();
+        assert_eq!(sanitize(&json!(input), &args).unwrap(), json!(expected));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(markdown(&json!(1), &HashMap::new()).is_err());
+        assert!(sanitize(
+            &json!("x"),
+            &HashMap::from([("tags".to_string(), json!([1]))])
+        )
+        .is_err());
+    }
+
+    #[rstest]
+    #[case(json!("</script><script>alert(1)</script>"), r#""\u003c/script\u003e\u003cscript\u003ealert(1)\u003c/script\u003e""#)]
+    #[case(json!({"html": "<!-- a & b -->"}), r#"{"html":"\u003c!-- a \u0026 b --\u003e"}"#)]
+    #[case(json!(["\u{2028}", 1, null]), r#"["\u2028",1,null]"#)]
+    fn can_encode_json_safely(#[case] input: Value, #[case] expected: &str) {
+        let encoded = json_encode_safe(&input, &HashMap::new()).unwrap();
+        assert_eq!(encoded, json!(expected));
+        let decoded: Value = serde_json::from_str(encoded.as_str().unwrap()).unwrap();
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn is_not_escaped_by_tera() {
+        let mut tera = tera::Tera::default();
+        tera.autoescape_on(vec![""]);
+        tera.register_filter("markdown", SafeHtml(markdown));
+        let rendered = tera
+            .render_str(
+                "{{ body | markdown }}",
+                &tera::Context::from_serialize(json!({ "body": "*hi*" })).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(rendered, "<p><em>hi</em></p>\n");
+
+        tera.register_filter("json_encode_safe", SafeHtml(json_encode_safe));
+        let rendered = tera
+            .render_str(
+                "{{ state | json_encode_safe }}",
+                &tera::Context::from_serialize(json!({ "state": {"a": "</b>"} })).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(rendered, r#"{"a":"\u003c/b\u003e"}"#);
+    }
+}
@@ -1,7 +1,11 @@
+pub mod html;
 pub mod number;
 
 pub fn register_filters(tera: &mut tera::Tera) {
     tera.register_filter("number_with_delimiter", number::number_with_delimiter);
     tera.register_filter("number_to_human_size", number::number_to_human_size);
     tera.register_filter("number_to_percentage", number::number_to_percentage);
+    tera.register_filter("markdown", html::SafeHtml(html::markdown));
+    tera.register_filter("sanitize", html::SafeHtml(html::sanitize));
+    tera.register_filter("json_encode_safe", html::SafeHtml(html::json_encode_safe));
 }
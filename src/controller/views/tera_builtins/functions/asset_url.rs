@@ -0,0 +1,97 @@
+#![allow(clippy::implicit_hasher)]
+use std::{collections::HashMap, path::Path};
+
+use serde_json::value::Value;
+use tera::Result;
+
+use crate::controller::assets::AssetManifest;
+
+/// The default static assets folder and the URI it is served under.
+const DEFAULT_FOLDER: &str = "assets/static";
+const DEFAULT_URI: &str = "/static";
+
+/// Returns the URL of a static asset, fingerprinted when assets were
+/// precompiled with `cargo loco assets precompile`.
+///
+/// # Examples:
+///
+/// ```ignore
+/// <link rel="stylesheet" href="{{ asset_url(path="css/app.css") }}">
+/// ```
+///
+/// The function registered by default resolves assets of the default
+/// `assets/static` folder served under `/static`. Register another one for
+/// a custom static assets configuration:
+///
+/// ```rust,ignore
+/// tera.register_function("asset_url", AssetUrl::new(Path::new("public"), "/assets")?);
+/// ```
+pub struct AssetUrl {
+    manifest: AssetManifest,
+    uri: String,
+}
+
+impl AssetUrl {
+    /// Loads the manifest of the assets in `folder`, served under `uri`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the manifest can not be read or parsed.
+    pub fn new(folder: &Path, uri: &str) -> crate::Result<Self> {
+        Ok(Self {
+            manifest: AssetManifest::load(folder)?.unwrap_or_default(),
+            uri: uri.to_string(),
+        })
+    }
+}
+
+impl Default for AssetUrl {
+    fn default() -> Self {
+        Self::new(Path::new(DEFAULT_FOLDER), DEFAULT_URI).unwrap_or_else(|err| {
+            tracing::warn!(error = %err, "could not load the assets manifest");
+            Self {
+                manifest: AssetManifest::default(),
+                uri: DEFAULT_URI.to_string(),
+            }
+        })
+    }
+}
+
+impl tera::Function for AssetUrl {
+    fn call(&self, args: &HashMap<String, Value>) -> Result<Value> {
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| tera::Error::msg("`asset_url` requires a `path` argument"))?;
+        Ok(Value::String(self.manifest.url(&self.uri, path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tera::Function;
+
+    use super::*;
+
+    #[test]
+    fn can_resolve_asset_urls() {
+        let mut manifest = AssetManifest::default();
+        manifest
+            .assets
+            .insert("css/app.css".to_string(), "css/app-0123.css".to_string());
+        let asset_url = AssetUrl {
+            manifest,
+            uri: "/static".to_string(),
+        };
+
+        let call = |path: &str| {
+            asset_url.call(&HashMap::from([(
+                "path".to_string(),
+                Value::String(path.to_string()),
+            )]))
+        };
+        assert_eq!(call("css/app.css").unwrap(), "/static/css/app-0123.css");
+        assert_eq!(call("js/app.js").unwrap(), "/static/js/app.js");
+        assert!(asset_url.call(&HashMap::new()).is_err());
+    }
+}
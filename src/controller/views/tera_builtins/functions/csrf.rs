@@ -0,0 +1,36 @@
+#![allow(clippy::implicit_hasher)]
+use std::collections::HashMap;
+
+use serde_json::value::Value;
+use tera::Result;
+
+use crate::controller::middleware::csrf;
+
+/// Returns the CSRF token of the current request, to be sent back in a
+/// hidden form field.
+///
+/// # Examples:
+///
+/// ```ignore
+/// <input type="hidden" name="csrf_token" value="{{ csrf_token() }}">
+/// ```
+///
+/// # Errors
+///
+/// Returns an error when rendering outside of a request handled by the
+/// `csrf` middleware.
+pub fn csrf_token(_args: &HashMap<String, Value>) -> Result<Value> {
+    csrf::current_token().map(Value::String).ok_or_else(|| {
+        tera::Error::msg("`csrf_token()` requires the csrf middleware to be enabled")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fails_outside_of_csrf_middleware() {
+        assert!(csrf_token(&HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod asset_url;
+pub mod csrf;
+
+pub fn register_functions(tera: &mut tera::Tera) {
+    tera.register_function("csrf_token", csrf::csrf_token);
+    tera.register_function("asset_url", asset_url::AssetUrl::default());
+}
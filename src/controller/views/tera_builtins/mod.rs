@@ -1 +1,3 @@
+pub mod cache;
 pub mod filters;
+pub mod functions;
@@ -0,0 +1,88 @@
+use std::{path::Path, sync::mpsc, time::Duration};
+
+use notify::{
+    event::{EventKind, ModifyKind},
+    Event, RecursiveMode, Watcher,
+};
+use tracing::info;
+
+use crate::{Error, Result};
+
+/// How long the view directory must stay quiet before `on_change` is
+/// called, so that saving a file (which usually emits several events)
+/// triggers a single reload.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Watches a view directory recursively, calling `on_change` whenever an
+/// `.html` file (or a sub-directory) is created, modified or removed.
+///
+/// Events are debounced, and `on_change` is called from a dedicated thread
+/// which stops once the returned watcher is dropped.
+///
+/// # Errors
+///
+/// This function will return an error if the watcher could not be created or
+/// the directory could not be watched
+pub fn watch_views(
+    view_dir: &Path,
+    on_change: impl Fn() + Send + 'static,
+) -> Result<Box<dyn Watcher + Send + Sync>> {
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        while rx.recv().is_ok() {
+            // Wait for the burst of events to settle
+            while rx.recv_timeout(DEBOUNCE).is_ok() {}
+            on_change();
+        }
+    });
+
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let Ok(Event { kind, paths, .. }) = event else {
+            return;
+        };
+
+        // Only handle sub-directories and .html files
+        if !paths
+            .iter()
+            .all(|p| p.is_dir() || p.extension().is_some_and(|ext| ext == "html"))
+        {
+            return;
+        }
+
+        // Log the change, then schedule a reload
+        match kind {
+            // Simple access, no changes
+            EventKind::Access(_) => return,
+            // Metadata changes, no content change
+            EventKind::Modify(ModifyKind::Metadata(_)) => return,
+            // Content modified
+            EventKind::Modify(ModifyKind::Data(change)) => {
+                info!(?paths, ?change, "View file modified");
+            }
+            // File renamed
+            EventKind::Modify(ModifyKind::Name(change)) => {
+                info!(?paths, ?change, "View file renamed");
+            }
+            // Other modifications
+            EventKind::Modify(change) => {
+                info!(?paths, ?change, "View file modified");
+            }
+            // File created.
+            EventKind::Create(_) => info!(?paths, "View file created"),
+            // File removed.
+            EventKind::Remove(_) => info!(?paths, "View file removed"),
+            // All other changes.
+            change => info!(?paths, ?change, "View file changed"),
+        }
+
+        let _ = tx.send(());
+    })
+    .map_err(|_| Error::string("error creating file watcher"))?;
+
+    watcher
+        .watch(view_dir, RecursiveMode::Recursive)
+        .map_err(|_| Error::string("error watching for file changes in view directory"))?;
+
+    Ok(Box::new(watcher))
+}
@@ -56,6 +56,18 @@ pub enum Error {
     #[error(transparent)]
     Tera(#[from] tera::Error),
 
+    #[cfg(feature = "view_minijinja")]
+    #[error(transparent)]
+    MiniJinja(#[from] minijinja::Error),
+
+    #[cfg(feature = "view_handlebars")]
+    #[error(transparent)]
+    HandlebarsTemplate(#[from] handlebars::TemplateError),
+
+    #[cfg(feature = "view_handlebars")]
+    #[error(transparent)]
+    HandlebarsRender(#[from] handlebars::RenderError),
+
     #[error(transparent)]
     JSON(serde_json::Error),
 
@@ -97,6 +109,10 @@ pub enum Error {
     #[error("{0}")]
     Unauthorized(String),
 
+    // API
+    #[error("{0}")]
+    Forbidden(String),
+
     // API
     #[error("not found")]
     NotFound,
@@ -104,6 +120,14 @@ pub enum Error {
     #[error("{0}")]
     BadRequest(String),
 
+    // API
+    #[error("request timed out")]
+    RequestTimeout,
+
+    // API
+    #[error("deadline exceeded")]
+    GatewayTimeout,
+
     #[error("")]
     CustomError(StatusCode, ErrorDetail),
 
@@ -127,7 +151,7 @@ pub enum Error {
     #[error(transparent)]
     Model(#[from] crate::model::ModelError),
 
-    #[cfg(feature = "bg_redis")]
+    #[cfg(any(feature = "bg_redis", feature = "channels_redis"))]
     #[error(transparent)]
     Redis(#[from] redis::RedisError),
 
@@ -135,6 +159,10 @@ pub enum Error {
     #[error(transparent)]
     Sqlx(#[from] sqlx::Error),
 
+    #[cfg(any(feature = "http_client", feature = "mailer_http"))]
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
     #[error(transparent)]
     Storage(#[from] crate::storage::StorageError),
 
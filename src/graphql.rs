@@ -0,0 +1,389 @@
+//! # GraphQL
+//!
+//! With the `graphql` feature, an [`async_graphql`] schema is served by the
+//! [`GraphQLInitializer`]: queries and mutations are posted to `/graphql`,
+//! which also serves a GraphiQL playground.
+//!
+//! The resolvers read the [`AppContext`] from their context, with the
+//! request headers over HTTP, and the [`Channels`] hub when subscriptions
+//! are enabled:
+//! ```rust,ignore
+//! let app = ctx.data::<AppContext>()?;
+//! let headers = ctx.data::<HeaderMap>()?;
+//! ```
+//!
+//! Subscriptions run over the channels layer: the [`GraphQLChannel`] routed
+//! at `/graphql/ws` runs the operations its sockets send with a `subscribe`
+//! event, `{"id": "1", "payload": {"query": "subscription { ... }"}}`, and
+//! sends their results back as `next` events, `{"id": "1", "payload": ...}`,
+//! until a `complete` event with the same `id`, sent by either side.
+//!
+//! # Example
+//! ```rust,ignore
+//! use loco_rs::{
+//!     channels::{backend::InProcess, Channels},
+//!     graphql::GraphQLInitializer,
+//! };
+//!
+//! async fn initializers(ctx: &AppContext) -> Result<Vec<Box<dyn Initializer>>> {
+//!     let channels = Channels::new(InProcess::new()).await?;
+//!     Ok(vec![Box::new(
+//!         GraphQLInitializer::new(graphql::schema()).subscriptions(&channels),
+//!     )])
+//! }
+//! ```
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+pub use async_graphql;
+use async_graphql::{http::GraphiQLSource, ObjectType, Request, Schema, SubscriptionType};
+use async_trait::async_trait;
+use axum::{http::HeaderMap, response::Html, routing::post, Json, Router as AxumRouter};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::task::AbortHandle;
+
+use crate::{
+    app::{AppContext, Initializer},
+    channels::{Channel, Channels, Message, Socket},
+    Error, Result,
+};
+
+/// The default path of the GraphQL endpoint.
+pub const DEFAULT_PATH: &str = "/graphql";
+
+/// Mounts a GraphQL schema on the application router.
+pub struct GraphQLInitializer<Query, Mutation, Subscription> {
+    schema: Schema<Query, Mutation, Subscription>,
+    path: String,
+    graphiql: bool,
+    channels: Option<Channels>,
+}
+
+impl<Query, Mutation, Subscription> GraphQLInitializer<Query, Mutation, Subscription>
+where
+    Query: ObjectType + 'static,
+    Mutation: ObjectType + 'static,
+    Subscription: SubscriptionType + 'static,
+{
+    /// Serves the schema at [`DEFAULT_PATH`].
+    #[must_use]
+    pub fn new(schema: Schema<Query, Mutation, Subscription>) -> Self {
+        Self {
+            schema,
+            path: DEFAULT_PATH.to_string(),
+            graphiql: true,
+            channels: None,
+        }
+    }
+
+    /// Serves the schema at another path.
+    #[must_use]
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    /// Whether the GraphiQL playground is served, which it is by default.
+    #[must_use]
+    pub const fn graphiql(mut self, graphiql: bool) -> Self {
+        self.graphiql = graphiql;
+        self
+    }
+
+    /// Serves the subscriptions with a [`GraphQLChannel`] of the hub, at the
+    /// `/ws` path under the schema one.
+    #[must_use]
+    pub fn subscriptions(mut self, channels: &Channels) -> Self {
+        self.channels = Some(channels.clone());
+        self
+    }
+}
+
+#[async_trait]
+impl<Query, Mutation, Subscription> Initializer
+    for GraphQLInitializer<Query, Mutation, Subscription>
+where
+    Query: ObjectType + 'static,
+    Mutation: ObjectType + 'static,
+    Subscription: SubscriptionType + 'static,
+{
+    fn name(&self) -> String {
+        "graphql".to_string()
+    }
+
+    async fn after_routes(&self, router: AxumRouter, ctx: &AppContext) -> Result<AxumRouter> {
+        let schema = self.schema.clone();
+        let app = ctx.clone();
+        let channels = self.channels.clone();
+        let mut route = post(
+            move |headers: HeaderMap, Json(request): Json<Request>| async move {
+                let request = with_context(request, &app, channels.as_ref()).data(headers);
+                Json(schema.execute(request).await)
+            },
+        );
+        if self.graphiql {
+            let page = GraphiQLSource::build().endpoint(&self.path).finish();
+            route = route.get(move || async move { Html(page) });
+        }
+
+        let mut router = router.route(&self.path, route);
+        if let Some(channels) = &self.channels {
+            let channel = GraphQLChannel::new(self.schema.clone());
+            router = router.route(
+                &format!("{}/ws", self.path.trim_end_matches('/')),
+                channels.handler(channel).with_state(ctx.clone()),
+            );
+        }
+        Ok(router)
+    }
+}
+
+/// Gives the resolvers of a request the application context, and the hub of
+/// the channels.
+fn with_context(request: Request, ctx: &AppContext, channels: Option<&Channels>) -> Request {
+    let request = request.data(ctx.clone());
+    match channels {
+        Some(channels) => request.data(channels.clone()),
+        None => request,
+    }
+}
+
+/// The running subscriptions, by socket and by ID.
+type Subscriptions = Arc<Mutex<HashMap<String, HashMap<String, AbortHandle>>>>;
+
+/// A [`Channel`] running the GraphQL operations of its sockets, usually
+/// subscriptions.
+pub struct GraphQLChannel<Query, Mutation, Subscription> {
+    schema: Schema<Query, Mutation, Subscription>,
+    subscriptions: Subscriptions,
+}
+
+impl<Query, Mutation, Subscription> GraphQLChannel<Query, Mutation, Subscription> {
+    #[must_use]
+    pub fn new(schema: Schema<Query, Mutation, Subscription>) -> Self {
+        Self {
+            schema,
+            subscriptions: Subscriptions::default(),
+        }
+    }
+
+    fn stop(&self, socket_id: &str, id: Option<&str>) {
+        let mut subscriptions = self
+            .subscriptions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let Some(running) = subscriptions.get_mut(socket_id) else {
+            return;
+        };
+        match id {
+            Some(id) => {
+                if let Some(task) = running.remove(id) {
+                    task.abort();
+                }
+            }
+            None => running.drain().for_each(|(_, task)| task.abort()),
+        }
+        if running.is_empty() {
+            subscriptions.remove(socket_id);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Subscribe {
+    id: String,
+    payload: Request,
+}
+
+#[derive(Deserialize)]
+struct Complete {
+    id: String,
+}
+
+#[async_trait]
+impl<Query, Mutation, Subscription> Channel for GraphQLChannel<Query, Mutation, Subscription>
+where
+    Query: ObjectType + 'static,
+    Mutation: ObjectType + 'static,
+    Subscription: SubscriptionType + 'static,
+{
+    async fn leave(&self, socket: &Socket, _ctx: &AppContext) -> Result<()> {
+        self.stop(socket.id(), None);
+        Ok(())
+    }
+
+    async fn handle_message(
+        &self,
+        socket: &Socket,
+        message: Message,
+        ctx: &AppContext,
+    ) -> Result<()> {
+        match message.event.as_str() {
+            "subscribe" => {
+                let Subscribe { id, payload } = serde_json::from_value(message.data)?;
+                self.stop(socket.id(), Some(&id));
+
+                let request = with_context(payload, ctx, Some(socket.channels()));
+                let mut stream = self.schema.execute_stream(request);
+                let outbox = socket.outbox();
+                let subscriptions = self.subscriptions.clone();
+                let socket_id = socket.id().to_string();
+                let subscription_id = id.clone();
+
+                // the task is registered before it can unregister itself
+                let mut running = self
+                    .subscriptions
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                let task = tokio::spawn(async move {
+                    while let Some(response) = stream.next().await {
+                        let next = Message {
+                            event: "next".to_string(),
+                            data: json!({ "id": subscription_id, "payload": response }),
+                        };
+                        if outbox.send(next).await.is_err() {
+                            return;
+                        }
+                    }
+                    let _ = outbox
+                        .send(Message {
+                            event: "complete".to_string(),
+                            data: json!({ "id": subscription_id }),
+                        })
+                        .await;
+
+                    let mut subscriptions = subscriptions
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                    if let Some(running) = subscriptions.get_mut(&socket_id) {
+                        running.remove(&subscription_id);
+                        if running.is_empty() {
+                            subscriptions.remove(&socket_id);
+                        }
+                    }
+                });
+                running
+                    .entry(socket.id().to_string())
+                    .or_default()
+                    .insert(id, task.abort_handle());
+                Ok(())
+            }
+            "complete" => {
+                let Complete { id } = serde_json::from_value(message.data)?;
+                self.stop(socket.id(), Some(&id));
+                Ok(())
+            }
+            event => Err(Error::BadRequest(format!(
+                "unknown GraphQL event `{event}`"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Subscription};
+    use axum::body::Body;
+    use futures_util::Stream;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        channels::{backend::InProcess, test_socket},
+        tests_cfg,
+    };
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn environment(&self, ctx: &Context<'_>) -> async_graphql::Result<String> {
+            Ok(ctx.data::<AppContext>()?.environment.to_string())
+        }
+    }
+
+    struct Ticks;
+
+    #[Subscription]
+    impl Ticks {
+        async fn ticks(&self) -> impl Stream<Item = i32> {
+            futures_util::stream::iter(0..2)
+        }
+    }
+
+    #[tokio::test]
+    async fn can_execute_queries() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+        let router = GraphQLInitializer::new(schema)
+            .after_routes(AxumRouter::new(), &ctx)
+            .await
+            .unwrap();
+
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri(DEFAULT_PATH)
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"query": "{ environment }"}"#))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body,
+            json!({ "data": { "environment": ctx.environment.to_string() } })
+        );
+    }
+
+    #[tokio::test]
+    async fn can_run_subscriptions() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let channels = Channels::new(InProcess::new()).await.unwrap();
+        let channel = GraphQLChannel::new(Schema::new(Query, EmptyMutation, Ticks));
+        let (socket, mut inbox) = test_socket(&channels);
+
+        channel
+            .handle_message(
+                &socket,
+                Message {
+                    event: "subscribe".to_string(),
+                    data: json!({ "id": "1", "payload": { "query": "subscription { ticks }" } }),
+                },
+                &ctx,
+            )
+            .await
+            .unwrap();
+
+        for tick in 0..2 {
+            let next = inbox.recv().await.unwrap();
+            assert_eq!(next.event, "next");
+            assert_eq!(
+                next.data,
+                json!({ "id": "1", "payload": { "data": { "ticks": tick } } })
+            );
+        }
+        assert_eq!(
+            inbox.recv().await.unwrap(),
+            Message {
+                event: "complete".to_string(),
+                data: json!({ "id": "1" }),
+            }
+        );
+
+        let unknown = Message {
+            event: "start".to_string(),
+            data: json!({}),
+        };
+        assert!(channel
+            .handle_message(&socket, unknown, &ctx)
+            .await
+            .is_err());
+    }
+}
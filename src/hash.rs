@@ -1,9 +1,15 @@
+use std::fmt::Write;
+
 use crate::{Error, Result};
 use argon2::{
     password_hash::{rand_core::OsRng, SaltString},
     Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
 };
 use rand::{distr::Alphanumeric, rng, Rng};
+use sha2::{Digest, Sha256};
+
+/// Length of the random part of generated API keys
+const API_KEY_LENGTH: usize = 40;
 
 /// Hashes a plain text password and returns the hashed result.
 ///
@@ -77,6 +83,114 @@ pub fn random_string(length: usize) -> String {
         .collect()
 }
 
+/// Generates a new random API key, starting with the given prefix to make
+/// keys easy to identify (and to scan for in leaked secrets).
+///
+/// # Example
+///
+/// ```rust
+/// use loco_rs::hash;
+///
+/// let key = hash::generate_api_key("lo_");
+/// assert!(key.starts_with("lo_"));
+/// ```
+#[must_use]
+pub fn generate_api_key(prefix: &str) -> String {
+    format!("{prefix}{}", random_string(API_KEY_LENGTH))
+}
+
+/// Hashes an API key for storage. Unlike [`hash_password`] the hash is
+/// deterministic (SHA-256, hex encoded), so that keys can be looked up by
+/// their hash.
+///
+/// # Example
+///
+/// ```rust
+/// use loco_rs::hash;
+///
+/// assert_eq!(hash::hash_api_key("lo_key"), hash::hash_api_key("lo_key"));
+/// ```
+#[must_use]
+pub fn hash_api_key(key: &str) -> String {
+    hash_token(key)
+}
+
+/// Hashes a random token, such as a refresh token, to store it without the
+/// token itself: SHA-256, hex encoded. Random tokens do not need the slow,
+/// salted hashes of [`hash_password`].
+///
+/// # Example
+///
+/// ```rust
+/// use loco_rs::hash;
+///
+/// assert_eq!(hash::hash_token("token").len(), 64);
+/// ```
+#[must_use]
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Encodes bytes, such as a digest or a signature, as lowercase hex.
+///
+/// # Example
+///
+/// ```rust
+/// use loco_rs::hash;
+///
+/// assert_eq!(hash::to_hex(&[0, 15, 255]), "000fff");
+/// ```
+#[must_use]
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Decodes hex, in lower or upper case. Returns `None` when it is not valid
+/// hex.
+///
+/// # Example
+///
+/// ```rust
+/// use loco_rs::hash;
+///
+/// assert_eq!(hash::from_hex("000fFF"), Some(vec![0, 15, 255]));
+/// assert_eq!(hash::from_hex("0g"), None);
+/// ```
+#[must_use]
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Compares two secrets in constant time, so that the comparison leaks
+/// neither how many leading bytes match nor the length of the secrets: it
+/// compares their SHA-256 digests.
+///
+/// # Example
+///
+/// ```rust
+/// use loco_rs::hash;
+///
+/// assert!(hash::constant_time_eq(b"token", b"token"));
+/// assert!(!hash::constant_time_eq(b"token", b"tokeN"));
+/// ```
+#[must_use]
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    Sha256::digest(a)
+        .iter()
+        .zip(Sha256::digest(b).iter())
+        .fold(0, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -100,4 +214,31 @@ mod tests {
         assert_eq!(second.len(), random_length);
         assert_ne!(first, second);
     }
+
+    #[test]
+    fn can_hash_api_key() {
+        assert_eq!(
+            hash_api_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_ne!(generate_api_key("lo_"), generate_api_key("lo_"));
+    }
+
+    #[test]
+    fn can_compare_in_constant_time() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secre"));
+        assert!(!constant_time_eq(b"secret", b"Secret"));
+    }
+
+    #[test]
+    fn can_encode_and_decode_hex() {
+        let bytes = Sha256::digest(b"abc");
+        assert_eq!(from_hex(&to_hex(&bytes)).unwrap(), bytes.to_vec());
+        assert_eq!(from_hex(""), Some(vec![]));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("+1"), None);
+        assert_eq!(from_hex("é1"), None);
+    }
 }
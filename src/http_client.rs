@@ -0,0 +1,598 @@
+//! # HTTP Client
+//!
+//! With the `http_client` feature, `ctx.http` is the client of the app to
+//! call external APIs, configured by the `http` section of the environment
+//! configuration: timeouts, proxy, base URL of the relative URLs and retry
+//! policy.
+//!
+//! The idempotent requests failing to connect, or answered with a retried
+//! status such as `503`, are retried with an exponential backoff. The
+//! requests made while serving a request carry its `x-request-id` and a
+//! `traceparent` header continuing its trace, with the request ID
+//! middleware enabled.
+//!
+//! In the tests, a stubbed client answers the requests with canned
+//! responses rather than calling the servers:
+//! ```rust,ignore
+//! let http = Client::stubbed(&ctx.config.http);
+//! http.stubs()
+//!     .on(Method::GET, "https://api.example.com/users/1")
+//!     .respond(Stub::json(200, &json!({ "name": "loco" })));
+//! ctx.http = Arc::new(http);
+//! ```
+//!
+//! # Example
+//! ```rust,ignore
+//! use loco_rs::prelude::*;
+//!
+//! async fn user(State(ctx): State<AppContext>) -> Result<Response> {
+//!     let user: serde_json::Value = ctx.http.get("/users/1").send().await?.json().await?;
+//!     format::json(user)
+//! }
+//! ```
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+pub use reqwest;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    Method, Response,
+};
+use serde::Serialize;
+use tracing::Instrument;
+
+use crate::{config, controller::middleware::request_id::TraceContext, Error, Result};
+
+/// The HTTP client of the app.
+#[derive(Clone)]
+pub struct Client {
+    inner: reqwest::Client,
+    config: config::HttpClient,
+    stubs: Option<Arc<Stubs>>,
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("config", &self.config)
+            .field("stubbed", &self.stubs.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Client {
+    /// Creates the client of a configuration, identified by `user_agent`
+    /// unless the configuration sets one.
+    ///
+    /// # Errors
+    ///
+    /// When the proxy URL is invalid or the TLS backend can not be
+    /// initialized.
+    pub fn new(config: &config::HttpClient, user_agent: &str) -> Result<Self> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout))
+            .connect_timeout(Duration::from_millis(config.connect_timeout))
+            .user_agent(config.user_agent.as_deref().unwrap_or(user_agent));
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        Ok(Self {
+            inner: builder.build()?,
+            config: config.clone(),
+            stubs: None,
+        })
+    }
+
+    /// Creates a client answering the requests with the responses of its
+    /// [`Stubs`], for the tests.
+    #[must_use]
+    pub fn stubbed(config: &config::HttpClient) -> Self {
+        Self {
+            inner: reqwest::Client::new(),
+            config: config.clone(),
+            stubs: Some(Arc::new(Stubs::default())),
+        }
+    }
+
+    /// The stubs of a stubbed client.
+    ///
+    /// # Panics
+    ///
+    /// When the client is not stubbed.
+    #[must_use]
+    pub fn stubs(&self) -> &Stubs {
+        self.stubs
+            .as_deref()
+            .expect("the HTTP client is not stubbed")
+    }
+
+    /// The underlying `reqwest` client, without the retries, the trace
+    /// propagation and the stubs.
+    #[must_use]
+    pub const fn inner(&self) -> &reqwest::Client {
+        &self.inner
+    }
+
+    #[must_use]
+    pub fn get(&self, url: &str) -> RequestBuilder<'_> {
+        self.request(Method::GET, url)
+    }
+
+    #[must_use]
+    pub fn post(&self, url: &str) -> RequestBuilder<'_> {
+        self.request(Method::POST, url)
+    }
+
+    #[must_use]
+    pub fn put(&self, url: &str) -> RequestBuilder<'_> {
+        self.request(Method::PUT, url)
+    }
+
+    #[must_use]
+    pub fn patch(&self, url: &str) -> RequestBuilder<'_> {
+        self.request(Method::PATCH, url)
+    }
+
+    #[must_use]
+    pub fn delete(&self, url: &str) -> RequestBuilder<'_> {
+        self.request(Method::DELETE, url)
+    }
+
+    /// Starts a request, relative URLs being joined to the base URL.
+    #[must_use]
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder<'_> {
+        RequestBuilder {
+            client: self,
+            builder: self.inner.request(method, self.url(url)),
+        }
+    }
+
+    fn url(&self, url: &str) -> String {
+        match &self.config.base_url {
+            Some(base_url) if !url.contains("://") => format!(
+                "{}/{}",
+                base_url.trim_end_matches('/'),
+                url.trim_start_matches('/')
+            ),
+            _ => url.to_string(),
+        }
+    }
+
+    /// Sends a request, retrying it when it is idempotent and fails to
+    /// connect or is answered with a retried status.
+    ///
+    /// # Errors
+    ///
+    /// When the request can not be sent, after the retries.
+    pub async fn execute(&self, mut request: reqwest::Request) -> Result<Response> {
+        if self.config.propagate_trace {
+            if let Some(trace) = TraceContext::current() {
+                propagate(request.headers_mut(), &trace);
+            }
+        }
+
+        let span = tracing::info_span!(
+            "http-client",
+            "http.method" = %request.method(),
+            "http.url" = %request.url(),
+        );
+        async move {
+            let retry = &self.config.retry;
+            let retries = if is_idempotent(request.method()) {
+                retry.max_retries
+            } else {
+                0
+            };
+            let mut backoff = Duration::from_millis(retry.initial_backoff);
+            let mut attempt = 0;
+            loop {
+                let next = if attempt < retries {
+                    request.try_clone()
+                } else {
+                    None
+                };
+                let result = self.attempt(request).await;
+                let Some(next) = next else {
+                    return result;
+                };
+
+                let wait = match &result {
+                    Ok(response) if retry.statuses.contains(&response.status().as_u16()) => {
+                        Some(retry_after(response.headers()).unwrap_or(backoff))
+                    }
+                    Err(Error::Http(err)) if err.is_connect() || err.is_timeout() => Some(backoff),
+                    _ => None,
+                };
+                let Some(wait) = wait else {
+                    return result;
+                };
+                let wait = wait.min(Duration::from_millis(retry.max_backoff));
+                attempt += 1;
+                tracing::warn!(
+                    attempt,
+                    wait = ?wait,
+                    status = ?result.as_ref().ok().map(Response::status),
+                    error = ?result.as_ref().err(),
+                    "retrying HTTP request"
+                );
+                tokio::time::sleep(wait).await;
+                backoff = (backoff * 2).min(Duration::from_millis(retry.max_backoff));
+                request = next;
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn attempt(&self, request: reqwest::Request) -> Result<Response> {
+        let response = match &self.stubs {
+            Some(stubs) => stubs.respond(&request)?,
+            None => self.inner.execute(request).await?,
+        };
+        tracing::debug!(status = response.status().as_u16(), "HTTP response");
+        Ok(response)
+    }
+}
+
+/// Adds the request ID and the trace of the request being served.
+fn propagate(headers: &mut HeaderMap, trace: &TraceContext) {
+    if let Ok(value) = HeaderValue::from_str(&trace.request_id) {
+        headers
+            .entry(HeaderName::from_static("x-request-id"))
+            .or_insert(value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&trace.traceparent()) {
+        headers
+            .entry(HeaderName::from_static("traceparent"))
+            .or_insert(value);
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
+
+/// The wait of a `Retry-After` header in seconds.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// A request of the [`Client`].
+pub struct RequestBuilder<'a> {
+    client: &'a Client,
+    builder: reqwest::RequestBuilder,
+}
+
+impl RequestBuilder<'_> {
+    #[must_use]
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.builder = self.builder.header(name, value);
+        self
+    }
+
+    #[must_use]
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.builder = self.builder.headers(headers);
+        self
+    }
+
+    #[must_use]
+    pub fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Self {
+        self.builder = self.builder.query(query);
+        self
+    }
+
+    #[must_use]
+    pub fn json<T: Serialize + ?Sized>(mut self, json: &T) -> Self {
+        self.builder = self.builder.json(json);
+        self
+    }
+
+    #[must_use]
+    pub fn form<T: Serialize + ?Sized>(mut self, form: &T) -> Self {
+        self.builder = self.builder.form(form);
+        self
+    }
+
+    #[must_use]
+    pub fn body(mut self, body: impl Into<reqwest::Body>) -> Self {
+        self.builder = self.builder.body(body);
+        self
+    }
+
+    #[must_use]
+    pub fn bearer_auth(mut self, token: &str) -> Self {
+        self.builder = self.builder.bearer_auth(token);
+        self
+    }
+
+    #[must_use]
+    pub fn basic_auth(mut self, username: &str, password: Option<&str>) -> Self {
+        self.builder = self.builder.basic_auth(username, password);
+        self
+    }
+
+    /// The timeout of the attempts of this request.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.builder = self.builder.timeout(timeout);
+        self
+    }
+
+    /// Changes the underlying `reqwest` request.
+    #[must_use]
+    pub fn with(
+        mut self,
+        f: impl FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Self {
+        self.builder = f(self.builder);
+        self
+    }
+
+    /// Sends the request, with the retries of the client.
+    ///
+    /// # Errors
+    ///
+    /// When the request is invalid or can not be sent.
+    pub async fn send(self) -> Result<Response> {
+        let request = self.builder.build()?;
+        self.client.execute(request).await
+    }
+}
+
+/// A canned response.
+#[derive(Debug, Clone)]
+pub struct Stub {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Bytes,
+}
+
+impl Stub {
+    #[must_use]
+    pub fn new(status: u16, body: impl Into<Bytes>) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    /// A JSON response.
+    ///
+    /// # Panics
+    ///
+    /// When `body` can not be serialized.
+    #[must_use]
+    pub fn json<T: Serialize>(status: u16, body: &T) -> Self {
+        Self::new(
+            status,
+            serde_json::to_vec(body).expect("serializable stub body"),
+        )
+        .header("content-type", "application/json")
+    }
+
+    #[must_use]
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    fn to_response(&self) -> Result<Response> {
+        let mut response = axum::http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            response = response.header(name, value);
+        }
+        Ok(Response::from(response.body(self.body.clone())?))
+    }
+}
+
+/// A request sent to a stubbed client.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<Bytes>,
+}
+
+struct Route {
+    method: Method,
+    url: String,
+    /// The responses answered in turn, the last one repeatedly
+    responses: VecDeque<Stub>,
+}
+
+/// The canned responses of a stubbed [`Client`], and the requests it got.
+#[derive(Default)]
+pub struct Stubs {
+    routes: Mutex<Vec<Route>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+impl Stubs {
+    /// Stubs the requests of a method and absolute URL.
+    #[must_use]
+    pub fn on(&self, method: Method, url: &str) -> StubRoute<'_> {
+        StubRoute {
+            stubs: self,
+            method,
+            url: url.to_string(),
+        }
+    }
+
+    /// The requests sent to the client.
+    #[must_use]
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    fn respond(&self, request: &reqwest::Request) -> Result<Response> {
+        self.requests
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(RecordedRequest {
+                method: request.method().clone(),
+                url: request.url().to_string(),
+                headers: request.headers().clone(),
+                body: request
+                    .body()
+                    .and_then(reqwest::Body::as_bytes)
+                    .map(Bytes::copy_from_slice),
+            });
+
+        let mut routes = self
+            .routes
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let route = routes
+            .iter_mut()
+            .find(|route| route.method == request.method() && route.url == request.url().as_str())
+            .ok_or_else(|| {
+                Error::Message(format!(
+                    "no HTTP stub for {} {}",
+                    request.method(),
+                    request.url()
+                ))
+            })?;
+        let stub = if route.responses.len() > 1 {
+            route.responses.pop_front()
+        } else {
+            route.responses.front().cloned()
+        };
+        stub.ok_or_else(|| Error::Message(format!("no HTTP stub response for {}", route.url)))?
+            .to_response()
+    }
+}
+
+/// The stubbed requests of a method and URL.
+pub struct StubRoute<'a> {
+    stubs: &'a Stubs,
+    method: Method,
+    url: String,
+}
+
+impl StubRoute<'_> {
+    /// Answers the requests with `stub`. The stubs of a route are answered
+    /// in turn, the last one to all the next requests.
+    pub fn respond(self, stub: Stub) {
+        let url = reqwest::Url::parse(&self.url).map_or(self.url, String::from);
+        let mut routes = self
+            .stubs
+            .routes
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match routes
+            .iter_mut()
+            .find(|route| route.method == self.method && route.url == url)
+        {
+            Some(route) => route.responses.push_back(stub),
+            None => routes.push(Route {
+                method: self.method,
+                url,
+                responses: VecDeque::from([stub]),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn config() -> config::HttpClient {
+        config::HttpClient {
+            base_url: Some("https://api.example.com/v1/".to_string()),
+            retry: config::HttpRetry {
+                initial_backoff: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn can_stub_requests() {
+        let http = Client::stubbed(&config());
+        http.stubs()
+            .on(Method::GET, "https://api.example.com/v1/users/1")
+            .respond(Stub::json(200, &json!({ "name": "loco" })));
+
+        let response = http.get("/users/1").send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let user: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(user, json!({ "name": "loco" }));
+
+        assert!(http.post("/users").send().await.is_err());
+        let requests = http.stubs().requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].method, Method::POST);
+        assert_eq!(requests[1].url, "https://api.example.com/v1/users");
+    }
+
+    #[tokio::test]
+    async fn can_retry_idempotent_requests() {
+        let http = Client::stubbed(&config());
+        let url = "https://api.example.com/v1/jobs";
+        http.stubs()
+            .on(Method::GET, url)
+            .respond(Stub::new(503, ""));
+        http.stubs()
+            .on(Method::GET, url)
+            .respond(Stub::new(502, ""));
+        http.stubs()
+            .on(Method::GET, url)
+            .respond(Stub::new(200, "done"));
+        http.stubs()
+            .on(Method::POST, url)
+            .respond(Stub::new(503, ""));
+
+        let response = http.get(url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "done");
+        assert_eq!(http.stubs().requests().len(), 3);
+
+        let response = http.post(url).body("job").send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 503);
+        assert_eq!(http.stubs().requests().len(), 4);
+        assert_eq!(
+            http.stubs().requests()[3].body.as_deref(),
+            Some(&b"job"[..])
+        );
+    }
+
+    #[test]
+    fn can_propagate_traces() {
+        let mut headers = HeaderMap::new();
+        propagate(
+            &mut headers,
+            &TraceContext {
+                request_id: "abc-123".to_string(),
+                trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+                flags: "01".to_string(),
+            },
+        );
+        assert_eq!(headers["x-request-id"], "abc-123");
+        assert!(headers["traceparent"]
+            .to_str()
+            .unwrap()
+            .starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+    }
+}
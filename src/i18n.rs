@@ -0,0 +1,288 @@
+//! # Internationalization
+//!
+//! Translations are [Fluent](https://projectfluent.org) files, in one
+//! directory per locale under `i18n.locales_dir` (`assets/i18n` by default):
+//!
+//! ```text
+//! assets/i18n/
+//! ├── en-US/main.ftl
+//! └── de-DE/main.ftl
+//! ```
+//!
+//! Load them once into the [`crate::app::SharedStore`], register the `t()`
+//! function into the Tera views, and pick the request locale with the
+//! [`Locale`] extractor:
+//!
+//! ```rust,ignore
+//! // in the view engine initializer
+//! let i18n = I18n::from_config(&ctx.config.i18n)?;
+//! ctx.shared_store.insert(i18n.clone());
+//! let tera_engine = engines::TeraView::build_with_post_process(move |tera| {
+//!     i18n.register(tera);
+//!     Ok(())
+//! })?;
+//!
+//! // in a controller
+//! async fn home(ViewEngine(v): ViewEngine<TeraView>, locale: Locale) -> Result<Response> {
+//!     format::render().view(&v, "home/hello.html", data!({ "locale": locale.to_string() }))
+//! }
+//! ```
+//!
+//! ```jinja
+//! <h1>{{ t(key="hello-world", lang=locale) }}</h1>
+//! <p>{{ t(key="greeting", lang=locale, name=user.name) }}</p>
+//! ```
+use std::{path::Path, sync::Arc};
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use axum_extra::extract::cookie::CookieJar;
+pub use fluent_templates::LanguageIdentifier;
+use fluent_templates::{ArcLoader, FluentLoader, Loader};
+
+use crate::{app::AppContext, config, Error, Result};
+
+/// The translations of the application.
+#[derive(Clone)]
+pub struct I18n {
+    loader: Arc<ArcLoader>,
+    locales: Vec<LanguageIdentifier>,
+    default_locale: LanguageIdentifier,
+    cookie: String,
+}
+
+impl I18n {
+    /// Loads the translations of every locale in `i18n.locales_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the default locale is invalid, or a translation
+    /// file can not be read or parsed.
+    pub fn from_config(config: &config::I18n) -> Result<Self> {
+        let default_locale = parse_locale(&config.default_locale)?;
+        let locales = Self::available_locales(Path::new(&config.locales_dir))?;
+
+        let loader = ArcLoader::builder(&config.locales_dir, default_locale.clone())
+            .customize(|bundle| bundle.set_use_isolating(false))
+            .build()
+            .map_err(|err| Error::string(&format!("could not load translations: {err}")))?;
+
+        Ok(Self {
+            loader: Arc::new(loader),
+            locales,
+            default_locale,
+            cookie: config.cookie.clone(),
+        })
+    }
+
+    /// The locales with a translation directory.
+    fn available_locales(locales_dir: &Path) -> Result<Vec<LanguageIdentifier>> {
+        let mut locales = Vec::new();
+        for entry in std::fs::read_dir(locales_dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                    locales.push(parse_locale(name)?);
+                }
+            }
+        }
+        locales.sort_by_key(ToString::to_string);
+        Ok(locales)
+    }
+
+    /// Registers the `t(key=.., lang=..)` function, which takes the Fluent
+    /// arguments of the message as extra named arguments.
+    pub fn register(&self, tera: &mut tera::Tera) {
+        tera.register_function(
+            "t",
+            FluentLoader::new(self.loader.clone()).with_default_lang(self.default_locale.clone()),
+        );
+    }
+
+    /// Translates the message `key`, falling back to the default locale.
+    #[must_use]
+    pub fn t(&self, locale: &LanguageIdentifier, key: &str) -> String {
+        self.loader.lookup(locale, key)
+    }
+
+    #[must_use]
+    pub fn locales(&self) -> &[LanguageIdentifier] {
+        &self.locales
+    }
+
+    #[must_use]
+    pub fn default_locale(&self) -> &LanguageIdentifier {
+        &self.default_locale
+    }
+
+    /// Picks the best available locale for the requested ones, in order of
+    /// preference: an exact match first, then a locale of the same language.
+    #[must_use]
+    pub fn negotiate<'a>(
+        &self,
+        requested: impl IntoIterator<Item = &'a LanguageIdentifier>,
+    ) -> LanguageIdentifier {
+        for locale in requested {
+            if let Some(found) = self.locales.iter().find(|l| *l == locale) {
+                return found.clone();
+            }
+            if let Some(found) = self.locales.iter().find(|l| l.language == locale.language) {
+                return found.clone();
+            }
+        }
+        self.default_locale.clone()
+    }
+}
+
+fn parse_locale(locale: &str) -> Result<LanguageIdentifier> {
+    locale
+        .parse()
+        .map_err(|err| Error::string(&format!("invalid locale `{locale}`: {err}")))
+}
+
+/// Parses an `Accept-Language` header into its locales, by decreasing
+/// quality.
+fn parse_accept_language(header: &str) -> Vec<LanguageIdentifier> {
+    let mut locales = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.trim().split(';');
+            let locale = parts.next()?.trim().parse::<LanguageIdentifier>().ok()?;
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (quality > 0.0).then_some((locale, quality))
+        })
+        .collect::<Vec<_>>();
+    // stable, so that locales of equal quality keep their order
+    locales.sort_by(|a, b| b.1.total_cmp(&a.1));
+    locales.into_iter().map(|(locale, _)| locale).collect()
+}
+
+/// The locale of the request: the one stored in the `i18n.cookie` cookie,
+/// or else the best match of the `Accept-Language` header, or else the
+/// default locale.
+///
+/// Requires an [`I18n`] in the [`crate::app::SharedStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale(pub LanguageIdentifier);
+
+impl Locale {
+    /// Picks the locale of a request.
+    #[must_use]
+    pub fn from_parts(i18n: &I18n, parts: &Parts) -> Self {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let from_cookie = jar
+            .get(&i18n.cookie)
+            .and_then(|cookie| cookie.value().parse::<LanguageIdentifier>().ok());
+
+        let from_header = parts
+            .headers
+            .get(axum::http::header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(parse_accept_language)
+            .unwrap_or_default();
+
+        Self(i18n.negotiate(from_cookie.iter().chain(from_header.iter())))
+    }
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromRequestParts<AppContext> for Locale {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppContext) -> Result<Self> {
+        let i18n = state.shared_store.get::<I18n>().ok_or_else(|| {
+            tracing::error!("the `Locale` extractor requires `I18n` in the shared store");
+            Error::InternalServerError
+        })?;
+        Ok(Self::from_parts(&i18n, parts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    fn i18n() -> (tree_fs::Tree, I18n) {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add_file(
+                "en-US/main.ftl",
+                "hello = Hello\nbye = Bye\ngreeting = Hi { $name }\n",
+            )
+            .add_file("de-DE/main.ftl", "hello = Hallo\n")
+            .add_file("fr/main.ftl", "hello = Bonjour\n")
+            .create()
+            .unwrap();
+
+        let i18n = I18n::from_config(&config::I18n {
+            locales_dir: tree_fs.root.display().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        (tree_fs, i18n)
+    }
+
+    fn locale(i18n: &I18n, headers: &[(&str, &str)]) -> String {
+        let mut request = Request::builder();
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let (parts, ()) = request.body(()).unwrap().into_parts();
+        Locale::from_parts(i18n, &parts).to_string()
+    }
+
+    #[test]
+    fn can_negotiate_locale() {
+        let (_tree_fs, i18n) = i18n();
+
+        assert_eq!(locale(&i18n, &[]), "en-US");
+        assert_eq!(locale(&i18n, &[("accept-language", "de-DE")]), "de-DE");
+        assert_eq!(
+            locale(&i18n, &[("accept-language", "es, fr-CA;q=0.8")]),
+            "fr"
+        );
+        assert_eq!(
+            locale(&i18n, &[("accept-language", "en;q=0.5, de;q=0.9")]),
+            "de-DE"
+        );
+        assert_eq!(
+            locale(
+                &i18n,
+                &[("accept-language", "de-DE"), ("cookie", "locale=fr")]
+            ),
+            "fr"
+        );
+        assert_eq!(
+            locale(
+                &i18n,
+                &[("accept-language", "de-DE"), ("cookie", "locale=???")]
+            ),
+            "de-DE"
+        );
+    }
+
+    #[test]
+    fn can_translate() {
+        let (_tree_fs, i18n) = i18n();
+
+        assert_eq!(i18n.t(&"de-DE".parse().unwrap(), "hello"), "Hallo");
+        assert_eq!(i18n.t(&"fr".parse().unwrap(), "bye"), "Bye");
+
+        let mut tera = tera::Tera::default();
+        i18n.register(&mut tera);
+        let render = tera
+            .render_str(
+                r#"{{ t(key="hello", lang="fr") }}, {{ t(key="greeting", lang="en-US", name="Loco") }}"#,
+                &tera::Context::new(),
+            )
+            .unwrap();
+        assert_eq!(render, "Bonjour, Hi Loco");
+    }
+}
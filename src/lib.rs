@@ -19,12 +19,16 @@ pub mod db;
 pub mod model;
 #[cfg(feature = "with-db")]
 pub mod schema;
+#[cfg(feature = "with-db")]
+pub mod seeder;
 mod tera;
 
 pub mod app;
 pub mod auth;
 pub mod boot;
 pub mod cache;
+#[cfg(feature = "channels")]
+pub mod channels;
 #[cfg(feature = "cli")]
 pub mod cli;
 pub mod config;
@@ -32,11 +36,25 @@ pub mod controller;
 mod env_vars;
 pub mod environment;
 pub mod errors;
+#[cfg(feature = "graphql")]
+pub mod graphql;
 pub mod hash;
+#[cfg(feature = "http_client")]
+pub mod http_client;
+#[cfg(feature = "i18n")]
+pub mod i18n;
+#[cfg(feature = "with-db")]
+pub mod listener;
 pub mod logger;
 pub mod mailer;
+#[cfg(feature = "with-db")]
+pub mod outbox;
+pub mod policy;
 pub mod scheduler;
+pub mod session;
 pub mod task;
+#[cfg(feature = "with-db")]
+pub mod tenancy;
 #[cfg(feature = "testing")]
 pub mod testing;
 #[cfg(feature = "testing")]
@@ -45,6 +63,7 @@ pub mod storage;
 #[cfg(feature = "testing")]
 pub mod tests_cfg;
 pub mod validation;
+pub mod webhooks;
 pub use validator;
 pub mod cargo_config;
 
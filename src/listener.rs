@@ -0,0 +1,313 @@
+//! # Database Notifications
+//!
+//! Bridges the `LISTEN`/`NOTIFY` notifications of Postgres to the app: the
+//! handlers registered on a channel with [`Listeners::on`] receive the
+//! payloads notified on it, by any connection to the database, such as a
+//! trigger or another instance of the app. The payloads can also be forwarded
+//! to an [`SseBroadcaster`], or to a room of the WebSocket channels, for
+//! realtime features without polling.
+//!
+//! The listener runs while the app is started, reconnecting when the
+//! connection is lost. The notifications sent while it is disconnected are
+//! lost.
+//!
+//! # Example
+//! ```rust,ignore
+//! // in your `Hooks` implementation
+//! fn register_listeners(listeners: &mut Listeners) {
+//!     listeners.on("orders", |ctx: AppContext, notification: Notification| async move {
+//!         let order: OrderCreated = notification.json()?;
+//!         tracing::info!(order.id, "order created");
+//!         Ok(())
+//!     });
+//! }
+//!
+//! // anywhere, in a transaction or not
+//! listener::notify(&ctx.db, "orders", &json!({ "id": 42 })).await?;
+//! ```
+use std::{collections::BTreeMap, future::Future, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::postgres::PgListener;
+use tokio::task::JoinHandle;
+
+use crate::{app::AppContext, controller::sse::SseBroadcaster, Error, Result};
+
+/// The delay before reconnecting a lost listener connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// A notification received on a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub channel: String,
+    pub payload: String,
+    /// The process id of the database connection which notified
+    pub process_id: u32,
+}
+
+impl Notification {
+    /// Deserializes a JSON payload.
+    ///
+    /// # Errors
+    ///
+    /// When the payload is not the JSON of `T`.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_str(&self.payload)?)
+    }
+}
+
+/// A handler of the notifications of a channel.
+///
+/// Implemented by the async closures taking the [`AppContext`] and the
+/// [`Notification`].
+#[async_trait]
+pub trait NotifyHandler: Send + Sync {
+    /// Handles a notification. Errors are logged.
+    async fn handle(&self, ctx: &AppContext, notification: &Notification) -> Result<()>;
+}
+
+#[async_trait]
+impl<F, Fut> NotifyHandler for F
+where
+    F: Fn(AppContext, Notification) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<()>> + Send,
+{
+    async fn handle(&self, ctx: &AppContext, notification: &Notification) -> Result<()> {
+        self(ctx.clone(), notification.clone()).await
+    }
+}
+
+/// Publishes the payloads to an [`SseBroadcaster`], as events named after
+/// the channel.
+struct ToSse(SseBroadcaster);
+
+#[async_trait]
+impl NotifyHandler for ToSse {
+    async fn handle(&self, _ctx: &AppContext, notification: &Notification) -> Result<()> {
+        self.0
+            .publish(&notification.channel, notification.payload.clone());
+        Ok(())
+    }
+}
+
+/// Broadcasts the payloads to a room of the WebSocket channels, as events
+/// named after the channel.
+#[cfg(feature = "channels")]
+struct ToChannels {
+    channels: crate::channels::Channels,
+    room: String,
+}
+
+#[cfg(feature = "channels")]
+#[async_trait]
+impl NotifyHandler for ToChannels {
+    async fn handle(&self, _ctx: &AppContext, notification: &Notification) -> Result<()> {
+        // JSON payloads are sent as is, others as strings
+        let data = serde_json::from_str(&notification.payload)
+            .unwrap_or_else(|_| serde_json::Value::String(notification.payload.clone()));
+        self.channels
+            .broadcast(&self.room, &notification.channel, data)
+            .await
+    }
+}
+
+/// The handlers of the notifications, by channel.
+#[derive(Default, Clone)]
+pub struct Listeners {
+    handlers: BTreeMap<String, Vec<Arc<dyn NotifyHandler>>>,
+}
+
+impl Listeners {
+    /// Registers a handler of the notifications of a channel. The handlers of
+    /// a channel run in their registration order.
+    pub fn on(&mut self, channel: &str, handler: impl NotifyHandler + 'static) -> &mut Self {
+        self.handlers
+            .entry(channel.to_string())
+            .or_default()
+            .push(Arc::new(handler));
+        self
+    }
+
+    /// Forwards the payloads of a channel to the subscribers of an
+    /// [`SseBroadcaster`].
+    pub fn forward_to_sse(&mut self, channel: &str, broadcaster: SseBroadcaster) -> &mut Self {
+        self.on(channel, ToSse(broadcaster))
+    }
+
+    /// Forwards the payloads of a channel to the sockets of a room.
+    #[cfg(feature = "channels")]
+    pub fn forward_to_channels(
+        &mut self,
+        channel: &str,
+        channels: crate::channels::Channels,
+        room: &str,
+    ) -> &mut Self {
+        self.on(
+            channel,
+            ToChannels {
+                channels,
+                room: room.to_string(),
+            },
+        )
+    }
+
+    /// The channels with handlers.
+    #[must_use]
+    pub fn channels(&self) -> Vec<String> {
+        self.handlers.keys().cloned().collect()
+    }
+
+    /// Runs the handlers of the channel of a notification, logging their
+    /// errors.
+    pub async fn dispatch(&self, ctx: &AppContext, notification: &Notification) {
+        let Some(handlers) = self.handlers.get(&notification.channel) else {
+            return;
+        };
+        for handler in handlers {
+            if let Err(err) = handler.handle(ctx, notification).await {
+                tracing::error!(
+                    channel = notification.channel,
+                    error = %err,
+                    "notification handler failed"
+                );
+            }
+        }
+    }
+
+    /// Listens to the channels with handlers, dispatching their notifications
+    /// until the returned task is aborted. Nothing is started when there is
+    /// no handler.
+    ///
+    /// # Errors
+    ///
+    /// When the database is not Postgres, or the listener can not connect.
+    pub async fn start(self, ctx: &AppContext) -> Result<Option<JoinHandle<()>>> {
+        if self.handlers.is_empty() {
+            return Ok(None);
+        }
+        if ctx.db.get_database_backend() != DatabaseBackend::Postgres {
+            return Err(Error::string(
+                "database notifications require a Postgres database",
+            ));
+        }
+
+        let mut listener = PgListener::connect_with(ctx.db.get_postgres_connection_pool())
+            .await
+            .map_err(Error::wrap)?;
+        let channels = self.channels();
+        listener
+            .listen_all(channels.iter().map(String::as_str))
+            .await
+            .map_err(Error::wrap)?;
+        tracing::info!(?channels, "listening to database notifications");
+
+        let ctx = ctx.clone();
+        Ok(Some(tokio::spawn(async move {
+            loop {
+                // the connection is reestablished by the next call after an error
+                match listener.recv().await {
+                    Ok(notification) => {
+                        let notification = Notification {
+                            channel: notification.channel().to_string(),
+                            payload: notification.payload().to_string(),
+                            process_id: notification.process_id(),
+                        };
+                        self.dispatch(&ctx, &notification).await;
+                    }
+                    Err(err) => {
+                        tracing::error!(error = %err, "database notifications listener failed");
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        })))
+    }
+}
+
+/// Notifies a channel with a JSON payload. Inside a transaction, the
+/// notification is sent when it commits.
+///
+/// # Errors
+///
+/// When the database is not Postgres, or the notification fails.
+pub async fn notify<C, T>(db: &C, channel: &str, payload: &T) -> Result<()>
+where
+    C: ConnectionTrait,
+    T: Serialize + Sync,
+{
+    if db.get_database_backend() != DatabaseBackend::Postgres {
+        return Err(Error::string(
+            "database notifications require a Postgres database",
+        ));
+    }
+    db.execute(Statement::from_sql_and_values(
+        DatabaseBackend::Postgres,
+        "SELECT pg_notify($1, $2)",
+        [channel.into(), serde_json::to_string(payload)?.into()],
+    ))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures_util::StreamExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[tokio::test]
+    async fn can_dispatch_notifications() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let received = Arc::new(Mutex::new(vec![]));
+        let broadcaster = SseBroadcaster::default();
+        let mut events = Box::pin(broadcaster.subscribe());
+
+        let mut listeners = Listeners::default();
+        let handled = received.clone();
+        listeners
+            .on(
+                "orders",
+                move |_ctx: AppContext, notification: Notification| {
+                    let handled = handled.clone();
+                    async move {
+                        let order: serde_json::Value = notification.json()?;
+                        handled.lock().unwrap().push(order["id"].clone());
+                        Ok(())
+                    }
+                },
+            )
+            .on(
+                "orders",
+                |_ctx: AppContext, _notification: Notification| async {
+                    Err::<(), _>(Error::string("failing handlers don't stop the others"))
+                },
+            )
+            .forward_to_sse("orders", broadcaster.clone());
+        assert_eq!(listeners.channels(), vec!["orders"]);
+
+        for channel in ["orders", "users"] {
+            listeners
+                .dispatch(
+                    &ctx,
+                    &Notification {
+                        channel: channel.to_string(),
+                        payload: r#"{"id":42}"#.to_string(),
+                        process_id: 1,
+                    },
+                )
+                .await;
+        }
+
+        assert_eq!(*received.lock().unwrap(), vec![serde_json::json!(42)]);
+        assert!(events.next().await.is_some());
+
+        // the notifications are Postgres only
+        assert!(listeners.start(&ctx).await.is_err());
+        assert!(Listeners::default().start(&ctx).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,174 @@
+//! This module defines the [`Attachment`]s of the emails: files attached to
+//! an email, or images shown inline by its HTML through their `cid:` URL.
+//!
+//! # Example
+//!
+//! ```rust, ignore
+//! use loco_rs::mailer::Attachment;
+//!
+//! let attachments = vec![
+//!     Attachment::from_bytes("invoice.pdf", "application/pdf", pdf),
+//!     // shown by `<img src="cid:logo">`
+//!     Attachment::from_bytes("logo.png", "image/png", include_bytes!("logo.png").to_vec())
+//!         .inline("logo"),
+//!     // read from the storage when the email is sent
+//!     Attachment::from_storage("reports/2025-01.csv", "text/csv"),
+//! ];
+//! ```
+
+use std::path::Path;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lettre::message::{header::ContentType, SinglePart};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{errors::Error, storage::Storage, Result};
+
+/// A file attached to an email.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// The name of the file
+    pub filename: String,
+    /// The MIME type of the file, such as `application/pdf`
+    pub content_type: String,
+    /// The content id of an inline attachment, referenced from the HTML of
+    /// the email as `cid:<content_id>`
+    pub content_id: Option<String>,
+    /// The content of the file
+    pub body: AttachmentBody,
+}
+
+/// The content of an [`Attachment`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentBody {
+    /// The bytes of the file, kept in base64 in the mailer jobs
+    Bytes(#[serde(serialize_with = "to_base64", deserialize_with = "from_base64")] Vec<u8>),
+    /// The path of a file of the storage, read when the email is sent
+    Storage(String),
+}
+
+impl Attachment {
+    /// Attaches the given bytes.
+    #[must_use]
+    pub fn from_bytes(filename: &str, content_type: &str, bytes: Vec<u8>) -> Self {
+        Self {
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            content_id: None,
+            body: AttachmentBody::Bytes(bytes),
+        }
+    }
+
+    /// Attaches a file of the storage, named after its path.
+    #[must_use]
+    pub fn from_storage(path: &str, content_type: &str) -> Self {
+        Self {
+            filename: Path::new(path).file_name().map_or_else(
+                || path.to_string(),
+                |name| name.to_string_lossy().to_string(),
+            ),
+            content_type: content_type.to_string(),
+            content_id: None,
+            body: AttachmentBody::Storage(path.to_string()),
+        }
+    }
+
+    /// Shows the attachment inline, referenced from the HTML of the email as
+    /// `cid:<content_id>`.
+    #[must_use]
+    pub fn inline(mut self, content_id: &str) -> Self {
+        self.content_id = Some(content_id.to_string());
+        self
+    }
+
+    /// Reads the attachment from the storage, when it is a storage one.
+    ///
+    /// # Errors
+    ///
+    /// When the file could not be read from the storage
+    pub async fn resolve(&mut self, storage: &Storage) -> Result<()> {
+        if let AttachmentBody::Storage(path) = &self.body {
+            let bytes: Vec<u8> = storage.download(Path::new(path)).await?;
+            self.body = AttachmentBody::Bytes(bytes);
+        }
+        Ok(())
+    }
+
+    /// The bytes of the attachment.
+    ///
+    /// # Errors
+    ///
+    /// When the attachment was not read from the storage
+    pub fn bytes(&self) -> Result<&[u8]> {
+        match &self.body {
+            AttachmentBody::Bytes(bytes) => Ok(bytes),
+            AttachmentBody::Storage(path) => Err(Error::Message(format!(
+                "attachment `{path}` was not read from the storage"
+            ))),
+        }
+    }
+
+    /// The MIME part of the attachment.
+    ///
+    /// # Errors
+    ///
+    /// When its content type is invalid, or it was not read from the storage
+    pub(crate) fn part(&self) -> Result<SinglePart> {
+        let content_type = ContentType::parse(&self.content_type).map_err(|_| {
+            Error::Message(format!(
+                "invalid content type `{}` of attachment `{}`",
+                self.content_type, self.filename
+            ))
+        })?;
+        let attachment = match &self.content_id {
+            Some(content_id) => lettre::message::Attachment::new_inline(content_id.clone()),
+            None => lettre::message::Attachment::new(self.filename.clone()),
+        };
+        Ok(attachment.body(self.bytes()?.to_vec(), content_type))
+    }
+}
+
+fn to_base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(bytes))
+}
+
+fn from_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::drivers::mem;
+
+    #[test]
+    fn can_serialize_attachments() {
+        let attachment =
+            Attachment::from_bytes("logo.png", "image/png", vec![1, 2, 3]).inline("logo");
+
+        let json = serde_json::to_value(&attachment).unwrap();
+        assert_eq!(json["body"]["bytes"], "AQID");
+        assert_eq!(
+            serde_json::from_value::<Attachment>(json).unwrap(),
+            attachment
+        );
+    }
+
+    #[tokio::test]
+    async fn can_resolve_storage_attachments() {
+        let storage = Storage::single(mem::new());
+        storage
+            .upload(Path::new("reports/2025-01.csv"), &bytes::Bytes::from("a,b"))
+            .await
+            .unwrap();
+
+        let mut attachment = Attachment::from_storage("reports/2025-01.csv", "text/csv");
+        assert_eq!(attachment.filename, "2025-01.csv");
+        assert!(attachment.bytes().is_err());
+
+        attachment.resolve(&storage).await.unwrap();
+        assert_eq!(attachment.bytes().unwrap(), b"a,b");
+    }
+}
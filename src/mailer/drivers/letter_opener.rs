@@ -0,0 +1,181 @@
+//! The letter opener driver, writing the emails to disk instead of sending
+//! them, so that they can be read in the browser during development.
+//!
+//! Each email gets a directory of its own under the configured path, with:
+//! * `message.eml`, its MIME message
+//! * `index.html`, its headers and bodies, to open in the browser
+//! * `body.html` and `body.txt`, its HTML and text bodies
+//! * its attachments, the `cid:` URLs of its HTML pointing to the inline ones
+
+use std::{fmt::Write, path::Path};
+
+use async_trait::async_trait;
+
+use super::EmailDriver;
+use crate::{
+    config::LetterOpenerMailer,
+    controller::views::escape,
+    mailer::{Email, Result},
+};
+
+/// Writes the emails to disk.
+#[derive(Debug)]
+pub struct LetterOpener {
+    config: LetterOpenerMailer,
+}
+
+impl LetterOpener {
+    /// Creates a letter opener driver.
+    #[must_use]
+    pub const fn new(config: LetterOpenerMailer) -> Self {
+        Self { config }
+    }
+}
+
+/// Writes the files of an email to its directory.
+async fn write(dir: &Path, email: &Email, message: &lettre::Message) -> Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::write(dir.join("message.eml"), message.formatted()).await?;
+
+    let mut html = email.html.clone();
+    for attachment in &email.attachments {
+        let filename = file_name(&attachment.filename);
+        tokio::fs::write(dir.join(&filename), attachment.bytes()?).await?;
+        if let Some(content_id) = &attachment.content_id {
+            html = html.replace(&format!("cid:{content_id}"), &filename);
+        }
+    }
+    tokio::fs::write(dir.join("body.html"), html).await?;
+    tokio::fs::write(dir.join("body.txt"), &email.text).await?;
+    tokio::fs::write(dir.join("index.html"), index(email)).await?;
+    Ok(())
+}
+
+#[async_trait]
+impl EmailDriver for LetterOpener {
+    async fn deliver(&self, email: &Email, message: &lettre::Message) -> Result<()> {
+        let dir = self.config.path.join(uuid::Uuid::now_v7().to_string());
+        write(&dir, email, message).await?;
+
+        let index = dir.join("index.html");
+        tracing::info!(path = %index.display(), subject = email.subject, "email written");
+        if self.config.open {
+            open(&index);
+        }
+        Ok(())
+    }
+}
+
+/// The name of an attachment file, without its directories.
+fn file_name(filename: &str) -> String {
+    Path::new(filename).file_name().map_or_else(
+        || "attachment".to_string(),
+        |name| name.to_string_lossy().to_string(),
+    )
+}
+
+/// The page showing the headers and bodies of an email.
+fn index(email: &Email) -> String {
+    let mut headers = String::new();
+    for (name, value) in [
+        ("From", email.from.as_deref()),
+        ("To", Some(email.to.as_str())),
+        ("Cc", email.cc.as_deref()),
+        ("Bcc", email.bcc.as_deref()),
+        ("Reply-To", email.reply_to.as_deref()),
+        ("Subject", Some(email.subject.as_str())),
+    ] {
+        if let Some(value) = value {
+            let _ = write!(headers, "<dt>{name}</dt><dd>{}</dd>", escape(value));
+        }
+    }
+    let mut attachments = String::new();
+    for attachment in &email.attachments {
+        let filename = escape(&file_name(&attachment.filename));
+        let _ = write!(
+            attachments,
+            "<li><a href=\"{filename}\">{filename}</a></li>"
+        );
+    }
+    if !attachments.is_empty() {
+        attachments = format!("<h2>Attachments</h2><ul>{attachments}</ul>");
+    }
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{subject}</title><style>body \
+         {{ font-family: system-ui, sans-serif; margin: 2rem; }} dl {{ display: grid; \
+         grid-template-columns: max-content auto; gap: .3rem 1rem; }} dt {{ font-weight: bold; \
+         }} iframe {{ width: 100%; height: 60vh; border: 1px solid #ddd; }} pre {{ white-space: \
+         pre-wrap; }}</style></head><body><dl>{headers}</dl><h2>HTML</h2><iframe \
+         src=\"body.html\"></iframe><h2>Text</h2><pre>{text}</pre>{attachments}</body></html>",
+        subject = escape(&email.subject),
+        text = escape(&email.text),
+    )
+}
+
+/// Opens a file with the default application of the system.
+fn open(path: &Path) {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    if let Err(err) = command.arg(path).spawn() {
+        tracing::warn!(err = %err, path = %path.display(), "could not open the email");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mailer::Attachment;
+
+    #[tokio::test]
+    async fn can_write_emails() {
+        let tree = tree_fs::TreeBuilder::default().create().unwrap();
+        let letter_opener = LetterOpener::new(LetterOpenerMailer {
+            path: tree.root.join("mails"),
+            open: false,
+        });
+        let email = Email {
+            from: Some("System <system@example.com>".to_string()),
+            to: "user1@example.com".to_string(),
+            subject: "Welcome <user1>".to_string(),
+            text: "Hello".to_string(),
+            html: "<img src=\"cid:logo\">".to_string(),
+            attachments: vec![
+                Attachment::from_bytes("logo.png", "image/png", vec![1, 2, 3]).inline("logo"),
+            ],
+            ..Default::default()
+        };
+        let message = lettre::Message::builder()
+            .from("System <system@example.com>".parse().unwrap())
+            .to("user1@example.com".parse().unwrap())
+            .subject("Welcome <user1>")
+            .body("Hello".to_string())
+            .unwrap();
+
+        letter_opener.deliver(&email, &message).await.unwrap();
+
+        let dirs = std::fs::read_dir(tree.root.join("mails"))
+            .unwrap()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(dirs.len(), 1);
+        let dir = dirs[0].path();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("body.html")).unwrap(),
+            "<img src=\"logo.png\">"
+        );
+        assert_eq!(std::fs::read(dir.join("logo.png")).unwrap(), vec![1, 2, 3]);
+        assert!(std::fs::read_to_string(dir.join("message.eml"))
+            .unwrap()
+            .contains("Subject: Welcome <user1>"));
+        assert!(std::fs::read_to_string(dir.join("index.html"))
+            .unwrap()
+            .contains("<dt>Subject</dt><dd>Welcome &lt;user1&gt;</dd>"));
+    }
+}
@@ -0,0 +1,157 @@
+//! The Mailgun driver, delivering the emails through its messages API.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use reqwest::multipart::{Form, Part};
+
+use super::{check, headers, EmailDriver};
+use crate::{
+    config::MailgunMailer,
+    mailer::{Email, Result},
+};
+
+/// Delivers the emails through Mailgun.
+pub struct Mailgun {
+    config: MailgunMailer,
+    client: reqwest::Client,
+}
+
+impl fmt::Debug for Mailgun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mailgun")
+            .field("endpoint", &self.config.endpoint)
+            .field("domain", &self.config.domain)
+            .field("test_mode", &self.config.test_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Mailgun {
+    /// Creates a Mailgun driver.
+    ///
+    /// # Errors
+    ///
+    /// When the HTTP client could not be built
+    pub fn new(config: MailgunMailer) -> Result<Self> {
+        Ok(Self {
+            config,
+            client: reqwest::Client::builder().build()?,
+        })
+    }
+
+    /// The form of the messages request of an email.
+    fn form(&self, email: &Email) -> Vec<(String, String)> {
+        let mut form = vec![
+            ("from".to_string(), email.from.clone().unwrap_or_default()),
+            ("to".to_string(), email.to.clone()),
+            ("subject".to_string(), email.subject.clone()),
+            ("text".to_string(), email.text.clone()),
+            ("html".to_string(), email.html.clone()),
+        ];
+        if let Some(cc) = &email.cc {
+            form.push(("cc".to_string(), cc.clone()));
+        }
+        if let Some(bcc) = &email.bcc {
+            form.push(("bcc".to_string(), bcc.clone()));
+        }
+        if let Some(reply_to) = &email.reply_to {
+            form.push(("h:Reply-To".to_string(), reply_to.clone()));
+        }
+        for (name, value) in headers(email) {
+            form.push((format!("h:{name}"), value));
+        }
+        if self.config.test_mode {
+            form.push(("o:testmode".to_string(), "yes".to_string()));
+        }
+        if let Some(tracking) = self.config.tracking {
+            form.push((
+                "o:tracking".to_string(),
+                if tracking { "yes" } else { "no" }.to_string(),
+            ));
+        }
+        form
+    }
+
+    /// The multipart form of the messages request of an email, with its
+    /// attachments.
+    fn multipart(&self, email: &Email) -> Result<Form> {
+        let mut form = self
+            .form(email)
+            .into_iter()
+            .fold(Form::new(), |form, (name, value)| form.text(name, value));
+        for attachment in &email.attachments {
+            // the inline attachments are referenced by their file name
+            let (field, filename) = match &attachment.content_id {
+                Some(content_id) => ("inline", content_id.clone()),
+                None => ("attachment", attachment.filename.clone()),
+            };
+            let part = Part::bytes(attachment.bytes()?.to_vec())
+                .file_name(filename)
+                .mime_str(&attachment.content_type)?;
+            form = form.part(field, part);
+        }
+        Ok(form)
+    }
+}
+
+#[async_trait]
+impl EmailDriver for Mailgun {
+    async fn deliver(&self, email: &Email, _message: &lettre::Message) -> Result<()> {
+        let response = self
+            .client
+            .post(format!(
+                "{}/v3/{}/messages",
+                self.config.endpoint, self.config.domain
+            ))
+            .basic_auth("api", Some(&self.config.api_key))
+            .multipart(self.multipart(email)?)
+            .send()
+            .await?;
+        check("mailgun", response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mailer::EmailHeaders;
+
+    #[test]
+    fn can_build_form() {
+        let mailgun = Mailgun::new(MailgunMailer {
+            api_key: "key".to_string(),
+            domain: "mg.example.com".to_string(),
+            endpoint: "https://api.mailgun.net".to_string(),
+            test_mode: true,
+            tracking: Some(false),
+        })
+        .unwrap();
+        let email = Email {
+            from: Some("System <system@example.com>".to_string()),
+            to: "user1@example.com".to_string(),
+            subject: "Welcome".to_string(),
+            text: "Hello".to_string(),
+            html: "<p>Hello</p>".to_string(),
+            bcc: Some("audit@example.com".to_string()),
+            headers: Some(EmailHeaders {
+                in_reply_to: Some("<a@example.com>".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let form = mailgun.form(&email);
+        let field = |name: &str| {
+            form.iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(field("from"), Some("System <system@example.com>"));
+        assert_eq!(field("bcc"), Some("audit@example.com"));
+        assert_eq!(field("h:In-Reply-To"), Some("<a@example.com>"));
+        assert_eq!(field("o:testmode"), Some("yes"));
+        assert_eq!(field("o:tracking"), Some("no"));
+        assert_eq!(field("cc"), None);
+    }
+}
@@ -0,0 +1,144 @@
+//! # Email Drivers
+//!
+//! The HTTP API backends of the mailer, selected by `mailer.kind` and
+//! configured by the section of the same name, so that switching providers
+//! only takes a configuration change:
+//!
+//! * `ses`: Amazon SES, with the `mailer_ses` feature
+//! * `sendgrid`, `mailgun` and `postmark`, with the `mailer_http` feature
+//! * `letter_opener`, writing the emails to disk during development
+//!
+//! Any other backend plugs in by implementing [`EmailDriver`] and building
+//! the mailer of the context with [`super::EmailSender::driver`].
+
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use lettre::Message;
+
+use super::{Email, Result};
+use crate::{config, Error};
+
+pub mod letter_opener;
+#[cfg(feature = "mailer_http")]
+pub mod mailgun;
+#[cfg(feature = "mailer_http")]
+pub mod postmark;
+#[cfg(feature = "mailer_http")]
+pub mod sendgrid;
+#[cfg(feature = "mailer_ses")]
+pub mod ses;
+
+/// Delivers the emails through a provider.
+#[async_trait]
+pub trait EmailDriver: Send + Sync + fmt::Debug {
+    /// Delivers an email, its sender set. The email is also given as its
+    /// MIME message, for the providers taking raw messages.
+    ///
+    /// # Errors
+    ///
+    /// When the provider rejects the email, or could not be reached
+    async fn deliver(&self, email: &Email, message: &Message) -> Result<()>;
+}
+
+/// Creates the driver of the `mailer.kind` API backend.
+///
+/// # Errors
+///
+/// When the section of the backend is missing, or its feature is not
+/// enabled
+#[allow(unused_variables, clippy::unused_async)]
+pub async fn create(
+    kind: config::MailerKind,
+    config: &config::Mailer,
+) -> Result<Arc<dyn EmailDriver>> {
+    let missing =
+        |section: &str| Error::Message(format!("mailer: the `{section}` section is missing"));
+    match kind {
+        config::MailerKind::Smtp => Err(Error::string("mailer: smtp is not an API backend")),
+        #[cfg(feature = "mailer_ses")]
+        config::MailerKind::Ses => Ok(Arc::new(
+            ses::Ses::new(config.ses.as_ref().ok_or_else(|| missing("ses"))?).await,
+        )),
+        #[cfg(feature = "mailer_http")]
+        config::MailerKind::Sendgrid => Ok(Arc::new(sendgrid::Sendgrid::new(
+            config.sendgrid.clone().ok_or_else(|| missing("sendgrid"))?,
+        )?)),
+        #[cfg(feature = "mailer_http")]
+        config::MailerKind::Mailgun => Ok(Arc::new(mailgun::Mailgun::new(
+            config.mailgun.clone().ok_or_else(|| missing("mailgun"))?,
+        )?)),
+        #[cfg(feature = "mailer_http")]
+        config::MailerKind::Postmark => Ok(Arc::new(postmark::Postmark::new(
+            config.postmark.clone().ok_or_else(|| missing("postmark"))?,
+        )?)),
+        config::MailerKind::LetterOpener => Ok(Arc::new(letter_opener::LetterOpener::new(
+            config.letter_opener.clone().unwrap_or_default(),
+        ))),
+        #[allow(unreachable_patterns)]
+        kind => Err(Error::Message(format!(
+            "mailer: the {kind:?} backend requires the `{}` feature",
+            if kind == config::MailerKind::Ses {
+                "mailer_ses"
+            } else {
+                "mailer_http"
+            }
+        ))),
+    }
+}
+
+/// The custom headers of an email.
+#[cfg(any(feature = "mailer_http", test))]
+fn headers(email: &Email) -> Vec<(&'static str, String)> {
+    let Some(headers) = &email.headers else {
+        return vec![];
+    };
+    [
+        ("References", &headers.references),
+        ("In-Reply-To", &headers.in_reply_to),
+        ("Message-ID", &headers.message_id),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| value.clone().map(|value| (name, value)))
+    .collect()
+}
+
+/// Fails with the body of the response when the provider rejected the
+/// email.
+#[cfg(feature = "mailer_http")]
+async fn check(provider: &str, response: reqwest::Response) -> Result<()> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = response.text().await.unwrap_or_default();
+    tracing::error!(provider, status = %status, body, "email delivery rejected");
+    Err(Error::Message(format!(
+        "{provider} rejected the email ({status}): {body}"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mailer::EmailHeaders;
+
+    #[test]
+    fn can_list_headers() {
+        let mut email = Email::default();
+        assert!(headers(&email).is_empty());
+
+        email.headers = Some(EmailHeaders {
+            references: Some("<a@example.com>".to_string()),
+            in_reply_to: None,
+            message_id: Some("<b@example.com>".to_string()),
+        });
+        assert_eq!(
+            headers(&email),
+            vec![
+                ("References", "<a@example.com>".to_string()),
+                ("Message-ID", "<b@example.com>".to_string()),
+            ]
+        );
+    }
+}
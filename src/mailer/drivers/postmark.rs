@@ -0,0 +1,169 @@
+//! The Postmark driver, delivering the emails through its email API.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+
+use super::{check, headers, EmailDriver};
+use crate::{
+    config::PostmarkMailer,
+    mailer::{Email, Result},
+};
+
+/// Delivers the emails through Postmark.
+pub struct Postmark {
+    config: PostmarkMailer,
+    client: reqwest::Client,
+}
+
+impl fmt::Debug for Postmark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Postmark")
+            .field("endpoint", &self.config.endpoint)
+            .field("message_stream", &self.config.message_stream)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Postmark {
+    /// Creates a Postmark driver.
+    ///
+    /// # Errors
+    ///
+    /// When the HTTP client could not be built
+    pub fn new(config: PostmarkMailer) -> Result<Self> {
+        Ok(Self {
+            config,
+            client: reqwest::Client::builder().build()?,
+        })
+    }
+
+    /// The body of the email request of an email.
+    fn body(&self, email: &Email) -> Result<Value> {
+        let mut body = json!({
+            "From": email.from.clone().unwrap_or_default(),
+            "To": email.to,
+            "Subject": email.subject,
+            "TextBody": email.text,
+            "HtmlBody": email.html,
+        });
+        if let Some(cc) = &email.cc {
+            body["Cc"] = Value::from(cc.as_str());
+        }
+        if let Some(bcc) = &email.bcc {
+            body["Bcc"] = Value::from(bcc.as_str());
+        }
+        if let Some(reply_to) = &email.reply_to {
+            body["ReplyTo"] = Value::from(reply_to.as_str());
+        }
+        let headers = headers(email);
+        if !headers.is_empty() {
+            body["Headers"] = headers
+                .into_iter()
+                .map(|(name, value)| json!({"Name": name, "Value": value}))
+                .collect();
+        }
+        if !email.attachments.is_empty() {
+            body["Attachments"] = email
+                .attachments
+                .iter()
+                .map(|attachment| {
+                    let mut part = json!({
+                        "Name": attachment.filename,
+                        "Content": STANDARD.encode(attachment.bytes()?),
+                        "ContentType": attachment.content_type,
+                    });
+                    if let Some(content_id) = &attachment.content_id {
+                        part["ContentID"] = Value::from(format!("cid:{content_id}"));
+                    }
+                    Ok(part)
+                })
+                .collect::<Result<Value>>()?;
+        }
+        if let Some(stream) = &self.config.message_stream {
+            body["MessageStream"] = Value::from(stream.as_str());
+        }
+        if let Some(track_opens) = self.config.track_opens {
+            body["TrackOpens"] = Value::from(track_opens);
+        }
+        if let Some(track_links) = &self.config.track_links {
+            body["TrackLinks"] = Value::from(track_links.as_str());
+        }
+        Ok(body)
+    }
+}
+
+#[async_trait]
+impl EmailDriver for Postmark {
+    async fn deliver(&self, email: &Email, _message: &lettre::Message) -> Result<()> {
+        let response = self
+            .client
+            .post(format!("{}/email", self.config.endpoint))
+            .header("X-Postmark-Server-Token", &self.config.server_token)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .json(&self.body(email)?)
+            .send()
+            .await?;
+        check("postmark", response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mailer::EmailHeaders;
+
+    #[test]
+    fn can_build_body() {
+        let postmark = Postmark::new(PostmarkMailer {
+            server_token: "POSTMARK_API_TEST".to_string(),
+            endpoint: "https://api.postmarkapp.com".to_string(),
+            message_stream: Some("outbound".to_string()),
+            track_opens: Some(true),
+            track_links: None,
+        })
+        .unwrap();
+        let email = Email {
+            from: Some("System <system@example.com>".to_string()),
+            to: "user1@example.com".to_string(),
+            cc: Some("user2@example.com".to_string()),
+            subject: "Welcome".to_string(),
+            text: "Hello".to_string(),
+            html: "<p>Hello</p>".to_string(),
+            headers: Some(EmailHeaders {
+                references: Some("<a@example.com>".to_string()),
+                ..Default::default()
+            }),
+            attachments: vec![crate::mailer::Attachment::from_bytes(
+                "logo.png",
+                "image/png",
+                vec![1, 2, 3],
+            )
+            .inline("logo")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            postmark.body(&email).unwrap(),
+            json!({
+                "From": "System <system@example.com>",
+                "To": "user1@example.com",
+                "Cc": "user2@example.com",
+                "Subject": "Welcome",
+                "TextBody": "Hello",
+                "HtmlBody": "<p>Hello</p>",
+                "Headers": [{"Name": "References", "Value": "<a@example.com>"}],
+                "Attachments": [{
+                    "Name": "logo.png",
+                    "Content": "AQID",
+                    "ContentType": "image/png",
+                    "ContentID": "cid:logo",
+                }],
+                "MessageStream": "outbound",
+                "TrackOpens": true,
+            })
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! The `SendGrid` driver, delivering the emails through its v3 mail send API.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lettre::message::{Mailbox, Mailboxes};
+use serde_json::{json, Map, Value};
+
+use super::{check, headers, EmailDriver};
+use crate::{
+    config::SendgridMailer,
+    mailer::{Email, Result},
+};
+
+/// Delivers the emails through `SendGrid`.
+pub struct Sendgrid {
+    config: SendgridMailer,
+    client: reqwest::Client,
+}
+
+impl fmt::Debug for Sendgrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sendgrid")
+            .field("endpoint", &self.config.endpoint)
+            .field("sandbox", &self.config.sandbox)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Sendgrid {
+    /// Creates a `SendGrid` driver.
+    ///
+    /// # Errors
+    ///
+    /// When the HTTP client could not be built
+    pub fn new(config: SendgridMailer) -> Result<Self> {
+        Ok(Self {
+            config,
+            client: reqwest::Client::builder().build()?,
+        })
+    }
+
+    /// The body of the mail send request of an email.
+    fn body(&self, email: &Email) -> Result<Value> {
+        let mut personalization = Map::new();
+        personalization.insert("to".to_string(), addresses(&email.to)?);
+        if let Some(cc) = &email.cc {
+            personalization.insert("cc".to_string(), addresses(cc)?);
+        }
+        if let Some(bcc) = &email.bcc {
+            personalization.insert("bcc".to_string(), addresses(bcc)?);
+        }
+
+        let mut body = json!({
+            "personalizations": [personalization],
+            "from": address(&email.from.clone().unwrap_or_default().parse()?),
+            "subject": email.subject,
+            "content": [
+                {"type": "text/plain", "value": email.text},
+                {"type": "text/html", "value": email.html},
+            ],
+        });
+        if let Some(reply_to) = &email.reply_to {
+            body["reply_to"] = address(&reply_to.parse()?);
+        }
+        let headers = headers(email);
+        if !headers.is_empty() {
+            body["headers"] = headers
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), Value::from(value)))
+                .collect::<Map<_, _>>()
+                .into();
+        }
+        if !email.attachments.is_empty() {
+            body["attachments"] = email
+                .attachments
+                .iter()
+                .map(|attachment| {
+                    let mut part = json!({
+                        "content": STANDARD.encode(attachment.bytes()?),
+                        "filename": attachment.filename,
+                        "type": attachment.content_type,
+                        "disposition": "attachment",
+                    });
+                    if let Some(content_id) = &attachment.content_id {
+                        part["disposition"] = Value::from("inline");
+                        part["content_id"] = Value::from(content_id.as_str());
+                    }
+                    Ok(part)
+                })
+                .collect::<Result<Value>>()?;
+        }
+        if self.config.sandbox {
+            body["mail_settings"] = json!({"sandbox_mode": {"enable": true}});
+        }
+        let mut tracking = Map::new();
+        if let Some(enable) = self.config.click_tracking {
+            tracking.insert("click_tracking".to_string(), json!({"enable": enable}));
+        }
+        if let Some(enable) = self.config.open_tracking {
+            tracking.insert("open_tracking".to_string(), json!({"enable": enable}));
+        }
+        if !tracking.is_empty() {
+            body["tracking_settings"] = tracking.into();
+        }
+        Ok(body)
+    }
+}
+
+fn address(mailbox: &Mailbox) -> Value {
+    match &mailbox.name {
+        Some(name) => json!({"email": mailbox.email.to_string(), "name": name}),
+        None => json!({"email": mailbox.email.to_string()}),
+    }
+}
+
+fn addresses(list: &str) -> Result<Value> {
+    Ok(list.parse::<Mailboxes>()?.iter().map(address).collect())
+}
+
+#[async_trait]
+impl EmailDriver for Sendgrid {
+    async fn deliver(&self, email: &Email, _message: &lettre::Message) -> Result<()> {
+        let response = self
+            .client
+            .post(format!("{}/v3/mail/send", self.config.endpoint))
+            .bearer_auth(&self.config.api_key)
+            .json(&self.body(email)?)
+            .send()
+            .await?;
+        check("sendgrid", response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_build_body() {
+        let sendgrid = Sendgrid::new(SendgridMailer {
+            api_key: "key".to_string(),
+            endpoint: "https://api.sendgrid.com".to_string(),
+            sandbox: true,
+            click_tracking: Some(false),
+            open_tracking: None,
+        })
+        .unwrap();
+        let email = Email {
+            from: Some("System <system@example.com>".to_string()),
+            to: "user1@example.com, User 2 <user2@example.com>".to_string(),
+            reply_to: Some("support@example.com".to_string()),
+            subject: "Welcome".to_string(),
+            text: "Hello".to_string(),
+            html: "<p>Hello</p>".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sendgrid.body(&email).unwrap(),
+            json!({
+                "personalizations": [{
+                    "to": [
+                        {"email": "user1@example.com"},
+                        {"email": "user2@example.com", "name": "User 2"},
+                    ],
+                }],
+                "from": {"email": "system@example.com", "name": "System"},
+                "reply_to": {"email": "support@example.com"},
+                "subject": "Welcome",
+                "content": [
+                    {"type": "text/plain", "value": "Hello"},
+                    {"type": "text/html", "value": "<p>Hello</p>"},
+                ],
+                "mail_settings": {"sandbox_mode": {"enable": true}},
+                "tracking_settings": {"click_tracking": {"enable": false}},
+            })
+        );
+    }
+}
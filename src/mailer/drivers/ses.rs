@@ -0,0 +1,70 @@
+//! The Amazon SES driver, delivering the MIME messages of the emails through
+//! the SES v2 API, with the credentials of the AWS environment.
+
+use async_trait::async_trait;
+use aws_sdk_sesv2::{
+    primitives::Blob,
+    types::{Destination, EmailContent, RawMessage},
+};
+
+use super::EmailDriver;
+use crate::{
+    config::SesMailer,
+    mailer::{Email, Result},
+    Error,
+};
+
+/// Delivers the emails through Amazon SES.
+#[derive(Debug)]
+pub struct Ses {
+    client: aws_sdk_sesv2::Client,
+    configuration_set: Option<String>,
+}
+
+impl Ses {
+    /// Creates an SES driver, loading the AWS configuration of the
+    /// environment.
+    pub async fn new(config: &SesMailer) -> Self {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = &config.region {
+            loader = loader.region(aws_config::Region::new(region.clone()));
+        }
+        if let Some(endpoint) = &config.endpoint {
+            loader = loader.endpoint_url(endpoint);
+        }
+        Self {
+            client: aws_sdk_sesv2::Client::new(&loader.load().await),
+            configuration_set: config.configuration_set.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailDriver for Ses {
+    async fn deliver(&self, _email: &Email, message: &lettre::Message) -> Result<()> {
+        let envelope = message.envelope();
+        // the raw message has no `Bcc` header, the envelope has every recipient
+        let destination = Destination::builder()
+            .set_to_addresses(Some(
+                envelope.to().iter().map(ToString::to_string).collect(),
+            ))
+            .build();
+        let raw = RawMessage::builder()
+            .data(Blob::new(message.formatted()))
+            .build()
+            .map_err(Error::wrap)?;
+        self.client
+            .send_email()
+            .set_from_email_address(envelope.from().map(ToString::to_string))
+            .destination(destination)
+            .content(EmailContent::builder().raw(raw).build())
+            .set_configuration_set_name(self.configuration_set.clone())
+            .send()
+            .await
+            .map_err(|err| {
+                tracing::error!(err.msg = %err, err.detail = ?err, "ses_send_error");
+                Error::wrap(err)
+            })?;
+        Ok(())
+    }
+}
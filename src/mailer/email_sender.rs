@@ -1,6 +1,9 @@
 //! This module defines an [`EmailSender`] responsible for sending emails using
-//! either the SMTP protocol. It includes an asynchronous method `mail` for
-//! sending emails with options like sender, recipient, subject, and content.
+//! either the SMTP protocol or an [`EmailDriver`] API. It includes an
+//! asynchronous method `mail` for sending emails with options like sender,
+//! recipient, subject, and content.
+
+use std::sync::Arc;
 
 use lettre::{
     message::{header, MultiPart},
@@ -9,7 +12,7 @@ use lettre::{
 };
 use tracing::error;
 
-use super::{Email, Result, DEFAULT_FROM_SENDER};
+use super::{drivers::EmailDriver, Email, Result, DEFAULT_FROM_SENDER};
 use crate::{config, errors::Error};
 
 /// An enumeration representing the possible transport methods for sending
@@ -20,6 +23,8 @@ pub enum EmailTransport {
     Smtp(lettre::AsyncSmtpTransport<lettre::Tokio1Executor>),
     /// Test/stub transport for testing purposes.
     Test(lettre::transport::stub::StubTransport),
+    /// An HTTP API, such as SES or Postmark.
+    Driver(Arc<dyn EmailDriver>),
 }
 
 /// A structure representing the email sender, encapsulating the chosen
@@ -36,6 +41,31 @@ pub struct Deliveries {
     pub messages: Vec<String>,
 }
 
+/// The body of an email: its text and HTML alternatives, related to its
+/// inline attachments, and mixed with its other attachments.
+fn content(email: &Email) -> Result<MultiPart> {
+    let mut content = MultiPart::alternative_plain_html(email.text.clone(), email.html.clone());
+    let (inline, attached): (Vec<_>, Vec<_>) = email
+        .attachments
+        .iter()
+        .partition(|attachment| attachment.content_id.is_some());
+    if !inline.is_empty() {
+        let mut related = MultiPart::related().multipart(content);
+        for attachment in inline {
+            related = related.singlepart(attachment.part()?);
+        }
+        content = related;
+    }
+    if !attached.is_empty() {
+        let mut mixed = MultiPart::mixed().multipart(content);
+        for attachment in attached {
+            mixed = mixed.singlepart(attachment.part()?);
+        }
+        content = mixed;
+    }
+    Ok(content)
+}
+
 impl EmailSender {
     /// Creates a new `EmailSender` using the SMTP transport method based on the
     /// provided SMTP configuration.
@@ -70,6 +100,14 @@ impl EmailSender {
         })
     }
 
+    /// Creates a new `EmailSender` delivering the emails through an API.
+    #[must_use]
+    pub fn driver(driver: Arc<dyn EmailDriver>) -> Self {
+        Self {
+            transport: EmailTransport::Driver(driver),
+        }
+    }
+
     #[must_use]
     pub fn stub() -> Self {
         Self {
@@ -101,7 +139,7 @@ impl EmailSender {
     /// When email doesn't send successfully or has an error to build the
     /// message
     pub async fn mail(&self, email: &Email) -> Result<()> {
-        let content = MultiPart::alternative_plain_html(email.text.clone(), email.html.clone());
+        let content = content(email)?;
         let mut builder = Message::builder()
             .from(
                 email
@@ -152,6 +190,13 @@ impl EmailSender {
                 xp.send(&msg)
                     .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
             }
+            EmailTransport::Driver(driver) => {
+                let mut email = email.clone();
+                email
+                    .from
+                    .get_or_insert_with(|| DEFAULT_FROM_SENDER.to_string());
+                driver.deliver(&email, &msg).await?;
+            }
         }
         Ok(())
     }
@@ -190,6 +235,9 @@ mod tests {
             bcc: None,
             cc: None,
             headers: None,
+            attachments: vec![],
+            locals: None,
+            delivery: None,
         };
         assert!(sender.mail(&data).await.is_ok());
 
@@ -232,6 +280,9 @@ mod tests {
             bcc: None,
             cc: None,
             headers: Some(headers),
+            attachments: vec![],
+            locals: None,
+            delivery: None,
         };
         assert!(sender.mail(&data).await.is_ok());
 
@@ -242,4 +293,40 @@ mod tests {
             assert_debug_snapshot!(stub.messages());
         });
     }
+
+    #[tokio::test]
+    async fn can_send_email_with_attachments() {
+        let stub = StubTransport::new_ok();
+
+        let sender = EmailSender {
+            transport: EmailTransport::Test(stub.clone()),
+        };
+
+        let data = Email {
+            from: Some("test@framework.com".to_string()),
+            to: "user1@framework.com".to_string(),
+            subject: "Your invoice".to_string(),
+            text: "Your invoice is attached".to_string(),
+            html: "<img src=\"cid:logo\"><p>Your invoice is attached</p>".to_string(),
+            attachments: vec![
+                crate::mailer::Attachment::from_bytes(
+                    "invoice.pdf",
+                    "application/pdf",
+                    b"%PDF".to_vec(),
+                ),
+                crate::mailer::Attachment::from_bytes("logo.png", "image/png", vec![137, 80])
+                    .inline("logo"),
+            ],
+            ..Default::default()
+        };
+        assert!(sender.mail(&data).await.is_ok());
+
+        let messages = stub.messages();
+        let (_, message) = &messages[0];
+        assert!(message.contains("Content-Type: multipart/mixed"));
+        assert!(message.contains("Content-Type: multipart/related"));
+        assert!(message.contains("Content-Type: multipart/alternative"));
+        assert!(message.contains("Content-ID: <logo>"));
+        assert!(message.contains("Content-Disposition: attachment; filename=\"invoice.pdf\""));
+    }
 }
@@ -0,0 +1,98 @@
+//! The post-processing of the HTML of the emails, since most email clients
+//! ignore the `<style>` elements and the layouts of the web:
+//!
+//! * the `html.mjml.t` template of a mailer is rendered, then compiled from
+//!   [MJML](https://mjml.io) to the HTML of the email, with the `mailer_mjml`
+//!   feature
+//! * with `mailer.inline_css` and the `mailer_inline_css` feature, the CSS of
+//!   the `<style>` elements is inlined into `style` attributes
+
+use crate::{Error, Result};
+
+/// Compiles an MJML document to HTML.
+///
+/// # Errors
+///
+/// When the document is invalid, or the `mailer_mjml` feature is not enabled
+#[cfg(feature = "mailer_mjml")]
+pub fn compile_mjml(source: &str) -> Result<String> {
+    let parsed = mrml::parse(source)
+        .map_err(|err| Error::Message(format!("invalid MJML template: {err}")))?;
+    parsed
+        .element
+        .render(&mrml::prelude::render::RenderOptions::default())
+        .map_err(|err| Error::Message(format!("could not render MJML template: {err}")))
+}
+
+/// Compiles an MJML document to HTML.
+///
+/// # Errors
+///
+/// When the document is invalid, or the `mailer_mjml` feature is not enabled
+#[cfg(not(feature = "mailer_mjml"))]
+pub fn compile_mjml(_source: &str) -> Result<String> {
+    Err(Error::string(
+        "MJML mailer templates require the `mailer_mjml` feature",
+    ))
+}
+
+/// Inlines the CSS of the `<style>` elements of an HTML document into the
+/// `style` attributes of its elements. The remote stylesheets are not loaded.
+///
+/// # Errors
+///
+/// When the CSS is invalid, or the `mailer_inline_css` feature is not enabled
+#[cfg(feature = "mailer_inline_css")]
+pub fn inline_css(html: &str) -> Result<String> {
+    css_inline::CSSInliner::options()
+        .load_remote_stylesheets(false)
+        .build()
+        .inline(html)
+        .map_err(|err| Error::Message(format!("could not inline the email CSS: {err}")))
+}
+
+/// Inlines the CSS of the `<style>` elements of an HTML document into the
+/// `style` attributes of its elements. The remote stylesheets are not loaded.
+///
+/// # Errors
+///
+/// When the CSS is invalid, or the `mailer_inline_css` feature is not enabled
+#[cfg(not(feature = "mailer_inline_css"))]
+pub fn inline_css(_html: &str) -> Result<String> {
+    Err(Error::string(
+        "`mailer.inline_css` requires the `mailer_inline_css` feature",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "mailer_mjml")]
+    #[test]
+    fn can_compile_mjml() {
+        let html = compile_mjml(
+            "<mjml><mj-body><mj-section><mj-column><mj-text>Hello \
+             Jane</mj-text></mj-column></mj-section></mj-body></mjml>",
+        )
+        .unwrap();
+        assert!(html.starts_with("<!doctype html>"));
+        assert!(html.contains("Hello Jane"));
+    }
+
+    #[cfg(feature = "mailer_inline_css")]
+    #[test]
+    fn can_inline_css() {
+        let html = inline_css(
+            "<html><head><style>p { color: red; }</style></head><body><p>Hello</p></body></html>",
+        )
+        .unwrap();
+        assert!(html.contains("<p style=\"color: red;\">Hello</p>"));
+    }
+
+    #[cfg(not(feature = "mailer_inline_css"))]
+    #[test]
+    fn requires_the_inline_css_feature() {
+        assert!(inline_css("<p>Hello</p>").is_err());
+    }
+}
@@ -2,21 +2,34 @@
 //! trait and its implementation, `Email` structure, and the `MailerWorker` for
 //! asynchronous email processing.
 
+mod attachment;
+pub mod drivers;
 mod email_sender;
+pub mod html;
+mod preview;
+pub mod suppression;
 mod template;
 
+use std::sync::{Arc, Mutex};
+
 use async_trait::async_trait;
+pub use attachment::{Attachment, AttachmentBody};
 pub use email_sender::EmailSender;
 use include_dir::Dir;
+pub use preview::{MailerPreview, MailerPreviews};
 use serde::{Deserialize, Serialize};
-use tracing::error;
+pub use template::{locale_fallbacks, Content};
+use tracing::{error, warn};
 
 use self::template::Template;
 use super::{app::AppContext, Result};
-use crate::prelude::BackgroundWorker;
+use crate::{bgworker::workflow::retry_delay, prelude::BackgroundWorker};
 
 pub const DEFAULT_FROM_SENDER: &str = "System <system@example.com>";
 
+/// The queue of the mailer jobs.
+pub const DEFAULT_QUEUE: &str = "mailer";
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct EmailHeaders {
     pub references: Option<String>,
@@ -35,6 +48,13 @@ pub struct Args {
     pub bcc: Option<String>,
     pub cc: Option<String>,
     pub headers: Option<EmailHeaders>,
+    /// Files attached to the email, or images shown inline by its HTML
+    /// template through their `cid:` URL
+    pub attachments: Vec<Attachment>,
+    /// The locale of the recipient, picking the templates of its
+    /// sub-directory and the `locale` of the translations, the
+    /// `i18n.default_locale` when not set
+    pub locale: Option<String>,
 }
 
 /// The structure representing an email details.
@@ -58,6 +78,120 @@ pub struct Email {
     pub cc: Option<String>,
     /// Custom headers for the email (e.g., References, In-Reply-To, Message-ID)
     pub headers: Option<EmailHeaders>,
+    /// Files attached to the email, or images shown inline by its HTML
+    /// through their `cid:` URL
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// How the mailer worker delivers the email, [`Delivery::default`] when
+    /// not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<Delivery>,
+    /// The locals the templates of the email were rendered with, kept for
+    /// the test assertions and not sent to the queue
+    #[serde(skip)]
+    pub locals: Option<serde_json::Value>,
+}
+
+/// The emails delivered by the stub mailer, kept in the shared store of the
+/// context for the assertions of [`crate::testing::mail`].
+#[derive(Clone, Default, Debug)]
+pub struct StubDeliveries(pub Arc<Mutex<Vec<Email>>>);
+
+/// How the mailer worker delivers an email: the queue and priority of its
+/// job, and the number of times a failed delivery is retried, with an
+/// exponential backoff.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub queue: String,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub retries: u32,
+    /// The number of failed deliveries so far
+    #[serde(default)]
+    pub attempt: u32,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Self {
+            queue: DEFAULT_QUEUE.to_string(),
+            priority: 0,
+            retries: 0,
+            attempt: 0,
+        }
+    }
+}
+
+impl Email {
+    /// Sets how the mailer worker delivers the email.
+    #[must_use]
+    pub fn with_delivery(mut self, delivery: Delivery) -> Self {
+        self.delivery = Some(delivery);
+        self
+    }
+
+    /// Delivers the email right away, through the mailer of the context,
+    /// reading its storage attachments. With `mailer.suppression`, the
+    /// suppressed recipients are dropped, and the email is skipped when none
+    /// of its `to` recipients is left.
+    ///
+    /// # Errors
+    ///
+    /// When no mailer is configured, or the delivery fails
+    pub async fn deliver(&self, ctx: &AppContext) -> Result<()> {
+        let Some(mailer) = &ctx.mailer else {
+            let err = crate::Error::Message(
+                "attempting to send email but no email sender configured".to_string(),
+            );
+            error!(err = err.to_string(), "mailer error");
+            return Err(err);
+        };
+        let mut email = self.clone();
+        let suppression = ctx
+            .config
+            .mailer
+            .as_ref()
+            .is_some_and(|mailer| mailer.suppression.is_some());
+        if suppression && !suppression::filter_recipients(ctx, &mut email).await? {
+            tracing::info!(
+                subject = email.subject,
+                "skipping an email to suppressed recipients"
+            );
+            return Ok(());
+        }
+        for attachment in &mut email.attachments {
+            attachment.resolve(&ctx.storage).await?;
+        }
+        mailer.mail(&email).await.map_err(|err| {
+            error!(err = err.to_string(), "mailer error");
+            err
+        })?;
+
+        if matches!(mailer.transport, email_sender::EmailTransport::Test(_)) {
+            let deliveries = ctx.shared_store.get::<StubDeliveries>().unwrap_or_else(|| {
+                let deliveries = StubDeliveries::default();
+                ctx.shared_store.insert(deliveries.clone());
+                deliveries
+            });
+            deliveries
+                .0
+                .lock()
+                .map_err(|_| crate::Error::string("stub deliveries lock poisoned"))?
+                .push(email);
+        }
+        Ok(())
+    }
+
+    /// Delivers the email in the background, as a job of the
+    /// [`MailerWorker`] placed by its [`Delivery`].
+    ///
+    /// # Errors
+    ///
+    /// When the job could not be enqueued
+    pub async fn deliver_later(&self, ctx: &AppContext) -> Result<()> {
+        MailerWorker::perform_later(ctx, self.clone()).await
+    }
 }
 
 /// The options struct for configuring the email sender.
@@ -81,6 +215,13 @@ pub trait Mailer {
         }
     }
 
+    /// How the emails of the mailer are delivered, unless an email sets its
+    /// own [`Delivery`].
+    #[must_use]
+    fn delivery() -> Delivery {
+        Delivery::default()
+    }
+
     /// Sends an email using the provided [`AppContext`] and email details.
     async fn mail(ctx: &AppContext, email: &Email) -> Result<()> {
         let opts = Self::opts();
@@ -88,15 +229,15 @@ pub trait Mailer {
 
         email.from = Some(email.from.unwrap_or_else(|| opts.from.clone()));
         email.reply_to = email.reply_to.or_else(|| opts.reply_to.clone());
+        email.delivery.get_or_insert_with(Self::delivery);
 
-        MailerWorker::perform_later(ctx, email.clone()).await?;
-        Ok(())
+        email.deliver_later(ctx).await
     }
 
     /// Renders and sends an email using the provided [`AppContext`], template
     /// directory, and arguments.
     async fn mail_template(ctx: &AppContext, dir: &Dir<'_>, args: Args) -> Result<()> {
-        let content = Template::new(dir).render(&args.locals)?;
+        let content = render(ctx, dir, args.locale.as_deref(), &args.locals)?;
         Self::mail(
             ctx,
             &Email {
@@ -109,12 +250,58 @@ pub trait Mailer {
                 bcc: args.bcc.clone(),
                 cc: args.cc.clone(),
                 headers: args.headers.clone(),
+                attachments: args.attachments,
+                delivery: None,
+                locals: Some(args.locals),
             },
         )
         .await
     }
 }
 
+/// Renders the templates of `dir` in the templates of the `locale`, falling
+/// back to the `i18n.default_locale`, and inlines the CSS of the HTML with
+/// `mailer.inline_css`. The `locale` is added to the locals, and
+/// the `t()` function of the [`crate::i18n::I18n`] of the shared store is
+/// available to the templates, with the `i18n` feature.
+///
+/// # Errors
+///
+/// When a template is missing or could not be rendered
+pub fn render(
+    ctx: &AppContext,
+    dir: &Dir<'_>,
+    locale: Option<&str>,
+    locals: &serde_json::Value,
+) -> Result<Content> {
+    let default_locale = &ctx.config.i18n.default_locale;
+    let template = Template::new(dir).localized(&locale_fallbacks(locale, default_locale));
+    let mut locals = locals.clone();
+    if let Some(locals) = locals.as_object_mut() {
+        locals
+            .entry("locale")
+            .or_insert_with(|| locale.unwrap_or(default_locale).into());
+    }
+
+    #[cfg(feature = "i18n")]
+    let mut content = match ctx.shared_store.get::<crate::i18n::I18n>() {
+        Some(i18n) => template.render_with(&locals, &i18n)?,
+        None => template.render(&locals)?,
+    };
+    #[cfg(not(feature = "i18n"))]
+    let mut content = template.render(&locals)?;
+
+    if ctx
+        .config
+        .mailer
+        .as_ref()
+        .is_some_and(|mailer| mailer.inline_css)
+    {
+        content.html = html::inline_css(&content.html)?;
+    }
+    Ok(content)
+}
+
 /// The [`MailerWorker`] struct represents a worker responsible for asynchronous
 /// email processing.
 #[allow(clippy::module_name_repetitions)]
@@ -126,7 +313,21 @@ pub struct MailerWorker {
 #[async_trait]
 impl BackgroundWorker<Email> for MailerWorker {
     fn queue() -> Option<String> {
-        Some("mailer".to_string())
+        Some(DEFAULT_QUEUE.to_string())
+    }
+
+    fn queue_for(email: &Email) -> Option<String> {
+        Some(email.delivery.as_ref().map_or_else(
+            || DEFAULT_QUEUE.to_string(),
+            |delivery| delivery.queue.clone(),
+        ))
+    }
+
+    fn priority_for(email: &Email) -> i32 {
+        email
+            .delivery
+            .as_ref()
+            .map_or(0, |delivery| delivery.priority)
     }
 
     fn build(ctx: &AppContext) -> Self {
@@ -135,22 +336,70 @@ impl BackgroundWorker<Email> for MailerWorker {
 
     /// Performs the email sending operation using the provided [`AppContext`]
     /// and email details.
-    async fn perform(&self, email: Email) -> crate::Result<()> {
-        if let Some(mailer) = &self.ctx.mailer {
-            let res = mailer.mail(&email).await;
-            match res {
-                Ok(res) => Ok(res),
-                Err(err) => {
-                    error!(err = err.to_string(), "mailer error");
-                    Err(err)
+    /// A failed delivery is retried in a new job, until the retries of its
+    /// [`Delivery`] are exhausted.
+    async fn perform(&self, mut email: Email) -> crate::Result<()> {
+        match email.deliver(&self.ctx).await {
+            Ok(()) => Ok(()),
+            Err(err) => match email.delivery.as_mut() {
+                Some(delivery) if delivery.attempt < delivery.retries => {
+                    let delay = retry_delay(delivery.attempt);
+                    delivery.attempt += 1;
+                    warn!(
+                        attempt = delivery.attempt,
+                        retry_in_secs = delay.as_secs(),
+                        err = err.to_string(),
+                        "email delivery failed, retrying"
+                    );
+                    Self::perform_in(&self.ctx, email, delay).await
                 }
-            }
-        } else {
-            let err = crate::Error::Message(
-                "attempting to send email but no email sender configured".to_string(),
-            );
-            error!(err = err.to_string(), "mailer error");
-            Err(err)
+                _ => Err(err),
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_place_mailer_jobs() {
+        let email = Email::default();
+        assert_eq!(MailerWorker::queue_for(&email), Some("mailer".to_string()));
+        assert_eq!(MailerWorker::priority_for(&email), 0);
+
+        let email = email.with_delivery(Delivery {
+            queue: "transactional".to_string(),
+            priority: 10,
+            retries: 3,
+            ..Default::default()
+        });
+        assert_eq!(
+            MailerWorker::queue_for(&email),
+            Some("transactional".to_string())
+        );
+        assert_eq!(MailerWorker::priority_for(&email), 10);
+    }
+
+    #[test]
+    fn can_read_jobs_without_delivery() {
+        let email: Email = serde_json::from_value(serde_json::json!({
+            "from": null,
+            "to": "user1@example.com",
+            "reply_to": null,
+            "subject": "Welcome",
+            "text": "Hello",
+            "html": "<p>Hello</p>",
+            "bcc": null,
+            "cc": null,
+            "headers": null,
+        }))
+        .unwrap();
+        assert_eq!(email.delivery, None);
+        assert!(serde_json::to_value(&email)
+            .unwrap()
+            .get("delivery")
+            .is_none());
+    }
+}
@@ -0,0 +1,117 @@
+//! This module defines the [`MailerPreview`]s of the application: the
+//! templates of a mailer rendered with sample locals, shown in development
+//! by [`crate::controller::mailer_preview`].
+//!
+//! # Example
+//!
+//! ```rust, ignore
+//! use include_dir::{include_dir, Dir};
+//! use loco_rs::mailer::MailerPreview;
+//!
+//! static welcome: Dir<'_> = include_dir!("src/mailers/auth/welcome");
+//!
+//! fn mailer_previews(_ctx: &AppContext) -> Vec<MailerPreview> {
+//!     vec![MailerPreview::new(
+//!         "auth/welcome",
+//!         &welcome,
+//!         serde_json::json!({"name": "Jane", "verifyToken": "1111-2222"}),
+//!     )]
+//! }
+//! ```
+
+use include_dir::Dir;
+
+use super::template::Content;
+use crate::{app::AppContext, Result};
+
+/// The templates of a mailer, rendered with sample locals.
+#[derive(Debug, Clone)]
+pub struct MailerPreview {
+    /// The name of the preview, part of its URL
+    pub name: String,
+    /// The directory of the embedded templates
+    pub dir: &'static Dir<'static>,
+    /// The locals the templates are rendered with
+    pub locals: serde_json::Value,
+    /// The locale the templates are rendered in, see [`super::render`]
+    pub locale: Option<String>,
+}
+
+impl MailerPreview {
+    /// Creates a preview of the templates of `dir`.
+    #[must_use]
+    pub fn new(name: &str, dir: &'static Dir<'static>, locals: serde_json::Value) -> Self {
+        Self {
+            name: name.to_string(),
+            dir,
+            locals,
+            locale: None,
+        }
+    }
+
+    /// Renders the templates in the given locale.
+    #[must_use]
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_string());
+        self
+    }
+
+    /// Renders the templates with the sample locals.
+    ///
+    /// # Errors
+    ///
+    /// When a template is missing or could not be rendered
+    pub fn render(&self, ctx: &AppContext) -> Result<Content> {
+        super::render(ctx, self.dir, self.locale.as_deref(), &self.locals)
+    }
+}
+
+/// The mailer previews of the application, kept in the shared store of the
+/// context.
+#[derive(Clone, Default, Debug)]
+pub struct MailerPreviews(pub Vec<MailerPreview>);
+
+impl MailerPreviews {
+    /// The preview of the given name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&MailerPreview> {
+        self.0.iter().find(|preview| preview.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use include_dir::include_dir;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    static TEST: Dir<'_> = include_dir!("tests/fixtures/email_template/test");
+
+    static LOCALIZED: Dir<'_> = include_dir!("tests/fixtures/email_template/localized");
+
+    #[tokio::test]
+    async fn can_render_previews() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let previews = MailerPreviews(vec![
+            MailerPreview::new(
+                "auth/welcome",
+                &TEST,
+                serde_json::json!({"name": "Jane", "verifyToken": "1111-2222"}),
+            ),
+            MailerPreview::new(
+                "welcome/de",
+                &LOCALIZED,
+                serde_json::json!({"name": "Jane"}),
+            )
+            .locale("de"),
+        ]);
+
+        assert!(previews.get("auth/reset").is_none());
+        let content = previews.get("auth/welcome").unwrap().render(&ctx).unwrap();
+        assert_eq!(content.subject.trim(), "Test Jane");
+        assert!(content.html.contains("verify/1111-2222"));
+        let content = previews.get("welcome/de").unwrap().render(&ctx).unwrap();
+        assert_eq!(content.subject.trim(), "Willkommen Jane");
+    }
+}
@@ -0,0 +1,346 @@
+//! # Suppression List
+//!
+//! The addresses the emails must not be sent to anymore: the ones which
+//! bounced for good, or whose owner reported an email as spam. They are
+//! added from the bounce and complaint webhooks of the providers, see
+//! [`crate::controller::mailer_webhooks`], and kept in the cache of the
+//! context, so a shared cache such as Redis keeps them across the processes.
+//!
+//! With `mailer.suppression` configured, the mailer worker drops the
+//! suppressed recipients of the emails, and skips an email left without
+//! any. The [`SuppressionHandler`]s of
+//! [`crate::app::Hooks::suppression_handlers`] are told of every address
+//! suppressed, such as to flag the account of the user.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use lettre::message::Mailboxes;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::Email;
+use crate::{app::AppContext, Result};
+
+/// The prefix of the cache keys of the suppressed addresses.
+const KEY_PREFIX: &str = "mailer:suppressed:";
+
+/// Why an address is suppressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressionReason {
+    /// The emails to the address bounced
+    Bounce,
+    /// The owner of the address reported an email as spam
+    Complaint,
+}
+
+/// A suppressed address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suppression {
+    pub email: String,
+    pub reason: SuppressionReason,
+    /// The provider which reported the address, such as `ses`
+    pub provider: String,
+    /// The diagnostic of the provider, when given
+    pub detail: Option<String>,
+    pub suppressed_at: DateTime<Utc>,
+}
+
+impl Suppression {
+    #[must_use]
+    pub fn new(email: &str, reason: SuppressionReason, provider: &str) -> Self {
+        Self {
+            email: normalize(email),
+            reason,
+            provider: provider.to_string(),
+            detail: None,
+            suppressed_at: Utc::now(),
+        }
+    }
+
+    #[must_use]
+    pub fn detail(mut self, detail: Option<&str>) -> Self {
+        self.detail = detail.map(ToString::to_string);
+        self
+    }
+}
+
+/// Told of the addresses added to the suppression list.
+#[async_trait]
+pub trait SuppressionHandler: Send + Sync {
+    /// Handles a suppressed address.
+    ///
+    /// # Errors
+    ///
+    /// The errors are logged, and do not prevent the suppression.
+    async fn suppressed(&self, ctx: &AppContext, suppression: &Suppression) -> Result<()>;
+}
+
+/// The suppression handlers of the application, kept in the shared store of
+/// the context.
+#[derive(Clone, Default)]
+pub struct SuppressionHandlers(pub Vec<Arc<dyn SuppressionHandler>>);
+
+fn normalize(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn key(email: &str) -> String {
+    format!("{KEY_PREFIX}{}", normalize(email))
+}
+
+/// Adds an address to the suppression list, and tells the handlers.
+///
+/// # Errors
+///
+/// When the cache could not be written
+pub async fn suppress(ctx: &AppContext, suppression: &Suppression) -> Result<()> {
+    ctx.cache
+        .insert(&key(&suppression.email), suppression)
+        .await?;
+    tracing::info!(
+        email = suppression.email,
+        reason = ?suppression.reason,
+        provider = suppression.provider,
+        "email address suppressed"
+    );
+    let handlers = ctx
+        .shared_store
+        .get::<SuppressionHandlers>()
+        .unwrap_or_default();
+    for handler in &handlers.0 {
+        if let Err(err) = handler.suppressed(ctx, suppression).await {
+            tracing::error!(
+                err = err.to_string(),
+                email = suppression.email,
+                "suppression handler failed"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The suppression of an address, if it is suppressed.
+///
+/// # Errors
+///
+/// When the cache could not be read
+pub async fn get(ctx: &AppContext, email: &str) -> Result<Option<Suppression>> {
+    Ok(ctx.cache.get(&key(email)).await?)
+}
+
+/// Removes an address from the suppression list.
+///
+/// # Errors
+///
+/// When the cache could not be written
+pub async fn remove(ctx: &AppContext, email: &str) -> Result<()> {
+    Ok(ctx.cache.remove(&key(email)).await?)
+}
+
+/// Drops the suppressed recipients of an email, returning `false` when it has
+/// no `to` recipient left.
+///
+/// # Errors
+///
+/// When the recipients are invalid, or the cache could not be read
+pub(crate) async fn filter_recipients(ctx: &AppContext, email: &mut Email) -> Result<bool> {
+    email.to = allowed(ctx, &email.to).await?;
+    if let Some(cc) = &email.cc {
+        email.cc = Some(allowed(ctx, cc).await?).filter(|cc| !cc.is_empty());
+    }
+    if let Some(bcc) = &email.bcc {
+        email.bcc = Some(allowed(ctx, bcc).await?).filter(|bcc| !bcc.is_empty());
+    }
+    Ok(!email.to.is_empty())
+}
+
+/// The mailboxes of a list which are not suppressed.
+async fn allowed(ctx: &AppContext, list: &str) -> Result<String> {
+    let mut allowed = Vec::new();
+    for mailbox in list.parse::<Mailboxes>()? {
+        let address = mailbox.email.to_string();
+        if get(ctx, &address).await?.is_some() {
+            tracing::info!(email = address, "skipping a suppressed recipient");
+        } else {
+            allowed.push(mailbox.to_string());
+        }
+    }
+    Ok(allowed.join(", "))
+}
+
+/// The suppressions of an Amazon SES bounce or complaint notification,
+/// received through SNS. Only the permanent bounces suppress an address,
+/// unless `soft_bounces` is set.
+#[must_use]
+pub fn from_ses(message: &Value, soft_bounces: bool) -> Vec<Suppression> {
+    let kind = message
+        .get("notificationType")
+        .or_else(|| message.get("eventType"))
+        .and_then(Value::as_str);
+    let recipients = |section: &str, list: &str| {
+        message[section][list]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+    };
+    match kind {
+        Some("Bounce") => {
+            let permanent = message["bounce"]["bounceType"] == "Permanent";
+            if !permanent && !soft_bounces {
+                return vec![];
+            }
+            recipients("bounce", "bouncedRecipients")
+                .iter()
+                .filter_map(|recipient| {
+                    let email = recipient["emailAddress"].as_str()?;
+                    Some(
+                        Suppression::new(email, SuppressionReason::Bounce, "ses")
+                            .detail(recipient["diagnosticCode"].as_str()),
+                    )
+                })
+                .collect()
+        }
+        Some("Complaint") => recipients("complaint", "complainedRecipients")
+            .iter()
+            .filter_map(|recipient| {
+                let email = recipient["emailAddress"].as_str()?;
+                Some(
+                    Suppression::new(email, SuppressionReason::Complaint, "ses")
+                        .detail(message["complaint"]["complaintFeedbackType"].as_str()),
+                )
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// The suppressions of a batch of `SendGrid` events. The `blocked` bounces
+/// only suppress an address when `soft_bounces` is set.
+#[must_use]
+pub fn from_sendgrid(events: &Value, soft_bounces: bool) -> Vec<Suppression> {
+    events
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|event| {
+            let email = event["email"].as_str()?;
+            let reason = match event["event"].as_str()? {
+                "bounce" if soft_bounces || event["type"] != "blocked" => SuppressionReason::Bounce,
+                "spamreport" => SuppressionReason::Complaint,
+                _ => return None,
+            };
+            Some(Suppression::new(email, reason, "sendgrid").detail(event["reason"].as_str()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[test]
+    fn can_read_ses_notifications() {
+        let bounce = json!({
+            "notificationType": "Bounce",
+            "bounce": {
+                "bounceType": "Permanent",
+                "bouncedRecipients": [
+                    {"emailAddress": "User1@Example.com", "diagnosticCode": "550 5.1.1 unknown"},
+                ],
+            },
+        });
+        let suppressions = from_ses(&bounce, false);
+        assert_eq!(suppressions.len(), 1);
+        assert_eq!(suppressions[0].email, "user1@example.com");
+        assert_eq!(suppressions[0].reason, SuppressionReason::Bounce);
+        assert_eq!(suppressions[0].detail.as_deref(), Some("550 5.1.1 unknown"));
+
+        let transient = json!({
+            "notificationType": "Bounce",
+            "bounce": {
+                "bounceType": "Transient",
+                "bouncedRecipients": [{"emailAddress": "user2@example.com"}],
+            },
+        });
+        assert!(from_ses(&transient, false).is_empty());
+        assert_eq!(from_ses(&transient, true).len(), 1);
+
+        let complaint = json!({
+            "eventType": "Complaint",
+            "complaint": {
+                "complaintFeedbackType": "abuse",
+                "complainedRecipients": [{"emailAddress": "user3@example.com"}],
+            },
+        });
+        let suppressions = from_ses(&complaint, false);
+        assert_eq!(suppressions[0].reason, SuppressionReason::Complaint);
+        assert_eq!(suppressions[0].detail.as_deref(), Some("abuse"));
+    }
+
+    #[test]
+    fn can_read_sendgrid_events() {
+        let events = json!([
+            {"email": "user1@example.com", "event": "bounce", "type": "bounce", "reason": "550 unknown"},
+            {"email": "user2@example.com", "event": "bounce", "type": "blocked"},
+            {"email": "user3@example.com", "event": "spamreport"},
+            {"email": "user4@example.com", "event": "delivered"},
+        ]);
+        let emails = |suppressions: Vec<Suppression>| {
+            suppressions
+                .into_iter()
+                .map(|suppression| (suppression.email, suppression.reason))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            emails(from_sendgrid(&events, false)),
+            vec![
+                ("user1@example.com".to_string(), SuppressionReason::Bounce),
+                (
+                    "user3@example.com".to_string(),
+                    SuppressionReason::Complaint
+                ),
+            ]
+        );
+        assert_eq!(from_sendgrid(&events, true).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn can_skip_suppressed_recipients() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.cache = crate::cache::drivers::inmem::new(&crate::config::InMemCacheConfig {
+            max_capacity: 100,
+        })
+        .into();
+        suppress(
+            &ctx,
+            &Suppression::new("user1@example.com", SuppressionReason::Bounce, "ses"),
+        )
+        .await
+        .unwrap();
+
+        let mut email = Email {
+            to: "User 1 <USER1@example.com>, user2@example.com".to_string(),
+            cc: Some("user1@example.com".to_string()),
+            ..Default::default()
+        };
+        assert!(filter_recipients(&ctx, &mut email).await.unwrap());
+        assert_eq!(email.to, "user2@example.com");
+        assert_eq!(email.cc, None);
+
+        let mut email = Email {
+            to: "user1@example.com".to_string(),
+            ..Default::default()
+        };
+        assert!(!filter_recipients(&ctx, &mut email).await.unwrap());
+
+        remove(&ctx, "user1@example.com").await.unwrap();
+        assert!(get(&ctx, "user1@example.com").await.unwrap().is_none());
+    }
+}
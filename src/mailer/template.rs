@@ -13,15 +13,28 @@
 //! let args = serde_json::json!({"name": "framework"});
 //! let content = Template::new("contnt").render(&args);
 //! ```
+//!
+//! The templates of each locale go in a sub-directory named after it, such as
+//! `welcome/de/`, and the ones at the root of the directory are used for the
+//! locales without one:
+//!
+//! ```rust, ignore
+//! let content = Template::new(&welcome)
+//!     .localized(&locale_fallbacks(Some("de-AT"), "en-US"))
+//!     .render(&args);
+//! ```
 
 use include_dir::Dir;
 
+use super::html;
 use crate::{errors::Error, tera, Result};
 
 /// The filename for the subject template file.
 const SUBJECT: &str = "subject.t";
 /// The filename for the HTML template file.
 const HTML: &str = "html.t";
+/// The filename for the MJML template file, compiled to the HTML of the email.
+const HTML_MJML: &str = "html.mjml.t";
 /// The filename for the plain text template file.
 const TEXT: &str = "text.t";
 
@@ -29,7 +42,7 @@ const TEXT: &str = "text.t";
 /// as a string.
 fn embedded_file(dir: &Dir<'_>, name: &str) -> Result<String> {
     Ok(String::from_utf8_lossy(
-        dir.get_file(name)
+        dir.get_file(dir.path().join(name))
             .ok_or_else(|| Error::Message(format!("no mailer template file found {name}")))?
             .contents(),
     )
@@ -58,16 +71,45 @@ impl<'a> Template<'a> {
         Self { dir }
     }
 
+    /// Picks the templates of the first of the `locales` with a
+    /// sub-directory, or else the templates at the root of the directory.
+    #[must_use]
+    pub fn localized(self, locales: &[String]) -> Self {
+        locales
+            .iter()
+            .find_map(|locale| self.dir.get_dir(self.dir.path().join(locale)))
+            .map_or(self, |dir| Self { dir })
+    }
+
+    /// The name of the HTML template: an MJML one when the directory has
+    /// one, or else the plain HTML one.
+    fn html_name(&self) -> &'static str {
+        if self.dir.get_file(self.dir.path().join(HTML_MJML)).is_some() {
+            HTML_MJML
+        } else {
+            HTML
+        }
+    }
+
+    /// The HTML of the email, compiled when its template is an MJML one.
+    fn html(&self, rendered: String) -> Result<String> {
+        if self.html_name() == HTML_MJML {
+            html::compile_mjml(&rendered)
+        } else {
+            Ok(rendered)
+        }
+    }
+
     /// Renders the email content based on the provided locals using the
     /// embedded templates.
     pub fn render(&self, locals: &serde_json::Value) -> Result<Content> {
         let subject_t = embedded_file(self.dir, SUBJECT)?;
         let text_t = embedded_file(self.dir, TEXT)?;
-        let html_t = embedded_file(self.dir, HTML)?;
+        let html_t = embedded_file(self.dir, self.html_name())?;
 
         // TODO(consider): check+consider offloading to tokio async this work
         let text = tera::render_string(&text_t, locals)?;
-        let html = tera::render_string(&html_t, locals)?;
+        let html = self.html(tera::render_string(&html_t, locals)?)?;
         let subject = tera::render_string(&subject_t, locals)?;
         Ok(Content {
             subject,
@@ -75,6 +117,43 @@ impl<'a> Template<'a> {
             html,
         })
     }
+
+    /// Renders the email content like [`Self::render`], with the
+    /// `t(key=.., lang=..)` function of the translations.
+    #[cfg(feature = "i18n")]
+    pub fn render_with(
+        &self,
+        locals: &serde_json::Value,
+        i18n: &crate::i18n::I18n,
+    ) -> Result<Content> {
+        let mut tera = ::tera::Tera::default();
+        i18n.register(&mut tera);
+        let context = ::tera::Context::from_serialize(locals)?;
+        let mut render = |name| -> Result<String> {
+            Ok(tera.render_str(&embedded_file(self.dir, name)?, &context)?)
+        };
+        Ok(Content {
+            subject: render(SUBJECT)?,
+            text: render(TEXT)?,
+            html: self.html(render(self.html_name())?)?,
+        })
+    }
+}
+
+/// The locales the templates are looked up in, in order: the locale, its
+/// language, then the default locale and its language.
+#[must_use]
+pub fn locale_fallbacks(locale: Option<&str>, default_locale: &str) -> Vec<String> {
+    let mut fallbacks: Vec<String> = Vec::new();
+    for locale in locale.into_iter().chain([default_locale]) {
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        for candidate in [locale, language] {
+            if !candidate.is_empty() && !fallbacks.iter().any(|f| f == candidate) {
+                fallbacks.push(candidate.to_string());
+            }
+        }
+    }
+    fallbacks
 }
 
 #[cfg(test)]
@@ -95,4 +174,46 @@ mod tests {
             Template::new(&include_dir!("tests/fixtures/email_template/test")).render(&args)
         );
     }
+
+    #[test]
+    fn can_render_localized_template() {
+        static DIR: Dir<'_> = include_dir!("tests/fixtures/email_template/localized");
+        let args = serde_json::json!({"name": "Jane"});
+
+        let render = |locale| {
+            Template::new(&DIR)
+                .localized(&locale_fallbacks(locale, "en-US"))
+                .render(&args)
+                .unwrap()
+                .subject
+        };
+        assert_eq!(render(Some("de-AT")), "Willkommen Jane");
+        assert_eq!(render(Some("fr")), "Welcome Jane");
+        assert_eq!(render(None), "Welcome Jane");
+    }
+
+    #[test]
+    fn can_render_mjml_template() {
+        static DIR: Dir<'_> = include_dir!("tests/fixtures/email_template/mjml");
+        let content = Template::new(&DIR).render(&serde_json::json!({"name": "Jane"}));
+
+        #[cfg(feature = "mailer_mjml")]
+        {
+            let html = content.unwrap().html;
+            assert!(html.contains("Welcome Jane"));
+            assert!(!html.contains("<mj-text>"));
+        }
+        #[cfg(not(feature = "mailer_mjml"))]
+        assert!(content.is_err());
+    }
+
+    #[test]
+    fn can_list_locale_fallbacks() {
+        assert_eq!(
+            locale_fallbacks(Some("de-AT"), "en-US"),
+            vec!["de-AT", "de", "en-US", "en"]
+        );
+        assert_eq!(locale_fallbacks(Some("en"), "en-US"), vec!["en", "en-US"]);
+        assert_eq!(locale_fallbacks(None, "en"), vec!["en"]);
+    }
 }
@@ -0,0 +1,432 @@
+//! # Audit Trail
+//!
+//! Records the creation, updates and deletion of the rows of [`Auditable`]
+//! entities as [`versions`], with the changed fields, the state of the row
+//! after the change, the actor making it and its time, and reverts rows to
+//! their recorded versions.
+//!
+//! Create the `versions` table in a migration with
+//! [`crate::schema::create_versions_table`], and record the changes from the
+//! hooks of the audited models:
+//! ```rust,ignore
+//! impl Auditable for Entity {}
+//!
+//! #[async_trait::async_trait]
+//! impl ActiveModelBehavior for ActiveModel {
+//!     async fn after_save<C>(model: Model, db: &C, insert: bool) -> Result<Model, DbErr>
+//!     where
+//!         C: ConnectionTrait,
+//!     {
+//!         Entity::audit_save(db, &model, insert).await?;
+//!         Ok(model)
+//!     }
+//!
+//!     async fn after_delete<C>(self, db: &C) -> Result<Self, DbErr>
+//!     where
+//!         C: ConnectionTrait,
+//!     {
+//!         Entity::audit_delete(db, &self.clone().try_into_model()?).await?;
+//!         Ok(self)
+//!     }
+//! }
+//! ```
+//!
+//! The actor is the `pid` of the JWT of the request with the `audit`
+//! middleware enabled, or the one given to [`with_actor`], for example in
+//! tasks and workers. The bulk `update_many` and `delete_many` statements do
+//! not run the hooks, and are not recorded.
+use std::future::Future;
+
+use async_trait::async_trait;
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    IdenStatic, IntoActiveModel, Iterable, PrimaryKeyToColumn, QueryFilter, QueryOrder, Set,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::{ModelError, ModelResult};
+
+/// The fields left out of the recorded changes.
+const IGNORED_FIELDS: &[&str] = &["updated_at"];
+
+tokio::task_local! {
+    static ACTOR: Option<String>;
+}
+
+/// Records the changes made by a future as made by an actor.
+pub async fn with_actor<F: Future>(actor: Option<String>, future: F) -> F::Output {
+    ACTOR.scope(actor, future).await
+}
+
+/// The actor of the changes being made, if known.
+#[must_use]
+pub fn current_actor() -> Option<String> {
+    ACTOR.try_with(Clone::clone).ok().flatten()
+}
+
+/// The recorded changes of the audited rows.
+pub mod versions {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "versions")]
+    pub struct Model {
+        pub created_at: DateTimeWithTimeZone,
+        pub updated_at: DateTimeWithTimeZone,
+        #[sea_orm(primary_key)]
+        pub id: i32,
+        /// The table of the row
+        pub item_type: String,
+        /// The primary key of the row, its values joined with `,` for
+        /// composite keys
+        pub item_id: String,
+        /// `create`, `update` or `delete`
+        pub event: String,
+        /// Who made the change, if known
+        pub actor: Option<String>,
+        /// The changed fields, as `{"field": [before, after]}`
+        pub changes: Json,
+        /// The row after the change, or before its deletion
+        pub object: Json,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+/// The events of the recorded versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Create,
+    Update,
+    Delete,
+}
+
+impl Event {
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+/// Entities whose changes are recorded as [`versions`].
+#[async_trait]
+pub trait Auditable: EntityTrait
+where
+    Self::Model: Serialize + DeserializeOwned + Sync,
+    Self::ActiveModel: ActiveModelTrait<Entity = Self> + ActiveModelBehavior + Send,
+    Self::Model: IntoActiveModel<Self::ActiveModel>,
+{
+    /// The item type of the versions, the table name by default.
+    #[must_use]
+    fn item_type() -> String {
+        Self::default().table_name().to_string()
+    }
+
+    /// Records the creation or the update of a row, from `after_save`.
+    /// Updates leaving the fields unchanged are not recorded.
+    ///
+    /// # Errors
+    /// When the version could not be stored
+    async fn audit_save<C>(db: &C, model: &Self::Model, insert: bool) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let object = to_object(model)?;
+        let item_id = item_id::<Self>(&object);
+        let (event, before) = if insert {
+            (Event::Create, Map::new())
+        } else {
+            let before = last_version(db, &Self::item_type(), &item_id)
+                .await?
+                .and_then(|version| version.object.as_object().cloned())
+                .unwrap_or_default();
+            (Event::Update, before)
+        };
+
+        let changes = diff(&before, &object);
+        if event == Event::Update && changes.is_empty() {
+            return Ok(());
+        }
+        record(db, Self::item_type(), item_id, event, changes, object).await
+    }
+
+    /// Records the deletion of a row, from `after_delete`.
+    ///
+    /// # Errors
+    /// When the version could not be stored
+    async fn audit_delete<C>(db: &C, model: &Self::Model) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let object = to_object(model)?;
+        let item_id = item_id::<Self>(&object);
+        let changes = diff(&object, &Map::new());
+        record(
+            db,
+            Self::item_type(),
+            item_id,
+            Event::Delete,
+            changes,
+            object,
+        )
+        .await
+    }
+
+    /// The versions of a row, oldest first.
+    ///
+    /// # Errors
+    /// When the versions could not be read
+    async fn versions<C>(db: &C, model: &Self::Model) -> ModelResult<Vec<versions::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        let item_id = item_id::<Self>(&to_object(model)?);
+        Ok(versions::Entity::find()
+            .filter(versions::Column::ItemType.eq(Self::item_type()))
+            .filter(versions::Column::ItemId.eq(item_id))
+            .order_by_asc(versions::Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Reverts a row to its state at a version, creating it again when it
+    /// was deleted. The revert is itself recorded by the hooks.
+    ///
+    /// # Errors
+    /// When the version is of another entity, or the row could not be
+    /// written
+    async fn revert<C>(db: &C, version: &versions::Model) -> ModelResult<Self::Model>
+    where
+        C: ConnectionTrait,
+    {
+        if version.item_type != Self::item_type() {
+            return Err(ModelError::Message(format!(
+                "version {} is of `{}`, not `{}`",
+                version.id,
+                version.item_type,
+                Self::item_type()
+            )));
+        }
+
+        let model = Self::ActiveModel::from_json(version.object.clone())?;
+        match model.clone().update(db).await {
+            Err(DbErr::RecordNotUpdated) => Ok(model.insert(db).await?),
+            result => Ok(result?),
+        }
+    }
+}
+
+fn to_object<M: Serialize>(model: &M) -> Result<Map<String, Value>, DbErr> {
+    match serde_json::to_value(model) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(DbErr::Custom("audited models must be structs".to_string())),
+        Err(err) => Err(DbErr::Json(err.to_string())),
+    }
+}
+
+/// The primary key of a row, from its serialized fields.
+fn item_id<E: EntityTrait>(object: &Map<String, Value>) -> String {
+    E::PrimaryKey::iter()
+        .map(|key| match object.get(key.into_column().as_str()) {
+            Some(Value::String(value)) => value.clone(),
+            Some(value) => value.to_string(),
+            None => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The changed fields, as `{"field": [before, after]}`.
+fn diff(before: &Map<String, Value>, after: &Map<String, Value>) -> Map<String, Value> {
+    before
+        .keys()
+        .chain(after.keys().filter(|field| !before.contains_key(*field)))
+        .filter(|field| !IGNORED_FIELDS.contains(&field.as_str()))
+        .filter_map(|field| {
+            let (old, new) = (
+                before.get(field).unwrap_or(&Value::Null),
+                after.get(field).unwrap_or(&Value::Null),
+            );
+            (old != new).then(|| (field.clone(), json!([old, new])))
+        })
+        .collect()
+}
+
+async fn last_version<C: ConnectionTrait>(
+    db: &C,
+    item_type: &str,
+    item_id: &str,
+) -> Result<Option<versions::Model>, DbErr> {
+    versions::Entity::find()
+        .filter(versions::Column::ItemType.eq(item_type))
+        .filter(versions::Column::ItemId.eq(item_id))
+        .order_by_desc(versions::Column::Id)
+        .one(db)
+        .await
+}
+
+async fn record<C: ConnectionTrait>(
+    db: &C,
+    item_type: String,
+    item_id: String,
+    event: Event,
+    changes: Map<String, Value>,
+    object: Map<String, Value>,
+) -> Result<(), DbErr> {
+    let now = chrono::Utc::now();
+    versions::Entity::insert(versions::ActiveModel {
+        created_at: Set(now.into()),
+        updated_at: Set(now.into()),
+        item_type: Set(item_type),
+        item_id: Set(item_id),
+        event: Set(event.as_str().to_string()),
+        actor: Set(current_actor()),
+        changes: Set(Value::Object(changes)),
+        object: Set(Value::Object(object)),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, Database, Set};
+    use serde::Deserialize;
+
+    use super::*;
+
+    mod posts {
+        use sea_orm::{entity::prelude::*, TryIntoModel};
+        use serde::{Deserialize, Serialize};
+
+        use crate::model::audit::Auditable;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+        #[sea_orm(table_name = "posts")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        #[async_trait::async_trait]
+        impl ActiveModelBehavior for ActiveModel {
+            async fn after_save<C>(model: Model, db: &C, insert: bool) -> Result<Model, DbErr>
+            where
+                C: ConnectionTrait,
+            {
+                Entity::audit_save(db, &model, insert).await?;
+                Ok(model)
+            }
+
+            async fn after_delete<C>(self, db: &C) -> Result<Self, DbErr>
+            where
+                C: ConnectionTrait,
+            {
+                Entity::audit_delete(db, &self.clone().try_into_model()?).await?;
+                Ok(self)
+            }
+        }
+
+        impl Auditable for Entity {}
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Change {
+        event: String,
+        actor: Option<String>,
+        changes: serde_json::Value,
+    }
+
+    #[tokio::test]
+    async fn can_record_and_revert_versions() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT NOT NULL);
+             CREATE TABLE versions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                item_type TEXT NOT NULL,
+                item_id TEXT NOT NULL,
+                event TEXT NOT NULL,
+                actor TEXT,
+                changes TEXT NOT NULL,
+                object TEXT NOT NULL
+             );",
+        )
+        .await
+        .unwrap();
+
+        let post = with_actor(Some("alice".to_string()), async {
+            posts::ActiveModel {
+                title: Set("draft".to_string()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap()
+        })
+        .await;
+
+        let mut edit = post.clone().into_active_model();
+        edit.title = Set("published".to_string());
+        let post = edit.update(&db).await.unwrap();
+        // an update changing nothing is not recorded
+        post.clone().into_active_model().update(&db).await.unwrap();
+        post.clone().delete(&db).await.unwrap();
+
+        let versions = posts::Entity::versions(&db, &post).await.unwrap();
+        let changes = versions
+            .iter()
+            .map(|version| Change {
+                event: version.event.clone(),
+                actor: version.actor.clone(),
+                changes: version.changes.clone(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    event: "create".to_string(),
+                    actor: Some("alice".to_string()),
+                    changes: json!({ "id": [null, 1], "title": [null, "draft"] }),
+                },
+                Change {
+                    event: "update".to_string(),
+                    actor: None,
+                    changes: json!({ "title": ["draft", "published"] }),
+                },
+                Change {
+                    event: "delete".to_string(),
+                    actor: None,
+                    changes: json!({ "id": [1, null], "title": ["published", null] }),
+                },
+            ]
+        );
+        assert!(versions.iter().all(|version| version.item_id == "1"));
+
+        let restored = posts::Entity::revert(&db, &versions[0]).await.unwrap();
+        assert_eq!(restored.title, "draft");
+        assert_eq!(
+            posts::Entity::find_by_id(1).one(&db).await.unwrap(),
+            Some(restored)
+        );
+        assert_eq!(posts::Entity::versions(&db, &post).await.unwrap().len(), 4);
+    }
+}
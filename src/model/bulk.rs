@@ -0,0 +1,237 @@
+//! # Bulk Inserts
+//!
+//! Imports many rows in a few statements: [`BulkInsert`] inserts active
+//! models by batches of multi-row `INSERT`s, and upserts them with an
+//! `ON CONFLICT` clause, instead of a statement and a round-trip per row.
+//! ```rust,ignore
+//! let ids = BulkInsert::new(rows)
+//!     .batch_size(500)
+//!     .on_conflict(
+//!         OnConflict::column(users::Column::Email)
+//!             .update_column(users::Column::Name)
+//!             .to_owned(),
+//!     )
+//!     .exec_with_returning_keys(&ctx.db)
+//!     .await?;
+//! ```
+//!
+//! Every batch is its own statement: run the import in a transaction for it
+//! to be all or nothing. The rows skip the hooks of `ActiveModelBehavior`,
+//! so the columns they set, such as the `pid`, need a database default or a
+//! value in the models. Each row binds a parameter per column and Postgres
+//! accepts 65535 of them per statement, which the batch size times the
+//! number of columns must stay below.
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ConnectionTrait, DbErr, EntityTrait, Insert,
+    IntoActiveModel, PrimaryKeyTrait,
+};
+
+use super::{ModelError, ModelResult};
+
+/// The rows inserted by a statement, unless set otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// The primary key values of an entity.
+type PrimaryKeyValue<A> =
+    <<<A as ActiveModelTrait>::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType;
+
+/// The batched insert of active models.
+pub struct BulkInsert<A: ActiveModelTrait> {
+    models: Vec<A>,
+    batch_size: usize,
+    on_conflict: Option<OnConflict>,
+}
+
+impl<A> BulkInsert<A>
+where
+    A: ActiveModelTrait + Send,
+{
+    /// Inserts the models by batches of [`DEFAULT_BATCH_SIZE`] rows.
+    #[must_use]
+    pub fn new(models: impl IntoIterator<Item = A>) -> Self {
+        Self {
+            models: models.into_iter().collect(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            on_conflict: None,
+        }
+    }
+
+    /// Sets the rows inserted by a statement.
+    #[must_use]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Upserts the rows: what to do with the rows conflicting with existing
+    /// ones, updating some of their columns or leaving them as they are.
+    #[must_use]
+    pub fn on_conflict(mut self, on_conflict: OnConflict) -> Self {
+        self.on_conflict = Some(on_conflict);
+        self
+    }
+
+    /// Inserts the rows, returning the number of inserted (or updated) rows.
+    ///
+    /// # Errors
+    ///
+    /// When a batch fails, leaving the previous batches inserted
+    pub async fn exec<C>(self, db: &C) -> ModelResult<u64>
+    where
+        C: ConnectionTrait,
+        <A::Entity as EntityTrait>::Model: IntoActiveModel<A>,
+    {
+        let mut inserted = 0;
+        for insert in self.statements() {
+            match insert.exec_without_returning(db).await {
+                Ok(rows) => inserted += rows,
+                // every row of the batch conflicted and was left as it is
+                Err(DbErr::RecordNotInserted) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(inserted)
+    }
+
+    /// Inserts the rows, returning the primary keys of the inserted (or
+    /// updated) rows, on the backends supporting `RETURNING`: Postgres, and
+    /// `SQLite` with the `sqlite-use-returning-for-3_35` feature of `SeaORM`.
+    ///
+    /// # Errors
+    ///
+    /// When the backend does not support `RETURNING`, or a batch fails,
+    /// leaving the previous batches inserted
+    pub async fn exec_with_returning_keys<C>(self, db: &C) -> ModelResult<Vec<PrimaryKeyValue<A>>>
+    where
+        C: ConnectionTrait,
+        <A::Entity as EntityTrait>::Model: IntoActiveModel<A>,
+    {
+        if !db.support_returning() {
+            return Err(ModelError::Message(
+                "the database backend does not support RETURNING".to_string(),
+            ));
+        }
+        let mut keys = Vec::with_capacity(self.models.len());
+        for insert in self.statements() {
+            match insert.exec_with_returning_keys(db).await {
+                Ok(batch) => keys.extend(batch),
+                Err(DbErr::RecordNotInserted) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(keys)
+    }
+
+    fn statements(self) -> Vec<Insert<A>> {
+        let mut models = self.models.into_iter().peekable();
+        let mut statements = vec![];
+        while models.peek().is_some() {
+            let insert = A::Entity::insert_many(models.by_ref().take(self.batch_size));
+            statements.push(match &self.on_conflict {
+                Some(on_conflict) => insert.on_conflict(on_conflict.clone()),
+                None => insert,
+            });
+        }
+        statements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, Database, Set};
+
+    use super::*;
+
+    mod users {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "users")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            #[sea_orm(unique)]
+            pub email: String,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    fn user(email: &str, name: &str) -> users::ActiveModel {
+        users::ActiveModel {
+            email: Set(email.to_string()),
+            name: Set(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn can_insert_and_upsert_in_batches() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE, name TEXT NOT NULL)",
+        )
+        .await
+        .unwrap();
+
+        let inserted =
+            BulkInsert::new((1..=5).map(|i| user(&format!("user{i}@example.com"), "user")))
+                .batch_size(2)
+                .exec(&db)
+                .await
+                .unwrap();
+        assert_eq!(inserted, 5);
+
+        let upserted = BulkInsert::new([
+            user("user1@example.com", "alice"),
+            user("user6@example.com", "bob"),
+        ])
+        .on_conflict(
+            OnConflict::column(users::Column::Email)
+                .update_column(users::Column::Name)
+                .to_owned(),
+        )
+        .exec(&db)
+        .await
+        .unwrap();
+        assert_eq!(upserted, 2);
+        // the bundled SQLite is used without RETURNING
+        assert!(BulkInsert::new([user("user7@example.com", "eve")])
+            .exec_with_returning_keys(&db)
+            .await
+            .is_err());
+
+        let inserted = BulkInsert::new([
+            user("user1@example.com", "carol"),
+            user("user2@example.com", "carol"),
+        ])
+        .on_conflict(
+            OnConflict::column(users::Column::Email)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec(&db)
+        .await
+        .unwrap();
+        assert_eq!(inserted, 0);
+
+        assert!(BulkInsert::new([user("user1@example.com", "dave")])
+            .exec(&db)
+            .await
+            .is_err());
+        assert!(BulkInsert::<users::ActiveModel>::new([])
+            .exec(&db)
+            .await
+            .is_ok());
+
+        let users = users::Entity::find().all(&db).await.unwrap();
+        assert_eq!(users.len(), 6);
+        assert_eq!(users[0].name, "alice");
+        assert_eq!(users[1].name, "user");
+        assert_eq!(users[5].name, "bob");
+    }
+}
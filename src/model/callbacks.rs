@@ -0,0 +1,329 @@
+//! # Lifecycle Callbacks
+//!
+//! Gathers the validations and side effects of a model in one conventional
+//! place, such as generating a slug, invalidating a cache or enqueueing a
+//! job: the [`Callbacks`] of an active model run around the saves and
+//! deletions of [`CallbackActiveModel`], with the [`AppContext`] and the
+//! connection the change is made on.
+//!
+//! An error returned by a `before_*` callback aborts the change, and the one
+//! of an `after_*` callback is returned once the change is made: make the
+//! change in a transaction for it to be rolled back too. The callbacks run
+//! around the hooks of [`ActiveModelBehavior`], which keep running on every
+//! save, with or without callbacks.
+//!
+//! ```rust,ignore
+//! #[async_trait]
+//! impl Callbacks for ActiveModel {
+//!     async fn before_save<C>(&mut self, _ctx: &AppContext, _db: &C, insert: bool) -> ModelResult<()>
+//!     where
+//!         C: ConnectionTrait,
+//!     {
+//!         if insert {
+//!             self.slug = Set(slug::slugify(self.title.as_ref()));
+//!         }
+//!         Ok(())
+//!     }
+//!
+//!     async fn after_create<C>(model: &Model, ctx: &AppContext, _db: &C) -> ModelResult<()>
+//!     where
+//!         C: ConnectionTrait,
+//!     {
+//!         NotifyFollowers::perform_later(ctx, model.id).await.map_err(ModelError::wrap)
+//!     }
+//! }
+//!
+//! let post = params.into_active_model().insert_with_callbacks(&ctx, &ctx.db).await?;
+//! ```
+use async_trait::async_trait;
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ConnectionTrait, EntityTrait, IntoActiveModel,
+};
+
+use super::ModelResult;
+use crate::app::AppContext;
+
+/// The lifecycle callbacks of an active model, all doing nothing by default.
+#[async_trait]
+pub trait Callbacks: ActiveModelTrait + ActiveModelBehavior + Send + Sync
+where
+    <Self::Entity as EntityTrait>::Model: IntoActiveModel<Self> + Sync,
+{
+    /// Runs before inserts and updates, and can change the model or reject
+    /// the save.
+    async fn before_save<C>(&mut self, _ctx: &AppContext, _db: &C, _insert: bool) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        Ok(())
+    }
+
+    /// Runs after the model is inserted.
+    async fn after_create<C>(
+        _model: &<Self::Entity as EntityTrait>::Model,
+        _ctx: &AppContext,
+        _db: &C,
+    ) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        Ok(())
+    }
+
+    /// Runs after the model is updated.
+    async fn after_update<C>(
+        _model: &<Self::Entity as EntityTrait>::Model,
+        _ctx: &AppContext,
+        _db: &C,
+    ) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        Ok(())
+    }
+
+    /// Runs before the model is deleted, and can reject the deletion.
+    async fn before_destroy<C>(&self, _ctx: &AppContext, _db: &C) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        Ok(())
+    }
+
+    /// Runs after the model is deleted.
+    async fn after_destroy<C>(&self, _ctx: &AppContext, _db: &C) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        Ok(())
+    }
+}
+
+/// The changes of the active models running their [`Callbacks`].
+#[async_trait]
+pub trait CallbackActiveModel: Callbacks
+where
+    <Self::Entity as EntityTrait>::Model: IntoActiveModel<Self> + Sync,
+{
+    /// Inserts the model, between `before_save` and `after_create`.
+    ///
+    /// # Errors
+    ///
+    /// When a callback or the insert fails
+    async fn insert_with_callbacks<C>(
+        mut self,
+        ctx: &AppContext,
+        db: &C,
+    ) -> ModelResult<<Self::Entity as EntityTrait>::Model>
+    where
+        C: ConnectionTrait,
+    {
+        Callbacks::before_save(&mut self, ctx, db, true).await?;
+        let model = self.insert(db).await?;
+        Self::after_create(&model, ctx, db).await?;
+        Ok(model)
+    }
+
+    /// Updates the model, between `before_save` and `after_update`.
+    ///
+    /// # Errors
+    ///
+    /// When a callback or the update fails
+    async fn update_with_callbacks<C>(
+        mut self,
+        ctx: &AppContext,
+        db: &C,
+    ) -> ModelResult<<Self::Entity as EntityTrait>::Model>
+    where
+        C: ConnectionTrait,
+    {
+        Callbacks::before_save(&mut self, ctx, db, false).await?;
+        let model = self.update(db).await?;
+        Self::after_update(&model, ctx, db).await?;
+        Ok(model)
+    }
+
+    /// Deletes the model, between `before_destroy` and `after_destroy`.
+    ///
+    /// # Errors
+    ///
+    /// When a callback or the delete fails
+    async fn delete_with_callbacks<C>(self, ctx: &AppContext, db: &C) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        self.before_destroy(ctx, db).await?;
+        self.clone().delete(db).await?;
+        self.after_destroy(ctx, db).await
+    }
+}
+
+impl<A> CallbackActiveModel for A
+where
+    A: Callbacks,
+    <A::Entity as EntityTrait>::Model: IntoActiveModel<A> + Sync,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use sea_orm::{entity::prelude::*, Set};
+
+    use super::*;
+    use crate::{model::ModelError, tests_cfg};
+
+    static EVENTS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    mod posts {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "posts")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+            pub slug: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    #[async_trait]
+    impl Callbacks for posts::ActiveModel {
+        async fn before_save<C>(
+            &mut self,
+            _ctx: &AppContext,
+            _db: &C,
+            _insert: bool,
+        ) -> ModelResult<()>
+        where
+            C: ConnectionTrait,
+        {
+            let title = self.title.as_ref().trim();
+            if title.is_empty() {
+                return Err(ModelError::msg("the title can't be blank"));
+            }
+            self.slug = Set(title.to_lowercase().replace(' ', "-"));
+            Ok(())
+        }
+
+        async fn after_create<C>(
+            model: &posts::Model,
+            _ctx: &AppContext,
+            _db: &C,
+        ) -> ModelResult<()>
+        where
+            C: ConnectionTrait,
+        {
+            EVENTS
+                .lock()
+                .unwrap()
+                .push(format!("created {}", model.slug));
+            Ok(())
+        }
+
+        async fn after_update<C>(
+            model: &posts::Model,
+            _ctx: &AppContext,
+            _db: &C,
+        ) -> ModelResult<()>
+        where
+            C: ConnectionTrait,
+        {
+            EVENTS
+                .lock()
+                .unwrap()
+                .push(format!("updated {}", model.slug));
+            Ok(())
+        }
+
+        async fn before_destroy<C>(&self, _ctx: &AppContext, _db: &C) -> ModelResult<()>
+        where
+            C: ConnectionTrait,
+        {
+            if self.slug.as_ref() == "pinned" {
+                return Err(ModelError::msg("pinned posts can't be deleted"));
+            }
+            Ok(())
+        }
+
+        async fn after_destroy<C>(&self, _ctx: &AppContext, _db: &C) -> ModelResult<()>
+        where
+            C: ConnectionTrait,
+        {
+            EVENTS
+                .lock()
+                .unwrap()
+                .push(format!("destroyed {}", self.slug.as_ref()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn can_run_callbacks() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let db = &ctx.db;
+        db.execute_unprepared(
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT NOT NULL, slug TEXT NOT NULL)",
+        )
+        .await
+        .unwrap();
+
+        let post = posts::ActiveModel {
+            title: Set("Hello World".to_string()),
+            slug: Set(String::new()),
+            ..Default::default()
+        }
+        .insert_with_callbacks(&ctx, db)
+        .await
+        .unwrap();
+        assert_eq!(post.slug, "hello-world");
+
+        let mut post = post.into_active_model();
+        post.title = Set("Pinned".to_string());
+        let post = post.update_with_callbacks(&ctx, db).await.unwrap();
+        assert_eq!(post.slug, "pinned");
+
+        assert!(posts::ActiveModel {
+            title: Set("  ".to_string()),
+            slug: Set(String::new()),
+            ..Default::default()
+        }
+        .insert_with_callbacks(&ctx, db)
+        .await
+        .is_err());
+        assert!(post
+            .clone()
+            .into_active_model()
+            .delete_with_callbacks(&ctx, db)
+            .await
+            .is_err());
+        assert_eq!(posts::Entity::find().count(db).await.unwrap(), 1);
+
+        let mut post = post.into_active_model();
+        post.title = Set("Archived".to_string());
+        post.update_with_callbacks(&ctx, db)
+            .await
+            .unwrap()
+            .into_active_model()
+            .delete_with_callbacks(&ctx, db)
+            .await
+            .unwrap();
+        assert_eq!(posts::Entity::find().count(db).await.unwrap(), 0);
+        assert_eq!(
+            *EVENTS.lock().unwrap(),
+            vec![
+                "created hello-world",
+                "updated pinned",
+                "updated archived",
+                "destroyed archived"
+            ]
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! # Counter Caches
+//!
+//! Keeps the number of the children of a row in a column of its table, such
+//! as the `comments_count` of `posts`, so that listing the rows with their
+//! number of children does not count them for every row.
+//!
+//! Add the counter column in a migration with
+//! [`crate::schema::add_counter_cache`], which also counts the existing
+//! children, then declare the counters of the child entity and maintain them
+//! from its hooks:
+//! ```rust,ignore
+//! impl CounterCached for Entity {
+//!     fn counter_caches() -> Vec<CounterCache> {
+//!         vec![CounterCache::new("posts", "comments")]
+//!     }
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl ActiveModelBehavior for ActiveModel {
+//!     async fn after_save<C>(model: Model, db: &C, insert: bool) -> Result<Model, DbErr>
+//!     where
+//!         C: ConnectionTrait,
+//!     {
+//!         if insert {
+//!             Entity::increment_counters(db, &model).await?;
+//!         }
+//!         Ok(model)
+//!     }
+//!
+//!     async fn after_delete<C>(self, db: &C) -> Result<Self, DbErr>
+//!     where
+//!         C: ConnectionTrait,
+//!     {
+//!         Entity::decrement_counters(db, &self.clone().try_into_model()?).await?;
+//!         Ok(self)
+//!     }
+//! }
+//! ```
+//!
+//! The counters follow the rows created and deleted through the hooks: the
+//! bulk `insert_many` and `delete_many` statements, and the children moved
+//! to another parent, leave them off until they are counted again with
+//! [`CounterCache::backfill`], or `cargo loco db counter-cache:backfill`.
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use sea_orm::{
+    sea_query::{Alias, Asterisk, Expr, Func, Query, SimpleExpr},
+    ConnectionTrait, DbErr, EntityTrait, ModelTrait, Value,
+};
+
+/// A counter column of a parent table, counting the rows of a child table
+/// which reference the parent by its `id`.
+#[derive(Debug, Clone)]
+pub struct CounterCache {
+    /// The parent table, such as `posts`
+    pub table: String,
+    /// The counter column of the parent table, such as `comments_count`
+    pub column: String,
+    /// The child table, such as `comments`
+    pub children: String,
+    /// The column of the child table referencing the parent, such as
+    /// `post_id`
+    pub foreign_key: String,
+}
+
+impl CounterCache {
+    /// Counts the `children` of the rows of `table` in its `<children>_count`
+    /// column, the children referencing their parent with the
+    /// `<singular table>_id` column.
+    #[must_use]
+    pub fn new(table: &str, children: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            column: format!("{children}_count"),
+            children: children.to_string(),
+            foreign_key: crate::schema::reference_id(table),
+        }
+    }
+
+    /// Sets the counter column of the parent table.
+    #[must_use]
+    pub fn column(mut self, column: &str) -> Self {
+        self.column = column.to_string();
+        self
+    }
+
+    /// Sets the column of the child table referencing the parent.
+    #[must_use]
+    pub fn foreign_key(mut self, foreign_key: &str) -> Self {
+        self.foreign_key = foreign_key.to_string();
+        self
+    }
+
+    /// Counts the children of every parent again, returning the number of
+    /// updated parents.
+    ///
+    /// # Errors
+    /// When the counters could not be updated
+    pub async fn backfill<C>(&self, db: &C) -> Result<u64, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let count = Query::select()
+            .expr(Func::count(Expr::col(Asterisk)))
+            .from(Alias::new(&self.children))
+            .and_where(
+                Expr::col((Alias::new(&self.children), Alias::new(&self.foreign_key)))
+                    .equals((Alias::new(&self.table), Alias::new("id"))),
+            )
+            .to_owned();
+        let update = Query::update()
+            .table(Alias::new(&self.table))
+            .value(
+                Alias::new(&self.column),
+                SimpleExpr::SubQuery(None, Box::new(count.into_sub_query_statement())),
+            )
+            .to_owned();
+        let backend = db.get_database_backend();
+        Ok(db.execute(backend.build(&update)).await?.rows_affected())
+    }
+
+    /// Adds `delta` to the counter of a parent.
+    async fn shift<C>(&self, db: &C, parent_id: Value, delta: i32) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let update = Query::update()
+            .table(Alias::new(&self.table))
+            .value(
+                Alias::new(&self.column),
+                Expr::col(Alias::new(&self.column)).add(delta),
+            )
+            .and_where(Expr::col(Alias::new("id")).eq(parent_id))
+            .to_owned();
+        let backend = db.get_database_backend();
+        db.execute(backend.build(&update)).await?;
+        Ok(())
+    }
+}
+
+/// Entities whose rows are counted by their parents, see [`CounterCache`].
+#[async_trait]
+pub trait CounterCached: EntityTrait
+where
+    Self::Model: Sync,
+{
+    /// The counters of the parents of the rows.
+    fn counter_caches() -> Vec<CounterCache>;
+
+    /// Increments the counters of the parents of a new row, from
+    /// `after_save`.
+    ///
+    /// # Errors
+    /// When a foreign key is not a column of the entity, or a counter could
+    /// not be updated
+    async fn increment_counters<C>(db: &C, model: &Self::Model) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        shift_counters::<Self, C>(db, model, 1).await
+    }
+
+    /// Decrements the counters of the parents of a deleted row, from
+    /// `after_delete`.
+    ///
+    /// # Errors
+    /// When a foreign key is not a column of the entity, or a counter could
+    /// not be updated
+    async fn decrement_counters<C>(db: &C, model: &Self::Model) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        shift_counters::<Self, C>(db, model, -1).await
+    }
+}
+
+async fn shift_counters<E, C>(db: &C, model: &E::Model, delta: i32) -> Result<(), DbErr>
+where
+    E: CounterCached,
+    E::Model: Sync,
+    C: ConnectionTrait,
+{
+    for counter in E::counter_caches() {
+        let column = E::Column::from_str(&counter.foreign_key).map_err(|_| {
+            DbErr::Custom(format!(
+                "`{}` is not a column of `{}`",
+                counter.foreign_key, counter.children
+            ))
+        })?;
+        // a child without parent matches no row
+        counter.shift(db, model.get(column), delta).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, Database, Set};
+
+    use super::*;
+
+    mod comments {
+        use sea_orm::{entity::prelude::*, TryIntoModel};
+
+        use crate::model::counter_cache::{CounterCache, CounterCached};
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "comments")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub post_id: Option<i32>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        #[async_trait::async_trait]
+        impl ActiveModelBehavior for ActiveModel {
+            async fn after_save<C>(model: Model, db: &C, insert: bool) -> Result<Model, DbErr>
+            where
+                C: ConnectionTrait,
+            {
+                if insert {
+                    Entity::increment_counters(db, &model).await?;
+                }
+                Ok(model)
+            }
+
+            async fn after_delete<C>(self, db: &C) -> Result<Self, DbErr>
+            where
+                C: ConnectionTrait,
+            {
+                Entity::decrement_counters(db, &self.clone().try_into_model()?).await?;
+                Ok(self)
+            }
+        }
+
+        impl CounterCached for Entity {
+            fn counter_caches() -> Vec<CounterCache> {
+                vec![CounterCache::new("posts", "comments")]
+            }
+        }
+    }
+
+    async fn counts(db: &DatabaseConnection) -> Vec<i32> {
+        db.query_all(sea_orm::Statement::from_string(
+            db.get_database_backend(),
+            "SELECT comments_count FROM posts ORDER BY id",
+        ))
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| row.try_get::<i32>("", "comments_count").unwrap())
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn can_maintain_counter_caches() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, comments_count INTEGER NOT NULL DEFAULT 0);
+             CREATE TABLE comments (id INTEGER PRIMARY KEY, post_id INTEGER);
+             INSERT INTO posts (id) VALUES (1), (2);",
+        )
+        .await
+        .unwrap();
+
+        let mut comments = vec![];
+        for post_id in [Some(1), Some(1), Some(2), None] {
+            comments.push(
+                comments::ActiveModel {
+                    post_id: Set(post_id),
+                    ..Default::default()
+                }
+                .insert(&db)
+                .await
+                .unwrap(),
+            );
+        }
+        assert_eq!(counts(&db).await, vec![2, 1]);
+
+        comments[0].clone().delete(&db).await.unwrap();
+        comments[3].clone().delete(&db).await.unwrap();
+        assert_eq!(counts(&db).await, vec![1, 1]);
+
+        comments::Entity::delete_many().exec(&db).await.unwrap();
+        assert_eq!(counts(&db).await, vec![1, 1]);
+        assert_eq!(
+            CounterCache::new("posts", "comments")
+                .backfill(&db)
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(counts(&db).await, vec![0, 0]);
+    }
+}
@@ -0,0 +1,631 @@
+//! # Encrypted Attributes
+//!
+//! Stores the personal data of the models, such as emails or phone numbers,
+//! encrypted with AES-256-GCM: the [`Encrypted`] columns hold their values
+//! encrypted, and are decrypted with the encryptor of the context,
+//! [`AppContext::encryptor`](crate::app::AppContext::encryptor).
+//!
+//! The keys are 32 random bytes in base64, such as the output of
+//! `openssl rand -base64 32`, set in the database configuration:
+//! ```yaml
+//! database:
+//!   encryption:
+//!     keys:
+//!       - {{ get_env(name="ENCRYPTION_KEY") }}
+//!       - {{ get_env(name="PREVIOUS_ENCRYPTION_KEY") }}
+//! ```
+//! The first key encrypts the values, and all of them decrypt: to rotate the
+//! key, put the new one first, then run `cargo loco db reencrypt` to encrypt
+//! the rows again with it before removing the previous one. Keys held by a
+//! KMS are set to [`AppContext::encryptor`](crate::app::AppContext) from
+//! `Hooks::after_context`.
+//!
+//! Every attribute is bound to its table and column, declared with
+//! [`encrypted_column!`](crate::encrypted_column): its values are
+//! authenticated with them, so they can not be decrypted once copied to
+//! another column.
+//!
+//! ```rust,ignore
+//! loco_rs::encrypted_column!(Phone, "users", "phone");
+//! loco_rs::encrypted_column!(TaxId, "users", "tax_id");
+//!
+//! #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+//! #[sea_orm(table_name = "users")]
+//! pub struct Model {
+//!     #[sea_orm(primary_key)]
+//!     pub id: i32,
+//!     #[sea_orm(column_type = "Text")]
+//!     pub phone: Encrypted<Phone>,
+//!     #[sea_orm(column_type = "Text", nullable)]
+//!     pub tax_id: Option<Encrypted<TaxId>>,
+//! }
+//!
+//! let encryptor = ctx.encryptor()?;
+//! let user = users::ActiveModel {
+//!     phone: Set(Encrypted::new(encryptor, "555-0100")?),
+//!     ..Default::default()
+//! }
+//! .insert(&ctx.db)
+//! .await?;
+//! let phone = user.phone.decrypt(encryptor)?;
+//! ```
+//!
+//! Every encryption uses a random nonce, so the same value is encrypted
+//! differently in every row: the encrypted columns can not be filtered,
+//! sorted or made unique by the database.
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, KeyInit, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sea_orm::{
+    sea_query::{
+        Alias, ArrayType, ColumnType, Expr, Nullable, Query, Value, ValueType, ValueTypeErr,
+    },
+    ColIdx, ConnectionTrait, QueryResult, TryGetError, TryGetable,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use super::{ModelError, ModelResult};
+
+/// The prefix of the values bound to a context, such as the table and column
+/// of an attribute, followed by the id of their key.
+const BOUND_PREFIX: &str = "enc:v2:";
+
+const NONCE_LEN: usize = 12;
+
+/// The rows encrypted again by a query of [`reencrypt`].
+const REENCRYPT_BATCH_SIZE: u64 = 500;
+
+/// Encryption configuration of the model attributes
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// The keys, as 32 bytes in base64: the first one encrypts the values,
+    /// and all of them decrypt
+    pub keys: Vec<String>,
+}
+
+/// Encrypts and decrypts the attributes with AES-256-GCM.
+pub struct Encryptor {
+    keys: Vec<(String, Aes256Gcm)>,
+}
+
+impl fmt::Debug for Encryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encryptor")
+            .field(
+                "keys",
+                &self.keys.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Encryptor {
+    /// Creates an encryptor from raw keys, the first one encrypting the
+    /// values.
+    ///
+    /// # Errors
+    ///
+    /// When there are no keys, or a key is not 32 bytes long
+    pub fn new(keys: &[Vec<u8>]) -> ModelResult<Self> {
+        if keys.is_empty() {
+            return Err(ModelError::msg("at least one encryption key is required"));
+        }
+        let keys = keys
+            .iter()
+            .map(|key| {
+                let cipher = Aes256Gcm::new_from_slice(key)
+                    .map_err(|_| ModelError::msg("encryption keys must be 32 bytes long"))?;
+                Ok((key_id(key), cipher))
+            })
+            .collect::<ModelResult<Vec<_>>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Creates an encryptor from the base64 keys of the configuration.
+    ///
+    /// # Errors
+    ///
+    /// When a key is not valid base64, or is not 32 bytes long
+    pub fn from_config(config: &Config) -> ModelResult<Self> {
+        let keys = config
+            .keys
+            .iter()
+            .map(|key| {
+                STANDARD
+                    .decode(key.trim())
+                    .map_err(|_| ModelError::msg("encryption keys must be base64"))
+            })
+            .collect::<ModelResult<Vec<_>>>()?;
+        Self::new(&keys)
+    }
+
+    /// The id of the key encrypting the values.
+    #[must_use]
+    pub fn key_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    /// Encrypts a value with the first key, bound to a context such as the
+    /// table and column it is stored in: it only decrypts with the same
+    /// context.
+    ///
+    /// # Errors
+    ///
+    /// When the value can not be encrypted
+    pub fn encrypt_bound(&self, plaintext: &str, context: &str) -> ModelResult<String> {
+        self.seal(plaintext, context.as_bytes())
+    }
+
+    /// Decrypts a value bound to a context by [`Encryptor::encrypt_bound`].
+    ///
+    /// # Errors
+    ///
+    /// When the value is not bound to a context, its key is unknown, it is
+    /// bound to another context, or it was tampered with
+    pub fn decrypt_bound(&self, ciphertext: &str, context: &str) -> ModelResult<String> {
+        let (id, payload) = ciphertext
+            .strip_prefix(BOUND_PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+            .ok_or_else(|| ModelError::msg("the value is not encrypted"))?;
+        self.open(id, payload, context.as_bytes())
+    }
+
+    /// Returns `true` when the value is bound to a context and encrypted with
+    /// the first key.
+    #[must_use]
+    pub fn is_current(&self, value: &str) -> bool {
+        value
+            .strip_prefix(BOUND_PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+            .is_some_and(|(id, _)| id == self.key_id())
+    }
+
+    fn seal(&self, plaintext: &str, aad: &[u8]) -> ModelResult<String> {
+        let (id, cipher) = &self.keys[0];
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let mut payload = nonce.to_vec();
+        payload.extend(
+            cipher
+                .encrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: plaintext.as_bytes(),
+                        aad,
+                    },
+                )
+                .map_err(|_| ModelError::msg("could not encrypt the value"))?,
+        );
+        Ok(format!("{BOUND_PREFIX}{id}:{}", STANDARD.encode(payload)))
+    }
+
+    fn open(&self, id: &str, payload: &str, aad: &[u8]) -> ModelResult<String> {
+        let (_, cipher) = self
+            .keys
+            .iter()
+            .find(|(key_id, _)| key_id == id)
+            .ok_or_else(|| ModelError::Message(format!("unknown encryption key `{id}`")))?;
+        let payload = STANDARD
+            .decode(payload)
+            .map_err(|_| ModelError::msg("the encrypted value is malformed"))?;
+        if payload.len() < NONCE_LEN {
+            return Err(ModelError::msg("the encrypted value is malformed"));
+        }
+        let (nonce, msg) = payload.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg, aad })
+            .map_err(|_| ModelError::msg("could not decrypt the value"))?;
+        String::from_utf8(plaintext).map_err(ModelError::wrap)
+    }
+}
+
+/// The id of a key, prefixing the values it encrypts: the start of the
+/// SHA-256 of the key, which tells the keys apart without revealing them.
+fn key_id(key: &[u8]) -> String {
+    crate::hash::to_hex(&Sha256::digest(key)[..4])
+}
+
+/// Returns `true` when the value was encrypted by an [`Encryptor`].
+#[must_use]
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(BOUND_PREFIX)
+}
+
+/// The table and column of an [`Encrypted`] attribute, which its values are
+/// bound to. Declared with [`encrypted_column!`](crate::encrypted_column).
+pub trait EncryptedColumn {
+    const TABLE: &'static str;
+    const COLUMN: &'static str;
+
+    /// The context the values are bound to, as `table.column`.
+    #[must_use]
+    fn context() -> String {
+        format!("{}.{}", Self::TABLE, Self::COLUMN)
+    }
+}
+
+/// Declares the table and column of an [`Encrypted`] attribute.
+///
+/// ```rust,ignore
+/// loco_rs::encrypted_column!(Phone, "users", "phone");
+/// ```
+#[macro_export]
+macro_rules! encrypted_column {
+    ($name:ident, $table:expr, $column:expr) => {
+        pub struct $name;
+
+        impl $crate::model::encryption::EncryptedColumn for $name {
+            const TABLE: &'static str = $table;
+            const COLUMN: &'static str = $column;
+        }
+    };
+}
+
+/// A text attribute of the column `C`, stored encrypted.
+///
+/// It holds the encrypted value, created and decrypted with an [`Encryptor`],
+/// which it serializes as, so that the plain text only leaves the model once
+/// decrypted. It hides it from `Debug` so that it stays out of the logs.
+pub struct Encrypted<C> {
+    ciphertext: String,
+    column: PhantomData<fn() -> C>,
+}
+
+impl<C: EncryptedColumn> Encrypted<C> {
+    /// Encrypts the plain text of the attribute.
+    ///
+    /// # Errors
+    ///
+    /// When the value can not be encrypted
+    pub fn new(encryptor: &Encryptor, plaintext: &str) -> ModelResult<Self> {
+        Ok(Self::from_ciphertext(
+            encryptor.encrypt_bound(plaintext, &C::context())?,
+        ))
+    }
+
+    /// Decrypts the plain text of the attribute.
+    ///
+    /// # Errors
+    ///
+    /// When the value is not encrypted, its key is unknown, it was copied
+    /// from another column, or it was tampered with
+    pub fn decrypt(&self, encryptor: &Encryptor) -> ModelResult<String> {
+        encryptor.decrypt_bound(&self.ciphertext, &C::context())
+    }
+}
+
+impl<C> Encrypted<C> {
+    const fn from_ciphertext(ciphertext: String) -> Self {
+        Self {
+            ciphertext,
+            column: PhantomData,
+        }
+    }
+}
+
+impl<C> Clone for Encrypted<C> {
+    fn clone(&self) -> Self {
+        Self::from_ciphertext(self.ciphertext.clone())
+    }
+}
+
+impl<C> PartialEq for Encrypted<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ciphertext == other.ciphertext
+    }
+}
+
+impl<C> Eq for Encrypted<C> {}
+
+impl<C> Hash for Encrypted<C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ciphertext.hash(state);
+    }
+}
+
+impl<C> fmt::Debug for Encrypted<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Encrypted(..)")
+    }
+}
+
+impl<C> Serialize for Encrypted<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.ciphertext.serialize(serializer)
+    }
+}
+
+impl<'de, C> Deserialize<'de> for Encrypted<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from_ciphertext(String::deserialize(deserializer)?))
+    }
+}
+
+impl<C> From<Encrypted<C>> for Value {
+    fn from(value: Encrypted<C>) -> Self {
+        Self::String(Some(Box::new(value.ciphertext)))
+    }
+}
+
+impl<C> Nullable for Encrypted<C> {
+    fn null() -> Value {
+        Value::String(None)
+    }
+}
+
+impl<C: EncryptedColumn> ValueType for Encrypted<C> {
+    fn try_from(value: Value) -> Result<Self, ValueTypeErr> {
+        match value {
+            Value::String(Some(ciphertext)) => Ok(Self::from_ciphertext(*ciphertext)),
+            _ => Err(ValueTypeErr),
+        }
+    }
+
+    fn type_name() -> String {
+        "Encrypted".to_string()
+    }
+
+    fn array_type() -> ArrayType {
+        ArrayType::String
+    }
+
+    fn column_type() -> ColumnType {
+        ColumnType::Text
+    }
+}
+
+impl<C: EncryptedColumn> TryGetable for Encrypted<C> {
+    fn try_get_by<I: ColIdx>(res: &QueryResult, index: I) -> Result<Self, TryGetError> {
+        String::try_get_by(res, index).map(Self::from_ciphertext)
+    }
+}
+
+/// Encrypts the values of the columns of a table again with the first key,
+/// bound to their table and column, when they are encrypted with a previous
+/// key or not encrypted yet, such as after rotating the key or encrypting an
+/// existing column. Returns the number of updated values.
+///
+/// # Errors
+///
+/// When a value is encrypted with an unknown key, or on database errors
+pub async fn reencrypt<C>(
+    db: &C,
+    encryptor: &Encryptor,
+    table: &str,
+    columns: &[String],
+) -> ModelResult<u64>
+where
+    C: ConnectionTrait,
+{
+    let backend = db.get_database_backend();
+    let current = format!("{BOUND_PREFIX}{}:%", encryptor.key_id());
+    let mut updated = 0;
+    for column in columns {
+        let context = format!("{table}.{column}");
+        loop {
+            let select = Query::select()
+                .column(Alias::new(column))
+                .from(Alias::new(table))
+                .and_where(Expr::col(Alias::new(column)).is_not_null())
+                .and_where(Expr::col(Alias::new(column)).not_like(current.as_str()))
+                .limit(REENCRYPT_BATCH_SIZE)
+                .to_owned();
+            let rows = db.query_all(backend.build(&select)).await?;
+            if rows.is_empty() {
+                break;
+            }
+            for row in rows {
+                let value: String = row.try_get("", column)?;
+                let plaintext = if is_encrypted(&value) {
+                    encryptor.decrypt_bound(&value, &context)?
+                } else {
+                    value.clone()
+                };
+                // the rows are told apart by their random nonces, and the
+                // rows sharing a value not encrypted yet share its encryption
+                let update = Query::update()
+                    .table(Alias::new(table))
+                    .value(
+                        Alias::new(column),
+                        encryptor.encrypt_bound(&plaintext, &context)?,
+                    )
+                    .and_where(Expr::col(Alias::new(column)).eq(value))
+                    .to_owned();
+                updated += db.execute(backend.build(&update)).await?.rows_affected();
+            }
+        }
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, Set};
+
+    use super::*;
+    use crate::tests_cfg;
+
+    mod users {
+        use sea_orm::entity::prelude::*;
+
+        use super::Encrypted;
+
+        crate::encrypted_column!(Phone, "users", "phone");
+        crate::encrypted_column!(TaxId, "users", "tax_id");
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "users")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            #[sea_orm(column_type = "Text")]
+            pub phone: Encrypted<Phone>,
+            #[sea_orm(column_type = "Text", nullable)]
+            pub tax_id: Option<Encrypted<TaxId>>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    fn key(byte: u8) -> String {
+        STANDARD.encode([byte; 32])
+    }
+
+    async fn stored_phones(db: &DatabaseConnection) -> Vec<String> {
+        db.query_all(sea_orm::Statement::from_string(
+            db.get_database_backend(),
+            "SELECT phone FROM users ORDER BY id",
+        ))
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| row.try_get::<String>("", "phone").unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn can_encrypt_and_decrypt() {
+        let old = Encryptor::from_config(&Config { keys: vec![key(1)] }).unwrap();
+        let rotated = Encryptor::from_config(&Config {
+            keys: vec![key(2), key(1)],
+        })
+        .unwrap();
+
+        let ciphertext = old.encrypt_bound("555-0100", "users.phone").unwrap();
+        assert!(is_encrypted(&ciphertext));
+        assert_ne!(
+            ciphertext,
+            old.encrypt_bound("555-0100", "users.phone").unwrap()
+        );
+        assert_eq!(
+            old.decrypt_bound(&ciphertext, "users.phone").unwrap(),
+            "555-0100"
+        );
+        assert_eq!(
+            rotated.decrypt_bound(&ciphertext, "users.phone").unwrap(),
+            "555-0100"
+        );
+        assert!(old.decrypt_bound(&ciphertext, "users.tax_id").is_err());
+        assert!(old
+            .decrypt_bound(
+                &rotated.encrypt_bound("555-0100", "users.phone").unwrap(),
+                "users.phone"
+            )
+            .is_err());
+        assert!(old.is_current(&ciphertext));
+        assert!(!rotated.is_current(&ciphertext));
+
+        let mut tampered = ciphertext.clone();
+        tampered.pop();
+        tampered.push(if ciphertext.ends_with('A') { 'B' } else { 'A' });
+        assert!(old.decrypt_bound(&tampered, "users.phone").is_err());
+        assert!(old.decrypt_bound("555-0100", "users.phone").is_err());
+        assert!(!is_encrypted("555-0100"));
+        assert!(Encryptor::from_config(&Config {
+            keys: vec![STANDARD.encode([1; 16])],
+        })
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn can_store_encrypted_attributes() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let db = &ctx.db;
+        db.execute_unprepared(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, phone TEXT NOT NULL, tax_id TEXT)",
+        )
+        .await
+        .unwrap();
+        let encryptor = Encryptor::from_config(&Config { keys: vec![key(1)] }).unwrap();
+
+        let user = users::ActiveModel {
+            phone: Set(Encrypted::new(&encryptor, "555-0100").unwrap()),
+            tax_id: Set(Some(Encrypted::new(&encryptor, "123-45-6789").unwrap())),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        assert_eq!(user.phone.decrypt(&encryptor).unwrap(), "555-0100");
+        assert_eq!(format!("{:?}", user.phone), "Encrypted(..)");
+        db.execute_unprepared("INSERT INTO users (phone) VALUES ('555-0101')")
+            .await
+            .unwrap();
+
+        let stored = stored_phones(db).await;
+        assert!(stored[0].starts_with(&format!("{BOUND_PREFIX}{}:", encryptor.key_id())));
+        assert_eq!(stored[1], "555-0101");
+
+        // a value copied to another column does not decrypt
+        db.execute_unprepared(&format!(
+            "INSERT INTO users (id, phone, tax_id) VALUES (100, '{0}', '{0}')",
+            stored[0]
+        ))
+        .await
+        .unwrap();
+        let copied = users::Entity::find_by_id(100)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(copied.tax_id.unwrap().decrypt(&encryptor).is_err());
+        db.execute_unprepared("DELETE FROM users WHERE id = 100")
+            .await
+            .unwrap();
+
+        let rotated = Encryptor::from_config(&Config {
+            keys: vec![key(2), key(1)],
+        })
+        .unwrap();
+        let found = users::Entity::find_by_id(user.id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, user);
+        assert_eq!(found.phone.decrypt(&rotated).unwrap(), "555-0100");
+
+        assert_eq!(
+            reencrypt(
+                db,
+                &rotated,
+                "users",
+                &["phone".to_string(), "tax_id".to_string()]
+            )
+            .await
+            .unwrap(),
+            3
+        );
+        assert!(stored_phones(db)
+            .await
+            .iter()
+            .all(|phone| rotated.is_current(phone)));
+        assert_eq!(
+            reencrypt(db, &rotated, "users", &["phone".to_string()])
+                .await
+                .unwrap(),
+            0
+        );
+
+        let users = users::Entity::find().all(db).await.unwrap();
+        assert_eq!(users[0].phone.decrypt(&rotated).unwrap(), "555-0100");
+        assert_eq!(
+            users[0].tax_id.as_ref().unwrap().decrypt(&rotated).unwrap(),
+            "123-45-6789"
+        );
+        assert_eq!(users[1].phone.decrypt(&rotated).unwrap(), "555-0101");
+        assert_eq!(users[1].tax_id, None);
+        assert!(users[0].phone.decrypt(&encryptor).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+//! # Time-ordered Ids
+//!
+//! Generates the UUIDv7 and ULID primary keys (and `pid`s) of the models.
+//! Both start with their creation time in milliseconds, so that new rows are
+//! appended to the primary key index like auto-increment integers are, while
+//! being unguessable and generated by the app without a round-trip to the
+//! database.
+//!
+//! The models generated with `--primary-key uuidv7` or `--primary-key ulid`
+//! set their id on insert, when it is not set:
+//! ```rust,ignore
+//! async fn before_save<C>(self, _db: &C, insert: bool) -> Result<Self, DbErr>
+//! where
+//!     C: ConnectionTrait,
+//! {
+//!     let mut this = self;
+//!     if insert && this.id.is_not_set() {
+//!         this.id = ActiveValue::Set(loco_rs::model::ids::uuid_v7());
+//!     }
+//!     Ok(this)
+//! }
+//! ```
+use uuid::Uuid;
+
+/// A new UUIDv7, for `uuid` columns.
+#[must_use]
+pub fn uuid_v7() -> Uuid {
+    Uuid::now_v7()
+}
+
+/// A new ULID, as its 26 characters, for `char(26)` columns.
+#[must_use]
+pub fn ulid() -> String {
+    ulid::Ulid::new().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_generate_time_ordered_ids() {
+        let first_uuid = uuid_v7();
+        let first_ulid = ulid();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second_uuid = uuid_v7();
+        let second_ulid = ulid();
+
+        assert_eq!(first_uuid.get_version_num(), 7);
+        assert!(first_uuid < second_uuid);
+        assert!(first_uuid.to_string() < second_uuid.to_string());
+
+        assert_eq!(first_ulid.len(), 26);
+        assert!(ulid::Ulid::from_string(&first_ulid).is_ok());
+        assert!(first_ulid < second_ulid);
+    }
+}
@@ -2,10 +2,23 @@
 //!
 //! Useful when using `sea_orm` and want to propagate errors
 
+pub mod audit;
+pub mod bulk;
+mod callbacks;
+pub mod counter_cache;
+pub mod encryption;
+pub mod ids;
+mod optimistic_lock;
 pub mod query;
+pub mod search;
+mod soft_delete;
 use async_trait::async_trait;
 use sea_orm::DatabaseConnection;
 
+pub use callbacks::{CallbackActiveModel, Callbacks};
+pub use optimistic_lock::{Lockable, LockableActiveModel};
+pub use soft_delete::{SoftDeletable, SoftDeletableModel};
+
 use crate::validation::ModelValidationErrors;
 
 #[derive(thiserror::Error, Debug)]
@@ -17,6 +30,9 @@ pub enum ModelError {
     #[error("Entity not found")]
     EntityNotFound,
 
+    #[error("Entity was changed concurrently, its lock version is stale")]
+    StaleObject,
+
     #[error(transparent)]
     Validation(#[from] ModelValidationErrors),
 
@@ -58,3 +74,127 @@ pub trait Authenticable: Clone {
     async fn find_by_api_key(db: &DatabaseConnection, api_key: &str) -> ModelResult<Self>;
     async fn find_by_claims_key(db: &DatabaseConnection, claims_key: &str) -> ModelResult<Self>;
 }
+
+/// The grants of an API key, used to authorize scoped `ApiToken` requests.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyGrant {
+    /// Scopes the key is allowed to access
+    pub scopes: Vec<String>,
+    /// When the key stops being valid, if ever
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Maximum number of requests per minute allowed for the key, if limited
+    pub rate_limit: Option<u32>,
+}
+
+impl ApiKeyGrant {
+    /// Returns `true` when the key is allowed to access the given scope.
+    #[must_use]
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    /// Returns `true` when the key is expired.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now())
+    }
+}
+
+/// Models whose API keys carry scopes, expiry and rate limits.
+///
+/// Store API keys hashed with [`crate::hash::hash_api_key`], and look them
+/// up by the hash of the presented key.
+#[async_trait]
+pub trait ScopedApiKey: Authenticable {
+    /// Finds the user owning an API key, along with the key grants.
+    async fn find_by_scoped_api_key(
+        db: &DatabaseConnection,
+        api_key: &str,
+    ) -> ModelResult<(Self, ApiKeyGrant)>;
+
+    /// Called after an API key was successfully used, for example to track
+    /// its `last_used_at`.
+    async fn api_key_used(_db: &DatabaseConnection, _api_key: &str) -> ModelResult<()> {
+        Ok(())
+    }
+}
+
+/// Models that can be authenticated with a username and password, used by
+/// the `BasicAuthWithUser` extractor.
+#[async_trait]
+pub trait BasicAuthenticable: Authenticable {
+    /// Finds the user matching the credentials. Verify the password with
+    /// [`crate::hash::verify_password`] and return
+    /// [`ModelError::EntityNotFound`] when the credentials do not match.
+    async fn find_by_basic_auth(
+        db: &DatabaseConnection,
+        username: &str,
+        password: &str,
+    ) -> ModelResult<Self>;
+}
+
+/// Models that can be logged in with an OAuth2 / OpenID Connect provider,
+/// used by [`crate::auth::oauth2::routes`].
+#[cfg(feature = "auth_oauth2")]
+#[async_trait]
+pub trait OAuth2Authenticable: Authenticable {
+    /// Finds the user matching the provider profile, creating it on the first
+    /// login. Match on `profile.provider` and `profile.subject` rather than
+    /// on the email, and only trust `profile.email` when
+    /// `profile.email_verified` is set.
+    async fn upsert_with_oauth2(
+        db: &DatabaseConnection,
+        profile: &crate::auth::oauth2::OAuth2Profile,
+    ) -> ModelResult<Self>;
+
+    /// The key stored in the JWT `pid` claim, as looked up by
+    /// [`Authenticable::find_by_claims_key`].
+    fn claims_key(&self) -> String;
+}
+
+/// Models that can register passkeys and log in with them, used by
+/// [`crate::auth::webauthn::routes`].
+#[cfg(feature = "auth_webauthn")]
+#[async_trait]
+pub trait WebAuthnAuthenticable: Authenticable {
+    /// Finds the user logging in with a username, typically the email.
+    async fn find_by_webauthn_username(
+        db: &DatabaseConnection,
+        username: &str,
+    ) -> ModelResult<Self>;
+
+    /// The passkeys registered by the user.
+    async fn passkeys(
+        &self,
+        db: &DatabaseConnection,
+    ) -> ModelResult<Vec<webauthn_rs::prelude::Passkey>>;
+
+    /// Stores a newly registered passkey.
+    async fn add_passkey(
+        &self,
+        db: &DatabaseConnection,
+        passkey: webauthn_rs::prelude::Passkey,
+    ) -> ModelResult<()>;
+
+    /// Called after a successful login, to persist the updated signature
+    /// counter of the passkey with
+    /// [`webauthn_rs::prelude::Passkey::update_credential`].
+    async fn passkey_used(
+        &self,
+        _db: &DatabaseConnection,
+        _result: &webauthn_rs::prelude::AuthenticationResult,
+    ) -> ModelResult<()> {
+        Ok(())
+    }
+
+    /// The WebAuthn user handle, a UUID that never changes for the user.
+    fn webauthn_user_id(&self) -> webauthn_rs::prelude::Uuid;
+
+    /// The username shown by authenticators.
+    fn webauthn_username(&self) -> String;
+
+    /// The key stored in the JWT `pid` claim, as looked up by
+    /// [`Authenticable::find_by_claims_key`].
+    fn claims_key(&self) -> String;
+}
@@ -0,0 +1,214 @@
+//! # Optimistic Locking
+//!
+//! Entities with an integer `lock_version` column detect concurrent updates:
+//! the saves of [`LockableActiveModel`] only apply when the row still has the
+//! version the model was read at, and bump it. Otherwise they fail with
+//! [`ModelError::StaleObject`], answered as a `409 Conflict`, instead of
+//! silently overwriting the other update.
+//!
+//! Add the column with [`crate::schema::add_lock_version`], or a
+//! `lock_version:int` field to the model generator, and implement the trait
+//! on the entity, which `cargo loco db entities` does for the new models:
+//! ```rust,ignore
+//! impl Lockable for Entity {
+//!     fn lock_version() -> Column {
+//!         Column::LockVersion
+//!     }
+//! }
+//!
+//! let mut post = post.into_active_model();
+//! // the version the client read the post at
+//! post.lock_version = Set(params.lock_version);
+//! post.title = Set(params.title);
+//! let post = post.update_locked(&ctx.db).await?;
+//! ```
+use async_trait::async_trait;
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    IntoActiveModel, QueryFilter, Value,
+};
+
+use super::{ModelError, ModelResult};
+
+/// Entities whose updates are checked against a `lock_version` column.
+pub trait Lockable: EntityTrait {
+    /// The integer column of the version of the rows.
+    fn lock_version() -> Self::Column;
+}
+
+/// The locked saves of the active models of [`Lockable`] entities.
+#[async_trait]
+pub trait LockableActiveModel: ActiveModelTrait + ActiveModelBehavior + Send
+where
+    Self::Entity: Lockable,
+    <Self::Entity as EntityTrait>::Model: IntoActiveModel<Self>,
+{
+    /// Updates the row when it still has the version of the model, and bumps
+    /// the version.
+    ///
+    /// # Errors
+    ///
+    /// [`ModelError::StaleObject`] when the row was updated or deleted since
+    /// the model was read, or when the update fails
+    async fn update_locked<C>(mut self, db: &C) -> ModelResult<<Self::Entity as EntityTrait>::Model>
+    where
+        C: ConnectionTrait,
+    {
+        let column = <Self::Entity as Lockable>::lock_version();
+        let version = current_version(&self)?;
+        self.set(column, bump(&version)?);
+
+        let model = self.before_save(db, false).await?;
+        let model = <Self::Entity as EntityTrait>::update(model)
+            .filter(column.eq(version))
+            .exec(db)
+            .await
+            .map_err(|err| match err {
+                DbErr::RecordNotUpdated => ModelError::StaleObject,
+                err => err.into(),
+            })?;
+        Ok(Self::after_save(model, db, false).await?)
+    }
+
+    /// Deletes the row when it still has the version of the model.
+    ///
+    /// # Errors
+    ///
+    /// [`ModelError::StaleObject`] when the row was updated or deleted since
+    /// the model was read, or when the delete fails
+    async fn delete_locked<C>(self, db: &C) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        let column = <Self::Entity as Lockable>::lock_version();
+        let version = current_version(&self)?;
+
+        let model = self.before_delete(db).await?;
+        let result = <Self::Entity as EntityTrait>::delete(model.clone())
+            .filter(column.eq(version))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(ModelError::StaleObject);
+        }
+        model.after_delete(db).await?;
+        Ok(())
+    }
+}
+
+impl<A> LockableActiveModel for A
+where
+    A: ActiveModelTrait + ActiveModelBehavior + Send,
+    A::Entity: Lockable,
+    <A::Entity as EntityTrait>::Model: IntoActiveModel<A>,
+{
+}
+
+fn current_version<A>(model: &A) -> ModelResult<Value>
+where
+    A: ActiveModelTrait,
+    A::Entity: Lockable,
+{
+    model
+        .get(<A::Entity as Lockable>::lock_version())
+        .into_value()
+        .ok_or_else(|| ModelError::msg("the lock version of the model is not set"))
+}
+
+fn bump(version: &Value) -> ModelResult<Value> {
+    match version {
+        Value::SmallInt(Some(version)) => Ok(Value::SmallInt(Some(version.wrapping_add(1)))),
+        Value::Int(Some(version)) => Ok(Value::Int(Some(version.wrapping_add(1)))),
+        Value::BigInt(Some(version)) => Ok(Value::BigInt(Some(version.wrapping_add(1)))),
+        _ => Err(ModelError::msg("the lock version must be an integer")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, Database, Set};
+
+    use super::*;
+
+    mod posts {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "posts")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+            pub lock_version: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    impl Lockable for posts::Entity {
+        fn lock_version() -> posts::Column {
+            posts::Column::LockVersion
+        }
+    }
+
+    #[tokio::test]
+    async fn can_detect_stale_objects() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT NOT NULL, lock_version \
+             INTEGER NOT NULL DEFAULT 0)",
+        )
+        .await
+        .unwrap();
+        let post = posts::ActiveModel {
+            title: Set("draft".to_string()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        assert_eq!(post.lock_version, 0);
+
+        // two admins edit the same version of the post
+        let mut first = post.clone().into_active_model();
+        first.title = Set("first".to_string());
+        let mut second = post.into_active_model();
+        second.title = Set("second".to_string());
+
+        let updated = first.update_locked(&db).await.unwrap();
+        assert_eq!(updated.lock_version, 1);
+        assert!(matches!(
+            second.update_locked(&db).await,
+            Err(ModelError::StaleObject)
+        ));
+        assert_eq!(
+            posts::Entity::find_by_id(1)
+                .one(&db)
+                .await
+                .unwrap()
+                .unwrap()
+                .title,
+            "first"
+        );
+
+        let mut stale = updated.clone().into_active_model();
+        stale.lock_version = Set(0);
+        assert!(matches!(
+            stale.delete_locked(&db).await,
+            Err(ModelError::StaleObject)
+        ));
+        updated
+            .into_active_model()
+            .delete_locked(&db)
+            .await
+            .unwrap();
+        assert!(posts::Entity::find_by_id(1)
+            .one(&db)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
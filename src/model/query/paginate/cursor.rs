@@ -0,0 +1,342 @@
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sea_orm::{
+    prelude::*, sea_query::Order, ColumnType, Condition, EntityTrait, Iterable, PrimaryKeyToColumn,
+    QueryFilter, QueryOrder, QuerySelect, Select, Value,
+};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::{
+    controller::extractor::pagination::{PageMeta, Pagination},
+    Error, Result as LocoResult,
+};
+
+/// The columns ordering the pages of a keyset pagination.
+///
+/// The primary key is appended to the columns, when missing, so that the
+/// order is total and no row is skipped or repeated between pages. The
+/// columns must not be nullable.
+///
+/// # Example
+/// ```rust,ignore
+/// let keyset = query::Keyset::new().desc(posts::Column::CreatedAt);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Keyset<E: EntityTrait> {
+    columns: Vec<(E::Column, Order)>,
+}
+
+impl<E: EntityTrait> Default for Keyset<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: EntityTrait> Keyset<E> {
+    /// Orders the pages by primary key only.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { columns: vec![] }
+    }
+
+    /// Orders the pages by a column, ascending.
+    #[must_use]
+    pub fn asc(mut self, column: E::Column) -> Self {
+        self.columns.push((column, Order::Asc));
+        self
+    }
+
+    /// Orders the pages by a column, descending.
+    #[must_use]
+    pub fn desc(mut self, column: E::Column) -> Self {
+        self.columns.push((column, Order::Desc));
+        self
+    }
+
+    /// The ordering columns, ending with the primary key ones, which follow
+    /// the direction of the last column.
+    fn columns(&self) -> Vec<(E::Column, Order)> {
+        let mut columns = self.columns.clone();
+        let order = columns
+            .last()
+            .map_or(Order::Asc, |(_, order)| order.clone());
+        for key in E::PrimaryKey::iter() {
+            let column = key.into_column();
+            if !columns
+                .iter()
+                .any(|(existing, _)| existing.as_str() == column.as_str())
+            {
+                columns.push((column, order.clone()));
+            }
+        }
+        columns
+    }
+}
+
+/// The page size and the cursor of a keyset pagination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorQuery {
+    /// The cursor of the page, the first page when `None`
+    pub cursor: Option<String>,
+    pub page_size: u64,
+}
+
+impl From<&Pagination> for CursorQuery {
+    fn from(pagination: &Pagination) -> Self {
+        Self {
+            cursor: pagination.cursor.clone(),
+            page_size: pagination.per_page,
+        }
+    }
+}
+
+/// A page of a keyset pagination.
+#[derive(Debug)]
+pub struct CursorPage<T> {
+    pub page: Vec<T>,
+    /// The cursor of the next page, if any
+    pub next_cursor: Option<String>,
+}
+
+impl<T> CursorPage<T> {
+    /// The metadata of the page requested with `pagination`, to respond
+    /// with [`crate::controller::format::paginated`].
+    #[must_use]
+    pub fn meta(&self, pagination: &Pagination) -> PageMeta {
+        pagination.cursor_meta(self.next_cursor.clone())
+    }
+}
+
+/// Fetches a page of a keyset pagination, the rows following the ones of
+/// the cursor in the order of the keyset.
+///
+/// Unlike the offset pagination of [`super::paginate`], the pages are found
+/// through the index of the ordering columns however deep they are, and stay
+/// stable while rows are inserted.
+///
+/// # Example
+/// ```rust,ignore
+/// async fn list(State(ctx): State<AppContext>, pagination: Pagination) -> Result<Response> {
+///     let keyset = query::Keyset::new().desc(posts::Column::CreatedAt);
+///     let page = query::fetch_cursor_page(
+///         &ctx.db,
+///         posts::Entity::find(),
+///         &keyset,
+///         &query::CursorQuery::from(&pagination),
+///     )
+///     .await?;
+///     let meta = page.meta(&pagination);
+///     format::paginated(page.page, meta)
+/// }
+/// ```
+///
+/// # Errors
+///
+/// When the cursor is invalid, or the query fails
+pub async fn fetch_cursor_page<C, E>(
+    db: &C,
+    select: Select<E>,
+    keyset: &Keyset<E>,
+    query: &CursorQuery,
+) -> LocoResult<CursorPage<E::Model>>
+where
+    C: ConnectionTrait,
+    E: EntityTrait,
+    E::Model: Serialize + Sync,
+{
+    let columns = keyset.columns();
+    let mut select = select;
+    if let Some(cursor) = &query.cursor {
+        select = select.filter(after(&columns, &decode_cursor(&columns, cursor)?));
+    }
+    for (column, order) in &columns {
+        select = select.order_by(*column, order.clone());
+    }
+
+    let mut page = select
+        .limit(query.page_size.saturating_add(1))
+        .all(db)
+        .await?;
+    let has_next = page.len() as u64 > query.page_size;
+    page.truncate(usize::try_from(query.page_size).unwrap_or(usize::MAX));
+
+    let next_cursor = match page.last() {
+        Some(last) if has_next => Some(encode_cursor(&columns, last)?),
+        _ => None,
+    };
+    Ok(CursorPage { page, next_cursor })
+}
+
+/// Encodes the cursor of a row, the values of its ordering columns.
+fn encode_cursor<C: ColumnTrait, M: Serialize>(
+    columns: &[(C, Order)],
+    model: &M,
+) -> LocoResult<String> {
+    let JsonValue::Object(object) = serde_json::to_value(model)? else {
+        return Err(Error::string("cursor rows must serialize to objects"));
+    };
+    let values = columns
+        .iter()
+        .map(|(column, _)| {
+            object
+                .get(column.as_str())
+                .cloned()
+                .unwrap_or(JsonValue::Null)
+        })
+        .collect::<Vec<_>>();
+    Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(&values)?))
+}
+
+/// Decodes the values of the ordering columns of a cursor.
+fn decode_cursor<C: ColumnTrait>(columns: &[(C, Order)], cursor: &str) -> LocoResult<Vec<Value>> {
+    let invalid = || Error::BadRequest("invalid cursor".to_string());
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let values: Vec<JsonValue> = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+    if values.len() != columns.len() {
+        return Err(invalid());
+    }
+    columns
+        .iter()
+        .zip(values)
+        .map(|((column, _), value)| {
+            to_value(column.def().get_column_type(), &value).ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// Converts a cursor value to the type of its column.
+fn to_value(column_type: &ColumnType, value: &JsonValue) -> Option<Value> {
+    Some(match (column_type, value) {
+        (
+            ColumnType::TinyInteger
+            | ColumnType::SmallInteger
+            | ColumnType::Integer
+            | ColumnType::BigInteger
+            | ColumnType::TinyUnsigned
+            | ColumnType::SmallUnsigned
+            | ColumnType::Unsigned
+            | ColumnType::BigUnsigned,
+            JsonValue::Number(number),
+        ) => number.as_i64()?.into(),
+        (ColumnType::TimestampWithTimeZone, JsonValue::String(value)) => {
+            chrono::DateTime::parse_from_rfc3339(value).ok()?.into()
+        }
+        (ColumnType::DateTime | ColumnType::Timestamp, JsonValue::String(value)) => {
+            chrono::NaiveDateTime::from_str(value).ok()?.into()
+        }
+        (ColumnType::Date, JsonValue::String(value)) => {
+            chrono::NaiveDate::from_str(value).ok()?.into()
+        }
+        (ColumnType::Uuid, JsonValue::String(value)) => Uuid::parse_str(value).ok()?.into(),
+        (_, JsonValue::String(value)) => value.clone().into(),
+        (_, JsonValue::Number(number)) => match number.as_i64() {
+            Some(number) => number.into(),
+            None => number.as_f64()?.into(),
+        },
+        (_, JsonValue::Bool(value)) => (*value).into(),
+        _ => return None,
+    })
+}
+
+/// The rows following the cursor values in the order of the columns:
+/// `(a > x) OR (a = x AND b > y) OR ...`.
+fn after<C: ColumnTrait>(columns: &[(C, Order)], values: &[Value]) -> Condition {
+    (0..columns.len()).fold(Condition::any(), |any, index| {
+        let (column, order) = &columns[index];
+        let tie = columns[..index]
+            .iter()
+            .zip(values)
+            .fold(Condition::all(), |all, ((column, _), value)| {
+                all.add(column.eq(value.clone()))
+            });
+        let next = match order {
+            Order::Desc => column.lt(values[index].clone()),
+            _ => column.gt(values[index].clone()),
+        };
+        any.add(tie.add(next))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, Database, Set};
+
+    use super::*;
+
+    mod events {
+        use sea_orm::entity::prelude::*;
+        use serde::Serialize;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize)]
+        #[sea_orm(table_name = "events")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub score: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    #[tokio::test]
+    async fn can_paginate_by_cursor() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE events (id INTEGER PRIMARY KEY, score INTEGER NOT NULL)",
+        )
+        .await
+        .unwrap();
+        // the scores repeat, so that the primary key breaks the ties
+        for score in [3, 1, 2, 3, 1, 2, 3] {
+            events::ActiveModel {
+                score: Set(score),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let keyset = Keyset::new().desc(events::Column::Score);
+        let mut query = CursorQuery {
+            cursor: None,
+            page_size: 3,
+        };
+        let mut pages = vec![];
+        loop {
+            let page = fetch_cursor_page(&db, events::Entity::find(), &keyset, &query)
+                .await
+                .unwrap();
+            pages.push(
+                page.page
+                    .iter()
+                    .map(|event| (event.score, event.id))
+                    .collect::<Vec<_>>(),
+            );
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(
+            pages,
+            vec![
+                vec![(3, 7), (3, 4), (3, 1)],
+                vec![(2, 6), (2, 3), (1, 5)],
+                vec![(1, 2)],
+            ]
+        );
+
+        query.cursor = Some("not a cursor".to_string());
+        assert!(matches!(
+            fetch_cursor_page(&db, events::Entity::find(), &keyset, &query).await,
+            Err(Error::BadRequest(_))
+        ));
+    }
+}
@@ -1,6 +1,11 @@
+mod cursor;
+
+pub use cursor::*;
 use sea_orm::{prelude::*, Condition, DatabaseConnection, EntityTrait, QueryFilter, SelectorTrait};
 use serde::{Deserialize, Serialize};
 
+use crate::controller::extractor::pagination::{PageMeta, Pagination};
+
 /// Set the default pagination page size.
 const fn default_page_size() -> u64 {
     25
@@ -74,6 +79,15 @@ where
     s.parse().map_err(serde::de::Error::custom)
 }
 
+impl From<&Pagination> for PaginationQuery {
+    fn from(pagination: &Pagination) -> Self {
+        Self {
+            page_size: pagination.per_page,
+            page: pagination.page,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct PageResponse<T> {
     pub page: Vec<T>,
@@ -81,6 +95,15 @@ pub struct PageResponse<T> {
     pub total_items: u64,
 }
 
+impl<T> PageResponse<T> {
+    /// The metadata of the page requested with `pagination`, to respond
+    /// with [`crate::controller::format::paginated`].
+    #[must_use]
+    pub fn meta(&self, pagination: &Pagination) -> PageMeta {
+        pagination.meta(self.total_items)
+    }
+}
+
 use crate::Result as LocoResult;
 
 /// Paginate function for fetching paginated data from the database.
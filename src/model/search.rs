@@ -0,0 +1,547 @@
+//! # Full-text Search
+//!
+//! Searches the text columns of the [`Searchable`] entities, ranking the
+//! matches and highlighting the matched terms:
+//!
+//! - on Postgres, in a weighted `search_vector` tsvector column (the first
+//!   column weighs the most) with a GIN index, queried with the web search
+//!   syntax of `websearch_to_tsquery`.
+//! - on SQLite, in a `<table>_search` FTS5 table indexing the rows of the
+//!   table by their `id`.
+//!
+//! The index is created by [`crate::schema::add_search_index`], and kept up
+//! to date by triggers, or by the application with [`refresh`].
+//!
+//! # Example
+//! ```rust,ignore
+//! // in a migration
+//! add_search_index(m, "posts", &SearchIndex::new(&["title", "content"])).await?;
+//!
+//! // on the entity
+//! loco_rs::searchable!(Entity, [Column::Title, Column::Content]);
+//!
+//! let hits = search::search(&ctx.db, posts::Entity::find().limit(20), "loco rails").await?;
+//! ```
+use sea_orm::{
+    sea_query::{Alias, Expr, JoinType, Order},
+    ConnectionTrait, DbBackend, DbErr, EntityTrait, FromQueryResult, IdenStatic, QueryTrait,
+    Select,
+};
+
+use super::ModelResult;
+
+/// The text search configuration of Postgres used by default.
+pub const DEFAULT_LANGUAGE: &str = "english";
+
+/// The tsvector column of the Postgres index.
+pub const VECTOR_COLUMN: &str = "search_vector";
+
+const RANK_ALIAS: &str = "search_rank";
+const HIGHLIGHT_ALIAS: &str = "search_highlight_";
+
+/// How the search index follows the changes of the rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Refresh {
+    /// Database triggers update the index on every insert, update and delete
+    #[default]
+    Trigger,
+    /// The application updates the index with [`refresh`], such as from a
+    /// scheduled job, keeping the writes fast
+    App,
+}
+
+/// The definition of a search index, for [`crate::schema::add_search_index`].
+#[derive(Debug, Clone)]
+pub struct SearchIndex<'a> {
+    /// The indexed text columns, by decreasing weight
+    pub columns: &'a [&'a str],
+    /// The text search configuration of Postgres, such as `english`
+    pub language: &'a str,
+    pub refresh: Refresh,
+}
+
+impl<'a> SearchIndex<'a> {
+    /// Indexes the columns in the default language, refreshed by triggers.
+    #[must_use]
+    pub const fn new(columns: &'a [&'a str]) -> Self {
+        Self {
+            columns,
+            language: DEFAULT_LANGUAGE,
+            refresh: Refresh::Trigger,
+        }
+    }
+
+    /// Sets the text search configuration of Postgres.
+    #[must_use]
+    pub const fn language(mut self, language: &'a str) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Sets how the index follows the changes of the rows.
+    #[must_use]
+    pub const fn refresh(mut self, refresh: Refresh) -> Self {
+        self.refresh = refresh;
+        self
+    }
+}
+
+/// Entities whose text columns are searched, see [`crate::searchable`].
+pub trait Searchable: EntityTrait {
+    /// The indexed columns, in the order of the index.
+    fn search_columns() -> Vec<Self::Column>;
+
+    /// The text search configuration of Postgres, the one of the index.
+    #[must_use]
+    fn search_language() -> &'static str {
+        DEFAULT_LANGUAGE
+    }
+}
+
+/// Implements [`Searchable`] for an entity, with its indexed columns and
+/// optionally the language of the index.
+///
+/// ```rust,ignore
+/// loco_rs::searchable!(Entity, [Column::Title, Column::Content]);
+/// loco_rs::searchable!(Entity, [Column::Title], language = "french");
+/// ```
+#[macro_export]
+macro_rules! searchable {
+    ($entity:ty, [$($column:expr),+ $(,)?]) => {
+        impl $crate::model::search::Searchable for $entity {
+            fn search_columns() -> Vec<Self::Column> {
+                vec![$($column),+]
+            }
+        }
+    };
+    ($entity:ty, [$($column:expr),+ $(,)?], language = $language:expr) => {
+        impl $crate::model::search::Searchable for $entity {
+            fn search_columns() -> Vec<Self::Column> {
+                vec![$($column),+]
+            }
+
+            fn search_language() -> &'static str {
+                $language
+            }
+        }
+    };
+}
+
+/// A row matching a search.
+#[derive(Debug, Clone)]
+pub struct SearchHit<M> {
+    pub model: M,
+    /// The relevance of the row, higher is better
+    pub rank: f64,
+    /// The fragments of the search columns around the matched terms, which are
+    /// wrapped in `<mark>` tags, in the order of the columns
+    pub highlights: Vec<String>,
+}
+
+/// Searches the rows of `select` matching the query, by decreasing rank,
+/// then in the order of the select. Limit or filter the select to paginate
+/// or narrow the search.
+///
+/// # Errors
+///
+/// When the database is not Postgres or SQLite, or the query fails
+pub async fn search<E, C>(
+    db: &C,
+    select: Select<E>,
+    query: &str,
+) -> ModelResult<Vec<SearchHit<E::Model>>>
+where
+    E: Searchable,
+    C: ConnectionTrait,
+{
+    if query.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let backend = db.get_database_backend();
+    let table = quote(E::default().table_name());
+    let columns = E::search_columns();
+    let language = E::search_language();
+
+    let mut statement = select.into_query();
+    match backend {
+        DbBackend::Postgres => {
+            let vector = format!("{table}.{}", quote(VECTOR_COLUMN));
+            let tsquery = || {
+                Expr::cust_with_values("websearch_to_tsquery(?::regconfig, ?)", [language, query])
+            };
+            let rank = Expr::cust_with_exprs(format!("ts_rank({vector}, $1)"), [tsquery()]);
+            statement
+                .and_where(Expr::cust_with_exprs(
+                    format!("{vector} @@ $1"),
+                    [tsquery()],
+                ))
+                .expr_as(rank.clone(), Alias::new(RANK_ALIAS))
+                .order_by_expr(rank, Order::Desc);
+            for (index, column) in columns.iter().enumerate() {
+                statement.expr_as(
+                    Expr::cust_with_exprs(
+                        format!(
+                            "ts_headline($1, coalesce({table}.{}::text, ''), $2, \
+                             'StartSel=<mark>, StopSel=</mark>')",
+                            quote(column.as_str())
+                        ),
+                        [
+                            Expr::cust_with_values("?::regconfig", [language]),
+                            tsquery(),
+                        ],
+                    ),
+                    Alias::new(format!("{HIGHLIGHT_ALIAS}{index}")),
+                );
+            }
+        }
+        DbBackend::Sqlite => {
+            let fts = quote(&fts_table(E::default().table_name()));
+            // bm25 is lower for the better matches
+            let rank = Expr::cust(format!("-bm25({fts})"));
+            statement
+                .join(
+                    JoinType::InnerJoin,
+                    Alias::new(fts_table(E::default().table_name())),
+                    Expr::cust(format!("{fts}.rowid = {table}.\"id\"")),
+                )
+                .and_where(Expr::cust_with_values(
+                    format!("{fts} MATCH ?"),
+                    [fts5_query(query)],
+                ))
+                .expr_as(rank.clone(), Alias::new(RANK_ALIAS))
+                .order_by_expr(rank, Order::Desc);
+            for index in 0..columns.len() {
+                statement.expr_as(
+                    Expr::cust(format!(
+                        "snippet({fts}, {index}, '<mark>', '</mark>', '…', 16)"
+                    )),
+                    Alias::new(format!("{HIGHLIGHT_ALIAS}{index}")),
+                );
+            }
+        }
+        DbBackend::MySql => return Err(unsupported().into()),
+    }
+
+    let rows = db.query_all(backend.build(&statement)).await?;
+    rows.iter()
+        .map(|row| -> ModelResult<SearchHit<E::Model>> {
+            let highlights = (0..columns.len())
+                .map(|index| {
+                    row.try_get::<Option<String>>("", &format!("{HIGHLIGHT_ALIAS}{index}"))
+                        .map(Option::unwrap_or_default)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(SearchHit {
+                model: E::Model::from_query_result(row, "")?,
+                rank: row
+                    .try_get::<f64>("", RANK_ALIAS)
+                    .or_else(|_| row.try_get::<f32>("", RANK_ALIAS).map(f64::from))?,
+                highlights,
+            })
+        })
+        .collect()
+}
+
+/// Rebuilds the search index of all the rows of the entity, for the indexes
+/// refreshed by the application.
+///
+/// # Errors
+///
+/// When the database is not Postgres or SQLite, or the update fails
+pub async fn refresh<E, C>(db: &C) -> ModelResult<()>
+where
+    E: Searchable,
+    C: ConnectionTrait,
+{
+    let table = E::default().table_name().to_string();
+    let columns = E::search_columns()
+        .iter()
+        .map(|column| column.as_str().to_string())
+        .collect::<Vec<_>>();
+    let columns = columns.iter().map(String::as_str).collect::<Vec<_>>();
+    db.execute_unprepared(&refresh_statement(
+        db.get_database_backend(),
+        &table,
+        &SearchIndex::new(&columns).language(E::search_language()),
+    )?)
+    .await?;
+    Ok(())
+}
+
+/// The statements creating the search index of a table.
+pub(crate) fn create_statements(
+    backend: DbBackend,
+    table: &str,
+    index: &SearchIndex<'_>,
+) -> Result<Vec<String>, DbErr> {
+    let name = quote(table);
+    let mut statements = match backend {
+        DbBackend::Postgres => {
+            let vector = quote(VECTOR_COLUMN);
+            let mut statements = vec![
+                format!("ALTER TABLE {name} ADD COLUMN {vector} tsvector"),
+                format!(
+                    "CREATE INDEX {} ON {name} USING GIN ({vector})",
+                    quote(&format!("idx-{table}-{VECTOR_COLUMN}"))
+                ),
+            ];
+            if index.refresh == Refresh::Trigger {
+                let function = quote(&format!("{table}_{VECTOR_COLUMN}_refresh"));
+                statements.push(format!(
+                    "CREATE FUNCTION {function}() RETURNS trigger AS $$ BEGIN NEW.{vector} := \
+                     {}; RETURN NEW; END $$ LANGUAGE plpgsql",
+                    pg_vector(index, "NEW.")
+                ));
+                statements.push(format!(
+                    "CREATE TRIGGER {function} BEFORE INSERT OR UPDATE ON {name} FOR EACH ROW \
+                     EXECUTE FUNCTION {function}()"
+                ));
+            }
+            statements
+        }
+        DbBackend::Sqlite => {
+            let fts = quote(&fts_table(table));
+            let columns = index
+                .columns
+                .iter()
+                .map(|column| quote(column))
+                .collect::<Vec<_>>();
+            let values = |prefix: &str| {
+                index
+                    .columns
+                    .iter()
+                    .map(|column| format!("{prefix}.{}", quote(column)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let mut statements = vec![format!(
+                "CREATE VIRTUAL TABLE {fts} USING fts5({}, content={}, content_rowid='id')",
+                columns.join(", "),
+                single_quote(table)
+            )];
+            if index.refresh == Refresh::Trigger {
+                let columns = columns.join(", ");
+                let insert = format!(
+                    "INSERT INTO {fts}(rowid, {columns}) VALUES (new.\"id\", {});",
+                    values("new")
+                );
+                let delete = format!(
+                    "INSERT INTO {fts}({fts}, rowid, {columns}) VALUES ('delete', old.\"id\", \
+                     {});",
+                    values("old")
+                );
+                for (suffix, event, body) in [
+                    ("ai", "INSERT", insert.clone()),
+                    ("ad", "DELETE", delete.clone()),
+                    ("au", "UPDATE", format!("{delete} {insert}")),
+                ] {
+                    statements.push(format!(
+                        "CREATE TRIGGER {} AFTER {event} ON {name} BEGIN {body} END",
+                        quote(&format!("{}_{suffix}", fts_table(table)))
+                    ));
+                }
+            }
+            statements
+        }
+        DbBackend::MySql => return Err(unsupported()),
+    };
+    // index the existing rows
+    statements.push(refresh_statement(backend, table, index)?);
+    Ok(statements)
+}
+
+/// The statements dropping the search index of a table.
+pub(crate) fn drop_statements(backend: DbBackend, table: &str) -> Result<Vec<String>, DbErr> {
+    let name = quote(table);
+    match backend {
+        DbBackend::Postgres => {
+            let function = quote(&format!("{table}_{VECTOR_COLUMN}_refresh"));
+            Ok(vec![
+                format!("DROP TRIGGER IF EXISTS {function} ON {name}"),
+                format!("DROP FUNCTION IF EXISTS {function}()"),
+                format!(
+                    "DROP INDEX IF EXISTS {}",
+                    quote(&format!("idx-{table}-{VECTOR_COLUMN}"))
+                ),
+                format!(
+                    "ALTER TABLE {name} DROP COLUMN IF EXISTS {}",
+                    quote(VECTOR_COLUMN)
+                ),
+            ])
+        }
+        DbBackend::Sqlite => {
+            let fts = fts_table(table);
+            let mut statements = ["ai", "ad", "au"]
+                .iter()
+                .map(|suffix| {
+                    format!(
+                        "DROP TRIGGER IF EXISTS {}",
+                        quote(&format!("{fts}_{suffix}"))
+                    )
+                })
+                .collect::<Vec<_>>();
+            statements.push(format!("DROP TABLE IF EXISTS {}", quote(&fts)));
+            Ok(statements)
+        }
+        DbBackend::MySql => Err(unsupported()),
+    }
+}
+
+fn refresh_statement(
+    backend: DbBackend,
+    table: &str,
+    index: &SearchIndex<'_>,
+) -> Result<String, DbErr> {
+    match backend {
+        DbBackend::Postgres => Ok(format!(
+            "UPDATE {} SET {} = {}",
+            quote(table),
+            quote(VECTOR_COLUMN),
+            pg_vector(index, "")
+        )),
+        DbBackend::Sqlite => {
+            let fts = quote(&fts_table(table));
+            Ok(format!("INSERT INTO {fts}({fts}) VALUES ('rebuild')"))
+        }
+        DbBackend::MySql => Err(unsupported()),
+    }
+}
+
+/// The weighted tsvector of the columns, `A` for the first one, then `B`,
+/// then `C` for the others.
+fn pg_vector(index: &SearchIndex<'_>, prefix: &str) -> String {
+    let language = single_quote(index.language);
+    index
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(position, column)| {
+            let weight = ["A", "B"].get(position).unwrap_or(&"C");
+            format!(
+                "setweight(to_tsvector({language}::regconfig, coalesce({prefix}{}::text, '')), \
+                 '{weight}')",
+                quote(column)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" || ")
+}
+
+fn fts_table(table: &str) -> String {
+    format!("{table}_search")
+}
+
+/// Matches all the words of a user query as FTS5 strings, so that its
+/// syntax characters don't fail the query.
+fn fts5_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn single_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn unsupported() -> DbErr {
+    DbErr::Custom("full-text search supports Postgres and SQLite only".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, Database, QuerySelect, Set};
+    use sea_orm_migration::SchemaManager;
+
+    use super::*;
+    use crate::schema::{add_search_index, remove_search_index};
+
+    mod posts {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "posts")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+            pub content: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+
+        crate::searchable!(Entity, [Column::Title, Column::Content]);
+    }
+
+    #[tokio::test]
+    async fn can_search_with_fts5() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT NOT NULL, content TEXT NOT \
+             NULL)",
+        )
+        .await
+        .unwrap();
+        for (title, content) in [
+            ("Rust web frameworks", "Loco is like Rails, for Rust"),
+            ("Gardening", "Growing tomatoes on a balcony"),
+            ("Loco", "Loco loco loco"),
+        ] {
+            posts::ActiveModel {
+                title: Set(title.to_string()),
+                content: Set(content.to_string()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        let manager = SchemaManager::new(&db);
+        add_search_index(&manager, "posts", &SearchIndex::new(&["title", "content"]))
+            .await
+            .unwrap();
+
+        let hits = search(&db, posts::Entity::find(), "loco").await.unwrap();
+        assert_eq!(
+            hits.iter().map(|hit| hit.model.id).collect::<Vec<_>>(),
+            vec![3, 1]
+        );
+        assert!(hits[0].rank > hits[1].rank);
+        assert_eq!(
+            hits[1].highlights[1],
+            "<mark>Loco</mark> is like Rails, for Rust"
+        );
+
+        // the triggers follow the changes, and the syntax of the query is escaped
+        posts::ActiveModel {
+            id: Set(2),
+            title: Set("Gardening".to_string()),
+            content: Set("Growing \"loco\" tomatoes".to_string()),
+        }
+        .update(&db)
+        .await
+        .unwrap();
+        let hits = search(&db, posts::Entity::find().limit(5), "tomatoes \"loco")
+            .await
+            .unwrap();
+        assert_eq!(
+            hits.iter().map(|hit| hit.model.id).collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert!(search(&db, posts::Entity::find(), "  ")
+            .await
+            .unwrap()
+            .is_empty());
+
+        remove_search_index(&manager, "posts").await.unwrap();
+        assert!(search(&db, posts::Entity::find(), "loco").await.is_err());
+    }
+}
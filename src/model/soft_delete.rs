@@ -0,0 +1,221 @@
+//! # Soft Delete
+//!
+//! Entities with a nullable `deleted_at` timestamp column are soft deleted:
+//! deleting a row sets its `deleted_at`, and the finders of
+//! [`SoftDeletable`] leave the deleted rows out, until they are restored.
+//!
+//! Add the column with [`crate::schema::add_soft_delete`], or a
+//! `deleted_at:tstz` field to the model generator, and implement the trait
+//! on the entity, which `cargo loco db entities` does for the new models:
+//! ```rust,ignore
+//! impl SoftDeletable for Entity {
+//!     fn deleted_at() -> Column {
+//!         Column::DeletedAt
+//!     }
+//! }
+//!
+//! let post = posts::Entity::find_active_by_id(id).one(&ctx.db).await?;
+//! post.soft_delete(&ctx.db).await?;
+//! ```
+use async_trait::async_trait;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, Condition, ConnectionTrait, EntityTrait, Iterable, ModelTrait,
+    PrimaryKeyToColumn, PrimaryKeyTrait, QueryFilter, Select, Value,
+};
+
+use super::ModelResult;
+
+/// Entities whose rows are soft deleted, by setting their `deleted_at`.
+pub trait SoftDeletable: EntityTrait {
+    /// The nullable timestamp column of the deletion time.
+    fn deleted_at() -> Self::Column;
+
+    /// Selects the rows which are not deleted, the default scope of the
+    /// entity.
+    #[must_use]
+    fn find_active() -> Select<Self> {
+        Self::find().filter(Self::deleted_at().is_null())
+    }
+
+    /// Selects the row with the primary key, unless it is deleted.
+    #[must_use]
+    fn find_active_by_id<T>(values: T) -> Select<Self>
+    where
+        T: Into<<Self::PrimaryKey as PrimaryKeyTrait>::ValueType>,
+    {
+        Self::find_by_id(values).filter(Self::deleted_at().is_null())
+    }
+
+    /// Selects the deleted rows only.
+    #[must_use]
+    fn find_deleted() -> Select<Self> {
+        Self::find().filter(Self::deleted_at().is_not_null())
+    }
+
+    /// Selects all the rows, deleted or not.
+    #[must_use]
+    fn find_with_deleted() -> Select<Self> {
+        Self::find()
+    }
+}
+
+/// The soft delete operations of the models of [`SoftDeletable`] entities.
+#[async_trait]
+pub trait SoftDeletableModel: ModelTrait + Sync
+where
+    Self::Entity: SoftDeletable,
+{
+    /// Marks the row as deleted, now.
+    ///
+    /// # Errors
+    /// When the update fails
+    async fn soft_delete<C>(&self, db: &C) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        set_deleted_at(self, db, chrono::Utc::now().into()).await
+    }
+
+    /// Restores a soft deleted row.
+    ///
+    /// # Errors
+    /// When the update fails
+    async fn restore<C>(&self, db: &C) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        set_deleted_at(self, db, Value::ChronoDateTimeUtc(None)).await
+    }
+
+    /// Deletes the row for good.
+    ///
+    /// # Errors
+    /// When the delete fails
+    async fn force_delete<C>(&self, db: &C) -> ModelResult<()>
+    where
+        C: ConnectionTrait,
+    {
+        <Self::Entity as EntityTrait>::delete_many()
+            .filter(primary_key(self))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
+
+impl<M> SoftDeletableModel for M
+where
+    M: ModelTrait + Sync,
+    M::Entity: SoftDeletable,
+{
+}
+
+/// Matches the row of a model by its primary key.
+fn primary_key<M: ModelTrait>(model: &M) -> Condition {
+    <M::Entity as EntityTrait>::PrimaryKey::iter().fold(Condition::all(), |condition, key| {
+        let column = key.into_column();
+        condition.add(column.eq(model.get(column)))
+    })
+}
+
+async fn set_deleted_at<M, C>(model: &M, db: &C, deleted_at: Value) -> ModelResult<()>
+where
+    M: ModelTrait,
+    M::Entity: SoftDeletable,
+    C: ConnectionTrait,
+{
+    <M::Entity as EntityTrait>::update_many()
+        .col_expr(
+            <M::Entity as SoftDeletable>::deleted_at(),
+            Expr::value(deleted_at),
+        )
+        .filter(primary_key(model))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{entity::prelude::*, Database, Set};
+
+    use super::*;
+
+    mod posts {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "posts")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub title: String,
+            pub deleted_at: Option<DateTimeWithTimeZone>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    impl SoftDeletable for posts::Entity {
+        fn deleted_at() -> posts::Column {
+            posts::Column::DeletedAt
+        }
+    }
+
+    #[tokio::test]
+    async fn can_soft_delete_and_restore() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, title TEXT NOT NULL, deleted_at TEXT)",
+        )
+        .await
+        .unwrap();
+        for title in ["kept", "deleted"] {
+            posts::ActiveModel {
+                title: Set(title.to_string()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let deleted = posts::Entity::find_active_by_id(2)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        deleted.soft_delete(&db).await.unwrap();
+
+        let titles =
+            |posts: Vec<posts::Model>| posts.into_iter().map(|post| post.title).collect::<Vec<_>>();
+        assert_eq!(
+            titles(posts::Entity::find_active().all(&db).await.unwrap()),
+            vec!["kept"]
+        );
+        assert_eq!(
+            titles(posts::Entity::find_deleted().all(&db).await.unwrap()),
+            vec!["deleted"]
+        );
+        assert!(posts::Entity::find_active_by_id(2)
+            .one(&db)
+            .await
+            .unwrap()
+            .is_none());
+
+        deleted.restore(&db).await.unwrap();
+        assert_eq!(
+            posts::Entity::find_active().all(&db).await.unwrap().len(),
+            2
+        );
+
+        deleted.force_delete(&db).await.unwrap();
+        assert_eq!(
+            titles(posts::Entity::find_with_deleted().all(&db).await.unwrap()),
+            vec!["kept"]
+        );
+    }
+}
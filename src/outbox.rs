@@ -0,0 +1,500 @@
+//! # Transactional Outbox
+//!
+//! Events which must be published when, and only when, a transaction commits
+//! are written to the `loco_outbox` table in that transaction, with
+//! [`Outbox::enqueue_in`]: a rollback discards them with the rest of the
+//! transaction, and a crash after the commit does not lose them.
+//!
+//! The [`Relay`] publishes the committed events, in their insertion order, to
+//! the background queue, as jobs of the worker named after their topic, or to
+//! the [`Publisher`] routed to their topic, such as an external broker. The
+//! failed publications are retried by the next polls, up to the configured
+//! attempts, so the events are delivered at least once.
+//!
+//! Create the table with [`crate::schema::create_outbox_table`] in a
+//! migration, and enable the relay in the configuration:
+//! ```yaml
+//! database:
+//!   outbox:
+//!     poll_interval: 1000
+//!     batch_size: 100
+//!     max_attempts: 10
+//! ```
+//!
+//! # Example
+//! ```rust,ignore
+//! #[derive(Serialize)]
+//! struct OrderPlaced {
+//!     order_id: i32,
+//! }
+//!
+//! impl OutboxEvent for OrderPlaced {
+//!     fn topic(&self) -> String {
+//!         // performed by the `SendReceipt` worker
+//!         "SendReceipt".to_string()
+//!     }
+//! }
+//!
+//! let txn = ctx.db.begin().await?;
+//! let order = order.insert(&txn).await?;
+//! ctx.outbox
+//!     .enqueue_in(&txn, &OrderPlaced { order_id: order.id })
+//!     .await?;
+//! txn.commit().await?;
+//! ```
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use sea_orm::{
+    sea_query::{
+        Alias, ColumnDef, Expr, LockBehavior, LockType, Order, Query, Table, TableCreateStatement,
+        TableDropStatement,
+    },
+    ConnectionTrait, DatabaseBackend, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::task::JoinHandle;
+
+use crate::{app::AppContext, bgworker::BackgroundWorker, Error, Result};
+
+/// The table of the outbox events.
+pub const OUTBOX_TABLE: &str = "loco_outbox";
+
+/// Outbox relay configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// The milliseconds between the polls of the pending events
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    /// The maximum number of events published by a poll
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u64,
+    /// The publication attempts of an event before it is left aside
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: i32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+const fn default_poll_interval() -> u64 {
+    1000
+}
+
+const fn default_batch_size() -> u64 {
+    100
+}
+
+const fn default_max_attempts() -> i32 {
+    10
+}
+
+/// An event published through the outbox.
+pub trait OutboxEvent: Serialize + Send + Sync {
+    /// The topic of the event. On the background queue, the class of the
+    /// worker performing it.
+    fn topic(&self) -> String;
+
+    /// The queue of the job, on the background queue.
+    fn queue(&self) -> Option<String> {
+        None
+    }
+}
+
+/// An event of the outbox, as read by the relay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub id: i32,
+    pub topic: String,
+    pub queue: Option<String>,
+    pub payload: serde_json::Value,
+    /// The failed publications of the event so far
+    pub attempts: i32,
+}
+
+/// Writes the events of the transactions to the outbox.
+#[derive(Debug, Clone, Default)]
+pub struct Outbox;
+
+impl Outbox {
+    /// Writes an event to the outbox, in the transaction (or connection) `db`:
+    /// it is published once the transaction commits, and never if it rolls
+    /// back.
+    ///
+    /// # Errors
+    ///
+    /// When the event can not be serialized, or the insert fails
+    pub async fn enqueue_in<C, E>(&self, db: &C, event: &E) -> Result<()>
+    where
+        C: ConnectionTrait,
+        E: OutboxEvent,
+    {
+        insert(
+            db,
+            &event.topic(),
+            event.queue(),
+            serde_json::to_value(event)?,
+        )
+        .await
+    }
+
+    /// Writes a job of the worker `W` to the outbox, in the transaction (or
+    /// connection) `db`. The relay enqueues it on the background queue once
+    /// the transaction commits.
+    ///
+    /// # Errors
+    ///
+    /// When the arguments can not be serialized, or the insert fails
+    pub async fn enqueue_job_in<W, A, C>(&self, db: &C, args: &A) -> Result<()>
+    where
+        W: BackgroundWorker<A>,
+        A: Serialize + Send + Sync + 'static,
+        C: ConnectionTrait,
+    {
+        insert(
+            db,
+            &W::class_name(),
+            W::queue_for(args),
+            serde_json::to_value(args)?,
+        )
+        .await
+    }
+}
+
+async fn insert<C: ConnectionTrait>(
+    db: &C,
+    topic: &str,
+    queue: Option<String>,
+    payload: serde_json::Value,
+) -> Result<()> {
+    let stmt = Query::insert()
+        .into_table(Alias::new(OUTBOX_TABLE))
+        .columns([
+            Alias::new("topic"),
+            Alias::new("queue"),
+            Alias::new("payload"),
+            Alias::new("attempts"),
+            Alias::new("created_at"),
+        ])
+        .values_panic([
+            topic.into(),
+            queue.into(),
+            payload.to_string().into(),
+            0.into(),
+            chrono::Utc::now().into(),
+        ])
+        .to_owned();
+    db.execute(db.get_database_backend().build(&stmt)).await?;
+    Ok(())
+}
+
+/// Publishes the events of the outbox.
+#[async_trait]
+pub trait Publisher: Send + Sync {
+    /// Publishes an event. On error, the event is published again by a
+    /// later poll.
+    async fn publish(&self, ctx: &AppContext, message: &Message) -> Result<()>;
+}
+
+/// Publishes the events to the background queue, as jobs of the worker
+/// named after their topic.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueuePublisher;
+
+#[async_trait]
+impl Publisher for QueuePublisher {
+    async fn publish(&self, ctx: &AppContext, message: &Message) -> Result<()> {
+        let queue = ctx
+            .queue_provider
+            .as_ref()
+            .ok_or_else(|| Error::string("the outbox requires a configured queue"))?;
+        queue
+            .enqueue(
+                message.topic.clone(),
+                message.queue.clone(),
+                message.payload.clone(),
+                None,
+            )
+            .await
+    }
+}
+
+/// Publishes the committed events of the outbox to their publishers.
+#[derive(Clone)]
+pub struct Relay {
+    fallback: Arc<dyn Publisher>,
+    routes: BTreeMap<String, Arc<dyn Publisher>>,
+}
+
+impl Default for Relay {
+    fn default() -> Self {
+        Self {
+            fallback: Arc::new(QueuePublisher),
+            routes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Relay {
+    /// Publishes the events of a topic with a publisher, instead of the
+    /// fallback one.
+    pub fn route(&mut self, topic: &str, publisher: impl Publisher + 'static) -> &mut Self {
+        self.routes.insert(topic.to_string(), Arc::new(publisher));
+        self
+    }
+
+    /// Publishes the events of the topics without a route with a publisher,
+    /// instead of the background queue.
+    pub fn fallback(&mut self, publisher: impl Publisher + 'static) -> &mut Self {
+        self.fallback = Arc::new(publisher);
+        self
+    }
+
+    fn publisher(&self, topic: &str) -> &dyn Publisher {
+        self.routes.get(topic).unwrap_or(&self.fallback).as_ref()
+    }
+
+    /// Publishes a batch of the pending events, in their insertion order, and
+    /// returns the number of the published ones. The failed ones are retried
+    /// by the next call, up to the maximum attempts.
+    ///
+    /// On Postgres, the batch is locked so that the relays of the instances
+    /// of the app do not publish the same events.
+    ///
+    /// # Errors
+    ///
+    /// When the outbox can not be read or updated
+    pub async fn relay_once(&self, ctx: &AppContext, config: &Config) -> Result<u64> {
+        let txn = ctx.db.begin().await?;
+        let mut select = Query::select();
+        select
+            .columns([
+                Alias::new("id"),
+                Alias::new("topic"),
+                Alias::new("queue"),
+                Alias::new("payload"),
+                Alias::new("attempts"),
+            ])
+            .from(Alias::new(OUTBOX_TABLE))
+            .and_where(Expr::col(Alias::new("published_at")).is_null())
+            .and_where(Expr::col(Alias::new("attempts")).lt(config.max_attempts))
+            .order_by(Alias::new("id"), Order::Asc)
+            .limit(config.batch_size);
+        if txn.get_database_backend() == DatabaseBackend::Postgres {
+            select.lock_with_behavior(LockType::Update, LockBehavior::SkipLocked);
+        }
+
+        let mut published: u64 = 0;
+        for row in txn
+            .query_all(txn.get_database_backend().build(&select))
+            .await?
+        {
+            let payload: String = row.try_get("", "payload")?;
+            let message = Message {
+                id: row.try_get("", "id")?,
+                topic: row.try_get("", "topic")?,
+                queue: row.try_get("", "queue")?,
+                payload: serde_json::from_str(&payload)?,
+                attempts: row.try_get("", "attempts")?,
+            };
+
+            let mut update = Query::update();
+            update
+                .table(Alias::new(OUTBOX_TABLE))
+                .and_where(Expr::col(Alias::new("id")).eq(message.id));
+            match self.publisher(&message.topic).publish(ctx, &message).await {
+                Ok(()) => {
+                    published += 1;
+                    update.value(Alias::new("published_at"), chrono::Utc::now());
+                }
+                Err(err) => {
+                    tracing::error!(
+                        id = message.id,
+                        topic = message.topic,
+                        attempts = message.attempts + 1,
+                        error = %err,
+                        "could not publish the outbox event"
+                    );
+                    update
+                        .value(Alias::new("attempts"), message.attempts + 1)
+                        .value(Alias::new("last_error"), err.to_string());
+                }
+            }
+            txn.execute(txn.get_database_backend().build(&update))
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(published)
+    }
+
+    /// Polls the outbox and publishes its events until the returned task is
+    /// aborted.
+    #[must_use]
+    pub fn start(self, ctx: &AppContext, config: &Config) -> JoinHandle<()> {
+        let ctx = ctx.clone();
+        let config = config.clone();
+        tracing::info!(?config, "relaying the outbox events");
+        tokio::spawn(async move {
+            loop {
+                match self.relay_once(&ctx, &config).await {
+                    // a full batch is followed by more events
+                    Ok(published) if published == config.batch_size => continue,
+                    Ok(_) => {}
+                    Err(err) => tracing::error!(error = %err, "outbox relay failed"),
+                }
+                tokio::time::sleep(Duration::from_millis(config.poll_interval)).await;
+            }
+        })
+    }
+}
+
+/// Deletes the events published before a date, returning their number.
+///
+/// # Errors
+///
+/// When the delete fails
+pub async fn prune<C: ConnectionTrait>(
+    db: &C,
+    published_before: chrono::DateTime<chrono::Utc>,
+) -> Result<u64> {
+    let stmt = Query::delete()
+        .from_table(Alias::new(OUTBOX_TABLE))
+        .and_where(Expr::col(Alias::new("published_at")).lt(published_before))
+        .to_owned();
+    Ok(db
+        .execute(db.get_database_backend().build(&stmt))
+        .await?
+        .rows_affected())
+}
+
+pub(crate) fn create_table_statement() -> TableCreateStatement {
+    Table::create()
+        .table(Alias::new(OUTBOX_TABLE))
+        .if_not_exists()
+        .col(
+            ColumnDef::new(Alias::new("id"))
+                .integer()
+                .not_null()
+                .auto_increment()
+                .primary_key(),
+        )
+        .col(ColumnDef::new(Alias::new("topic")).string().not_null())
+        .col(ColumnDef::new(Alias::new("queue")).string().null())
+        .col(ColumnDef::new(Alias::new("payload")).text().not_null())
+        .col(
+            ColumnDef::new(Alias::new("attempts"))
+                .integer()
+                .not_null()
+                .default(0),
+        )
+        .col(ColumnDef::new(Alias::new("last_error")).text().null())
+        .col(
+            ColumnDef::new(Alias::new("created_at"))
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Alias::new("published_at"))
+                .timestamp_with_time_zone()
+                .null(),
+        )
+        .to_owned()
+}
+
+pub(crate) fn drop_table_statement() -> TableDropStatement {
+    Table::drop()
+        .table(Alias::new(OUTBOX_TABLE))
+        .if_exists()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    #[derive(Serialize)]
+    struct OrderPlaced {
+        order_id: i32,
+    }
+
+    impl OutboxEvent for OrderPlaced {
+        fn topic(&self) -> String {
+            "orders".to_string()
+        }
+    }
+
+    #[derive(Default)]
+    struct Recording(Mutex<Vec<serde_json::Value>>);
+
+    #[async_trait]
+    impl Publisher for Arc<Recording> {
+        async fn publish(&self, _ctx: &AppContext, message: &Message) -> Result<()> {
+            self.0.lock().unwrap().push(message.payload.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn can_relay_committed_events() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let db = &ctx.db;
+        db.execute(db.get_database_backend().build(&create_table_statement()))
+            .await
+            .unwrap();
+
+        let txn = db.begin().await.unwrap();
+        ctx.outbox
+            .enqueue_in(&txn, &OrderPlaced { order_id: 1 })
+            .await
+            .unwrap();
+        txn.rollback().await.unwrap();
+
+        let txn = db.begin().await.unwrap();
+        for order_id in [2, 3] {
+            ctx.outbox
+                .enqueue_in(&txn, &OrderPlaced { order_id })
+                .await
+                .unwrap();
+        }
+        txn.commit().await.unwrap();
+
+        let recording = Arc::new(Recording::default());
+        let mut relay = Relay::default();
+        relay.route("orders", recording.clone());
+        let config = Config::default();
+        assert_eq!(relay.relay_once(&ctx, &config).await.unwrap(), 2);
+        assert_eq!(relay.relay_once(&ctx, &config).await.unwrap(), 0);
+        assert_eq!(
+            *recording.0.lock().unwrap(),
+            vec![json!({ "order_id": 2 }), json!({ "order_id": 3 })]
+        );
+
+        // without a queue, the events fail to publish, and are left aside
+        // after the maximum attempts
+        ctx.outbox
+            .enqueue_in(db, &OrderPlaced { order_id: 4 })
+            .await
+            .unwrap();
+        let config = Config {
+            max_attempts: 1,
+            ..Default::default()
+        };
+        let relay = Relay::default();
+        assert_eq!(relay.relay_once(&ctx, &config).await.unwrap(), 0);
+        assert_eq!(relay.relay_once(&ctx, &config).await.unwrap(), 0);
+
+        assert_eq!(
+            prune(db, chrono::Utc::now() + chrono::Duration::seconds(1))
+                .await
+                .unwrap(),
+            2
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! # Authorization Policies
+//!
+//! Keeps authorization rules out of controllers: a policy wraps a user and
+//! a resource, and answers whether the user can perform an action on it.
+//! Each action is a method of the policy, denied unless overridden.
+//!
+//! Generate a policy with `cargo loco generate policy post`.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//!
+//! struct User {
+//!     id: i32,
+//!     admin: bool,
+//! }
+//!
+//! struct Post {
+//!     author_id: i32,
+//!     published: bool,
+//! }
+//!
+//! struct PostPolicy<'a> {
+//!     user: &'a User,
+//!     post: &'a Post,
+//! }
+//!
+//! impl<'a> Policy<&'a User, &'a Post> for PostPolicy<'a> {
+//!     fn new(user: &'a User, post: &'a Post) -> Self {
+//!         Self { user, post }
+//!     }
+//!
+//!     fn show(&self) -> bool {
+//!         self.post.published || self.update()
+//!     }
+//!
+//!     fn update(&self) -> bool {
+//!         self.user.admin || self.post.author_id == self.user.id
+//!     }
+//! }
+//!
+//! impl PostPolicy<'_> {
+//!     // a custom action
+//!     fn publish(&self) -> bool {
+//!         self.user.admin
+//!     }
+//! }
+//!
+//! fn publish(user: &User, post: &Post) -> Result<()> {
+//!     authorize!(PostPolicy::new(user, post), publish);
+//!     // publish the post
+//!     Ok(())
+//! }
+//! ```
+use crate::{Error, Result};
+
+/// The permissions of a user on a resource, one method per action.
+///
+/// All actions are denied by default.
+pub trait Policy<User, Resource>: Sized {
+    /// Creates the policy of `user` on `resource`.
+    fn new(user: User, resource: Resource) -> Self;
+
+    fn index(&self) -> bool {
+        false
+    }
+
+    fn show(&self) -> bool {
+        false
+    }
+
+    fn create(&self) -> bool {
+        false
+    }
+
+    fn update(&self) -> bool {
+        false
+    }
+
+    fn destroy(&self) -> bool {
+        false
+    }
+
+    /// Whether the action named `action` is allowed, for actions only known
+    /// at runtime. Override it to include custom actions.
+    fn allowed(&self, action: &str) -> bool {
+        match action {
+            "index" => self.index(),
+            "show" => self.show(),
+            "create" => self.create(),
+            "update" => self.update(),
+            "destroy" => self.destroy(),
+            _ => false,
+        }
+    }
+
+    /// Rejects with `403 Forbidden` unless the action named `action` is
+    /// allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Forbidden`] when the action is not allowed.
+    fn authorize(&self, action: &str) -> Result<()> {
+        verify(self.allowed(action), action)
+    }
+}
+
+/// Turns the outcome of a policy check into a `403 Forbidden` error.
+///
+/// # Errors
+///
+/// Returns [`Error::Forbidden`] when `allowed` is `false`.
+pub fn verify(allowed: bool, action: &str) -> Result<()> {
+    if allowed {
+        Ok(())
+    } else {
+        Err(Error::Forbidden(format!("not allowed to {action}")))
+    }
+}
+
+/// Returns early with `403 Forbidden` unless the policy allows the action,
+/// calling the policy method named after it.
+///
+/// ```rust,ignore
+/// authorize!(PostPolicy::new(&user, &post), update);
+/// ```
+#[macro_export]
+macro_rules! authorize {
+    ($policy:expr, $action:ident) => {
+        $crate::policy::verify($policy.$action(), stringify!($action))?
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct OwnerPolicy {
+        user: i32,
+        owner: i32,
+    }
+
+    impl Policy<i32, i32> for OwnerPolicy {
+        fn new(user: i32, owner: i32) -> Self {
+            Self { user, owner }
+        }
+
+        fn show(&self) -> bool {
+            true
+        }
+
+        fn update(&self) -> bool {
+            self.user == self.owner
+        }
+    }
+
+    fn update(user: i32, owner: i32) -> Result<()> {
+        authorize!(OwnerPolicy::new(user, owner), update);
+        Ok(())
+    }
+
+    #[test]
+    fn denies_by_default() {
+        let policy = OwnerPolicy::new(1, 1);
+
+        assert!(policy.allowed("show"));
+        assert!(!policy.allowed("destroy"));
+        assert!(!policy.allowed("unknown"));
+    }
+
+    #[test]
+    fn authorize_rejects_with_forbidden() {
+        assert!(update(1, 1).is_ok());
+        assert!(matches!(update(1, 2), Err(Error::Forbidden(_))));
+        assert!(matches!(
+            OwnerPolicy::new(1, 2).authorize("update"),
+            Err(Error::Forbidden(_))
+        ));
+    }
+}
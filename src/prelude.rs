@@ -23,27 +23,55 @@ pub use serde_json::json as data;
 #[cfg(feature = "auth_jwt")]
 pub use crate::controller::extractor::auth;
 pub use crate::controller::extractor::{
+    pagination::Pagination,
     shared_store::SharedStore,
     validate::{JsonValidate, JsonValidateWithMessage},
 };
 #[cfg(feature = "with-db")]
-pub use crate::model::{query, Authenticable, ModelError, ModelResult};
+pub use crate::controller::middleware::transaction::Tx;
+#[cfg(all(feature = "view_handlebars", not(feature = "embedded_assets")))]
+pub use crate::controller::views::HandlebarsView;
+#[cfg(all(feature = "view_minijinja", not(feature = "embedded_assets")))]
+pub use crate::controller::views::MiniJinjaView;
+#[cfg(feature = "i18n")]
+pub use crate::i18n::{I18n, Locale};
+#[cfg(feature = "with-db")]
+pub use crate::listener::{self, Listeners, Notification};
+#[cfg(feature = "with-db")]
+pub use crate::model::{
+    audit::Auditable,
+    bulk::BulkInsert,
+    counter_cache::{CounterCache, CounterCached},
+    encryption::Encrypted,
+    query,
+    search::{self, Searchable},
+    Authenticable, CallbackActiveModel, Callbacks, Lockable, LockableActiveModel, ModelError,
+    ModelResult, SoftDeletable, SoftDeletableModel,
+};
+#[cfg(feature = "with-db")]
+pub use crate::outbox::OutboxEvent;
+#[cfg(feature = "with-db")]
+pub use crate::seeder::{Seeder, SeederInfo};
+#[cfg(feature = "with-db")]
+pub use crate::tenancy::{TenantContext, TenantScoped};
 pub use crate::{
     app::{AppContext, Initializer},
+    authorize,
     bgworker::{BackgroundWorker, Queue},
     controller::{
-        bad_request, format,
+        bad_request, forbidden, format,
         middleware::{
-            format::{Format, RespondTo},
+            format::{Format, Negotiate, RespondTo},
             remote_ip::RemoteIP,
         },
         not_found, unauthorized,
-        views::{engines::TeraView, ViewEngine, ViewRenderer},
+        views::{engines::TeraView, Layout, ViewContext, ViewEngine, ViewRenderer},
         Json, Routes,
     },
     errors::Error,
     mailer,
     mailer::Mailer,
+    policy::{self, Policy},
     task::{self, Task, TaskInfo},
     validation::{self, Validatable, ValidatorTrait},
     Result,
@@ -2,16 +2,22 @@
 //! TBD
 
 use std::{
-    collections::HashMap,
-    fmt, io,
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::OpenOptions,
+    io::{self, Write},
     path::{Path, PathBuf},
-    sync::OnceLock,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
     time::{Duration, Instant},
 };
 
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use tokio_cron_scheduler::{JobScheduler, JobSchedulerError};
+use tracing::Instrument;
 use uuid::Uuid;
 
 use crate::{app::Hooks, environment::Environment, task::Tasks};
@@ -22,6 +28,105 @@ fn get_re_is_cron_syntax() -> &'static Regex {
     RE_IS_CRON_SYNTAX.get_or_init(|| Regex::new(r"^[\*\d]").unwrap())
 }
 
+/// Returns the cron syntax of a schedule, a cron expression or an English one
+/// such as `every 5 minutes`.
+///
+/// # Errors
+///
+/// When an English schedule could not be converted
+pub(crate) fn cron_syntax(schedule: &str) -> Result<String> {
+    if get_re_is_cron_syntax().is_match(schedule) {
+        return Ok(schedule.to_string());
+    }
+    english_to_cron::str_cron_syntax(schedule).map_err(|err| Error::InvalidCronSyntax {
+        cron: schedule.to_string(),
+        error: err.to_string(),
+    })
+}
+
+/// Parses the schedule of a job and its timezone, UTC by default.
+///
+/// # Errors
+///
+/// When the schedule or the timezone is invalid
+pub(crate) fn parse_schedule(
+    job: &str,
+    schedule: &str,
+    timezone: Option<&str>,
+) -> Result<(cron::Schedule, Tz)> {
+    let cron = cron_syntax(schedule)?;
+    let parsed = cron::Schedule::from_str(&cron).map_err(|err| Error::InvalidCronSyntax {
+        cron: schedule.to_string(),
+        error: err.to_string(),
+    })?;
+    let timezone = match timezone {
+        Some(timezone) => timezone.parse::<Tz>().map_err(|_| Error::InvalidTimezone {
+            job: job.to_string(),
+            timezone: timezone.to_string(),
+        })?,
+        None => Tz::UTC,
+    };
+    Ok((parsed, timezone))
+}
+
+/// The next runs of a job, as shown by `cargo loco scheduler --explain`.
+#[derive(Debug)]
+pub struct Explanation {
+    /// The name of the job.
+    pub job: String,
+    /// The schedule of the job, as configured.
+    pub schedule: String,
+    /// The timezone of the schedule, when not UTC.
+    pub timezone: Option<String>,
+    /// The next runs of the job in its timezone, or why its schedule is
+    /// invalid.
+    pub next_runs: Result<Vec<DateTime<Tz>>>,
+}
+
+impl Explanation {
+    /// Computes the next `count` runs of a job after `from`.
+    #[must_use]
+    pub fn new(
+        job: &str,
+        schedule: &str,
+        timezone: Option<&str>,
+        from: DateTime<Utc>,
+        count: usize,
+    ) -> Self {
+        let next_runs = parse_schedule(job, schedule, timezone).map(|(parsed, timezone)| {
+            parsed
+                .after(&from.with_timezone(&timezone))
+                .take(count)
+                .collect()
+        });
+        Self {
+            job: job.to_string(),
+            schedule: schedule.to_string(),
+            timezone: timezone.map(ToString::to_string),
+            next_runs,
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ({}, {})",
+            self.job,
+            self.schedule,
+            self.timezone.as_deref().unwrap_or("UTC")
+        )?;
+        match &self.next_runs {
+            Ok(runs) if runs.is_empty() => writeln!(f, "  never runs"),
+            Ok(runs) => runs
+                .iter()
+                .try_for_each(|run| writeln!(f, "  {}", run.format("%Y-%m-%d %H:%M:%S %Z"))),
+            Err(err) => writeln!(f, "  invalid: {err}"),
+        }
+    }
+}
+
 /// Errors that may occur while operating the scheduler.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -40,6 +145,15 @@ pub enum Error {
     #[error("Invalid cron {cron}. err: '{}'", error.as_display())]
     InvalidCronSyntax { cron: String, error: String },
 
+    #[error("Invalid timezone `{timezone}` of job `{job}`")]
+    InvalidTimezone { job: String, timezone: String },
+
+    #[error("scheduler history not configured")]
+    HistoryNotConfigured,
+
+    #[error(transparent)]
+    InvalidHistory(#[from] serde_json::Error),
+
     #[error(transparent)]
     Question(#[from] JobSchedulerError),
 
@@ -59,8 +173,35 @@ pub struct Config {
     /// The default output setting for the jobs.
     #[serde(default)]
     pub output: Output,
+    /// Records the runs of the jobs, listed by `cargo loco scheduler
+    /// --history`.
+    #[serde(default)]
+    pub history: Option<History>,
+}
+
+/// The file recording the runs of the jobs, one JSON line per run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct History {
+    /// The path of the file.
+    #[serde(default = "default_history_path")]
+    pub path: PathBuf,
+    /// The number of runs kept, the oldest runs being dropped.
+    #[serde(default = "default_history_keep")]
+    pub keep: usize,
 }
 
+fn default_history_path() -> PathBuf {
+    PathBuf::from("tmp/scheduler-history.jsonl")
+}
+
+const fn default_history_keep() -> usize {
+    1000
+}
+
+/// The output of a run kept in the history, its last bytes.
+const HISTORY_OUTPUT_LIMIT: usize = 4096;
+
 /// Representing a single job in the scheduler.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -83,6 +224,124 @@ pub struct Job {
     pub tags: Option<Vec<String>>,
     /// Output settings for the job.
     pub output: Option<Output>,
+    /// The timezone of the schedule, such as `Europe/Paris`. UTC by default.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// What to do when the job is due while its previous run is running.
+    #[serde(default)]
+    pub overlap: Overlap,
+}
+
+/// What to do when a job is due while its previous run is running.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Overlap {
+    /// Starts the run alongside the previous one.
+    #[default]
+    #[serde(rename = "allow")]
+    Allow,
+    /// Skips the run.
+    #[serde(rename = "skip")]
+    Skip,
+    /// Starts the run once the previous one finished.
+    #[serde(rename = "queue")]
+    Queue,
+    /// Kills the previous run, then starts the run.
+    #[serde(rename = "kill")]
+    Kill,
+}
+
+/// The outcome of a run of a job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// The command exited successfully.
+    Succeeded,
+    /// The command failed, or could not start.
+    Failed,
+    /// The command was killed, by a signal or by the next run.
+    Killed,
+    /// The run was skipped, as the previous run was running.
+    Skipped,
+}
+
+/// A run of a job, as recorded in the history.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Run {
+    pub job: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    /// The last bytes of the standard output and error of the command.
+    pub output: String,
+}
+
+impl fmt::Display for Run {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<27} {:<15} {:<10} {:<6} {}ms",
+            self.started_at
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            self.job,
+            format!("{:?}", self.status).to_lowercase(),
+            self.exit_code
+                .map_or_else(|| "-".to_string(), |code| code.to_string()),
+            self.duration_ms,
+        )?;
+        for line in self.output.lines() {
+            write!(f, "\n    {line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl History {
+    /// Records a run, dropping the oldest runs beyond `keep`.
+    ///
+    /// # Errors
+    ///
+    /// When the file could not be written.
+    pub fn record(&self, run: &Run) -> Result<()> {
+        // the jobs record their runs concurrently
+        static LOCK: Mutex<()> = Mutex::new(());
+        let _guard = LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(run)?)?;
+
+        let content = std::fs::read_to_string(&self.path)?;
+        let lines = content.lines().collect::<Vec<_>>();
+        if lines.len() > self.keep {
+            let kept = &lines[lines.len() - self.keep..];
+            std::fs::write(&self.path, format!("{}\n", kept.join("\n")))?;
+        }
+        Ok(())
+    }
+
+    /// Lists the recorded runs, the latest last.
+    ///
+    /// # Errors
+    ///
+    /// When the file could not be read or parsed.
+    pub fn runs(&self) -> Result<Vec<Run>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        std::fs::read_to_string(&self.path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
 }
 
 impl fmt::Display for Scheduler {
@@ -123,6 +382,7 @@ pub struct Scheduler {
     binary_path: PathBuf,
     default_output: Output,
     environment: Environment,
+    history: Option<History>,
 }
 
 /// Specification used to filter all scheduler job with the given Spec.
@@ -203,6 +463,27 @@ impl JobDescription {
 
         exec_job.run()
     }
+
+    /// Starts the job command without waiting for it, capturing its
+    /// standard output and error with `capture`. The command exiting with a
+    /// failure is not an error.
+    ///
+    /// # Errors
+    ///
+    /// When the command could not start
+    pub fn start(&self, capture: bool) -> io::Result<duct::Handle> {
+        tracing::info!(command = &self.command, "start job command");
+        let mut exec_job = duct_sh::sh_dangerous(&self.command)
+            .env("LOCO_ENV", self.environment.to_string())
+            .unchecked();
+        exec_job = match (&self.output, capture) {
+            (_, true) => exec_job.stderr_to_stdout().stdout_capture(),
+            (Output::Silent, false) => exec_job.stdout_null().stderr_null(),
+            (Output::STDOUT, false) => exec_job,
+        };
+
+        exec_job.start()
+    }
 }
 
 impl Scheduler {
@@ -238,6 +519,14 @@ impl Scheduler {
 
         let mut jobs = HashMap::new();
         for (job_name, job) in &data.jobs {
+            if let Some(timezone) = &job.timezone {
+                timezone
+                    .parse::<chrono_tz::Tz>()
+                    .map_err(|_| Error::InvalidTimezone {
+                        job: job_name.clone(),
+                        timezone: timezone.clone(),
+                    })?;
+            }
             if job.shell {
                 jobs.insert(job_name.clone(), job.clone());
             } else {
@@ -259,9 +548,25 @@ impl Scheduler {
             binary_path: std::env::current_exe()?,
             default_output: data.output.clone(),
             environment: environment.clone(),
+            history: data.history.clone(),
         })
     }
 
+    /// Lists the recorded runs of the scheduler's jobs, the latest last.
+    ///
+    /// # Errors
+    ///
+    /// When the history is not configured, or could not be read.
+    pub fn history(&self) -> Result<Vec<Run>> {
+        let history = self.history.as_ref().ok_or(Error::HistoryNotConfigured)?;
+        let jobs = self.jobs.keys().collect::<HashSet<_>>();
+        Ok(history
+            .runs()?
+            .into_iter()
+            .filter(|run| jobs.contains(&run.job))
+            .collect())
+    }
+
     /// Filters the scheduler's jobs based on the provided specification.
     #[must_use]
     pub fn by_spec(self, include_jobs: &Spec) -> Self {
@@ -286,6 +591,21 @@ impl Scheduler {
         Self { jobs, ..self }
     }
 
+    /// Explains the schedules of the jobs with their next `count` runs after
+    /// `from`, sorted by job name.
+    #[must_use]
+    pub fn explain(&self, from: DateTime<Utc>, count: usize) -> Vec<Explanation> {
+        let mut explanations = self
+            .jobs
+            .iter()
+            .map(|(job_name, job)| {
+                Explanation::new(job_name, &job.cron, job.timezone.as_deref(), from, count)
+            })
+            .collect::<Vec<_>>();
+        explanations.sort_by(|a, b| a.job.cmp(&b.job));
+        explanations
+    }
+
     /// Runs the scheduled jobs according to their cron expressions.
     ///
     /// # Errors
@@ -295,50 +615,57 @@ impl Scheduler {
         let mut sched = JobScheduler::new().await?;
 
         for (job_name, job) in &self.jobs {
-            let job_description =
-                job.prepare_command(&self.binary_path, &self.default_output, &self.environment);
-
-            let cron_syntax = if get_re_is_cron_syntax().is_match(&job.cron) {
-                job.cron.clone()
-            } else {
-                english_to_cron::str_cron_syntax(&job.cron).map_err(|err| {
-                    Error::InvalidCronSyntax {
-                        cron: job.cron.clone(),
-                        error: err.to_string(),
-                    }
-                })?
-            };
+            let scheduled = Arc::new(ScheduledJob {
+                name: job_name.clone(),
+                description: job.prepare_command(
+                    &self.binary_path,
+                    &self.default_output,
+                    &self.environment,
+                ),
+                overlap: job.overlap,
+                history: self.history.clone(),
+                queue: tokio::sync::Mutex::new(()),
+                running: Mutex::new(None),
+            });
+
+            let cron_syntax = cron_syntax(&job.cron)?;
 
             if job.run_on_start {
-                let job_description = job_description.clone();
-                let job_name = job_name.clone();
+                let scheduled = scheduled.clone();
                 sched
                     .add(tokio_cron_scheduler::Job::new_one_shot_async(
                         Duration::from_secs(0),
                         move |uuid, _l| {
-                            let job_description = job_description.clone();
-                            let job_name = job_name.clone();
+                            let scheduled = scheduled.clone();
                             Box::pin(async move {
-                                execute_job(job_name.as_str(), uuid, &job_description);
+                                scheduled.execute(uuid).await;
                             })
                         },
                     )?)
                     .await?;
             }
 
-            let job_name = job_name.clone();
-            sched
-                .add(tokio_cron_scheduler::Job::new_async(
-                    cron_syntax.as_str(),
-                    move |uuid, mut _l| {
-                        let job_description = job_description.clone();
-                        let job_name = job_name.clone();
-                        Box::pin(async move {
-                            execute_job(job_name.as_str(), uuid, &job_description);
-                        })
-                    },
-                )?)
-                .await?;
+            let run = move |uuid: Uuid, _l: JobScheduler| {
+                let scheduled = scheduled.clone();
+                Box::pin(async move {
+                    scheduled.execute(uuid).await;
+                })
+                    as std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>
+            };
+            let cron_job = match &job.timezone {
+                Some(timezone) => {
+                    let timezone =
+                        timezone
+                            .parse::<chrono_tz::Tz>()
+                            .map_err(|_| Error::InvalidTimezone {
+                                job: job_name.clone(),
+                                timezone: timezone.clone(),
+                            })?;
+                    tokio_cron_scheduler::Job::new_async_tz(cron_syntax.as_str(), timezone, run)?
+                }
+                None => tokio_cron_scheduler::Job::new_async(cron_syntax.as_str(), run)?,
+            };
+            sched.add(cron_job).await?;
         }
 
         sched.start().await?;
@@ -350,35 +677,155 @@ impl Scheduler {
     }
 }
 
-fn execute_job(job_name: &str, uuid: Uuid, job_description: &JobDescription) {
-    let task_span = tracing::span!(
-        tracing::Level::DEBUG,
-        "run_job",
-        job_name,
-        job_id = ?uuid,
-    );
-    let start = Instant::now();
-    let _guard = task_span.enter();
-    match job_description.run() {
-        Ok(output) => {
-            tracing::debug!(
-                duration = ?start.elapsed(),
-                status_code = output.status.code(),
-                "execute scheduler job finished"
-            );
+/// A job with the state of its runs, shared by its triggers.
+struct ScheduledJob {
+    name: String,
+    description: JobDescription,
+    overlap: Overlap,
+    history: Option<History>,
+    /// Held by a run of a queued job
+    queue: tokio::sync::Mutex<()>,
+    /// The command of the latest run, while it runs
+    running: Mutex<Option<Arc<duct::Handle>>>,
+}
+
+impl ScheduledJob {
+    async fn execute(&self, uuid: Uuid) {
+        let task_span = tracing::span!(
+            tracing::Level::DEBUG,
+            "run_job",
+            job_name = self.name,
+            job_id = ?uuid,
+        );
+        async {
+            let _queued = match self.overlap {
+                Overlap::Queue => Some(self.queue.lock().await),
+                _ => None,
+            };
+            let started_at = Utc::now();
+            let start = Instant::now();
+            let run = |status, exit_code, output: String| Run {
+                job: self.name.clone(),
+                started_at,
+                duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+                status,
+                exit_code,
+                output,
+            };
+
+            let handle = {
+                let mut running = self
+                    .running
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                if let Some(previous) = running.as_ref() {
+                    match self.overlap {
+                        Overlap::Skip => {
+                            tracing::info!("previous run is running, skipping scheduler job");
+                            drop(running);
+                            self.record(&run(RunStatus::Skipped, None, String::new()));
+                            return;
+                        }
+                        Overlap::Kill => {
+                            tracing::info!("killing the previous run of scheduler job");
+                            if let Err(err) = previous.kill() {
+                                tracing::error!(error = %err, "failed to kill scheduler job");
+                            }
+                        }
+                        Overlap::Allow | Overlap::Queue => {}
+                    }
+                }
+                match self.description.start(self.history.is_some()) {
+                    Ok(handle) => {
+                        let handle = Arc::new(handle);
+                        *running = Some(handle.clone());
+                        handle
+                    }
+                    Err(err) => {
+                        drop(running);
+                        tracing::error!(
+                            error = %err,
+                            "failed to execute scheduler job in sub process"
+                        );
+                        self.record(&run(RunStatus::Failed, None, err.to_string()));
+                        return;
+                    }
+                }
+            };
+
+            let waited = tokio::task::spawn_blocking({
+                let handle = handle.clone();
+                move || handle.wait().cloned()
+            })
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+            .and_then(|output| output);
+            {
+                let mut running = self
+                    .running
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                if running
+                    .as_ref()
+                    .is_some_and(|latest| Arc::ptr_eq(latest, &handle))
+                {
+                    *running = None;
+                }
+            }
+
+            match waited {
+                Ok(output) => {
+                    tracing::debug!(
+                        duration = ?start.elapsed(),
+                        status_code = output.status.code(),
+                        "execute scheduler job finished"
+                    );
+                    let captured = String::from_utf8_lossy(&output.stdout);
+                    if matches!(self.description.output, Output::STDOUT) {
+                        print!("{captured}");
+                    }
+                    let status = match output.status.code() {
+                        Some(0) => RunStatus::Succeeded,
+                        Some(_) => RunStatus::Failed,
+                        None => RunStatus::Killed,
+                    };
+                    self.record(&run(status, output.status.code(), tail(&captured)));
+                }
+                Err(err) => {
+                    tracing::error!(
+                        duration = ?start.elapsed(),
+                        error = %err,
+                        "failed to execute scheduler job in sub process"
+                    );
+                    self.record(&run(RunStatus::Failed, None, err.to_string()));
+                }
+            }
         }
-        Err(err) => {
-            tracing::error!(
-                duration = ?start.elapsed(),
-                error = %err,
-                "failed to execute scheduler job in sub process"
-            );
+        .instrument(task_span)
+        .await;
+    }
+
+    fn record(&self, run: &Run) {
+        if let Some(history) = &self.history {
+            if let Err(err) = history.record(run) {
+                tracing::error!(error = %err, "failed to record scheduler job run");
+            }
         }
     }
 }
 
+/// The last bytes of an output, on a character boundary.
+fn tail(output: &str) -> String {
+    let mut start = output.len().saturating_sub(HISTORY_OUTPUT_LIMIT);
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    output[start..].to_string()
+}
+
 #[cfg(test)]
 mod tests {
+    use chrono::TimeZone;
     use insta::assert_debug_snapshot;
     use rstest::rstest;
     use tests_cfg::db::AppHook;
@@ -500,6 +947,8 @@ jobs:
             cron: "*/5 * * * * *".to_string(),
             tags: None,
             output: None,
+            timezone: None,
+            overlap: Overlap::default(),
         };
 
         let prepare_command = job.prepare_command(
@@ -560,6 +1009,8 @@ jobs:
                     cron: "run every 1 second".to_string(),
                     tags: None,
                     output: None,
+                    timezone: None,
+                    overlap: Overlap::default(),
                 },
             ),
             (
@@ -571,9 +1022,11 @@ jobs:
                     ),
                     shell: true,
                     run_on_start: false,
-                    cron: "* * * * * ? *".to_string(),
+                    cron: "* * * * * *".to_string(),
                     tags: None,
                     output: None,
+                    timezone: None,
+                    overlap: Overlap::default(),
                 },
             ),
             (
@@ -588,6 +1041,8 @@ jobs:
                     cron: "0 0 * * * * *".to_string(),
                     tags: None,
                     output: None,
+                    timezone: None,
+                    overlap: Overlap::default(),
                 },
             ),
         ]);
@@ -621,4 +1076,141 @@ jobs:
             1
         );
     }
+
+    #[test]
+    pub fn can_keep_the_latest_runs_in_history() {
+        let tree_fs = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let history = History {
+            path: tree_fs.root.join("history").join("runs.jsonl"),
+            keep: 2,
+        };
+        assert!(history.runs().unwrap().is_empty());
+
+        for (job, status) in [
+            ("a", RunStatus::Succeeded),
+            ("b", RunStatus::Failed),
+            ("c", RunStatus::Skipped),
+        ] {
+            history
+                .record(&Run {
+                    job: job.to_string(),
+                    started_at: Utc::now(),
+                    duration_ms: 1,
+                    status,
+                    exit_code: None,
+                    output: String::new(),
+                })
+                .unwrap();
+        }
+
+        let runs = history.runs().unwrap();
+        assert_eq!(
+            runs.iter()
+                .map(|run| (run.job.as_str(), run.status))
+                .collect::<Vec<_>>(),
+            vec![("b", RunStatus::Failed), ("c", RunStatus::Skipped)]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn can_skip_overlapping_runs() {
+        let (mut scheduler, _config_tree) = setup_scheduler_config();
+        let tree_fs = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+
+        scheduler.history = Some(History {
+            path: tree_fs.root.join("runs.jsonl"),
+            keep: 100,
+        });
+        scheduler.jobs = HashMap::from([(
+            "slow".to_string(),
+            Job {
+                run: "sleep 2 && echo done".to_string(),
+                shell: true,
+                run_on_start: false,
+                cron: "* * * * * *".to_string(),
+                tags: None,
+                output: None,
+                timezone: Some("Europe/Paris".to_string()),
+                overlap: Overlap::Skip,
+            },
+        )]);
+        let history = scheduler.clone();
+
+        let handle = tokio::spawn(async move {
+            scheduler.run().await.unwrap();
+        });
+        time::sleep(Duration::from_secs(4)).await;
+        handle.abort();
+
+        let runs = history.history().unwrap();
+        assert!(runs.iter().any(|run| run.status == RunStatus::Skipped));
+        assert!(runs
+            .iter()
+            .any(|run| run.status == RunStatus::Succeeded && run.output == "done\n"));
+    }
+
+    #[test]
+    pub fn can_not_load_an_unknown_timezone() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+jobs:
+  report:
+    run: "echo loco"
+    shell: true
+    schedule: "0 0 9 * * *"
+    timezone: Mars/Olympus
+"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            Scheduler::new::<AppHook>(&config, &Environment::Test),
+            Err(Error::InvalidTimezone { .. })
+        ));
+    }
+
+    #[test]
+    pub fn can_explain_the_next_runs() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+jobs:
+  report:
+    run: "echo loco"
+    shell: true
+    schedule: "0 0 9 * * *"
+    timezone: Europe/Paris
+  typo:
+    run: "echo loco"
+    shell: true
+    schedule: "0 0 25 * * *"
+"#,
+        )
+        .unwrap();
+        let scheduler = Scheduler::new::<AppHook>(&config, &Environment::Test).unwrap();
+        let from = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap();
+
+        let explanations = scheduler.explain(from, 2);
+        assert_eq!(
+            explanations
+                .iter()
+                .map(|explanation| explanation.job.as_str())
+                .collect::<Vec<_>>(),
+            vec!["report", "typo"]
+        );
+        let runs = explanations[0]
+            .next_runs
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|run| run.to_rfc3339())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            vec!["2025-01-02T09:00:00+01:00", "2025-01-03T09:00:00+01:00"]
+        );
+        assert!(matches!(
+            explanations[1].next_runs,
+            Err(Error::InvalidCronSyntax { .. })
+        ));
+    }
 }
@@ -9,6 +9,9 @@ use sea_orm::{
 pub use sea_orm_migration::schema::*;
 use sea_orm_migration::{prelude::Iden, sea_query, SchemaManager};
 
+use crate::model::counter_cache::CounterCache;
+pub use crate::model::search::{self, Refresh as SearchRefresh, SearchIndex};
+
 #[derive(Iden)]
 enum GeneralIds {
     CreatedAt,
@@ -158,6 +161,8 @@ async fn check_enum_exists(m: &SchemaManager<'_>, enum_name: &str) -> Result<boo
 pub enum ColType {
     PkAuto,
     PkUuid,
+    /// A ULID primary key, in a `char(26)` column
+    PkUlid,
     CharLen(u32),
     CharLenWithDefault(u32, char),
     CharLenNull(u32),
@@ -325,6 +330,7 @@ impl ColType {
         match self {
             Self::PkAuto => pk_auto(name),
             Self::PkUuid => pk_uuid(name),
+            Self::PkUlid => char_len(name, 26).primary_key().take(),
             Self::CharLen(len) => char_len(name, *len),
             Self::CharLenNull(len) => char_len_null(name, *len),
             Self::CharLenUniq(len) => char_len_uniq(name, *len),
@@ -694,7 +700,7 @@ fn normalize_table(table: &str) -> String {
 }
 
 /// users -> `user_id`
-fn reference_id(totbl: &str) -> String {
+pub(crate) fn reference_id(totbl: &str) -> String {
     format!("{}_id", cruet::to_singular(totbl).to_snake_case())
 }
 
@@ -741,6 +747,222 @@ pub async fn remove_column(m: &SchemaManager<'_>, table: &str, name: &str) -> Re
     Ok(())
 }
 
+///
+/// Adds the nullable `deleted_at` column of soft deletes to a table, with
+/// its index, see [`crate::model::SoftDeletable`].
+///
+/// ```ignore
+/// add_soft_delete(m, "movies").await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn add_soft_delete(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    add_column(m, table, "deleted_at", ColType::TimestampWithTimeZoneNull).await?;
+    let nz_table = normalize_table(table);
+    m.create_index(
+        Index::create()
+            .name(format!("idx-{nz_table}-deleted_at"))
+            .table(Alias::new(&nz_table))
+            .col(Alias::new("deleted_at"))
+            .to_owned(),
+    )
+    .await
+}
+
+///
+/// Removes the `deleted_at` column of soft deletes from a table.
+///
+/// ```ignore
+/// remove_soft_delete(m, "movies").await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn remove_soft_delete(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    let nz_table = normalize_table(table);
+    m.drop_index(
+        Index::drop()
+            .name(format!("idx-{nz_table}-deleted_at"))
+            .table(Alias::new(&nz_table))
+            .to_owned(),
+    )
+    .await?;
+    remove_column(m, table, "deleted_at").await
+}
+
+///
+/// Creates the `versions` table of the audit trail, see
+/// [`crate::model::audit`].
+///
+/// ```ignore
+/// create_versions_table(m).await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn create_versions_table(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    create_table(
+        m,
+        "versions",
+        &[
+            ("id", ColType::PkAuto),
+            ("item_type", ColType::String),
+            ("item_id", ColType::String),
+            ("event", ColType::String),
+            ("actor", ColType::StringNull),
+            ("changes", ColType::Json),
+            ("object", ColType::Json),
+        ],
+        &[],
+    )
+    .await?;
+    m.create_index(
+        Index::create()
+            .name("idx-versions-item")
+            .table(Alias::new("versions"))
+            .col(Alias::new("item_type"))
+            .col(Alias::new("item_id"))
+            .to_owned(),
+    )
+    .await
+}
+
+///
+/// Drops the `versions` table of the audit trail.
+///
+/// ```ignore
+/// drop_versions_table(m).await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn drop_versions_table(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    drop_table(m, "versions").await
+}
+
+///
+/// Adds the `lock_version` column of optimistic locking to a table, starting
+/// at 0, see [`crate::model::Lockable`].
+///
+/// ```ignore
+/// add_lock_version(m, "movies").await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn add_lock_version(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    add_column(m, table, "lock_version", ColType::IntegerWithDefault(0)).await
+}
+
+///
+/// Removes the `lock_version` column of optimistic locking from a table.
+///
+/// ```ignore
+/// remove_lock_version(m, "movies").await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn remove_lock_version(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    remove_column(m, table, "lock_version").await
+}
+
+///
+/// Adds the counter column of the `children` of a table, such as
+/// `comments_count` for `comments`, counting the existing children, see
+/// [`crate::model::counter_cache`].
+///
+/// ```ignore
+/// add_counter_cache(m, "posts", "comments").await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn add_counter_cache(
+    m: &SchemaManager<'_>,
+    table: &str,
+    children: &str,
+) -> Result<(), DbErr> {
+    let counter = CounterCache::new(table, children);
+    add_column(m, table, &counter.column, ColType::IntegerWithDefault(0)).await?;
+    counter.backfill(m.get_connection()).await?;
+    Ok(())
+}
+
+///
+/// Removes the counter column of the `children` of a table.
+///
+/// ```ignore
+/// remove_counter_cache(m, "posts", "comments").await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn remove_counter_cache(
+    m: &SchemaManager<'_>,
+    table: &str,
+    children: &str,
+) -> Result<(), DbErr> {
+    remove_column(m, table, &CounterCache::new(table, children).column).await
+}
+
+///
+/// Adds the full-text search index of a table: a weighted tsvector column
+/// with a GIN index on Postgres, an FTS5 table on SQLite, see
+/// [`crate::model::search`]. The existing rows are indexed.
+///
+/// ```ignore
+/// add_search_index(m, "movies", &SearchIndex::new(&["title", "plot"])).await;
+/// ```
+/// # Errors
+/// fails when it fails, or on MySQL
+pub async fn add_search_index(
+    m: &SchemaManager<'_>,
+    table: &str,
+    index: &SearchIndex<'_>,
+) -> Result<(), DbErr> {
+    let nz_table = normalize_table(table);
+    for statement in search::create_statements(m.get_database_backend(), &nz_table, index)? {
+        m.get_connection().execute_unprepared(&statement).await?;
+    }
+    Ok(())
+}
+
+///
+/// Removes the full-text search index of a table, with its triggers.
+///
+/// ```ignore
+/// remove_search_index(m, "movies").await;
+/// ```
+/// # Errors
+/// fails when it fails, or on MySQL
+pub async fn remove_search_index(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    let nz_table = normalize_table(table);
+    for statement in search::drop_statements(m.get_database_backend(), &nz_table)? {
+        m.get_connection().execute_unprepared(&statement).await?;
+    }
+    Ok(())
+}
+
+///
+/// Creates the `loco_outbox` table of the transactional outbox, see
+/// [`crate::outbox`].
+///
+/// ```ignore
+/// create_outbox_table(m).await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn create_outbox_table(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    m.create_table(crate::outbox::create_table_statement())
+        .await
+}
+
+///
+/// Drops the `loco_outbox` table of the transactional outbox.
+///
+/// ```ignore
+/// drop_outbox_table(m).await;
+/// ```
+/// # Errors
+/// fails when it fails
+pub async fn drop_outbox_table(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    m.drop_table(crate::outbox::drop_table_statement()).await
+}
+
 ///
 /// Adds a reference. Reads "movies belongs-to users":
 /// ```ignore
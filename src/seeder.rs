@@ -0,0 +1,283 @@
+//! # Seeders
+//!
+//! Programmatic seeding of the database, next to the YAML fixtures of
+//! [`crate::app::Hooks::seed`]. A [`Seeder`] inserts its data with the models
+//! of the application, and only runs in the environments it is meant for: the
+//! fixtures of the development environment, or the reference data of the
+//! production one.
+//!
+//! The seeders run in their registration order, and each one runs once per
+//! database: the applied seeders are recorded in the `loco_seeds` table, and
+//! skipped by the next runs unless forced.
+//!
+//! # Example
+//! ```rust,ignore
+//! pub struct Demo;
+//!
+//! #[async_trait]
+//! impl Seeder for Demo {
+//!     fn seeder(&self) -> SeederInfo {
+//!         SeederInfo::new("demo").environments(&[Environment::Development])
+//!     }
+//!
+//!     async fn seed(&self, ctx: &AppContext) -> Result<()> {
+//!         users::ActiveModel {
+//!             name: Set("demo".to_string()),
+//!             ..Default::default()
+//!         }
+//!         .insert(&ctx.db)
+//!         .await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // in your `Hooks` implementation
+//! fn register_seeders(seeders: &mut Seeders) {
+//!     seeders.register(Demo);
+//! }
+//! ```
+use async_trait::async_trait;
+use sea_orm::{
+    sea_query::{Alias, ColumnDef, Expr, Query, Table},
+    ConnectionTrait, DatabaseConnection,
+};
+
+use crate::{app::AppContext, environment::Environment, errors::Error, Result};
+
+/// The table recording the applied seeders.
+const SEEDS_TABLE: &str = "loco_seeds";
+
+/// Information about a seeder, its name and the environments it runs in.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone)]
+pub struct SeederInfo {
+    pub name: String,
+    /// The environments the seeder runs in, all of them when empty
+    pub environments: Vec<Environment>,
+}
+
+impl SeederInfo {
+    /// A seeder running in all the environments.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            environments: vec![],
+        }
+    }
+
+    /// Restricts the seeder to the given environments.
+    #[must_use]
+    pub fn environments(mut self, environments: &[Environment]) -> Self {
+        self.environments = environments.to_vec();
+        self
+    }
+
+    /// Whether the seeder runs in the environment.
+    #[must_use]
+    pub fn runs_in(&self, environment: &Environment) -> bool {
+        self.environments.is_empty() || self.environments.contains(environment)
+    }
+}
+
+/// A trait defining a seeder of the database.
+#[async_trait]
+pub trait Seeder: Send + Sync {
+    /// Get information about the seeder.
+    fn seeder(&self) -> SeederInfo;
+    /// Insert the data of the seeder.
+    async fn seed(&self, ctx: &AppContext) -> Result<()>;
+}
+
+/// Managing and running seeders, in their registration order.
+#[derive(Default)]
+pub struct Seeders {
+    registry: Vec<Box<dyn Seeder>>,
+}
+
+impl Seeders {
+    /// List all registered seeders with their information.
+    #[must_use]
+    pub fn list(&self) -> Vec<SeederInfo> {
+        self.registry.iter().map(|s| s.seeder()).collect::<Vec<_>>()
+    }
+
+    /// List of all seeders names
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        self.registry
+            .iter()
+            .map(|s| s.seeder().name)
+            .collect::<Vec<_>>()
+    }
+
+    /// Register a new seeder, replacing the one with the same name.
+    pub fn register(&mut self, seeder: impl Seeder + 'static) {
+        let name = seeder.seeder().name;
+        match self.registry.iter().position(|s| s.seeder().name == name) {
+            Some(index) => self.registry[index] = Box::new(seeder),
+            None => self.registry.push(Box::new(seeder)),
+        }
+    }
+
+    /// Runs the seeders of the environment of the application, or only the
+    /// one named `name`, skipping the ones already applied unless `force`.
+    /// Returns the names of the seeders which ran.
+    ///
+    /// # Errors
+    ///
+    /// When the named seeder is not registered or does not run in the
+    /// environment, or when a seeder fails.
+    pub async fn run(
+        &self,
+        ctx: &AppContext,
+        name: Option<&str>,
+        force: bool,
+    ) -> Result<Vec<String>> {
+        let seeders = match name {
+            Some(name) => {
+                let seeder = self
+                    .registry
+                    .iter()
+                    .find(|s| s.seeder().name == name)
+                    .ok_or_else(|| Error::Message(format!("the seeder {name} does not exist")))?;
+                if !seeder.seeder().runs_in(&ctx.environment) {
+                    return Err(Error::Message(format!(
+                        "the seeder {name} does not run in the {} environment",
+                        ctx.environment
+                    )));
+                }
+                vec![seeder]
+            }
+            None => self
+                .registry
+                .iter()
+                .filter(|s| s.seeder().runs_in(&ctx.environment))
+                .collect(),
+        };
+
+        create_seeds_table(&ctx.db).await?;
+        let mut ran = vec![];
+        for seeder in seeders {
+            let name = seeder.seeder().name;
+            if !force && is_applied(&ctx.db, &name).await? {
+                tracing::info!(seeder = name, "seeder already applied, skipping");
+                continue;
+            }
+            tracing::info!(seeder = name, "running seeder");
+            seeder.seed(ctx).await?;
+            mark_applied(&ctx.db, &name).await?;
+            ran.push(name);
+        }
+        Ok(ran)
+    }
+}
+
+async fn create_seeds_table(db: &DatabaseConnection) -> Result<()> {
+    let stmt = Table::create()
+        .table(Alias::new(SEEDS_TABLE))
+        .if_not_exists()
+        .col(
+            ColumnDef::new(Alias::new("name"))
+                .string()
+                .not_null()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(Alias::new("applied_at"))
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .to_owned();
+    db.execute(db.get_database_backend().build(&stmt)).await?;
+    Ok(())
+}
+
+async fn is_applied(db: &DatabaseConnection, name: &str) -> Result<bool> {
+    let stmt = Query::select()
+        .column(Alias::new("name"))
+        .from(Alias::new(SEEDS_TABLE))
+        .and_where(Expr::col(Alias::new("name")).eq(name))
+        .to_owned();
+    Ok(db
+        .query_one(db.get_database_backend().build(&stmt))
+        .await?
+        .is_some())
+}
+
+async fn mark_applied(db: &DatabaseConnection, name: &str) -> Result<()> {
+    let delete = Query::delete()
+        .from_table(Alias::new(SEEDS_TABLE))
+        .and_where(Expr::col(Alias::new("name")).eq(name))
+        .to_owned();
+    db.execute(db.get_database_backend().build(&delete)).await?;
+
+    let insert = Query::insert()
+        .into_table(Alias::new(SEEDS_TABLE))
+        .columns([Alias::new("name"), Alias::new("applied_at")])
+        .values_panic([name.into(), chrono::Utc::now().into()])
+        .to_owned();
+    db.execute(db.get_database_backend().build(&insert)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::tests_cfg;
+
+    struct Counting {
+        name: &'static str,
+        environments: Vec<Environment>,
+        runs: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Seeder for Counting {
+        fn seeder(&self) -> SeederInfo {
+            SeederInfo::new(self.name).environments(&self.environments)
+        }
+
+        async fn seed(&self, _ctx: &AppContext) -> Result<()> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn can_run_seeders_once_per_environment() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut seeders = Seeders::default();
+        for (name, environments) in [
+            ("reference", vec![]),
+            ("demo", vec![Environment::Development]),
+            ("fixtures", vec![Environment::Test]),
+        ] {
+            seeders.register(Counting {
+                name,
+                environments,
+                runs: runs.clone(),
+            });
+        }
+
+        assert_eq!(
+            seeders.run(&ctx, None, false).await.unwrap(),
+            vec!["reference", "fixtures"]
+        );
+        assert!(seeders.run(&ctx, None, false).await.unwrap().is_empty());
+        assert_eq!(
+            seeders.run(&ctx, Some("fixtures"), true).await.unwrap(),
+            vec!["fixtures"]
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        assert!(seeders.run(&ctx, Some("demo"), false).await.is_err());
+        assert!(seeders.run(&ctx, Some("missing"), false).await.is_err());
+    }
+}
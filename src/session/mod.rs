@@ -0,0 +1,288 @@
+//! # Session Module
+//!
+//! Server-side sessions for server rendered apps. The session data lives in
+//! a [`SessionStore`] and the browser only holds a signed (or encrypted)
+//! cookie with the session ID.
+//!
+//! Sessions are loaded and saved by the `session` middleware, which has to
+//! be enabled in the configuration:
+//!
+//! ```yaml
+//! server:
+//!   middlewares:
+//!     session:
+//!       enable: true
+//!       secret: "a long random secret, at least 32 characters"
+//!       store: cache
+//! ```
+//!
+//! Handlers then read and write values with the [`Session`] extractor.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::session::Session;
+//!
+//! async fn visit(session: Session) -> Result<Response> {
+//!     let visits = session.get::<u64>("visits")?.unwrap_or_default() + 1;
+//!     session.insert("visits", visits)?;
+//!     format::text(&format!("visits: {visits}"))
+//! }
+//!
+//! async fn logout(session: Session) -> Result<Response> {
+//!     session.destroy();
+//!     session.flash("info", "You were logged out");
+//!     format::redirect("/")
+//! }
+//! ```
+pub mod store;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+pub use self::store::SessionStore;
+use crate::{Error, Result};
+
+/// The key holding the flash messages in the session data
+const FLASH_KEY: &str = "_flash";
+
+/// The values stored in a session.
+pub type SessionData = HashMap<String, Value>;
+
+/// A one-time message stored in the session, typically rendered on the next
+/// page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Flash {
+    /// The message level, for example `info` or `error`
+    pub level: String,
+    pub message: String,
+}
+
+/// What the middleware has to do with the session once the request was
+/// handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    Unchanged,
+    Changed,
+    Renewed,
+    Destroyed,
+}
+
+#[derive(Debug)]
+pub(crate) struct State {
+    pub(crate) id: Option<String>,
+    pub(crate) data: SessionData,
+    pub(crate) status: Status,
+}
+
+/// The session of the current request.
+///
+/// Changes are saved to the store by the `session` middleware once the
+/// response was produced.
+#[derive(Debug, Clone)]
+pub struct Session(Arc<Mutex<State>>);
+
+impl Session {
+    pub(crate) fn new(id: Option<String>, data: SessionData) -> Self {
+        Self(Arc::new(Mutex::new(State {
+            id,
+            data,
+            status: Status::Unchanged,
+        })))
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn change(state: &mut State) {
+        state.status = match state.status {
+            Status::Unchanged | Status::Changed => Status::Changed,
+            // a destroyed session is replaced with a new one
+            Status::Renewed | Status::Destroyed => Status::Renewed,
+        };
+    }
+
+    /// Returns the session ID, `None` for a session not saved yet.
+    #[must_use]
+    pub fn id(&self) -> Option<String> {
+        self.state().id.clone()
+    }
+
+    /// Gets a value from the session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the value could not be deserialized into `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        self.state()
+            .data
+            .get(key)
+            .map(|value| serde_json::from_value(value.clone()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Inserts a value into the session, replacing the previous one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the value could not be serialized.
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) -> Result<()> {
+        let value = serde_json::to_value(value)?;
+        let mut state = self.state();
+        state.data.insert(key.to_string(), value);
+        Self::change(&mut state);
+        Ok(())
+    }
+
+    /// Removes a value from the session, returning it.
+    pub fn remove(&self, key: &str) -> Option<Value> {
+        let mut state = self.state();
+        let value = state.data.remove(key);
+        if value.is_some() {
+            Self::change(&mut state);
+        }
+        value
+    }
+
+    /// Removes all values from the session, keeping its ID.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        Self::change(&mut state);
+    }
+
+    /// Issues a new session ID, keeping the values. Call it after a login to
+    /// prevent session fixation.
+    pub fn renew(&self) {
+        self.state().status = Status::Renewed;
+    }
+
+    /// Deletes the session from the store and removes the cookie. Values
+    /// inserted afterwards are saved in a new session.
+    pub fn destroy(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        state.status = Status::Destroyed;
+    }
+
+    /// Adds a flash message, kept in the session until read with
+    /// [`Session::take_flashes`].
+    pub fn flash(&self, level: &str, message: &str) {
+        let mut state = self.state();
+        let mut flashes = Self::flashes(&state.data);
+        flashes.push(Flash {
+            level: level.to_string(),
+            message: message.to_string(),
+        });
+        state.data.insert(
+            FLASH_KEY.to_string(),
+            serde_json::to_value(flashes).unwrap_or_default(),
+        );
+        Self::change(&mut state);
+    }
+
+    /// Reads and removes the flash messages.
+    #[must_use]
+    pub fn take_flashes(&self) -> Vec<Flash> {
+        let mut state = self.state();
+        let flashes = Self::flashes(&state.data);
+        if state.data.remove(FLASH_KEY).is_some() {
+            Self::change(&mut state);
+        }
+        flashes
+    }
+
+    fn flashes(data: &SessionData) -> Vec<Flash> {
+        data.get(FLASH_KEY)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Takes the session state for the middleware to persist it.
+    pub(crate) fn take_state(&self) -> State {
+        let mut state = self.state();
+        State {
+            id: state.id.clone(),
+            data: std::mem::take(&mut state.data),
+            status: state.status,
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        parts.extensions.get::<Self>().cloned().ok_or_else(|| {
+            tracing::error!("the session middleware is not enabled");
+            Error::InternalServerError
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_insert_and_get() {
+        let session = Session::new(None, SessionData::new());
+        assert_eq!(session.get::<String>("user").unwrap(), None);
+
+        session.insert("user", "loco").unwrap();
+        assert_eq!(
+            session.get::<String>("user").unwrap(),
+            Some("loco".to_string())
+        );
+        assert_eq!(session.take_state().status, Status::Changed);
+    }
+
+    #[test]
+    fn reading_does_not_change_session() {
+        let mut data = SessionData::new();
+        data.insert("user".to_string(), Value::from("loco"));
+        let session = Session::new(Some("id".to_string()), data);
+
+        assert!(session.get::<String>("user").unwrap().is_some());
+        assert!(session.remove("missing").is_none());
+        assert_eq!(session.take_state().status, Status::Unchanged);
+    }
+
+    #[test]
+    fn can_take_flashes() {
+        let session = Session::new(None, SessionData::new());
+        session.flash("info", "saved");
+        session.flash("error", "but not everything");
+
+        let flashes = session.take_flashes();
+        assert_eq!(flashes.len(), 2);
+        assert_eq!(flashes[0].level, "info");
+        assert_eq!(flashes[1].message, "but not everything");
+        assert!(session.take_flashes().is_empty());
+    }
+
+    #[test]
+    fn can_destroy_and_reuse() {
+        let session = Session::new(Some("id".to_string()), SessionData::new());
+        session.insert("user", "loco").unwrap();
+        session.destroy();
+        assert_eq!(session.get::<String>("user").unwrap(), None);
+        assert_eq!(session.take_state().status, Status::Destroyed);
+
+        session.flash("info", "logged out");
+        assert_eq!(session.take_state().status, Status::Renewed);
+    }
+}
@@ -0,0 +1,264 @@
+//! # Session Stores
+//!
+//! Where the session data is kept between requests:
+//! * [`MemoryStore`]: in the process memory, lost on restart. Intended for
+//!   development and tests.
+//! * [`CacheStore`]: in the application [`Cache`], use a persistent cache
+//!   such as Redis in production.
+//! * [`DbStore`]: in a database table (requires the `with-db` feature).
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use super::SessionData;
+use crate::{cache::Cache, Result};
+
+/// Storage for session data, keyed by session ID.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Loads the data of a session, `None` when the session does not exist
+    /// or expired.
+    async fn load(&self, id: &str) -> Result<Option<SessionData>>;
+
+    /// Saves (or replaces) the data of a session, expiring after `ttl`.
+    async fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Result<()>;
+
+    /// Deletes a session.
+    async fn destroy(&self, id: &str) -> Result<()>;
+}
+
+/// The kind of [`SessionStore`] used by the `session` middleware.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreKind {
+    /// [`MemoryStore`]
+    #[default]
+    Memory,
+    /// [`CacheStore`]
+    Cache,
+    /// [`DbStore`]
+    #[cfg(feature = "with-db")]
+    Db,
+}
+
+/// A [`SessionStore`] keeping sessions in the process memory.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    sessions: DashMap<String, (SessionData, Instant)>,
+}
+
+impl MemoryStore {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn load(&self, id: &str) -> Result<Option<SessionData>> {
+        let now = Instant::now();
+        self.sessions
+            .remove_if(id, |_, (_, expires_at)| *expires_at <= now);
+        Ok(self.sessions.get(id).map(|entry| entry.0.clone()))
+    }
+
+    async fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Result<()> {
+        let now = Instant::now();
+        self.sessions.retain(|_, (_, expires_at)| *expires_at > now);
+        self.sessions
+            .insert(id.to_string(), (data.clone(), now + ttl));
+        Ok(())
+    }
+
+    async fn destroy(&self, id: &str) -> Result<()> {
+        self.sessions.remove(id);
+        Ok(())
+    }
+}
+
+/// A [`SessionStore`] keeping sessions in the application [`Cache`].
+pub struct CacheStore {
+    cache: Arc<Cache>,
+}
+
+impl CacheStore {
+    #[must_use]
+    pub fn new(cache: Arc<Cache>) -> Self {
+        Self { cache }
+    }
+}
+
+#[async_trait]
+impl SessionStore for CacheStore {
+    async fn load(&self, id: &str) -> Result<Option<SessionData>> {
+        Ok(self.cache.get(&format!("session:{id}")).await?)
+    }
+
+    async fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Result<()> {
+        self.cache
+            .insert_with_expiry(&format!("session:{id}"), data, ttl)
+            .await?;
+        Ok(())
+    }
+
+    async fn destroy(&self, id: &str) -> Result<()> {
+        self.cache.remove(&format!("session:{id}")).await?;
+        Ok(())
+    }
+}
+
+/// A [`SessionStore`] keeping sessions in the `sessions` database table,
+/// which has to be created by a migration:
+///
+/// ```rust,ignore
+/// manager
+///     .create_table(
+///         Table::create()
+///             .table(Alias::new("sessions"))
+///             .col(string(Alias::new("id")).primary_key())
+///             .col(text(Alias::new("data")))
+///             .col(big_integer(Alias::new("expires_at")))
+///             .to_owned(),
+///     )
+///     .await?;
+/// ```
+///
+/// Expired rows are ignored but not deleted, use [`DbStore::delete_expired`]
+/// from a scheduled task to clean them up.
+#[cfg(feature = "with-db")]
+pub struct DbStore {
+    db: sea_orm::DatabaseConnection,
+}
+
+#[cfg(feature = "with-db")]
+mod db {
+    use sea_orm::{
+        sea_query::{Alias, Expr, OnConflict, Query},
+        ConnectionTrait, DatabaseConnection,
+    };
+
+    use super::{async_trait, DbStore, Duration, SessionData, SessionStore};
+    use crate::Result;
+
+    const TABLE: &str = "sessions";
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+
+    impl DbStore {
+        #[must_use]
+        pub fn new(db: DatabaseConnection) -> Self {
+            Self { db }
+        }
+
+        /// Deletes the expired sessions.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error on database failures.
+        pub async fn delete_expired(&self) -> Result<()> {
+            let stmt = Query::delete()
+                .from_table(Alias::new(TABLE))
+                .and_where(Expr::col(Alias::new("expires_at")).lte(now()))
+                .to_owned();
+            let backend = self.db.get_database_backend();
+            self.db.execute(backend.build(&stmt)).await?;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl SessionStore for DbStore {
+        async fn load(&self, id: &str) -> Result<Option<SessionData>> {
+            let stmt = Query::select()
+                .column(Alias::new("data"))
+                .from(Alias::new(TABLE))
+                .and_where(Expr::col(Alias::new("id")).eq(id))
+                .and_where(Expr::col(Alias::new("expires_at")).gt(now()))
+                .to_owned();
+            let backend = self.db.get_database_backend();
+            let Some(row) = self.db.query_one(backend.build(&stmt)).await? else {
+                return Ok(None);
+            };
+            let data: String = row.try_get("", "data")?;
+            Ok(Some(serde_json::from_str(&data)?))
+        }
+
+        async fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Result<()> {
+            let ttl = i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX);
+            let expires_at = now().saturating_add(ttl);
+            let stmt = Query::insert()
+                .into_table(Alias::new(TABLE))
+                .columns([
+                    Alias::new("id"),
+                    Alias::new("data"),
+                    Alias::new("expires_at"),
+                ])
+                .values_panic([
+                    id.into(),
+                    serde_json::to_string(data)?.into(),
+                    expires_at.into(),
+                ])
+                .on_conflict(
+                    OnConflict::column(Alias::new("id"))
+                        .update_columns([Alias::new("data"), Alias::new("expires_at")])
+                        .to_owned(),
+                )
+                .to_owned();
+            let backend = self.db.get_database_backend();
+            self.db.execute(backend.build(&stmt)).await?;
+            Ok(())
+        }
+
+        async fn destroy(&self, id: &str) -> Result<()> {
+            let stmt = Query::delete()
+                .from_table(Alias::new(TABLE))
+                .and_where(Expr::col(Alias::new("id")).eq(id))
+                .to_owned();
+            let backend = self.db.get_database_backend();
+            self.db.execute(backend.build(&stmt)).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_store_can_save_and_load() {
+        let store = MemoryStore::new();
+        let mut data = SessionData::new();
+        data.insert("user".to_string(), Value::from("loco"));
+
+        store
+            .save("id", &data, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(store.load("id").await.unwrap(), Some(data));
+
+        store.destroy("id").await.unwrap();
+        assert_eq!(store.load("id").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn memory_store_expires_sessions() {
+        let store = MemoryStore::new();
+
+        store
+            .save("id", &SessionData::new(), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(store.load("id").await.unwrap(), None);
+    }
+}
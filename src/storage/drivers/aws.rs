@@ -1,7 +1,7 @@
 use opendal::{services::S3, Operator};
 
 use super::{opendal_adapter::OpendalAdapter, StoreDriver};
-use crate::storage::StorageResult;
+use crate::{config::S3StorageConfig, storage::StorageResult};
 
 /// A set of AWS security credentials
 #[derive(Debug)]
@@ -102,6 +102,26 @@ pub fn with_credentials(
     Ok(Box::new(OpendalAdapter::new(Operator::new(s3)?.finish())))
 }
 
+/// Create new AWS s3 storage from the `storage` configuration, with the
+/// credentials of the environment, such as `AWS_ACCESS_KEY_ID` and
+/// `AWS_SECRET_ACCESS_KEY`.
+///
+/// # Errors
+///
+/// When could not initialize the client instance
+pub fn from_config(config: &S3StorageConfig) -> StorageResult<Box<dyn StoreDriver>> {
+    let mut s3 = S3::default().bucket(&config.bucket).region(&config.region);
+    if let Some(endpoint) = &config.endpoint {
+        s3 = s3.endpoint(endpoint);
+    }
+    if let Some(root) = &config.root {
+        s3 = s3.root(root);
+    }
+    Ok(Box::new(
+        OpendalAdapter::new(Operator::new(s3)?.finish()).with_multipart(&config.multipart),
+    ))
+}
+
 /// Build store with failure
 ///
 /// # Panics
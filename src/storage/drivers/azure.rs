@@ -1,7 +1,10 @@
 use opendal::{services::Azblob, Operator};
 
 use super::StoreDriver;
-use crate::storage::{drivers::opendal_adapter::OpendalAdapter, StorageResult};
+use crate::{
+    config::AzureStorageConfig,
+    storage::{drivers::opendal_adapter::OpendalAdapter, StorageError, StorageResult},
+};
 
 /// Create new Azure storage.
 ///
@@ -30,3 +33,54 @@ pub fn new(
         Operator::new(azure)?.finish(),
     )))
 }
+
+/// Create new Azure storage from the `storage` configuration, reading the
+/// missing credentials from `AZURE_STORAGE_ACCOUNT_NAME`,
+/// `AZURE_STORAGE_ACCOUNT_KEY` and `AZURE_STORAGE_SAS_TOKEN`.
+///
+/// # Errors
+///
+/// When the account name is missing, or could not initialize the client
+/// instance
+pub fn from_config(config: &AzureStorageConfig) -> StorageResult<Box<dyn StoreDriver>> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let account_name = config
+        .account_name
+        .clone()
+        .or_else(|| env("AZURE_STORAGE_ACCOUNT_NAME"))
+        .ok_or_else(|| {
+            StorageError::Any(
+                "azure storage requires `account_name` or AZURE_STORAGE_ACCOUNT_NAME".into(),
+            )
+        })?;
+    let endpoint = config
+        .endpoint
+        .clone()
+        .unwrap_or_else(|| format!("https://{account_name}.blob.core.windows.net"));
+
+    let mut azure = Azblob::default()
+        .container(&config.container)
+        .account_name(&account_name)
+        .endpoint(&endpoint);
+    if let Some(account_key) = config
+        .account_key
+        .clone()
+        .or_else(|| env("AZURE_STORAGE_ACCOUNT_KEY"))
+    {
+        azure = azure.account_key(&account_key);
+    }
+    if let Some(sas_token) = config
+        .sas_token
+        .clone()
+        .or_else(|| env("AZURE_STORAGE_SAS_TOKEN"))
+    {
+        azure = azure.sas_token(&sas_token);
+    }
+    if let Some(root) = &config.root {
+        azure = azure.root(root);
+    }
+
+    Ok(Box::new(
+        OpendalAdapter::new(Operator::new(azure)?.finish()).with_multipart(&config.multipart),
+    ))
+}
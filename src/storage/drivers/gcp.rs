@@ -1,7 +1,10 @@
 use opendal::{services::Gcs, Operator};
 
 use super::StoreDriver;
-use crate::storage::{drivers::opendal_adapter::OpendalAdapter, StorageResult};
+use crate::{
+    config::GcsStorageConfig,
+    storage::{drivers::opendal_adapter::OpendalAdapter, StorageResult},
+};
 
 /// Create new GCP storage.
 ///
@@ -21,3 +24,25 @@ pub fn new(bucket_name: &str, credential_path: &str) -> StorageResult<Box<dyn St
 
     Ok(Box::new(OpendalAdapter::new(Operator::new(gcs)?.finish())))
 }
+
+/// Create new GCP storage from the `storage` configuration. Without a
+/// `credential_path`, the credentials are loaded from
+/// `GOOGLE_APPLICATION_CREDENTIALS`, then from the service account of the
+/// machine.
+///
+/// # Errors
+///
+/// When could not initialize the client instance
+pub fn from_config(config: &GcsStorageConfig) -> StorageResult<Box<dyn StoreDriver>> {
+    let mut gcs = Gcs::default().bucket(&config.bucket);
+    if let Some(credential_path) = &config.credential_path {
+        gcs = gcs.credential_path(&credential_path.display().to_string());
+    }
+    if let Some(root) = &config.root {
+        gcs = gcs.root(root);
+    }
+
+    Ok(Box::new(
+        OpendalAdapter::new(Operator::new(gcs)?.finish()).with_multipart(&config.multipart),
+    ))
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -15,7 +15,11 @@ pub mod mem;
 pub mod null;
 pub mod opendal_adapter;
 
-use super::{stream::BytesStream, StorageResult};
+use super::{
+    presign::{PresignOperation, PresignedRequest},
+    stream::BytesStream,
+    StorageResult,
+};
 
 #[derive(Debug)]
 pub struct UploadResponse {
@@ -149,4 +153,26 @@ pub trait StoreDriver: Sync + Send {
             .map_err(|e| super::StorageError::Any(Box::new(e)))?;
         self.upload(path, &bytes).await
     }
+
+    /// Presigns a request, letting a client download or upload the content at
+    /// the specified path directly from the store until the expiry.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation returns
+    /// [`StorageError::PresignNotSupported`](super::StorageError::PresignNotSupported),
+    /// for the [`Storage`](super::Storage) to fall back to its
+    /// [`UrlSigner`](super::presign::UrlSigner).
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the presigned request.
+    async fn presign(
+        &self,
+        _operation: PresignOperation,
+        _path: &Path,
+        _expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        Err(super::StorageError::PresignNotSupported)
+    }
 }
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -6,10 +6,18 @@ use futures_util::{SinkExt, StreamExt};
 use opendal::{layers::RetryLayer, Operator};
 
 use super::{GetResponse, StoreDriver, UploadResponse};
-use crate::storage::{stream::BytesStream, StorageError, StorageResult};
+use crate::{
+    config::MultipartConfig,
+    storage::{
+        presign::{PresignOperation, PresignedRequest},
+        stream::BytesStream,
+        StorageError, StorageResult,
+    },
+};
 
 pub struct OpendalAdapter {
     opendal_impl: Operator,
+    multipart: MultipartConfig,
 }
 
 impl OpendalAdapter {
@@ -19,7 +27,28 @@ impl OpendalAdapter {
         let opendal_impl = opendal_impl
             // Add retry layer with default settings
             .layer(RetryLayer::default().with_jitter());
-        Self { opendal_impl }
+        Self {
+            opendal_impl,
+            multipart: MultipartConfig::default(),
+        }
+    }
+
+    /// Sets the size and the concurrency of the parts of the streaming
+    /// uploads and downloads, for the stores writing in multiple parts.
+    #[must_use]
+    pub fn with_multipart(mut self, multipart: &MultipartConfig) -> Self {
+        self.multipart = multipart.clone();
+        self
+    }
+
+    /// Whether the store writes in multiple parts, such as the S3 multipart
+    /// uploads.
+    fn is_multipart(&self) -> bool {
+        self.opendal_impl.info().full_capability().write_can_multi
+    }
+
+    fn chunk_size(&self) -> usize {
+        usize::try_from(self.multipart.chunk_size).unwrap_or(usize::MAX)
     }
 }
 
@@ -143,17 +172,28 @@ impl StoreDriver for OpendalAdapter {
     }
 
     /// Native streaming implementation for `OpenDAL`.
-    /// This directly uses `OpenDAL`'s reader for efficient streaming.
+    /// This directly uses `OpenDAL`'s reader for efficient streaming, reading
+    /// the parts of the multipart stores concurrently with range requests.
     async fn get_stream(&self, path: &Path) -> StorageResult<BytesStream> {
-        let reader = self
-            .opendal_impl
-            .reader(&path.display().to_string())
-            .await?;
+        let path = path.display().to_string();
+        let reader = if self.is_multipart() {
+            self.opendal_impl
+                .reader_with(&path)
+                .chunk(self.chunk_size())
+                .concurrent(self.multipart.concurrency)
+                .await?
+        } else {
+            self.opendal_impl.reader(&path).await?
+        };
         BytesStream::from_reader(reader).await
     }
 
     /// Native streaming upload for `OpenDAL`.
-    /// This uses `OpenDAL`'s writer to stream data directly without buffering.
+    /// This uses `OpenDAL`'s writer to stream data directly without buffering
+    /// the whole content. The multipart stores upload it in parts of the
+    /// configured chunk size, a failed part being retried on its own by the
+    /// retry layer, so that the upload resumes where it failed. An upload
+    /// which fails for good is aborted, not to leave its parts behind.
     async fn upload_stream(
         &self,
         path: &Path,
@@ -162,21 +202,66 @@ impl StoreDriver for OpendalAdapter {
         let path_str = path.display().to_string();
 
         // Create writer with OpenDAL's native API
-        let mut writer = self.opendal_impl.writer(&path_str).await?;
+        let mut writer = if self.is_multipart() {
+            self.opendal_impl
+                .writer_with(&path_str)
+                .chunk(self.chunk_size())
+                .concurrent(self.multipart.concurrency)
+                .await?
+        } else {
+            self.opendal_impl.writer(&path_str).await?
+        };
 
         // Stream data directly to the writer using native write method
         let mut stream = Box::pin(stream);
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk.map_err(|e| StorageError::Any(Box::new(e)))?;
-            // Use the native write method which handles the data more efficiently
-            writer.write(chunk).await?;
+        let written = async {
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| StorageError::Any(Box::new(e)))?;
+                // Use the native write method which handles the data more efficiently
+                writer.write(chunk).await?;
+            }
+            Ok::<_, StorageError>(writer.close().await?)
         }
+        .await;
 
-        let meta = writer.close().await?;
+        let meta = match written {
+            Ok(meta) => meta,
+            Err(err) => {
+                if let Err(abort_err) = writer.abort().await {
+                    tracing::warn!(
+                        err = %abort_err,
+                        path = path_str,
+                        "could not abort the failed upload"
+                    );
+                }
+                return Err(err);
+            }
+        };
 
         Ok(UploadResponse {
             e_tag: meta.etag().map(std::string::ToString::to_string),
             version: meta.version().map(std::string::ToString::to_string),
         })
     }
+
+    /// Presigns a request with the store, when it supports it.
+    async fn presign(
+        &self,
+        operation: PresignOperation,
+        path: &Path,
+        expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        let capability = self.opendal_impl.info().full_capability();
+        let path = path.display().to_string();
+        let request = match operation {
+            PresignOperation::Get if capability.presign_read => {
+                self.opendal_impl.presign_read(&path, expiry).await?
+            }
+            PresignOperation::Put if capability.presign_write => {
+                self.opendal_impl.presign_write(&path, expiry).await?
+            }
+            _ => return Err(StorageError::PresignNotSupported),
+        };
+        Ok(PresignedRequest::from_opendal(&request, expiry))
+    }
 }
@@ -12,16 +12,24 @@
 //! The selected strategy can be dynamically changed at runtime.
 mod contents;
 pub mod drivers;
+pub mod presign;
 pub mod strategies;
 pub mod stream;
 use std::{
     collections::BTreeMap,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use bytes::Bytes;
+use futures_util::Stream;
 
-use self::{drivers::StoreDriver, stream::BytesStream};
+use self::{
+    drivers::StoreDriver,
+    presign::{PresignOperation, PresignedRequest, UrlSigner},
+    stream::BytesStream,
+};
+use crate::config::StorageConfig;
 
 #[derive(thiserror::Error, Debug)]
 #[allow(clippy::module_name_repetitions)]
@@ -35,6 +43,9 @@ pub enum StorageError {
     #[error("Unable to read data from file {}", path.display().to_string())]
     UnableToReadBytes { path: PathBuf },
 
+    #[error("presigned urls are not supported by the store")]
+    PresignNotSupported,
+
     #[error("secondaries errors")]
     Multi(BTreeMap<String, String>),
 
@@ -50,9 +61,23 @@ impl From<opendal::Error> for StorageError {
     }
 }
 
+/// The message of a storage `kind` whose feature is not enabled.
+#[allow(dead_code)]
+fn missing_feature(config: &StorageConfig) -> String {
+    let feature = match config {
+        StorageConfig::S3(_) => "storage_aws_s3",
+        StorageConfig::Azure(_) => "storage_azure",
+        StorageConfig::Gcs(_) => "storage_gcp",
+        StorageConfig::Null | StorageConfig::Mem | StorageConfig::Local(_) => "",
+    };
+    format!("the configured storage kind requires the `{feature}` feature")
+}
+
 pub struct Storage {
     pub stores: BTreeMap<String, Box<dyn StoreDriver>>,
     pub strategy: Box<dyn strategies::StorageStrategy>,
+    /// Signs the app URLs of the presigned requests the stores can not sign
+    pub url_signer: Option<UrlSigner>,
 }
 
 impl Storage {
@@ -71,16 +96,123 @@ impl Storage {
         Self {
             strategy: Box::new(strategies::single::SingleStrategy::new(default_key)),
             stores: BTreeMap::from([(default_key.to_string(), store)]),
+            url_signer: None,
         }
     }
 
+    /// Creates a new storage instance with the single driver of the
+    /// `storage` configuration.
+    ///
+    /// # Errors
+    ///
+    /// When the driver could not be initialized, or its feature is not
+    /// enabled
+    pub fn from_config(config: &StorageConfig) -> StorageResult<Self> {
+        let driver = match config {
+            StorageConfig::Null => drivers::null::new(),
+            StorageConfig::Mem => drivers::mem::new(),
+            StorageConfig::Local(config) => {
+                let storage = Self::single(drivers::local::new_with_prefix(&config.path)?);
+                return Ok(match &config.presign {
+                    Some(presign) => {
+                        storage.with_url_signer(UrlSigner::new(&presign.secret, &presign.base_url))
+                    }
+                    None => storage,
+                });
+            }
+            #[cfg(feature = "storage_aws_s3")]
+            StorageConfig::S3(config) => drivers::aws::from_config(config)?,
+            #[cfg(feature = "storage_azure")]
+            StorageConfig::Azure(config) => drivers::azure::from_config(config)?,
+            #[cfg(feature = "storage_gcp")]
+            StorageConfig::Gcs(config) => drivers::gcp::from_config(config)?,
+            #[allow(unreachable_patterns)]
+            _ => return Err(StorageError::Any(missing_feature(config).into())),
+        };
+        Ok(Self::single(driver))
+    }
+
     /// Creates a new storage instance with the provided stores and strategy.
     #[must_use]
     pub fn new(
         stores: BTreeMap<String, Box<dyn StoreDriver>>,
         strategy: Box<dyn strategies::StorageStrategy>,
     ) -> Self {
-        Self { stores, strategy }
+        Self {
+            stores,
+            strategy,
+            url_signer: None,
+        }
+    }
+
+    /// Sets the signer of the app URLs the presigned requests fall back to,
+    /// when the store can not presign them, served by
+    /// [`crate::controller::storage::routes`].
+    #[must_use]
+    pub fn with_url_signer(mut self, url_signer: UrlSigner) -> Self {
+        self.url_signer = Some(url_signer);
+        self
+    }
+
+    /// Presigns a download of the content at the specified path, valid until
+    /// the expiry.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage::{self, presign::UrlSigner};
+    /// use std::{path::Path, time::Duration};
+    /// pub async fn presign_get() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new())
+    ///         .with_url_signer(UrlSigner::new("secret", "https://example.com"));
+    ///     let request = storage
+    ///         .presign_get(Path::new("example.txt"), Duration::from_secs(300))
+    ///         .await;
+    ///     assert!(request.is_ok());
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// When the store could not presign the request, or can not presign
+    /// requests and there is no URL signer
+    pub async fn presign_get(
+        &self,
+        path: &Path,
+        expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        self.presign(PresignOperation::Get, path, expiry).await
+    }
+
+    /// Presigns an upload of the content to the specified path, valid until
+    /// the expiry. With the mirror and backup strategies, the upload only
+    /// reaches the primary store.
+    ///
+    /// # Errors
+    ///
+    /// When the store could not presign the request, or can not presign
+    /// requests and there is no URL signer
+    pub async fn presign_put(
+        &self,
+        path: &Path,
+        expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        self.presign(PresignOperation::Put, path, expiry).await
+    }
+
+    async fn presign(
+        &self,
+        operation: PresignOperation,
+        path: &Path,
+        expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        match self.strategy.presign(self, operation, path, expiry).await {
+            Err(StorageError::PresignNotSupported) => self
+                .url_signer
+                .as_ref()
+                .map(|signer| signer.sign(operation, path, expiry))
+                .ok_or(StorageError::PresignNotSupported),
+            result => result,
+        }
     }
 
     /// Uploads content to the storage at the specified path.
@@ -416,6 +548,9 @@ impl Storage {
 
     /// Uploads content from a stream to storage, enabling efficient
     /// handling of large files without loading them entirely into memory.
+    /// The cloud stores upload it in parts, see
+    /// [`crate::config::MultipartConfig`], while the mirror and backup
+    /// strategies buffer it to upload it to each of their stores.
     ///
     /// This method uses the selected strategy for the upload operation.
     ///
@@ -435,8 +570,12 @@ impl Storage {
     ///
     /// This method returns an error if the upload operation fails or if there
     /// is an issue with the strategy configuration.
-    pub async fn upload_stream(&self, path: &Path, stream: BytesStream) -> StorageResult<()> {
-        self.upload_stream_with_policy(path, stream, &*self.strategy)
+    pub async fn upload_stream<S, E>(&self, path: &Path, stream: S) -> StorageResult<()>
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.upload_stream_with_policy(path, BytesStream::from_stream(stream), &*self.strategy)
             .await
     }
 
@@ -455,3 +594,97 @@ impl Storage {
         strategy.upload_stream(self, path, stream).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn can_create_from_config() {
+        let config: StorageConfig = serde_json::from_value(serde_json::json!({
+            "kind": "Mem",
+        }))
+        .unwrap();
+        let storage = Storage::from_config(&config).unwrap();
+        let path = PathBuf::from("users").join("1.txt");
+        storage
+            .upload(path.as_path(), &Bytes::from("file content"))
+            .await
+            .unwrap();
+        let content: String = storage.download(path.as_path()).await.unwrap();
+        assert_eq!(content, "file content");
+
+        let tree = tree_fs::TreeBuilder::default().create().unwrap();
+        let config: StorageConfig = serde_json::from_value(serde_json::json!({
+            "kind": "Local",
+            "path": tree.root,
+        }))
+        .unwrap();
+        let storage = Storage::from_config(&config).unwrap();
+        storage
+            .upload(path.as_path(), &Bytes::from("file content"))
+            .await
+            .unwrap();
+        assert!(tree.root.join("users").join("1.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn can_upload_streams() {
+        let storage = Storage::single(drivers::mem::new());
+        let path = Path::new("users/large.bin");
+        let chunks = (0..4).map(|i| Ok::<_, std::convert::Infallible>(Bytes::from(vec![i; 1024])));
+        storage
+            .upload_stream(path, futures_util::stream::iter(chunks))
+            .await
+            .unwrap();
+        let content: Vec<u8> = storage.download(path).await.unwrap();
+        assert_eq!(content.len(), 4 * 1024);
+        assert_eq!(content[3 * 1024], 3);
+
+        let failing = futures_util::stream::iter(vec![
+            Ok(Bytes::from("partial")),
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "connection reset",
+            )),
+        ]);
+        let path = Path::new("users/failed.bin");
+        assert!(storage.upload_stream(path, failing).await.is_err());
+        assert!(!storage
+            .as_store("store")
+            .unwrap()
+            .exists(path)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_fall_back_to_the_url_signer() {
+        let path = Path::new("users/1.txt");
+        let expiry = Duration::from_secs(60);
+        let storage = Storage::single(drivers::mem::new());
+        assert!(matches!(
+            storage.presign_get(path, expiry).await,
+            Err(StorageError::PresignNotSupported)
+        ));
+
+        let storage = storage.with_url_signer(UrlSigner::new("secret", "https://example.com"));
+        let request = storage.presign_put(path, expiry).await.unwrap();
+        assert_eq!(request.method, "PUT");
+        assert!(request
+            .url
+            .starts_with("https://example.com/_storage/files/users/1.txt?expires="));
+    }
+
+    #[cfg(not(feature = "storage_azure"))]
+    #[test]
+    fn requires_the_storage_feature() {
+        let config: StorageConfig = serde_json::from_value(serde_json::json!({
+            "kind": "Azure",
+            "container": "uploads",
+        }))
+        .unwrap();
+        let err = Storage::from_config(&config).err().unwrap();
+        assert!(err.to_string().contains("storage_azure"));
+    }
+}
@@ -0,0 +1,234 @@
+//! # Presigned URLs
+//!
+//! The presigned URLs let the clients upload or download a file directly
+//! from the store, without proxying its bytes through the app server. The
+//! cloud stores (S3, Azure Blob Storage and GCS) sign them themselves.
+//!
+//! The other stores, such as the local filesystem, fall back to the
+//! [`UrlSigner`] of the storage: it issues signed, expiring URLs of the app
+//! routes of [`crate::controller::storage::routes`], which check the
+//! signature before reading or writing the file through the storage.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path, time::Duration};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::hash;
+
+/// The path the routes of the [`UrlSigner`] URLs are mounted at.
+pub const PATH: &str = "/_storage/files";
+
+/// The operation a presigned URL allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresignOperation {
+    /// Downloading the file, with a `GET`
+    Get,
+    /// Uploading the file, with a `PUT`
+    Put,
+}
+
+impl PresignOperation {
+    /// The HTTP method of the operation.
+    #[must_use]
+    pub const fn method(self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Put => "PUT",
+        }
+    }
+}
+
+/// A presigned request, handed to the client to perform it as is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PresignedRequest {
+    /// The HTTP method of the request
+    pub method: String,
+    pub url: String,
+    /// The headers the request must be sent with
+    pub headers: BTreeMap<String, String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl PresignedRequest {
+    pub(crate) fn from_opendal(request: &opendal::raw::PresignedRequest, expiry: Duration) -> Self {
+        Self {
+            method: request.method().to_string(),
+            url: request.uri().to_string(),
+            headers: request
+                .header()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            expires_at: expires_at(expiry),
+        }
+    }
+}
+
+fn expires_at(expiry: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(expiry)
+        .ok()
+        .and_then(|expiry| Utc::now().checked_add_signed(expiry))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// Signs the URLs of the app routes serving the files of the stores which
+/// can not presign them.
+#[derive(Clone)]
+pub struct UrlSigner {
+    secret: Vec<u8>,
+    base_url: String,
+}
+
+impl std::fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UrlSigner")
+            .field("base_url", &self.base_url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl UrlSigner {
+    /// Creates a signer, its URLs starting with the `base_url` of the app,
+    /// such as `https://example.com`, or relative to it when empty.
+    #[must_use]
+    pub fn new(secret: impl AsRef<[u8]>, base_url: &str) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// A signed URL allowing an operation on a path until the expiry.
+    #[must_use]
+    pub fn sign(
+        &self,
+        operation: PresignOperation,
+        path: &Path,
+        expiry: Duration,
+    ) -> PresignedRequest {
+        let expires_at = expires_at(expiry);
+        let path = path.display().to_string();
+        let signature = self.signature(operation, &path, expires_at.timestamp());
+        PresignedRequest {
+            method: operation.method().to_string(),
+            url: format!(
+                "{}{PATH}/{}?expires={}&signature={signature}",
+                self.base_url,
+                encode_path(path.trim_start_matches('/')),
+                expires_at.timestamp()
+            ),
+            headers: BTreeMap::new(),
+            expires_at,
+        }
+    }
+
+    /// Whether the signature of a URL is valid and not expired.
+    #[must_use]
+    pub fn verify(
+        &self,
+        operation: PresignOperation,
+        path: &str,
+        expires: i64,
+        signature: &str,
+    ) -> bool {
+        if Utc::now().timestamp() > expires {
+            return false;
+        }
+        hash::from_hex(signature).is_some_and(|signature| {
+            self.mac(operation, path, expires)
+                .verify_slice(&signature)
+                .is_ok()
+        })
+    }
+
+    /// The hex HMAC-SHA256 signature of an operation on a path.
+    fn signature(&self, operation: PresignOperation, path: &str, expires: i64) -> String {
+        hash::to_hex(&self.mac(operation, path, expires).finalize().into_bytes())
+    }
+
+    /// The HMAC-SHA256 of an operation on a path.
+    fn mac(&self, operation: PresignOperation, path: &str, expires: i64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC can take a key of any size");
+        mac.update(
+            format!(
+                "{}\n{}\n{expires}",
+                operation.method(),
+                path.trim_start_matches('/')
+            )
+            .as_bytes(),
+        );
+        mac
+    }
+}
+
+/// Percent-encodes a path, keeping its `/` separators.
+fn encode_path(path: &str) -> String {
+    path.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+        encoded
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_sign_and_verify_urls() {
+        let signer = UrlSigner::new("secret", "https://example.com/");
+        let request = signer.sign(
+            PresignOperation::Put,
+            Path::new("users/avatar 1.png"),
+            Duration::from_secs(60),
+        );
+        assert_eq!(request.method, "PUT");
+        let expires = request.expires_at.timestamp();
+        let prefix = format!("https://example.com{PATH}/users/avatar%201.png?expires={expires}");
+        assert!(request.url.starts_with(&prefix));
+
+        let signature = request.url.rsplit_once("signature=").unwrap().1;
+        assert!(signer.verify(
+            PresignOperation::Put,
+            "users/avatar 1.png",
+            expires,
+            signature
+        ));
+        assert!(!signer.verify(
+            PresignOperation::Get,
+            "users/avatar 1.png",
+            expires,
+            signature
+        ));
+        assert!(!signer.verify(PresignOperation::Put, "users/other.png", expires, signature));
+        assert!(!signer.verify(
+            PresignOperation::Put,
+            "users/avatar 1.png",
+            expires + 1,
+            signature
+        ));
+        assert!(!UrlSigner::new("other", "").verify(
+            PresignOperation::Put,
+            "users/avatar 1.png",
+            expires,
+            signature
+        ));
+    }
+
+    #[test]
+    fn rejects_expired_urls() {
+        let signer = UrlSigner::new("secret", "");
+        let expires = Utc::now().timestamp() - 1;
+        let signature = signer.signature(PresignOperation::Get, "users/1.txt", expires);
+        assert!(!signer.verify(PresignOperation::Get, "users/1.txt", expires, &signature));
+    }
+}
@@ -21,11 +21,15 @@
 //!
 //! * `download`: Initiates the download of the given path only from primary
 //!   storage.
-use std::{collections::BTreeMap, path::Path};
+use std::{collections::BTreeMap, path::Path, time::Duration};
 
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageError, StorageResult};
+use crate::storage::{
+    presign::{PresignOperation, PresignedRequest},
+    strategies::StorageStrategy,
+    Storage, StorageError, StorageResult,
+};
 
 /// Enum representing the failure mode for the [`BackupStrategy`].
 #[derive(Clone, Debug)]
@@ -256,6 +260,24 @@ impl StorageStrategy for BackupStrategy {
 
         Ok(())
     }
+
+    /// Presigns a request on the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presign(
+        &self,
+        storage: &Storage,
+        operation: PresignOperation,
+        path: &Path,
+        expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        storage
+            .as_store_err(&self.primary)?
+            .presign(operation, path, expiry)
+            .await
+    }
 }
 
 impl BackupStrategy {
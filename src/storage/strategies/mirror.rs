@@ -20,11 +20,15 @@
 //!   primary, it looks for the content in the secondary storages. If the
 //!   content is not found in any storage backend (both primary and secondary),
 //!   it returns an error.
-use std::{collections::BTreeMap, path::Path};
+use std::{collections::BTreeMap, path::Path, time::Duration};
 
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageError, StorageResult};
+use crate::storage::{
+    presign::{PresignOperation, PresignedRequest},
+    strategies::StorageStrategy,
+    Storage, StorageError, StorageResult,
+};
 
 /// Enum representing the failure mode for the [`MirrorStrategy`].
 #[derive(Clone, Debug)]
@@ -288,6 +292,24 @@ impl StorageStrategy for MirrorStrategy {
 
         Ok(())
     }
+
+    /// Presigns a request on the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presign(
+        &self,
+        storage: &Storage,
+        operation: PresignOperation,
+        path: &Path,
+        expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        storage
+            .as_store_err(&self.primary)?
+            .presign(operation, path, expiry)
+            .await
+    }
 }
 
 impl MirrorStrategy {
@@ -2,11 +2,15 @@ pub mod backup;
 pub mod mirror;
 pub mod single;
 
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use bytes::Bytes;
 
-use crate::storage::{stream::BytesStream, Storage, StorageResult};
+use crate::storage::{
+    presign::{PresignOperation, PresignedRequest},
+    stream::BytesStream,
+    Storage, StorageResult,
+};
 
 #[async_trait::async_trait]
 pub trait StorageStrategy: Sync + Send {
@@ -30,4 +34,19 @@ pub trait StorageStrategy: Sync + Send {
         path: &Path,
         stream: BytesStream,
     ) -> StorageResult<()>;
+
+    /// Presign a request on the store of the strategy serving the reads, the
+    /// presigned uploads only reaching that store.
+    ///
+    /// The default implementation returns
+    /// [`StorageError::PresignNotSupported`](crate::storage::StorageError::PresignNotSupported).
+    async fn presign(
+        &self,
+        _storage: &Storage,
+        _operation: PresignOperation,
+        _path: &Path,
+        _expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        Err(crate::storage::StorageError::PresignNotSupported)
+    }
 }
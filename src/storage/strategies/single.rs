@@ -2,11 +2,15 @@
 //!
 //! This module provides an implementation of the [`StorageStrategy`] for a
 //! single storage strategy.
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageResult};
+use crate::storage::{
+    presign::{PresignOperation, PresignedRequest},
+    strategies::StorageStrategy,
+    Storage, StorageResult,
+};
 
 /// Represents a single storage strategy.
 #[derive(Clone)]
@@ -111,6 +115,24 @@ impl StorageStrategy for SingleStrategy {
             .await?;
         Ok(())
     }
+
+    /// Presigns a request on the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presign(
+        &self,
+        storage: &Storage,
+        operation: PresignOperation,
+        path: &Path,
+        expiry: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        storage
+            .as_store_err(&self.primary)?
+            .presign(operation, path, expiry)
+            .await
+    }
 }
 
 #[cfg(test)]
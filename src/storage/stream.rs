@@ -12,6 +12,20 @@ pub struct BytesStream {
 }
 
 impl BytesStream {
+    /// Create a `BytesStream` from any stream of bytes, such as a file being
+    /// read or the body of a request.
+    pub fn from_stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self {
+            inner: Box::pin(stream.map(|result| {
+                result.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+            })),
+        }
+    }
+
     /// Create a `BytesStream` from an `OpenDAL` `Reader`.
     /// This is an internal method used by storage drivers.
     pub(crate) async fn from_reader(reader: Reader) -> Result<Self, crate::storage::StorageError> {
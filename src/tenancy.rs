@@ -0,0 +1,459 @@
+//! # Multi-tenancy
+//!
+//! The tenant of a request is resolved by the
+//! [`crate::controller::middleware::tenancy`] middleware, from a header, the
+//! subdomain or a JWT claim, and handed to the handlers with the
+//! [`TenantContext`] extractor. The data of the tenants is isolated in one of
+//! two modes:
+//!
+//! - `row`: the tables are shared, and the rows of the [`TenantScoped`]
+//!   entities are scoped by their `tenant_id` column. On Postgres, the
+//!   transactions of [`TenantContext::begin`] set the `app.tenant_id`
+//!   setting, for row level security policies to scope every query of the
+//!   request automatically.
+//! - `schema`: each tenant has its own Postgres schema, which the
+//!   transactions of [`TenantContext::begin`] set as the search path. The
+//!   schemas are created and migrated with [`migrate`].
+//!
+//! The tenant of a header or a subdomain is chosen by the client, so these
+//! resolvers require a [`TenantAuthorizer`], from
+//! [`crate::app::Hooks::tenant_authorizer`], checking that the user is a
+//! member of the tenant. The JWT claim, the default resolver, is signed by the
+//! app and trusted as is.
+//!
+//! # Example
+//! ```rust,ignore
+//! // row mode
+//! async fn list(tenant: TenantContext, State(ctx): State<AppContext>) -> Result<Response> {
+//!     format::json(posts::Entity::find_for_tenant(&tenant).all(&ctx.db).await?)
+//! }
+//!
+//! // schema mode
+//! async fn list(tenant: TenantContext, State(ctx): State<AppContext>) -> Result<Response> {
+//!     let txn = tenant.begin(&ctx.db).await?;
+//!     let posts = posts::Entity::find().all(&txn).await?;
+//!     txn.commit().await?;
+//!     format::json(posts)
+//! }
+//! ```
+//!
+//! A row level security policy scoping the rows of the tenant transactions:
+//! ```sql
+//! ALTER TABLE posts ENABLE ROW LEVEL SECURITY;
+//! ALTER TABLE posts FORCE ROW LEVEL SECURITY;
+//! CREATE POLICY tenant_isolation ON posts
+//!     USING (tenant_id = current_setting('app.tenant_id', true));
+//! ```
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, DatabaseTransaction,
+    EntityTrait, PrimaryKeyTrait, QueryFilter, Select, Statement, TransactionTrait,
+};
+use sea_orm_migration::MigratorTrait;
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppContext, Error, Result};
+
+/// The maximum length of a tenant id, so that the schema names stay below
+/// the 63 bytes of Postgres identifiers.
+const MAX_ID_LEN: usize = 48;
+
+/// How the data of the tenants is isolated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Shared tables, with rows scoped by a `tenant_id` column
+    #[default]
+    Row,
+    /// A Postgres schema per tenant
+    Schema,
+}
+
+/// Where the tenant of a request is read from, a JWT claim by default
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Resolver {
+    /// A request header
+    Header {
+        #[serde(default = "default_header")]
+        name: String,
+    },
+    /// The subdomain of the `Host` header, `acme` for `acme.example.com`
+    /// with `domain: example.com`
+    Subdomain { domain: String },
+    /// A claim of the JWT of the request
+    #[cfg(feature = "auth_jwt")]
+    Claim {
+        #[serde(default = "default_claim")]
+        name: String,
+    },
+}
+
+impl Default for Resolver {
+    #[cfg(feature = "auth_jwt")]
+    fn default() -> Self {
+        Self::Claim {
+            name: default_claim(),
+        }
+    }
+
+    #[cfg(not(feature = "auth_jwt"))]
+    fn default() -> Self {
+        Self::Header {
+            name: default_header(),
+        }
+    }
+}
+
+fn default_header() -> String {
+    "x-tenant-id".to_string()
+}
+
+#[cfg(feature = "auth_jwt")]
+fn default_claim() -> String {
+    "tenant".to_string()
+}
+
+impl Resolver {
+    /// Whether the tenant is vouched for by the app, rather than chosen by
+    /// the client.
+    #[must_use]
+    pub const fn is_trusted(&self) -> bool {
+        match self {
+            Self::Header { .. } | Self::Subdomain { .. } => false,
+            #[cfg(feature = "auth_jwt")]
+            Self::Claim { .. } => true,
+        }
+    }
+
+    /// Reads the tenant id of a request, if any.
+    #[cfg_attr(
+        not(feature = "auth_jwt"),
+        allow(unused_variables, clippy::unused_async)
+    )]
+    pub async fn resolve(&self, parts: &Parts, ctx: &AppContext) -> Option<String> {
+        let id = match self {
+            Self::Header { name } => parts
+                .headers
+                .get(name.as_str())
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            Self::Subdomain { domain } => parts
+                .headers
+                .get(header::HOST)
+                .and_then(|value| value.to_str().ok())
+                .or_else(|| parts.uri.host())
+                .and_then(|host| {
+                    let host = host.split(':').next().unwrap_or(host);
+                    host.strip_suffix(domain.as_str())?
+                        .strip_suffix('.')
+                        .map(str::to_string)
+                }),
+            #[cfg(feature = "auth_jwt")]
+            Self::Claim { name } => {
+                crate::controller::extractor::auth::extract_jwt_from_request_parts(parts, ctx)
+                    .await
+                    .ok()?
+                    .claims
+                    .claims
+                    .get(name)
+                    .and_then(|value| value.as_str())
+                    .map(str::to_string)
+            }
+        };
+        id.filter(|id| !id.is_empty())
+    }
+}
+
+/// Whether a tenant id is made of ASCII letters, digits, `_` and `-` only,
+/// so that it is safe in schema names.
+#[must_use]
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Decides whether the user of a request may act in a tenant, such as by
+/// looking up their memberships.
+#[async_trait]
+pub trait TenantAuthorizer: Send + Sync {
+    /// Whether the request may access the tenant.
+    ///
+    /// # Errors
+    ///
+    /// The errors are logged, and the request is rejected.
+    async fn authorize(
+        &self,
+        ctx: &AppContext,
+        parts: &Parts,
+        tenant: &TenantContext,
+    ) -> Result<bool>;
+}
+
+/// The tenant authorizer of the application, kept in the shared store of
+/// the context.
+#[derive(Clone)]
+pub struct TenantAuthorization(pub Arc<dyn TenantAuthorizer>);
+
+/// The tenant of a request, resolved by the tenancy middleware.
+///
+/// Extracting it fails with `400 Bad Request` when the request has no tenant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantContext {
+    pub id: String,
+    /// The schema of the tenant, in the `schema` mode
+    pub schema: Option<String>,
+}
+
+impl TenantContext {
+    /// Begins a transaction in the data of the tenant: in the `schema` mode,
+    /// its search path is the schema of the tenant, then `public`. On
+    /// Postgres, the `app.tenant_id` setting of the transaction is the id of
+    /// the tenant.
+    ///
+    /// # Errors
+    ///
+    /// When the transaction can't begin, or in the `schema` mode when the
+    /// database is not Postgres.
+    pub async fn begin(&self, db: &DatabaseConnection) -> Result<DatabaseTransaction> {
+        let txn = db.begin().await?;
+        if let Some(schema) = &self.schema {
+            set_search_path(&txn, schema).await?;
+        }
+        if txn.get_database_backend() == DatabaseBackend::Postgres {
+            txn.execute(Statement::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                "SELECT set_config('app.tenant_id', $1, true)",
+                [self.id.clone().into()],
+            ))
+            .await?;
+        }
+        Ok(txn)
+    }
+}
+
+impl<S> FromRequestParts<S> for TenantContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or_else(|| Error::BadRequest("missing tenant".to_string()))
+    }
+}
+
+/// Entities whose rows belong to a tenant, in the `row` mode.
+pub trait TenantScoped: EntityTrait {
+    /// The column of the tenant id.
+    fn tenant_id() -> Self::Column;
+
+    /// Selects the rows of the tenant.
+    #[must_use]
+    fn find_for_tenant(tenant: &TenantContext) -> Select<Self> {
+        Self::find().filter(Self::tenant_id().eq(tenant.id.as_str()))
+    }
+
+    /// Selects the row with the primary key, when it belongs to the tenant.
+    #[must_use]
+    fn find_by_id_for_tenant<T>(tenant: &TenantContext, values: T) -> Select<Self>
+    where
+        T: Into<<Self::PrimaryKey as PrimaryKeyTrait>::ValueType>,
+    {
+        Self::find_by_id(values).filter(Self::tenant_id().eq(tenant.id.as_str()))
+    }
+}
+
+fn quote(schema: &str) -> String {
+    format!("\"{}\"", schema.replace('"', "\"\""))
+}
+
+async fn set_search_path<C: ConnectionTrait>(db: &C, schema: &str) -> Result<()> {
+    if db.get_database_backend() != DatabaseBackend::Postgres {
+        return Err(Error::string(
+            "the schema tenancy mode requires a Postgres database",
+        ));
+    }
+    db.execute_unprepared(&format!(
+        "SET LOCAL search_path TO {}, public",
+        quote(schema)
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Creates the schema of a tenant, unless it exists.
+///
+/// # Errors
+///
+/// When the statement fails
+pub async fn create_schema(db: &DatabaseConnection, schema: &str) -> Result<()> {
+    db.execute_unprepared(&format!("CREATE SCHEMA IF NOT EXISTS {}", quote(schema)))
+        .await?;
+    Ok(())
+}
+
+/// Drops the schema of a tenant, with all its tables.
+///
+/// # Errors
+///
+/// When the statement fails
+pub async fn drop_schema(db: &DatabaseConnection, schema: &str) -> Result<()> {
+    db.execute_unprepared(&format!("DROP SCHEMA IF EXISTS {} CASCADE", quote(schema)))
+        .await?;
+    Ok(())
+}
+
+/// Creates the schema of a tenant if needed, and applies the pending
+/// migrations in it. Each schema tracks its own applied migrations.
+///
+/// # Errors
+///
+/// When the database is not Postgres, or a migration fails
+pub async fn migrate<M: MigratorTrait>(db: &DatabaseConnection, schema: &str) -> Result<()> {
+    create_schema(db, schema).await?;
+    let txn = db.begin().await?;
+    set_search_path(&txn, schema).await?;
+    M::up(&txn, None).await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+    use sea_orm::{entity::prelude::*, Database, Set};
+
+    use super::*;
+    use crate::tests_cfg;
+
+    mod posts {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "posts")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub tenant_id: String,
+            pub title: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    impl TenantScoped for posts::Entity {
+        fn tenant_id() -> posts::Column {
+            posts::Column::TenantId
+        }
+    }
+
+    fn parts(header: &str, value: &str) -> Parts {
+        Request::builder()
+            .header(header, value)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    }
+
+    #[tokio::test]
+    async fn can_resolve_tenant() {
+        let ctx = tests_cfg::app::get_app_context().await;
+        let subdomain = Resolver::Subdomain {
+            domain: "example.com".to_string(),
+        };
+
+        let header = Resolver::Header {
+            name: "x-tenant-id".to_string(),
+        };
+        assert!(!header.is_trusted());
+        #[cfg(feature = "auth_jwt")]
+        assert!(Resolver::default().is_trusted());
+        assert_eq!(
+            header.resolve(&parts("x-tenant-id", "acme"), &ctx).await,
+            Some("acme".to_string())
+        );
+        assert_eq!(
+            subdomain
+                .resolve(&parts("host", "acme.example.com:5150"), &ctx)
+                .await,
+            Some("acme".to_string())
+        );
+        assert_eq!(
+            subdomain.resolve(&parts("host", "example.com"), &ctx).await,
+            None
+        );
+        assert_eq!(
+            subdomain
+                .resolve(&parts("host", "acme.other.com"), &ctx)
+                .await,
+            None
+        );
+
+        assert!(is_valid_id("acme_corp-1"));
+        assert!(!is_valid_id("acme\"; DROP SCHEMA public"));
+        assert!(!is_valid_id(""));
+    }
+
+    #[tokio::test]
+    async fn can_scope_rows_by_tenant() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared(
+            "CREATE TABLE posts (id INTEGER PRIMARY KEY, tenant_id TEXT NOT NULL, title TEXT NOT NULL)",
+        )
+        .await
+        .unwrap();
+        for (tenant_id, title) in [("acme", "one"), ("globex", "two"), ("acme", "three")] {
+            posts::ActiveModel {
+                tenant_id: Set(tenant_id.to_string()),
+                title: Set(title.to_string()),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let acme = TenantContext {
+            id: "acme".to_string(),
+            schema: None,
+        };
+        let titles = posts::Entity::find_for_tenant(&acme)
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|post| post.title)
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["one", "three"]);
+        assert!(posts::Entity::find_by_id_for_tenant(&acme, 2)
+            .one(&db)
+            .await
+            .unwrap()
+            .is_none());
+
+        // the schema mode is Postgres only
+        let schema = TenantContext {
+            id: "acme".to_string(),
+            schema: Some("tenant_acme".to_string()),
+        };
+        assert!(schema.begin(&db).await.is_err());
+    }
+}
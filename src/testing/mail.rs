@@ -0,0 +1,215 @@
+//! Assertions on the emails sent by the stub mailer, enabled with
+//! `mailer.stub: true` and the workers in `ForegroundBlocking` mode.
+//!
+//! The emails are kept as they were handed to the mailer, with their
+//! recipients, subject and bodies in clear, and the locals their templates
+//! were rendered with.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use loco_rs::testing::prelude::*;
+//!
+//! let email = mail::assert_sent_to(&ctx, "user@example.com");
+//! mail::assert_subject(&email, "Welcome to Loco");
+//! mail::assert_body_contains(&email, "/verify/");
+//! let token = mail::local(&email, "verifyToken").unwrap();
+//! assert_snapshot!(email.html);
+//! ```
+
+use lettre::message::Mailboxes;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    app::AppContext,
+    mailer::{Email, StubDeliveries},
+};
+
+/// The emails delivered by the stub mailer of the context, oldest first.
+///
+/// # Panics
+///
+/// When the deliveries lock is poisoned
+#[must_use]
+pub fn deliveries(ctx: &AppContext) -> Vec<Email> {
+    ctx.shared_store
+        .get::<StubDeliveries>()
+        .map(|deliveries| deliveries.0.lock().unwrap().clone())
+        .unwrap_or_default()
+}
+
+/// Forgets the emails delivered so far.
+///
+/// # Panics
+///
+/// When the deliveries lock is poisoned
+pub fn clear_deliveries(ctx: &AppContext) {
+    if let Some(deliveries) = ctx.shared_store.get::<StubDeliveries>() {
+        deliveries.0.lock().unwrap().clear();
+    }
+}
+
+/// Asserts the number of emails delivered.
+///
+/// # Panics
+///
+/// When another number of emails was delivered
+pub fn assert_sent_count(ctx: &AppContext, count: usize) {
+    let deliveries = deliveries(ctx);
+    assert_eq!(
+        deliveries.len(),
+        count,
+        "expected {count} emails to be sent, got {}: {:?}",
+        deliveries.len(),
+        deliveries
+            .iter()
+            .map(|email| (&email.to, &email.subject))
+            .collect::<Vec<_>>()
+    );
+}
+
+/// Whether an address is one of the recipients of an email, in its `to`,
+/// `cc` or `bcc` mailboxes.
+#[must_use]
+pub fn is_recipient(email: &Email, address: &str) -> bool {
+    [Some(&email.to), email.cc.as_ref(), email.bcc.as_ref()]
+        .into_iter()
+        .flatten()
+        .filter_map(|list| list.parse::<Mailboxes>().ok())
+        .flatten()
+        .any(|mailbox| {
+            mailbox
+                .email
+                .to_string()
+                .eq_ignore_ascii_case(address.trim())
+        })
+}
+
+/// Asserts an email was delivered to an address, returning the latest one.
+///
+/// # Panics
+///
+/// When no email was delivered to the address
+#[must_use]
+pub fn assert_sent_to(ctx: &AppContext, address: &str) -> Email {
+    let deliveries = deliveries(ctx);
+    deliveries
+        .iter()
+        .rev()
+        .find(|email| is_recipient(email, address))
+        .cloned()
+        .unwrap_or_else(|| {
+            panic!(
+                "no email sent to `{address}`, the emails were sent to: {:?}",
+                deliveries.iter().map(|email| &email.to).collect::<Vec<_>>()
+            )
+        })
+}
+
+/// Asserts the subject of an email.
+///
+/// # Panics
+///
+/// When the email has another subject
+pub fn assert_subject(email: &Email, subject: &str) {
+    assert_eq!(email.subject, subject, "unexpected email subject");
+}
+
+/// Asserts the text or the HTML body of an email contains some content.
+///
+/// # Panics
+///
+/// When neither body contains the content
+pub fn assert_body_contains(email: &Email, content: &str) {
+    assert!(
+        email.text.contains(content) || email.html.contains(content),
+        "email `{}` does not contain `{content}`, its text is:\n{}",
+        email.subject,
+        email.text
+    );
+}
+
+/// A local the templates of an email were rendered with, by its key, or by
+/// its JSON pointer such as `/user/name`.
+#[must_use]
+pub fn local<'a>(email: &'a Email, key: &str) -> Option<&'a Value> {
+    let locals = email.locals.as_ref()?;
+    if key.starts_with('/') {
+        locals.pointer(key)
+    } else {
+        locals.get(key)
+    }
+}
+
+/// Asserts the value of a local the templates of an email were rendered
+/// with, see [`local`].
+///
+/// # Panics
+///
+/// When the local is missing, or has another value
+pub fn assert_local<T: Serialize>(email: &Email, key: &str, expected: T) {
+    let expected = serde_json::to_value(expected).expect("serializable local");
+    assert_eq!(
+        local(email, key),
+        Some(&expected),
+        "unexpected local `{key}` of email `{}`",
+        email.subject
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{mailer::EmailSender, tests_cfg};
+
+    #[tokio::test]
+    async fn can_assert_deliveries() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.mailer = Some(EmailSender::stub());
+        assert_sent_count(&ctx, 0);
+
+        let email = Email {
+            from: Some("System <system@example.com>".to_string()),
+            to: "User 1 <user1@example.com>".to_string(),
+            bcc: Some("audit@example.com".to_string()),
+            subject: "Welcome".to_string(),
+            text: "Verify at /verify/1111".to_string(),
+            html: "<a href=\"/verify/1111\">Verify</a>".to_string(),
+            locals: Some(json!({"verifyToken": "1111", "user": {"name": "Jane"}})),
+            ..Default::default()
+        };
+        email.deliver(&ctx).await.unwrap();
+
+        assert_sent_count(&ctx, 1);
+        let sent = assert_sent_to(&ctx, "USER1@example.com");
+        assert!(is_recipient(&sent, "audit@example.com"));
+        assert!(!is_recipient(&sent, "user2@example.com"));
+        assert_subject(&sent, "Welcome");
+        assert_body_contains(&sent, "/verify/1111");
+        assert_local(&sent, "verifyToken", "1111");
+        assert_local(&sent, "/user/name", "Jane");
+        assert_eq!(local(&sent, "missing"), None);
+
+        clear_deliveries(&ctx);
+        assert_sent_count(&ctx, 0);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "no email sent to `user2@example.com`")]
+    async fn can_fail_on_missing_recipient() {
+        let mut ctx = tests_cfg::app::get_app_context().await;
+        ctx.mailer = Some(EmailSender::stub());
+        Email {
+            to: "user1@example.com".to_string(),
+            ..Default::default()
+        }
+        .deliver(&ctx)
+        .await
+        .unwrap();
+
+        let _ = assert_sent_to(&ctx, "user2@example.com");
+    }
+}
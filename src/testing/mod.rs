@@ -1,5 +1,6 @@
 #[cfg(feature = "with-db")]
 pub mod db;
+pub mod mail;
 pub mod prelude;
 pub mod redaction;
 pub mod request;
@@ -1,3 +1,3 @@
 #[cfg(feature = "with-db")]
 pub use crate::testing::db::*;
-pub use crate::testing::{redaction::*, request::*, selector::*};
+pub use crate::testing::{mail, redaction::*, request::*, selector::*};
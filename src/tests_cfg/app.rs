@@ -21,11 +21,21 @@ pub async fn get_app_context() -> AppContext {
         environment: Environment::Test,
         #[cfg(feature = "with-db")]
         db: super::db::dummy_connection().await,
+        #[cfg(feature = "with-db")]
+        replicas: std::sync::Arc::default(),
+        #[cfg(feature = "with-db")]
+        outbox: crate::outbox::Outbox,
+        #[cfg(feature = "with-db")]
+        encryptor: None,
         queue_provider: None,
         config: test_config(),
         mailer: None,
         storage: Storage::single(storage::drivers::mem::new()).into(),
         cache: cache.into(),
         shared_store: std::sync::Arc::new(SharedStore::default()),
+        #[cfg(feature = "http_client")]
+        http: std::sync::Arc::new(crate::http_client::Client::stubbed(
+            &crate::config::HttpClient::default(),
+        )),
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::{
     config::{self, Config},
@@ -24,6 +24,7 @@ pub fn test_config() -> Config {
             port: 5555,
             host: "localhost".to_string(),
             ident: None,
+            shutdown_timeout: 30,
             middlewares: middleware::Config::default(),
         },
         #[cfg(feature = "with-db")]
@@ -32,9 +33,18 @@ pub fn test_config() -> Config {
         auth: None,
         workers: config::Workers {
             mode: config::WorkerMode::ForegroundBlocking,
+            queues: BTreeMap::new(),
+            shutdown_timeout: None,
+            async_queue: config::AsyncQueueConfig::default(),
+            payload: config::PayloadConfig::default(),
         },
         mailer: None,
         initializers: None,
+        views: config::Views::default(),
+        i18n: config::I18n::default(),
+        pagination: config::Pagination::default(),
+        uploads: config::Uploads::default(),
+        http: config::HttpClient::default(),
         settings: None,
         scheduler: Some(scheduler::Config {
             jobs: HashMap::from([(
@@ -46,10 +56,13 @@ pub fn test_config() -> Config {
                     cron: "*/5 * * * * *".to_string(),
                     tags: Some(vec!["base".to_string()]),
                     output: None,
+                    timezone: None,
+                    overlap: scheduler::Overlap::default(),
                 },
             )]),
 
             output: scheduler::Output::STDOUT,
+            history: None,
         }),
         // Always use in-memory cache for tests if available
         #[cfg(feature = "cache_inmem")]
@@ -59,6 +72,7 @@ pub fn test_config() -> Config {
         // If cache_inmem is not enabled, use null cache
         #[cfg(not(feature = "cache_inmem"))]
         cache: config::CacheConfig::Null,
+        storage: config::StorageConfig::Null,
     }
 }
 
@@ -76,6 +90,14 @@ pub fn get_database_config() -> config::Database {
         dangerously_truncate: false,
         dangerously_recreate: false,
         run_on_start: None,
+        replicas: vec![],
+        replicas_check_interval: 5,
+        max_lifetime: None,
+        statement_timeout: None,
+        pool_stats_interval: None,
+        outbox: None,
+        encryption: None,
+        sqlite: config::Sqlite::default(),
     }
 }
 
@@ -0,0 +1,523 @@
+//! # Webhooks
+//!
+//! The [`Webhook`] extractor receives the webhooks of other services: it
+//! reads the raw body of the request, verifies its signature with the
+//! [`Verifier`] of a [`WebhookSource`], and keeps the body, so that it can be
+//! parsed once verified.
+//!
+//! The signatures are HMAC-SHA256 ones, with the presets of GitHub, Stripe
+//! and Slack, and a generic scheme for the other services. Signed timestamps
+//! older or newer than the tolerance, 5 minutes by default, are refused to
+//! prevent replays.
+//!
+//! Requests with a missing or invalid signature are refused with a
+//! `401 Unauthorized`.
+//!
+//! # Example
+//! ```rust
+//! use loco_rs::prelude::*;
+//! use loco_rs::webhooks::{Verifier, Webhook, WebhookSource};
+//!
+//! struct GitHub;
+//!
+//! impl WebhookSource for GitHub {
+//!     fn verifier(_ctx: &AppContext) -> Result<Verifier> {
+//!         let secret = std::env::var("GITHUB_WEBHOOK_SECRET")
+//!             .map_err(|_| Error::string("missing GITHUB_WEBHOOK_SECRET"))?;
+//!         Ok(Verifier::github(secret))
+//!     }
+//! }
+//!
+//! async fn receive(webhook: Webhook<GitHub>) -> Result<Response> {
+//!     let event: serde_json::Value = webhook.json()?;
+//!     format::empty()
+//! }
+//! ```
+use std::{
+    marker::PhantomData,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{FromRequest, Request},
+    http::{HeaderMap, HeaderName, HeaderValue},
+};
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+
+use crate::{app::AppContext, hash, Error, Result};
+
+/// The default tolerance of the signed timestamps.
+pub const DEFAULT_TOLERANCE: Duration = Duration::from_secs(300);
+
+/// How the signature of a webhook is sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scheme {
+    /// `X-Hub-Signature-256: sha256=<signature of the body>`
+    GitHub,
+    /// `Stripe-Signature: t=<timestamp>,v1=<signature of "{timestamp}.{body}">`,
+    /// with a `v1` signature per secret while they are rolled
+    Stripe,
+    /// `X-Slack-Signature: v0=<signature of "v0:{timestamp}:{body}">`, with
+    /// the timestamp in `X-Slack-Request-Timestamp`
+    Slack,
+    /// The signature of the body in a header, after a prefix such as
+    /// `sha256=`. With a timestamp header, the signed payload is
+    /// `{timestamp}.{body}`.
+    HmacSha256 {
+        signature_header: String,
+        prefix: String,
+        timestamp_header: Option<String>,
+    },
+}
+
+/// Verifies the signatures of the webhooks of a service.
+#[derive(Clone)]
+pub struct Verifier {
+    scheme: Scheme,
+    secret: Vec<u8>,
+    tolerance: Duration,
+}
+
+impl std::fmt::Debug for Verifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Verifier")
+            .field("scheme", &self.scheme)
+            .field("tolerance", &self.tolerance)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Verifier {
+    #[must_use]
+    pub fn new(scheme: Scheme, secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            scheme,
+            secret: secret.as_ref().to_vec(),
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// Verifies the webhooks of GitHub.
+    #[must_use]
+    pub fn github(secret: impl AsRef<[u8]>) -> Self {
+        Self::new(Scheme::GitHub, secret)
+    }
+
+    /// Verifies the webhooks of Stripe, with the `whsec_...` secret of the
+    /// endpoint.
+    #[must_use]
+    pub fn stripe(secret: impl AsRef<[u8]>) -> Self {
+        Self::new(Scheme::Stripe, secret)
+    }
+
+    /// Verifies the requests of Slack, with the signing secret of the app.
+    #[must_use]
+    pub fn slack(secret: impl AsRef<[u8]>) -> Self {
+        Self::new(Scheme::Slack, secret)
+    }
+
+    /// Verifies a hex HMAC-SHA256 signature of the body in a header.
+    #[must_use]
+    pub fn hmac_sha256(signature_header: &str, secret: impl AsRef<[u8]>) -> Self {
+        Self::new(
+            Scheme::HmacSha256 {
+                signature_header: signature_header.to_string(),
+                prefix: String::new(),
+                timestamp_header: None,
+            },
+            secret,
+        )
+    }
+
+    /// How old or new the signed timestamps may be.
+    #[must_use]
+    pub const fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Verifies the signature of a body.
+    ///
+    /// # Errors
+    ///
+    /// An [`Error::Unauthorized`] error when the signature is missing or
+    /// invalid, or the timestamp out of the tolerance.
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<()> {
+        self.verify_at(headers, body, now())
+    }
+
+    fn verify_at(&self, headers: &HeaderMap, body: &[u8], now: i64) -> Result<()> {
+        match &self.scheme {
+            Scheme::GitHub => {
+                let signature = header(headers, "x-hub-signature-256")?;
+                self.check(&[body], strip(signature, "sha256=")?)
+            }
+            Scheme::Stripe => {
+                let signature = header(headers, "stripe-signature")?;
+                let mut timestamp = None;
+                let mut signatures = vec![];
+                for pair in signature.split(',') {
+                    match pair.trim().split_once('=') {
+                        Some(("t", value)) => timestamp = Some(value),
+                        Some(("v1", value)) => signatures.push(value),
+                        _ => {}
+                    }
+                }
+                let timestamp = timestamp.ok_or_else(|| unauthorized("missing timestamp"))?;
+                self.check_timestamp(timestamp, now)?;
+                let payload = [timestamp.as_bytes(), b".", body];
+                if signatures
+                    .iter()
+                    .any(|signature| self.check(&payload, signature).is_ok())
+                {
+                    Ok(())
+                } else {
+                    Err(unauthorized("invalid signature"))
+                }
+            }
+            Scheme::Slack => {
+                let timestamp = header(headers, "x-slack-request-timestamp")?;
+                self.check_timestamp(timestamp, now)?;
+                let signature = header(headers, "x-slack-signature")?;
+                self.check(
+                    &[b"v0:", timestamp.as_bytes(), b":", body],
+                    strip(signature, "v0=")?,
+                )
+            }
+            Scheme::HmacSha256 {
+                signature_header,
+                prefix,
+                timestamp_header,
+            } => {
+                let signature = strip(header(headers, signature_header)?, prefix)?;
+                match timestamp_header {
+                    Some(timestamp_header) => {
+                        let timestamp = header(headers, timestamp_header)?;
+                        self.check_timestamp(timestamp, now)?;
+                        self.check(&[timestamp.as_bytes(), b".", body], signature)
+                    }
+                    None => self.check(&[body], signature),
+                }
+            }
+        }
+    }
+
+    /// The headers signing a body at a timestamp, in seconds, to test the
+    /// receivers of the webhooks, or to send webhooks to other apps.
+    #[must_use]
+    pub fn sign(&self, body: &[u8], timestamp: i64) -> HeaderMap {
+        let timestamp_text = timestamp.to_string();
+        let mut headers = HeaderMap::new();
+        let mut insert = |name: &str, value: String| {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                headers.insert(name, value);
+            }
+        };
+        match &self.scheme {
+            Scheme::GitHub => insert(
+                "x-hub-signature-256",
+                format!("sha256={}", self.signature(&[body])),
+            ),
+            Scheme::Stripe => insert(
+                "stripe-signature",
+                format!(
+                    "t={timestamp_text},v1={}",
+                    self.signature(&[timestamp_text.as_bytes(), b".", body])
+                ),
+            ),
+            Scheme::Slack => {
+                insert(
+                    "x-slack-signature",
+                    format!(
+                        "v0={}",
+                        self.signature(&[b"v0:", timestamp_text.as_bytes(), b":", body])
+                    ),
+                );
+                insert("x-slack-request-timestamp", timestamp_text);
+            }
+            Scheme::HmacSha256 {
+                signature_header,
+                prefix,
+                timestamp_header,
+            } => match timestamp_header {
+                Some(timestamp_header) => {
+                    insert(
+                        signature_header,
+                        format!(
+                            "{prefix}{}",
+                            self.signature(&[timestamp_text.as_bytes(), b".", body])
+                        ),
+                    );
+                    insert(timestamp_header, timestamp_text);
+                }
+                None => insert(
+                    signature_header,
+                    format!("{prefix}{}", self.signature(&[body])),
+                ),
+            },
+        }
+        headers
+    }
+
+    /// The HMAC-SHA256 of a payload.
+    fn mac(&self, payload: &[&[u8]]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC can take a key of any size");
+        for part in payload {
+            mac.update(part);
+        }
+        mac
+    }
+
+    /// The hex HMAC-SHA256 signature of a payload.
+    fn signature(&self, payload: &[&[u8]]) -> String {
+        hash::to_hex(&self.mac(payload).finalize().into_bytes())
+    }
+
+    fn check(&self, payload: &[&[u8]], signature: &str) -> Result<()> {
+        let signature =
+            hash::from_hex(signature).ok_or_else(|| unauthorized("invalid signature"))?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| unauthorized("invalid signature"))
+    }
+
+    fn check_timestamp(&self, timestamp: &str, now: i64) -> Result<()> {
+        let timestamp: i64 = timestamp
+            .parse()
+            .map_err(|_| unauthorized("invalid timestamp"))?;
+        if now.abs_diff(timestamp) > self.tolerance.as_secs() {
+            return Err(unauthorized("timestamp out of the tolerance"));
+        }
+        Ok(())
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| unauthorized(&format!("missing `{name}` header")))
+}
+
+fn strip<'a>(signature: &'a str, prefix: &str) -> Result<&'a str> {
+    signature
+        .strip_prefix(prefix)
+        .ok_or_else(|| unauthorized("invalid signature"))
+}
+
+fn unauthorized(reason: &str) -> Error {
+    Error::Unauthorized(format!("webhook refused: {reason}"))
+}
+
+/// A service sending webhooks.
+pub trait WebhookSource: Send + Sync + 'static {
+    /// The verifier of the webhooks of the service, usually with a secret of
+    /// the app settings.
+    ///
+    /// # Errors
+    ///
+    /// When the secret is not configured.
+    fn verifier(ctx: &AppContext) -> Result<Verifier>;
+}
+
+/// A webhook of `S` whose signature was verified, with its raw body.
+#[derive(Debug)]
+pub struct Webhook<S> {
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    source: PhantomData<S>,
+}
+
+impl<S> Webhook<S> {
+    /// Parses the JSON body.
+    ///
+    /// # Errors
+    ///
+    /// A `400 Bad Request` error when the body is not the expected JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_slice(&self.body).map_err(|err| Error::BadRequest(err.to_string()))
+    }
+}
+
+impl<S: WebhookSource> FromRequest<AppContext> for Webhook<S> {
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &AppContext) -> Result<Self> {
+        let verifier = S::verifier(state)?;
+        let headers = req.headers().clone();
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(|rejection| Error::BadRequest(rejection.body_text()))?;
+        verifier.verify(&headers, &body)?;
+        Ok(Self {
+            headers,
+            body,
+            source: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode, routing::post, Router};
+    use rstest::rstest;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::tests_cfg;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn custom(secret: &str) -> Verifier {
+        Verifier::new(
+            Scheme::HmacSha256 {
+                signature_header: "x-signature".to_string(),
+                prefix: "sha256=".to_string(),
+                timestamp_header: Some("x-timestamp".to_string()),
+            },
+            secret,
+        )
+    }
+
+    #[test]
+    fn can_verify_github_example() {
+        // the example of the GitHub documentation
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-hub-signature-256",
+            HeaderValue::from_static(
+                "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17",
+            ),
+        );
+        let verifier = Verifier::github("It's a Secret to Everybody");
+        assert!(verifier.verify(&headers, b"Hello, World!").is_ok());
+        assert!(verifier.verify(&headers, b"Hello, World?").is_err());
+    }
+
+    #[rstest]
+    #[case(Verifier::github("secret"))]
+    #[case(Verifier::stripe("secret"))]
+    #[case(Verifier::slack("secret"))]
+    #[case(Verifier::hmac_sha256("x-signature", "secret"))]
+    #[case(custom("secret"))]
+    fn can_verify_signatures(#[case] verifier: Verifier) {
+        let body = br#"{"event":"ping"}"#;
+        let headers = verifier.sign(body, NOW);
+
+        assert!(verifier.verify_at(&headers, body, NOW + 10).is_ok());
+        assert!(verifier
+            .verify_at(&headers, br#"{"event":"pong"}"#, NOW)
+            .is_err());
+        assert!(verifier
+            .clone()
+            .tolerance(Duration::from_secs(10))
+            .verify_at(&headers, body, NOW + 10)
+            .is_ok());
+        assert!(verifier.verify_at(&HeaderMap::new(), body, NOW).is_err());
+
+        let other = Verifier::new(verifier.scheme.clone(), "other");
+        assert!(other.verify_at(&headers, body, NOW).is_err());
+    }
+
+    #[rstest]
+    #[case(Verifier::stripe("secret"))]
+    #[case(Verifier::slack("secret"))]
+    #[case(custom("secret"))]
+    fn can_refuse_replays(#[case] verifier: Verifier) {
+        let headers = verifier.sign(b"{}", NOW);
+        assert!(verifier.verify_at(&headers, b"{}", NOW + 301).is_err());
+        assert!(verifier.verify_at(&headers, b"{}", NOW - 301).is_err());
+    }
+
+    #[test]
+    fn can_verify_rolled_stripe_secrets() {
+        let old = Verifier::stripe("old");
+        let new = Verifier::stripe("new");
+        let signature = |verifier: &Verifier| {
+            verifier.sign(b"{}", NOW)["stripe-signature"]
+                .to_str()
+                .unwrap()
+                .rsplit_once("v1=")
+                .unwrap()
+                .1
+                .to_string()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "stripe-signature",
+            HeaderValue::from_str(&format!(
+                "t={NOW},v1={},v1={}",
+                signature(&old),
+                signature(&new)
+            ))
+            .unwrap(),
+        );
+        assert!(new.verify_at(&headers, b"{}", NOW).is_ok());
+    }
+
+    struct GitHub;
+
+    impl WebhookSource for GitHub {
+        fn verifier(_ctx: &AppContext) -> Result<Verifier> {
+            Ok(Verifier::github("secret"))
+        }
+    }
+
+    #[tokio::test]
+    async fn can_extract_webhooks() {
+        async fn receive(webhook: Webhook<GitHub>) -> Result<String> {
+            let event: serde_json::Value = webhook.json()?;
+            Ok(event["action"].as_str().unwrap_or_default().to_string())
+        }
+
+        let app = Router::new()
+            .route("/webhooks/github", post(receive))
+            .with_state(tests_cfg::app::get_app_context().await);
+
+        let body = json!({ "action": "opened" }).to_string();
+        let request = |headers: HeaderMap| {
+            let mut request = axum::http::Request::builder()
+                .method("POST")
+                .uri("/webhooks/github")
+                .body(Body::from(body.clone()))
+                .unwrap();
+            request.headers_mut().extend(headers);
+            request
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request(
+                Verifier::github("secret").sign(body.as_bytes(), NOW),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&text[..], b"opened");
+
+        let response = app
+            .oneshot(request(
+                Verifier::github("other").sign(body.as_bytes(), NOW),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,7 +1,10 @@
 use loco_rs::{controller::extractor::auth, prelude::*, tests_cfg};
 use serde::{Deserialize, Serialize};
 
-use loco_rs::model::{Authenticable, ModelError};
+use loco_rs::{
+    controller::extractor::auth::Scope,
+    model::{ApiKeyGrant, Authenticable, ModelError, ScopedApiKey},
+};
 
 use crate::infra_cfg;
 
@@ -52,6 +55,42 @@ impl Authenticable for TestUser {
     }
 }
 
+#[async_trait::async_trait]
+impl ScopedApiKey for TestUser {
+    async fn find_by_scoped_api_key(
+        _db: &sea_orm::DatabaseConnection,
+        api_key: &str,
+    ) -> Result<(Self, ApiKeyGrant), ModelError> {
+        // Simple mock: a read-only key and a read-write key limited to one
+        // request per minute
+        let grant = match api_key {
+            "test_api_key_123" => ApiKeyGrant {
+                scopes: vec!["posts:read".to_string()],
+                ..Default::default()
+            },
+            "limited_api_key" => ApiKeyGrant {
+                scopes: vec!["posts:read".to_string(), "posts:write".to_string()],
+                rate_limit: Some(1),
+                ..Default::default()
+            },
+            _ => return Err(ModelError::EntityNotFound),
+        };
+        Ok((
+            Self {
+                id: 1,
+                email: "test@example.com".to_string(),
+            },
+            grant,
+        ))
+    }
+}
+
+struct WritePosts;
+
+impl Scope for WritePosts {
+    const NAME: &'static str = "posts:write";
+}
+
 // Test handler for ApiToken extractor
 async fn api_token_handler(auth: auth::ApiToken<TestUser>) -> Result<Response> {
     format::json(TestUserResponse {
@@ -131,6 +170,68 @@ async fn can_handle_api_token_missing() {
     handle.abort();
 }
 
+async fn scoped_api_token_handler(auth: auth::ApiToken<TestUser, WritePosts>) -> Result<Response> {
+    format::json(TestUserResponse {
+        pid: String::new(),
+        user_id: auth.user.id,
+        user_email: auth.user.email,
+    })
+}
+
+// Test scoped ApiToken extractor rejects keys missing the scope
+#[tokio::test]
+async fn can_handle_api_token_missing_scope() {
+    let ctx = tests_cfg::app::get_app_context().await;
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(scoped_api_token_handler), Some(port))
+            .await;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get(get_base_url_port(port))
+        .header("Authorization", "Bearer test_api_key_123")
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), 403);
+
+    handle.abort();
+}
+
+// Test scoped ApiToken extractor enforces the key rate limit
+#[cfg(feature = "cache_inmem")]
+#[tokio::test]
+async fn can_rate_limit_scoped_api_token() {
+    let ctx = tests_cfg::app::get_app_context().await;
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(scoped_api_token_handler), Some(port))
+            .await;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get(get_base_url_port(port))
+        .header("Authorization", "Bearer limited_api_key")
+        .send()
+        .await
+        .expect("Valid response");
+    assert_eq!(res.status(), 200);
+
+    let res = client
+        .get(get_base_url_port(port))
+        .header("Authorization", "Bearer limited_api_key")
+        .send()
+        .await
+        .expect("Valid response");
+    assert_eq!(res.status(), 429);
+
+    handle.abort();
+}
+
 // Test response serialization
 #[tokio::test]
 async fn test_user_response_serialization() {
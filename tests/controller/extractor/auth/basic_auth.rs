@@ -0,0 +1,94 @@
+use loco_rs::{controller::extractor::auth, prelude::*, tests_cfg};
+
+use crate::infra_cfg;
+
+async fn basic_auth_handler(auth: auth::BasicAuth) -> Result<Response> {
+    format::text(&auth.username)
+}
+
+fn basic_auth_config() -> Option<loco_rs::config::Auth> {
+    Some(loco_rs::config::Auth {
+        jwt: None,
+        basic: Some(loco_rs::config::BasicAuth {
+            realm: "admin".to_string(),
+            users: vec![loco_rs::config::BasicAuthUser {
+                username: "admin".to_string(),
+                password: "secret".to_string(),
+            }],
+        }),
+        ..Default::default()
+    })
+}
+
+// Test BasicAuth extractor with valid credentials
+#[tokio::test]
+async fn can_extract_basic_auth_valid() {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+    ctx.config.auth = basic_auth_config();
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(basic_auth_handler), Some(port)).await;
+
+    let res = reqwest::Client::new()
+        .get(get_base_url_port(port))
+        .basic_auth("admin", Some("secret"))
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.text().await.expect("body"), "admin");
+
+    handle.abort();
+}
+
+// Test BasicAuth extractor with invalid credentials
+#[tokio::test]
+async fn can_handle_basic_auth_invalid() {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+    ctx.config.auth = basic_auth_config();
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(basic_auth_handler), Some(port)).await;
+
+    let res = reqwest::Client::new()
+        .get(get_base_url_port(port))
+        .basic_auth("admin", Some("wrong"))
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), 401);
+    assert_eq!(
+        res.headers()
+            .get("www-authenticate")
+            .expect("challenge header"),
+        "Basic realm=\"admin\""
+    );
+
+    handle.abort();
+}
+
+// Test BasicAuth extractor with missing Authorization header
+#[tokio::test]
+async fn can_handle_basic_auth_missing() {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+    ctx.config.auth = basic_auth_config();
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(basic_auth_handler), Some(port)).await;
+
+    let res = reqwest::Client::new()
+        .get(get_base_url_port(port))
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), 401);
+    assert!(res.headers().contains_key("www-authenticate"));
+
+    handle.abort();
+}
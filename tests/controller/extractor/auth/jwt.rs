@@ -1,4 +1,5 @@
 use loco_rs::{controller::extractor::auth, prelude::*, tests_cfg};
+use rstest::rstest;
 use serde::{Deserialize, Serialize};
 
 use crate::infra_cfg;
@@ -25,7 +26,9 @@ async fn can_extract_jwt_with_valid_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
     let jwt = loco_rs::auth::jwt::JWT::new(&secret);
     let token = jwt
@@ -59,7 +62,9 @@ async fn can_handle_invalid_jwt_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -87,7 +92,9 @@ async fn can_handle_missing_jwt_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -114,7 +121,9 @@ async fn can_handle_expired_jwt_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
     let jwt = loco_rs::auth::jwt::JWT::new(&secret);
     let token = jwt
@@ -147,7 +156,9 @@ async fn can_handle_malformed_authorization_header() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -203,7 +214,9 @@ async fn can_extract_jwt_from_cookie() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT token
@@ -247,7 +260,9 @@ async fn can_extract_jwt_from_query() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT token
@@ -293,7 +308,9 @@ async fn can_extract_jwt_with_multiple_locations_cookie_fallback() {
             ])),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT token
@@ -337,7 +354,9 @@ async fn can_extract_jwt_with_multiple_locations_query_fallback() {
             ])),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT token
@@ -384,7 +403,9 @@ async fn can_handle_multiple_locations_all_fail() {
             ])),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -418,7 +439,9 @@ async fn can_handle_cookie_location_missing_cookie() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -452,7 +475,9 @@ async fn can_handle_query_location_missing_param() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -480,7 +505,9 @@ async fn can_handle_jwt_with_wrong_algorithm() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a JWT with different secret (simulating wrong algorithm)
@@ -516,7 +543,9 @@ async fn can_handle_jwt_with_invalid_signature() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT then modify it to have invalid signature
@@ -557,7 +586,9 @@ async fn can_handle_malformed_jwt_structure() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -591,7 +622,9 @@ async fn can_extract_jwt_from_cookie_with_special_chars() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT token
@@ -635,7 +668,9 @@ async fn can_handle_cookie_with_empty_value() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -669,7 +704,9 @@ async fn can_extract_jwt_from_query_with_special_chars() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT token
@@ -712,7 +749,9 @@ async fn can_handle_query_with_empty_value() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -745,7 +784,9 @@ async fn can_handle_query_with_spaces() {
             )),
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -775,7 +816,9 @@ async fn can_validate_error_message_for_missing_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -807,7 +850,9 @@ async fn can_validate_error_message_for_invalid_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -840,7 +885,9 @@ async fn can_validate_error_message_for_malformed_header() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -873,7 +920,9 @@ async fn can_handle_jwt_expires_exactly_at_current_time() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a JWT that expires exactly at current time (0 seconds from now)
@@ -908,7 +957,9 @@ async fn can_handle_jwt_expired_one_second_ago() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a JWT that expired 1 second ago
@@ -946,7 +997,9 @@ async fn can_handle_jwt_expires_in_one_second() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a JWT that expires in 5 seconds to account for test setup time
@@ -985,7 +1038,9 @@ async fn can_handle_jwt_with_missing_exp_claim() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a JWT manually without exp claim
@@ -1021,7 +1076,9 @@ async fn can_handle_jwt_with_invalid_exp_claim() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a JWT with invalid exp claim format
@@ -1057,7 +1114,9 @@ async fn can_handle_jwt_with_distant_future_expiration() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a JWT that expires in 10 years (very distant future)
@@ -1100,7 +1159,9 @@ async fn can_handle_jwt_with_epoch_expiration() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a JWT that expired at epoch time (1970)
@@ -1127,3 +1188,64 @@ async fn can_handle_jwt_with_epoch_expiration() {
     assert_eq!(res.status(), 401);
     handle.abort();
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TenantClaims {
+    pub pid: String,
+    pub tenant_id: String,
+}
+
+// Test handler for JWT extractor with custom claims
+async fn tenant_handler(auth: auth::JWT<TenantClaims>) -> Result<Response> {
+    format::json(auth.claims)
+}
+
+// Test JWT extractor with a custom claims type
+#[rstest]
+#[case::with_claim(serde_json::json!({ "tenant_id": "acme" }), 200)]
+#[case::missing_claim(serde_json::json!({}), 401)]
+#[tokio::test]
+async fn can_extract_jwt_with_custom_claims(
+    #[case] claims: serde_json::Value,
+    #[case] status: u16,
+) {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+    let secret = "PqRwLF2rhHe8J22oBeHy".to_string();
+    ctx.config.auth = Some(loco_rs::config::Auth {
+        jwt: Some(loco_rs::config::JWT {
+            location: None,
+            secret: secret.clone(),
+            expiration: 3600,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    let jwt = loco_rs::auth::jwt::JWT::new(&secret);
+    let token = jwt
+        .generate_token(
+            3600,
+            "test_pid_123".to_string(),
+            claims.as_object().unwrap().clone(),
+        )
+        .expect("Failed to generate token");
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(tenant_handler), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get(get_base_url_port(port))
+        .header("Authorization", format!("Bearer {token}"))
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), status);
+    if status == 200 {
+        let body: TenantClaims = res.json().await.expect("Valid JSON response");
+        assert_eq!(body.pid, "test_pid_123");
+        assert_eq!(body.tenant_id, "acme");
+    }
+    handle.abort();
+}
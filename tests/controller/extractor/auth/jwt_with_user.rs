@@ -26,13 +26,16 @@ impl Authenticable for TestUser {
         pid: &str,
     ) -> Result<Self, ModelError> {
         // Simple mock: return user if pid matches, otherwise not found
-        if pid == "test_pid_123" {
-            Ok(Self {
+        match pid {
+            "test_pid_123" => Ok(Self {
                 id: 1,
                 email: "test@example.com".to_string(),
-            })
-        } else {
-            Err(ModelError::EntityNotFound)
+            }),
+            "admin_pid_456" => Ok(Self {
+                id: 2,
+                email: "admin@example.com".to_string(),
+            }),
+            _ => Err(ModelError::EntityNotFound),
         }
     }
 
@@ -73,7 +76,9 @@ async fn can_extract_jwt_with_user_valid_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT token with known PID
@@ -116,7 +121,9 @@ async fn can_handle_jwt_with_user_invalid_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -148,7 +155,9 @@ async fn can_handle_jwt_with_user_nonexistent_user() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     // Create a valid JWT token with unknown PID
@@ -186,7 +195,9 @@ async fn can_handle_jwt_with_user_missing_token() {
             location: None,
             secret: secret.clone(),
             expiration: 3600,
+            ..Default::default()
         }),
+        ..Default::default()
     });
 
     let port = get_available_port().await;
@@ -204,3 +215,94 @@ async fn can_handle_jwt_with_user_missing_token() {
 
     handle.abort();
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TestImpersonationResponse {
+    pub user_id: i32,
+    pub impersonator_id: Option<i32>,
+}
+
+#[derive(Clone, Default)]
+struct RecordingAudit(std::sync::Arc<std::sync::Mutex<Vec<(String, String)>>>);
+
+#[async_trait::async_trait]
+impl loco_rs::auth::impersonation::ImpersonationAudit for RecordingAudit {
+    async fn record(
+        &self,
+        _ctx: &AppContext,
+        claims: &loco_rs::auth::jwt::UserClaims,
+        parts: &axum::http::request::Parts,
+    ) -> Result<()> {
+        self.0.lock().unwrap().push((
+            claims.impersonator.clone().unwrap_or_default(),
+            parts.uri.path().to_string(),
+        ));
+        Ok(())
+    }
+}
+
+async fn impersonation_handler(auth: auth::JWTWithUser<TestUser>) -> Result<Response> {
+    format::json(TestImpersonationResponse {
+        user_id: auth.user.id,
+        impersonator_id: auth.impersonator.map(|impersonator| impersonator.id),
+    })
+}
+
+// Test JWTWithUser extractor with an impersonation token
+#[tokio::test]
+async fn can_extract_jwt_with_user_impersonation_token() {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+
+    // Configure JWT auth
+    let secret = "PqRwLF2rhHe8J22oBeHy".to_string();
+    ctx.config.auth = Some(loco_rs::config::Auth {
+        jwt: Some(loco_rs::config::JWT {
+            location: None,
+            secret: secret.clone(),
+            expiration: 3600,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    let audit = RecordingAudit::default();
+    ctx.shared_store
+        .insert(loco_rs::auth::impersonation::ImpersonationAuditLog::new(
+            audit.clone(),
+        ));
+
+    // Create a token for the admin acting as the test user
+    let jwt = loco_rs::auth::jwt::JWT::new(&secret);
+    let token = jwt
+        .generate_impersonation_token(
+            3600,
+            "test_pid_123".to_string(),
+            "admin_pid_456".to_string(),
+            serde_json::Map::new(),
+        )
+        .expect("Failed to generate token");
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(impersonation_handler), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get(get_base_url_port(port))
+        .header("Authorization", format!("Bearer {token}"))
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), 200);
+
+    let body: TestImpersonationResponse = res.json().await.expect("Valid JSON response");
+    assert_eq!(body.user_id, 1);
+    assert_eq!(body.impersonator_id, Some(2));
+    assert_eq!(
+        audit.0.lock().unwrap().as_slice(),
+        &[("admin_pid_456".to_string(), "/".to_string())]
+    );
+
+    handle.abort();
+}
@@ -0,0 +1,59 @@
+use loco_rs::{auth::magic_link::MagicLinks, controller::extractor::auth, prelude::*, tests_cfg};
+
+use crate::infra_cfg;
+
+async fn magic_link_handler(login: auth::MagicLinkLogin) -> Result<Response> {
+    format::text(&login.pid)
+}
+
+// Test MagicLinkLogin extractor exchanges a link token only once
+#[tokio::test]
+async fn can_login_with_magic_link_once() {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+    ctx.config.auth = Some(loco_rs::config::Auth {
+        jwt: Some(loco_rs::config::JWT {
+            location: None,
+            secret: "PqRwLF2rhHe8J22oBeHy".to_string(),
+            expiration: 3600,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    let token = MagicLinks::from_context(&ctx)
+        .create("test_pid_123")
+        .await
+        .expect("magic link token");
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(magic_link_handler), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let url = format!("{}?token={token}", get_base_url_port(port));
+
+    let res = client.get(&url).send().await.expect("Valid response");
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.text().await.expect("body"), "test_pid_123");
+
+    let res = client.get(&url).send().await.expect("Valid response");
+    assert_eq!(res.status(), 401);
+
+    handle.abort();
+}
+
+// Test MagicLinkLogin extractor with missing token
+#[tokio::test]
+async fn can_handle_magic_link_missing_token() {
+    let ctx = tests_cfg::app::get_app_context().await;
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(magic_link_handler), Some(port)).await;
+
+    let res = reqwest::get(get_base_url_port(port))
+        .await
+        .expect("Valid response");
+    assert_eq!(res.status(), 401);
+
+    handle.abort();
+}
@@ -1,4 +1,10 @@
+mod basic_auth;
 mod jwt;
+#[cfg(feature = "cache_inmem")]
+mod magic_link;
+mod optional_jwt;
+mod require_role;
+mod token_validator;
 
 #[cfg(feature = "with-db")]
 mod jwt_with_user;
@@ -0,0 +1,89 @@
+use loco_rs::{controller::extractor::auth, prelude::*, tests_cfg};
+use serde::{Deserialize, Serialize};
+
+use crate::infra_cfg;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TestResponse {
+    pub pid: Option<String>,
+}
+
+// Test handler for OptionalJWT extractor
+async fn optional_jwt_handler(auth::OptionalJWT(auth): auth::OptionalJWT) -> Result<Response> {
+    format::json(TestResponse {
+        pid: auth.map(|auth| auth.claims.pid),
+    })
+}
+
+async fn start_server(secret: &str) -> (i32, tokio::task::JoinHandle<()>) {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+    ctx.config.auth = Some(loco_rs::config::Auth {
+        jwt: Some(loco_rs::config::JWT {
+            location: None,
+            secret: secret.to_string(),
+            expiration: 3600,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(optional_jwt_handler), Some(port)).await;
+    (port, handle)
+}
+
+// Test OptionalJWT extractor with valid token
+#[tokio::test]
+async fn can_extract_optional_jwt_with_valid_token() {
+    let secret = "PqRwLF2rhHe8J22oBeHy";
+    let token = loco_rs::auth::jwt::JWT::new(secret)
+        .generate_token(3600, "test_pid_123".to_string(), serde_json::Map::new())
+        .expect("Failed to generate token");
+    let (port, handle) = start_server(secret).await;
+
+    let res = reqwest::Client::new()
+        .get(get_base_url_port(port))
+        .header("Authorization", format!("Bearer {token}"))
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), 200);
+    let body: TestResponse = res.json().await.expect("Valid JSON response");
+    assert_eq!(body.pid.as_deref(), Some("test_pid_123"));
+    handle.abort();
+}
+
+// Test OptionalJWT extractor without a token
+#[tokio::test]
+async fn can_extract_optional_jwt_without_token() {
+    let (port, handle) = start_server("PqRwLF2rhHe8J22oBeHy").await;
+
+    let res = reqwest::Client::new()
+        .get(get_base_url_port(port))
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), 200);
+    let body: TestResponse = res.json().await.expect("Valid JSON response");
+    assert_eq!(body.pid, None);
+    handle.abort();
+}
+
+// Test OptionalJWT extractor still rejects an invalid token
+#[tokio::test]
+async fn can_reject_optional_jwt_with_invalid_token() {
+    let (port, handle) = start_server("PqRwLF2rhHe8J22oBeHy").await;
+
+    let res = reqwest::Client::new()
+        .get(get_base_url_port(port))
+        .header("Authorization", "Bearer invalid_token")
+        .send()
+        .await
+        .expect("Valid response");
+
+    assert_eq!(res.status(), 401);
+    handle.abort();
+}
@@ -0,0 +1,107 @@
+use loco_rs::{
+    controller::extractor::auth::{Permission, RequirePermission, RequireRole, Role},
+    prelude::*,
+    tests_cfg,
+};
+
+use crate::infra_cfg;
+
+const SECRET: &str = "PqRwLF2rhHe8J22oBeHy";
+
+struct Admin;
+
+impl Role for Admin {
+    const NAME: &'static str = "admin";
+}
+
+struct WritePosts;
+
+impl Permission for WritePosts {
+    const NAME: &'static str = "posts:write";
+}
+
+async fn admin_handler(auth: RequireRole<Admin>) -> Result<Response> {
+    format::text(&auth.claims.pid)
+}
+
+async fn write_posts_handler(auth: RequirePermission<WritePosts>) -> Result<Response> {
+    format::text(&auth.claims.pid)
+}
+
+async fn get_app_context() -> AppContext {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+    ctx.config.auth = Some(loco_rs::config::Auth {
+        jwt: Some(loco_rs::config::JWT {
+            location: None,
+            secret: SECRET.to_string(),
+            expiration: 3600,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    ctx
+}
+
+fn token(roles: &[&str], permissions: &[&str]) -> String {
+    loco_rs::auth::jwt::JWT::new(SECRET)
+        .generate_token_with_roles(
+            3600,
+            "test_pid_123".to_string(),
+            roles.iter().map(ToString::to_string).collect(),
+            permissions.iter().map(ToString::to_string).collect(),
+            serde_json::Map::new(),
+        )
+        .expect("Failed to generate token")
+}
+
+async fn request_status(
+    method: axum::routing::MethodRouter<AppContext>,
+    token: Option<String>,
+) -> reqwest::StatusCode {
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(get_app_context().await, "/", method, Some(port)).await;
+
+    let mut request = reqwest::Client::new().get(get_base_url_port(port));
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {token}"));
+    }
+    let res = request.send().await.expect("Valid response");
+
+    handle.abort();
+    res.status()
+}
+
+#[tokio::test]
+async fn can_access_with_required_role() {
+    let status = request_status(get(admin_handler), Some(token(&["admin"], &[]))).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn forbid_access_without_required_role() {
+    let status = request_status(get(admin_handler), Some(token(&["editor"], &[]))).await;
+    assert_eq!(status, 403);
+}
+
+#[tokio::test]
+async fn reject_role_access_without_token() {
+    let status = request_status(get(admin_handler), None).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn can_access_with_required_permission() {
+    let status = request_status(get(write_posts_handler), Some(token(&[], &["posts:write"]))).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn forbid_access_without_required_permission() {
+    let status = request_status(
+        get(write_posts_handler),
+        Some(token(&["admin"], &["posts:read"])),
+    )
+    .await;
+    assert_eq!(status, 403);
+}
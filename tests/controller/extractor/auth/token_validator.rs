@@ -0,0 +1,88 @@
+use axum::http::request::Parts;
+use loco_rs::{
+    auth::{
+        jwt::UserClaims,
+        validator::{TokenValidator, TokenValidators},
+    },
+    controller::extractor::auth,
+    prelude::*,
+    tests_cfg,
+};
+
+use crate::infra_cfg;
+
+const SECRET: &str = "PqRwLF2rhHe8J22oBeHy";
+
+struct Denylist;
+
+#[async_trait]
+impl TokenValidator for Denylist {
+    async fn validate(&self, _ctx: &AppContext, claims: &UserClaims) -> Result<()> {
+        if claims.jti() == Some("revoked") {
+            return unauthorized("token was revoked");
+        }
+        Ok(())
+    }
+}
+
+async fn jwt_handler(auth: auth::JWT) -> Result<Response> {
+    format::text(&auth.claims.pid)
+}
+
+async fn parts_handler(State(ctx): State<AppContext>, parts: Parts) -> Result<Response> {
+    let jwt = auth::extract_jwt_from_request_parts(&parts, &ctx).await?;
+    format::text(&jwt.claims.pid)
+}
+
+async fn request_status(
+    jti: &str,
+    method: axum::routing::MethodRouter<AppContext>,
+) -> reqwest::StatusCode {
+    let mut ctx = tests_cfg::app::get_app_context().await;
+    ctx.config.auth = Some(loco_rs::config::Auth {
+        jwt: Some(loco_rs::config::JWT {
+            location: None,
+            secret: SECRET.to_string(),
+            expiration: 3600,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    ctx.shared_store
+        .insert(TokenValidators::new().register(Denylist));
+
+    let mut claims = serde_json::Map::new();
+    claims.insert("jti".to_string(), jti.into());
+    let token = loco_rs::auth::jwt::JWT::new(SECRET)
+        .generate_token(3600, "test_pid_123".to_string(), claims)
+        .expect("Failed to generate token");
+
+    let port = get_available_port().await;
+    let handle = infra_cfg::server::start_with_route(ctx, "/", method, Some(port)).await;
+
+    let res = reqwest::Client::new()
+        .get(get_base_url_port(port))
+        .header("Authorization", format!("Bearer {token}"))
+        .send()
+        .await
+        .expect("Valid response");
+
+    handle.abort();
+    res.status()
+}
+
+#[tokio::test]
+async fn can_accept_token_passing_validators() {
+    assert_eq!(request_status("valid", get(jwt_handler)).await, 200);
+}
+
+#[tokio::test]
+async fn can_reject_token_with_validator() {
+    assert_eq!(request_status("revoked", get(jwt_handler)).await, 401);
+}
+
+#[tokio::test]
+async fn can_reject_token_with_validator_from_request_parts() {
+    assert_eq!(request_status("valid", get(parts_handler)).await, 200);
+    assert_eq!(request_status("revoked", get(parts_handler)).await, 401);
+}
@@ -73,14 +73,19 @@ async fn can_validation_without_response() {
         .await
         .expect("Valid response");
 
-    assert_eq!(res.status(), 400);
+    assert_eq!(res.status(), 422);
 
     let res_text = res.text().await.expect("response text");
     let res_json: serde_json::Value = serde_json::from_str(&res_text).expect("Valid JSON response");
 
     let expected_json = serde_json::json!(
         {
-            "error": "Bad Request"
+            "error": "validation_error",
+            "description": "The request is invalid",
+            "errors": {
+                "email": ["email"],
+                "name": ["message_str"]
+            }
         }
     );
 
@@ -60,7 +60,10 @@ async fn etag(#[case] enable: bool) {
 
     let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
 
-    ctx.config.server.middlewares.etag = Some(middleware::etag::Etag { enable });
+    ctx.config.server.middlewares.etag = Some(middleware::etag::Etag {
+        enable,
+        compute: None,
+    });
 
     let port = get_available_port().await;
     let handle = infra_cfg::server::start_with_route(ctx, "/", get(action), Some(port)).await;
@@ -81,6 +84,44 @@ async fn etag(#[case] enable: bool) {
     handle.abort();
 }
 
+#[tokio::test]
+async fn etag_computed() {
+    async fn action() -> Result<Response> {
+        format::json(serde_json::json!({ "ok": true }))
+    }
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+
+    ctx.config.server.middlewares.etag = Some(middleware::etag::Etag {
+        enable: true,
+        compute: Some(middleware::etag::Compute::default()),
+    });
+
+    let port = get_available_port().await;
+    let handle = infra_cfg::server::start_with_route(ctx, "/", get(action), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get(get_base_url_port(port))
+        .send()
+        .await
+        .expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = res.headers().get("etag").expect("etag").clone();
+    assert_eq!(res.text().await.expect("body"), r#"{"ok":true}"#);
+
+    let res = client
+        .get(get_base_url_port(port))
+        .header("if-none-match", etag.clone())
+        .send()
+        .await
+        .expect("response");
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers().get("etag"), Some(&etag));
+
+    handle.abort();
+}
+
 #[rstest]
 #[case(true, "remote: 51.50.51.50")]
 #[case(false, "--")]
@@ -129,8 +170,11 @@ async fn timeout(#[case] enable: bool) {
 
     let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
 
-    ctx.config.server.middlewares.timeout_request =
-        Some(middleware::timeout::TimeOut { enable, timeout: 2 });
+    ctx.config.server.middlewares.timeout_request = Some(middleware::timeout::TimeOut {
+        enable,
+        timeout: 2,
+        ..Default::default()
+    });
 
     let port = get_available_port().await;
     let handle = infra_cfg::server::start_with_route(ctx, "/", get(action), Some(port)).await;
@@ -280,6 +324,7 @@ async fn static_assets() {
         fallback: base_static_path.join("404.html"),
         precompressed: false,
         cache_control: None,
+        spa: false,
     });
 
     let port = get_available_port().await;
@@ -306,6 +351,69 @@ async fn static_assets() {
     handle.abort();
 }
 
+#[cfg(not(feature = "embedded_assets"))]
+#[tokio::test]
+async fn static_assets_fingerprinted() {
+    let base_static_assets_path = PathBuf::from("assets").join("static");
+    let static_asset_path = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add(
+            base_static_assets_path.join("404.html"),
+            "<h1>404 not found</h1>",
+        )
+        .add(
+            base_static_assets_path.join("css").join("app.css"),
+            "body {}",
+        )
+        .create()
+        .expect("create static tree file");
+
+    let base_static_path = static_asset_path.root.join(base_static_assets_path);
+    let manifest = loco_rs::controller::assets::AssetManifest::precompile(&base_static_path)
+        .expect("precompile assets");
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+    ctx.config.server.middlewares.static_assets = Some(middleware::static_assets::StaticAssets {
+        enable: true,
+        must_exist: true,
+        folder: middleware::static_assets::FolderConfig {
+            uri: "/static".to_string(),
+            path: base_static_path.clone(),
+        },
+        fallback: base_static_path.join("404.html"),
+        precompressed: false,
+        cache_control: None,
+        spa: false,
+    });
+
+    let port = get_available_port().await;
+    let handle = infra_cfg::server::start_from_ctx(ctx, Some(port)).await;
+
+    let fingerprinted = reqwest::get(format!(
+        "{}static/{}",
+        get_base_url_port(port),
+        manifest.resolve("css/app.css")
+    ))
+    .await
+    .expect("valid response");
+    assert_eq!(
+        fingerprinted.headers().get("cache-control").unwrap(),
+        "public, max-age=31536000, immutable"
+    );
+    assert_eq!(
+        fingerprinted.text().await.expect("text response"),
+        "body {}"
+    );
+
+    let original = reqwest::get(format!("{}static/css/app.css", get_base_url_port(port)))
+        .await
+        .expect("valid response");
+    assert!(original.headers().get("cache-control").is_none());
+    assert_eq!(original.text().await.expect("text response"), "body {}");
+
+    handle.abort();
+}
+
 #[rstest]
 #[case(None, None)]
 #[case(Some("empty".to_string()), None)]
@@ -457,3 +565,242 @@ async fn powered_by_header(#[case] ident: Option<String>) {
 
     handle.abort();
 }
+
+#[rstest]
+#[case(true)]
+#[case(false)]
+#[tokio::test]
+async fn session(#[case] encrypt: bool) {
+    async fn action(session: loco_rs::session::Session) -> Result<Response> {
+        let visits = session.get::<u64>("visits")?.unwrap_or_default() + 1;
+        session.insert("visits", visits)?;
+        format::text(&visits.to_string())
+    }
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+
+    ctx.config.server.middlewares.session = Some(middleware::session::Config {
+        enable: true,
+        secret: "0123456789abcdef0123456789abcdef".to_string(),
+        encrypt,
+        secure: false,
+        ..Default::default()
+    });
+
+    let port = get_available_port().await;
+    let handle = infra_cfg::server::start_with_route(ctx, "/", get(action), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get(get_base_url_port(port))
+        .send()
+        .await
+        .expect("response");
+    let cookie = res
+        .headers()
+        .get("set-cookie")
+        .expect("session cookie")
+        .to_str()
+        .expect("value")
+        .split(';')
+        .next()
+        .expect("cookie pair")
+        .to_string();
+    assert!(cookie.starts_with("loco_session="));
+    assert_eq!(res.text().await.expect("body"), "1");
+
+    let res = client
+        .get(get_base_url_port(port))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .expect("response");
+    assert_eq!(res.text().await.expect("body"), "2");
+
+    // a tampered cookie starts a new session
+    let res = client
+        .get(get_base_url_port(port))
+        .header("cookie", format!("{cookie}x"))
+        .send()
+        .await
+        .expect("response");
+    assert_eq!(res.text().await.expect("body"), "1");
+
+    handle.abort();
+}
+
+#[tokio::test]
+async fn csrf() {
+    async fn action() -> Result<Response> {
+        format::text(&middleware::csrf::current_token().unwrap_or_default())
+    }
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+
+    ctx.config.server.middlewares.session = Some(middleware::session::Config {
+        enable: true,
+        secret: "0123456789abcdef0123456789abcdef".to_string(),
+        secure: false,
+        ..Default::default()
+    });
+    ctx.config.server.middlewares.csrf = Some(middleware::csrf::Csrf {
+        enable: true,
+        ..Default::default()
+    });
+
+    let port = get_available_port().await;
+    let handle =
+        infra_cfg::server::start_with_route(ctx, "/", get(action).post(action), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let res = client
+        .get(get_base_url_port(port))
+        .send()
+        .await
+        .expect("response");
+    let cookie = res
+        .headers()
+        .get("set-cookie")
+        .expect("session cookie")
+        .to_str()
+        .expect("value")
+        .split(';')
+        .next()
+        .expect("cookie pair")
+        .to_string();
+    let token = res.text().await.expect("body");
+    assert!(!token.is_empty());
+
+    let res = client
+        .post(get_base_url_port(port))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .expect("response");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let res = client
+        .post(get_base_url_port(port))
+        .header("cookie", &cookie)
+        .header("x-csrf-token", &token)
+        .send()
+        .await
+        .expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .post(get_base_url_port(port))
+        .header("cookie", &cookie)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(format!("name=loco&csrf_token={token}"))
+        .send()
+        .await
+        .expect("response");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    handle.abort();
+}
+
+#[cfg(feature = "cache_inmem")]
+#[tokio::test]
+async fn rate_limit() {
+    async fn action() -> Result<Response> {
+        format::render().text("loco")
+    }
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+
+    ctx.config.server.middlewares.rate_limit = Some(middleware::rate_limit::RateLimit {
+        enable: true,
+        rules: vec![middleware::rate_limit::Rule {
+            prefix: "/".to_string(),
+            requests: 2,
+            window: 3600,
+            key: middleware::rate_limit::KeyKind::Header("x-api-key".to_string()),
+            ip_limit: None,
+        }],
+    });
+
+    let port = get_available_port().await;
+    let handle = infra_cfg::server::start_with_route(ctx, "/", get(action), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let request = |key: &'static str| {
+        client
+            .get(get_base_url_port(port))
+            .header("x-api-key", key)
+            .send()
+    };
+
+    for _ in 0..2 {
+        assert_eq!(
+            request("a").await.expect("response").status(),
+            StatusCode::OK
+        );
+    }
+    let res = request("a").await.expect("response");
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = res
+        .headers()
+        .get("retry-after")
+        .expect("retry-after header")
+        .to_str()
+        .expect("value")
+        .parse()
+        .expect("seconds");
+    assert!(retry_after > 0 && retry_after <= 3600);
+
+    // other clients are limited apart
+    assert_eq!(
+        request("b").await.expect("response").status(),
+        StatusCode::OK
+    );
+
+    handle.abort();
+}
+
+#[cfg(feature = "cache_inmem")]
+#[tokio::test]
+async fn rate_limit_ip_limit() {
+    async fn action() -> Result<Response> {
+        format::render().text("loco")
+    }
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+
+    ctx.config.server.middlewares.rate_limit = Some(middleware::rate_limit::RateLimit {
+        enable: true,
+        rules: vec![middleware::rate_limit::Rule {
+            prefix: "/".to_string(),
+            requests: 10,
+            window: 3600,
+            key: middleware::rate_limit::KeyKind::Header("x-api-key".to_string()),
+            ip_limit: Some(2),
+        }],
+    });
+
+    let port = get_available_port().await;
+    let handle = infra_cfg::server::start_with_route(ctx, "/", get(action), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let request = |key: &'static str| {
+        client
+            .get(get_base_url_port(port))
+            .header("x-api-key", key)
+            .send()
+    };
+
+    for key in ["a", "b"] {
+        assert_eq!(
+            request(key).await.expect("response").status(),
+            StatusCode::OK
+        );
+    }
+    // a new key does not escape the limit of the client address
+    assert_eq!(
+        request("c").await.expect("response").status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    handle.abort();
+}
@@ -0,0 +1 @@
+<p>Willkommen {{ name }}</p>
@@ -0,0 +1 @@
+Willkommen {{ name }}
\ No newline at end of file
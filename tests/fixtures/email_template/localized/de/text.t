@@ -0,0 +1 @@
+Willkommen {{ name }}
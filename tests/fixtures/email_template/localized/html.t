@@ -0,0 +1 @@
+<p>Welcome {{ name }}</p>
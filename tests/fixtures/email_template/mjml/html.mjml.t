@@ -0,0 +1,9 @@
+<mjml>
+  <mj-body>
+    <mj-section>
+      <mj-column>
+        <mj-text>Welcome {{ name }}</mj-text>
+      </mj-column>
+    </mj-section>
+  </mj-body>
+</mjml>
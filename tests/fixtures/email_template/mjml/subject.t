@@ -0,0 +1 @@
+Welcome {{ name }}
\ No newline at end of file